@@ -0,0 +1,4 @@
+-- Record when a per-subscriber send failed, for the failed-sends detail view
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS failed_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS idx_newsletter_sends_newsletter_status
+    ON newsletter_sends(newsletter_id, status);
@@ -0,0 +1,3 @@
+-- Store the SMTP reply code of failed sends, and count consecutive soft bounces per subscriber
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS smtp_code SMALLINT;
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS soft_bounce_count INTEGER NOT NULL DEFAULT 0;
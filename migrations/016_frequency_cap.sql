@@ -0,0 +1,7 @@
+-- Frequency capping: sends skipped because the subscriber hit the weekly cap are recorded as 'deferred'
+ALTER TABLE newsletter_sends DROP CONSTRAINT IF EXISTS newsletter_sends_status_check;
+ALTER TABLE newsletter_sends ADD CONSTRAINT newsletter_sends_status_check
+    CHECK (status IN ('pending', 'sent', 'failed', 'deferred'));
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS deferred_count INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS idx_newsletter_sends_subscriber_sent_at
+    ON newsletter_sends(subscriber_id, sent_at) WHERE status = 'sent';
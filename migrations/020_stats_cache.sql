@@ -0,0 +1,24 @@
+-- Cached dashboard/stats aggregates, refreshed periodically by the stats cache loop.
+-- Each view carries refreshed_at so the admin UI can show how stale the numbers are.
+CREATE MATERIALIZED VIEW IF NOT EXISTS stats_subscriber_counts AS
+SELECT
+    1 AS id,
+    COUNT(*) AS total,
+    COUNT(*) FILTER (WHERE status = true) AS active,
+    COUNT(*) FILTER (WHERE verified_email = true) AS verified,
+    NOW() AS refreshed_at
+FROM subscribers;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_stats_subscriber_counts_id ON stats_subscriber_counts(id);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS stats_topic_events AS
+SELECT
+    topic,
+    event_type,
+    COUNT(*) AS event_count,
+    COUNT(DISTINCT ucode) AS unique_count,
+    NOW() AS refreshed_at
+FROM email_events
+GROUP BY topic, event_type;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_stats_topic_events_topic_type ON stats_topic_events(topic, event_type);
@@ -0,0 +1,28 @@
+-- Flag clicks made by corporate link scanners (SafeLinks, Proofpoint, ...) so stats can exclude them.
+-- scanner_reason: user_agent, head, head_then_get, timing
+ALTER TABLE email_events ADD COLUMN IF NOT EXISTS is_scanner BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE email_events ADD COLUMN IF NOT EXISTS scanner_reason VARCHAR(20);
+
+CREATE INDEX IF NOT EXISTS idx_email_events_ucode_topic ON email_events(ucode, topic, created_at);
+
+-- Rebuild the cached topic counts to exclude scanner events (once)
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_attribute
+        WHERE attrelid = 'stats_topic_events'::regclass AND attname = 'scanner_count'
+    ) THEN
+        DROP MATERIALIZED VIEW stats_topic_events;
+        CREATE MATERIALIZED VIEW stats_topic_events AS
+        SELECT
+            topic,
+            event_type,
+            COUNT(*) FILTER (WHERE NOT is_scanner) AS event_count,
+            COUNT(DISTINCT ucode) FILTER (WHERE NOT is_scanner) AS unique_count,
+            COUNT(*) FILTER (WHERE is_scanner) AS scanner_count,
+            NOW() AS refreshed_at
+        FROM email_events
+        GROUP BY topic, event_type;
+        CREATE UNIQUE INDEX idx_stats_topic_events_topic_type ON stats_topic_events(topic, event_type);
+    END IF;
+END $$;
@@ -0,0 +1,3 @@
+-- Per-newsletter short-link domain (NULL = default YOURLS instance) and custom slugs (URL → slug)
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS short_domain VARCHAR(255);
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS custom_slugs JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,2 @@
+-- Admin-defined extra email headers per newsletter, as [[name, value], ...]
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS extra_headers JSONB NOT NULL DEFAULT '[]'::jsonb;
@@ -0,0 +1,3 @@
+-- "Remember this device" choice, carried from the magic link to the session
+ALTER TABLE verification_tokens ADD COLUMN IF NOT EXISTS remember BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS remember BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,16 @@
+-- Device an admin session was created from, and a token for revoking it from the
+-- new sign-in email
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS ip_address INET;
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS user_agent TEXT;
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS fingerprint VARCHAR(64);
+ALTER TABLE admin_sessions ADD COLUMN IF NOT EXISTS revoke_token VARCHAR(64);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_admin_sessions_revoke_token ON admin_sessions(revoke_token);
+
+-- Devices (IP + User-Agent fingerprints) each admin has signed in from
+CREATE TABLE IF NOT EXISTS admin_known_devices (
+    admin_email VARCHAR(255) NOT NULL,
+    fingerprint VARCHAR(64) NOT NULL,
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (admin_email, fingerprint)
+);
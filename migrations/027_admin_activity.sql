@@ -0,0 +1,10 @@
+-- Last sign-in per admin (to spot dormant accounts) and soft disabling
+ALTER TABLE admins ADD COLUMN IF NOT EXISTS last_login_at TIMESTAMPTZ;
+ALTER TABLE admins ADD COLUMN IF NOT EXISTS disabled_at TIMESTAMPTZ;
+ALTER TABLE admins ADD COLUMN IF NOT EXISTS disabled_by VARCHAR(255);
+
+-- Backfill from the audit log
+UPDATE admins a SET last_login_at = l.last_login
+FROM (SELECT admin_email, MAX(created_at) AS last_login FROM audit_log
+      WHERE action = 'admin.login' GROUP BY admin_email) l
+WHERE l.admin_email = a.email AND a.last_login_at IS NULL;
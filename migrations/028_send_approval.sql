@@ -0,0 +1,10 @@
+-- Two-person approval for large sends
+ALTER TABLE newsletters DROP CONSTRAINT IF EXISTS newsletters_status_check;
+ALTER TABLE newsletters ADD CONSTRAINT newsletters_status_check
+    CHECK (status IN ('draft', 'pending_approval', 'scheduled', 'sending', 'paused', 'sent', 'failed'));
+
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS approval_requested_by VARCHAR(255);
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS approval_requested_at TIMESTAMPTZ;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS approval_recipients BIGINT;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS approved_by VARCHAR(255);
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS approved_at TIMESTAMPTZ;
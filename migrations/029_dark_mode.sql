@@ -0,0 +1,2 @@
+-- Opt-in dark-mode color overrides injected into each newsletter's emails
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS dark_mode BOOLEAN NOT NULL DEFAULT false;
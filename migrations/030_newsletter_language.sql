@@ -0,0 +1,5 @@
+-- Content language (BCP 47 tag) and text direction, set on the email's <html> root
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS lang VARCHAR(35) NOT NULL DEFAULT 'zh-TW';
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS dir VARCHAR(3) NOT NULL DEFAULT 'ltr';
+ALTER TABLE newsletters DROP CONSTRAINT IF EXISTS newsletters_dir_check;
+ALTER TABLE newsletters ADD CONSTRAINT newsletters_dir_check CHECK (dir IN ('ltr', 'rtl'));
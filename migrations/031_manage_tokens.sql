@@ -0,0 +1,4 @@
+-- Secret code rotation: the previous secret still verifies tracking links in
+-- emails sent before the rotation, but no longer opens the manage page
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS previous_secret_code VARCHAR(64);
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS secret_rotated_at TIMESTAMPTZ;
@@ -0,0 +1,10 @@
+-- Language editions: a newsletter row with parent_id set is an alternate-language
+-- version of its parent, sent as part of the parent's send
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS parent_id UUID REFERENCES newsletters(id) ON DELETE CASCADE;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_newsletters_parent_lang ON newsletters(parent_id, lang) WHERE parent_id IS NOT NULL;
+
+-- Which edition each subscriber was sent (NULL for sends before editions existed)
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS edition_id UUID REFERENCES newsletters(id) ON DELETE SET NULL;
+
+-- Preferred language (BCP 47 tag); NULL receives the primary edition
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS locale VARCHAR(35);
@@ -0,0 +1,8 @@
+-- Subscriber's IANA timezone (e.g. Europe/Berlin); NULL is treated as Asia/Taipei
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS timezone VARCHAR(64);
+
+-- "Deliver at local time": scheduled_at's Taipei wall-clock time is used as the
+-- target time in each subscriber's timezone, and the scheduler releases batches
+-- as timezones reach it. local_release_at is when the next batch is due.
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS local_delivery BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS local_release_at TIMESTAMPTZ;
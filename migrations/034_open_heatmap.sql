@@ -0,0 +1,13 @@
+-- Cached open counts by weekday (ISO, 1 = Monday) and hour, in Taipei time, for
+-- the send-time heatmap on the stats page. Scanner-flagged events are left out.
+CREATE MATERIALIZED VIEW IF NOT EXISTS stats_open_heatmap AS
+SELECT
+    EXTRACT(ISODOW FROM created_at AT TIME ZONE 'Asia/Taipei')::INT AS weekday,
+    EXTRACT(HOUR FROM created_at AT TIME ZONE 'Asia/Taipei')::INT AS hour,
+    COUNT(*) AS opens,
+    NOW() AS refreshed_at
+FROM email_events
+WHERE event_type = 'open' AND NOT is_scanner
+GROUP BY 1, 2;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_stats_open_heatmap_cell ON stats_open_heatmap(weekday, hour);
@@ -0,0 +1,11 @@
+-- Admin notes on a subscriber. Each save adds a row, so the latest row is the
+-- current note and earlier rows are its history.
+CREATE TABLE IF NOT EXISTS subscriber_notes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscriber_id UUID NOT NULL REFERENCES subscribers(id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    author_email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscriber_notes_subscriber ON subscriber_notes(subscriber_id, created_at DESC);
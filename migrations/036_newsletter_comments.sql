@@ -0,0 +1,13 @@
+-- Review comments on newsletters, shown next to the draft in the editor.
+-- Open (unresolved) comments block approving a send.
+CREATE TABLE IF NOT EXISTS newsletter_comments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    newsletter_id UUID NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    author_email VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    resolved_by VARCHAR(255),
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_newsletter_comments_newsletter ON newsletter_comments(newsletter_id, created_at);
@@ -0,0 +1,25 @@
+-- Link tracking events to their newsletter by id instead of joining on topic = slug.
+ALTER TABLE email_events ADD COLUMN IF NOT EXISTS newsletter_id UUID REFERENCES newsletters(id) ON DELETE SET NULL;
+
+-- Backfill events recorded before the column existed
+UPDATE email_events e SET newsletter_id = n.id
+FROM newsletters n
+WHERE e.newsletter_id IS NULL AND n.slug = e.topic;
+
+CREATE INDEX IF NOT EXISTS idx_email_events_newsletter ON email_events(newsletter_id, event_type);
+CREATE INDEX IF NOT EXISTS idx_email_events_ucode_newsletter ON email_events(ucode, newsletter_id, created_at);
+
+-- Cached per-newsletter counts for the stats page
+CREATE MATERIALIZED VIEW IF NOT EXISTS stats_newsletter_events AS
+SELECT
+    newsletter_id,
+    event_type,
+    COUNT(*) FILTER (WHERE NOT is_scanner) AS event_count,
+    COUNT(DISTINCT ucode) FILTER (WHERE NOT is_scanner) AS unique_count,
+    COUNT(*) FILTER (WHERE is_scanner) AS scanner_count,
+    NOW() AS refreshed_at
+FROM email_events
+WHERE newsletter_id IS NOT NULL
+GROUP BY newsletter_id, event_type;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_stats_newsletter_events_newsletter_type ON stats_newsletter_events(newsletter_id, event_type);
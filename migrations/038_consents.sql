@@ -0,0 +1,14 @@
+-- Consent given at subscribe and verify time, kept to show the lawful basis
+-- for each address. consent_version identifies the wording shown on the form.
+CREATE TABLE IF NOT EXISTS consents (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscriber_id UUID NOT NULL REFERENCES subscribers(id) ON DELETE CASCADE,
+    action VARCHAR(20) NOT NULL,
+    consent_version VARCHAR(50) NOT NULL,
+    source VARCHAR(50) NOT NULL,
+    ip_address INET,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_consents_subscriber ON consents(subscriber_id, created_at);
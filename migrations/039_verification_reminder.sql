@@ -0,0 +1,2 @@
+-- When the one verification reminder went out to an unverified sign-up
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS verification_reminder_sent_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+-- Sending identity (a SENDING_IDENTITIES name) a newsletter goes out through;
+-- NULL uses the default SMTP settings.
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS sending_identity VARCHAR(50);
@@ -0,0 +1,18 @@
+-- Replies subscribers send back to a newsletter, posted by the inbound mail
+-- webhook and threaded to the newsletter and subscriber where possible.
+CREATE TABLE IF NOT EXISTS newsletter_replies (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    newsletter_id UUID REFERENCES newsletters(id) ON DELETE SET NULL,
+    subscriber_id UUID REFERENCES subscribers(id) ON DELETE CASCADE,
+    from_email VARCHAR(255) NOT NULL,
+    subject TEXT NOT NULL DEFAULT '',
+    body_text TEXT NOT NULL DEFAULT '',
+    message_id VARCHAR(998),
+    handled_by VARCHAR(255),
+    handled_at TIMESTAMPTZ,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_newsletter_replies_message_id ON newsletter_replies(message_id);
+CREATE INDEX IF NOT EXISTS idx_newsletter_replies_received ON newsletter_replies(received_at DESC);
+CREATE INDEX IF NOT EXISTS idx_newsletter_replies_newsletter ON newsletter_replies(newsletter_id);
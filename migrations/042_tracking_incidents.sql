@@ -0,0 +1,12 @@
+-- Sources blocked on the click tracking endpoint: a ucode clicking far more
+-- than a person would, or an IP guessing hashes.
+CREATE TABLE IF NOT EXISTS tracking_incidents (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    hits BIGINT NOT NULL,
+    blocked_until TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tracking_incidents_created ON tracking_incidents(created_at);
@@ -0,0 +1,7 @@
+-- Trigram indexes so the admin subscriber search (`ILIKE '%term%'`) no longer
+-- scans the whole table.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_subscribers_email_trgm ON subscribers USING gin (email gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_subscribers_name_trgm ON subscribers USING gin (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_subscribers_created ON subscribers(created_at DESC);
@@ -0,0 +1,14 @@
+-- Runs of background tasks (sends, imports, syncs, cleanup) for the admin
+-- jobs page. Rows are pruned with the other logs.
+CREATE TABLE IF NOT EXISTS background_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    subject TEXT,
+    state VARCHAR(16) NOT NULL DEFAULT 'running',
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_background_jobs_started ON background_jobs(started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_background_jobs_kind ON background_jobs(kind, started_at DESC);
@@ -0,0 +1,10 @@
+-- Per-admin profile, keyed by email so admins listed only in ADMIN_EMAILS
+-- (without an `admins` row) can have one too.
+CREATE TABLE IF NOT EXISTS admin_profiles (
+    email VARCHAR(255) PRIMARY KEY,
+    display_name VARCHAR(100),
+    timezone VARCHAR(64),
+    notify_new_signin BOOLEAN NOT NULL DEFAULT TRUE,
+    notify_comments BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+-- Where web signups came from: utm_* parameters of the link to the subscribe
+-- page and the referring site's host.
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS utm_source VARCHAR(100);
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS utm_medium VARCHAR(100);
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS utm_campaign VARCHAR(100);
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS utm_term VARCHAR(100);
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS utm_content VARCHAR(100);
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS referrer VARCHAR(255);
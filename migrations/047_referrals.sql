@@ -0,0 +1,3 @@
+-- Subscriber whose referral link a signup came through.
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS referred_by UUID REFERENCES subscribers(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_subscribers_referred_by ON subscribers(referred_by) WHERE referred_by IS NOT NULL;
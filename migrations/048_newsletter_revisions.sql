@@ -0,0 +1,12 @@
+-- Previous title and content of a scheduled newsletter, saved on each edit
+CREATE TABLE IF NOT EXISTS newsletter_revisions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    newsletter_id UUID NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    title VARCHAR(500) NOT NULL,
+    markdown_content TEXT NOT NULL,
+    edited_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_newsletter_revisions_newsletter
+    ON newsletter_revisions (newsletter_id, created_at DESC);
@@ -0,0 +1,3 @@
+-- Click counts reported by YOURLS for each short link, synced in the background
+ALTER TABLE newsletter_links ADD COLUMN IF NOT EXISTS short_clicks BIGINT;
+ALTER TABLE newsletter_links ADD COLUMN IF NOT EXISTS short_clicks_synced_at TIMESTAMPTZ;
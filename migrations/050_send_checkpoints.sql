@@ -0,0 +1,7 @@
+-- Why and by whom a send was paused, and how far it got: the send loop
+-- records a checkpoint after each batch of recipients.
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS pause_reason TEXT;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS paused_by TEXT;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS paused_at TIMESTAMPTZ;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS checkpoint_batches INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS checkpoint_at TIMESTAMPTZ;
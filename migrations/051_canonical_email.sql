@@ -0,0 +1,13 @@
+-- Canonical form of each subscriber's address (Gmail dots and +tags removed),
+-- used to spot the same mailbox signing up again under another spelling.
+ALTER TABLE subscribers ADD COLUMN IF NOT EXISTS canonical_email TEXT;
+
+UPDATE subscribers SET canonical_email = CASE
+    WHEN split_part(lower(email), '@', 2) IN ('gmail.com', 'googlemail.com')
+         AND split_part(split_part(lower(email), '@', 1), '+', 1) <> ''
+    THEN replace(split_part(split_part(lower(email), '@', 1), '+', 1), '.', '') || '@gmail.com'
+    ELSE lower(email)
+END
+WHERE canonical_email IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_subscribers_canonical_email ON subscribers(canonical_email);
@@ -0,0 +1,10 @@
+-- Subscriber totals at the end of each day (Taiwan time), for the dashboard's
+-- growth chart. Past counts can't be rebuilt from the subscribers table once
+-- people unsubscribe or are deleted.
+CREATE TABLE IF NOT EXISTS subscriber_snapshots (
+    snapshot_date DATE PRIMARY KEY,
+    total BIGINT NOT NULL,
+    active BIGINT NOT NULL,
+    verified BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,9 @@
+-- Delivery ids of signed webhooks seen recently, to reject replays
+CREATE TABLE IF NOT EXISTS webhook_nonces (
+    source TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (source, nonce)
+);
+CREATE INDEX IF NOT EXISTS idx_webhook_nonces_expires_at ON webhook_nonces(expires_at);
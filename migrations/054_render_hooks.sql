@@ -0,0 +1,3 @@
+-- Post-render transforms of each newsletter, in order, as a JSON array of
+-- hook names; NULL means the default pipeline.
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS render_hooks JSONB;
@@ -0,0 +1,14 @@
+-- Newsletters can belong to a list topic (a series such as 議程公告 or
+-- 志工招募) that subscribers can stop on its own, short of unsubscribing
+-- from everything.
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS list_topic TEXT;
+
+CREATE TABLE IF NOT EXISTS subscriber_topic_optouts (
+    subscriber_id UUID NOT NULL REFERENCES subscribers(id) ON DELETE CASCADE,
+    topic TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (subscriber_id, topic)
+);
+
+-- The topic stopped; NULL for unsubscribing from everything
+ALTER TABLE unsubscribe_events ADD COLUMN IF NOT EXISTS topic TEXT;
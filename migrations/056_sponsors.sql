@@ -0,0 +1,13 @@
+-- Sponsors and partners shown in newsletters through the %sponsors%
+-- shortcode while their active period (inclusive dates, Taiwan time) lasts.
+CREATE TABLE IF NOT EXISTS sponsors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    logo_url TEXT NOT NULL,
+    url TEXT NOT NULL,
+    tier VARCHAR(20) NOT NULL,
+    starts_on DATE,
+    ends_on DATE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
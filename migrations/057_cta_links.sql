@@ -0,0 +1,3 @@
+-- Links editors marked as a newsletter's primary calls to action, with an
+-- optional goal of unique clicks: [{"url": "...", "goal": 500}]. NULL for none.
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS cta_links JSONB;
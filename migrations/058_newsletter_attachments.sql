@@ -0,0 +1,15 @@
+-- PDF files attached to every email of a newsletter (all language editions),
+-- and offered as downloads on its web version. The file itself lives under
+-- ATTACHMENT_DIR as stored_name.
+CREATE TABLE IF NOT EXISTS newsletter_attachments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    newsletter_id UUID NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    filename TEXT NOT NULL,
+    stored_name TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    download_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_newsletter_attachments_newsletter
+    ON newsletter_attachments (newsletter_id);
@@ -0,0 +1,24 @@
+-- Sends waiting for or claimed by a worker (`newsletter::send_worker`), so a
+-- send survives a restart and any replica can pick it up. A running job holds
+-- a lease its worker keeps renewing; once the lease runs out the job is put
+-- back in the queue and resumes from the send's last checkpoint.
+CREATE TABLE IF NOT EXISTS send_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    newsletter_id UUID NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    state TEXT NOT NULL DEFAULT 'pending'
+        CHECK (state IN ('pending', 'running', 'done', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    worker TEXT,
+    locked_until TIMESTAMPTZ,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+
+-- One waiting and one running job per newsletter at most: two send loops at
+-- once would mail the same recipients twice
+CREATE UNIQUE INDEX IF NOT EXISTS idx_send_jobs_one_pending
+    ON send_jobs (newsletter_id) WHERE state = 'pending';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_send_jobs_one_running
+    ON send_jobs (newsletter_id) WHERE state = 'running';
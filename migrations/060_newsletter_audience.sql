@@ -0,0 +1,16 @@
+-- Who a newsletter was addressed to, recorded as each send starts: the
+-- subscriber id and a SHA-256 of the address, so "was X included?" can be
+-- answered after the list changes or the subscriber is deleted, without
+-- keeping the address itself. Local-time sends add each release's recipients.
+CREATE TABLE IF NOT EXISTS newsletter_audience (
+    newsletter_id UUID NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    subscriber_id UUID NOT NULL,
+    email_sha256 TEXT NOT NULL,
+    -- Held back by the frequency cap rather than sent to
+    deferred BOOLEAN NOT NULL DEFAULT FALSE,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (newsletter_id, subscriber_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_newsletter_audience_email
+    ON newsletter_audience (newsletter_id, email_sha256);
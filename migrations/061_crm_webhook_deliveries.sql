@@ -0,0 +1,20 @@
+-- Subscriber lifecycle events waiting to be POSTed to the CRM webhook
+-- (`crm_webhook::delivery_loop`). Written in the same request as the status
+-- change, so an event is retried until the CRM accepts it even across
+-- restarts. The payload is built when the event happens, not when it is sent.
+CREATE TABLE IF NOT EXISTS crm_webhook_deliveries (
+    id UUID PRIMARY KEY,
+    subscriber_id UUID,
+    event TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    failed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_crm_webhook_deliveries_due
+    ON crm_webhook_deliveries (next_attempt_at)
+    WHERE delivered_at IS NULL AND failed_at IS NULL;
@@ -0,0 +1,17 @@
+-- Rules that keep a tag in sync with subscriber engagement, e.g. "opened the
+-- last 3 newsletters" (see `tags::TagRule`). Tags a rule added carry its id,
+-- so re-applying the rule removes them from those who no longer match while
+-- tags added by hand, by import or by the registration sync stay.
+CREATE TABLE IF NOT EXISTS tag_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    rule JSONB NOT NULL,
+    created_by VARCHAR(255),
+    last_applied_at TIMESTAMPTZ,
+    last_matched INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE subscriber_tags ADD COLUMN IF NOT EXISTS rule_id UUID REFERENCES tag_rules(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_subscriber_tags_rule_id ON subscriber_tags(rule_id) WHERE rule_id IS NOT NULL;
@@ -0,0 +1,6 @@
+-- Scheduled sends that came due while the server was down, held for an admin
+ALTER TABLE newsletters DROP CONSTRAINT IF EXISTS newsletters_status_check;
+ALTER TABLE newsletters ADD CONSTRAINT newsletters_status_check
+    CHECK (status IN ('draft', 'pending_approval', 'scheduled', 'missed', 'sending', 'paused', 'sent', 'failed'));
+
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS missed_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Send to subscribers with a tag, alone or within the target segment
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS tag_id UUID REFERENCES tags(id) ON DELETE SET NULL;
@@ -0,0 +1,13 @@
+-- Named sample data for previewing a template (long titles, no images, other languages)
+CREATE TABLE IF NOT EXISTS template_preview_profiles (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    template_id UUID NOT NULL REFERENCES newsletter_templates(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    title TEXT NOT NULL,
+    markdown_content TEXT NOT NULL DEFAULT '',
+    recipient_name VARCHAR(255) NOT NULL DEFAULT '',
+    lang VARCHAR(35) NOT NULL DEFAULT 'zh-TW',
+    created_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (template_id, name)
+);
@@ -0,0 +1,9 @@
+-- Admin-managed destination domains for links in newsletters: 'block' refuses
+-- them at send and click time, 'allow' overrides blocks and shortener warnings
+CREATE TABLE IF NOT EXISTS link_domains (
+    domain VARCHAR(253) PRIMARY KEY,
+    action VARCHAR(10) NOT NULL CHECK (action IN ('block', 'allow')),
+    note TEXT NOT NULL DEFAULT '',
+    added_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
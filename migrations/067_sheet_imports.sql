@@ -0,0 +1,17 @@
+-- Google Sheets synced into subscribers on a schedule, with the outcome of
+-- the last sync
+CREATE TABLE IF NOT EXISTS sheet_imports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    url TEXT NOT NULL,
+    csv_url TEXT NOT NULL UNIQUE,
+    tag VARCHAR(100) NOT NULL,
+    allow_role_accounts BOOLEAN NOT NULL DEFAULT FALSE,
+    created_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_synced_at TIMESTAMPTZ,
+    last_fetched INTEGER,
+    last_created INTEGER,
+    last_tagged INTEGER,
+    last_invalid INTEGER,
+    last_error TEXT
+);
@@ -0,0 +1,13 @@
+-- Idempotency-Key values of API calls that start sends, with the response
+-- to replay when the same call is retried
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key TEXT PRIMARY KEY,
+    -- Hash of the method, path and body the key was first used with
+    fingerprint TEXT NOT NULL,
+    -- NULL while the first call is still running
+    status_code INTEGER,
+    response JSONB,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires_at ON idempotency_keys(expires_at);
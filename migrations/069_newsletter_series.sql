@@ -0,0 +1,15 @@
+-- Series group newsletters (2025 CFP, 每月摘要, ...) in the admin list and
+-- the archive; new issues of a series start from its default template
+CREATE TABLE IF NOT EXISTS newsletter_series (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL DEFAULT '',
+    default_template_id UUID REFERENCES newsletter_templates(id) ON DELETE SET NULL,
+    created_by TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE newsletters ADD COLUMN IF NOT EXISTS series_id UUID
+    REFERENCES newsletter_series(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_newsletters_series_id ON newsletters(series_id);
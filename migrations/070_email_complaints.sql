@@ -0,0 +1,18 @@
+-- Bounces and complaints the mail provider reports after accepting a message
+-- (POST /webhooks/email-events), noted on the send they belong to
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS bounced_at TIMESTAMPTZ;
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS bounce_type VARCHAR(10)
+    CHECK (bounce_type IN ('hard', 'soft'));
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS bounce_detail TEXT;
+ALTER TABLE newsletter_sends ADD COLUMN IF NOT EXISTS complained_at TIMESTAMPTZ;
+
+-- Spam complaints (feedback loop reports); the subscriber is also unsubscribed
+CREATE TABLE IF NOT EXISTS email_complaints (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscriber_id UUID NOT NULL REFERENCES subscribers(id) ON DELETE CASCADE,
+    newsletter_id UUID REFERENCES newsletters(id) ON DELETE SET NULL,
+    provider TEXT NOT NULL,
+    feedback_type TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_email_complaints_subscriber_id ON email_complaints(subscriber_id);
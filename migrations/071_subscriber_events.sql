@@ -0,0 +1,14 @@
+-- Security-relevant things subscribers do through their links (verifying,
+-- opening the manage page from a new IP, unsubscribing), shown on the admin
+-- subscriber page. Kept apart from audit_log, which records admin actions.
+CREATE TABLE IF NOT EXISTS subscriber_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscriber_id UUID NOT NULL REFERENCES subscribers(id) ON DELETE CASCADE,
+    event VARCHAR(50) NOT NULL,
+    details JSONB,
+    ip_address INET,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_subscriber_events_subscriber_created
+    ON subscriber_events(subscriber_id, created_at DESC);
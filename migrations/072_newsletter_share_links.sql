@@ -0,0 +1,17 @@
+-- Signed, expiring links to a newsletter that is not public yet, e.g. for a
+-- sponsor to check their issue before the blast. The token is signed with
+-- the link's own secret, so a link can be revoked without touching others.
+CREATE TABLE IF NOT EXISTS newsletter_share_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    newsletter_id UUID NOT NULL REFERENCES newsletters(id) ON DELETE CASCADE,
+    label TEXT NOT NULL DEFAULT '',
+    secret VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_by TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ,
+    view_count INTEGER NOT NULL DEFAULT 0,
+    last_viewed_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_newsletter_share_links_newsletter
+    ON newsletter_share_links(newsletter_id);
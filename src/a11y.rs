@@ -0,0 +1,209 @@
+use regex::Regex;
+use serde::Serialize;
+
+/// WCAG AA minimum contrast ratio for body text.
+const MIN_CONTRAST: f64 = 4.5;
+
+/// An accessibility problem found in newsletter HTML.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Issue {
+    /// `img_alt`, `contrast` or `heading_jump`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Check rendered newsletter content for images without alt text,
+/// low-contrast inline colors and skipped heading levels.
+pub fn lint(html: &str) -> Vec<Issue> {
+    let mut issues = image_alt_issues(html);
+    issues.extend(contrast_issues(html));
+    issues.extend(heading_issues(html));
+    issues
+}
+
+fn image_alt_issues(html: &str) -> Vec<Issue> {
+    let img_re = Regex::new(r"(?is)<img\b[^>]*>").expect("valid regex");
+    let alt_re = Regex::new(r#"(?is)\balt\s*=\s*("([^"]*)"|'([^']*)')"#).expect("valid regex");
+
+    img_re
+        .find_iter(html)
+        .filter_map(|m| {
+            let tag = m.as_str();
+            let alt = alt_re
+                .captures(tag)
+                .and_then(|c| c.get(2).or_else(|| c.get(3)))
+                .map(|a| a.as_str().trim());
+            if alt.is_some_and(|a| !a.is_empty()) {
+                return None;
+            }
+            // Any quoting, as the parser reads it
+            let src = crate::html_rewrite::image_urls(tag)
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            Some(Issue {
+                rule: "img_alt",
+                message: format!("圖片缺少替代文字（alt）：{src}"),
+            })
+        })
+        .collect()
+}
+
+fn contrast_issues(html: &str) -> Vec<Issue> {
+    let style_re = Regex::new(r#"(?is)\bstyle\s*=\s*"([^"]*)""#).expect("valid regex");
+
+    style_re
+        .captures_iter(html)
+        .filter_map(|caps| {
+            let style = caps.get(1)?.as_str();
+            let fg = style_property(style, "color")?;
+            let bg = style_property(style, "background-color")
+                .or_else(|| style_property(style, "background"))
+                .unwrap_or("#ffffff");
+            let ratio = contrast_ratio(parse_color(fg)?, parse_color(bg)?);
+            (ratio < MIN_CONTRAST).then(|| Issue {
+                rule: "contrast",
+                message: format!(
+                    "文字顏色 {fg} 與背景 {bg} 對比度 {ratio:.1}:1，低於 {MIN_CONTRAST}:1"
+                ),
+            })
+        })
+        .collect()
+}
+
+fn heading_issues(html: &str) -> Vec<Issue> {
+    let re = Regex::new(r"(?i)<h([1-6])\b").expect("valid regex");
+
+    let mut previous: Option<u8> = None;
+    let mut issues = Vec::new();
+    for caps in re.captures_iter(html) {
+        let level = caps[1].parse::<u8>().unwrap_or(1);
+        if let Some(prev) = previous {
+            if level > prev + 1 {
+                issues.push(Issue {
+                    rule: "heading_jump",
+                    message: format!("標題層級從 h{prev} 跳到 h{level}，中間缺少 h{}", prev + 1),
+                });
+            }
+        }
+        previous = Some(level);
+    }
+    issues
+}
+
+/// Value of a CSS property in an inline style, e.g. `color` in
+/// `font-weight: bold; color: #999`.
+fn style_property<'a>(style: &'a str, name: &str) -> Option<&'a str> {
+    style.split(';').rev().find_map(|decl| {
+        let (prop, value) = decl.split_once(':')?;
+        prop.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_end_matches("!important").trim())
+    })
+}
+
+/// Parse `#rgb`, `#rrggbb`, `rgb(r, g, b)` and a few common color names.
+/// Anything else (gradients, `transparent`, ...) is skipped.
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let hex: String = match hex.len() {
+            3 => hex.chars().flat_map(|c| [c, c]).collect(),
+            6 => hex.to_string(),
+            _ => return None,
+        };
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some([channel(0)?, channel(2)?, channel(4)?]);
+    }
+    if let Some(args) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let mut channels = args.split(',').map(|c| c.trim().parse::<u8>().ok());
+        let rgb = [channels.next()??, channels.next()??, channels.next()??];
+        return channels.next().is_none().then_some(rgb);
+    }
+    match value.as_str() {
+        "white" => Some([255, 255, 255]),
+        "black" => Some([0, 0, 0]),
+        "gray" | "grey" => Some([128, 128, 128]),
+        "silver" => Some([192, 192, 192]),
+        "lightgray" | "lightgrey" => Some([211, 211, 211]),
+        "yellow" => Some([255, 255, 0]),
+        "red" => Some([255, 0, 0]),
+        _ => None,
+    }
+}
+
+fn relative_luminance([r, g, b]: [u8; 3]) -> f64 {
+    let linear = |c: u8| {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.039_28 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// WCAG contrast ratio, from 1 (same color) to 21 (black on white).
+fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(html: &str) -> Vec<&'static str> {
+        lint(html).into_iter().map(|i| i.rule).collect()
+    }
+
+    #[test]
+    fn test_image_alt() {
+        assert_eq!(
+            rules(r#"<img src="/a.png" alt="COSCUP logo">"#),
+            Vec::<&str>::new()
+        );
+        assert_eq!(rules(r#"<img src="/a.png">"#), vec!["img_alt"]);
+        assert_eq!(rules(r#"<img src="/a.png" alt="">"#), vec!["img_alt"]);
+        assert!(lint(r#"<img src="/a.png">"#)[0].message.contains("/a.png"));
+        assert!(lint(r"<img src='/b.png?x=1&amp;y=2' alt=''>")[0]
+            .message
+            .ends_with("/b.png?x=1&y=2"));
+        assert!(lint("<img src=/c.png>")[0].message.ends_with("/c.png"));
+    }
+
+    #[test]
+    fn test_contrast() {
+        assert!((contrast_ratio([0, 0, 0], [255, 255, 255]) - 21.0).abs() < 0.01);
+        assert_eq!(parse_color("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse_color("rgb(10, 20, 30)"), Some([10, 20, 30]));
+        assert_eq!(parse_color("transparent"), None);
+
+        assert_eq!(rules(r#"<p style="color: #333">x</p>"#), Vec::<&str>::new());
+        assert_eq!(rules(r#"<p style="color: #ccc">x</p>"#), vec!["contrast"]);
+        assert_eq!(
+            rules(r#"<p style="color: #fff; background-color: #000">x</p>"#),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            rules(r#"<p style="color:yellow;background:white">x</p>"#),
+            vec!["contrast"]
+        );
+        // background-color alone is not a text color
+        assert_eq!(
+            rules(r#"<p style="background-color: #eee">x</p>"#),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn test_heading_jump() {
+        assert_eq!(
+            rules("<h2>a</h2><h3>b</h3><h2>c</h2><h3>d</h3>"),
+            Vec::<&str>::new()
+        );
+        assert_eq!(rules("<h2>a</h2><h4>b</h4>"), vec!["heading_jump"]);
+        assert!(lint("<h1>a</h1><h3>b</h3>")[0].message.contains("h2"));
+    }
+}
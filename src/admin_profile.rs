@@ -0,0 +1,104 @@
+use sqlx::PgPool;
+
+/// Longest display name accepted.
+pub const MAX_DISPLAY_NAME_CHARS: usize = 50;
+
+/// Settings an admin manages on `/admin/profile`. Admins without a row get
+/// the defaults.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct AdminProfile {
+    /// Shown instead of the email address in the audit log, newsletter list
+    /// and comment threads
+    pub display_name: Option<String>,
+    /// IANA timezone name; `None` is Taipei
+    pub timezone: Option<String>,
+    /// Email on sign-in from a new device
+    pub notify_new_signin: bool,
+    /// Email when another admin comments on a newsletter this admin created
+    pub notify_comments: bool,
+}
+
+impl Default for AdminProfile {
+    fn default() -> Self {
+        Self {
+            display_name: None,
+            timezone: None,
+            notify_new_signin: true,
+            notify_comments: true,
+        }
+    }
+}
+
+pub async fn load(db: &PgPool, email: &str) -> Result<AdminProfile, sqlx::Error> {
+    let row = sqlx::query_as::<_, (Option<String>, Option<String>, bool, bool)>(
+        "SELECT display_name, timezone, notify_new_signin, notify_comments \
+         FROM admin_profiles WHERE email = $1",
+    )
+    .bind(email)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map_or_else(
+        AdminProfile::default,
+        |(display_name, timezone, notify_new_signin, notify_comments)| AdminProfile {
+            display_name,
+            timezone,
+            notify_new_signin,
+            notify_comments,
+        },
+    ))
+}
+
+pub async fn save(db: &PgPool, email: &str, profile: &AdminProfile) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO admin_profiles (email, display_name, timezone, notify_new_signin, notify_comments) \
+         VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (email) DO UPDATE SET display_name = $2, timezone = $3, \
+         notify_new_signin = $4, notify_comments = $5, updated_at = NOW()",
+    )
+    .bind(email)
+    .bind(&profile.display_name)
+    .bind(&profile.timezone)
+    .bind(profile.notify_new_signin)
+    .bind(profile.notify_comments)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Trimmed display name, `None` when blank.
+pub fn normalize_display_name(name: &str) -> Result<Option<String>, String> {
+    let name = name.trim();
+    if name.chars().count() > MAX_DISPLAY_NAME_CHARS {
+        return Err(format!("顯示名稱不可超過 {MAX_DISPLAY_NAME_CHARS} 字"));
+    }
+    Ok((!name.is_empty()).then(|| name.to_string()))
+}
+
+/// SQL expression for the display name of the admin whose email is in
+/// `column`, falling back to the email itself.
+pub fn display_name_sql(column: &str) -> String {
+    format!("COALESCE((SELECT display_name FROM admin_profiles WHERE email = {column}), {column})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_display_name() {
+        assert_eq!(
+            normalize_display_name("  小明 "),
+            Ok(Some("小明".to_string()))
+        );
+        assert_eq!(normalize_display_name("   "), Ok(None));
+        assert!(normalize_display_name(&"名".repeat(MAX_DISPLAY_NAME_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn test_display_name_sql() {
+        assert_eq!(
+            display_name_sql("c.author_email"),
+            "COALESCE((SELECT display_name FROM admin_profiles WHERE email = c.author_email), c.author_email)"
+        );
+    }
+}
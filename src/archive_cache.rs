@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Cached pages expire after this long even without an invalidation, as a
+/// safety net for changes made by another instance.
+const TTL: Duration = Duration::from_hours(1);
+
+/// Key of the `/newsletters` list page; single newsletters are keyed by slug,
+/// which can't contain a `/`.
+pub const LIST_KEY: &str = "/";
+
+/// Key of the list page filtered to one series.
+pub fn series_key(series_id: uuid::Uuid) -> String {
+    format!("/series/{series_id}")
+}
+
+/// Rendered public archive pages. Sent newsletters don't change, so the
+/// HTML is kept until a send completes or a template is edited. Only pages
+/// that rendered successfully are stored, so unknown slugs can't fill it up.
+#[derive(Clone, Default)]
+pub struct ArchiveCache {
+    pages: Arc<RwLock<HashMap<String, (Instant, String)>>>,
+}
+
+impl ArchiveCache {
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<String> {
+        let pages = self.pages.read().ok()?;
+        let (stored_at, html) = pages.get(key)?;
+        (now.duration_since(*stored_at) < TTL).then(|| html.clone())
+    }
+
+    pub fn insert(&self, key: &str, html: &str) {
+        if let Ok(mut pages) = self.pages.write() {
+            pages.insert(key.to_string(), (Instant::now(), html.to_string()));
+        }
+    }
+
+    /// Drop every page: called when a newsletter finishes sending (new list
+    /// entry, editions published) and when a template changes.
+    pub fn invalidate(&self) {
+        if let Ok(mut pages) = self.pages.write() {
+            pages.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_and_invalidate() {
+        let cache = ArchiveCache::default();
+        assert_eq!(cache.get(LIST_KEY), None);
+
+        cache.insert(LIST_KEY, "<ul></ul>");
+        cache.insert("2025-08", "<p>Aug</p>");
+        assert_eq!(cache.clone().get("2025-08").as_deref(), Some("<p>Aug</p>"));
+
+        let later = Instant::now() + TTL;
+        assert_eq!(cache.get_at("2025-08", later), None);
+
+        cache.invalidate();
+        assert_eq!(cache.get(LIST_KEY), None);
+        assert_eq!(cache.get("2025-08"), None);
+    }
+}
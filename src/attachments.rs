@@ -0,0 +1,174 @@
+//! PDF attachments of a newsletter, e.g. the sponsorship prospectus.
+//!
+//! Files are kept in `ATTACHMENT_DIR` under a random name and attached to
+//! every email of every language edition. The web version links to them
+//! through `/attachments/{id}` instead, which counts downloads; that route
+//! only serves attachments of sent newsletters.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::config::AppConfig;
+
+pub const CONTENT_TYPE: &str = "application/pdf";
+
+/// Longest filename kept, in characters, extension included.
+const MAX_FILENAME_CHARS: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub id: uuid::Uuid,
+    pub newsletter_id: uuid::Uuid,
+    /// As uploaded, shown to recipients
+    pub filename: String,
+    /// Name under `ATTACHMENT_DIR`
+    #[serde(skip)]
+    pub stored_name: String,
+    pub size_bytes: i32,
+    pub download_count: i32,
+}
+
+type AttachmentRow = (uuid::Uuid, uuid::Uuid, String, String, i32, i32);
+
+const COLUMNS: &str = "id, newsletter_id, filename, stored_name, size_bytes, download_count";
+
+fn from_row(
+    (id, newsletter_id, filename, stored_name, size_bytes, download_count): AttachmentRow,
+) -> Attachment {
+    Attachment {
+        id,
+        newsletter_id,
+        filename,
+        stored_name,
+        size_bytes,
+        download_count,
+    }
+}
+
+/// Whether the data is a PDF, by its header rather than the declared type.
+pub fn is_pdf(data: &[u8]) -> bool {
+    data.starts_with(b"%PDF-")
+}
+
+/// The uploaded filename, safe for a MIME header and a download: path and
+/// control characters removed, shortened, and ending in `.pdf`.
+pub fn sanitize_filename(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .collect();
+    let cleaned = cleaned.trim();
+    let stem = cleaned
+        .len()
+        .checked_sub(4)
+        .filter(|&i| {
+            cleaned
+                .get(i..)
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(".pdf"))
+        })
+        .map_or(cleaned, |i| &cleaned[..i]);
+    let stem: String = stem.chars().take(MAX_FILENAME_CHARS - 4).collect();
+    let stem = stem.trim();
+    if stem.is_empty() {
+        "attachment.pdf".to_string()
+    } else {
+        format!("{stem}.pdf")
+    }
+}
+
+/// Refuse an upload that would take the newsletter's attachments over `limit`.
+pub fn check_size(attached_bytes: i64, new_bytes: usize, limit: usize) -> Result<(), String> {
+    let total = attached_bytes.saturating_add(i64::try_from(new_bytes).unwrap_or(i64::MAX));
+    if total > i64::try_from(limit).unwrap_or(i64::MAX) {
+        return Err(format!(
+            "Attachments may total at most {limit} bytes per newsletter \
+             ({attached_bytes} bytes already attached, this file is {new_bytes} bytes)"
+        ));
+    }
+    Ok(())
+}
+
+pub fn path(config: &AppConfig, stored_name: &str) -> PathBuf {
+    std::path::Path::new(&config.attachment_dir).join(stored_name)
+}
+
+pub async fn list(db: &PgPool, newsletter_id: uuid::Uuid) -> Result<Vec<Attachment>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, AttachmentRow>(&format!(
+        "SELECT {COLUMNS} FROM newsletter_attachments WHERE newsletter_id = $1 ORDER BY created_at"
+    ))
+    .bind(newsletter_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+pub async fn load(db: &PgPool, id: uuid::Uuid) -> Result<Option<Attachment>, sqlx::Error> {
+    let row = sqlx::query_as::<_, AttachmentRow>(&format!(
+        "SELECT {COLUMNS} FROM newsletter_attachments WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(from_row))
+}
+
+/// The files to send with a newsletter, read once per send.
+pub async fn load_files(
+    db: &PgPool,
+    config: &AppConfig,
+    newsletter_id: uuid::Uuid,
+) -> Result<Vec<crate::email::Attachment>, String> {
+    let attachments = list(db, newsletter_id).await.map_err(|e| e.to_string())?;
+    let mut files = Vec::with_capacity(attachments.len());
+    for attachment in attachments {
+        let data = tokio::fs::read(path(config, &attachment.stored_name))
+            .await
+            .map_err(|e| format!("Failed to read attachment {}: {e}", attachment.filename))?;
+        files.push(crate::email::Attachment {
+            filename: attachment.filename,
+            content_type: CONTENT_TYPE.to_string(),
+            data,
+        });
+    }
+    Ok(files)
+}
+
+/// The download link shown on the web version.
+pub fn link(base_url: &str, id: uuid::Uuid) -> String {
+    format!("{base_url}/attachments/{id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_pdf() {
+        assert!(is_pdf(b"%PDF-1.7\n..."));
+        assert!(!is_pdf(b"<html>"));
+        assert!(!is_pdf(b""));
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(
+            sanitize_filename("COSCUP 2025 贊助徵求書.PDF"),
+            "COSCUP 2025 贊助徵求書.pdf"
+        );
+        assert_eq!(sanitize_filename("C:\\Users\\me\\deck.pdf"), "deck.pdf");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd.pdf");
+        assert_eq!(sanitize_filename("a\"b\r\n.pdf"), "ab.pdf");
+        assert_eq!(sanitize_filename(" .pdf "), "attachment.pdf");
+        assert_eq!(sanitize_filename(&"長".repeat(200)).chars().count(), 100);
+    }
+
+    #[test]
+    fn test_check_size() {
+        assert!(check_size(0, 1000, 1000).is_ok());
+        assert!(check_size(600, 400, 1000).is_ok());
+        assert!(check_size(600, 401, 1000).is_err());
+    }
+}
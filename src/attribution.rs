@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Longest value kept per field; longer ones are cut.
+const MAX_VALUE_CHARS: usize = 100;
+
+/// Where a web signup came from: the `utm_*` parameters of the link to the
+/// subscribe page and the referring site.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Attribution {
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+    /// Host of the referring page (`?ref=` is accepted too)
+    #[serde(alias = "ref")]
+    pub referrer: Option<String>,
+}
+
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().chars().take(MAX_VALUE_CHARS).collect::<String>())
+        .filter(|v| !v.is_empty())
+}
+
+/// Host of a referrer given as a URL or a bare host.
+fn referrer_host(value: &str) -> Option<String> {
+    let value = value.trim();
+    let url = if value.contains("://") {
+        reqwest::Url::parse(value).ok()?
+    } else {
+        reqwest::Url::parse(&format!("https://{value}")).ok()?
+    };
+    url.host_str()
+        .map(|h| h.trim_start_matches("www.").to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+}
+
+impl Attribution {
+    /// Trim and cut every field, lowercase source and medium so `Blog` and
+    /// `blog` count together, and keep only the referrer's host. Values come
+    /// straight from the query string and form, so this runs on both.
+    #[must_use]
+    pub fn normalized(self) -> Self {
+        let lower = |v: Option<String>| clean(v).map(|v| v.to_lowercase());
+        Self {
+            utm_source: lower(self.utm_source),
+            utm_medium: lower(self.utm_medium),
+            utm_campaign: clean(self.utm_campaign),
+            utm_term: clean(self.utm_term),
+            utm_content: clean(self.utm_content),
+            referrer: clean(self.referrer).and_then(|r| referrer_host(&r)),
+        }
+    }
+
+    /// Fill in the referrer from the `Referer` header of the subscribe page
+    /// request, unless the page linked to itself.
+    #[must_use]
+    pub fn with_referer_header(mut self, referer: Option<&str>, own_base_url: &str) -> Self {
+        if self.referrer.is_none() {
+            let own = referrer_host(own_base_url);
+            self.referrer = referer
+                .and_then(referrer_host)
+                .filter(|host| Some(host) != own.as_ref());
+        }
+        self
+    }
+}
+
+/// Signups per channel (UTM source, else referring site, else how they were
+/// added) and medium, most first.
+pub async fn breakdown(db: &PgPool, limit: i64) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, Option<String>, i64, i64)>(
+        "SELECT COALESCE(utm_source, referrer, \
+                CASE WHEN COALESCE(subscription_source, 'web') = 'web' THEN '(direct)' \
+                ELSE subscription_source END) AS channel, \
+         utm_medium, COUNT(*), \
+         COUNT(*) FILTER (WHERE status = true AND verified_email = true) \
+         FROM subscribers GROUP BY channel, utm_medium ORDER BY COUNT(*) DESC, channel LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(channel, medium, total, active)| {
+            serde_json::json!({
+                "channel": channel,
+                "medium": medium.unwrap_or_default(),
+                "total": total,
+                "active": active,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized() {
+        let attribution = Attribution {
+            utm_source: Some(" Booth-QR ".to_string()),
+            utm_medium: Some("Print".to_string()),
+            utm_campaign: Some("COSCUP 2025".to_string()),
+            utm_term: Some("  ".to_string()),
+            utm_content: Some("x".repeat(MAX_VALUE_CHARS + 5)),
+            referrer: Some("https://www.Blog.coscup.org/2025/recap?x=1".to_string()),
+        }
+        .normalized();
+        assert_eq!(attribution.utm_source.as_deref(), Some("booth-qr"));
+        assert_eq!(attribution.utm_medium.as_deref(), Some("print"));
+        assert_eq!(attribution.utm_campaign.as_deref(), Some("COSCUP 2025"));
+        assert_eq!(attribution.utm_term, None);
+        assert_eq!(
+            attribution.utm_content.map(|c| c.chars().count()),
+            Some(MAX_VALUE_CHARS)
+        );
+        assert_eq!(attribution.referrer.as_deref(), Some("blog.coscup.org"));
+    }
+
+    #[test]
+    fn test_with_referer_header() {
+        let base = "https://newsletter.coscup.org";
+        let from_blog =
+            Attribution::default().with_referer_header(Some("https://blog.coscup.org/post"), base);
+        assert_eq!(from_blog.referrer.as_deref(), Some("blog.coscup.org"));
+
+        let from_self = Attribution::default()
+            .with_referer_header(Some("https://newsletter.coscup.org/newsletters"), base);
+        assert_eq!(from_self.referrer, None);
+
+        let explicit = Attribution {
+            referrer: Some("twitter.com".to_string()),
+            ..Attribution::default()
+        }
+        .with_referer_header(Some("https://blog.coscup.org/"), base);
+        assert_eq!(explicit.referrer.as_deref(), Some("twitter.com"));
+    }
+}
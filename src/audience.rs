@@ -0,0 +1,214 @@
+//! The audience snapshot of a newsletter: every subscriber it was addressed
+//! to, recorded by `newsletter::send_newsletter` before the first email goes
+//! out. Addresses are kept only as a SHA-256 of the normalized address, enough
+//! to check whether someone was included and to download the list.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+/// Hex SHA-256 of the trimmed, lowercased address, as stored for subscribers.
+pub fn email_sha256(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+/// Add recipients, as (subscriber id, address, deferred), to the snapshot;
+/// those already in it (a resumed send) are kept as first recorded.
+pub async fn record<'a>(
+    db: &PgPool,
+    newsletter_id: uuid::Uuid,
+    recipients: impl IntoIterator<Item = (uuid::Uuid, &'a str, bool)>,
+) -> Result<(), sqlx::Error> {
+    let mut ids = Vec::new();
+    let mut hashes = Vec::new();
+    let mut deferred_flags = Vec::new();
+    for (id, email, deferred) in recipients {
+        ids.push(id);
+        hashes.push(email_sha256(email));
+        deferred_flags.push(deferred);
+    }
+    sqlx::query(
+        "INSERT INTO newsletter_audience (newsletter_id, subscriber_id, email_sha256, deferred) \
+         SELECT $1, * FROM UNNEST($2::UUID[], $3::TEXT[], $4::BOOLEAN[]) \
+         ON CONFLICT (newsletter_id, subscriber_id) DO NOTHING",
+    )
+    .bind(newsletter_id)
+    .bind(&ids)
+    .bind(&hashes)
+    .bind(&deferred_flags)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Size of the snapshot and when it was (last added to).
+pub async fn summary(
+    db: &PgPool,
+    newsletter_id: uuid::Uuid,
+) -> Result<(i64, Option<DateTime<Utc>>), sqlx::Error> {
+    sqlx::query_as(
+        "SELECT COUNT(*), MAX(added_at) FROM newsletter_audience WHERE newsletter_id = $1",
+    )
+    .bind(newsletter_id)
+    .fetch_one(db)
+    .await
+}
+
+#[derive(Debug, Serialize)]
+pub struct Member {
+    pub subscriber_id: uuid::Uuid,
+    pub email_sha256: String,
+    pub deferred: bool,
+    pub added_at: DateTime<Utc>,
+}
+
+/// Whether the address was in the snapshot.
+pub async fn find(
+    db: &PgPool,
+    newsletter_id: uuid::Uuid,
+    email: &str,
+) -> Result<Option<Member>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (uuid::Uuid, String, bool, DateTime<Utc>)>(
+        "SELECT subscriber_id, email_sha256, deferred, added_at FROM newsletter_audience \
+         WHERE newsletter_id = $1 AND email_sha256 = $2",
+    )
+    .bind(newsletter_id)
+    .bind(email_sha256(email))
+    .fetch_optional(db)
+    .await?;
+    Ok(
+        row.map(|(subscriber_id, email_sha256, deferred, added_at)| Member {
+            subscriber_id,
+            email_sha256,
+            deferred,
+            added_at,
+        }),
+    )
+}
+
+/// The snapshot as CSV: `subscriber_id,email_sha256,deferred,added_at`.
+pub async fn export_csv(db: &PgPool, newsletter_id: uuid::Uuid) -> Result<String, String> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, bool, DateTime<Utc>)>(
+        "SELECT subscriber_id, email_sha256, deferred, added_at FROM newsletter_audience \
+         WHERE newsletter_id = $1 ORDER BY added_at, subscriber_id",
+    )
+    .bind(newsletter_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for (subscriber_id, email_sha256, deferred, added_at) in rows {
+        writer
+            .serialize(Member {
+                subscriber_id,
+                email_sha256,
+                deferred,
+                added_at,
+            })
+            .map_err(|e| e.to_string())?;
+    }
+    let data = writer.into_inner().map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_sha256_normalizes() {
+        assert_eq!(
+            email_sha256(" Someone@COSCUP.org "),
+            email_sha256("someone@coscup.org")
+        );
+        assert_eq!(
+            email_sha256(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_snapshot_kept_as_first_recorded(db: PgPool) {
+        crate::db::run_migrations(&db).await.unwrap();
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('T', 'audience-test', '') RETURNING id",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        let (a, b) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+
+        record(
+            &db,
+            newsletter_id,
+            [(a, "a@example.org", false), (b, "b@example.org", true)],
+        )
+        .await
+        .unwrap();
+        // A resume after the address changed keeps the original
+        record(&db, newsletter_id, [(a, "new@example.org", false)])
+            .await
+            .unwrap();
+
+        assert_eq!(summary(&db, newsletter_id).await.unwrap().0, 2);
+        assert_eq!(
+            find(&db, newsletter_id, "A@example.org")
+                .await
+                .unwrap()
+                .unwrap()
+                .subscriber_id,
+            a
+        );
+        assert!(
+            find(&db, newsletter_id, "b@example.org")
+                .await
+                .unwrap()
+                .unwrap()
+                .deferred
+        );
+        assert!(find(&db, newsletter_id, "new@example.org")
+            .await
+            .unwrap()
+            .is_none());
+
+        let csv = export_csv(&db, newsletter_id).await.unwrap();
+        assert!(csv.starts_with("subscriber_id,email_sha256,deferred,added_at\n"));
+        assert!(csv.contains(&email_sha256("a@example.org")));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_audience_page(db: PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('T', 'audience-page', '') RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let page = format!("/admin/newsletters/{newsletter_id}/audience");
+        assert!(app.get(&page).await.body.contains("還沒有收件名單"));
+
+        let a = uuid::Uuid::from_u128(1);
+        record(&app.state.db, newsletter_id, [(a, "a@example.org", false)])
+            .await
+            .unwrap();
+        let found = app.get(&format!("{page}?email=A%40example.org")).await;
+        assert_eq!(found.status, StatusCode::OK);
+        assert!(found.body.contains(&a.to_string()));
+        let missing = app.get(&format!("{page}?email=b%40example.org")).await;
+        assert!(missing.body.contains("不在收件名單中"));
+
+        let csv = app.get(&format!("{page}.csv")).await;
+        assert_eq!(csv.status, StatusCode::OK);
+        assert!(csv.body.contains(&a.to_string()));
+    }
+}
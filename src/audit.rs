@@ -1,7 +1,79 @@
 use std::net::IpAddr;
+use std::sync::OnceLock;
 
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use serde_json::Value as JsonValue;
 use sqlx::PgPool;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+/// Entries waiting to be forwarded; beyond this they are dropped (and the
+/// drop logged) rather than slowing down the request that wrote them.
+const FORWARD_QUEUE: usize = 1024;
+
+/// Syslog priority: facility 13 (log audit), severity 6 (informational).
+const SYSLOG_PRI: u8 = 13 * 8 + 6;
+
+static FORWARDER: OnceLock<mpsc::Sender<AuditEntry>> = OnceLock::new();
+
+/// External copy of the audit log, so security-relevant actions survive the
+/// database being tampered with or restored from backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditSink {
+    /// RFC 5424 messages over UDP, `syslog://host[:port]`
+    Syslog(String),
+    /// JSON POST per entry, `https://...`
+    Webhook(String),
+    /// One JSON line per entry, `file:///path`
+    File(String),
+}
+
+impl AuditSink {
+    /// Parse `AUDIT_SINK`; `None` for an unsupported scheme.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        if let Some(addr) = spec.strip_prefix("syslog://") {
+            let addr = addr.trim_end_matches('/');
+            if addr.is_empty() {
+                return None;
+            }
+            let addr = if addr.contains(':') {
+                addr.to_string()
+            } else {
+                format!("{addr}:514")
+            };
+            Some(Self::Syslog(addr))
+        } else if spec.starts_with("https://") || spec.starts_with("http://") {
+            Some(Self::Webhook(spec.to_string()))
+        } else {
+            spec.strip_prefix("file://")
+                .filter(|path| !path.is_empty())
+                .map(|path| Self::File(path.to_string()))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AuditEntry {
+    admin_email: String,
+    action: String,
+    details: Option<JsonValue>,
+    ip_address: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// RFC 5424 line with the entry as JSON in the message part.
+fn syslog_message(entry: &AuditEntry) -> String {
+    format!(
+        "<{SYSLOG_PRI}>1 {} - coscup-newsletter - {} - {}",
+        entry
+            .created_at
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        entry.action,
+        serde_json::to_string(entry).unwrap_or_default()
+    )
+}
 
 pub async fn log(
     pool: &PgPool,
@@ -24,4 +96,125 @@ pub async fn log(
     if let Err(e) = result {
         tracing::error!("Failed to write audit log: {e}");
     }
+
+    if let Some(forwarder) = FORWARDER.get() {
+        let entry = AuditEntry {
+            admin_email: admin_email.to_string(),
+            action: action.to_string(),
+            details,
+            ip_address: ip_str,
+            created_at: Utc::now(),
+        };
+        if forwarder.try_send(entry).is_err() {
+            tracing::error!("Audit forwarding queue is full, dropped {action} by {admin_email}");
+        }
+    }
+}
+
+/// Mirror every audit log entry written from now on to `sink`.
+pub fn start_forwarding(sink: AuditSink) {
+    let (tx, rx) = mpsc::channel(FORWARD_QUEUE);
+    if FORWARDER.set(tx).is_ok() {
+        tokio::spawn(forward_loop(sink, rx));
+    }
+}
+
+async fn forward_loop(sink: AuditSink, mut rx: mpsc::Receiver<AuditEntry>) {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    while let Some(entry) = rx.recv().await {
+        if let Err(e) = forward(&sink, &client, &entry).await {
+            tracing::error!("Failed to forward audit entry {}: {e}", entry.action);
+        }
+    }
+}
+
+async fn forward(
+    sink: &AuditSink,
+    client: &reqwest::Client,
+    entry: &AuditEntry,
+) -> Result<(), String> {
+    match sink {
+        AuditSink::Syslog(addr) => {
+            let socket = tokio::net::UdpSocket::bind("0.0.0.0:0")
+                .await
+                .map_err(|e| e.to_string())?;
+            socket
+                .send_to(syslog_message(entry).as_bytes(), addr)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        AuditSink::Webhook(url) => {
+            client
+                .post(url)
+                .json(entry)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| e.to_string())?;
+        }
+        AuditSink::File(path) => {
+            let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+            line.push('\n');
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .map_err(|e| e.to_string())?;
+            file.write_all(line.as_bytes())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_sink() {
+        assert_eq!(
+            AuditSink::parse("syslog://logs.example.org"),
+            Some(AuditSink::Syslog("logs.example.org:514".to_string()))
+        );
+        assert_eq!(
+            AuditSink::parse("syslog://10.0.0.5:5514"),
+            Some(AuditSink::Syslog("10.0.0.5:5514".to_string()))
+        );
+        assert_eq!(
+            AuditSink::parse("https://siem.example.org/hook"),
+            Some(AuditSink::Webhook(
+                "https://siem.example.org/hook".to_string()
+            ))
+        );
+        assert_eq!(
+            AuditSink::parse("file:///var/log/newsletter/audit.jsonl"),
+            Some(AuditSink::File(
+                "/var/log/newsletter/audit.jsonl".to_string()
+            ))
+        );
+        assert_eq!(AuditSink::parse("syslog://"), None);
+        assert_eq!(AuditSink::parse("ftp://example.org"), None);
+    }
+
+    #[test]
+    fn test_syslog_message() {
+        let entry = AuditEntry {
+            admin_email: "admin@coscup.org".to_string(),
+            action: "admin.login".to_string(),
+            details: None,
+            ip_address: Some("203.0.113.5".to_string()),
+            created_at: Utc.with_ymd_and_hms(2026, 8, 9, 1, 2, 3).unwrap(),
+        };
+        let message = syslog_message(&entry);
+        assert!(message
+            .starts_with("<110>1 2026-08-09T01:02:03.000Z - coscup-newsletter - admin.login - {"));
+        assert!(message.contains("\"ip_address\":\"203.0.113.5\""));
+    }
 }
@@ -1,15 +1,20 @@
 use axum::extract::State;
 use axum::http::Request;
 use axum::middleware::Next;
-use axum::response::Response;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_extra::extract::CookieJar;
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 
 use crate::error::AppError;
 use crate::AppState;
 
 pub const SESSION_COOKIE: &str = "admin_session";
 
+/// Sessions are only extended when this much of the lifetime has been used,
+/// so active admins don't cause a write on every request.
+const TOUCH_THRESHOLD_SECS: i64 = 60;
+
 #[derive(Clone)]
 struct AdminEmail(String);
 
@@ -37,27 +42,115 @@ impl<S: Send + Sync> axum::extract::FromRequestParts<S> for AdminUser {
 }
 
 /// Middleware that verifies admin session from cookie and stores the email
-/// in request extensions for downstream extractors.
+/// in request extensions for downstream extractors. Each request extends the
+/// session (sliding expiration); remembered sessions also get a fresh cookie.
 pub async fn admin_auth_middleware(
     State(state): State<AppState>,
     jar: CookieJar,
     mut req: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, AppError> {
-    let email = get_admin_email_from_jar(&state, &jar).await?;
+    let (email, refreshed_cookie) = touch_session(&state, &jar).await?;
     req.extensions_mut().insert(AdminEmail(email));
+    let response = next.run(req).await;
+    Ok(match refreshed_cookie {
+        Some(cookie) => (jar.add(cookie), response).into_response(),
+        None => response,
+    })
+}
+
+/// Which of the `API_TOKENS` a request came with: a hash of the token, so it
+/// can be stored without storing the token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiTokenId(pub String);
+
+impl ApiTokenId {
+    pub fn of(token: &str) -> Self {
+        use sha2::{Digest, Sha256};
+        Self(hex::encode(&Sha256::digest(token.as_bytes())[..8]))
+    }
+}
+
+/// Middleware for `/api/v1` routes: requires `Authorization: Bearer <token>`
+/// matching one of the configured `API_TOKENS`, and hands the handlers its
+/// `ApiTokenId`.
+pub async fn api_auth_middleware(
+    State(state): State<AppState>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token = req
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(bearer_token)
+        .ok_or(AppError::Unauthorized)?;
+
+    if !state.config.is_api_token(token) {
+        return Err(AppError::Unauthorized);
+    }
+    let token_id = ApiTokenId::of(token);
+    req.extensions_mut().insert(token_id);
     Ok(next.run(req).await)
 }
 
-async fn get_admin_email_from_jar(state: &AppState, jar: &CookieJar) -> Result<String, AppError> {
+/// Extract the token from an `Authorization: Bearer <token>` header value.
+fn bearer_token(value: &str) -> Option<&str> {
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Lifetime of a session from its last activity.
+pub fn session_ttl(config: &crate::config::AppConfig, remember: bool) -> Duration {
+    if remember {
+        Duration::days(config.admin_session_remember_days)
+    } else {
+        Duration::hours(config.admin_session_idle_hours)
+    }
+}
+
+/// New expiry for a session used at `now`, or `None` if it was extended
+/// recently enough to skip the write.
+fn extended_expiry(
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    ttl: Duration,
+) -> Option<DateTime<Utc>> {
+    let new_expiry = now + ttl;
+    (new_expiry - expires_at > Duration::seconds(TOUCH_THRESHOLD_SECS)).then_some(new_expiry)
+}
+
+/// Session cookie. Remembered sessions persist across browser restarts;
+/// others are dropped when the browser closes.
+pub fn session_cookie(token: String, is_https: bool, max_age: Option<Duration>) -> Cookie<'static> {
+    let mut builder = Cookie::build((SESSION_COOKIE, token))
+        .path("/admin")
+        .http_only(true)
+        .secure(is_https)
+        .same_site(SameSite::Lax);
+    if let Some(max_age) = max_age {
+        builder = builder.max_age(time::Duration::seconds(max_age.num_seconds()));
+    }
+    builder.build()
+}
+
+/// Look up the session from the cookie and slide its expiry forward. Returns
+/// the admin email and, for remembered sessions that were extended, a cookie
+/// with a matching max-age.
+async fn touch_session(
+    state: &AppState,
+    jar: &CookieJar,
+) -> Result<(String, Option<Cookie<'static>>), AppError> {
     let token = jar
         .get(SESSION_COOKIE)
         .map(|c| c.value().to_string())
         .ok_or(AppError::Unauthorized)?;
 
     let now = Utc::now();
-    let email = sqlx::query_scalar::<_, String>(
-        "SELECT admin_email FROM admin_sessions WHERE session_token = $1 AND expires_at > $2",
+    let (email, remember, expires_at) = sqlx::query_as::<_, (String, bool, DateTime<Utc>)>(
+        "SELECT admin_email, remember, expires_at FROM admin_sessions \
+         WHERE session_token = $1 AND expires_at > $2",
     )
     .bind(&token)
     .bind(now)
@@ -65,7 +158,19 @@ async fn get_admin_email_from_jar(state: &AppState, jar: &CookieJar) -> Result<S
     .await?
     .ok_or(AppError::Unauthorized)?;
 
-    Ok(email)
+    let ttl = session_ttl(&state.config, remember);
+    let Some(new_expiry) = extended_expiry(expires_at, now, ttl) else {
+        return Ok((email, None));
+    };
+    sqlx::query("UPDATE admin_sessions SET expires_at = $1 WHERE session_token = $2")
+        .bind(new_expiry)
+        .bind(&token)
+        .execute(&state.db)
+        .await?;
+
+    let is_https = state.config.base_url.starts_with("https://");
+    let cookie = remember.then(|| session_cookie(token, is_https, Some(ttl)));
+    Ok((email, cookie))
 }
 
 #[cfg(test)]
@@ -91,6 +196,42 @@ mod tests {
         assert_eq!(result.unwrap().0, "admin@example.com");
     }
 
+    #[test]
+    fn extended_expiry_skips_recent_touches() {
+        let now = Utc::now();
+        let ttl = Duration::hours(24);
+        assert_eq!(extended_expiry(now + ttl, now, ttl), None);
+        assert_eq!(
+            extended_expiry(now + ttl - Duration::seconds(30), now, ttl),
+            None
+        );
+        assert_eq!(
+            extended_expiry(now + Duration::hours(1), now, ttl),
+            Some(now + ttl)
+        );
+    }
+
+    #[test]
+    fn session_cookie_max_age() {
+        let cookie = session_cookie("tok".to_string(), true, Some(Duration::days(30)));
+        assert_eq!(cookie.max_age(), Some(time::Duration::days(30)));
+        assert_eq!(cookie.path(), Some("/admin"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.http_only(), Some(true));
+
+        let cookie = session_cookie("tok".to_string(), false, None);
+        assert_eq!(cookie.max_age(), None);
+    }
+
+    #[test]
+    fn bearer_token_parsing() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer  abc "), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("abc"), None);
+    }
+
     #[tokio::test]
     async fn admin_user_from_extensions_missing() {
         let mut parts = make_parts();
@@ -0,0 +1,285 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use sqlx::PgPool;
+
+use crate::csv_handler::{self, ExportCsvRecord};
+use crate::error::AppError;
+use crate::jobs::JobKind;
+use crate::security;
+use crate::storage::ObjectStore;
+
+/// Build the subscriber CSV export (same content as the admin download).
+pub async fn subscriber_export_csv(db: &PgPool) -> Result<String, AppError> {
+    let rows = sqlx::query_as::<_, (String, String, String, bool, String)>(
+        "SELECT email, name, ucode, status, secret_code FROM subscribers ORDER BY created_at DESC",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let records: Vec<ExportCsvRecord> = rows
+        .into_iter()
+        .map(|(email, name, ucode, status, secret_code)| {
+            let admin_link = security::compute_admin_link(&secret_code, &email);
+            let openhash = security::compute_openhash(&secret_code, &ucode, "", "");
+            ExportCsvRecord {
+                email,
+                name,
+                ucode,
+                status,
+                admin_link,
+                openhash,
+            }
+        })
+        .collect();
+
+    csv_handler::write_export_csv(&records).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Key numbers for an off-site snapshot: subscriber totals and per-newsletter results.
+pub async fn stats_snapshot(db: &PgPool) -> Result<serde_json::Value, AppError> {
+    let (total, active, verified, bounced) = sqlx::query_as::<_, (i64, i64, i64, i64)>(
+        "SELECT COUNT(*), \
+         COUNT(*) FILTER (WHERE status = true), \
+         COUNT(*) FILTER (WHERE verified_email = true), \
+         COUNT(*) FILTER (WHERE bounced_at IS NOT NULL) \
+         FROM subscribers",
+    )
+    .fetch_one(db)
+    .await?;
+
+    let newsletters = sqlx::query_as::<_, (uuid::Uuid, String, String, String, i32, i32, i32, Option<DateTime<Utc>>, i64, i64, i64)>(
+        "SELECT n.id, n.title, n.slug, n.status, n.sent_count, n.failed_count, n.total_count, \
+         n.sending_completed_at, \
+         (SELECT COUNT(DISTINCT ucode) FROM email_events WHERE newsletter_id = n.id AND event_type = 'open'), \
+         (SELECT COUNT(DISTINCT ucode) FROM email_events WHERE newsletter_id = n.id AND event_type = 'click' AND NOT is_scanner), \
+         (SELECT COUNT(*) FROM unsubscribe_events WHERE newsletter_id = n.id) \
+         FROM newsletters n WHERE n.status IN ('sent', 'sending', 'paused') \
+         ORDER BY n.created_at DESC",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let newsletters: Vec<serde_json::Value> = newsletters
+        .into_iter()
+        .map(
+            |(
+                id,
+                title,
+                slug,
+                status,
+                sent,
+                failed,
+                total,
+                completed_at,
+                opens,
+                clicks,
+                unsubs,
+            )| {
+                serde_json::json!({
+                    "id": id.to_string(),
+                    "title": title,
+                    "slug": slug,
+                    "status": status,
+                    "sent_count": sent,
+                    "failed_count": failed,
+                    "total_count": total,
+                    "completed_at": completed_at.map(|t| t.to_rfc3339()),
+                    "unique_opens": opens,
+                    "unique_clicks": clicks,
+                    "unsubscribes": unsubs,
+                })
+            },
+        )
+        .collect();
+
+    Ok(serde_json::json!({
+        "generated_at": Utc::now().to_rfc3339(),
+        "subscribers": {
+            "total": total,
+            "active": active,
+            "verified": verified,
+            "bounced": bounced,
+        },
+        "newsletters": newsletters,
+    }))
+}
+
+/// Object keys for one day's export.
+pub fn export_keys(prefix: &str, date: NaiveDate) -> (String, String) {
+    let stamp = date.format("%Y%m%d");
+    (
+        format!("{prefix}subscribers/subscribers-{stamp}.csv"),
+        format!("{prefix}stats/stats-{stamp}.json"),
+    )
+}
+
+/// Date stamp embedded in an export key (`...-YYYYMMDD.ext`).
+fn key_date(key: &str) -> Option<NaiveDate> {
+    let stem = key.rsplit_once('.').map_or(key, |(stem, _)| stem);
+    let stamp = stem.rsplit_once('-')?.1;
+    NaiveDate::parse_from_str(stamp, "%Y%m%d").ok()
+}
+
+/// Keys whose date stamp is older than `retention_days` before `today`.
+/// Keys without a recognizable stamp are never deleted.
+pub fn expired_keys(keys: &[String], today: NaiveDate, retention_days: i64) -> Vec<String> {
+    let cutoff = today - Duration::days(retention_days);
+    keys.iter()
+        .filter(|k| key_date(k).is_some_and(|d| d < cutoff))
+        .cloned()
+        .collect()
+}
+
+/// Next time (UTC) the nightly export should run: `hour`:00 Taiwan time.
+pub fn next_run_at(now: DateTime<Utc>, hour: u32) -> DateTime<Utc> {
+    let tz = crate::timezone::DEFAULT;
+    let local = now.with_timezone(&tz);
+    let today_run = local
+        .date_naive()
+        .and_hms_opt(hour.min(23), 0, 0)
+        .and_then(|t| tz.from_local_datetime(&t).single())
+        .expect("Taiwan has no DST gaps");
+    let run = if today_run > local {
+        today_run
+    } else {
+        today_run + Duration::days(1)
+    };
+    run.with_timezone(&Utc)
+}
+
+/// Export settings read from config.
+#[derive(Clone, Debug)]
+pub struct ExportSettings {
+    pub prefix: String,
+    pub retention_days: i64,
+    pub hour: u32,
+}
+
+/// Write today's subscriber CSV and stats snapshot, then prune old exports.
+pub async fn run_export(
+    db: &PgPool,
+    store: &dyn ObjectStore,
+    settings: &ExportSettings,
+) -> Result<(), String> {
+    let today = Utc::now()
+        .with_timezone(&crate::timezone::DEFAULT)
+        .date_naive();
+    let (csv_key, stats_key) = export_keys(&settings.prefix, today);
+
+    let csv_data = subscriber_export_csv(db).await.map_err(|e| e.to_string())?;
+    store
+        .put_object(&csv_key, csv_data.into_bytes(), "text/csv; charset=utf-8")
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let snapshot = stats_snapshot(db).await.map_err(|e| e.to_string())?;
+    let snapshot = serde_json::to_vec_pretty(&snapshot).map_err(|e| e.to_string())?;
+    store
+        .put_object(&stats_key, snapshot, "application/json")
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let keys = store
+        .list_keys(&settings.prefix)
+        .await
+        .map_err(|e| e.to_string())?;
+    let expired = expired_keys(&keys, today, settings.retention_days);
+    for key in &expired {
+        if let Err(e) = store.delete_object(key).await {
+            tracing::warn!("Failed to delete expired export {key}: {e}");
+        }
+    }
+
+    tracing::info!(
+        "Nightly export written ({csv_key}, {stats_key}), {} expired exports removed",
+        expired.len()
+    );
+    Ok(())
+}
+
+/// Background loop running `run_export` once a night.
+pub async fn export_loop(
+    db: PgPool,
+    store: std::sync::Arc<dyn ObjectStore>,
+    settings: ExportSettings,
+) {
+    loop {
+        let next = next_run_at(Utc::now(), settings.hour);
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let export = run_export(&db, store.as_ref(), &settings);
+        if let Err(e) = crate::jobs::track(&db, JobKind::Export, None, export).await {
+            tracing::error!("Nightly export failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_keys() {
+        let date = NaiveDate::from_ymd_opt(2025, 8, 9).unwrap();
+        let (csv, stats) = export_keys("exports/", date);
+        assert_eq!(csv, "exports/subscribers/subscribers-20250809.csv");
+        assert_eq!(stats, "exports/stats/stats-20250809.json");
+    }
+
+    #[test]
+    fn test_expired_keys() {
+        let today = NaiveDate::from_ymd_opt(2025, 8, 31).unwrap();
+        let keys = vec![
+            "exports/subscribers/subscribers-20250801.csv".to_string(),
+            "exports/stats/stats-20250730.json".to_string(),
+            "exports/stats/stats-20250830.json".to_string(),
+            "exports/README.txt".to_string(),
+        ];
+        assert_eq!(
+            expired_keys(&keys, today, 30),
+            vec!["exports/stats/stats-20250730.json".to_string()]
+        );
+        assert_eq!(expired_keys(&keys, today, 0).len(), 3);
+    }
+
+    #[test]
+    fn test_next_run_at() {
+        // 2025-08-09 01:00 Taiwan = 2025-08-08 17:00 UTC → same-day 03:00 Taiwan
+        let now = Utc.with_ymd_and_hms(2025, 8, 8, 17, 0, 0).unwrap();
+        assert_eq!(
+            next_run_at(now, 3),
+            Utc.with_ymd_and_hms(2025, 8, 8, 19, 0, 0).unwrap()
+        );
+        // Already past 03:00 Taiwan → next day
+        let now = Utc.with_ymd_and_hms(2025, 8, 8, 20, 0, 0).unwrap();
+        assert_eq!(
+            next_run_at(now, 3),
+            Utc.with_ymd_and_hms(2025, 8, 9, 19, 0, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_exports_pruned_from_store() {
+        use crate::storage::tests::MockObjectStore;
+
+        let store = MockObjectStore::default();
+        for key in [
+            "exports/stats/stats-20250101.json",
+            "exports/stats/stats-20250830.json",
+        ] {
+            store
+                .put_object(key, Vec::new(), "application/json")
+                .await
+                .unwrap();
+        }
+        let keys = store.list_keys("exports/").await.unwrap();
+        let today = NaiveDate::from_ymd_opt(2025, 8, 31).unwrap();
+        for key in expired_keys(&keys, today, 30) {
+            store.delete_object(&key).await.unwrap();
+        }
+        assert_eq!(
+            store.list_keys("exports/").await.unwrap(),
+            vec!["exports/stats/stats-20250830.json"]
+        );
+    }
+}
@@ -0,0 +1,970 @@
+//! Bounces and spam complaints the mail provider reports after it accepted a
+//! message, posted to `/webhooks/email-events`. Bounces the relay gives right
+//! away are handled when sending (`newsletter::record_send_failure`); these
+//! come later and are applied the same way: a hard bounce stops mail to the
+//! subscriber, soft bounces count towards `SOFT_BOUNCE_THRESHOLD`. A complaint
+//! is recorded and unsubscribes them. The send the event is about is found by
+//! our `Message-ID` (see `inbound::send_message_id`) when the provider passes
+//! it back, otherwise it is the latest send to the address.
+//!
+//! Each provider's format is checked with its own signature:
+//!
+//! - Amazon SES through SNS (`x-amz-sns-message-type`): the topic must be in
+//!   `SES_SNS_TOPIC_ARNS`, and the message signed (`SignatureVersion` 2) with
+//!   the certificate SNS serves for it. Subscription confirmations are
+//!   followed, so subscribing the endpoint needs no manual step.
+//! - Mailgun (JSON with `signature` and `event-data`): HMAC of the timestamp
+//!   and token with `MAILGUN_WEBHOOK_SIGNING_KEY`.
+//! - `SendGrid` signed Event Webhook: ECDSA over the timestamp and body with
+//!   `SENDGRID_WEBHOOK_PUBLIC_KEY`.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::CertificateDer;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::error::AppError;
+use crate::webhook::{record_nonce, WebhookError};
+use crate::AppState;
+
+pub const SNS_TYPE_HEADER: &str = "x-amz-sns-message-type";
+pub const SENDGRID_SIGNATURE_HEADER: &str = "x-twilio-email-event-webhook-signature";
+pub const SENDGRID_TIMESTAMP_HEADER: &str = "x-twilio-email-event-webhook-timestamp";
+
+/// SNS retries a notification for a while with its original timestamp, so
+/// SNS messages get this long instead of `WEBHOOK_TOLERANCE_SECS`.
+const SNS_MAX_AGE_SECS: i64 = 86_400;
+
+/// Longest bounce diagnostic kept on the send.
+const MAX_DETAIL_CHARS: usize = 500;
+
+/// `SubjectPublicKeyInfo` of a P-256 key, up to the point it wraps.
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    HardBounce,
+    SoftBounce,
+    Complaint,
+}
+
+/// A bounce or complaint for one recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailEvent {
+    pub kind: EventKind,
+    pub email: String,
+    /// Our `Message-ID` of the message, when the provider passes it back
+    pub message_id: Option<String>,
+    /// Bounce diagnostic or complaint feedback type
+    pub detail: String,
+}
+
+fn text(value: &Value, pointer: &str) -> Option<String> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+fn truncate(detail: &str) -> String {
+    detail.trim().chars().take(MAX_DETAIL_CHARS).collect()
+}
+
+// --- Amazon SES (through SNS) ---
+
+/// A message SNS posts: a notification, or a (un)subscription confirmation.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub kind: String,
+    pub message_id: String,
+    pub topic_arn: String,
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub message: String,
+    pub timestamp: String,
+    pub signature_version: String,
+    pub signature: String,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: String,
+    #[serde(default, rename = "SubscribeURL")]
+    pub subscribe_url: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// The text SNS signs: the message's fields as `Name\nvalue\n`, in order.
+fn sns_string_to_sign(msg: &SnsMessage) -> String {
+    let notification = msg.kind == "Notification";
+    let mut fields = vec![
+        ("Message", msg.message.as_str()),
+        ("MessageId", msg.message_id.as_str()),
+    ];
+    if notification {
+        if let Some(subject) = &msg.subject {
+            fields.push(("Subject", subject));
+        }
+    } else {
+        fields.push((
+            "SubscribeURL",
+            msg.subscribe_url.as_deref().unwrap_or_default(),
+        ));
+    }
+    fields.push(("Timestamp", &msg.timestamp));
+    if !notification {
+        fields.push(("Token", msg.token.as_deref().unwrap_or_default()));
+    }
+    fields.push(("TopicArn", &msg.topic_arn));
+    fields.push(("Type", &msg.kind));
+    let mut text = String::new();
+    for (name, value) in fields {
+        text.push_str(name);
+        text.push('\n');
+        text.push_str(value);
+        text.push('\n');
+    }
+    text
+}
+
+/// Whether `url` is an HTTPS URL on SNS itself (`sns.<region>.amazonaws.com`),
+/// so a forged message can't point us at its own certificate or make us
+/// fetch arbitrary URLs.
+fn is_sns_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let Some(region) = url.host_str().and_then(|host| {
+        host.strip_prefix("sns.").and_then(|rest| {
+            rest.strip_suffix(".amazonaws.com")
+                .or_else(|| rest.strip_suffix(".amazonaws.com.cn"))
+        })
+    }) else {
+        return false;
+    };
+    url.scheme() == "https"
+        && !region.is_empty()
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Check an SNS message's signature against the PEM certificate it names.
+fn check_sns_signature(msg: &SnsMessage, cert_pem: &[u8]) -> Result<(), WebhookError> {
+    // Version 1 signs with SHA1; set the topic's SignatureVersion to 2
+    if msg.signature_version != "2" {
+        return Err(WebhookError::BadSignature);
+    }
+    let signature = base64::engine::general_purpose::STANDARD
+        .decode(&msg.signature)
+        .map_err(|_| WebhookError::BadSignature)?;
+    let cert = CertificateDer::from_pem_slice(cert_pem).map_err(|_| WebhookError::BadSignature)?;
+    let cert = webpki::EndEntityCert::try_from(&cert).map_err(|_| WebhookError::BadSignature)?;
+    cert.verify_signature(
+        webpki::ring::RSA_PKCS1_2048_8192_SHA256,
+        sns_string_to_sign(msg).as_bytes(),
+        &signature,
+    )
+    .map_err(|_| WebhookError::BadSignature)
+}
+
+/// The SNS signing certificate at `url`, fetched once per process.
+async fn sns_certificate(url: &str) -> Result<Vec<u8>, AppError> {
+    static CERTS: OnceLock<Mutex<HashMap<String, Vec<u8>>>> = OnceLock::new();
+    let certs = CERTS.get_or_init(Mutex::default);
+    if let Some(cert) = certs.lock().expect("certificate cache lock").get(url) {
+        return Ok(cert.clone());
+    }
+
+    let resp = reqwest::Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| AppError::Internal(format!("Failed to fetch SNS certificate: {e}")))?;
+    let cert = resp
+        .bytes()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to fetch SNS certificate: {e}")))?
+        .to_vec();
+    certs
+        .lock()
+        .expect("certificate cache lock")
+        .insert(url.to_string(), cert.clone());
+    Ok(cert)
+}
+
+/// Verify an SNS message: allowed topic, SNS certificate, signature, age,
+/// and not seen before.
+async fn verify_sns(state: &AppState, msg: &SnsMessage) -> Result<(), AppError> {
+    if state.config.ses_sns_topic_arns.is_empty() {
+        return Err(WebhookError::NotConfigured("SES").into());
+    }
+    if !state.config.ses_sns_topic_arns.contains(&msg.topic_arn) {
+        tracing::warn!("Rejected SNS message from unknown topic {}", msg.topic_arn);
+        return Err(AppError::Unauthorized);
+    }
+    let is_pem = reqwest::Url::parse(&msg.signing_cert_url).is_ok_and(|u| {
+        std::path::Path::new(u.path())
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pem"))
+    });
+    if !is_pem || !is_sns_url(&msg.signing_cert_url) {
+        return Err(WebhookError::BadSignature.into());
+    }
+    let cert = sns_certificate(&msg.signing_cert_url).await?;
+    check_sns_signature(msg, &cert)?;
+
+    let sent_at = chrono::DateTime::parse_from_rfc3339(&msg.timestamp)
+        .map_err(|_| WebhookError::BadTimestamp)?;
+    if (chrono::Utc::now().timestamp() - sent_at.timestamp()).abs() > SNS_MAX_AGE_SECS {
+        return Err(WebhookError::Expired.into());
+    }
+    record_nonce(
+        &state.db,
+        "ses",
+        &msg.message_id,
+        SNS_MAX_AGE_SECS.saturating_mul(2),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Our `Message-ID` of the message an SES notification is about, from its
+/// common headers or, when the topic includes them, the original headers.
+fn ses_message_id(notification: &Value) -> Option<String> {
+    text(notification, "/mail/commonHeaders/messageId")
+        .filter(|id| crate::inbound::parse_thread_ref(id).is_some())
+        .or_else(|| {
+            notification
+                .pointer("/mail/headers")?
+                .as_array()?
+                .iter()
+                .filter(|h| {
+                    text(h, "/name").is_some_and(|name| name.eq_ignore_ascii_case("message-id"))
+                })
+                .find_map(|h| text(h, "/value"))
+        })
+}
+
+/// Bounces and complaints in an SES notification (the SNS `Message`), from
+/// identity notifications or configuration set event publishing.
+pub fn parse_ses(message: &str) -> Vec<EmailEvent> {
+    let Ok(notification) = serde_json::from_str::<Value>(message) else {
+        return Vec::new();
+    };
+    let kind = text(&notification, "/notificationType")
+        .or_else(|| text(&notification, "/eventType"))
+        .unwrap_or_default();
+    let message_id = ses_message_id(&notification);
+
+    let (kind, recipients, detail) = match kind.as_str() {
+        "Bounce" => {
+            // Transient and Undetermined bounces gave up after SES retried
+            let kind = if text(&notification, "/bounce/bounceType").as_deref() == Some("Permanent")
+            {
+                EventKind::HardBounce
+            } else {
+                EventKind::SoftBounce
+            };
+            let detail = text(&notification, "/bounce/bounceSubType").unwrap_or_default();
+            (kind, "/bounce/bouncedRecipients", detail)
+        }
+        "Complaint" => (
+            EventKind::Complaint,
+            "/complaint/complainedRecipients",
+            text(&notification, "/complaint/complaintFeedbackType").unwrap_or_default(),
+        ),
+        _ => return Vec::new(),
+    };
+
+    notification
+        .pointer(recipients)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|recipient| {
+            Some(EmailEvent {
+                kind,
+                email: text(recipient, "/emailAddress")?,
+                message_id: message_id.clone(),
+                detail: truncate(
+                    &text(recipient, "/diagnosticCode").unwrap_or_else(|| detail.clone()),
+                ),
+            })
+        })
+        .collect()
+}
+
+// --- Mailgun ---
+
+#[derive(Debug, Deserialize)]
+pub struct MailgunSignature {
+    pub timestamp: String,
+    pub token: String,
+    pub signature: String,
+}
+
+/// What Mailgun posts for each event.
+#[derive(Debug, Deserialize)]
+struct MailgunWebhook {
+    signature: MailgunSignature,
+    #[serde(rename = "event-data")]
+    event_data: Value,
+}
+
+/// Check a Mailgun webhook signature: HMAC-SHA256 of timestamp and token.
+fn check_mailgun_signature(
+    key: &str,
+    signature: &MailgunSignature,
+    now: i64,
+    tolerance_secs: i64,
+) -> Result<(), WebhookError> {
+    let timestamp: i64 = signature
+        .timestamp
+        .parse()
+        .map_err(|_| WebhookError::BadTimestamp)?;
+    if (now - timestamp).abs() > tolerance_secs {
+        return Err(WebhookError::Expired);
+    }
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(signature.timestamp.as_bytes());
+    mac.update(signature.token.as_bytes());
+    let expected = hex::encode(mac.finalize().into_bytes());
+    if !crate::security::constant_time_eq(&signature.signature.to_ascii_lowercase(), &expected) {
+        return Err(WebhookError::BadSignature);
+    }
+    Ok(())
+}
+
+/// The bounce or complaint in a Mailgun event, if it is one.
+pub fn parse_mailgun(event: &Value) -> Option<EmailEvent> {
+    let email = text(event, "/recipient")?;
+    // Mailgun gives the header value without the angle brackets
+    let message_id = text(event, "/message/headers/message-id").map(|id| {
+        if id.starts_with('<') {
+            id
+        } else {
+            format!("<{id}>")
+        }
+    });
+    let (kind, detail) = match text(event, "/event")?.as_str() {
+        // Temporary failures are reported on every retry while Mailgun keeps trying
+        "failed" if text(event, "/severity").as_deref() == Some("permanent") => {
+            let kind = match text(event, "/reason").as_deref() {
+                Some("bounce" | "suppress-bounce" | "hardfail") => EventKind::HardBounce,
+                // Gave up retrying a temporary failure
+                Some("old") => EventKind::SoftBounce,
+                // Suppressed for an unsubscribe or complaint, or blocked by Mailgun
+                _ => return None,
+            };
+            let detail = text(event, "/delivery-status/message")
+                .or_else(|| text(event, "/delivery-status/description"))
+                .unwrap_or_default();
+            (kind, detail)
+        }
+        "complained" => (EventKind::Complaint, String::new()),
+        _ => return None,
+    };
+    Some(EmailEvent {
+        kind,
+        email,
+        message_id,
+        detail: truncate(&detail),
+    })
+}
+
+// --- SendGrid ---
+
+/// Check a `SendGrid` Event Webhook signature: ECDSA P-256 over the
+/// timestamp followed by the body. `public_key` is the base64 DER key
+/// `SendGrid` shows when signing is turned on.
+fn check_sendgrid_signature(
+    public_key: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: i64,
+    tolerance_secs: i64,
+) -> Result<(), WebhookError> {
+    let header = |name: &'static str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .ok_or(WebhookError::MissingHeader(name))
+    };
+    let timestamp = header(SENDGRID_TIMESTAMP_HEADER)?;
+    let sent_at: i64 = timestamp.parse().map_err(|_| WebhookError::BadTimestamp)?;
+    if (now - sent_at).abs() > tolerance_secs {
+        return Err(WebhookError::Expired);
+    }
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let key = engine.decode(public_key.trim()).unwrap_or_default();
+    let Some(point) = key.strip_prefix(P256_SPKI_PREFIX.as_slice()) else {
+        tracing::error!("SENDGRID_WEBHOOK_PUBLIC_KEY is not a base64 P-256 public key");
+        return Err(WebhookError::BadSignature);
+    };
+    let signature = engine
+        .decode(header(SENDGRID_SIGNATURE_HEADER)?)
+        .map_err(|_| WebhookError::BadSignature)?;
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    webpki::ring::ECDSA_P256_SHA256
+        .verify_signature(point, &message, &signature)
+        .map_err(|_| WebhookError::BadSignature)
+}
+
+/// Bounces and complaints in a `SendGrid` event batch. Our `Message-ID` comes
+/// back as the `message_id` custom arg (see `email_http`).
+pub fn parse_sendgrid(events: &[Value]) -> Vec<EmailEvent> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let (kind, detail) = match text(event, "/event")?.as_str() {
+                // `blocked` is the receiving server refusing for now
+                "bounce" if text(event, "/type").as_deref() == Some("blocked") => {
+                    (EventKind::SoftBounce, text(event, "/reason"))
+                }
+                "bounce" => (EventKind::HardBounce, text(event, "/reason")),
+                "spamreport" => (EventKind::Complaint, None),
+                _ => return None,
+            };
+            Some(EmailEvent {
+                kind,
+                email: text(event, "/email")?,
+                message_id: text(event, "/message_id"),
+                detail: truncate(&detail.unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+// --- Receiving ---
+
+/// Verify a posted callback and return the provider and the bounces and
+/// complaints in it. SNS subscription confirmations are confirmed here.
+pub async fn receive(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(&'static str, Vec<EmailEvent>), AppError> {
+    let now = chrono::Utc::now().timestamp();
+    let tolerance = state.config.webhook_tolerance_secs;
+
+    if headers.contains_key(SNS_TYPE_HEADER) {
+        let msg: SnsMessage = serde_json::from_slice(body)
+            .map_err(|e| AppError::BadRequest(format!("Invalid SNS message: {e}")))?;
+        verify_sns(state, &msg).await?;
+        return match msg.kind.as_str() {
+            "Notification" => Ok(("ses", parse_ses(&msg.message))),
+            "SubscriptionConfirmation" => {
+                let url = msg.subscribe_url.as_deref().unwrap_or_default();
+                if !is_sns_url(url) {
+                    return Err(AppError::BadRequest("Invalid SubscribeURL".to_string()));
+                }
+                reqwest::Client::new()
+                    .get(url)
+                    .timeout(Duration::from_secs(10))
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(|e| {
+                        AppError::Internal(format!("Failed to confirm SNS subscription: {e}"))
+                    })?;
+                tracing::info!("Confirmed SNS subscription to {}", msg.topic_arn);
+                Ok(("ses", Vec::new()))
+            }
+            _ => Ok(("ses", Vec::new())),
+        };
+    }
+
+    if headers.contains_key(SENDGRID_SIGNATURE_HEADER) {
+        let key = state
+            .config
+            .sendgrid_webhook_public_key
+            .as_deref()
+            .ok_or(WebhookError::NotConfigured("SendGrid"))?;
+        check_sendgrid_signature(key, headers, body, now, tolerance)?;
+        let signature = headers
+            .get(SENDGRID_SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        record_nonce(
+            &state.db,
+            "sendgrid",
+            signature,
+            tolerance.saturating_mul(2),
+        )
+        .await?;
+        let events: Vec<Value> = serde_json::from_slice(body)
+            .map_err(|e| AppError::BadRequest(format!("Invalid SendGrid events: {e}")))?;
+        return Ok(("sendgrid", parse_sendgrid(&events)));
+    }
+
+    let Ok(webhook) = serde_json::from_slice::<MailgunWebhook>(body) else {
+        return Err(AppError::BadRequest(
+            "Unrecognized email event format".to_string(),
+        ));
+    };
+    let key = state
+        .config
+        .mailgun_webhook_signing_key
+        .as_deref()
+        .ok_or(WebhookError::NotConfigured("Mailgun"))?;
+    check_mailgun_signature(key, &webhook.signature, now, tolerance)?;
+    record_nonce(
+        &state.db,
+        "mailgun",
+        &webhook.signature.token,
+        tolerance.saturating_mul(2),
+    )
+    .await?;
+    Ok((
+        "mailgun",
+        parse_mailgun(&webhook.event_data).into_iter().collect(),
+    ))
+}
+
+/// The subscriber an event is about and, if found, the newsletter whose
+/// send it concerns.
+async fn find_send(
+    db: &sqlx::PgPool,
+    event: &EmailEvent,
+) -> Result<Option<(uuid::Uuid, Option<uuid::Uuid>)>, sqlx::Error> {
+    if let Some((newsletter_id, ucode)) = event
+        .message_id
+        .as_deref()
+        .and_then(crate::inbound::parse_thread_ref)
+    {
+        let found = sqlx::query_as::<_, (uuid::Uuid, bool)>(
+            "SELECT s.id, EXISTS(SELECT 1 FROM newsletter_sends \
+             WHERE newsletter_id = $1 AND subscriber_id = s.id) \
+             FROM subscribers s WHERE s.ucode = $2",
+        )
+        .bind(newsletter_id)
+        .bind(&ucode)
+        .fetch_optional(db)
+        .await?;
+        if let Some((subscriber_id, sent)) = found {
+            return Ok(Some((subscriber_id, sent.then_some(newsletter_id))));
+        }
+    }
+    let (newsletter_id, subscriber_id) =
+        crate::inbound::latest_send_to(db, &event.email.to_lowercase()).await?;
+    Ok(subscriber_id.map(|id| (id, newsletter_id)))
+}
+
+/// Apply an event to its subscriber and send. Returns false when no
+/// subscriber has the address.
+pub async fn apply(
+    state: &AppState,
+    provider: &str,
+    event: &EmailEvent,
+) -> Result<bool, sqlx::Error> {
+    let Some((subscriber_id, newsletter_id)) = find_send(&state.db, event).await? else {
+        tracing::info!(
+            "Ignoring {provider} {:?} for unknown address {}",
+            event.kind,
+            event.email
+        );
+        return Ok(false);
+    };
+
+    match event.kind {
+        EventKind::HardBounce | EventKind::SoftBounce => {
+            let hard = event.kind == EventKind::HardBounce;
+            if let Some(newsletter_id) = newsletter_id {
+                sqlx::query(
+                    "UPDATE newsletter_sends SET bounced_at = NOW(), bounce_type = $3, \
+                     bounce_detail = $4 WHERE newsletter_id = $1 AND subscriber_id = $2",
+                )
+                .bind(newsletter_id)
+                .bind(subscriber_id)
+                .bind(if hard { "hard" } else { "soft" })
+                .bind(&event.detail)
+                .execute(&state.db)
+                .await?;
+            }
+            if hard {
+                tracing::warn!(
+                    "{provider} reported a hard bounce for {}, marking as bounced",
+                    event.email
+                );
+                crate::newsletter::mark_bounced(state, subscriber_id).await;
+            } else {
+                crate::newsletter::record_soft_bounce(state, subscriber_id, &event.email).await;
+            }
+        }
+        EventKind::Complaint => {
+            if let Some(newsletter_id) = newsletter_id {
+                sqlx::query(
+                    "UPDATE newsletter_sends SET complained_at = NOW() \
+                     WHERE newsletter_id = $1 AND subscriber_id = $2",
+                )
+                .bind(newsletter_id)
+                .bind(subscriber_id)
+                .execute(&state.db)
+                .await?;
+            }
+            sqlx::query(
+                "INSERT INTO email_complaints (subscriber_id, newsletter_id, provider, feedback_type) \
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(subscriber_id)
+            .bind(newsletter_id)
+            .bind(provider)
+            .bind(Some(event.detail.as_str()).filter(|d| !d.is_empty()))
+            .execute(&state.db)
+            .await?;
+
+            // Someone who reported us as spam gets no more mail
+            let unsubscribed = sqlx::query(
+                "UPDATE subscribers SET status = false, updated_at = NOW() \
+                 WHERE id = $1 AND status = true",
+            )
+            .bind(subscriber_id)
+            .execute(&state.db)
+            .await?
+            .rows_affected();
+            if unsubscribed > 0 {
+                tracing::warn!(
+                    "{provider} reported a spam complaint from {}, unsubscribing",
+                    event.email
+                );
+                sqlx::query(
+                    "INSERT INTO unsubscribe_events (subscriber_id, newsletter_id) VALUES ($1, $2)",
+                )
+                .bind(subscriber_id)
+                .bind(newsletter_id)
+                .execute(&state.db)
+                .await?;
+                if let Err(e) = crate::crm_webhook::emit(
+                    state,
+                    subscriber_id,
+                    crate::crm_webhook::Lifecycle::Unsubscribed,
+                )
+                .await
+                {
+                    tracing::error!(
+                        "Failed to queue CRM unsubscribe event for {subscriber_id}: {e}"
+                    );
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE_ID: &str = "<nl.0123456789abcdef0123456789abcdef.abc123@newsletter.coscup.org>";
+
+    fn sns_complaint() -> SnsMessage {
+        SnsMessage {
+            kind: "Notification".to_string(),
+            message_id: "22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324".to_string(),
+            topic_arn: "arn:aws:sns:ap-northeast-1:123456789012:ses-events".to_string(),
+            subject: None,
+            message: format!(
+                r#"{{"notificationType":"Complaint","complaint":{{"complainedRecipients":[{{"emailAddress":"a@example.com"}}],"complaintFeedbackType":"abuse"}},"mail":{{"commonHeaders":{{"messageId":"{MESSAGE_ID}"}}}}}}"#
+            ),
+            timestamp: "2025-08-09T00:00:00.000Z".to_string(),
+            signature_version: "2".to_string(),
+            // Made with the key of tests/bounce_webhook/sns_cert.pem
+            signature: "e6OIY2yQ6kZlqPHxQe1DrCBORXp8oK6rZATDJKHeJK6y1F1s9zD1vEOqBSu0C7nPTuP5vfT/0uDNANU5RT21MIXaE+B/91hUaK30m35yGTX8CFMs8aMoOd1oy65rL/HpPdPNa9iEERgJlLfysDdH3PEHzG/qKTyA1P2oUo2R1VUOVRAdeQDAjcmI3yDFsubpGw2DQKHxKDAC+zW1/3RMgsEt/jht7yl31X2A1R+u/SL5kizl4tEwY5+5njZFCcMX2zDn6bGx3YO2lyCPzG08sXxk4ePOn4uj8Qz/+O16sPQ38W4BDBh2yi7lbdt7Z14I4Qw25xGj7mwgVuLggrIxiQ==".to_string(),
+            signing_cert_url: "https://sns.ap-northeast-1.amazonaws.com/SimpleNotificationService-abc.pem".to_string(),
+            subscribe_url: None,
+            token: None,
+        }
+    }
+
+    #[test]
+    fn test_sns_signature() {
+        let cert = include_bytes!("../tests/bounce_webhook/sns_cert.pem");
+        let msg = sns_complaint();
+        assert!(check_sns_signature(&msg, cert).is_ok());
+
+        let mut tampered = sns_complaint();
+        tampered.message = tampered.message.replace("a@example.com", "b@example.com");
+        assert!(matches!(
+            check_sns_signature(&tampered, cert),
+            Err(WebhookError::BadSignature)
+        ));
+        let mut v1 = sns_complaint();
+        v1.signature_version = "1".to_string();
+        assert!(check_sns_signature(&v1, cert).is_err());
+
+        let mut confirmation = sns_complaint();
+        confirmation.kind = "SubscriptionConfirmation".to_string();
+        confirmation.subscribe_url = Some("https://sns.example/confirm".to_string());
+        confirmation.token = Some("tok".to_string());
+        assert!(sns_string_to_sign(&confirmation).contains(
+            "SubscribeURL\nhttps://sns.example/confirm\nTimestamp\n2025-08-09T00:00:00.000Z\nToken\ntok\n"
+        ));
+    }
+
+    #[test]
+    fn test_is_sns_url() {
+        assert!(is_sns_url(
+            "https://sns.ap-northeast-1.amazonaws.com/SimpleNotificationService-abc.pem"
+        ));
+        assert!(is_sns_url("https://sns.cn-north-1.amazonaws.com.cn/x.pem"));
+        assert!(!is_sns_url("http://sns.us-east-1.amazonaws.com/x.pem"));
+        assert!(!is_sns_url("https://sns.evil.com/x.pem"));
+        assert!(!is_sns_url(
+            "https://sns.us-east-1.amazonaws.com.evil.com/x.pem"
+        ));
+        assert!(!is_sns_url("https://evil.com/sns.us-east-1.amazonaws.com"));
+        assert!(!is_sns_url("https://sns.a.b.amazonaws.com/x.pem"));
+    }
+
+    #[test]
+    fn test_parse_ses() {
+        assert_eq!(
+            parse_ses(&sns_complaint().message),
+            vec![EmailEvent {
+                kind: EventKind::Complaint,
+                email: "a@example.com".to_string(),
+                message_id: Some(MESSAGE_ID.to_string()),
+                detail: "abuse".to_string(),
+            }]
+        );
+
+        let bounce = r#"{"eventType":"Bounce","bounce":{"bounceType":"Permanent","bounceSubType":"General",
+            "bouncedRecipients":[{"emailAddress":"x@example.com","diagnosticCode":"smtp; 550 5.1.1 user unknown"},
+            {"emailAddress":"y@example.com"}]},
+            "mail":{"commonHeaders":{"messageId":"<0100018a@email.amazonses.com>"},
+            "headers":[{"name":"Message-ID","value":"<nl.0123456789abcdef0123456789abcdef.xyz@n.coscup.org>"}]}}"#;
+        let events = parse_ses(bounce);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, EventKind::HardBounce);
+        assert_eq!(events[0].detail, "smtp; 550 5.1.1 user unknown");
+        assert_eq!(events[1].detail, "General");
+        assert_eq!(
+            events[0].message_id.as_deref(),
+            Some("<nl.0123456789abcdef0123456789abcdef.xyz@n.coscup.org>")
+        );
+
+        let transient = r#"{"notificationType":"Bounce","bounce":{"bounceType":"Transient",
+            "bouncedRecipients":[{"emailAddress":"x@example.com"}]},"mail":{}}"#;
+        assert_eq!(parse_ses(transient)[0].kind, EventKind::SoftBounce);
+        assert!(parse_ses(r#"{"notificationType":"Delivery"}"#).is_empty());
+        assert!(parse_ses("not json").is_empty());
+    }
+
+    #[test]
+    fn test_mailgun() {
+        let key = "mailgun-key";
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(b"1754700000tok");
+        let signature = MailgunSignature {
+            timestamp: "1754700000".to_string(),
+            token: "tok".to_string(),
+            signature: hex::encode(mac.finalize().into_bytes()),
+        };
+        assert!(check_mailgun_signature(key, &signature, 1_754_700_100, 300).is_ok());
+        assert!(matches!(
+            check_mailgun_signature("other", &signature, 1_754_700_100, 300),
+            Err(WebhookError::BadSignature)
+        ));
+        assert!(matches!(
+            check_mailgun_signature(key, &signature, 1_754_701_000, 300),
+            Err(WebhookError::Expired)
+        ));
+
+        let failed = serde_json::json!({
+            "event": "failed", "severity": "permanent", "reason": "bounce",
+            "recipient": "x@example.com",
+            "message": { "headers": { "message-id": "nl.0123456789abcdef0123456789abcdef.abc@n.coscup.org" } },
+            "delivery-status": { "code": 550, "message": "5.1.1 The email account does not exist" },
+        });
+        let event = parse_mailgun(&failed).unwrap();
+        assert_eq!(event.kind, EventKind::HardBounce);
+        assert_eq!(
+            event.message_id.as_deref(),
+            Some("<nl.0123456789abcdef0123456789abcdef.abc@n.coscup.org>")
+        );
+        assert_eq!(event.detail, "5.1.1 The email account does not exist");
+
+        let temporary = serde_json::json!({
+            "event": "failed", "severity": "temporary", "recipient": "x@example.com",
+        });
+        assert_eq!(parse_mailgun(&temporary), None);
+        let suppressed = serde_json::json!({
+            "event": "failed", "severity": "permanent", "reason": "suppress-unsubscribe",
+            "recipient": "x@example.com",
+        });
+        assert_eq!(parse_mailgun(&suppressed), None);
+        let complained = serde_json::json!({ "event": "complained", "recipient": "x@example.com" });
+        assert_eq!(
+            parse_mailgun(&complained).unwrap().kind,
+            EventKind::Complaint
+        );
+    }
+
+    #[test]
+    fn test_sendgrid() {
+        // Key pair and signature made with openssl
+        let public_key = "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEEx0kbKhR3DeK/RTz0xuip9HZbVKsj1Ti4vUTxWVJE9MtdoX9Cq8FcWhpbu+7fQ+0ltdKnmOJI1M43dS/H2TEGQ==";
+        let body = br#"[{"email":"a@example.com","event":"spamreport"}]"#;
+        let mut headers = HeaderMap::new();
+        headers.insert(SENDGRID_TIMESTAMP_HEADER, "1754700000".parse().unwrap());
+        headers.insert(
+            SENDGRID_SIGNATURE_HEADER,
+            "MEUCID5cqJH2r9WCppiiNFrvTG3XJB8gUDEDRZaYoAofOM/rAiEA74PXqNfeagPgj5JRHgVi/8b5dK25NF6VqksahNYJ59A="
+                .parse()
+                .unwrap(),
+        );
+        assert!(check_sendgrid_signature(public_key, &headers, body, 1_754_700_000, 300).is_ok());
+        assert!(matches!(
+            check_sendgrid_signature(public_key, &headers, b"[]", 1_754_700_000, 300),
+            Err(WebhookError::BadSignature)
+        ));
+        assert!(matches!(
+            check_sendgrid_signature(public_key, &headers, body, 1_754_800_000, 300),
+            Err(WebhookError::Expired)
+        ));
+        assert!(matches!(
+            check_sendgrid_signature("bm90IGEga2V5", &headers, body, 1_754_700_000, 300),
+            Err(WebhookError::BadSignature)
+        ));
+
+        let events: Vec<Value> = serde_json::from_str(
+            r#"[{"email":"a@example.com","event":"bounce","type":"bounce","reason":"550 5.1.1 unknown",
+                "message_id":"<nl.0123456789abcdef0123456789abcdef.abc@n.coscup.org>"},
+               {"email":"b@example.com","event":"bounce","type":"blocked","reason":"421 try later"},
+               {"email":"c@example.com","event":"spamreport"},
+               {"email":"d@example.com","event":"delivered"}]"#,
+        )
+        .unwrap();
+        let parsed = parse_sendgrid(&events);
+        assert_eq!(
+            parsed.iter().map(|e| e.kind).collect::<Vec<_>>(),
+            vec![
+                EventKind::HardBounce,
+                EventKind::SoftBounce,
+                EventKind::Complaint
+            ]
+        );
+        assert!(parsed[0].message_id.is_some());
+        assert_eq!(parsed[1].detail, "421 try later");
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_apply_events(db: sqlx::PgPool) {
+        let app = crate::test_utils::TestApp::with_db(db).await;
+        let state = &app.state;
+        let (sub_id, ucode): (uuid::Uuid, String) = sqlx::query_as(
+            "INSERT INTO subscribers (email, name, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.com', 'A', 'secret', 'abc123', true, true) RETURNING id, ucode",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content, status) \
+             VALUES ('週報', 'weekly', '內容', 'sent') RETURNING id",
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO newsletter_sends (newsletter_id, subscriber_id, status, sent_at) \
+             VALUES ($1, $2, 'sent', NOW())",
+        )
+        .bind(newsletter_id)
+        .bind(sub_id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        let complaint = EmailEvent {
+            kind: EventKind::Complaint,
+            email: "A@example.com".to_string(),
+            message_id: None,
+            detail: "abuse".to_string(),
+        };
+        assert!(apply(state, "ses", &complaint).await.unwrap());
+        let (status, complained): (bool, bool) = sqlx::query_as(
+            "SELECT s.status, ns.complained_at IS NOT NULL FROM subscribers s \
+             JOIN newsletter_sends ns ON ns.subscriber_id = s.id WHERE s.id = $1",
+        )
+        .bind(sub_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert!(!status);
+        assert!(complained);
+        let recorded: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM email_complaints WHERE subscriber_id = $1 AND newsletter_id = $2",
+        )
+        .bind(sub_id)
+        .bind(newsletter_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(recorded, 1);
+
+        let bounce = EmailEvent {
+            kind: EventKind::HardBounce,
+            email: "changed@example.com".to_string(),
+            message_id: Some(crate::inbound::send_message_id(
+                newsletter_id,
+                &ucode,
+                "n.coscup.org",
+            )),
+            detail: "550 5.1.1 user unknown".to_string(),
+        };
+        assert!(apply(state, "mailgun", &bounce).await.unwrap());
+        let (bounced, bounce_type): (bool, Option<String>) = sqlx::query_as(
+            "SELECT s.bounced_at IS NOT NULL, ns.bounce_type FROM subscribers s \
+             JOIN newsletter_sends ns ON ns.subscriber_id = s.id WHERE s.id = $1",
+        )
+        .bind(sub_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert!(bounced);
+        assert_eq!(bounce_type.as_deref(), Some("hard"));
+
+        let unknown = EmailEvent {
+            email: "nobody@example.com".to_string(),
+            message_id: None,
+            ..bounce
+        };
+        assert!(!apply(state, "mailgun", &unknown).await.unwrap());
+
+        // Unsigned callbacks are refused
+        let resp = app
+            .send(
+                axum::http::Request::post("/webhooks/email-events")
+                    .header("content-type", "application/json")
+                    .body(axum::body::Body::from(
+                        r#"{"signature":{"timestamp":"1","token":"t","signature":"s"},"event-data":{}}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(resp.status, axum::http::StatusCode::UNAUTHORIZED);
+    }
+}
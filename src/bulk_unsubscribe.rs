@@ -0,0 +1,348 @@
+//! Unsubscribing a list of addresses in one go, e.g. spam complaints the
+//! relay provider forwarded by email rather than to the bounce webhook.
+//! Admins paste the addresses or upload a file on
+//! `/admin/subscribers/unsubscribe`; every line gets its own result.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::email_validation;
+use crate::AppState;
+
+/// Most lines handled in one operation.
+pub const MAX_ROWS: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Set the subscription off, as if they had unsubscribed themselves
+    Unsubscribe,
+    /// Stop mailing the address as if it had hard-bounced; the subscription
+    /// itself stays, and resubscribing on the manage page lifts it
+    Suppress,
+}
+
+impl Mode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "unsubscribe" => Some(Self::Unsubscribe),
+            "suppress" => Some(Self::Suppress),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unsubscribe => "unsubscribe",
+            Self::Suppress => "suppress",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Unsubscribed,
+    AlreadyUnsubscribed,
+    Suppressed,
+    AlreadySuppressed,
+    NotFound,
+    Invalid,
+    Duplicate,
+}
+
+impl Outcome {
+    pub const ALL: [Self; 7] = [
+        Self::Unsubscribed,
+        Self::AlreadyUnsubscribed,
+        Self::Suppressed,
+        Self::AlreadySuppressed,
+        Self::NotFound,
+        Self::Invalid,
+        Self::Duplicate,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unsubscribed => "unsubscribed",
+            Self::AlreadyUnsubscribed => "already_unsubscribed",
+            Self::Suppressed => "suppressed",
+            Self::AlreadySuppressed => "already_suppressed",
+            Self::NotFound => "not_found",
+            Self::Invalid => "invalid",
+            Self::Duplicate => "duplicate",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unsubscribed => "已取消訂閱",
+            Self::AlreadyUnsubscribed => "原本已取消訂閱",
+            Self::Suppressed => "已停止寄送",
+            Self::AlreadySuppressed => "原本已停止寄送",
+            Self::NotFound => "找不到訂閱者",
+            Self::Invalid => "Email 格式錯誤",
+            Self::Duplicate => "與前面的列重複",
+        }
+    }
+
+    /// Whether the row changed a subscriber.
+    pub fn changed(self) -> bool {
+        matches!(self, Self::Unsubscribed | Self::Suppressed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowResult {
+    /// Line in the pasted text or file, from 1
+    pub line: usize,
+    pub input: String,
+    pub outcome: Outcome,
+    pub label: &'static str,
+}
+
+/// The address on one line: the line itself, the first comma-, semicolon- or
+/// tab-separated field with an `@` (CSV exports), or the part inside `<>` of
+/// `Name <address>`. Surrounding quotes are dropped.
+fn address_in(line: &str) -> &str {
+    let field = line
+        .split([',', ';', '\t'])
+        .map(|f| f.trim().trim_matches('"').trim())
+        .find(|f| f.contains('@'))
+        .unwrap_or(line.trim());
+    match (field.rfind('<'), field.rfind('>')) {
+        (Some(start), Some(end)) if start < end => field[start + 1..end].trim(),
+        _ => field,
+    }
+}
+
+/// Lines to handle as (line number, address). Blank lines are skipped, and so
+/// is a first line without an `@`, taken as a CSV header.
+pub fn parse_lines(text: &str) -> Vec<(usize, String)> {
+    text.lines()
+        .enumerate()
+        .filter(|(i, line)| !line.trim().is_empty() && (*i > 0 || line.contains('@')))
+        .map(|(i, line)| (i + 1, address_in(line).to_string()))
+        .collect()
+}
+
+/// Unsubscribe or suppress every subscriber on the lines, matching Gmail
+/// spellings of the same mailbox too, and return a result per line.
+pub async fn apply(
+    state: &AppState,
+    lines: &[(usize, String)],
+    mode: Mode,
+) -> Result<Vec<RowResult>, sqlx::Error> {
+    let mut seen = HashSet::new();
+    let mut results = Vec::with_capacity(lines.len());
+    for (line, input) in lines {
+        let outcome = match email_validation::normalize(input) {
+            Err(_) => Outcome::Invalid,
+            Ok(normalized) if !seen.insert(normalized.canonical.clone()) => Outcome::Duplicate,
+            Ok(normalized) => apply_one(state, &normalized, mode).await?,
+        };
+        results.push(RowResult {
+            line: *line,
+            input: input.clone(),
+            outcome,
+            label: outcome.label(),
+        });
+    }
+    Ok(results)
+}
+
+async fn apply_one(
+    state: &AppState,
+    normalized: &email_validation::NormalizedEmail,
+    mode: Mode,
+) -> Result<Outcome, sqlx::Error> {
+    let matches = sqlx::query_as::<_, (uuid::Uuid, bool, bool)>(
+        "SELECT id, status, bounced_at IS NOT NULL FROM subscribers \
+         WHERE email = $1 OR canonical_email = $2",
+    )
+    .bind(&normalized.email)
+    .bind(&normalized.canonical)
+    .fetch_all(&state.db)
+    .await?;
+    if matches.is_empty() {
+        return Ok(Outcome::NotFound);
+    }
+
+    let mut changed = false;
+    for (id, status, bounced) in matches {
+        match mode {
+            Mode::Unsubscribe if status => {
+                let updated = sqlx::query(
+                    "UPDATE subscribers SET status = false, updated_at = NOW() \
+                     WHERE id = $1 AND status = true",
+                )
+                .bind(id)
+                .execute(&state.db)
+                .await?
+                .rows_affected();
+                if updated == 0 {
+                    continue;
+                }
+                changed = true;
+                sqlx::query("INSERT INTO unsubscribe_events (subscriber_id) VALUES ($1)")
+                    .bind(id)
+                    .execute(&state.db)
+                    .await?;
+                if let Err(e) =
+                    crate::crm_webhook::emit(state, id, crate::crm_webhook::Lifecycle::Unsubscribed)
+                        .await
+                {
+                    tracing::error!("Failed to queue CRM unsubscribe event for {id}: {e}");
+                }
+            }
+            Mode::Suppress if !bounced => {
+                crate::newsletter::mark_bounced(state, id).await;
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+    Ok(match (mode, changed) {
+        (Mode::Unsubscribe, true) => Outcome::Unsubscribed,
+        (Mode::Unsubscribe, false) => Outcome::AlreadyUnsubscribed,
+        (Mode::Suppress, true) => Outcome::Suppressed,
+        (Mode::Suppress, false) => Outcome::AlreadySuppressed,
+    })
+}
+
+/// How many rows had each outcome, leaving out outcomes no row had.
+pub fn counts(results: &[RowResult]) -> Vec<(Outcome, usize)> {
+    Outcome::ALL
+        .into_iter()
+        .map(|o| (o, results.iter().filter(|r| r.outcome == o).count()))
+        .filter(|(_, n)| *n > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines() {
+        let text = "email,name\n\
+                    someone@example.org,Someone\n\
+                    \n\
+                    \"Other Person\" <Other@Example.org>\n\
+                    \"x\",\"third@example.org\"\n\
+                    not an address\n";
+        assert_eq!(
+            parse_lines(text),
+            [
+                (2, "someone@example.org".to_string()),
+                (4, "Other@Example.org".to_string()),
+                (5, "third@example.org".to_string()),
+                (6, "not an address".to_string()),
+            ]
+        );
+        // Without a header the first line counts
+        assert_eq!(
+            parse_lines("a@example.org\r\nb@example.org"),
+            [
+                (1, "a@example.org".to_string()),
+                (2, "b@example.org".to_string())
+            ]
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_bulk_unsubscribe(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        for (email, canonical, status) in [
+            ("first.person@gmail.com", "firstperson@gmail.com", true),
+            ("gone@example.org", "gone@example.org", false),
+        ] {
+            sqlx::query(
+                "INSERT INTO subscribers (email, canonical_email, name, secret_code, ucode, status, verified_email) \
+                 VALUES ($1, $2, 'x', 'secret', $3, $4, true)",
+            )
+            .bind(email)
+            .bind(canonical)
+            .bind(&email[..8])
+            .bind(status)
+            .execute(&app.state.db)
+            .await
+            .unwrap();
+        }
+
+        app.login_as("admin@coscup.org").await;
+        let page = app
+            .send(multipart_request(&[
+                (
+                    "emails",
+                    "FirstPerson@gmail.com\ngone@example.org\nnobody@example.org\nbroken@\nfirst.person+x@gmail.com",
+                ),
+                ("mode", "unsubscribe"),
+                ("reason", "SES 轉寄的檢舉"),
+            ]))
+            .await;
+        assert_eq!(page.status, axum::http::StatusCode::OK);
+        for label in [
+            "已取消訂閱",
+            "原本已取消訂閱",
+            "找不到訂閱者",
+            "Email 格式錯誤",
+            "與前面的列重複",
+        ] {
+            assert!(page.body.contains(label), "{label}");
+        }
+        let status: bool = sqlx::query_scalar(
+            "SELECT status FROM subscribers WHERE email = 'first.person@gmail.com'",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert!(!status);
+        let unsubscribes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM unsubscribe_events")
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        assert_eq!(unsubscribes, 1);
+
+        // Suppressing marks the address as bounced, once
+        let lines = parse_lines("gone@example.org\n");
+        let results = apply(&app.state, &lines, Mode::Suppress).await.unwrap();
+        assert_eq!(results[0].outcome, Outcome::Suppressed);
+        let results = apply(&app.state, &lines, Mode::Suppress).await.unwrap();
+        assert_eq!(results[0].outcome, Outcome::AlreadySuppressed);
+
+        let details: serde_json::Value = sqlx::query_scalar(
+            "SELECT details FROM audit_log WHERE action = 'subscriber.bulk_unsubscribe'",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(details["rows"], 5);
+        assert_eq!(details["counts"]["unsubscribed"], 1);
+        assert_eq!(details["changed"][0], "FirstPerson@gmail.com");
+        assert_eq!(details["reason"], "SES 轉寄的檢舉");
+    }
+
+    fn multipart_request(fields: &[(&str, &str)]) -> axum::http::Request<axum::body::Body> {
+        use std::fmt::Write;
+
+        let boundary = "XBOUNDARYX";
+        let mut body = String::new();
+        for (name, value) in fields {
+            write!(
+                body,
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .unwrap();
+        }
+        write!(body, "--{boundary}--\r\n").unwrap();
+        axum::http::Request::post("/admin/subscribers/unsubscribe")
+            .header(
+                axum::http::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(axum::body::Body::from(body))
+            .unwrap()
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::rate_limit::{within_limit, RateLimitRule};
+
+/// How long a tarpitted client waits before being redirected.
+pub const TARPIT_DELAY: Duration = Duration::from_secs(3);
+
+/// Counters are pruned once there are this many keys.
+const PRUNE_AFTER_KEYS: usize = 50_000;
+
+/// Whether to record a click. Where the reader is sent is decided by the
+/// link's hash alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    /// The subscriber's clicks are flooding: don't record.
+    Ignore,
+    /// The client keeps sending bad hashes: delay it and don't record.
+    Tarpit,
+}
+
+/// A source that was just blocked, for the incident log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Incident {
+    /// `click_flood` (per ucode) or `hash_guessing` (per IP)
+    pub kind: &'static str,
+    pub subject: String,
+    pub hits: i64,
+}
+
+/// Fixed-window hit counter: (window start, hits in it, hits in the one before).
+#[derive(Debug, Clone, Copy)]
+struct Counter {
+    window_start: i64,
+    window_secs: i64,
+    current: i64,
+    previous: i64,
+}
+
+#[derive(Default)]
+struct GuardState {
+    counters: HashMap<String, Counter>,
+    /// Blocked key → Unix time the block ends
+    blocked: HashMap<String, i64>,
+}
+
+/// In-memory detection of abnormal traffic on `/r/c`: one ucode clicking
+/// far more than a person would, or one IP sending hash after hash that
+/// doesn't verify. Offenders are blocked for a while without touching the
+/// database. Per process, like the rest of the tracking path.
+#[derive(Clone)]
+pub struct ClickGuard {
+    state: Arc<Mutex<GuardState>>,
+    ucode_rule: RateLimitRule,
+    bad_hash_rule: RateLimitRule,
+    block_secs: i64,
+}
+
+impl ClickGuard {
+    pub fn new(ucode_rule: RateLimitRule, bad_hash_rule: RateLimitRule, block_secs: i64) -> Self {
+        Self {
+            state: Arc::default(),
+            ucode_rule,
+            bad_hash_rule,
+            block_secs,
+        }
+    }
+
+    pub fn verdict(&self, ucode: &str, ip: &str) -> Verdict {
+        self.verdict_at(ucode, ip, Utc::now().timestamp())
+    }
+
+    /// Count a verified click for `ucode`; returns the incident when this
+    /// click pushes it over the limit.
+    pub fn record_click(&self, ucode: &str) -> Option<Incident> {
+        self.hit_at(
+            "click_flood",
+            ucode,
+            self.ucode_rule,
+            Utc::now().timestamp(),
+        )
+    }
+
+    /// Count a click from `ip` whose hash didn't verify (or whose ucode
+    /// doesn't exist).
+    pub fn record_bad_hash(&self, ip: &str) -> Option<Incident> {
+        self.hit_at(
+            "hash_guessing",
+            ip,
+            self.bad_hash_rule,
+            Utc::now().timestamp(),
+        )
+    }
+
+    fn verdict_at(&self, ucode: &str, ip: &str, now: i64) -> Verdict {
+        let Ok(state) = self.state.lock() else {
+            return Verdict::Allow;
+        };
+        let is_blocked = |key: String| state.blocked.get(&key).is_some_and(|&until| until > now);
+        if is_blocked(key("hash_guessing", ip)) {
+            Verdict::Tarpit
+        } else if is_blocked(key("click_flood", ucode)) {
+            Verdict::Ignore
+        } else {
+            Verdict::Allow
+        }
+    }
+
+    fn hit_at(
+        &self,
+        kind: &'static str,
+        subject: &str,
+        rule: RateLimitRule,
+        now: i64,
+    ) -> Option<Incident> {
+        if rule.limit == 0 {
+            return None;
+        }
+        let mut state = self.state.lock().ok()?;
+        if state.counters.len() >= PRUNE_AFTER_KEYS {
+            prune(&mut state, now);
+        }
+
+        let key = key(kind, subject);
+        let elapsed = now.rem_euclid(rule.window_secs);
+        let window_start = now - elapsed;
+        let counter = state.counters.entry(key.clone()).or_insert(Counter {
+            window_start,
+            window_secs: rule.window_secs,
+            current: 0,
+            previous: 0,
+        });
+        if counter.window_start != window_start {
+            counter.previous = if counter.window_start == window_start - rule.window_secs {
+                counter.current
+            } else {
+                0
+            };
+            counter.current = 0;
+            counter.window_start = window_start;
+        }
+        counter.current += 1;
+        let (current, previous) = (counter.current, counter.previous);
+
+        if within_limit(rule, current, previous, elapsed) {
+            return None;
+        }
+        let until = now + self.block_secs;
+        let newly_blocked = state
+            .blocked
+            .insert(key, until)
+            .is_none_or(|previous_until| previous_until <= now);
+        newly_blocked.then(|| Incident {
+            kind,
+            subject: subject.to_string(),
+            hits: current + previous,
+        })
+    }
+}
+
+fn key(kind: &str, subject: &str) -> String {
+    format!("{kind}:{subject}")
+}
+
+/// Drop counters whose windows no longer count and blocks that have ended.
+fn prune(state: &mut GuardState, now: i64) {
+    state
+        .counters
+        .retain(|_, c| c.window_start + 2 * c.window_secs > now);
+    state.blocked.retain(|_, &mut until| until > now);
+}
+
+/// Log a newly blocked source. Failures are only logged; the block itself
+/// is in memory and already in effect.
+pub async fn log_incident(db: &PgPool, incident: &Incident, block_secs: i64) {
+    tracing::warn!(
+        "Tracking anomaly ({}) from {}: {} hits, blocked for {block_secs}s",
+        incident.kind,
+        incident.subject,
+        incident.hits
+    );
+    if let Err(e) = sqlx::query(
+        "INSERT INTO tracking_incidents (kind, subject, hits, blocked_until) \
+         VALUES ($1, $2, $3, NOW() + ($4::BIGINT * INTERVAL '1 second'))",
+    )
+    .bind(incident.kind)
+    .bind(&incident.subject)
+    .bind(incident.hits)
+    .bind(block_secs)
+    .execute(db)
+    .await
+    {
+        tracing::error!("Failed to log tracking incident: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: i64 = 1_700_000_040;
+
+    fn guard() -> ClickGuard {
+        ClickGuard::new(RateLimitRule::new(3, 60), RateLimitRule::new(2, 60), 600)
+    }
+
+    #[test]
+    fn test_click_flood_ignores_ucode() {
+        let guard = guard();
+        for i in 0..3 {
+            assert_eq!(
+                guard.hit_at("click_flood", "abc", guard.ucode_rule, T0 + i),
+                None
+            );
+        }
+        let incident = guard.hit_at("click_flood", "abc", guard.ucode_rule, T0 + 3);
+        assert_eq!(incident.map(|i| i.kind), Some("click_flood"));
+        // Logged once per block
+        assert_eq!(
+            guard.hit_at("click_flood", "abc", guard.ucode_rule, T0 + 4),
+            None
+        );
+
+        assert_eq!(
+            guard.verdict_at("abc", "203.0.113.5", T0 + 5),
+            Verdict::Ignore
+        );
+        assert_eq!(
+            guard.verdict_at("def", "203.0.113.5", T0 + 5),
+            Verdict::Allow
+        );
+        // The block ends
+        assert_eq!(
+            guard.verdict_at("abc", "203.0.113.5", T0 + 700),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn test_hash_guessing_tarpits_ip() {
+        let guard = guard();
+        assert_eq!(
+            guard.hit_at("hash_guessing", "203.0.113.5", guard.bad_hash_rule, T0),
+            None
+        );
+        assert_eq!(
+            guard.hit_at("hash_guessing", "203.0.113.5", guard.bad_hash_rule, T0 + 1),
+            None
+        );
+        assert!(guard
+            .hit_at("hash_guessing", "203.0.113.5", guard.bad_hash_rule, T0 + 2)
+            .is_some());
+        assert_eq!(
+            guard.verdict_at("abc", "203.0.113.5", T0 + 3),
+            Verdict::Tarpit
+        );
+        assert_eq!(
+            guard.verdict_at("abc", "203.0.113.6", T0 + 3),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn test_prune_and_disabled_rule() {
+        let guard = ClickGuard::new(RateLimitRule::new(0, 60), RateLimitRule::new(2, 60), 600);
+        for i in 0..10 {
+            assert_eq!(
+                guard.hit_at("click_flood", "abc", guard.ucode_rule, T0 + i),
+                None
+            );
+        }
+
+        guard.hit_at("hash_guessing", "203.0.113.5", guard.bad_hash_rule, T0);
+        let mut state = guard.state.lock().unwrap();
+        prune(&mut state, T0 + 60);
+        assert_eq!(state.counters.len(), 1);
+        prune(&mut state, T0 + 180);
+        assert!(state.counters.is_empty());
+    }
+}
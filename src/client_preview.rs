@@ -0,0 +1,369 @@
+//! Approximations of how mail clients mangle a message, for the preview page:
+//! a frame width (phone, the usual 600px email column, desktop) and quirks
+//! toggled on top of it. Done on the server so what the editor sees is the
+//! transformed HTML, not a browser's guess.
+//!
+//! - Outlook for Windows renders with Word: it shows what `<!--[if mso]>`
+//!   comments hide from everyone else, skips `<!--[if !mso]><!-->` blocks,
+//!   and ignores media queries and CSS such as `max-width`, `border-radius`
+//!   and `background-image`.
+//! - Some clients (Gmail with non-Google accounts, many webmail filters)
+//!   drop `<style>` blocks and keep only inline styles.
+
+use lol_html::html_content::ContentType;
+use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+use regex::Regex;
+use serde::Serialize;
+
+/// Preview frame width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frame {
+    /// Small phone, 320px
+    Mobile,
+    /// The 600px column most email templates are built for
+    Email,
+    /// As wide as the page
+    Desktop,
+}
+
+impl Frame {
+    /// From the `frame` query parameter; desktop when missing or unknown.
+    pub fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some("320" | "mobile") => Self::Mobile,
+            Some("600" | "email") => Self::Email,
+            _ => Self::Desktop,
+        }
+    }
+
+    /// Width in CSS pixels; `None` for the full width.
+    pub fn width(self) -> Option<u32> {
+        match self {
+            Self::Mobile => Some(320),
+            Self::Email => Some(600),
+            Self::Desktop => None,
+        }
+    }
+}
+
+/// Client behaviours to simulate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Quirks {
+    /// Outlook for Windows: conditional comments and its CSS support
+    pub outlook: bool,
+    /// Drop `<style>` blocks and stylesheet links
+    pub strip_style: bool,
+}
+
+/// CSS properties Outlook for Windows ignores.
+const OUTLOOK_UNSUPPORTED: [&str; 16] = [
+    "max-width",
+    "min-width",
+    "max-height",
+    "min-height",
+    "border-radius",
+    "box-shadow",
+    "background-image",
+    "background-size",
+    "background-position",
+    "position",
+    "float",
+    "opacity",
+    "transform",
+    "transition",
+    "animation",
+    "object-fit",
+];
+
+/// Layout values Outlook doesn't know for `display`.
+const OUTLOOK_UNSUPPORTED_DISPLAY: [&str; 4] = ["flex", "inline-flex", "grid", "inline-grid"];
+
+/// Apply `quirks` to a rendered email.
+pub fn simulate(html: &str, quirks: Quirks) -> String {
+    if !quirks.outlook && !quirks.strip_style {
+        return html.to_string();
+    }
+    let html = if quirks.outlook {
+        outlook_conditionals(html)
+    } else {
+        html.to_string()
+    };
+
+    let mut handlers = Vec::new();
+    if quirks.strip_style {
+        handlers.push(element!("style", |el| {
+            el.remove();
+            Ok(())
+        }));
+        handlers.push(element!("link[rel]", |el| {
+            let rel = el.get_attribute("rel").unwrap_or_default();
+            if rel.eq_ignore_ascii_case("stylesheet") {
+                el.remove();
+            }
+            Ok(())
+        }));
+    } else {
+        // Outlook: keep the block, minus what it would ignore
+        let mut style_text = String::new();
+        handlers.push(text!("style", move |chunk| {
+            style_text.push_str(chunk.as_str());
+            if chunk.last_in_text_node() {
+                let css = outlook_css(&strip_media_queries(&style_text));
+                chunk.replace(&css, ContentType::Html);
+                style_text.clear();
+            } else {
+                chunk.remove();
+            }
+            Ok(())
+        }));
+    }
+    if quirks.outlook {
+        handlers.push(element!("[style]", |el| {
+            let style = el.get_attribute("style").unwrap_or_default();
+            let kept = outlook_declarations(&style);
+            if kept.is_empty() {
+                el.remove_attribute("style");
+            } else if kept != style {
+                el.set_attribute("style", &kept)?;
+            }
+            Ok(())
+        }));
+    }
+
+    rewrite_str(
+        &html,
+        RewriteStrSettings {
+            element_content_handlers: handlers,
+            ..RewriteStrSettings::new()
+        },
+    )
+    .unwrap_or_else(|e| {
+        tracing::warn!("Client preview rewrite failed, showing the original: {e}");
+        html.clone()
+    })
+}
+
+/// Conditional comments as Outlook evaluates them: `mso` conditions are true,
+/// anything else (`!mso`, `IE`) false.
+fn outlook_conditionals(html: &str) -> String {
+    // <!--[if !mso]><!--> shown everywhere but Outlook <!--<![endif]-->
+    let revealed = Regex::new(r"(?is)<!--\[if\s+([^\]]*)\]><!-->(.*?)<!--<!\[endif\]-->")
+        .expect("valid regex");
+    let html = revealed.replace_all(html, |caps: &regex::Captures| {
+        if is_mso_condition(&caps[1]) {
+            caps[2].to_string()
+        } else {
+            String::new()
+        }
+    });
+    // <!--[if mso]> hidden from everything but Outlook <![endif]-->
+    let hidden =
+        Regex::new(r"(?is)<!--\[if\s+([^\]]*)\]>(.*?)<!\[endif\]-->").expect("valid regex");
+    hidden
+        .replace_all(&html, |caps: &regex::Captures| {
+            if is_mso_condition(&caps[1]) {
+                caps[2].to_string()
+            } else {
+                String::new()
+            }
+        })
+        .into_owned()
+}
+
+/// Whether a conditional comment's condition holds in Outlook, e.g.
+/// `mso`, `gte mso 9`, `mso | IE` or `(gte mso 9)|(IE)`.
+fn is_mso_condition(condition: &str) -> bool {
+    let condition = condition.to_ascii_lowercase();
+    condition.contains("mso") && !condition.trim_start().starts_with('!')
+}
+
+/// Whether Outlook ignores a `property: value` declaration.
+fn outlook_ignores(property: &str, value: &str) -> bool {
+    let property = property.trim().to_ascii_lowercase();
+    if property == "display" {
+        let value = value.trim().trim_end_matches("!important").trim();
+        return OUTLOOK_UNSUPPORTED_DISPLAY
+            .iter()
+            .any(|v| value.eq_ignore_ascii_case(v));
+    }
+    OUTLOOK_UNSUPPORTED.contains(&property.as_str())
+}
+
+/// An inline style without the declarations Outlook ignores.
+fn outlook_declarations(style: &str) -> String {
+    style
+        .split(';')
+        .filter(|declaration| {
+            let declaration = declaration.trim();
+            match declaration.split_once(':') {
+                Some((property, value)) => !outlook_ignores(property, value),
+                None => !declaration.is_empty(),
+            }
+        })
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A style sheet without the declarations Outlook ignores.
+fn outlook_css(css: &str) -> String {
+    let block = Regex::new(r"\{([^{}]*)\}").expect("valid regex");
+    block
+        .replace_all(css, |caps: &regex::Captures| {
+            format!("{{ {} }}", outlook_declarations(&caps[1]))
+        })
+        .into_owned()
+}
+
+/// A style sheet without its `@media` rules, which Outlook doesn't apply.
+fn strip_media_queries(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.to_ascii_lowercase().find("@media") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let Some(open) = after.find('{') else {
+            // Unterminated rule: drop the remainder
+            return out;
+        };
+        let mut depth = 0;
+        let mut end = after.len();
+        for (i, c) in after[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = open + i + 1;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        rest = &after[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMAIL: &str = r#"<html><head><style>
+.card { border-radius: 8px; color: #333; }
+@media (max-width: 600px) { .card { width: 100% !important; } }
+</style></head><body>
+<!--[if mso]><table width="600"><tr><td><![endif]-->
+<div class="card" style="max-width:600px; margin:0 auto; display:flex">內容</div>
+<!--[if mso]></td></tr></table><![endif]-->
+<!--[if !mso]><!--><img src="https://coscup.org/hero.gif" alt="動畫"><!--<![endif]-->
+<!--[if IE]><p>IE only</p><![endif]-->
+</body></html>"#;
+
+    #[test]
+    fn test_frame() {
+        assert_eq!(Frame::parse(Some("320")), Frame::Mobile);
+        assert_eq!(Frame::parse(Some("600")).width(), Some(600));
+        assert_eq!(Frame::parse(Some("huge")), Frame::Desktop);
+        assert_eq!(Frame::parse(None).width(), None);
+    }
+
+    #[test]
+    fn test_outlook() {
+        let html = simulate(
+            EMAIL,
+            Quirks {
+                outlook: true,
+                strip_style: false,
+            },
+        );
+        assert!(html.contains(r#"<table width="600"><tr><td>"#));
+        assert!(html.contains("</td></tr></table>"));
+        assert!(!html.contains("hero.gif"));
+        assert!(!html.contains("IE only"));
+        assert!(!html.contains("<!--[if"));
+        assert!(html.contains(r#"style="margin:0 auto""#));
+        assert!(html.contains(".card { color: #333 }"));
+        assert!(!html.contains("@media"));
+        assert!(!html.contains("border-radius"));
+    }
+
+    #[test]
+    fn test_strip_style() {
+        let html = simulate(
+            EMAIL,
+            Quirks {
+                outlook: false,
+                strip_style: true,
+            },
+        );
+        assert!(!html.contains("<style"));
+        assert!(html.contains("max-width:600px"));
+        // Other clients keep hiding the Outlook-only markup
+        assert!(html.contains("<!--[if mso]>"));
+        assert_eq!(simulate(EMAIL, Quirks::default()), EMAIL);
+    }
+
+    #[test]
+    fn test_css_helpers() {
+        assert_eq!(
+            outlook_declarations("display: block; Max-Width: 100px;color:red;"),
+            "display: block; color:red"
+        );
+        assert_eq!(outlook_declarations("display:inline-grid !important"), "");
+        assert_eq!(
+            strip_media_queries("a{color:red}@media screen{a{color:blue}b{x:y}}p{margin:0}"),
+            "a{color:red}p{margin:0}"
+        );
+        assert_eq!(strip_media_queries("a{} @MEDIA x { b{"), "a{} ");
+        assert!(is_mso_condition("(gte mso 9)|(IE)"));
+        assert!(!is_mso_condition("!mso"));
+        assert!(!is_mso_condition("IE"));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_preview_page(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let template_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletter_templates (name, slug, description, html_body) \
+             VALUES ('T', 'client-preview', '', \
+             '<style>p { color: red; }</style><!--[if mso]><b>OUTLOOK-ONLY</b><![endif]-->\
+             {{ content }}<a href=\"{{ unsubscribe_url }}\">x</a>') RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content, template_id) \
+             VALUES ('預覽', 'preview', '內容', $1) RETURNING id",
+        )
+        .bind(template_id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        // The email is escaped once in the srcdoc, so a style element shows as &lt;style
+        let srcdoc = |body: &str| body[body.find("srcdoc=").unwrap()..].to_string();
+        let plain = app.get(&format!("/admin/newsletters/{id}/preview")).await;
+        assert_eq!(plain.status, axum::http::StatusCode::OK);
+        assert!(!plain.body.contains("style=\"width:"));
+        assert!(srcdoc(&plain.body).contains("&lt;style"));
+        assert!(srcdoc(&plain.body).contains("[if mso]&gt;&lt;b&gt;OUTLOOK-ONLY"));
+        let framed = app
+            .get(&format!(
+                "/admin/newsletters/{id}/preview?frame=320&outlook=1&no_style=1"
+            ))
+            .await;
+        assert_eq!(framed.status, axum::http::StatusCode::OK);
+        assert!(framed.body.contains("style=\"width:320px;\""));
+        assert!(framed.body.contains("value=\"320\" selected"));
+        assert!(!srcdoc(&framed.body).contains("&lt;style"));
+        assert!(srcdoc(&framed.body).contains("&lt;b&gt;OUTLOOK-ONLY"));
+        assert!(!srcdoc(&framed.body).contains("[if mso]"));
+    }
+}
@@ -1,14 +1,53 @@
 use std::env;
 
+use chrono::NaiveDate;
+
+use crate::rate_limit::RateLimitRule;
+
 #[derive(Clone, Debug)]
 pub struct AppConfig {
     pub database_url: String,
+    /// Database queries slower than this are logged with their SQL; 0 disables.
+    pub slow_query_ms: u64,
     pub host: String,
     pub port: u16,
+    /// PEM certificate chain and key; when both are set `PORT` serves HTTPS.
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    /// Plain HTTP port redirecting to HTTPS (TLS only), e.g. 80.
+    pub http_redirect_port: Option<u16>,
+    /// `Strict-Transport-Security` max-age sent over HTTPS; 0 disables it.
+    pub hsts_max_age_secs: u64,
     pub base_url: String,
     pub admin_emails: Vec<String>,
+    /// Bearer tokens accepted by the `/api/v1` endpoints; empty disables the API.
+    pub api_tokens: Vec<String>,
+    /// Secrets `/api/v1/inbound` deliveries must be signed with (see
+    /// `webhook`); empty = not signed, the API token alone applies.
+    pub inbound_webhook_secrets: Vec<String>,
+    /// How far a signed webhook's timestamp may be from now.
+    pub webhook_tolerance_secs: i64,
+    /// SNS topics whose SES bounce and complaint notifications
+    /// `/webhooks/email-events` accepts; empty refuses SES notifications.
+    pub ses_sns_topic_arns: Vec<String>,
+    /// Mailgun's HTTP webhook signing key; unset refuses Mailgun events.
+    pub mailgun_webhook_signing_key: Option<String>,
+    /// Base64 ECDSA public key of the `SendGrid` signed Event Webhook; unset
+    /// refuses `SendGrid` events.
+    pub sendgrid_webhook_public_key: Option<String>,
     pub turnstile_secret: String,
     pub turnstile_sitekey: String,
+    /// How mail goes out: `smtp` (the default), `sendgrid` or `mailgun`. The
+    /// HTTP providers still send from `SMTP_FROM_EMAIL` at the
+    /// `SMTP_RATE_LIMIT_MS` pace; sending identities always use SMTP.
+    pub email_provider: String,
+    pub sendgrid_api_key: Option<String>,
+    pub mailgun_api_key: Option<String>,
+    /// Sending domain registered with Mailgun; defaults to the domain of
+    /// `SMTP_FROM_EMAIL`.
+    pub mailgun_domain: Option<String>,
+    /// `https://api.eu.mailgun.net` for domains in Mailgun's EU region
+    pub mailgun_api_base: String,
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_username: Option<String>,
@@ -16,14 +55,134 @@ pub struct AppConfig {
     pub smtp_tls: bool,
     pub smtp_from_email: String,
     pub smtp_rate_limit_ms: u64,
+    /// Longest delay between sends while backing off from relay rate limiting
+    pub smtp_rate_limit_max_ms: u64,
+    /// Recipients per send batch; a pause takes effect, and progress is
+    /// checkpointed, between batches.
+    pub send_batch_size: usize,
+    /// Emails of a send delivered at once; `SMTP_RATE_LIMIT_MS` still spaces
+    /// their starts.
+    pub smtp_concurrency: usize,
+    /// Extra sending identities for newsletters as
+    /// `name|from_email|host|port|username|password` (see `email::parse_sending_identities`).
+    pub sending_identities: String,
+    /// DKIM private key (RSA in PKCS#1 PEM, or a base64 Ed25519 seed) for
+    /// signing mail ourselves when the relay does not.
+    pub dkim_private_key_path: Option<String>,
+    pub dkim_selector: Option<String>,
+    /// Signing domain (`d=`); defaults to the domain of `SMTP_FROM_EMAIL`.
+    pub dkim_domain: Option<String>,
+    pub soft_bounce_threshold: i32,
+    /// Refuse role addresses (`info@`, `noreply@`, ...) on the subscribe
+    /// form and in imports; admins can still import them explicitly.
+    pub block_role_accounts: bool,
+    /// Max newsletters a subscriber receives per window; 0 disables capping.
+    pub frequency_cap_max: i64,
+    pub frequency_cap_window_days: i32,
+    pub list_id: Option<String>,
+    pub precedence_bulk: bool,
+    pub feedback_id_sender: Option<String>,
+    /// Physical mailing address shown in every newsletter's footer; sends are
+    /// refused without one (CAN-SPAM).
+    pub postal_address: Option<String>,
+    /// Extra legal text shown under the postal address.
+    pub legal_footer: Option<String>,
+    /// First and last day of the event, for the `{{countdown}}` and
+    /// `{{event_dates}}` shortcodes.
+    pub event_start_date: Option<NaiveDate>,
+    pub event_end_date: Option<NaiveDate>,
     pub newsletter_scheduler_interval_secs: u64,
+    /// A scheduled send found overdue by more than this (e.g. after downtime)
+    /// waits for an admin instead of going out; 0 holds every late send.
+    pub schedule_catch_up_hours: i64,
+    /// Sends to more recipients than this need a second admin's approval; 0 disables.
+    pub send_approval_threshold: i64,
+    /// Clicks this soon after delivery are flagged as link-scanner clicks; 0 disables.
+    pub scanner_click_window_secs: i64,
+    /// How often the cached dashboard/stats aggregates are refreshed.
+    pub stats_refresh_interval_secs: u64,
     pub yourls_api_url: Option<String>,
     pub yourls_signature: Option<String>,
+    /// Extra short-link domains as `domain|api_url|signature` (see `shorturl::parse_short_domains`).
+    pub short_domains: String,
+    /// How often YOURLS click counts are copied into the per-link stats; 0 disables.
+    pub short_link_sync_interval_secs: u64,
+    /// Newsletters whose short-link clicks keep syncing after sending finished.
+    pub short_link_sync_days: i32,
     pub upload_dir: String,
     pub max_upload_size_bytes: usize,
+    /// Newsletter PDF attachments; kept out of `upload_dir` so they are only
+    /// served (and counted) through `/attachments/{id}` once sent.
+    pub attachment_dir: String,
+    /// Max total size of a newsletter's attachments, since every recipient
+    /// gets a copy.
+    pub max_attachment_bytes: usize,
+    /// Signing key for the newsletter image proxy; unset disables proxying.
+    pub image_proxy_key: Option<String>,
+    pub image_proxy_cache_dir: String,
+    /// Registration exports to sync, as `segment|url` pairs (see `registration::parse_sources`).
+    pub registration_sync_sources: String,
+    pub registration_sync_token: Option<String>,
+    pub registration_sync_interval_secs: u64,
+    /// How often the sheets on `/admin/subscribers/sheets` are synced.
+    pub sheet_sync_interval_secs: u64,
+    /// Endpoint and signing secret for subscriber lifecycle webhooks; both must be set.
+    pub crm_webhook_url: Option<String>,
+    pub crm_webhook_secret: Option<String>,
+    /// S3-compatible bucket for nightly exports; exports are disabled unless fully configured.
+    pub s3_endpoint: Option<String>,
+    pub s3_region: String,
+    pub s3_bucket: Option<String>,
+    pub s3_access_key_id: Option<String>,
+    pub s3_secret_access_key: Option<String>,
+    pub export_prefix: String,
+    pub export_retention_days: i64,
+    pub export_hour: u32,
+    /// Admin sessions expire after this much inactivity...
+    pub admin_session_idle_hours: i64,
+    /// ...or this many days when "remember this device" was chosen; 0 hides the option.
+    pub admin_session_remember_days: i64,
+    /// Admins without a sign-in for this many months are flagged as dormant; 0 disables.
+    pub dormant_admin_months: u32,
+    /// OIDC provider for admin single sign-on, e.g. `https://accounts.google.com`;
+    /// enabled when the issuer, client ID and secret are all set.
+    pub oidc_issuer: Option<String>,
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    /// Days to keep subscribe/login logs, and used or expired tokens and sessions.
+    pub log_retention_days: i64,
+    pub token_retention_days: i64,
+    pub housekeeping_interval_secs: u64,
+    /// Days before web sign-ups that never verified are deleted; 0 keeps them.
+    pub unverified_prune_days: i64,
+    /// Days a manage link in a newly sent email stays valid.
+    pub manage_link_ttl_days: i64,
+    /// Last day the old never-expiring manage links are accepted; `None` keeps them working.
+    pub legacy_manage_links_until: Option<NaiveDate>,
+    /// Per-endpoint rate limits (see `RateLimitRule::parse`). These, the SMTP rate
+    /// limits, the scheduler interval and the admin seed list can be reloaded at
+    /// runtime; read the current values from `AppState::live`.
+    pub rate_limit_subscribe_email: RateLimitRule,
+    pub rate_limit_subscribe_ip: RateLimitRule,
+    pub rate_limit_login_email: RateLimitRule,
+    pub rate_limit_login_ip: RateLimitRule,
+    /// Verified clicks per ucode, and clicks with a bad hash per IP, on `/r/c`
+    /// before the source is blocked for `click_block_secs`.
+    pub click_limit_ucode: RateLimitRule,
+    pub click_limit_bad_hash_ip: RateLimitRule,
+    pub click_block_secs: i64,
+    /// Views of `/share/{token}` share links per IP.
+    pub rate_limit_share_ip: RateLimitRule,
+    /// Destination domains `/r/c` won't redirect to and newsletters can't
+    /// link to, subdomains included, on top of the ones blocked on
+    /// `/admin/link-domains`.
+    pub blocked_link_domains: Vec<String>,
+    /// Where audit log entries are mirrored to (see `audit::AuditSink::parse`).
+    pub audit_sink: Option<String>,
 }
 
 impl AppConfig {
+    #[allow(clippy::too_many_lines)]
     pub fn from_env() -> Result<Self, env::VarError> {
         let admin_emails_str = env::var("ADMIN_EMAILS")?;
         let admin_emails = admin_emails_str
@@ -31,18 +190,80 @@ impl AppConfig {
             .map(|s| s.trim().to_lowercase())
             .filter(|s| !s.is_empty())
             .collect();
+        let api_tokens = env::var("API_TOKENS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let blocked_link_domains = env::var("BLOCKED_LINK_DOMAINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().trim_start_matches('.').to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let inbound_webhook_secrets = env::var("INBOUND_WEBHOOK_SECRETS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let ses_sns_topic_arns = env::var("SES_SNS_TOPIC_ARNS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
 
         Ok(Self {
             database_url: env::var("DATABASE_URL")?,
+            slow_query_ms: env::var("SLOW_QUERY_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .unwrap_or(500),
             host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
             port: env::var("PORT")
                 .unwrap_or_else(|_| "8080".to_string())
                 .parse()
                 .unwrap_or(8080),
+            tls_cert_path: env::var("TLS_CERT_PATH").ok().filter(|s| !s.is_empty()),
+            tls_key_path: env::var("TLS_KEY_PATH").ok().filter(|s| !s.is_empty()),
+            http_redirect_port: env::var("HTTP_REDIRECT_PORT")
+                .ok()
+                .and_then(|s| s.trim().parse().ok()),
+            hsts_max_age_secs: env::var("HSTS_MAX_AGE_SECS")
+                .unwrap_or_else(|_| "31536000".to_string())
+                .parse()
+                .unwrap_or(31_536_000),
             base_url: env::var("BASE_URL")?,
             admin_emails,
+            api_tokens,
+            inbound_webhook_secrets,
+            webhook_tolerance_secs: env::var("WEBHOOK_TOLERANCE_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .unwrap_or(300),
+            ses_sns_topic_arns,
+            mailgun_webhook_signing_key: env::var("MAILGUN_WEBHOOK_SIGNING_KEY")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            sendgrid_webhook_public_key: env::var("SENDGRID_WEBHOOK_PUBLIC_KEY")
+                .ok()
+                .filter(|s| !s.is_empty()),
             turnstile_secret: env::var("TURNSTILE_SECRET")?,
             turnstile_sitekey: env::var("TURNSTILE_SITEKEY")?,
+            email_provider: env::var("EMAIL_PROVIDER")
+                .map(|s| s.trim().to_lowercase())
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "smtp".to_string()),
+            sendgrid_api_key: env::var("SENDGRID_API_KEY").ok().filter(|s| !s.is_empty()),
+            mailgun_api_key: env::var("MAILGUN_API_KEY").ok().filter(|s| !s.is_empty()),
+            mailgun_domain: env::var("MAILGUN_DOMAIN").ok().filter(|s| !s.is_empty()),
+            mailgun_api_base: env::var("MAILGUN_API_BASE")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "https://api.mailgun.net".to_string()),
             smtp_host: env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string()),
             smtp_port: env::var("SMTP_PORT")
                 .unwrap_or_else(|_| "1025".to_string())
@@ -60,39 +281,276 @@ impl AppConfig {
                 .unwrap_or_else(|_| "100".to_string())
                 .parse()
                 .unwrap_or(100),
+            smtp_rate_limit_max_ms: env::var("SMTP_RATE_LIMIT_MAX_MS")
+                .unwrap_or_else(|_| "30000".to_string())
+                .parse()
+                .unwrap_or(30000),
+            send_batch_size: env::var("SEND_BATCH_SIZE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(50),
+            smtp_concurrency: env::var("SMTP_CONCURRENCY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(1),
+            sending_identities: env::var("SENDING_IDENTITIES").unwrap_or_default(),
+            dkim_private_key_path: env::var("DKIM_PRIVATE_KEY_PATH")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            dkim_selector: env::var("DKIM_SELECTOR").ok().filter(|s| !s.is_empty()),
+            dkim_domain: env::var("DKIM_DOMAIN").ok().filter(|s| !s.is_empty()),
+            soft_bounce_threshold: env::var("SOFT_BOUNCE_THRESHOLD")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            block_role_accounts: env::var("BLOCK_ROLE_ACCOUNTS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            frequency_cap_max: env::var("FREQUENCY_CAP_MAX")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .unwrap_or(0),
+            frequency_cap_window_days: env::var("FREQUENCY_CAP_WINDOW_DAYS")
+                .unwrap_or_else(|_| "7".to_string())
+                .parse()
+                .unwrap_or(7),
+            list_id: env::var("LIST_ID").map_or_else(
+                |_| Some("COSCUP Newsletter <newsletter.coscup.org>".to_string()),
+                |s| Some(s).filter(|s| !s.is_empty()),
+            ),
+            precedence_bulk: env::var("PRECEDENCE_BULK")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            feedback_id_sender: env::var("FEEDBACK_ID_SENDER").map_or_else(
+                |_| Some("coscup".to_string()),
+                |s| Some(s).filter(|s| !s.is_empty()),
+            ),
+            postal_address: env::var("ORG_POSTAL_ADDRESS")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            legal_footer: env::var("LEGAL_FOOTER")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            event_start_date: env::var("EVENT_START_DATE")
+                .ok()
+                .and_then(|s| NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()),
+            event_end_date: env::var("EVENT_END_DATE")
+                .ok()
+                .and_then(|s| NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()),
             newsletter_scheduler_interval_secs: env::var("NEWSLETTER_SCHEDULER_INTERVAL_SECS")
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .unwrap_or(30),
+            schedule_catch_up_hours: env::var("SCHEDULE_CATCH_UP_HOURS")
+                .unwrap_or_else(|_| "6".to_string())
+                .parse()
+                .unwrap_or(6),
+            send_approval_threshold: env::var("SEND_APPROVAL_THRESHOLD")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .unwrap_or(0),
+            scanner_click_window_secs: env::var("SCANNER_CLICK_WINDOW_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .unwrap_or(10),
+            stats_refresh_interval_secs: env::var("STATS_REFRESH_INTERVAL_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .unwrap_or(300),
             yourls_api_url: env::var("YOURLS_API_URL").ok().filter(|s| !s.is_empty()),
             yourls_signature: env::var("YOURLS_SIGNATURE").ok().filter(|s| !s.is_empty()),
+            short_domains: env::var("SHORT_DOMAINS").unwrap_or_default(),
+            short_link_sync_interval_secs: env::var("SHORT_LINK_SYNC_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
+            short_link_sync_days: env::var("SHORT_LINK_SYNC_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
             upload_dir: env::var("UPLOAD_DIR").unwrap_or_else(|_| "uploads".to_string()),
             max_upload_size_bytes: env::var("MAX_UPLOAD_SIZE_BYTES")
                 .unwrap_or_else(|_| "5242880".to_string())
                 .parse()
                 .unwrap_or(5_242_880),
+            attachment_dir: env::var("ATTACHMENT_DIR")
+                .unwrap_or_else(|_| "attachments".to_string()),
+            max_attachment_bytes: env::var("MAX_ATTACHMENT_BYTES")
+                .unwrap_or_else(|_| "3145728".to_string())
+                .parse()
+                .unwrap_or(3_145_728),
+            image_proxy_key: env::var("IMAGE_PROXY_KEY").ok().filter(|s| !s.is_empty()),
+            image_proxy_cache_dir: env::var("IMAGE_PROXY_CACHE_DIR")
+                .unwrap_or_else(|_| "image_cache".to_string()),
+            registration_sync_sources: env::var("REGISTRATION_SYNC_SOURCES").unwrap_or_default(),
+            registration_sync_token: env::var("REGISTRATION_SYNC_TOKEN")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            registration_sync_interval_secs: env::var("REGISTRATION_SYNC_INTERVAL_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .unwrap_or(86400),
+            sheet_sync_interval_secs: env::var("SHEET_SYNC_INTERVAL_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .unwrap_or(900),
+            crm_webhook_url: env::var("CRM_WEBHOOK_URL").ok().filter(|s| !s.is_empty()),
+            crm_webhook_secret: env::var("CRM_WEBHOOK_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            s3_endpoint: env::var("S3_ENDPOINT").ok().filter(|s| !s.is_empty()),
+            s3_region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            s3_bucket: env::var("S3_BUCKET").ok().filter(|s| !s.is_empty()),
+            s3_access_key_id: env::var("S3_ACCESS_KEY_ID").ok().filter(|s| !s.is_empty()),
+            s3_secret_access_key: env::var("S3_SECRET_ACCESS_KEY")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            export_prefix: env::var("EXPORT_PREFIX")
+                .unwrap_or_else(|_| "newsletter-exports/".to_string()),
+            export_retention_days: env::var("EXPORT_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            export_hour: env::var("EXPORT_HOUR")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            admin_session_idle_hours: env::var("ADMIN_SESSION_IDLE_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap_or(24),
+            admin_session_remember_days: env::var("ADMIN_SESSION_REMEMBER_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            dormant_admin_months: env::var("DORMANT_ADMIN_MONTHS")
+                .unwrap_or_else(|_| "6".to_string())
+                .parse()
+                .unwrap_or(6),
+            oidc_issuer: env::var("OIDC_ISSUER").ok().filter(|s| !s.is_empty()),
+            oidc_client_id: env::var("OIDC_CLIENT_ID").ok().filter(|s| !s.is_empty()),
+            oidc_client_secret: env::var("OIDC_CLIENT_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            log_retention_days: env::var("LOG_RETENTION_DAYS")
+                .unwrap_or_else(|_| "90".to_string())
+                .parse()
+                .unwrap_or(90),
+            token_retention_days: env::var("TOKEN_RETENTION_DAYS")
+                .unwrap_or_else(|_| "7".to_string())
+                .parse()
+                .unwrap_or(7),
+            housekeeping_interval_secs: env::var("HOUSEKEEPING_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
+            unverified_prune_days: env::var("UNVERIFIED_PRUNE_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            manage_link_ttl_days: env::var("MANAGE_LINK_TTL_DAYS")
+                .unwrap_or_else(|_| "180".to_string())
+                .parse()
+                .unwrap_or(180),
+            legacy_manage_links_until: env::var("LEGACY_MANAGE_LINKS_UNTIL")
+                .ok()
+                .and_then(|s| NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()),
+            rate_limit_subscribe_email: rate_limit_rule(
+                "RATE_LIMIT_SUBSCRIBE_EMAIL",
+                RateLimitRule::new(5, 86400),
+            ),
+            rate_limit_subscribe_ip: rate_limit_rule(
+                "RATE_LIMIT_SUBSCRIBE_IP",
+                RateLimitRule::new(10, 86400),
+            ),
+            rate_limit_login_email: rate_limit_rule(
+                "RATE_LIMIT_LOGIN_EMAIL",
+                RateLimitRule::new(5, 86400),
+            ),
+            rate_limit_login_ip: rate_limit_rule(
+                "RATE_LIMIT_LOGIN_IP",
+                RateLimitRule::new(10, 86400),
+            ),
+            click_limit_ucode: rate_limit_rule("CLICK_LIMIT_UCODE", RateLimitRule::new(100, 3600)),
+            click_limit_bad_hash_ip: rate_limit_rule(
+                "CLICK_LIMIT_BAD_HASH_IP",
+                RateLimitRule::new(20, 600),
+            ),
+            click_block_secs: env::var("CLICK_BLOCK_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
+            rate_limit_share_ip: rate_limit_rule(
+                "RATE_LIMIT_SHARE_IP",
+                RateLimitRule::new(60, 3600),
+            ),
+            blocked_link_domains,
+            audit_sink: env::var("AUDIT_SINK").ok().filter(|s| !s.is_empty()),
         })
     }
 
     pub fn is_admin_email(&self, email: &str) -> bool {
         self.admin_emails.contains(&email.to_lowercase())
     }
+
+    pub fn is_api_token(&self, token: &str) -> bool {
+        self.api_tokens
+            .iter()
+            .any(|t| crate::security::constant_time_eq(t, token))
+    }
+
+    pub fn inbound_webhook(&self) -> crate::webhook::Verifier {
+        crate::webhook::Verifier {
+            source: "inbound",
+            secrets: self.inbound_webhook_secrets.clone(),
+            tolerance_secs: self.webhook_tolerance_secs,
+        }
+    }
+}
+
+fn rate_limit_rule(var: &str, default: RateLimitRule) -> RateLimitRule {
+    env::var(var)
+        .ok()
+        .and_then(|s| RateLimitRule::parse(&s))
+        .unwrap_or(default)
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
 
-    #[test]
-    fn test_is_admin_email() {
-        let config = AppConfig {
+    #[allow(clippy::too_many_lines)]
+    pub fn test_config() -> AppConfig {
+        AppConfig {
             database_url: String::new(),
+            slow_query_ms: 500,
             host: "0.0.0.0".to_string(),
             port: 8080,
+            tls_cert_path: None,
+            tls_key_path: None,
+            http_redirect_port: None,
+            hsts_max_age_secs: 31_536_000,
             base_url: "http://localhost:8080".to_string(),
             admin_emails: vec!["admin@coscup.org".to_string()],
+            api_tokens: vec![],
+            inbound_webhook_secrets: vec![],
+            webhook_tolerance_secs: 300,
+            ses_sns_topic_arns: vec![],
+            mailgun_webhook_signing_key: None,
+            sendgrid_webhook_public_key: None,
             turnstile_secret: String::new(),
             turnstile_sitekey: String::new(),
+            email_provider: "smtp".to_string(),
+            sendgrid_api_key: None,
+            mailgun_api_key: None,
+            mailgun_domain: None,
+            mailgun_api_base: "https://api.mailgun.net".to_string(),
             smtp_host: "localhost".to_string(),
             smtp_port: 1025,
             smtp_username: None,
@@ -100,15 +558,97 @@ mod tests {
             smtp_tls: false,
             smtp_from_email: "test@example.com".to_string(),
             smtp_rate_limit_ms: 100,
+            smtp_rate_limit_max_ms: 30000,
+            send_batch_size: 50,
+            smtp_concurrency: 1,
+            sending_identities: String::new(),
+            dkim_private_key_path: None,
+            dkim_selector: None,
+            dkim_domain: None,
+            soft_bounce_threshold: 3,
+            block_role_accounts: true,
+            frequency_cap_max: 0,
+            frequency_cap_window_days: 7,
+            list_id: None,
+            precedence_bulk: true,
+            feedback_id_sender: None,
+            postal_address: None,
+            legal_footer: None,
+            event_start_date: None,
+            event_end_date: None,
             newsletter_scheduler_interval_secs: 30,
+            schedule_catch_up_hours: 6,
+            send_approval_threshold: 0,
+            scanner_click_window_secs: 10,
+            stats_refresh_interval_secs: 300,
             yourls_api_url: None,
             yourls_signature: None,
+            short_domains: String::new(),
+            short_link_sync_interval_secs: 3600,
+            short_link_sync_days: 30,
             upload_dir: "uploads".to_string(),
             max_upload_size_bytes: 5_242_880,
-        };
+            attachment_dir: "attachments".to_string(),
+            max_attachment_bytes: 3_145_728,
+            image_proxy_key: None,
+            image_proxy_cache_dir: "image_cache".to_string(),
+            registration_sync_sources: String::new(),
+            registration_sync_token: None,
+            registration_sync_interval_secs: 86400,
+            sheet_sync_interval_secs: 900,
+            crm_webhook_url: None,
+            crm_webhook_secret: None,
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+            s3_bucket: None,
+            s3_access_key_id: None,
+            s3_secret_access_key: None,
+            export_prefix: "newsletter-exports/".to_string(),
+            export_retention_days: 30,
+            export_hour: 3,
+            admin_session_idle_hours: 24,
+            admin_session_remember_days: 30,
+            dormant_admin_months: 6,
+            oidc_issuer: None,
+            oidc_client_id: None,
+            oidc_client_secret: None,
+            log_retention_days: 90,
+            token_retention_days: 7,
+            housekeeping_interval_secs: 3600,
+            unverified_prune_days: 30,
+            manage_link_ttl_days: 180,
+            legacy_manage_links_until: None,
+            rate_limit_subscribe_email: RateLimitRule::new(5, 86400),
+            rate_limit_subscribe_ip: RateLimitRule::new(10, 86400),
+            rate_limit_login_email: RateLimitRule::new(5, 86400),
+            rate_limit_login_ip: RateLimitRule::new(10, 86400),
+            click_limit_ucode: RateLimitRule::new(100, 3600),
+            click_limit_bad_hash_ip: RateLimitRule::new(20, 600),
+            click_block_secs: 3600,
+            rate_limit_share_ip: RateLimitRule::new(60, 3600),
+            blocked_link_domains: Vec::new(),
+            audit_sink: None,
+        }
+    }
+
+    #[test]
+    fn test_is_admin_email() {
+        let config = test_config();
 
         assert!(config.is_admin_email("admin@coscup.org"));
         assert!(config.is_admin_email("ADMIN@COSCUP.ORG"));
         assert!(!config.is_admin_email("other@coscup.org"));
+        assert!(!config.is_api_token(""));
+    }
+
+    #[test]
+    fn test_is_api_token() {
+        let config = AppConfig {
+            api_tokens: vec!["tok-a".to_string(), "tok-b".to_string()],
+            ..test_config()
+        };
+        assert!(config.is_api_token("tok-b"));
+        assert!(!config.is_api_token("tok-c"));
+        assert!(!config.is_api_token(""));
     }
 }
@@ -0,0 +1,84 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Version of the consent wording on the subscribe page. Bump it whenever
+/// that text changes so each record points at what the subscriber agreed to.
+pub const CONSENT_VERSION: &str = "2026-10";
+
+/// One recorded consent step (`subscribe` when the form is submitted,
+/// `verify` when the address is confirmed).
+#[derive(Debug, Clone, Serialize)]
+pub struct Consent {
+    pub action: String,
+    /// `CONSENT_VERSION` at the time
+    pub version: String,
+    pub source: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Record a consent step for the subscriber.
+pub async fn record(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    action: &str,
+    source: &str,
+    ip: Option<IpAddr>,
+    user_agent: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO consents (subscriber_id, action, consent_version, source, ip_address, user_agent) \
+         VALUES ($1, $2, $3, $4, $5::inet, $6)",
+    )
+    .bind(subscriber_id)
+    .bind(action)
+    .bind(CONSENT_VERSION)
+    .bind(source)
+    .bind(ip.map(|ip| ip.to_string()))
+    .bind(user_agent)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// All consent records for a subscriber, oldest first.
+pub async fn for_subscriber(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+) -> Result<Vec<Consent>, sqlx::Error> {
+    #[allow(clippy::type_complexity)]
+    let rows = sqlx::query_as::<
+        _,
+        (
+            String,
+            String,
+            String,
+            Option<String>,
+            Option<String>,
+            DateTime<Utc>,
+        ),
+    >(
+        "SELECT action, consent_version, source, host(ip_address), user_agent, created_at \
+         FROM consents WHERE subscriber_id = $1 ORDER BY created_at",
+    )
+    .bind(subscriber_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(action, version, source, ip_address, user_agent, created_at)| Consent {
+                action,
+                version,
+                source,
+                ip_address,
+                user_agent,
+                created_at,
+            },
+        )
+        .collect())
+}
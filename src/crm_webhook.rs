@@ -0,0 +1,340 @@
+//! Subscriber lifecycle webhooks for COSCUP's volunteer CRM.
+//!
+//! When a subscriber is verified, unsubscribes, bounces or subscribes again,
+//! `emit` stores the event in `crm_webhook_deliveries` and `delivery_loop`
+//! POSTs it as JSON to `CRM_WEBHOOK_URL`:
+//!
+//! ```json
+//! {"id": "…", "event": "subscriber.unsubscribed", "occurred_at": "…",
+//!  "subscriber": {"id": "…", "email": "…", "name": "…",
+//!                 "status": false, "verified": true, "bounced": false}}
+//! ```
+//!
+//! Requests are signed the way `webhook` checks inbound ones: the
+//! `X-Webhook-Signature` header is `sha256=HMAC-SHA256(CRM_WEBHOOK_SECRET,
+//! "{X-Webhook-Timestamp}.{body}")`, and `X-Webhook-Id` is the event id, the
+//! same on every retry. Anything but a 2xx is retried with backoff, so a
+//! retried event can arrive after a newer one; order them by `occurred_at`.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::config::AppConfig;
+use crate::{webhook, AppState};
+
+pub const EVENT_HEADER: &str = "x-webhook-event";
+
+const POLL_SECS: u64 = 5;
+const BATCH_SIZE: i64 = 20;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// A claimed event isn't picked up again for this long, so replicas don't
+/// deliver it twice while a slow request is in flight.
+const LEASE_SECS: i64 = 120;
+/// Events still refused after this many attempts (about 8.5 hours) are
+/// given up on.
+pub const MAX_ATTEMPTS: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifecycle {
+    Verified,
+    Unsubscribed,
+    Bounced,
+    Resubscribed,
+}
+
+impl Lifecycle {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Verified => "subscriber.verified",
+            Self::Unsubscribed => "subscriber.unsubscribed",
+            Self::Bounced => "subscriber.bounced",
+            Self::Resubscribed => "subscriber.resubscribed",
+        }
+    }
+}
+
+/// Whether `CRM_WEBHOOK_URL` and `CRM_WEBHOOK_SECRET` are both set.
+pub fn enabled(config: &AppConfig) -> bool {
+    config.crm_webhook_url.is_some() && config.crm_webhook_secret.is_some()
+}
+
+/// Queue a lifecycle event for the subscriber, with their details as they are
+/// now. Does nothing when the webhook isn't configured.
+pub async fn emit(
+    state: &AppState,
+    subscriber_id: uuid::Uuid,
+    event: Lifecycle,
+) -> Result<(), sqlx::Error> {
+    if !enabled(&state.config) {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO crm_webhook_deliveries (id, subscriber_id, event, payload) \
+         SELECT $1, s.id, $2, jsonb_build_object( \
+             'id', $1, 'event', $2::TEXT, 'occurred_at', NOW(), \
+             'subscriber', jsonb_build_object( \
+                 'id', s.id, 'email', s.email, 'name', s.name, 'status', s.status, \
+                 'verified', s.verified_email, 'bounced', s.bounced_at IS NOT NULL)) \
+         FROM subscribers s WHERE s.id = $3",
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(event.as_str())
+    .bind(subscriber_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+/// How long to wait after the given number of failed attempts: 30 seconds,
+/// doubling up to 6 hours.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = u32::try_from(attempts.saturating_sub(1))
+        .unwrap_or(0)
+        .min(16);
+    Duration::from_secs((30u64 << doublings).min(6 * 3600))
+}
+
+/// Posts queued events to the CRM.
+#[derive(Clone)]
+pub struct CrmWebhook {
+    url: String,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl CrmWebhook {
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        Some(Self {
+            url: config.crm_webhook_url.clone()?,
+            secret: config.crm_webhook_secret.clone()?,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn post(&self, id: uuid::Uuid, event: &str, body: String) -> Result<(), String> {
+        let timestamp = Utc::now().timestamp();
+        let signature = webhook::sign(&self.secret, timestamp, body.as_bytes());
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(webhook::TIMESTAMP_HEADER, timestamp.to_string())
+            .header(webhook::SIGNATURE_HEADER, signature)
+            .header(webhook::ID_HEADER, id.to_string())
+            .header(EVENT_HEADER, event)
+            .body(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Send the events that are due, oldest first. Returns how many the CRM
+/// accepted.
+pub async fn deliver_due(db: &PgPool, hook: &CrmWebhook) -> Result<usize, sqlx::Error> {
+    let mut due = sqlx::query_as::<_, (uuid::Uuid, String, String, i32, DateTime<Utc>)>(
+        "UPDATE crm_webhook_deliveries \
+         SET attempts = attempts + 1, next_attempt_at = NOW() + ($2::BIGINT * INTERVAL '1 second') \
+         WHERE id IN ( \
+             SELECT id FROM crm_webhook_deliveries \
+             WHERE delivered_at IS NULL AND failed_at IS NULL AND next_attempt_at <= NOW() \
+             ORDER BY created_at LIMIT $1 FOR UPDATE SKIP LOCKED) \
+         RETURNING id, event, payload::TEXT, attempts, created_at",
+    )
+    .bind(BATCH_SIZE)
+    .bind(LEASE_SECS)
+    .fetch_all(db)
+    .await?;
+    due.sort_by_key(|row| row.4);
+
+    let mut delivered = 0;
+    for (id, event, body, attempts, _) in due {
+        match hook.post(id, &event, body).await {
+            Ok(()) => {
+                sqlx::query(
+                    "UPDATE crm_webhook_deliveries SET delivered_at = NOW(), last_error = NULL \
+                     WHERE id = $1",
+                )
+                .bind(id)
+                .execute(db)
+                .await?;
+                delivered += 1;
+            }
+            Err(e) if attempts >= MAX_ATTEMPTS => {
+                tracing::error!(
+                    "Giving up on CRM webhook {event} {id} after {attempts} attempts: {e}"
+                );
+                sqlx::query(
+                    "UPDATE crm_webhook_deliveries SET failed_at = NOW(), last_error = $2 \
+                     WHERE id = $1",
+                )
+                .bind(id)
+                .bind(e)
+                .execute(db)
+                .await?;
+            }
+            Err(e) => {
+                tracing::warn!("CRM webhook {event} {id} failed (attempt {attempts}): {e}");
+                let delay = i64::try_from(retry_delay(attempts).as_secs()).unwrap_or(i64::MAX);
+                sqlx::query(
+                    "UPDATE crm_webhook_deliveries SET last_error = $2, \
+                     next_attempt_at = NOW() + ($3::BIGINT * INTERVAL '1 second') WHERE id = $1",
+                )
+                .bind(id)
+                .bind(e)
+                .bind(delay)
+                .execute(db)
+                .await?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// Background loop delivering queued events every few seconds.
+pub async fn delivery_loop(db: PgPool, hook: CrmWebhook) {
+    let mut interval = tokio::time::interval(Duration::from_secs(POLL_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = deliver_due(&db, &hook).await {
+            tracing::error!("CRM webhook delivery failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(3), Duration::from_mins(2));
+        assert_eq!(retry_delay(12), Duration::from_hours(6));
+        assert_eq!(retry_delay(i32::MAX), Duration::from_hours(6));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_signed_delivery_and_retry(db: PgPool) {
+        use std::sync::{Arc, Mutex};
+
+        use axum::http::HeaderMap;
+
+        type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+        let received = Received::default();
+        let crm = axum::Router::new()
+            .route(
+                "/hook",
+                axum::routing::post(
+                    |axum::extract::State(received): axum::extract::State<Received>,
+                     headers: HeaderMap,
+                     body: String| async move {
+                        received.lock().unwrap().push((headers, body));
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, crm).await });
+
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| {
+                c.crm_webhook_url = Some(format!("http://{addr}/hook"));
+                c.crm_webhook_secret = Some("crm-secret".to_string());
+            })
+            .build();
+        app.migrate().await;
+        let db = &app.state.db;
+        let subscriber_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO subscribers (email, name, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.org', 'A', 's', 'u1', false, true) RETURNING id",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap();
+        emit(&app.state, subscriber_id, Lifecycle::Unsubscribed)
+            .await
+            .unwrap();
+
+        let hook = CrmWebhook::from_config(&app.state.config).unwrap();
+        assert_eq!(deliver_due(db, &hook).await.unwrap(), 1);
+        assert_eq!(deliver_due(db, &hook).await.unwrap(), 0);
+
+        let (headers, body) = received.lock().unwrap().pop().unwrap();
+        let timestamp: i64 = headers[webhook::TIMESTAMP_HEADER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            headers[webhook::SIGNATURE_HEADER],
+            webhook::sign("crm-secret", timestamp, body.as_bytes()).as_str()
+        );
+        assert_eq!(headers[EVENT_HEADER], "subscriber.unsubscribed");
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["id"], headers[webhook::ID_HEADER].to_str().unwrap());
+        assert_eq!(payload["subscriber"]["email"], "a@example.org");
+        assert_eq!(payload["subscriber"]["status"], false);
+
+        // The CRM is down: kept for a later attempt
+        emit(&app.state, subscriber_id, Lifecycle::Resubscribed)
+            .await
+            .unwrap();
+        let down = CrmWebhook {
+            url: "http://127.0.0.1:1/hook".to_string(),
+            ..hook
+        };
+        assert_eq!(deliver_due(db, &down).await.unwrap(), 0);
+        let (attempts, waiting, error): (i32, bool, Option<String>) = sqlx::query_as(
+            "SELECT attempts, next_attempt_at > NOW(), last_error FROM crm_webhook_deliveries \
+             WHERE delivered_at IS NULL",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap();
+        assert_eq!(attempts, 1);
+        assert!(waiting);
+        assert!(error.is_some());
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_verify_emits_event(db: PgPool) {
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| {
+                c.crm_webhook_url = Some("http://127.0.0.1:1/hook".to_string());
+                c.crm_webhook_secret = Some("crm-secret".to_string());
+            })
+            .build();
+        app.migrate().await;
+        app.post_form(
+            "/api/subscribe",
+            &[
+                ("email", "someone@example.org"),
+                ("name", "Someone"),
+                ("cf-turnstile-response", "token"),
+            ],
+        )
+        .await;
+        let token: String = sqlx::query_scalar("SELECT token FROM verification_tokens")
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        app.get(&format!("/verify/{token}")).await;
+
+        let events: Vec<String> =
+            sqlx::query_scalar("SELECT event FROM crm_webhook_deliveries ORDER BY created_at")
+                .fetch_all(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(events, ["subscriber.verified"]);
+    }
+}
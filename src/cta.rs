@@ -0,0 +1,155 @@
+//! Primary call-to-action links: the links of a newsletter editors care
+//! about most, each with an optional goal of unique clicks. Stats show them
+//! apart from the other links, and compare them across issues.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CtaLink {
+    /// The link as written in the content, before shortening
+    pub url: String,
+    /// Unique clicks aimed for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal: Option<i64>,
+}
+
+/// Links stored with a newsletter; `NULL` (or anything unreadable) means none.
+pub fn from_json(value: Option<&serde_json::Value>) -> Vec<CtaLink> {
+    value
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Parse the CTA field: one link per line, optionally followed by its goal,
+/// e.g. `https://coscup.org/2025/tickets 500`.
+pub fn parse(text: &str) -> Result<Vec<CtaLink>, String> {
+    let mut links: Vec<CtaLink> = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut parts = line.split_whitespace();
+        let Some(url) = parts.next() else {
+            continue;
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!(
+                "第 {line_no} 行：網址需以 http:// 或 https:// 開頭"
+            ));
+        }
+        let goal = match parts.next() {
+            None => None,
+            Some(goal) => match goal.parse::<i64>() {
+                Ok(n) if n > 0 => Some(n),
+                _ => return Err(format!("第 {line_no} 行：目標需為正整數")),
+            },
+        };
+        if parts.next().is_some() {
+            return Err(format!("第 {line_no} 行格式應為「網址 目標」"));
+        }
+        if links.iter().any(|l| l.url == url) {
+            return Err(format!("第 {line_no} 行：網址重複設定"));
+        }
+        links.push(CtaLink {
+            url: url.to_string(),
+            goal,
+        });
+    }
+    Ok(links)
+}
+
+/// Inverse of `parse`, for pre-filling the form.
+pub fn format(links: &[CtaLink]) -> String {
+    links
+        .iter()
+        .map(|l| match l.goal {
+            Some(goal) => format!("{} {goal}", l.url),
+            None => l.url.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether `url` (as shortened from or tracked) is the CTA link. UTM
+/// parameters the render hooks added are ignored.
+pub fn matches(cta_url: &str, url: &str) -> bool {
+    without_utm(cta_url) == without_utm(url)
+}
+
+fn without_utm(url: &str) -> String {
+    let (url, fragment) = url
+        .split_once('#')
+        .map_or((url, None), |(u, f)| (u, Some(f)));
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    let params: Vec<&str> = query
+        .split('&')
+        .filter(|p| !p.is_empty() && !p.starts_with("utm_"))
+        .collect();
+    let mut out = base.to_string();
+    if !params.is_empty() {
+        out.push('?');
+        out.push_str(&params.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(fragment);
+    }
+    out
+}
+
+/// Links as stored, `None` when there are none.
+pub fn to_json(links: &[CtaLink]) -> Option<serde_json::Value> {
+    (!links.is_empty()).then(|| serde_json::json!(links))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let links =
+            parse("https://coscup.org/2025/tickets 500\n\n  https://coscup.org/2025/cfp  \n")
+                .unwrap();
+        assert_eq!(
+            links,
+            [
+                CtaLink {
+                    url: "https://coscup.org/2025/tickets".to_string(),
+                    goal: Some(500),
+                },
+                CtaLink {
+                    url: "https://coscup.org/2025/cfp".to_string(),
+                    goal: None,
+                },
+            ]
+        );
+        assert_eq!(parse(&format(&links)).unwrap(), links);
+        assert_eq!(from_json(to_json(&links).as_ref()), links);
+        assert_eq!(to_json(&[]), None);
+        assert!(from_json(None).is_empty());
+    }
+
+    #[test]
+    fn test_matches_ignores_utm() {
+        let cta = "https://coscup.org/2025/?lang=zh#tickets";
+        assert!(matches(cta, cta));
+        assert!(matches(
+            cta,
+            "https://coscup.org/2025/?lang=zh&utm_source=newsletter&utm_medium=email&utm_campaign=2025-08#tickets"
+        ));
+        assert!(matches(
+            "https://coscup.org/cfp",
+            "https://coscup.org/cfp?utm_source=newsletter"
+        ));
+        assert!(!matches(cta, "https://coscup.org/2025/?lang=en#tickets"));
+        assert!(!matches(cta, "https://coscup.org/2025/?lang=zh"));
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        assert!(parse("coscup.org/tickets").is_err());
+        assert!(parse("https://coscup.org/ 0").is_err());
+        assert!(parse("https://coscup.org/ many").is_err());
+        assert!(parse("https://coscup.org/ 5 6").is_err());
+        assert!(parse("https://coscup.org/\nhttps://coscup.org/ 5").is_err());
+    }
+}
@@ -1,13 +1,26 @@
-use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
 
-pub async fn create_pool(database_url: &str) -> Result<PgPool, sqlx::Error> {
+use log::LevelFilter;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, PgPool};
+
+/// Queries taking longer than `slow_query_ms` are logged as warnings, tagged
+/// with the start of the statement; 0 turns that off.
+pub async fn create_pool(database_url: &str, slow_query_ms: u64) -> Result<PgPool, sqlx::Error> {
+    let options = PgConnectOptions::from_str(database_url)?;
+    let options = if slow_query_ms == 0 {
+        options.log_slow_statements(LevelFilter::Off, Duration::ZERO)
+    } else {
+        options.log_slow_statements(LevelFilter::Warn, Duration::from_millis(slow_query_ms))
+    };
     PgPoolOptions::new()
         .max_connections(10)
-        .connect(database_url)
+        .connect_with(options)
         .await
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     let migration_001 = include_str!("../migrations/001_initial.sql");
     sqlx::raw_sql(migration_001).execute(pool).await?;
@@ -45,6 +58,180 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     let migration_012 = include_str!("../migrations/012_admin_login_log.sql");
     sqlx::raw_sql(migration_012).execute(pool).await?;
 
+    let migration_013 = include_str!("../migrations/013_update_template_logo_png.sql");
+    sqlx::raw_sql(migration_013).execute(pool).await?;
+
+    let migration_014 = include_str!("../migrations/014_send_failed_at.sql");
+    sqlx::raw_sql(migration_014).execute(pool).await?;
+
+    let migration_015 = include_str!("../migrations/015_soft_bounce.sql");
+    sqlx::raw_sql(migration_015).execute(pool).await?;
+
+    let migration_016 = include_str!("../migrations/016_frequency_cap.sql");
+    sqlx::raw_sql(migration_016).execute(pool).await?;
+
+    let migration_017 = include_str!("../migrations/017_import_jobs.sql");
+    sqlx::raw_sql(migration_017).execute(pool).await?;
+
+    let migration_018 = include_str!("../migrations/018_tags.sql");
+    sqlx::raw_sql(migration_018).execute(pool).await?;
+
+    let migration_019 = include_str!("../migrations/019_segments.sql");
+    sqlx::raw_sql(migration_019).execute(pool).await?;
+
+    let migration_020 = include_str!("../migrations/020_stats_cache.sql");
+    sqlx::raw_sql(migration_020).execute(pool).await?;
+
+    let migration_021 = include_str!("../migrations/021_scanner_clicks.sql");
+    sqlx::raw_sql(migration_021).execute(pool).await?;
+
+    let migration_022 = include_str!("../migrations/022_short_links.sql");
+    sqlx::raw_sql(migration_022).execute(pool).await?;
+
+    let migration_023 = include_str!("../migrations/023_extra_headers.sql");
+    sqlx::raw_sql(migration_023).execute(pool).await?;
+
+    let migration_024 = include_str!("../migrations/024_rate_limit.sql");
+    sqlx::raw_sql(migration_024).execute(pool).await?;
+
+    let migration_025 = include_str!("../migrations/025_remember_sessions.sql");
+    sqlx::raw_sql(migration_025).execute(pool).await?;
+
+    let migration_026 = include_str!("../migrations/026_admin_devices.sql");
+    sqlx::raw_sql(migration_026).execute(pool).await?;
+
+    let migration_027 = include_str!("../migrations/027_admin_activity.sql");
+    sqlx::raw_sql(migration_027).execute(pool).await?;
+
+    let migration_028 = include_str!("../migrations/028_send_approval.sql");
+    sqlx::raw_sql(migration_028).execute(pool).await?;
+
+    let migration_029 = include_str!("../migrations/029_dark_mode.sql");
+    sqlx::raw_sql(migration_029).execute(pool).await?;
+
+    let migration_030 = include_str!("../migrations/030_newsletter_language.sql");
+    sqlx::raw_sql(migration_030).execute(pool).await?;
+
+    let migration_031 = include_str!("../migrations/031_manage_tokens.sql");
+    sqlx::raw_sql(migration_031).execute(pool).await?;
+
+    let migration_032 = include_str!("../migrations/032_editions.sql");
+    sqlx::raw_sql(migration_032).execute(pool).await?;
+
+    let migration_033 = include_str!("../migrations/033_local_delivery.sql");
+    sqlx::raw_sql(migration_033).execute(pool).await?;
+
+    let migration_034 = include_str!("../migrations/034_open_heatmap.sql");
+    sqlx::raw_sql(migration_034).execute(pool).await?;
+
+    let migration_035 = include_str!("../migrations/035_subscriber_notes.sql");
+    sqlx::raw_sql(migration_035).execute(pool).await?;
+
+    let migration_036 = include_str!("../migrations/036_newsletter_comments.sql");
+    sqlx::raw_sql(migration_036).execute(pool).await?;
+
+    let migration_037 = include_str!("../migrations/037_email_events_newsletter_id.sql");
+    sqlx::raw_sql(migration_037).execute(pool).await?;
+
+    let migration_038 = include_str!("../migrations/038_consents.sql");
+    sqlx::raw_sql(migration_038).execute(pool).await?;
+
+    let migration_039 = include_str!("../migrations/039_verification_reminder.sql");
+    sqlx::raw_sql(migration_039).execute(pool).await?;
+
+    let migration_040 = include_str!("../migrations/040_sending_identity.sql");
+    sqlx::raw_sql(migration_040).execute(pool).await?;
+
+    let migration_041 = include_str!("../migrations/041_newsletter_replies.sql");
+    sqlx::raw_sql(migration_041).execute(pool).await?;
+
+    let migration_042 = include_str!("../migrations/042_tracking_incidents.sql");
+    sqlx::raw_sql(migration_042).execute(pool).await?;
+
+    let migration_043 = include_str!("../migrations/043_subscriber_search.sql");
+    sqlx::raw_sql(migration_043).execute(pool).await?;
+
+    let migration_044 = include_str!("../migrations/044_background_jobs.sql");
+    sqlx::raw_sql(migration_044).execute(pool).await?;
+
+    let migration_045 = include_str!("../migrations/045_admin_profiles.sql");
+    sqlx::raw_sql(migration_045).execute(pool).await?;
+
+    let migration_046 = include_str!("../migrations/046_signup_attribution.sql");
+    sqlx::raw_sql(migration_046).execute(pool).await?;
+
+    let migration_047 = include_str!("../migrations/047_referrals.sql");
+    sqlx::raw_sql(migration_047).execute(pool).await?;
+
+    let migration_048 = include_str!("../migrations/048_newsletter_revisions.sql");
+    sqlx::raw_sql(migration_048).execute(pool).await?;
+
+    let migration_049 = include_str!("../migrations/049_short_link_clicks.sql");
+    sqlx::raw_sql(migration_049).execute(pool).await?;
+    let migration_050 = include_str!("../migrations/050_send_checkpoints.sql");
+    sqlx::raw_sql(migration_050).execute(pool).await?;
+    let migration_051 = include_str!("../migrations/051_canonical_email.sql");
+    sqlx::raw_sql(migration_051).execute(pool).await?;
+    let migration_052 = include_str!("../migrations/052_subscriber_snapshots.sql");
+    sqlx::raw_sql(migration_052).execute(pool).await?;
+    let migration_053 = include_str!("../migrations/053_webhook_nonces.sql");
+    sqlx::raw_sql(migration_053).execute(pool).await?;
+
+    let migration_054 = include_str!("../migrations/054_render_hooks.sql");
+    sqlx::raw_sql(migration_054).execute(pool).await?;
+
+    let migration_055 = include_str!("../migrations/055_list_topics.sql");
+    sqlx::raw_sql(migration_055).execute(pool).await?;
+
+    let migration_056 = include_str!("../migrations/056_sponsors.sql");
+    sqlx::raw_sql(migration_056).execute(pool).await?;
+
+    let migration_057 = include_str!("../migrations/057_cta_links.sql");
+    sqlx::raw_sql(migration_057).execute(pool).await?;
+
+    let migration_058 = include_str!("../migrations/058_newsletter_attachments.sql");
+    sqlx::raw_sql(migration_058).execute(pool).await?;
+
+    let migration_059 = include_str!("../migrations/059_send_jobs.sql");
+    sqlx::raw_sql(migration_059).execute(pool).await?;
+
+    let migration_060 = include_str!("../migrations/060_newsletter_audience.sql");
+    sqlx::raw_sql(migration_060).execute(pool).await?;
+
+    let migration_061 = include_str!("../migrations/061_crm_webhook_deliveries.sql");
+    sqlx::raw_sql(migration_061).execute(pool).await?;
+
+    let migration_062 = include_str!("../migrations/062_tag_rules.sql");
+    sqlx::raw_sql(migration_062).execute(pool).await?;
+
+    let migration_063 = include_str!("../migrations/063_missed_schedules.sql");
+    sqlx::raw_sql(migration_063).execute(pool).await?;
+
+    let migration_064 = include_str!("../migrations/064_newsletter_tag_target.sql");
+    sqlx::raw_sql(migration_064).execute(pool).await?;
+
+    let migration_065 = include_str!("../migrations/065_template_preview_profiles.sql");
+    sqlx::raw_sql(migration_065).execute(pool).await?;
+    let migration_066 = include_str!("../migrations/066_link_domains.sql");
+    sqlx::raw_sql(migration_066).execute(pool).await?;
+    let migration_067 = include_str!("../migrations/067_sheet_imports.sql");
+    sqlx::raw_sql(migration_067).execute(pool).await?;
+
+    let migration_068 = include_str!("../migrations/068_idempotency_keys.sql");
+    sqlx::raw_sql(migration_068).execute(pool).await?;
+
+    let migration_069 = include_str!("../migrations/069_newsletter_series.sql");
+    sqlx::raw_sql(migration_069).execute(pool).await?;
+
+    let migration_070 = include_str!("../migrations/070_email_complaints.sql");
+    sqlx::raw_sql(migration_070).execute(pool).await?;
+
+    let migration_071 = include_str!("../migrations/071_subscriber_events.sql");
+    sqlx::raw_sql(migration_071).execute(pool).await?;
+
+    let migration_072 = include_str!("../migrations/072_newsletter_share_links.sql");
+    sqlx::raw_sql(migration_072).execute(pool).await?;
+
     Ok(())
 }
 
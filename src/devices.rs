@@ -0,0 +1,91 @@
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+/// Fingerprint of the device an admin signs in from (IP + User-Agent).
+pub fn fingerprint(ip: &str, user_agent: &str) -> String {
+    hex::encode(Sha256::digest(format!("{ip}|{user_agent}").as_bytes()))
+}
+
+/// Rough location from the visitor location headers Cloudflare adds in front
+/// of the app, e.g. "Taipei, Taipei City, TW". `None` when none are present.
+pub fn rough_location(headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && *v != "XX")
+    };
+    let country = header("cf-ipcountry").map(|c| if c == "T1" { "Tor" } else { c });
+    let parts: Vec<&str> = [header("cf-ipcity"), header("cf-region"), country]
+        .into_iter()
+        .flatten()
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Remember the device and return whether the admin should be notified:
+/// it is new to them and they have signed in from other devices before.
+/// The very first sign-in (or the first after this feature shipped) is silent.
+pub async fn register(
+    db: &PgPool,
+    admin_email: &str,
+    fingerprint: &str,
+) -> Result<bool, sqlx::Error> {
+    let (has_devices, is_known) = sqlx::query_as::<_, (bool, bool)>(
+        "SELECT EXISTS(SELECT 1 FROM admin_known_devices WHERE admin_email = $1), \
+                EXISTS(SELECT 1 FROM admin_known_devices WHERE admin_email = $1 AND fingerprint = $2)",
+    )
+    .bind(admin_email)
+    .bind(fingerprint)
+    .fetch_one(db)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO admin_known_devices (admin_email, fingerprint) VALUES ($1, $2) \
+         ON CONFLICT (admin_email, fingerprint) DO UPDATE SET last_seen_at = NOW()",
+    )
+    .bind(admin_email)
+    .bind(fingerprint)
+    .execute(db)
+    .await?;
+
+    Ok(has_devices && !is_known)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let a = fingerprint("203.0.113.5", "Mozilla/5.0");
+        assert_eq!(a.len(), 64);
+        assert_eq!(a, fingerprint("203.0.113.5", "Mozilla/5.0"));
+        assert_ne!(a, fingerprint("203.0.113.6", "Mozilla/5.0"));
+        assert_ne!(a, fingerprint("203.0.113.5", "curl/8.0"));
+    }
+
+    #[test]
+    fn test_rough_location() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(rough_location(&headers), None);
+
+        headers.insert("cf-ipcountry", "TW".parse().unwrap());
+        assert_eq!(rough_location(&headers).as_deref(), Some("TW"));
+
+        headers.insert("cf-ipcity", "Taipei".parse().unwrap());
+        headers.insert("cf-region", "Taipei City".parse().unwrap());
+        assert_eq!(
+            rough_location(&headers).as_deref(),
+            Some("Taipei, Taipei City, TW")
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("cf-ipcountry", "XX".parse().unwrap());
+        assert_eq!(rough_location(&headers), None);
+        headers.insert("cf-ipcountry", "T1".parse().unwrap());
+        assert_eq!(rough_location(&headers).as_deref(), Some("Tor"));
+    }
+}
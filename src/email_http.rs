@@ -0,0 +1,516 @@
+//! Sending through the HTTP APIs of `SendGrid` or Mailgun instead of SMTP
+//! (`EMAIL_PROVIDER`), for hosts where outgoing SMTP is blocked.
+//!
+//! Their error responses are turned into the same `EmailError`s an SMTP relay
+//! would give, carrying the SMTP code closest in meaning: a rate limit becomes
+//! a throttled 421 so the send backs off (see `throttle`), and a rejected
+//! recipient a 553 hard bounce. Errors that are the provider's or ours, such
+//! as a bad API key or an outage, stay `SendFailed` so they don't count
+//! against the subscriber.
+//!
+//! Both providers have their own open and click tracking turned off for our
+//! mail, since links and the pixel are already ours.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use reqwest::StatusCode;
+
+use crate::email::{build_message, Attachment, EmailError, EmailHeader, EmailService};
+
+const SENDGRID_API: &str = "https://api.sendgrid.com";
+
+/// Longest wait for a provider to answer one request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest part of a response body kept in an error message.
+const MAX_DETAIL_CHARS: usize = 300;
+
+/// The error for a non-2xx response. `bad_recipient` is whether the
+/// provider said the `to` address itself is invalid.
+fn response_error(
+    provider: &str,
+    status: StatusCode,
+    detail: &str,
+    bad_recipient: bool,
+) -> EmailError {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        EmailError::SoftBounce(format!("{provider} rate limit (421): {detail}"))
+    } else if bad_recipient {
+        EmailError::HardBounce(format!("{provider} invalid address (553): {detail}"))
+    } else if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        EmailError::SendFailed(format!("{provider} authentication failed: {detail}"))
+    } else if status.is_server_error() {
+        EmailError::SendFailed(format!("{provider} is unavailable: {detail}"))
+    } else {
+        EmailError::SendFailed(format!("{provider} rejected the request: {detail}"))
+    }
+}
+
+/// The error for a request that got no response.
+fn request_error(provider: &str, e: &reqwest::Error) -> EmailError {
+    let what = if e.is_timeout() {
+        "request timed out"
+    } else if e.is_connect() {
+        "connection failed"
+    } else {
+        "request failed"
+    };
+    EmailError::SendFailed(format!("{provider} {what}: {e}"))
+}
+
+/// A response body short enough for `newsletter_sends.error_message`.
+fn truncate(body: &str) -> String {
+    let body = body.trim();
+    match body.char_indices().nth(MAX_DETAIL_CHARS) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+/// Turn a `SendGrid` error response (`{"errors": [{"message", "field"}]}`)
+/// into an `EmailError`.
+fn sendgrid_error(status: StatusCode, body: &str) -> EmailError {
+    let errors: Vec<(String, String)> = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v.get("errors").and_then(|e| e.as_array()).cloned())
+        .unwrap_or_default()
+        .iter()
+        .map(|e| {
+            let field = |name| e.get(name).and_then(|v| v.as_str()).unwrap_or_default();
+            (field("message").to_string(), field("field").to_string())
+        })
+        .collect();
+    let detail = if errors.is_empty() {
+        truncate(body)
+    } else {
+        truncate(
+            &errors
+                .iter()
+                .map(|(message, _)| message.as_str())
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    };
+    // e.g. `personalizations.0.to.0.email`
+    let bad_recipient = status == StatusCode::BAD_REQUEST
+        && errors
+            .iter()
+            .any(|(_, field)| field.starts_with("personalizations.") && field.contains(".to."));
+    response_error("SendGrid", status, &detail, bad_recipient)
+}
+
+/// Turn a Mailgun error response (`{"message": ...}`) into an `EmailError`.
+fn mailgun_error(status: StatusCode, body: &str) -> EmailError {
+    let message = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.get("message")
+                .and_then(|m| m.as_str())
+                .map(str::to_string)
+        });
+    let detail = truncate(message.as_deref().unwrap_or(body));
+    let lower = detail.to_lowercase();
+    let bad_recipient = status == StatusCode::BAD_REQUEST
+        && lower.contains("'to'")
+        && lower.contains("not a valid address");
+    response_error("Mailgun", status, &detail, bad_recipient)
+}
+
+// --- SendGrid ---
+
+/// The v3 Mail Send API of `SendGrid`, which assembles the MIME message
+/// itself from the parts we pass.
+pub struct SendGridEmailService {
+    client: reqwest::Client,
+    api_key: String,
+    from_email: String,
+    from: serde_json::Value,
+}
+
+impl SendGridEmailService {
+    pub fn new(api_key: String, from_email: String) -> Result<Self, EmailError> {
+        let mailbox: lettre::message::Mailbox = from_email
+            .parse()
+            .map_err(|e: lettre::address::AddressError| EmailError::SendFailed(e.to_string()))?;
+        let mut from = serde_json::json!({ "email": mailbox.email.to_string() });
+        if let Some(name) = mailbox.name {
+            from["name"] = serde_json::Value::String(name);
+        }
+        Ok(Self {
+            client: reqwest::Client::new(),
+            api_key,
+            from_email,
+            from,
+        })
+    }
+
+    fn body(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+        attachments: &[Attachment],
+    ) -> serde_json::Value {
+        // SendGrid wants text/plain first and refuses empty parts
+        let mut content = Vec::new();
+        let text = crate::plain_text::from_html(html_body);
+        if !text.is_empty() {
+            content.push(serde_json::json!({ "type": "text/plain", "value": text }));
+        }
+        content.push(serde_json::json!({ "type": "text/html", "value": html_body }));
+
+        let mut body = serde_json::json!({
+            "personalizations": [{ "to": [{ "email": to }] }],
+            "from": self.from,
+            "subject": subject,
+            "content": content,
+            "tracking_settings": {
+                "click_tracking": { "enable": false },
+                "open_tracking": { "enable": false },
+            },
+        });
+        if !headers.is_empty() {
+            body["headers"] = headers
+                .iter()
+                .map(|(name, value)| (name.clone(), serde_json::Value::String(value.clone())))
+                .collect::<serde_json::Map<_, _>>()
+                .into();
+        }
+        // Event webhooks don't include headers; this brings our Message-ID back
+        // so a bounce is matched to its send (see `bounce_webhook`)
+        if let Some((_, message_id)) = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Message-ID"))
+        {
+            body["custom_args"] = serde_json::json!({ "message_id": message_id });
+        }
+        if !attachments.is_empty() {
+            body["attachments"] = attachments
+                .iter()
+                .map(|a| {
+                    serde_json::json!({
+                        "content": base64::engine::general_purpose::STANDARD.encode(&a.data),
+                        "filename": a.filename,
+                        "type": a.content_type,
+                        "disposition": "attachment",
+                    })
+                })
+                .collect();
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl EmailService for SendGridEmailService {
+    async fn send_email(&self, to: &str, subject: &str, html_body: &str) -> Result<(), EmailError> {
+        self.send_email_with_attachments(to, subject, html_body, &[], &[])
+            .await
+    }
+
+    async fn send_email_with_headers(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), EmailError> {
+        self.send_email_with_attachments(to, subject, html_body, headers, &[])
+            .await
+    }
+
+    async fn send_email_with_attachments(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+        attachments: &[Attachment],
+    ) -> Result<(), EmailError> {
+        let resp = self
+            .client
+            .post(format!("{SENDGRID_API}/v3/mail/send"))
+            .bearer_auth(&self.api_key)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&self.body(to, subject, html_body, headers, attachments))
+            .send()
+            .await
+            .map_err(|e| request_error("SendGrid", &e))?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        Err(sendgrid_error(status, &body))
+    }
+
+    /// Checks the API key by listing its scopes.
+    async fn check_connection(&self) -> Result<(), EmailError> {
+        let resp = self
+            .client
+            .get(format!("{SENDGRID_API}/v3/scopes"))
+            .bearer_auth(&self.api_key)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| request_error("SendGrid", &e))?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        Err(sendgrid_error(status, &body))
+    }
+
+    /// The same content as MIME; the message `SendGrid` builds from it differs
+    /// in boundaries and its own added headers.
+    fn format_message(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+        attachments: &[Attachment],
+    ) -> Result<Vec<u8>, EmailError> {
+        Ok(build_message(
+            &self.from_email,
+            to,
+            subject,
+            html_body,
+            headers,
+            attachments,
+        )?
+        .formatted())
+    }
+}
+
+// --- Mailgun ---
+
+/// Mailgun's `messages.mime` API: we build the MIME message as for SMTP and
+/// Mailgun sends it as is.
+pub struct MailgunEmailService {
+    client: reqwest::Client,
+    api_key: String,
+    /// `https://api.mailgun.net`, or `https://api.eu.mailgun.net` for EU domains
+    api_base: String,
+    domain: String,
+    from_email: String,
+}
+
+impl MailgunEmailService {
+    pub fn new(api_key: String, api_base: &str, domain: String, from_email: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key,
+            api_base: api_base.trim_end_matches('/').to_string(),
+            domain,
+            from_email,
+        }
+    }
+}
+
+#[async_trait]
+impl EmailService for MailgunEmailService {
+    async fn send_email(&self, to: &str, subject: &str, html_body: &str) -> Result<(), EmailError> {
+        self.send_email_with_attachments(to, subject, html_body, &[], &[])
+            .await
+    }
+
+    async fn send_email_with_headers(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), EmailError> {
+        self.send_email_with_attachments(to, subject, html_body, headers, &[])
+            .await
+    }
+
+    async fn send_email_with_attachments(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+        attachments: &[Attachment],
+    ) -> Result<(), EmailError> {
+        let message = self.format_message(to, subject, html_body, headers, attachments)?;
+        let form = reqwest::multipart::Form::new()
+            .text("to", to.to_string())
+            .text("o:tracking", "no")
+            .part(
+                "message",
+                reqwest::multipart::Part::bytes(message).file_name("message.mime"),
+            );
+        let resp = self
+            .client
+            .post(format!(
+                "{}/v3/{}/messages.mime",
+                self.api_base, self.domain
+            ))
+            .basic_auth("api", Some(&self.api_key))
+            .timeout(REQUEST_TIMEOUT)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| request_error("Mailgun", &e))?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        Err(mailgun_error(status, &body))
+    }
+
+    /// Checks the API key and domain by looking the domain up.
+    async fn check_connection(&self) -> Result<(), EmailError> {
+        let resp = self
+            .client
+            .get(format!("{}/v3/domains/{}", self.api_base, self.domain))
+            .basic_auth("api", Some(&self.api_key))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| request_error("Mailgun", &e))?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        Err(mailgun_error(status, &body))
+    }
+
+    fn format_message(
+        &self,
+        to: &str,
+        subject: &str,
+        html_body: &str,
+        headers: &[EmailHeader],
+        attachments: &[Attachment],
+    ) -> Result<Vec<u8>, EmailError> {
+        Ok(build_message(
+            &self.from_email,
+            to,
+            subject,
+            html_body,
+            headers,
+            attachments,
+        )?
+        .formatted())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::{classify_send_error, SendErrorClass};
+
+    #[test]
+    fn test_sendgrid_errors() {
+        let rate = sendgrid_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            r#"{"errors":[{"message":"too many requests","field":null}]}"#,
+        );
+        assert!(rate.is_throttled());
+        assert_eq!(rate.smtp_code(), Some(421));
+
+        let invalid = sendgrid_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"errors":[{"message":"Does not contain a valid address.","field":"personalizations.0.to.0.email"}]}"#,
+        );
+        assert!(invalid.is_hard_bounce());
+        assert_eq!(invalid.smtp_code(), Some(553));
+        assert_eq!(
+            classify_send_error(&invalid.to_string()),
+            SendErrorClass::UnknownRecipient
+        );
+
+        let from = sendgrid_error(
+            StatusCode::FORBIDDEN,
+            r#"{"errors":[{"message":"The from address does not match a verified Sender Identity."}]}"#,
+        );
+        assert!(!from.is_hard_bounce() && !from.is_soft_bounce());
+        assert_eq!(from.smtp_code(), None);
+        assert_eq!(
+            classify_send_error(&from.to_string()),
+            SendErrorClass::AuthFailure
+        );
+
+        let down = sendgrid_error(StatusCode::BAD_GATEWAY, "<html>Bad Gateway</html>");
+        assert!(matches!(down, EmailError::SendFailed(_)));
+        assert!(down.to_string().contains("Bad Gateway"));
+    }
+
+    #[test]
+    fn test_mailgun_errors() {
+        let invalid = mailgun_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"message":"'to' parameter is not a valid address. please check documentation"}"#,
+        );
+        assert!(invalid.is_hard_bounce());
+
+        let sandbox = mailgun_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"message":"Sandbox subdomains are for test purposes only."}"#,
+        );
+        assert!(matches!(sandbox, EmailError::SendFailed(_)));
+
+        assert!(mailgun_error(StatusCode::TOO_MANY_REQUESTS, "").is_throttled());
+        assert_eq!(
+            classify_send_error(&mailgun_error(StatusCode::UNAUTHORIZED, "Forbidden").to_string()),
+            SendErrorClass::AuthFailure
+        );
+        let long = mailgun_error(StatusCode::INTERNAL_SERVER_ERROR, &"x".repeat(1000));
+        assert!(long.to_string().chars().count() < 400);
+    }
+
+    #[test]
+    fn test_sendgrid_body() {
+        let service = SendGridEmailService::new(
+            "key".to_string(),
+            "COSCUP <newsletter@coscup.org>".to_string(),
+        )
+        .unwrap();
+        let headers = vec![
+            ("List-Unsubscribe".to_string(), "<https://x/u>".to_string()),
+            (
+                "Message-ID".to_string(),
+                "<nl.1.abc@coscup.org>".to_string(),
+            ),
+        ];
+        let attachments = vec![Attachment {
+            filename: "cfp.ics".to_string(),
+            content_type: "text/calendar".to_string(),
+            data: b"BEGIN".to_vec(),
+        }];
+        let body = service.body(
+            "a@example.com",
+            "議程公布",
+            "<p>哈囉</p>",
+            &headers,
+            &attachments,
+        );
+        assert_eq!(body["from"]["email"], "newsletter@coscup.org");
+        assert_eq!(body["from"]["name"], "COSCUP");
+        assert_eq!(
+            body["personalizations"][0]["to"][0]["email"],
+            "a@example.com"
+        );
+        assert_eq!(body["content"][0]["type"], "text/plain");
+        assert_eq!(body["content"][0]["value"], "哈囉");
+        assert_eq!(body["content"][1]["value"], "<p>哈囉</p>");
+        assert_eq!(body["headers"]["List-Unsubscribe"], "<https://x/u>");
+        assert_eq!(body["custom_args"]["message_id"], "<nl.1.abc@coscup.org>");
+        assert_eq!(body["attachments"][0]["content"], "QkVHSU4=");
+
+        let plain = service.body("a@example.com", "s", "<p></p>", &[], &[]);
+        assert_eq!(plain["content"].as_array().unwrap().len(), 1);
+        assert!(plain.get("headers").is_none());
+        assert!(plain.get("custom_args").is_none());
+        assert!(plain.get("attachments").is_none());
+    }
+}
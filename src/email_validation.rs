@@ -0,0 +1,182 @@
+//! Email addresses as entered on the subscribe form, in imports and by
+//! admins: trimmed, lowercased and checked with a real address parser.
+//!
+//! Next to the address we keep a canonical form, used to spot the same
+//! mailbox written differently. Gmail ignores dots and anything after `+` in
+//! the local part, and `googlemail.com` is the same domain, so
+//! `Jane.Doe+coscup@googlemail.com` is canonically `janedoe@gmail.com`.
+//! Mail still goes to the address as entered.
+//!
+//! Role addresses (`info@`, `noreply@`, `admin@`, ...) belong to a function
+//! rather than a person: they often bounce, go to a ticket queue, or reach
+//! someone who never signed up and reports the mail as spam. With
+//! `BLOCK_ROLE_ACCOUNTS` set they are refused on the subscribe form and in
+//! imports, unless an admin overrides it for an import.
+
+/// Domains whose local part is canonicalized the Gmail way.
+const GMAIL_DOMAINS: [&str; 2] = ["gmail.com", "googlemail.com"];
+
+/// Local parts of role addresses, compared without a `+tag`.
+const ROLE_LOCAL_PARTS: [&str; 24] = [
+    "abuse",
+    "admin",
+    "administrator",
+    "billing",
+    "contact",
+    "do-not-reply",
+    "donotreply",
+    "help",
+    "hostmaster",
+    "info",
+    "mailer-daemon",
+    "marketing",
+    "no-reply",
+    "noc",
+    "noreply",
+    "office",
+    "postmaster",
+    "root",
+    "sales",
+    "security",
+    "service",
+    "support",
+    "sysadmin",
+    "webmaster",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EmailError {
+    #[error("Email is required")]
+    Empty,
+    #[error("Invalid email address")]
+    Invalid,
+    #[error(
+        "Role addresses such as info@ or noreply@ are not accepted; please use a personal address"
+    )]
+    RoleAccount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedEmail {
+    /// Trimmed and lowercased, stored as the subscriber's address
+    pub email: String,
+    /// Same mailbox regardless of Gmail dots and `+` tags
+    pub canonical: String,
+}
+
+/// Trim, lowercase and validate an address.
+pub fn normalize(raw: &str) -> Result<NormalizedEmail, EmailError> {
+    let email = raw.trim().to_lowercase();
+    if email.is_empty() {
+        return Err(EmailError::Empty);
+    }
+    let address = email
+        .parse::<lettre::Address>()
+        .map_err(|_| EmailError::Invalid)?;
+    let canonical = canonicalize(address.user(), address.domain());
+    Ok(NormalizedEmail { email, canonical })
+}
+
+/// Whether a normalized address is a role address.
+pub fn is_role_account(email: &str) -> bool {
+    let user = email.rsplit_once('@').map_or(email, |(user, _)| user);
+    let base = user.split('+').next().unwrap_or(user);
+    ROLE_LOCAL_PARTS.contains(&base)
+}
+
+/// `normalize`, refusing role addresses when `block_role_accounts` is set.
+pub fn normalize_for_signup(
+    raw: &str,
+    block_role_accounts: bool,
+) -> Result<NormalizedEmail, EmailError> {
+    let normalized = normalize(raw)?;
+    if block_role_accounts && is_role_account(&normalized.email) {
+        return Err(EmailError::RoleAccount);
+    }
+    Ok(normalized)
+}
+
+fn canonicalize(user: &str, domain: &str) -> String {
+    if !GMAIL_DOMAINS.contains(&domain) {
+        return format!("{user}@{domain}");
+    }
+    let base = user.split('+').next().unwrap_or(user).replace('.', "");
+    // `+tag@gmail.com` has no mailbox left to canonicalize to
+    if base.is_empty() {
+        return format!("{user}@{domain}");
+    }
+    format!("{base}@gmail.com")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_trims_and_lowercases() {
+        let n = normalize("  Alice@Example.ORG ").unwrap();
+        assert_eq!(n.email, "alice@example.org");
+        assert_eq!(n.canonical, "alice@example.org");
+    }
+
+    #[test]
+    fn test_normalize_rejects_invalid() {
+        assert_eq!(normalize("   "), Err(EmailError::Empty));
+        assert_eq!(normalize("not-an-email"), Err(EmailError::Invalid));
+        assert_eq!(normalize("a@b@example.org"), Err(EmailError::Invalid));
+        assert_eq!(normalize("a b@example.org"), Err(EmailError::Invalid));
+        assert_eq!(normalize("alice@"), Err(EmailError::Invalid));
+    }
+
+    #[test]
+    fn test_gmail_canonicalization() {
+        let n = normalize("Jane.Doe+coscup@googlemail.com").unwrap();
+        assert_eq!(n.email, "jane.doe+coscup@googlemail.com");
+        assert_eq!(n.canonical, "janedoe@gmail.com");
+        assert_eq!(
+            normalize("janedoe@gmail.com").unwrap().canonical,
+            "janedoe@gmail.com"
+        );
+        assert_eq!(
+            normalize("+tag@gmail.com").unwrap().canonical,
+            "+tag@gmail.com"
+        );
+    }
+
+    #[test]
+    fn test_role_accounts() {
+        for email in [
+            "info@coscup.org",
+            "noreply@example.org",
+            "no-reply@example.org",
+            "admin+news@example.org",
+        ] {
+            assert!(is_role_account(email), "{email}");
+        }
+        for email in [
+            "alice@example.org",
+            "information@example.org",
+            "admin.lee@example.org",
+        ] {
+            assert!(!is_role_account(email), "{email}");
+        }
+
+        assert_eq!(
+            normalize_for_signup(" Info@COSCUP.org", true),
+            Err(EmailError::RoleAccount)
+        );
+        assert_eq!(
+            normalize_for_signup("info@coscup.org", false)
+                .unwrap()
+                .email,
+            "info@coscup.org"
+        );
+        assert!(normalize_for_signup("alice@coscup.org", true).is_ok());
+    }
+
+    #[test]
+    fn test_other_domains_keep_dots_and_tags() {
+        let n = normalize("jane.doe+news@example.org").unwrap();
+        assert_eq!(n.canonical, "jane.doe+news@example.org");
+    }
+}
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// Events buffered in memory before the flusher falls behind and hits are dropped.
+const CHANNEL_CAPACITY: usize = 10_000;
+
+/// Buffered events are written at least this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Max rows per multi-row INSERT (9 binds per row, well under Postgres' 65535 limit).
+const MAX_BATCH: usize = 1000;
+
+/// An open or click hit waiting to be written to `email_events`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackingEvent {
+    pub ucode: String,
+    pub event_type: &'static str,
+    pub topic: String,
+    /// Newsletter the topic resolved to, if any
+    pub newsletter_id: Option<uuid::Uuid>,
+    pub user_agent: String,
+    pub clicked_url: Option<String>,
+    /// Set when the hit is already known to come from a link scanner
+    pub scanner_reason: Option<&'static str>,
+    /// Time of the hit, not of the flush
+    pub created_at: DateTime<Utc>,
+}
+
+/// Handle used by request handlers to queue tracking events.
+#[derive(Clone)]
+pub struct EventBuffer {
+    tx: mpsc::Sender<TrackingEvent>,
+}
+
+impl EventBuffer {
+    fn new(capacity: usize) -> (Self, mpsc::Receiver<TrackingEvent>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Self { tx }, rx)
+    }
+
+    /// Queue an event without waiting. Returns false if it was dropped because
+    /// the buffer is full or the flusher has shut down.
+    pub fn record(&self, event: TrackingEvent) -> bool {
+        match self.tx.try_send(event) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("Tracking event buffer full, dropping event");
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Background task writing buffered events; call `shutdown` to drain it.
+pub struct EventFlusher {
+    shutdown_tx: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl EventFlusher {
+    /// Stop accepting events, write everything still buffered, and wait for it.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown_tx.send(());
+        if let Err(e) = self.handle.await {
+            tracing::error!("Tracking event flusher panicked: {e}");
+        }
+    }
+}
+
+/// Start the flusher task and return the handle for queuing events.
+/// `scanner_window_secs` is passed on to `scanner::flag_batch`.
+pub fn start(db: PgPool, scanner_window_secs: i64) -> (EventBuffer, EventFlusher) {
+    let (buffer, rx) = EventBuffer::new(CHANNEL_CAPACITY);
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let handle = tokio::spawn(run_flusher(db, scanner_window_secs, rx, shutdown_rx));
+    (
+        buffer,
+        EventFlusher {
+            shutdown_tx,
+            handle,
+        },
+    )
+}
+
+async fn run_flusher(
+    db: PgPool,
+    scanner_window_secs: i64,
+    mut rx: mpsc::Receiver<TrackingEvent>,
+    mut shutdown_rx: oneshot::Receiver<()>,
+) {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => flush(&db, scanner_window_secs, &mut batch).await,
+            event = rx.recv() => match event {
+                Some(event) => {
+                    batch.push(event);
+                    if batch.len() >= MAX_BATCH {
+                        flush(&db, scanner_window_secs, &mut batch).await;
+                    }
+                }
+                None => break,
+            },
+            _ = &mut shutdown_rx => break,
+        }
+    }
+
+    // Drain whatever is still queued
+    rx.close();
+    while let Ok(event) = rx.try_recv() {
+        batch.push(event);
+        if batch.len() >= MAX_BATCH {
+            flush(&db, scanner_window_secs, &mut batch).await;
+        }
+    }
+    flush(&db, scanner_window_secs, &mut batch).await;
+    tracing::info!("Tracking event buffer drained");
+}
+
+fn insert_query(events: &[TrackingEvent]) -> QueryBuilder<'_, Postgres> {
+    let mut qb = QueryBuilder::new(
+        "INSERT INTO email_events \
+         (ucode, event_type, topic, newsletter_id, user_agent, clicked_url, is_scanner, scanner_reason, created_at) ",
+    );
+    qb.push_values(events, |mut row, e| {
+        row.push_bind(&e.ucode)
+            .push_bind(e.event_type)
+            .push_bind(&e.topic)
+            .push_bind(e.newsletter_id)
+            .push_bind(&e.user_agent)
+            .push_bind(&e.clicked_url)
+            .push_bind(e.scanner_reason.is_some())
+            .push_bind(e.scanner_reason)
+            .push_bind(e.created_at);
+    });
+    qb.push(" RETURNING id");
+    qb
+}
+
+/// Write a batch with one multi-row INSERT (best-effort, like the old per-hit
+/// insert), then flag scanner clicks among the new rows.
+async fn flush(db: &PgPool, scanner_window_secs: i64, batch: &mut Vec<TrackingEvent>) {
+    if batch.is_empty() {
+        return;
+    }
+    match insert_query(batch)
+        .build_query_scalar::<uuid::Uuid>()
+        .fetch_all(db)
+        .await
+    {
+        Ok(ids) => {
+            if let Err(e) = crate::scanner::flag_batch(db, &ids, scanner_window_secs).await {
+                tracing::warn!("Failed to flag scanner clicks: {e}");
+            }
+        }
+        Err(e) => tracing::error!("Failed to write {} tracking events: {e}", batch.len()),
+    }
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(ucode: &str) -> TrackingEvent {
+        TrackingEvent {
+            ucode: ucode.to_string(),
+            event_type: "open",
+            topic: "2025-08".to_string(),
+            newsletter_id: None,
+            user_agent: String::new(),
+            clicked_url: None,
+            scanner_reason: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_insert_query_is_multi_row() {
+        let events = vec![event("a"), event("b")];
+        let qb = insert_query(&events);
+        assert_eq!(
+            qb.sql(),
+            "INSERT INTO email_events \
+             (ucode, event_type, topic, newsletter_id, user_agent, clicked_url, is_scanner, scanner_reason, created_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9), ($10, $11, $12, $13, $14, $15, $16, $17, $18) RETURNING id"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_drops_when_full_or_closed() {
+        let (buffer, mut rx) = EventBuffer::new(1);
+        assert!(buffer.record(event("a")));
+        assert!(!buffer.record(event("b")));
+        assert_eq!(rx.recv().await.unwrap().ucode, "a");
+
+        rx.close();
+        assert!(!buffer.record(event("c")));
+    }
+}
@@ -0,0 +1,138 @@
+use sqlx::PgPool;
+
+use crate::jobs::JobKind;
+
+/// How long to keep rows in the tables that would otherwise grow unbounded.
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    /// `subscribe_email_log`, `admin_login_log`, `tracking_incidents`, and
+    /// finished `background_jobs` and `send_jobs`, and delivered or abandoned
+    /// `crm_webhook_deliveries`
+    pub log_days: i64,
+    /// Expired or used `verification_tokens` and expired `admin_sessions`
+    pub token_days: i64,
+}
+
+/// (table, DELETE statement). `$1` is the retention in days; the rate limiter
+/// counters, webhook nonces and idempotency keys carry their own expiry.
+const LOG_TABLES: [(&str, &str); 6] = [
+    (
+        "subscribe_email_log",
+        "DELETE FROM subscribe_email_log WHERE created_at < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+    (
+        "admin_login_log",
+        "DELETE FROM admin_login_log WHERE created_at < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+    (
+        "tracking_incidents",
+        "DELETE FROM tracking_incidents WHERE created_at < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+    (
+        "background_jobs",
+        "DELETE FROM background_jobs \
+         WHERE state <> 'running' AND started_at < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+    (
+        "send_jobs",
+        "DELETE FROM send_jobs \
+         WHERE finished_at < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+    (
+        "crm_webhook_deliveries",
+        "DELETE FROM crm_webhook_deliveries \
+         WHERE COALESCE(delivered_at, failed_at) < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+];
+
+const TOKEN_TABLES: [(&str, &str); 2] = [
+    (
+        "verification_tokens",
+        "DELETE FROM verification_tokens \
+         WHERE COALESCE(used_at, expires_at) < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+    (
+        "admin_sessions",
+        "DELETE FROM admin_sessions WHERE expires_at < NOW() - ($1::BIGINT * INTERVAL '1 day')",
+    ),
+];
+
+/// Prune old rows and return the number deleted per table. A failing table
+/// is logged and skipped so the others are still cleaned.
+pub async fn run(db: &PgPool, retention: Retention) -> Vec<(&'static str, u64)> {
+    let tasks = LOG_TABLES
+        .iter()
+        .map(|&(table, sql)| (table, sql, Some(retention.log_days)))
+        .chain(
+            TOKEN_TABLES
+                .iter()
+                .map(|&(table, sql)| (table, sql, Some(retention.token_days))),
+        )
+        .chain([
+            (
+                "rate_limit_hits",
+                "DELETE FROM rate_limit_hits WHERE expires_at < NOW()",
+                None,
+            ),
+            (
+                "webhook_nonces",
+                "DELETE FROM webhook_nonces WHERE expires_at < NOW()",
+                None,
+            ),
+            (
+                "idempotency_keys",
+                "DELETE FROM idempotency_keys WHERE expires_at < NOW()",
+                None,
+            ),
+        ]);
+
+    let mut deleted = Vec::new();
+    for (table, sql, days) in tasks {
+        let mut query = sqlx::query(sql);
+        if let Some(days) = days {
+            query = query.bind(days);
+        }
+        match query.execute(db).await {
+            Ok(result) => deleted.push((table, result.rows_affected())),
+            Err(e) => tracing::error!("Housekeeping failed for {table}: {e}"),
+        }
+    }
+    deleted
+}
+
+/// One-line summary for the log, e.g. `subscribe_email_log=12, admin_login_log=0`.
+fn summary(deleted: &[(&str, u64)]) -> String {
+    deleted
+        .iter()
+        .map(|(table, count)| format!("{table}={count}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Background loop running the cleanup every `interval_secs`.
+pub async fn housekeeping_loop(db: PgPool, retention: Retention, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        let _ = crate::jobs::track(&db, JobKind::Housekeeping, None, async {
+            let deleted = run(&db, retention).await;
+            tracing::info!("Housekeeping pruned rows: {}", summary(&deleted));
+            Ok::<_, std::convert::Infallible>(())
+        })
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(
+            summary(&[("subscribe_email_log", 12), ("admin_login_log", 0)]),
+            "subscribe_email_log=12, admin_login_log=0"
+        );
+        assert_eq!(summary(&[]), "");
+    }
+}
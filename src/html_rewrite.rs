@@ -0,0 +1,311 @@
+//! Rewriting the URLs in rendered newsletter HTML on a streaming HTML parser
+//! rather than regexes, so attribute quoting and order don't matter and
+//! `srcset` candidates are rewritten along with `src`.
+//!
+//! Callbacks see URLs the way a browser does, with HTML entities decoded
+//! (`a=1&b=2`, not `a=1&amp;b=2`); what they return is escaped again.
+//! Attributes they leave alone are written back byte for byte.
+
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+
+type UrlFn<'a> = Box<dyn FnMut(&str) -> Option<String> + 'a>;
+
+/// One rewrite pass over a document. Each callback gets a URL and returns its
+/// replacement, or `None` to keep it.
+#[derive(Default)]
+pub struct Rewriter<'a> {
+    links: Option<UrlFn<'a>>,
+    images: Option<UrlFn<'a>>,
+    image_style: Option<&'a str>,
+}
+
+impl<'a> Rewriter<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rewrite the `href` of every `<a>`.
+    pub fn links(mut self, f: impl FnMut(&str) -> Option<String> + 'a) -> Self {
+        self.links = Some(Box::new(f));
+        self
+    }
+
+    /// Rewrite the `src` and each `srcset` candidate of every `<img>` and
+    /// `<source>`.
+    pub fn images(mut self, f: impl FnMut(&str) -> Option<String> + 'a) -> Self {
+        self.images = Some(Box::new(f));
+        self
+    }
+
+    /// Put an inline style in front of every `<img>`'s own.
+    pub fn image_style(mut self, style: &'a str) -> Self {
+        self.image_style = Some(style);
+        self
+    }
+
+    /// Run the pass. HTML the parser gives up on is returned unchanged.
+    pub fn rewrite(self, html: &str) -> String {
+        let Self {
+            links,
+            images,
+            image_style,
+        } = self;
+        let mut handlers = Vec::new();
+        if let Some(mut links) = links {
+            handlers.push(element!("a[href]", move |el| {
+                if let Some(href) = el.get_attribute("href") {
+                    if let Some(new) = links(&decode_attr(&href)) {
+                        el.set_attribute("href", &escape_attr(&new))?;
+                    }
+                }
+                Ok(())
+            }));
+        }
+        if images.is_some() || image_style.is_some() {
+            let mut images = images;
+            handlers.push(element!("img, source", move |el| {
+                if let Some(f) = images.as_mut() {
+                    if let Some(src) = el.get_attribute("src") {
+                        if let Some(new) = f(&decode_attr(&src)) {
+                            el.set_attribute("src", &escape_attr(&new))?;
+                        }
+                    }
+                    if let Some(srcset) = el.get_attribute("srcset") {
+                        if let Some(new) = map_srcset(&decode_attr(&srcset), f) {
+                            el.set_attribute("srcset", &escape_attr(&new))?;
+                        }
+                    }
+                }
+                if let Some(style) = image_style.filter(|_| el.tag_name() == "img") {
+                    let own = el.get_attribute("style").unwrap_or_default();
+                    el.set_attribute("style", &format!("{style}{own}"))?;
+                }
+                Ok(())
+            }));
+        }
+        if handlers.is_empty() {
+            return html.to_string();
+        }
+
+        rewrite_str(
+            html,
+            RewriteStrSettings {
+                element_content_handlers: handlers,
+                ..RewriteStrSettings::new()
+            },
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!("HTML rewrite failed, keeping the original: {e}");
+            html.to_string()
+        })
+    }
+}
+
+/// Every `<a href>` in the document, decoded, in order of appearance.
+pub fn link_urls(html: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    Rewriter::new()
+        .links(|url| {
+            urls.push(url.to_string());
+            None
+        })
+        .rewrite(html);
+    urls
+}
+
+/// Every image URL (`src` and `srcset` candidates) in the document, decoded,
+/// in order of appearance.
+pub fn image_urls(html: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    Rewriter::new()
+        .images(|url| {
+            urls.push(url.to_string());
+            None
+        })
+        .rewrite(html);
+    urls
+}
+
+/// Decode the entities that show up in attribute values.
+pub fn decode_attr(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Escape `&` for an attribute value; the parser escapes quotes itself.
+fn escape_attr(value: &str) -> String {
+    value.replace('&', "&amp;")
+}
+
+/// Split a `srcset` into (URL, descriptor) candidates the way browsers do:
+/// a URL runs to the next whitespace (a trailing comma ends the candidate),
+/// and a descriptor to the next comma.
+fn srcset_candidates(srcset: &str) -> Vec<(&str, &str)> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            return candidates;
+        }
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = &rest[..url_end];
+        if url.ends_with(',') {
+            candidates.push((url.trim_end_matches(','), ""));
+            rest = &rest[url_end..];
+            continue;
+        }
+        let after = &rest[url_end..];
+        let descriptor_end = after.find(',').unwrap_or(after.len());
+        candidates.push((url, after[..descriptor_end].trim()));
+        rest = &after[descriptor_end..];
+    }
+}
+
+/// Apply `f` to each URL of a `srcset`, keeping the width and density
+/// descriptors. `None` when no URL changed.
+fn map_srcset(srcset: &str, f: &mut dyn FnMut(&str) -> Option<String>) -> Option<String> {
+    let mut changed = false;
+    let candidates: Vec<String> = srcset_candidates(srcset)
+        .into_iter()
+        .map(|(url, descriptor)| {
+            let new = f(url);
+            changed |= new.is_some();
+            let url = new.as_deref().unwrap_or(url);
+            if descriptor.is_empty() {
+                url.to_string()
+            } else {
+                format!("{url} {descriptor}")
+            }
+        })
+        .collect();
+    changed.then(|| candidates.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upper(url: &str) -> Option<String> {
+        url.starts_with("https://").then(|| url.to_uppercase())
+    }
+
+    #[test]
+    fn test_rewrites_any_quoting() {
+        let html = r#"<a href='https://a.example/'>1</a><a class="x" href=https://b.example/>2</a><a href="https://c.example/" target="_blank">3</a>"#;
+        let result = Rewriter::new().links(upper).rewrite(html);
+        assert_eq!(
+            result,
+            r#"<a href="HTTPS://A.EXAMPLE/">1</a><a class="x" href="HTTPS://B.EXAMPLE/">2</a><a href="HTTPS://C.EXAMPLE/" target="_blank">3</a>"#
+        );
+    }
+
+    #[test]
+    fn test_leaves_other_attributes_and_urls_alone() {
+        let html = r##"<p title='a &amp; b'><a href="#top" data-x='1'>top</a> <a href="mailto:a@b.c">m</a></p>"##;
+        assert_eq!(Rewriter::new().links(upper).rewrite(html), html);
+        assert_eq!(Rewriter::new().rewrite(html), html);
+    }
+
+    #[test]
+    fn test_urls_are_decoded_and_escaped_again() {
+        let html = r#"<a href="https://a.example/?x=1&amp;y=2">a</a>"#;
+        let mut seen = Vec::new();
+        let result = Rewriter::new()
+            .links(|url| {
+                seen.push(url.to_string());
+                Some(format!("{url}&z=3"))
+            })
+            .rewrite(html);
+        assert_eq!(seen, ["https://a.example/?x=1&y=2"]);
+        assert_eq!(
+            result,
+            r#"<a href="https://a.example/?x=1&amp;y=2&amp;z=3">a</a>"#
+        );
+    }
+
+    #[test]
+    fn test_quotes_in_new_urls_are_escaped() {
+        let result = Rewriter::new()
+            .links(|_| Some(r#"https://a.example/"onmouseover="x"#.to_string()))
+            .rewrite(r#"<a href="https://a.example/">a</a>"#);
+        assert!(!result.contains(r#"" onmouseover"#));
+        assert!(result.contains("&quot;onmouseover=&quot;"));
+    }
+
+    #[test]
+    fn test_rewrites_src_and_srcset() {
+        let html = r#"<picture><source srcset="https://a.example/w.webp 1x, https://a.example/w2.webp 2x"><img src='https://a.example/a.png' srcset="https://a.example/a.png 480w,https://a.example/b.png 960w" alt="a"></picture>"#;
+        let result = Rewriter::new().images(upper).rewrite(html);
+        assert!(result.contains(
+            r#"<source srcset="HTTPS://A.EXAMPLE/W.WEBP 1x, HTTPS://A.EXAMPLE/W2.WEBP 2x">"#
+        ));
+        assert!(result.contains(r#"src="HTTPS://A.EXAMPLE/A.PNG""#));
+        assert!(result
+            .contains(r#"srcset="HTTPS://A.EXAMPLE/A.PNG 480w, HTTPS://A.EXAMPLE/B.PNG 960w""#));
+        assert!(result.contains(r#"alt="a""#));
+        assert_eq!(
+            image_urls(html),
+            [
+                "https://a.example/w.webp",
+                "https://a.example/w2.webp",
+                "https://a.example/a.png",
+                "https://a.example/a.png",
+                "https://a.example/b.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_srcset_candidates() {
+        assert_eq!(
+            srcset_candidates(" a.png 1x,b.png  2x , c.png, d.png"),
+            [
+                ("a.png", "1x"),
+                ("b.png", "2x"),
+                ("c.png", ""),
+                ("d.png", "")
+            ]
+        );
+        assert_eq!(
+            srcset_candidates("https://cdn.example/x.png?w=1,2 480w"),
+            [("https://cdn.example/x.png?w=1,2", "480w")]
+        );
+        assert!(srcset_candidates(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_map_srcset() {
+        let mut f = |url: &str| Some(format!("/p{url}"));
+        assert_eq!(
+            map_srcset("/a.png 1x, /b.png 2x", &mut f).as_deref(),
+            Some("/p/a.png 1x, /p/b.png 2x")
+        );
+        assert_eq!(map_srcset("/a.png", &mut f).as_deref(), Some("/p/a.png"));
+        assert_eq!(map_srcset("/a.png 1x", &mut |_| None), None);
+    }
+
+    #[test]
+    fn test_image_style() {
+        let html = r#"<img src="a.png"><img style="border:0" src='b.png'/><source srcset="c.png">"#;
+        let result = Rewriter::new().image_style("max-width:100%;").rewrite(html);
+        assert_eq!(
+            result,
+            r#"<img src="a.png" style="max-width:100%;"><img style="max-width:100%;border:0" src='b.png' /><source srcset="c.png">"#
+        );
+    }
+
+    #[test]
+    fn test_link_urls() {
+        let html = r#"<a href="https://a.example/?x=1&amp;y=2">a</a><a name="n">n</a><a href='mailto:a@b.c'>m</a>"#;
+        assert_eq!(
+            link_urls(html),
+            ["https://a.example/?x=1&y=2", "mailto:a@b.c"]
+        );
+    }
+}
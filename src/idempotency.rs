@@ -0,0 +1,300 @@
+//! `Idempotency-Key` support for API calls that start sends: a retried call
+//! with the same key gets the first call's response instead of acting again.
+//! Each API token has its own keys. Keys are kept for `TTL_HOURS`, then
+//! pruned by `housekeeping`.
+
+use std::future::Future;
+
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::auth::ApiTokenId;
+use crate::error::AppError;
+
+pub const HEADER: &str = "idempotency-key";
+
+/// Set on a response replayed from an earlier call.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a key is remembered.
+pub const TTL_HOURS: i64 = 24;
+
+/// A key still held by an unfinished call after this long is taken to belong
+/// to a call that died (e.g. in a restart), and a retry may claim it.
+pub const STALE_AFTER_MINS: i64 = 5;
+
+/// Longest key accepted.
+const MAX_KEY_LEN: usize = 255;
+
+/// The request's key, if it sent one.
+fn key(headers: &HeaderMap) -> Result<Option<&str>, AppError> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+        .map(Some)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {MAX_KEY_LEN} visible ASCII characters"
+            ))
+        })
+}
+
+/// Identifies the request a key was used with, so reusing a key for a
+/// different call is caught.
+fn fingerprint(request: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(request.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// The stored key: the client's key under its token's id, so two API clients
+/// picking the same key don't see each other's responses.
+fn scoped_key(token: &ApiTokenId, key: &str) -> String {
+    format!("{}:{key}", token.0)
+}
+
+/// What an earlier call with the same key left.
+enum Seen {
+    Done(StatusCode, serde_json::Value),
+    Running,
+    OtherRequest,
+}
+
+/// Claim the key for this request; `None` if it is new, expired, or left
+/// unfinished by the same request more than `STALE_AFTER_MINS` ago. A failed
+/// call gives its key up again, so that case is simply claimed anew.
+async fn claim(db: &PgPool, key: &str, fingerprint: &str) -> Result<Option<Seen>, sqlx::Error> {
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (key, fingerprint, expires_at) \
+         VALUES ($1, $2, NOW() + ($3::BIGINT * INTERVAL '1 hour')) \
+         ON CONFLICT (key) DO UPDATE SET fingerprint = EXCLUDED.fingerprint, \
+         status_code = NULL, response = NULL, expires_at = EXCLUDED.expires_at, created_at = NOW() \
+         WHERE idempotency_keys.expires_at < NOW() \
+         OR (idempotency_keys.status_code IS NULL \
+             AND idempotency_keys.fingerprint = EXCLUDED.fingerprint \
+             AND idempotency_keys.created_at < NOW() - ($4::BIGINT * INTERVAL '1 minute'))",
+    )
+    .bind(key)
+    .bind(fingerprint)
+    .bind(TTL_HOURS)
+    .bind(STALE_AFTER_MINS)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if claimed > 0 {
+        return Ok(None);
+    }
+
+    let row = sqlx::query_as::<_, (String, Option<i32>, Option<serde_json::Value>)>(
+        "SELECT fingerprint, status_code, response FROM idempotency_keys WHERE key = $1",
+    )
+    .bind(key)
+    .fetch_optional(db)
+    .await?;
+    Ok(Some(match row {
+        Some((seen, _, _)) if seen != fingerprint => Seen::OtherRequest,
+        Some((_, Some(code), response)) => Seen::Done(
+            u16::try_from(code)
+                .ok()
+                .and_then(|code| StatusCode::from_u16(code).ok())
+                .unwrap_or(StatusCode::OK),
+            response.unwrap_or_default(),
+        ),
+        // Still running, or just given up by a failed call
+        _ => Seen::Running,
+    }))
+}
+
+/// Run `call` once per `Idempotency-Key` of `token`. `request` names the
+/// endpoint (e.g. `POST /api/v1/newsletters/{id}/send`), and with the body
+/// tells calls apart. Without the header `call` just runs. Errors aren't
+/// remembered, so a call that failed can be retried with the same key.
+pub async fn run<F, Fut>(
+    db: &PgPool,
+    headers: &HeaderMap,
+    token: &ApiTokenId,
+    request: &str,
+    body: &[u8],
+    call: F,
+) -> Result<Response, AppError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<(StatusCode, serde_json::Value), AppError>>,
+{
+    let Some(key) = key(headers)? else {
+        let (status, response) = call().await?;
+        return Ok((status, Json(response)).into_response());
+    };
+
+    let key = &scoped_key(token, key);
+    match claim(db, key, &fingerprint(request, body)).await? {
+        None => {}
+        Some(Seen::Done(status, response)) => {
+            let mut replay = (status, Json(response)).into_response();
+            replay
+                .headers_mut()
+                .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+            return Ok(replay);
+        }
+        Some(Seen::Running) => {
+            return Ok((
+                StatusCode::CONFLICT,
+                "A request with this Idempotency-Key is still being processed",
+            )
+                .into_response());
+        }
+        Some(Seen::OtherRequest) => {
+            return Ok((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "This Idempotency-Key was used for a different request",
+            )
+                .into_response());
+        }
+    }
+
+    match call().await {
+        Ok((status, response)) => {
+            sqlx::query(
+                "UPDATE idempotency_keys SET status_code = $2, response = $3 WHERE key = $1",
+            )
+            .bind(key)
+            .bind(i32::from(status.as_u16()))
+            .bind(&response)
+            .execute(db)
+            .await?;
+            Ok((status, Json(response)).into_response())
+        }
+        Err(e) => {
+            sqlx::query("DELETE FROM idempotency_keys WHERE key = $1")
+                .bind(key)
+                .execute(db)
+                .await?;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_and_fingerprint() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(key(&headers).unwrap(), None);
+        headers.insert(HEADER, HeaderValue::from_static(" abc-123 "));
+        assert_eq!(key(&headers).unwrap(), Some("abc-123"));
+        headers.insert(HEADER, HeaderValue::from_static(" "));
+        assert!(key(&headers).is_err());
+        headers.insert(HEADER, HeaderValue::from_str(&"k".repeat(256)).unwrap());
+        assert!(key(&headers).is_err());
+
+        let token = ApiTokenId::of("tok");
+        assert_eq!(token, ApiTokenId::of("tok"));
+        assert_ne!(
+            scoped_key(&token, "k1"),
+            scoped_key(&ApiTokenId::of("other"), "k1")
+        );
+        assert!(!scoped_key(&token, "k1").contains("tok"));
+
+        let send = fingerprint("POST /api/v1/newsletters/1/send", b"{}");
+        assert_eq!(send, fingerprint("POST /api/v1/newsletters/1/send", b"{}"));
+        assert_ne!(send, fingerprint("POST /api/v1/newsletters/2/send", b"{}"));
+        assert_ne!(
+            send,
+            fingerprint(
+                "POST /api/v1/newsletters/1/send",
+                b"{\"override_size\":true}"
+            )
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_stale_reservations_and_token_scope(db: PgPool) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        crate::db::run_migrations(&db).await.unwrap();
+        let token = ApiTokenId::of("tok");
+        let calls = AtomicUsize::new(0);
+        let send = |token: ApiTokenId, key: &'static str, request: &'static str| {
+            let (db, calls) = (&db, &calls);
+            let mut headers = HeaderMap::new();
+            headers.insert(HEADER, HeaderValue::from_static(key));
+            async move {
+                run(db, &headers, &token, request, b"", || async {
+                    let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    Ok((StatusCode::ACCEPTED, serde_json::json!({ "call": n })))
+                })
+                .await
+                .unwrap()
+            }
+        };
+
+        // A call that is still running holds its key
+        assert!(claim(
+            &db,
+            &scoped_key(&token, "k1"),
+            &fingerprint("POST /send", b"")
+        )
+        .await
+        .unwrap()
+        .is_none());
+        assert_eq!(
+            send(token.clone(), "k1", "POST /send").await.status(),
+            StatusCode::CONFLICT
+        );
+
+        // Until it is stale: then it died, and a retry runs
+        sqlx::query(
+            "UPDATE idempotency_keys \
+             SET created_at = NOW() - ($1::BIGINT + 1) * INTERVAL '1 minute'",
+        )
+        .bind(STALE_AFTER_MINS)
+        .execute(&db)
+        .await
+        .unwrap();
+        let retry = send(token.clone(), "k1", "POST /send").await;
+        assert_eq!(retry.status(), StatusCode::ACCEPTED);
+        assert!(!retry.headers().contains_key(REPLAYED_HEADER));
+        let replay = send(token.clone(), "k1", "POST /send").await;
+        assert!(replay.headers().contains_key(REPLAYED_HEADER));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Another token's key of the same name is its own
+        let other = send(ApiTokenId::of("other"), "k1", "POST /send").await;
+        assert!(!other.headers().contains_key(REPLAYED_HEADER));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // A stale key of another request is not taken over
+        claim(
+            &db,
+            &scoped_key(&token, "k2"),
+            &fingerprint("POST /send", b""),
+        )
+        .await
+        .unwrap();
+        sqlx::query(
+            "UPDATE idempotency_keys SET created_at = NOW() - INTERVAL '1 day' \
+             WHERE status_code IS NULL",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert_eq!(
+            send(token, "k2", "POST /schedule").await.status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
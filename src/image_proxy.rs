@@ -0,0 +1,516 @@
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::security;
+
+/// Largest image the proxy will fetch and cache.
+pub const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageProxyError {
+    #[error("Failed to fetch image: {0}")]
+    FetchFailed(String),
+
+    #[error("Not an image: {0}")]
+    NotAnImage(String),
+
+    #[error("Image too large")]
+    TooLarge,
+}
+
+/// A fetched image: (content type, body).
+pub type Image = (String, Vec<u8>);
+
+/// Preflight image checks give up on a slow host after this long.
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Redirects followed before giving up on an image.
+const MAX_REDIRECTS: usize = 5;
+
+#[async_trait]
+pub trait ImageFetcher: Send + Sync {
+    async fn fetch(&self, url: &str) -> Result<Image, ImageProxyError>;
+
+    /// Check that `url` serves an image without downloading it.
+    async fn probe(&self, url: &str) -> Result<(), ImageProxyError>;
+}
+
+// --- HTTP implementation ---
+
+pub struct HttpImageFetcher {
+    client: reqwest::Client,
+}
+
+impl HttpImageFetcher {
+    pub fn new() -> Self {
+        Self {
+            // Redirects are followed by `send`, which checks each hop
+            client: reqwest::Client::builder()
+                .user_agent("COSCUP-Newsletter-ImageProxy")
+                .timeout(std::time::Duration::from_secs(15))
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl HttpImageFetcher {
+    /// Request `url`, following up to `MAX_REDIRECTS` redirects. The
+    /// signature only covers the first URL, so a redirect may not leave
+    /// http(s), and one to another host may not lead into our own network.
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<reqwest::Response, ImageProxyError> {
+        let failed = |e: &dyn std::fmt::Display| ImageProxyError::FetchFailed(e.to_string());
+        let mut url = reqwest::Url::parse(url).map_err(|e| failed(&e))?;
+        for _ in 0..=MAX_REDIRECTS {
+            let mut request = self.client.request(method.clone(), url.clone());
+            if let Some(timeout) = timeout {
+                request = request.timeout(timeout);
+            }
+            let resp = request.send().await.map_err(|e| failed(&e))?;
+            if !resp.status().is_redirection() {
+                return Ok(resp);
+            }
+
+            let next = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|location| url.join(location).ok())
+                .ok_or_else(|| {
+                    failed(&format!("HTTP {} without a valid Location", resp.status()))
+                })?;
+            if !matches!(next.scheme(), "http" | "https") {
+                return Err(failed(&format!("Redirect to {next}")));
+            }
+            if next.host_str() != url.host_str() && resolves_internal(&next).await? {
+                return Err(failed(&format!("Redirect to an internal address: {next}")));
+            }
+            url = next;
+        }
+        Err(failed(&"Too many redirects"))
+    }
+}
+
+/// Whether `ip` is in a private, loopback or link-local range, which a
+/// redirect must not lead an image fetch into.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Whether any address `url`'s host resolves to is internal.
+async fn resolves_internal(url: &reqwest::Url) -> Result<bool, ImageProxyError> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| ImageProxyError::FetchFailed(format!("No host in {url}")))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let mut addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| ImageProxyError::FetchFailed(format!("{host}: {e}")))?;
+    Ok(addrs.any(|addr| is_internal(addr.ip())))
+}
+
+#[async_trait]
+impl ImageFetcher for HttpImageFetcher {
+    async fn fetch(&self, url: &str) -> Result<Image, ImageProxyError> {
+        let mut resp = self.send(reqwest::Method::GET, url, None).await?;
+        if !resp.status().is_success() {
+            return Err(ImageProxyError::FetchFailed(format!(
+                "HTTP {}",
+                resp.status()
+            )));
+        }
+
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        if !content_type.starts_with("image/") {
+            return Err(ImageProxyError::NotAnImage(content_type));
+        }
+        if resp
+            .content_length()
+            .is_some_and(|len| len > MAX_IMAGE_BYTES as u64)
+        {
+            return Err(ImageProxyError::TooLarge);
+        }
+
+        // Content-Length is optional, so the cap is also enforced while reading
+        let mut body = Vec::new();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| ImageProxyError::FetchFailed(e.to_string()))?
+        {
+            if body.len() + chunk.len() > MAX_IMAGE_BYTES {
+                return Err(ImageProxyError::TooLarge);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok((content_type, body))
+    }
+
+    async fn probe(&self, url: &str) -> Result<(), ImageProxyError> {
+        let mut resp = self
+            .send(reqwest::Method::HEAD, url, Some(PROBE_TIMEOUT))
+            .await?;
+        // Some hosts don't support HEAD; the body of a GET is never read
+        if matches!(
+            resp.status(),
+            reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED
+        ) {
+            resp = self
+                .send(reqwest::Method::GET, url, Some(PROBE_TIMEOUT))
+                .await?;
+        }
+        if !resp.status().is_success() {
+            return Err(ImageProxyError::FetchFailed(format!(
+                "HTTP {}",
+                resp.status()
+            )));
+        }
+
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if content_type.starts_with("image/") {
+            Ok(())
+        } else {
+            Err(ImageProxyError::NotAnImage(content_type.to_string()))
+        }
+    }
+}
+
+/// Proxy URL for an external image, signed so the endpoint cannot be used as
+/// an open proxy.
+pub fn proxy_url(base_url: &str, key: &str, image_url: &str) -> String {
+    format!(
+        "{base_url}/img?url={}&sig={}",
+        urlencoding::encode(image_url),
+        security::compute_url_signature(key, image_url),
+    )
+}
+
+fn is_external(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Rewrite external image URLs (`src` and `srcset`) to go through the image
+/// proxy. Images already served from `base_url` are left alone.
+pub fn rewrite_image_srcs(html: &str, base_url: &str, key: &str) -> String {
+    let own = format!("{base_url}/");
+    crate::html_rewrite::Rewriter::new()
+        .images(|url| {
+            (is_external(url) && !url.starts_with(&own)).then(|| proxy_url(base_url, key, url))
+        })
+        .rewrite(html)
+}
+
+/// Distinct absolute image URLs (`src` and `srcset`) in `html`, unescaped.
+pub fn image_srcs(html: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for url in crate::html_rewrite::image_urls(html) {
+        if is_external(&url) && !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// An image in a newsletter that recipients would see as a broken icon.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct BrokenImage {
+    pub url: String,
+    pub reason: String,
+}
+
+/// Probe every image in `html` concurrently and return the broken ones, in
+/// the order they appear.
+pub async fn find_broken_images(fetcher: Arc<dyn ImageFetcher>, html: &str) -> Vec<BrokenImage> {
+    let mut probes = tokio::task::JoinSet::new();
+    for (index, url) in image_srcs(html).into_iter().enumerate() {
+        let fetcher = fetcher.clone();
+        probes.spawn(async move {
+            let result = fetcher.probe(&url).await;
+            (index, url, result)
+        });
+    }
+
+    let mut broken = Vec::new();
+    while let Some(joined) = probes.join_next().await {
+        let Ok((index, url, Err(e))) = joined else {
+            continue;
+        };
+        let reason = match e {
+            ImageProxyError::FetchFailed(msg) => msg,
+            ImageProxyError::NotAnImage(content_type) => format!("不是圖片（{content_type}）"),
+            ImageProxyError::TooLarge => "圖片過大".to_string(),
+        };
+        broken.push((index, BrokenImage { url, reason }));
+    }
+    broken.sort_by_key(|(index, _)| *index);
+    broken.into_iter().map(|(_, image)| image).collect()
+}
+
+/// On-disk cache paths for a URL: (body, content type).
+fn cache_paths(cache_dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let name = hex::encode(Sha256::digest(url.as_bytes()));
+    (
+        cache_dir.join(&name),
+        cache_dir.join(format!("{name}.type")),
+    )
+}
+
+async fn read_cached(cache_dir: &Path, url: &str) -> Option<Image> {
+    let (body_path, type_path) = cache_paths(cache_dir, url);
+    let content_type = tokio::fs::read_to_string(&type_path).await.ok()?;
+    let body = tokio::fs::read(&body_path).await.ok()?;
+    Some((content_type, body))
+}
+
+async fn write_cached(cache_dir: &Path, url: &str, image: &Image) -> std::io::Result<()> {
+    let (body_path, type_path) = cache_paths(cache_dir, url);
+    tokio::fs::create_dir_all(cache_dir).await?;
+    // Write the body first via a temp file, so a reader never sees a type
+    // file pointing at a partial body.
+    let tmp_path = body_path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, &image.1).await?;
+    tokio::fs::rename(&tmp_path, &body_path).await?;
+    tokio::fs::write(&type_path, &image.0).await
+}
+
+/// Serve an image from the disk cache, fetching and caching it on a miss.
+pub async fn fetch_cached(
+    cache_dir: &Path,
+    fetcher: &dyn ImageFetcher,
+    url: &str,
+) -> Result<Image, ImageProxyError> {
+    if let Some(image) = read_cached(cache_dir, url).await {
+        return Ok(image);
+    }
+    let image = fetcher.fetch(url).await?;
+    if let Err(e) = write_cached(cache_dir, url, &image).await {
+        tracing::warn!("Failed to cache proxied image {url}: {e}");
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serves a fixed PNG (or an HTML error for `/page` URLs, a 404 for
+    /// `/missing` ones) and counts fetches.
+    #[derive(Default)]
+    pub struct MockImageFetcher {
+        pub fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ImageFetcher for MockImageFetcher {
+        async fn fetch(&self, url: &str) -> Result<Image, ImageProxyError> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            if url.contains("/page") {
+                Err(ImageProxyError::NotAnImage("text/html".to_string()))
+            } else {
+                Ok(("image/png".to_string(), b"png-bytes".to_vec()))
+            }
+        }
+
+        async fn probe(&self, url: &str) -> Result<(), ImageProxyError> {
+            if url.contains("/page") {
+                Err(ImageProxyError::NotAnImage("text/html".to_string()))
+            } else if url.contains("/missing") {
+                Err(ImageProxyError::FetchFailed(
+                    "HTTP 404 Not Found".to_string(),
+                ))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewrite_image_srcs() {
+        let html = r#"<p><img style="max-width:100%" src="https://cdn.example.com/a.png?x=1&amp;y=2" alt="a"></p><img src="https://newsletter.coscup.org/uploads/b.png"><a href="https://example.com/">link</a>"#;
+        let out = rewrite_image_srcs(html, "https://newsletter.coscup.org", "key");
+
+        let sig = security::compute_url_signature("key", "https://cdn.example.com/a.png?x=1&y=2");
+        assert!(out.contains(&format!(
+            "src=\"https://newsletter.coscup.org/img?url=https%3A%2F%2Fcdn.example.com%2Fa.png%3Fx%3D1%26y%3D2&amp;sig={sig}\" alt=\"a\""
+        )));
+        assert!(out.contains(r#"<img src="https://newsletter.coscup.org/uploads/b.png">"#));
+        assert!(out.contains(r#"<a href="https://example.com/">"#));
+    }
+
+    #[tokio::test]
+    async fn test_find_broken_images() {
+        let html = r#"<img src="https://cdn.example.com/missing.png"><img src="https://cdn.example.com/ok.png?a=1&amp;b=2"><img src="https://cdn.example.com/page"><img src="https://cdn.example.com/missing.png">"#;
+        assert_eq!(
+            image_srcs(html),
+            vec![
+                "https://cdn.example.com/missing.png",
+                "https://cdn.example.com/ok.png?a=1&b=2",
+                "https://cdn.example.com/page",
+            ]
+        );
+
+        let broken = find_broken_images(Arc::new(MockImageFetcher::default()), html).await;
+        assert_eq!(
+            broken,
+            vec![
+                BrokenImage {
+                    url: "https://cdn.example.com/missing.png".to_string(),
+                    reason: "HTTP 404 Not Found".to_string(),
+                },
+                BrokenImage {
+                    url: "https://cdn.example.com/page".to_string(),
+                    reason: "不是圖片（text/html）".to_string(),
+                },
+            ]
+        );
+    }
+
+    /// Answer one connection with `head` followed by `body_len` bytes, then
+    /// close it, which is how the body ends when there is no Content-Length.
+    async fn serve_once(head: &'static str, body_len: usize) -> String {
+        serve(vec![(head, body_len)]).await
+    }
+
+    /// Answer a connection per reply, in order, as `serve_once` does.
+    async fn serve(replies: Vec<(&'static str, usize)>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for (head, body_len) in replies {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&vec![0u8; body_len]).await;
+            }
+        });
+        format!("http://{addr}/a.png")
+    }
+
+    #[tokio::test]
+    async fn test_http_fetch_caps_unsized_body() {
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nConnection: close\r\n\r\n";
+        let fetcher = HttpImageFetcher::new();
+
+        let url = serve_once(head, 16).await;
+        let (content_type, body) = fetcher.fetch(&url).await.unwrap();
+        assert_eq!(content_type, "image/png");
+        assert_eq!(body.len(), 16);
+
+        let url = serve_once(head, MAX_IMAGE_BYTES + 1).await;
+        assert!(matches!(
+            fetcher.fetch(&url).await,
+            Err(ImageProxyError::TooLarge)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_fetch_follows_redirects() {
+        let moved = "HTTP/1.1 302 Found\r\nLocation: /b.png\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let image = "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 16\r\nConnection: close\r\n\r\n";
+        let fetcher = HttpImageFetcher::new();
+
+        let url = serve(vec![(moved, 0), (image, 16)]).await;
+        let (content_type, body) = fetcher.fetch(&url).await.unwrap();
+        assert_eq!(content_type, "image/png");
+        assert_eq!(body.len(), 16);
+
+        let url = serve(vec![(moved, 0), (image, 16)]).await;
+        fetcher.probe(&url).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_fetch_refuses_redirects_into_internal_hosts() {
+        let url = serve_once(
+            "HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/a.png\r\nContent-Length: 0\r\n\r\n",
+            0,
+        )
+        .await;
+        let Err(ImageProxyError::FetchFailed(reason)) = HttpImageFetcher::new().fetch(&url).await
+        else {
+            panic!("redirect followed");
+        };
+        assert!(reason.contains("internal address"), "{reason}");
+    }
+
+    #[test]
+    fn test_is_internal() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "2001:4860:4860::8888"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cached_hits_origin_once() {
+        let dir = std::env::temp_dir().join(format!("image-proxy-test-{}", uuid::Uuid::new_v4()));
+        let fetcher = MockImageFetcher::default();
+        let url = "https://cdn.example.com/a.png";
+
+        let first = fetch_cached(&dir, &fetcher, url).await.unwrap();
+        let second = fetch_cached(&dir, &fetcher, url).await.unwrap();
+        assert_eq!(first, ("image/png".to_string(), b"png-bytes".to_vec()));
+        assert_eq!(first, second);
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+
+        assert!(fetch_cached(&dir, &fetcher, "https://cdn.example.com/page")
+            .await
+            .is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
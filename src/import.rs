@@ -0,0 +1,513 @@
+use serde::{Deserialize, Serialize};
+
+use crate::csv_handler;
+use crate::email_validation;
+use crate::security;
+use crate::AppState;
+
+/// Max per-row errors stored on a job; further errors are only counted.
+const MAX_STORED_ERRORS: usize = 1000;
+
+/// Progress is written back to `import_jobs` every this many rows.
+const PROGRESS_INTERVAL: usize = 100;
+
+/// Payload format accepted by `POST /api/v1/subscribers/import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Json,
+    Csv,
+}
+
+impl ImportFormat {
+    /// Pick the format from a `Content-Type` header value.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+        match mime.as_str() {
+            "application/json" => Some(Self::Json),
+            "text/csv" | "application/csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// One subscriber row from an API import payload.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ImportRow {
+    pub email: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub ucode: Option<String>,
+    #[serde(default = "default_true")]
+    pub status: bool,
+    #[serde(default = "default_true")]
+    pub verified_email: bool,
+    /// Tags to add, new and existing subscribers alike
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// JSON payload: either a bare array of rows or `{"subscribers": [...]}`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPayload {
+    Rows(Vec<ImportRow>),
+    Wrapped { subscribers: Vec<ImportRow> },
+}
+
+/// A per-row problem reported on the job status endpoint.
+/// `row` is 1-based and counts data rows (CSV header excluded).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub email: String,
+    pub message: String,
+}
+
+/// Parse a raw import payload into rows. Legacy v1/v2 CSV exports are also
+/// accepted, so the same files used in the admin UI can be posted as-is.
+pub fn parse_payload(format: ImportFormat, body: &str) -> Result<Vec<ImportRow>, String> {
+    match format {
+        ImportFormat::Json => serde_json::from_str::<JsonPayload>(body)
+            .map(|p| match p {
+                JsonPayload::Rows(rows) | JsonPayload::Wrapped { subscribers: rows } => rows,
+            })
+            .map_err(|e| format!("Invalid JSON payload: {e}")),
+        ImportFormat::Csv => parse_csv(body),
+    }
+}
+
+/// Headers of the email column, including what Google Forms names it in a
+/// responses sheet.
+const EMAIL_HEADERS: [&str; 5] = [
+    "email",
+    "e-mail",
+    "email address",
+    "電子郵件",
+    "電子郵件地址",
+];
+
+const NAME_HEADERS: [&str; 2] = ["name", "姓名"];
+
+fn parse_csv(body: &str) -> Result<Vec<ImportRow>, String> {
+    let first_line = body.lines().next().unwrap_or("");
+    let headers: Vec<String> = first_line
+        .split(',')
+        .map(|h| h.trim().trim_matches(['\u{feff}', '"']).to_lowercase())
+        .collect();
+
+    if let Some(email_idx) = headers
+        .iter()
+        .position(|h| EMAIL_HEADERS.contains(&h.as_str()))
+    {
+        // Simple format: `email[,name][,tags]`, header names are case-insensitive;
+        // `tags` holds `;`-separated tag names
+        let name_idx = headers
+            .iter()
+            .position(|h| NAME_HEADERS.contains(&h.as_str()));
+        let tags_idx = headers.iter().position(|h| h == "tags");
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(body.as_bytes());
+        reader
+            .records()
+            .map(|r| {
+                r.map(|r| ImportRow {
+                    email: r.get(email_idx).unwrap_or("").to_string(),
+                    name: name_idx.and_then(|i| r.get(i)).unwrap_or("").to_string(),
+                    ucode: None,
+                    status: true,
+                    verified_email: true,
+                    tags: tags_idx
+                        .and_then(|i| r.get(i))
+                        .map(crate::tags::parse_list)
+                        .unwrap_or_default(),
+                })
+                .map_err(|e| format!("Invalid CSV: {e}"))
+            })
+            .collect()
+    } else {
+        csv_handler::parse_import_csv(body)
+            .map(|records| {
+                records
+                    .into_iter()
+                    .map(|r| ImportRow {
+                        email: r.email,
+                        name: r.name,
+                        ucode: Some(r.ucode).filter(|u| !u.is_empty()),
+                        status: r.status,
+                        verified_email: r.verified_email,
+                        tags: Vec::new(),
+                    })
+                    .collect()
+            })
+            .map_err(|e| format!("Invalid CSV: {e}"))
+    }
+}
+
+/// Normalize and validate rows. Returns the valid rows (with their 1-based
+/// row number and canonical email) and errors for the rest; duplicate emails
+/// within the payload, including Gmail spellings of the same mailbox, keep
+/// the first occurrence. Role addresses are errors when `block_role_accounts`
+/// is set.
+pub fn validate_rows(
+    rows: Vec<ImportRow>,
+    block_role_accounts: bool,
+) -> (Vec<(usize, ImportRow, String)>, Vec<RowError>) {
+    let mut seen = std::collections::HashSet::new();
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+
+    for (idx, mut row) in rows.into_iter().enumerate() {
+        let row_no = idx + 1;
+        row.name = row.name.trim().to_string();
+        row.tags = crate::tags::clean_list(row.tags.iter().map(String::as_str));
+
+        let result = match email_validation::normalize_for_signup(&row.email, block_role_accounts) {
+            Err(e) => Err(e.to_string()),
+            Ok(normalized) => {
+                row.email = normalized.email;
+                if row.ucode.as_ref().is_some_and(|u| u.len() > 16) {
+                    Err("ucode must be at most 16 characters".to_string())
+                } else if !seen.insert(normalized.canonical.clone()) {
+                    Err("Duplicate email in payload".to_string())
+                } else {
+                    Ok(normalized.canonical)
+                }
+            }
+        };
+
+        match result {
+            Err(message) => errors.push(RowError {
+                row: row_no,
+                email: row.email.trim().to_lowercase(),
+                message,
+            }),
+            Ok(canonical) => valid.push((row_no, row, canonical)),
+        }
+    }
+
+    (valid, errors)
+}
+
+/// Create a queued import job and return its id.
+pub async fn create_job(
+    db: &sqlx::PgPool,
+    format: ImportFormat,
+    total_rows: usize,
+) -> Result<uuid::Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO import_jobs (format, total_rows) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(format.as_str())
+    .bind(to_i32(total_rows))
+    .fetch_one(db)
+    .await
+}
+
+/// Process an import job in the background. Rows that fail validation are
+/// recorded up front; existing subscribers are skipped, not updated, except
+/// that the row's tags are added to them too.
+/// `allow_role_accounts` overrides `BLOCK_ROLE_ACCOUNTS` for this job.
+pub async fn run_job(
+    state: AppState,
+    job_id: uuid::Uuid,
+    rows: Vec<ImportRow>,
+    allow_role_accounts: bool,
+) {
+    let block_role_accounts = state.config.block_role_accounts && !allow_role_accounts;
+    let (valid, mut errors) = validate_rows(rows, block_role_accounts);
+    let invalid_count = errors.len();
+
+    let _ = sqlx::query(
+        "UPDATE import_jobs SET status = 'running', started_at = NOW(), \
+         processed_rows = $1, error_count = $1 WHERE id = $2",
+    )
+    .bind(to_i32(invalid_count))
+    .bind(job_id)
+    .execute(&state.db)
+    .await;
+
+    let mut processed = invalid_count;
+    let mut imported = 0usize;
+    let mut skipped = 0usize;
+    let mut error_count = invalid_count;
+    let mut tag_ids = std::collections::HashMap::new();
+
+    for (row_no, row, canonical) in valid {
+        let ucode = row.ucode.clone().unwrap_or_else(security::generate_ucode);
+        // Another spelling of an existing Gmail mailbox counts as existing
+        let result = sqlx::query_scalar::<_, uuid::Uuid>(
+            "INSERT INTO subscribers (email, canonical_email, name, secret_code, ucode, status, verified_email, subscription_source) \
+             SELECT $1, $2, $3, $4, $5, $6, $7, 'api' \
+             WHERE NOT EXISTS (SELECT 1 FROM subscribers WHERE canonical_email = $2) \
+             ON CONFLICT (email) DO NOTHING RETURNING id",
+        )
+        .bind(&row.email)
+        .bind(&canonical)
+        .bind(&row.name)
+        .bind(security::generate_secret_code())
+        .bind(&ucode)
+        .bind(row.status)
+        .bind(row.verified_email)
+        .fetch_optional(&state.db)
+        .await;
+        let result = match result {
+            Ok(created) if !row.tags.is_empty() => add_tags(&state.db, &mut tag_ids, created, &row)
+                .await
+                .map(|()| created),
+            result => result,
+        };
+
+        match result {
+            Ok(Some(_)) => imported += 1,
+            Ok(None) => skipped += 1,
+            Err(e) => {
+                tracing::warn!("Import job {job_id}: row {row_no} failed: {e}");
+                error_count += 1;
+                errors.push(RowError {
+                    row: row_no,
+                    email: row.email.clone(),
+                    message: "Database error".to_string(),
+                });
+            }
+        }
+        processed += 1;
+
+        if processed % PROGRESS_INTERVAL == 0 {
+            update_progress(&state.db, job_id, processed, imported, skipped, error_count).await;
+        }
+    }
+
+    errors.sort_by_key(|e| e.row);
+    errors.truncate(MAX_STORED_ERRORS);
+
+    let result = sqlx::query(
+        "UPDATE import_jobs SET status = 'completed', finished_at = NOW(), processed_rows = $1, \
+         imported_count = $2, skipped_count = $3, error_count = $4, errors = $5 WHERE id = $6",
+    )
+    .bind(to_i32(processed))
+    .bind(to_i32(imported))
+    .bind(to_i32(skipped))
+    .bind(to_i32(error_count))
+    .bind(serde_json::to_value(&errors).unwrap_or_default())
+    .bind(job_id)
+    .execute(&state.db)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Import job {job_id}: failed to record completion: {e}");
+        let _ = sqlx::query(
+            "UPDATE import_jobs SET status = 'failed', finished_at = NOW(), error_message = $1 WHERE id = $2",
+        )
+        .bind(e.to_string())
+        .bind(job_id)
+        .execute(&state.db)
+        .await;
+        return;
+    }
+
+    tracing::info!(
+        "Import job {job_id} complete: {imported} imported, {skipped} skipped, {error_count} errors"
+    );
+}
+
+/// Tag the subscriber a row created or matched. `tag_ids` caches tag ids by
+/// name for the job.
+async fn add_tags(
+    db: &sqlx::PgPool,
+    tag_ids: &mut std::collections::HashMap<String, uuid::Uuid>,
+    created: Option<uuid::Uuid>,
+    row: &ImportRow,
+) -> Result<(), sqlx::Error> {
+    let subscriber_id = match created {
+        Some(id) => Some(id),
+        None => crate::tags::find_subscriber(db, &row.email).await?,
+    };
+    let Some(subscriber_id) = subscriber_id else {
+        return Ok(());
+    };
+    for name in &row.tags {
+        let tag_id = if let Some(&id) = tag_ids.get(name) {
+            id
+        } else {
+            let id = crate::tags::ensure_tag(db, name).await?;
+            tag_ids.insert(name.clone(), id);
+            id
+        };
+        crate::tags::tag_subscriber(db, subscriber_id, tag_id).await?;
+    }
+    Ok(())
+}
+
+async fn update_progress(
+    db: &sqlx::PgPool,
+    job_id: uuid::Uuid,
+    processed: usize,
+    imported: usize,
+    skipped: usize,
+    error_count: usize,
+) {
+    let _ = sqlx::query(
+        "UPDATE import_jobs SET processed_rows = $1, imported_count = $2, \
+         skipped_count = $3, error_count = $4 WHERE id = $5",
+    )
+    .bind(to_i32(processed))
+    .bind(to_i32(imported))
+    .bind(to_i32(skipped))
+    .bind(to_i32(error_count))
+    .bind(job_id)
+    .execute(db)
+    .await;
+}
+
+fn to_i32(n: usize) -> i32 {
+    i32::try_from(n).unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_content_type() {
+        assert_eq!(
+            ImportFormat::from_content_type("application/json"),
+            Some(ImportFormat::Json)
+        );
+        assert_eq!(
+            ImportFormat::from_content_type("text/csv; charset=utf-8"),
+            Some(ImportFormat::Csv)
+        );
+        assert_eq!(ImportFormat::from_content_type("text/plain"), None);
+    }
+
+    #[test]
+    fn test_parse_json_array_and_wrapped() {
+        let rows = parse_payload(
+            ImportFormat::Json,
+            r#"[{"email": "a@example.com", "name": "A"}, {"email": "b@example.com", "verified_email": false}]"#,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].name, "A");
+        assert!(rows[0].verified_email);
+        assert!(!rows[1].verified_email);
+
+        let rows = parse_payload(
+            ImportFormat::Json,
+            r#"{"subscribers": [{"email": "a@example.com"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 1);
+
+        assert!(parse_payload(ImportFormat::Json, "{").is_err());
+    }
+
+    #[test]
+    fn test_parse_simple_csv() {
+        let rows = parse_payload(
+            ImportFormat::Csv,
+            "Email,Name\na@example.com, A \nb@example.com\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].email, "a@example.com");
+        assert_eq!(rows[0].name, "A");
+        assert_eq!(rows[0].ucode, None);
+        assert_eq!(rows[1].name, "");
+        assert!(rows[0].tags.is_empty());
+
+        let rows = parse_payload(
+            ImportFormat::Csv,
+            "email,name,tags\na@example.com,A,volunteer; speaker\nb@example.com,B,\n",
+        )
+        .unwrap();
+        assert_eq!(rows[0].tags, vec!["volunteer", "speaker"]);
+        assert!(rows[1].tags.is_empty());
+
+        // A Google Forms responses sheet
+        let rows = parse_payload(
+            ImportFormat::Csv,
+            "時間戳記,電子郵件地址,姓名
+2025/8/9 上午 10:00:00,a@example.com,小明
+",
+        )
+        .unwrap();
+        assert_eq!(
+            (rows[0].email.as_str(), rows[0].name.as_str()),
+            ("a@example.com", "小明")
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_csv() {
+        let rows = parse_payload(
+            ImportFormat::Csv,
+            "uid,mail,name,created_at\nb3514a49,a@example.com,A,1613500741",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].ucode.as_deref(), Some("b3514a49"));
+    }
+
+    #[test]
+    fn test_validate_rows() {
+        let row = |email: &str| ImportRow {
+            email: email.to_string(),
+            name: String::new(),
+            ucode: None,
+            status: true,
+            verified_email: true,
+            tags: Vec::new(),
+        };
+        let (valid, errors) = validate_rows(
+            vec![
+                row(" A@Example.com "),
+                row("not-an-email"),
+                row("a@example.com"),
+                row(""),
+                row("Jane.Doe@gmail.com"),
+                row("janedoe+coscup@gmail.com"),
+                row("info@coscup.org"),
+            ],
+            true,
+        );
+        assert_eq!(valid.len(), 2);
+        assert_eq!(valid[0].0, 1);
+        assert_eq!(valid[0].1.email, "a@example.com");
+        assert_eq!(valid[1].1.email, "jane.doe@gmail.com");
+        assert_eq!(valid[1].2, "janedoe@gmail.com");
+        assert_eq!(
+            errors.iter().map(|e| e.row).collect::<Vec<_>>(),
+            vec![2, 3, 4, 6, 7]
+        );
+        assert_eq!(errors[1].message, "Duplicate email in payload");
+        assert_eq!(errors[3].message, "Duplicate email in payload");
+        assert_eq!(
+            errors[4].message,
+            email_validation::EmailError::RoleAccount.to_string()
+        );
+
+        let (valid, errors) = validate_rows(vec![row("info@coscup.org")], false);
+        assert_eq!(valid.len(), 1);
+        assert!(errors.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use regex::Regex;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+/// Longest reply body kept; the rest of very long threads is quoted history.
+const MAX_BODY_CHARS: usize = 20_000;
+
+/// A reply as posted by the mail provider's inbound webhook.
+#[derive(Debug, Deserialize)]
+pub struct InboundEmail {
+    pub from: String,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub html: Option<String>,
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    #[serde(default)]
+    pub references: Option<String>,
+}
+
+/// `Message-ID` of a newsletter sent to one subscriber, e.g.
+/// `<nl.6f1c...e2.a1b2c3@newsletter.coscup.org>`. Replies quote it in
+/// `In-Reply-To`, which is how they are threaded back.
+pub fn send_message_id(newsletter_id: uuid::Uuid, ucode: &str, domain: &str) -> String {
+    format!("<nl.{}.{ucode}@{domain}>", newsletter_id.simple())
+}
+
+/// Newsletter id and subscriber ucode from the first of our message ids
+/// found in `In-Reply-To` / `References`.
+pub fn parse_thread_ref(header: &str) -> Option<(uuid::Uuid, String)> {
+    let re = Regex::new(r"<nl\.([0-9a-f]{32})\.([^.@<>\s]+)@[^<>\s]+>").expect("valid regex");
+    let found = re.captures_iter(header).find_map(|caps| {
+        let id = uuid::Uuid::parse_str(&caps[1]).ok()?;
+        Some((id, caps[2].to_string()))
+    });
+    found
+}
+
+/// Bare address from a `From` value like `"Alice" <alice@example.com>`.
+pub fn sender_address(from: &str) -> Option<String> {
+    let from = from.trim();
+    let address = match (from.rfind('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &from[start + 1..end],
+        _ => from,
+    };
+    let address = address.trim().to_lowercase();
+    address.contains('@').then_some(address)
+}
+
+/// Plain text of the reply: the text part, or the HTML part with tags removed.
+fn body_text(email: &InboundEmail) -> String {
+    let text = match (&email.text, &email.html) {
+        (Some(text), _) if !text.trim().is_empty() => text.clone(),
+        (_, Some(html)) => {
+            let tags = Regex::new(r"(?s)<[^>]*>").expect("valid regex");
+            tags.replace_all(html, "").into_owned()
+        }
+        _ => String::new(),
+    };
+    text.trim().chars().take(MAX_BODY_CHARS).collect()
+}
+
+/// Subscriber with this address and the newsletter last sent to them.
+pub async fn latest_send_to(
+    db: &PgPool,
+    from_email: &str,
+) -> Result<(Option<uuid::Uuid>, Option<uuid::Uuid>), sqlx::Error> {
+    let Some(subscriber_id) =
+        sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM subscribers WHERE LOWER(email) = $1")
+            .bind(from_email)
+            .fetch_optional(db)
+            .await?
+    else {
+        return Ok((None, None));
+    };
+    let newsletter_id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT newsletter_id FROM newsletter_sends \
+         WHERE subscriber_id = $1 AND status = 'sent' \
+         ORDER BY sent_at DESC NULLS LAST LIMIT 1",
+    )
+    .bind(subscriber_id)
+    .fetch_optional(db)
+    .await?;
+    Ok((newsletter_id, Some(subscriber_id)))
+}
+
+/// Store a reply, threading it to the newsletter and subscriber it answers.
+/// Replies without our message id fall back to the sender's latest newsletter.
+/// Returns `None` when the same message was already stored (webhook retries).
+pub async fn store_reply(
+    db: &PgPool,
+    email: &InboundEmail,
+) -> Result<Option<uuid::Uuid>, sqlx::Error> {
+    let from_email = sender_address(&email.from).unwrap_or_else(|| email.from.trim().to_string());
+
+    let thread_ref = [&email.in_reply_to, &email.references]
+        .into_iter()
+        .flatten()
+        .find_map(|h| parse_thread_ref(h));
+
+    let threaded = match &thread_ref {
+        Some((newsletter_id, ucode)) => {
+            sqlx::query_as::<_, (Option<uuid::Uuid>, Option<uuid::Uuid>)>(
+                "SELECT (SELECT id FROM newsletters WHERE id = $1), \
+                    (SELECT id FROM subscribers WHERE ucode = $2)",
+            )
+            .bind(newsletter_id)
+            .bind(ucode)
+            .fetch_one(db)
+            .await?
+        }
+        None => (None, None),
+    };
+
+    let (newsletter_id, subscriber_id) = match threaded {
+        (Some(newsletter_id), subscriber_id) => (Some(newsletter_id), subscriber_id),
+        (None, _) => latest_send_to(db, &from_email).await?,
+    };
+
+    sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletter_replies \
+         (newsletter_id, subscriber_id, from_email, subject, body_text, message_id) \
+         VALUES ($1, $2, $3, $4, $5, $6) \
+         ON CONFLICT (message_id) DO NOTHING RETURNING id",
+    )
+    .bind(newsletter_id)
+    .bind(subscriber_id)
+    .bind(&from_email)
+    .bind(email.subject.trim())
+    .bind(body_text(email))
+    .bind(email.message_id.as_deref().map(str::trim))
+    .fetch_optional(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_ref_round_trip() {
+        let id = uuid::Uuid::new_v4();
+        let message_id = send_message_id(id, "a1b2c3", "newsletter.coscup.org");
+        assert_eq!(
+            parse_thread_ref(&message_id),
+            Some((id, "a1b2c3".to_string()))
+        );
+
+        let references = format!("<CAF=abc@mail.gmail.com> {message_id}");
+        assert_eq!(
+            parse_thread_ref(&references),
+            Some((id, "a1b2c3".to_string()))
+        );
+
+        assert_eq!(parse_thread_ref("<CAF=abc@mail.gmail.com>"), None);
+        assert_eq!(parse_thread_ref(""), None);
+    }
+
+    #[test]
+    fn test_sender_address() {
+        assert_eq!(
+            sender_address("\"Alice\" <Alice@Example.com>").as_deref(),
+            Some("alice@example.com")
+        );
+        assert_eq!(
+            sender_address(" bob@example.com ").as_deref(),
+            Some("bob@example.com")
+        );
+        assert_eq!(sender_address("not an address"), None);
+    }
+
+    #[test]
+    fn test_body_text() {
+        let email = |text: Option<&str>, html: Option<&str>| InboundEmail {
+            from: "a@example.com".to_string(),
+            subject: String::new(),
+            text: text.map(str::to_string),
+            html: html.map(str::to_string),
+            message_id: None,
+            in_reply_to: None,
+            references: None,
+        };
+        assert_eq!(
+            body_text(&email(Some(" thanks! "), Some("<p>x</p>"))),
+            "thanks!"
+        );
+        assert_eq!(
+            body_text(&email(None, Some("<p>see <b>you</b></p>"))),
+            "see you"
+        );
+        assert_eq!(body_text(&email(None, None)), "");
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::Display;
+use std::future::Future;
+
+use sqlx::PgPool;
+
+/// Background work reported on `/admin/jobs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    /// A scheduler round that found due newsletters, or whose query failed
+    Scheduler,
+    Send,
+    Import,
+    RegistrationSync,
+    SheetSync,
+    Housekeeping,
+    VerificationReminders,
+    UnverifiedPrune,
+    StatsRefresh,
+    Export,
+    ShortLinkSync,
+    SubscriberSnapshot,
+    TagRules,
+}
+
+impl JobKind {
+    pub const ALL: [Self; 13] = [
+        Self::Scheduler,
+        Self::Send,
+        Self::Import,
+        Self::RegistrationSync,
+        Self::SheetSync,
+        Self::Housekeeping,
+        Self::VerificationReminders,
+        Self::UnverifiedPrune,
+        Self::StatsRefresh,
+        Self::Export,
+        Self::ShortLinkSync,
+        Self::SubscriberSnapshot,
+        Self::TagRules,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Scheduler => "scheduler",
+            Self::Send => "send",
+            Self::Import => "import",
+            Self::RegistrationSync => "registration_sync",
+            Self::SheetSync => "sheet_sync",
+            Self::Housekeeping => "housekeeping",
+            Self::VerificationReminders => "verification_reminders",
+            Self::UnverifiedPrune => "unverified_prune",
+            Self::StatsRefresh => "stats_refresh",
+            Self::Export => "export",
+            Self::ShortLinkSync => "short_link_sync",
+            Self::SubscriberSnapshot => "subscriber_snapshot",
+            Self::TagRules => "tag_rules",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Scheduler => "排程檢查",
+            Self::Send => "電子報寄送",
+            Self::Import => "API 匯入",
+            Self::RegistrationSync => "報名系統同步",
+            Self::SheetSync => "試算表同步",
+            Self::Housekeeping => "資料清理",
+            Self::VerificationReminders => "驗證提醒信",
+            Self::UnverifiedPrune => "刪除逾期未驗證",
+            Self::StatsRefresh => "統計快取更新",
+            Self::Export => "每日匯出",
+            Self::ShortLinkSync => "短網址點擊同步",
+            Self::SubscriberSnapshot => "訂閱人數快照",
+            Self::TagRules => "標籤規則",
+        }
+    }
+}
+
+/// Longest error message stored with a failed job.
+const MAX_ERROR_CHARS: usize = 2000;
+
+fn truncate_error(error: &str) -> String {
+    error.chars().take(MAX_ERROR_CHARS).collect()
+}
+
+/// Insert a `running` row. Recording is best effort: on failure the job
+/// still runs, it just won't show up on the page.
+async fn start(db: &PgPool, kind: JobKind, subject: Option<&str>) -> Option<i64> {
+    sqlx::query_scalar::<_, i64>(
+        "INSERT INTO background_jobs (kind, subject) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(kind.as_str())
+    .bind(subject)
+    .fetch_one(db)
+    .await
+    .inspect_err(|e| tracing::warn!("Failed to record {} job: {e}", kind.as_str()))
+    .ok()
+}
+
+async fn finish(db: &PgPool, id: i64, error: Option<&str>) {
+    let result = sqlx::query(
+        "UPDATE background_jobs SET state = $1, error = $2, finished_at = NOW() WHERE id = $3",
+    )
+    .bind(if error.is_some() {
+        "failed"
+    } else {
+        "succeeded"
+    })
+    .bind(error.map(truncate_error))
+    .bind(id)
+    .execute(db)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record outcome of job {id}: {e}");
+    }
+}
+
+/// Run `task` as a recorded job: a `running` row before it starts, updated
+/// with the outcome and error message once it ends.
+pub async fn track<T, E: Display>(
+    db: &PgPool,
+    kind: JobKind,
+    subject: Option<String>,
+    task: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let id = start(db, kind, subject.as_deref()).await;
+    let result = task.await;
+    if let Some(id) = id {
+        let error = result.as_ref().err().map(ToString::to_string);
+        finish(db, id, error.as_deref()).await;
+    }
+    result
+}
+
+/// Record work that finished instantly, such as a scheduler round.
+pub async fn record(db: &PgPool, kind: JobKind, subject: Option<&str>, error: Option<&str>) {
+    if let Some(id) = start(db, kind, subject).await {
+        finish(db, id, error).await;
+    }
+}
+
+/// Jobs still `running` when the process starts were cut off by the previous
+/// shutdown or crash.
+pub async fn mark_interrupted(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE background_jobs SET state = 'interrupted', finished_at = NOW() \
+         WHERE state = 'running'",
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_round_trip() {
+        for kind in JobKind::ALL {
+            assert_eq!(JobKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(JobKind::parse("unknown"), None);
+    }
+
+    #[test]
+    fn test_truncate_error() {
+        assert_eq!(truncate_error("SMTP 逾時"), "SMTP 逾時");
+        assert_eq!(
+            truncate_error(&"x".repeat(MAX_ERROR_CHARS + 10)).len(),
+            MAX_ERROR_CHARS
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Destination domains newsletters may not link to. Blocked domains (from
+//! `/admin/link-domains` and `BLOCKED_LINK_DOMAINS`) keep a newsletter from
+//! being sent and `/r/c` from redirecting to them; links through public URL
+//! shorteners, whose destination nobody checked, are flagged in the preview.
+//! Allowed domains override both.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::AppState;
+
+/// Public URL shorteners flagged unless allowed.
+pub const KNOWN_SHORTENERS: &[&str] = &[
+    "bit.ly",
+    "buff.ly",
+    "cutt.ly",
+    "goo.gl",
+    "is.gd",
+    "lihi.cc",
+    "lihi1.com",
+    "ow.ly",
+    "pse.is",
+    "rb.gy",
+    "rebrand.ly",
+    "reurl.cc",
+    "shorturl.at",
+    "t.co",
+    "tiny.cc",
+    "tinyurl.com",
+];
+
+/// Longest domain name (`link_domains.domain`).
+const MAX_DOMAIN_LEN: usize = 253;
+
+/// Whether `host` is `domain` or a subdomain of it.
+pub fn matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// The domain an admin typed, lowercased, or the host of a pasted URL.
+/// `None` when it isn't a plausible domain name.
+pub fn normalize(input: &str) -> Option<String> {
+    let input = input.trim();
+    let domain = if input.contains("://") {
+        crate::shorturl::domain_of(input)?
+    } else {
+        input
+            .trim_start_matches('.')
+            .trim_end_matches('.')
+            .to_lowercase()
+    };
+    let valid = domain.len() <= MAX_DOMAIN_LEN
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    valid.then_some(domain)
+}
+
+/// What is done about links to a domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Block,
+    Allow,
+}
+
+impl Action {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "block" => Some(Self::Block),
+            "allow" => Some(Self::Allow),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Allow => "allow",
+        }
+    }
+}
+
+/// A domain on `/admin/link-domains`.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub domain: String,
+    pub action: String,
+    pub note: String,
+    pub added_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn list(db: &PgPool) -> Result<Vec<Entry>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String, String, Option<String>, DateTime<Utc>)>(
+        "SELECT domain, action, note, added_by, created_at FROM link_domains ORDER BY action, domain",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(domain, action, note, added_by, created_at)| Entry {
+            domain,
+            action,
+            note,
+            added_by,
+            created_at,
+        })
+        .collect())
+}
+
+/// Add a domain, or change what is done about it.
+pub async fn save(
+    db: &PgPool,
+    domain: &str,
+    action: Action,
+    note: &str,
+    added_by: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO link_domains (domain, action, note, added_by) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (domain) DO UPDATE SET action = EXCLUDED.action, note = EXCLUDED.note, \
+         added_by = EXCLUDED.added_by, created_at = NOW()",
+    )
+    .bind(domain)
+    .bind(action.as_str())
+    .bind(note)
+    .bind(added_by)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Returns false if the domain wasn't listed.
+pub async fn remove(db: &PgPool, domain: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM link_domains WHERE domain = $1")
+        .bind(domain)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Why a link was flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    Blocked,
+    Shortener,
+}
+
+/// The blocked and allowed domains in effect.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    blocked: Vec<String>,
+    allowed: Vec<String>,
+}
+
+impl Rules {
+    /// Only the configured `BLOCKED_LINK_DOMAINS`, plus this site and its
+    /// short link domains as allowed.
+    pub fn from_config(state: &AppState) -> Self {
+        let mut allowed: Vec<String> = state
+            .short_domains
+            .iter()
+            .map(|sd| sd.domain.to_lowercase())
+            .collect();
+        allowed.extend(crate::shorturl::domain_of(&state.config.base_url));
+        if let Some(api_url) = &state.config.yourls_api_url {
+            allowed.extend(crate::shorturl::domain_of(api_url));
+        }
+        Self {
+            blocked: state.config.blocked_link_domains.clone(),
+            allowed,
+        }
+    }
+
+    /// The configured rules and the ones managed on `/admin/link-domains`.
+    pub async fn load(state: &AppState) -> Result<Self, sqlx::Error> {
+        let mut rules = Self::from_config(state);
+        let rows = sqlx::query_as::<_, (String, String)>("SELECT domain, action FROM link_domains")
+            .fetch_all(&state.db)
+            .await?;
+        for (domain, action) in rows {
+            match Action::parse(&action) {
+                Some(Action::Block) => rules.blocked.push(domain),
+                Some(Action::Allow) => rules.allowed.push(domain),
+                None => {}
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn check_host(&self, host: &str) -> Option<Problem> {
+        let on = |domains: &[String]| domains.iter().any(|d| matches(host, d));
+        if on(&self.allowed) {
+            None
+        } else if on(&self.blocked) {
+            Some(Problem::Blocked)
+        } else if KNOWN_SHORTENERS.iter().any(|d| matches(host, d)) {
+            Some(Problem::Shortener)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_blocked(&self, host: &str) -> bool {
+        self.check_host(host) == Some(Problem::Blocked)
+    }
+}
+
+/// A flagged link in a newsletter.
+#[derive(Debug, Serialize)]
+pub struct LinkIssue {
+    pub url: String,
+    pub host: String,
+    pub blocked: bool,
+    pub message: String,
+}
+
+/// Flagged links in the HTML, each once, in order of appearance.
+pub fn check(html: &str, rules: &Rules) -> Vec<LinkIssue> {
+    let mut issues: Vec<LinkIssue> = Vec::new();
+    for url in crate::html_rewrite::link_urls(html) {
+        if !(url.starts_with("https://") || url.starts_with("http://"))
+            || issues.iter().any(|i| i.url == url)
+        {
+            continue;
+        }
+        let Some(host) = crate::shorturl::domain_of(&url) else {
+            continue;
+        };
+        let Some(problem) = rules.check_host(&host) else {
+            continue;
+        };
+        let message = match problem {
+            Problem::Blocked => format!("{host} 已被封鎖，含此連結的電子報無法發送"),
+            Problem::Shortener => {
+                format!("{host} 是公開短網址服務，收件者看不出實際目的地，建議改用原始網址")
+            }
+        };
+        issues.push(LinkIssue {
+            blocked: problem == Problem::Blocked,
+            url,
+            host,
+            message,
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(blocked: &[&str], allowed: &[&str]) -> Rules {
+        Rules {
+            blocked: blocked.iter().map(ToString::to_string).collect(),
+            allowed: allowed.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_matches_subdomains_only() {
+        assert!(matches("evil.example", "evil.example"));
+        assert!(matches("a.b.evil.example", "evil.example"));
+        assert!(!matches("notevil.example", "evil.example"));
+        assert!(!matches("evil.example.org", "evil.example"));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(" .Evil.Example. ").as_deref(),
+            Some("evil.example")
+        );
+        assert_eq!(normalize("https://BIT.ly/abc").as_deref(), Some("bit.ly"));
+        assert_eq!(normalize("localhost"), None);
+        assert_eq!(normalize("evil..example"), None);
+        assert_eq!(normalize("evil.example/path"), None);
+        assert_eq!(normalize("-evil.example"), None);
+    }
+
+    #[test]
+    fn test_check_host() {
+        let rules = rules(
+            &["evil.example", "example.com"],
+            &["good.example.com", "bit.ly"],
+        );
+        assert_eq!(rules.check_host("www.evil.example"), Some(Problem::Blocked));
+        assert_eq!(rules.check_host("good.example.com"), None);
+        assert_eq!(rules.check_host("bit.ly"), None);
+        assert_eq!(rules.check_host("tinyurl.com"), Some(Problem::Shortener));
+        assert_eq!(rules.check_host("coscup.org"), None);
+    }
+
+    #[test]
+    fn test_check_lists_each_link_once() {
+        let rules = rules(&["evil.example"], &[]);
+        let html = r#"<a href="https://evil.example/a">1</a> <a href="https://coscup.org/">2</a>
+            <a href="https://evil.example/a">3</a> <a href="https://reurl.cc/x">4</a>
+            <a href="mailto:x@evil.example">5</a>"#;
+        let issues = check(html, &rules);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].blocked);
+        assert_eq!(issues[0].host, "evil.example");
+        assert!(!issues[1].blocked);
+        assert_eq!(issues[1].url, "https://reurl.cc/x");
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_blocked_domain_stops_send_and_click(db: PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.postal_address = Some("臺北市中正區 10 號".to_string()))
+            .build();
+        app.migrate().await;
+        app.login_as("admin@coscup.org").await;
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('T', 'link-domains', '[登入](https://login.evil.example/) [短](https://bit.ly/x)') \
+             RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        let saved = app
+            .post_form(
+                "/admin/link-domains",
+                &[("domain", "https://Evil.Example/"), ("action", "block")],
+            )
+            .await;
+        assert_eq!(saved.status, StatusCode::SEE_OTHER);
+        assert!(app
+            .get("/admin/link-domains")
+            .await
+            .body
+            .contains("evil.example"));
+
+        let preview = app
+            .get(&format!("/admin/newsletters/{newsletter_id}/preview"))
+            .await;
+        assert!(preview.body.contains("evil.example 已被封鎖"));
+        assert!(preview.body.contains("bit.ly 是公開短網址服務"));
+
+        let send = app
+            .post_form(&format!("/admin/newsletters/{newsletter_id}/send"), &[])
+            .await;
+        assert_eq!(send.status, StatusCode::BAD_REQUEST);
+        let click = app
+            .get("/r/c?ucode=u1&topic=t&hash=h&url=https%3A%2F%2Flogin.evil.example%2F")
+            .await;
+        assert_eq!(click.status, StatusCode::FORBIDDEN);
+
+        // Blocked after scheduling: the worker takes it off the schedule
+        sqlx::query(
+            "UPDATE newsletters SET status = 'scheduled', scheduled_at = NOW() WHERE id = $1",
+        )
+        .bind(newsletter_id)
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+        let result = crate::newsletter::send_newsletter(
+            &app.state,
+            newsletter_id,
+            app.state.shorturl.as_ref(),
+            1,
+        )
+        .await;
+        assert!(result.is_err());
+        let status: String = sqlx::query_scalar("SELECT status FROM newsletters WHERE id = $1")
+            .bind(newsletter_id)
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        assert_eq!(status, "draft");
+
+        app.post_form(
+            "/admin/link-domains",
+            &[("domain", "login.evil.example"), ("action", "allow")],
+        )
+        .await;
+        let send = app
+            .post_form(&format!("/admin/newsletters/{newsletter_id}/send"), &[])
+            .await;
+        assert_eq!(send.status, StatusCode::SEE_OTHER);
+        app.post_form("/admin/link-domains/login.evil.example/delete", &[])
+            .await;
+        assert!(remove(&app.state.db, "login.evil.example")
+            .await
+            .is_ok_and(|removed| !removed));
+    }
+}
@@ -0,0 +1,160 @@
+//! List topics: a series of newsletters (議程公告, 志工招募, …) that
+//! subscribers can stop receiving on its own, short of unsubscribing from
+//! everything. Not to be confused with the tracking `topic`, which is the
+//! newsletter slug.
+
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+/// Longest topic name.
+pub const MAX_NAME_CHARS: usize = 50;
+
+/// A topic on the manage page and whether the subscriber still receives it.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct TopicPreference {
+    pub name: String,
+    pub subscribed: bool,
+}
+
+/// Validate the topic field of the newsletter form; empty means none.
+pub fn parse_name(raw: &str) -> Result<Option<String>, String> {
+    let name = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!("主題名稱不可超過 {MAX_NAME_CHARS} 字"));
+    }
+    Ok((!name.is_empty()).then_some(name))
+}
+
+/// Every topic in use, with whether the subscriber still receives it.
+pub fn preferences(all: &[String], opted_out: &[String]) -> Vec<TopicPreference> {
+    all.iter()
+        .map(|name| TopicPreference {
+            name: name.clone(),
+            subscribed: !opted_out.contains(name),
+        })
+        .collect()
+}
+
+/// Topic of the newsletter with this slug, the `from` of manage links.
+pub async fn for_newsletter_slug(db: &PgPool, slug: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<String>>("SELECT list_topic FROM newsletters WHERE slug = $1")
+        .bind(slug)
+        .fetch_optional(db)
+        .await
+        .map(Option::flatten)
+}
+
+/// Topics of newsletters that were sent, by name.
+pub async fn all(db: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT list_topic FROM newsletters \
+         WHERE list_topic IS NOT NULL AND status IN ('sending', 'paused', 'sent') \
+         ORDER BY list_topic",
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Every topic name on any newsletter, for suggestions on the edit form.
+pub async fn names(db: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT list_topic FROM newsletters WHERE list_topic IS NOT NULL ORDER BY list_topic",
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn opted_out(db: &PgPool, subscriber_id: uuid::Uuid) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT topic FROM subscriber_topic_optouts WHERE subscriber_id = $1 ORDER BY topic",
+    )
+    .bind(subscriber_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Stop sending the topic to the subscriber; false if it already was.
+pub async fn opt_out(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    topic: &str,
+) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query(
+        "INSERT INTO subscriber_topic_optouts (subscriber_id, topic) VALUES ($1, $2) \
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(subscriber_id)
+    .bind(topic)
+    .execute(db)
+    .await?
+    .rows_affected();
+    Ok(inserted > 0)
+}
+
+pub async fn opt_in(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    topic: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM subscriber_topic_optouts WHERE subscriber_id = $1 AND topic = $2")
+        .bind(subscriber_id)
+        .bind(topic)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Leave out subscribers (`s`) who stopped `topic`, appended to a recipient query.
+pub fn push_exclusion(qb: &mut QueryBuilder<'_, Postgres>, topic: &str) {
+    qb.push(
+        " AND NOT EXISTS (SELECT 1 FROM subscriber_topic_optouts o \
+         WHERE o.subscriber_id = s.id AND o.topic = ",
+    )
+    .push_bind(topic.to_string())
+    .push(")");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name() {
+        assert_eq!(parse_name("  "), Ok(None));
+        assert_eq!(
+            parse_name("  議程   公告 "),
+            Ok(Some("議程 公告".to_string()))
+        );
+        assert!(parse_name(&"字".repeat(MAX_NAME_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn test_preferences() {
+        let all = vec!["志工招募".to_string(), "議程公告".to_string()];
+        let prefs = preferences(&all, &["志工招募".to_string()]);
+        assert_eq!(
+            prefs,
+            [
+                TopicPreference {
+                    name: "志工招募".to_string(),
+                    subscribed: false,
+                },
+                TopicPreference {
+                    name: "議程公告".to_string(),
+                    subscribed: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_push_exclusion() {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT s.id FROM subscribers s WHERE true");
+        push_exclusion(&mut qb, "議程公告");
+        assert_eq!(
+            qb.sql(),
+            "SELECT s.id FROM subscribers s WHERE true AND NOT EXISTS (SELECT 1 FROM subscriber_topic_optouts o \
+             WHERE o.subscriber_id = s.id AND o.topic = $1)"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Languages subscribers can receive newsletters in. New subscribers start
+//! with the one their browser asks for on the signup page; they can change it
+//! on the manage page.
+
+/// Supported languages: (tag, label). Language editions are matched against
+/// these tags.
+pub const OPTIONS: [(&str, &str); 3] = [("zh-TW", "中文"), ("en", "English"), ("ja", "日本語")];
+
+/// The supported tag `tag` names exactly, ignoring case.
+pub fn find(tag: &str) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .find(|(t, _)| t.eq_ignore_ascii_case(tag.trim()))
+        .map(|(t, _)| *t)
+}
+
+/// The supported language closest to a browser language tag: an exact match,
+/// otherwise one with the same primary subtag, so `en-US` is `en` and
+/// `zh-Hant-HK` is `zh-TW`.
+fn closest(tag: &str) -> Option<&'static str> {
+    find(tag).or_else(|| {
+        let primary = tag.split('-').next()?;
+        OPTIONS
+            .iter()
+            .find(|(t, _)| {
+                t.split('-')
+                    .next()
+                    .is_some_and(|p| p.eq_ignore_ascii_case(primary))
+            })
+            .map(|(t, _)| *t)
+    })
+}
+
+/// The supported language a browser prefers, from its `Accept-Language`
+/// header. Languages are tried by descending quality; `None` when none of
+/// them is supported.
+pub fn from_accept_language(header: &str) -> Option<&'static str> {
+    let mut ranges: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so equal qualities keep the browser's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges.into_iter().find_map(|(tag, _)| closest(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_accept_language() {
+        assert_eq!(
+            from_accept_language("zh-TW,zh;q=0.9,en-US;q=0.8,en;q=0.7"),
+            Some("zh-TW")
+        );
+        assert_eq!(from_accept_language("en-US,en;q=0.5"), Some("en"));
+        assert_eq!(from_accept_language("ja"), Some("ja"));
+        assert_eq!(from_accept_language("zh-CN"), Some("zh-TW"));
+        // Quality wins over order; unsupported and refused languages are skipped
+        assert_eq!(from_accept_language("en;q=0.4, ja;q=0.8"), Some("ja"));
+        assert_eq!(
+            from_accept_language("de-DE, fr;q=0.9, en;q=0.1"),
+            Some("en")
+        );
+        assert_eq!(from_accept_language("ja;q=0, en;q=0.2"), Some("en"));
+        assert_eq!(from_accept_language("de, *;q=0.5"), None);
+        assert_eq!(from_accept_language(""), None);
+        assert_eq!(from_accept_language("en;q=abc"), None);
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("zh-tw"), Some("zh-TW"));
+        assert_eq!(find("en"), Some("en"));
+        assert_eq!(find("en-US"), None);
+        assert_eq!(find("ko"), None);
+    }
+}
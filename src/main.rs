@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect};
 use axum::routing::{get, post};
@@ -7,18 +8,78 @@ use axum::Router;
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 
+mod a11y;
+mod admin_profile;
+mod archive_cache;
+mod attachments;
+mod attribution;
+mod audience;
 mod audit;
 mod auth;
+mod backup;
+mod bounce_webhook;
+mod bulk_unsubscribe;
 mod captcha;
+mod click_guard;
+mod client_preview;
 mod config;
+mod consent;
+mod crm_webhook;
 mod csv_handler;
+mod cta;
 mod db;
+mod devices;
 mod email;
+mod email_http;
+mod email_validation;
 mod error;
+mod event_buffer;
+mod housekeeping;
+mod html_rewrite;
+mod idempotency;
+mod image_proxy;
+mod import;
+mod inbound;
+mod jobs;
+mod link_domains;
+mod list_topics;
+mod locale;
+mod metrics;
 mod newsletter;
+mod oidc;
+mod plain_text;
+mod qr;
+mod rate_limit;
+mod readiness;
+mod referral;
+mod registration;
+mod reload;
 mod routes;
+mod scanner;
 mod security;
+mod segment;
+mod send_queue;
+mod series;
+mod share_links;
+mod sheets;
+mod shortcodes;
 mod shorturl;
+mod snapshots;
+mod sponsors;
+mod stats;
+mod stats_cache;
+mod storage;
+mod subscriber_events;
+mod tags;
+mod template_profiles;
+#[cfg(test)]
+mod test_utils;
+mod throttle;
+mod timezone;
+mod tls;
+mod topics;
+mod verification;
+mod webhook;
 
 use captcha::CaptchaVerifier;
 use email::EmailService;
@@ -32,21 +93,59 @@ pub struct AppState {
     pub email: Arc<dyn EmailService>,
     pub captcha: Arc<dyn CaptchaVerifier>,
     pub shorturl: Arc<dyn ShortUrlService>,
+    pub short_domains: Vec<shorturl::ShortDomain>,
+    pub identities: Vec<email::SendingIdentity>,
+    pub events: event_buffer::EventBuffer,
+    pub topics: topics::TopicIds,
+    pub images: Arc<dyn image_proxy::ImageFetcher>,
+    /// Admin single sign-on, when configured
+    pub oidc: Option<Arc<dyn oidc::OidcProvider>>,
+    pub rate_limiter: rate_limit::RateLimiter,
+    pub click_guard: click_guard::ClickGuard,
+    pub readiness: readiness::Readiness,
+    pub live: reload::LiveConfig,
+    pub archive_cache: archive_cache::ArchiveCache,
+    pub metrics: metrics::RouteMetrics,
 }
 
+/// Liveness: the process is up and serving requests.
 async fn health() -> impl IntoResponse {
     (StatusCode::OK, "ok")
 }
 
+/// Readiness: migrations are done, the database answers and the scheduler
+/// is running. Load balancers should only route traffic here once it is 200.
+async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    let database = sqlx::query("SELECT 1").execute(&state.db).await.is_ok();
+    let migrations = state.readiness.is_migrated();
+    let scheduler = state.readiness.is_scheduler_started();
+    let status = if database && migrations && scheduler {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        axum::Json(serde_json::json!({
+            "migrations": migrations,
+            "database": database,
+            "scheduler": scheduler,
+        })),
+    )
+}
+
 #[allow(clippy::too_many_lines)]
 fn build_router(state: AppState) -> Router {
     // Public routes (no auth required)
     let public_routes = Router::new()
         .route("/health", get(health))
+        .route("/health/live", get(health))
+        .route("/health/ready", get(health_ready))
         .route("/", get(routes::subscribe::subscribe_page))
         .route("/subscribe/coscup", get(|| async { Redirect::to("/") }))
         .route("/api/subscribe", post(routes::subscribe::subscribe_api))
         .route("/verify/{token}", get(routes::subscribe::verify_email))
+        .route("/webhooks/email-events", post(routes::api::email_events))
         .route("/manage/{admin_link}", get(routes::manage::manage_page))
         .route(
             "/manage/{admin_link}/update",
@@ -56,10 +155,22 @@ fn build_router(state: AppState) -> Router {
             "/manage/{admin_link}/unsubscribe",
             post(routes::manage::unsubscribe),
         )
+        .route(
+            "/manage/{admin_link}/topics",
+            post(routes::manage::update_topic),
+        )
         .route(
             "/manage/{admin_link}/resubscribe",
             post(routes::manage::resubscribe),
         )
+        .route(
+            "/manage/{admin_link}/rotate",
+            post(routes::manage::rotate_link),
+        )
+        .route(
+            "/manage/{admin_link}/export",
+            get(routes::manage::export_data),
+        )
         .route(
             "/unsubscribe/{admin_link}",
             post(routes::manage::one_click_unsubscribe),
@@ -67,18 +178,67 @@ fn build_router(state: AppState) -> Router {
         .route("/newsletters", get(routes::archive::list))
         .route("/newsletters/{slug}", get(routes::archive::view))
         .route("/r/o", get(routes::tracking::track_open))
+        .route("/img", get(routes::image::proxy_image))
         .route("/r/c", get(routes::tracking::track_click))
+        .route("/sp/{id}", get(routes::sponsor::visit))
+        .route("/attachments/{id}", get(routes::attachment::download))
+        .route("/share/{token}", get(routes::share::view))
         // Admin login/auth (must be accessible without session)
         .route("/admin/login", get(routes::admin::login_page))
         .route("/admin/login", post(routes::admin::login_submit))
-        .route("/admin/auth/{token}", get(routes::admin::auth_magic_link));
+        .route("/admin/auth/{token}", get(routes::admin::auth_magic_link))
+        .route("/admin/auth/oidc/start", get(routes::admin::oidc_start))
+        .route(
+            "/admin/auth/oidc/callback",
+            get(routes::admin::oidc_callback),
+        )
+        .route(
+            "/admin/revoke/{token}",
+            get(routes::admin::revoke_session_page).post(routes::admin::revoke_session),
+        );
 
     // Admin routes (protected by auth middleware)
     let admin_routes = Router::new()
         .route("/admin", get(routes::admin::dashboard))
         .route("/admin/subscribers", get(routes::admin::subscribers_list))
         .route("/admin/subscribers/import", post(routes::admin::import_csv))
+        .route(
+            "/admin/subscribers/unsubscribe",
+            get(routes::admin::bulk_unsubscribe_page).post(routes::admin::bulk_unsubscribe),
+        )
         .route("/admin/subscribers/export", get(routes::admin::export_csv))
+        .route(
+            "/admin/subscribers/search",
+            get(routes::admin::subscribers_search),
+        )
+        .route(
+            "/admin/subscribers/sync-registration",
+            post(routes::admin::sync_registration),
+        )
+        .route(
+            "/admin/subscribers/sheets",
+            get(routes::admin::sheets_page).post(routes::admin::add_sheet),
+        )
+        .route(
+            "/admin/subscribers/sheets/{id}/sync",
+            post(routes::admin::sync_sheet),
+        )
+        .route(
+            "/admin/subscribers/sheets/{id}/delete",
+            post(routes::admin::remove_sheet),
+        )
+        .route(
+            "/admin/subscribers/{id}",
+            get(routes::admin::subscriber_detail),
+        )
+        .route(
+            "/admin/subscribers/{id}/notes",
+            post(routes::admin::save_note),
+        )
+        .route(
+            "/admin/subscribers/{id}/tags",
+            post(routes::admin::update_tags),
+        )
         .route(
             "/admin/subscribers/{id}/toggle",
             post(routes::admin::toggle_status),
@@ -88,6 +248,12 @@ fn build_router(state: AppState) -> Router {
             post(routes::admin::resend_verification),
         )
         .route("/admin/stats", get(routes::admin::stats_page))
+        .route("/admin/stats/refresh", post(routes::admin::refresh_stats))
+        .route(
+            "/admin/tools/subscribe-qr",
+            get(routes::admin::subscribe_qr),
+        )
+        .route("/admin/stats/heatmap", get(routes::admin::open_heatmap))
         .route("/admin/logout", post(routes::admin::logout))
         // Newsletter admin routes
         .route("/admin/newsletters", get(routes::newsletter::list))
@@ -103,10 +269,26 @@ fn build_router(state: AppState) -> Router {
             "/admin/newsletters/{id}/preview",
             get(routes::newsletter::preview),
         )
+        .route(
+            "/admin/newsletters/{id}/preview/source",
+            get(routes::newsletter::preview_source),
+        )
+        .route(
+            "/admin/newsletters/{id}/test-send",
+            post(routes::newsletter::test_send),
+        )
+        .route(
+            "/admin/newsletters/{id}/editions",
+            post(routes::newsletter::create_edition),
+        )
         .route(
             "/admin/newsletters/{id}/send",
             post(routes::newsletter::send_now),
         )
+        .route(
+            "/admin/newsletters/{id}/approve",
+            post(routes::newsletter::approve),
+        )
         .route(
             "/admin/newsletters/{id}/schedule",
             post(routes::newsletter::schedule),
@@ -115,6 +297,22 @@ fn build_router(state: AppState) -> Router {
             "/admin/newsletters/{id}/cancel",
             post(routes::newsletter::cancel),
         )
+        .route(
+            "/admin/newsletters/{id}/pause",
+            post(routes::newsletter::pause),
+        )
+        .route(
+            "/admin/newsletters/{id}/resume",
+            post(routes::newsletter::resume),
+        )
+        .route(
+            "/admin/newsletters/{id}/comments",
+            post(routes::newsletter::add_comment),
+        )
+        .route(
+            "/admin/newsletters/{id}/comments/{comment_id}/resolve",
+            post(routes::newsletter::resolve_comment),
+        )
         .route(
             "/admin/newsletters/{id}/status",
             get(routes::newsletter::status_json),
@@ -123,16 +321,105 @@ fn build_router(state: AppState) -> Router {
             "/admin/newsletters/{id}/stats",
             get(routes::newsletter::stats),
         )
+        .route(
+            "/admin/newsletters/{id}/cta",
+            post(routes::newsletter::update_cta),
+        )
+        .route(
+            "/admin/newsletters/{id}/failures",
+            get(routes::newsletter::failures),
+        )
+        .route(
+            "/admin/newsletters/{id}/audience",
+            get(routes::newsletter::audience),
+        )
+        .route(
+            "/admin/newsletters/{id}/audience.csv",
+            get(routes::newsletter::audience_csv),
+        )
+        .route(
+            "/admin/newsletters/{id}/series",
+            post(routes::newsletter::set_series),
+        )
         .route(
             "/admin/newsletters/{id}/delete",
             post(routes::newsletter::delete),
         )
+        .route(
+            "/admin/newsletters/{id}/attachments",
+            post(routes::attachment::upload)
+                .layer(axum::extract::DefaultBodyLimit::max(10 * 1024 * 1024)),
+        )
+        .route(
+            "/admin/newsletters/{id}/attachments/{attachment_id}/delete",
+            post(routes::attachment::delete),
+        )
+        .route(
+            "/admin/newsletters/{id}/share-links",
+            post(routes::share::create),
+        )
+        .route(
+            "/admin/newsletters/{id}/share-links/{link_id}/revoke",
+            post(routes::share::revoke),
+        )
+        .route(
+            "/admin/render-preview",
+            post(routes::newsletter::render_preview),
+        )
         // Image upload (increased body limit for large images)
         .route(
             "/admin/upload/image",
             post(routes::upload::upload_image)
                 .layer(axum::extract::DefaultBodyLimit::max(10 * 1024 * 1024)),
         )
+        // Segment routes
+        .route("/admin/segments", get(routes::segment::list))
+        .route(
+            "/admin/segments/new",
+            get(routes::segment::new_form).post(routes::segment::create),
+        )
+        .route(
+            "/admin/segments/{id}",
+            get(routes::segment::edit_form).post(routes::segment::update),
+        )
+        .route("/admin/segments/{id}/delete", post(routes::segment::delete))
+        .route("/admin/segments/tags", post(routes::segment::tag_emails))
+        .route(
+            "/admin/segments/tags/{id}/delete",
+            post(routes::segment::delete_tag),
+        )
+        .route("/admin/segments/rules", post(routes::segment::create_rule))
+        .route(
+            "/admin/segments/rules/{id}/apply",
+            post(routes::segment::apply_rule),
+        )
+        .route(
+            "/admin/segments/rules/{id}/delete",
+            post(routes::segment::delete_rule),
+        )
+        // Sponsor routes
+        .route("/admin/sponsors", get(routes::sponsor::list))
+        .route("/admin/sponsors/report", get(routes::sponsor::report))
+        .route(
+            "/admin/sponsors/new",
+            get(routes::sponsor::new_form).post(routes::sponsor::create),
+        )
+        .route(
+            "/admin/sponsors/{id}",
+            get(routes::sponsor::edit_form).post(routes::sponsor::update),
+        )
+        .route("/admin/sponsors/{id}/delete", post(routes::sponsor::delete))
+        // Series routes
+        .route("/admin/series", get(routes::series::list))
+        .route(
+            "/admin/series/new",
+            get(routes::series::new_form).post(routes::series::create),
+        )
+        .route(
+            "/admin/series/{id}",
+            get(routes::series::edit_form).post(routes::series::update),
+        )
+        .route("/admin/series/{id}/delete", post(routes::series::delete))
         // Template management routes
         .route("/admin/templates", get(routes::template::list))
         .route(
@@ -151,6 +438,14 @@ fn build_router(state: AppState) -> Router {
             "/admin/templates/{id}/preview",
             get(routes::template::preview),
         )
+        .route(
+            "/admin/templates/{id}/profiles",
+            post(routes::template::save_profile),
+        )
+        .route(
+            "/admin/templates/{id}/profiles/{profile_id}/delete",
+            post(routes::template::delete_profile),
+        )
         .route(
             "/admin/templates/{id}/duplicate",
             post(routes::template::duplicate),
@@ -158,25 +453,109 @@ fn build_router(state: AppState) -> Router {
         // Admin management routes
         .route("/admin/admins", get(routes::admin_mgmt::admins_list))
         .route("/admin/admins/add", post(routes::admin_mgmt::add_admin))
+        .route(
+            "/admin/profile",
+            get(routes::admin_mgmt::profile_page).post(routes::admin_mgmt::update_profile),
+        )
+        .route(
+            "/admin/admins/{id}/disable",
+            post(routes::admin_mgmt::disable_admin),
+        )
+        .route(
+            "/admin/admins/{id}/enable",
+            post(routes::admin_mgmt::enable_admin),
+        )
         .route(
             "/admin/admins/{id}/remove",
             post(routes::admin_mgmt::remove_admin),
         )
         .route("/admin/audit-log", get(routes::admin_mgmt::audit_log_page))
+        .route("/admin/jobs", get(routes::admin_mgmt::jobs_page))
+        .route("/admin/metrics", get(routes::admin_mgmt::metrics_page))
+        .route(
+            "/admin/link-domains",
+            get(routes::admin_mgmt::link_domains_page).post(routes::admin_mgmt::save_link_domain),
+        )
+        .route(
+            "/admin/link-domains/{domain}/delete",
+            post(routes::admin_mgmt::remove_link_domain),
+        )
+        .route(
+            "/admin/config/reload",
+            post(routes::admin_mgmt::reload_config),
+        )
+        .route(
+            "/admin/settings/test-smtp",
+            post(routes::admin_mgmt::test_smtp),
+        )
+        .route("/admin/replies", get(routes::reply::inbox))
+        .route(
+            "/admin/replies/{id}/handled",
+            post(routes::reply::toggle_handled),
+        )
         .layer(axum::middleware::from_fn_with_state(
             state.clone(),
             auth::admin_auth_middleware,
         ));
 
+    // Token-authenticated JSON API
+    let api_routes = Router::new()
+        .route(
+            "/api/v1/subscribers/import",
+            post(routes::api::import_subscribers)
+                .layer(axum::extract::DefaultBodyLimit::max(20 * 1024 * 1024)),
+        )
+        .route(
+            "/api/v1/subscribers/import/{id}",
+            get(routes::api::import_job_status),
+        )
+        .route(
+            "/api/v1/subscribers/tags",
+            post(routes::api::tag_subscribers),
+        )
+        .route("/api/v1/inbound", post(routes::api::inbound_reply))
+        .route(
+            "/api/v1/newsletters/compare",
+            get(routes::api::compare_newsletters),
+        )
+        .route(
+            "/api/v1/newsletters/{id}/stats",
+            get(routes::api::newsletter_stats),
+        )
+        .route(
+            "/api/v1/newsletters/{id}/send",
+            post(routes::api::send_newsletter),
+        )
+        .route(
+            "/api/v1/newsletters/{id}/schedule",
+            post(routes::api::schedule_newsletter),
+        )
+        .route("/api/v1/series/{id}/stats", get(routes::api::series_stats))
+        .route("/api/v1/stats/overview", get(routes::api::stats_overview))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::api_auth_middleware,
+        ));
+
     public_routes
         .merge(admin_routes)
+        .merge(api_routes)
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            metrics::metrics_middleware,
+        ))
         .nest_service("/uploads", ServeDir::new(&state.config.upload_dir))
         .nest_service("/static", ServeDir::new("static"))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            readiness::readiness_middleware,
+        ))
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
 #[tokio::main]
+#[allow(clippy::too_many_lines)]
 async fn main() {
     tracing_subscriber::fmt::init();
 
@@ -184,34 +563,70 @@ async fn main() {
 
     let config = config::AppConfig::from_env().expect("Failed to load config");
 
+    // Mirror audit entries to an external sink, if configured
+    if let Some(spec) = &config.audit_sink {
+        if let Some(sink) = audit::AuditSink::parse(spec) {
+            audit::start_forwarding(sink);
+        } else {
+            tracing::warn!("Ignoring AUDIT_SINK with unsupported scheme: {spec}");
+        }
+    }
+
     // Ensure upload directory exists
     std::fs::create_dir_all(&config.upload_dir).expect("Failed to create upload directory");
+    std::fs::create_dir_all(&config.attachment_dir).expect("Failed to create attachment directory");
 
-    let pool = db::create_pool(&config.database_url)
+    let pool = db::create_pool(&config.database_url, config.slow_query_ms)
         .await
         .expect("Failed to create DB pool");
 
-    db::run_migrations(&pool)
-        .await
-        .expect("Failed to run migrations");
-
-    db::sync_seed_admins(&pool, &config.admin_emails)
-        .await
-        .expect("Failed to sync seed admins");
+    let mut tera = tera::Tera::new("src/templates/**/*.html").expect("Failed to load templates");
+    timezone::register(&mut tera);
 
-    let tera = tera::Tera::new("src/templates/**/*.html").expect("Failed to load templates");
+    // Sign mail ourselves when the relay does not
+    let dkim = email::DkimSigner::from_config(&config)
+        .expect("Failed to load DKIM key")
+        .map(Arc::new);
 
-    let email_service: Arc<dyn EmailService> = Arc::new(
-        email::SmtpEmailService::new(
-            &config.smtp_host,
-            config.smtp_port,
-            config.smtp_username.as_deref(),
-            config.smtp_password.as_deref(),
-            config.smtp_tls,
+    let email_service: Arc<dyn EmailService> = match config.email_provider.as_str() {
+        "smtp" => Arc::new(
+            email::SmtpEmailService::new(
+                &config.smtp_host,
+                config.smtp_port,
+                config.smtp_username.as_deref(),
+                config.smtp_password.as_deref(),
+                config.smtp_tls,
+                config.smtp_from_email.clone(),
+            )
+            .expect("Failed to create SMTP email service")
+            .with_dkim(dkim.clone()),
+        ),
+        // DKIM_* is for SMTP; these sign with the keys set up for the domain there
+        "sendgrid" => Arc::new(
+            email_http::SendGridEmailService::new(
+                config
+                    .sendgrid_api_key
+                    .clone()
+                    .expect("EMAIL_PROVIDER=sendgrid needs SENDGRID_API_KEY"),
+                config.smtp_from_email.clone(),
+            )
+            .expect("Failed to create SendGrid email service"),
+        ),
+        "mailgun" => Arc::new(email_http::MailgunEmailService::new(
+            config
+                .mailgun_api_key
+                .clone()
+                .expect("EMAIL_PROVIDER=mailgun needs MAILGUN_API_KEY"),
+            &config.mailgun_api_base,
+            config
+                .mailgun_domain
+                .clone()
+                .or_else(|| email::from_domain(&config.smtp_from_email))
+                .expect("EMAIL_PROVIDER=mailgun needs MAILGUN_DOMAIN"),
             config.smtp_from_email.clone(),
-        )
-        .expect("Failed to create SMTP email service"),
-    );
+        )),
+        other => panic!("Unsupported EMAIL_PROVIDER: {other} (use smtp, sendgrid or mailgun)"),
+    };
 
     let captcha_verifier: Arc<dyn CaptchaVerifier> = Arc::new(captcha::TurnstileVerifier::new(
         config.turnstile_secret.clone(),
@@ -232,6 +647,83 @@ async fn main() {
         Arc::new(PassthroughShortUrlService)
     };
 
+    // Additional short-link domains selectable per newsletter
+    let short_domains: Vec<shorturl::ShortDomain> =
+        shorturl::parse_short_domains(&config.short_domains)
+            .into_iter()
+            .map(|(domain, api_url, signature)| shorturl::ShortDomain {
+                domain,
+                service: Arc::new(shorturl::YourlsService::new(api_url, signature)),
+            })
+            .collect();
+
+    // Additional sending identities selectable per newsletter
+    let identities: Vec<email::SendingIdentity> =
+        email::parse_sending_identities(&config.sending_identities)
+            .into_iter()
+            .filter_map(|spec| {
+                let service = email::SmtpEmailService::new(
+                    &spec.host,
+                    spec.port,
+                    spec.username.as_deref(),
+                    spec.password.as_deref(),
+                    config.smtp_tls,
+                    spec.from_email.clone(),
+                )
+                .inspect_err(|e| {
+                    tracing::warn!("Ignoring sending identity {}: {e}", spec.name);
+                })
+                .ok()?
+                .with_dkim(
+                    dkim.clone()
+                        .filter(|signer| signer.aligns_with(&spec.from_email)),
+                );
+                Some(email::SendingIdentity {
+                    name: spec.name,
+                    from_email: spec.from_email,
+                    service: Arc::new(service),
+                })
+            })
+            .collect();
+
+    // Warn early if a relay is unreachable or rejects the credentials
+    let mut relays = vec![("default".to_string(), email_service.clone())];
+    relays.extend(
+        identities
+            .iter()
+            .map(|i| (i.name.clone(), i.service.clone())),
+    );
+    tokio::spawn(email::check_connections(relays));
+
+    let oidc_provider: Option<Arc<dyn oidc::OidcProvider>> = match (
+        &config.oidc_issuer,
+        &config.oidc_client_id,
+        &config.oidc_client_secret,
+    ) {
+        (Some(issuer), Some(client_id), Some(secret)) => {
+            Some(Arc::new(oidc::HttpOidcProvider::new(
+                issuer,
+                client_id.clone(),
+                secret.clone(),
+                &config.base_url,
+            )))
+        }
+        (None, None, None) => None,
+        _ => {
+            tracing::warn!(
+                "OIDC sign-in disabled: OIDC_ISSUER, OIDC_CLIENT_ID and OIDC_CLIENT_SECRET must all be set"
+            );
+            None
+        }
+    };
+
+    // Tracking hits are buffered and written in batches
+    let (event_buffer, event_flusher) =
+        event_buffer::start(pool.clone(), config.scanner_click_window_secs);
+
+    let rate_limiter =
+        rate_limit::RateLimiter::new(Arc::new(rate_limit::PgRateLimitStore::new(pool.clone())));
+
     let state = AppState {
         db: pool,
         config: config.clone(),
@@ -239,38 +731,240 @@ async fn main() {
         email: email_service,
         captcha: captcha_verifier,
         shorturl: shorturl_service,
+        short_domains,
+        identities,
+        events: event_buffer,
+        topics: topics::TopicIds::default(),
+        images: Arc::new(image_proxy::HttpImageFetcher::new()),
+        oidc: oidc_provider,
+        rate_limiter,
+        click_guard: click_guard::ClickGuard::new(
+            config.click_limit_ucode,
+            config.click_limit_bad_hash_ip,
+            config.click_block_secs,
+        ),
+        readiness: readiness::Readiness::default(),
+        live: reload::LiveConfig::new(&config),
+        archive_cache: archive_cache::ArchiveCache::default(),
+        metrics: metrics::RouteMetrics::default(),
     };
 
+    // Listen right away so liveness checks pass while migrations run; every
+    // other route answers 503 until they are done
+    let server = start_server(&config, build_router(state.clone())).await;
+
+    db::run_migrations(&state.db)
+        .await
+        .expect("Failed to run migrations");
+
+    db::sync_seed_admins(&state.db, &config.admin_emails)
+        .await
+        .expect("Failed to sync seed admins");
+
+    match jobs::mark_interrupted(&state.db).await {
+        Ok(0) => {}
+        Ok(n) => {
+            tracing::warn!(
+                "Marked {n} background jobs cut off by the last shutdown as interrupted"
+            );
+        }
+        Err(e) => tracing::error!("Failed to mark interrupted background jobs: {e}"),
+    }
+
+    state.readiness.mark_migrated();
+
     // Spawn newsletter scheduler
     let scheduler_state = state.clone();
-    let scheduler_interval = config.newsletter_scheduler_interval_secs;
-    let rate_limit = config.smtp_rate_limit_ms;
+    tokio::spawn(newsletter::newsletter_scheduler(scheduler_state));
+
+    // Spawn the worker running queued sends
+    tokio::spawn(newsletter::send_worker(state.clone()));
+
+    // Reload rate limits, scheduler interval and seed admins on SIGHUP
+    tokio::spawn(reload::sighup_loop(state.clone()));
+
+    // Spawn cached stats refresh
+    let stats_db = state.db.clone();
+    let stats_interval = config.stats_refresh_interval_secs;
     tokio::spawn(async move {
-        newsletter::newsletter_scheduler(
-            scheduler_state.clone(),
-            scheduler_state.shorturl.clone(),
-            scheduler_interval,
-            rate_limit,
-        )
-        .await;
+        stats_cache::refresh_loop(stats_db, stats_interval).await;
+    });
+
+    // Spawn nightly subscriber count snapshots for the growth chart
+    let snapshot_db = state.db.clone();
+    tokio::spawn(async move {
+        snapshots::snapshot_loop(snapshot_db).await;
+    });
+
+    // Spawn pruning of log, token and session tables
+    let housekeeping_db = state.db.clone();
+    let retention = housekeeping::Retention {
+        log_days: config.log_retention_days,
+        token_days: config.token_retention_days,
+    };
+    let housekeeping_interval = config.housekeeping_interval_secs;
+    tokio::spawn(async move {
+        housekeeping::housekeeping_loop(housekeeping_db, retention, housekeeping_interval).await;
+    });
+
+    // Spawn verification reminders and pruning of unverified sign-ups
+    let verification_state = state.clone();
+    let prune_days = config.unverified_prune_days;
+    tokio::spawn(async move {
+        verification::verification_loop(verification_state, prune_days, housekeeping_interval)
+            .await;
+    });
+
+    // Spawn registration system sync (if any sources are configured)
+    let registration_sources = registration::sources_from_config(&config);
+    if registration_sources.is_empty() {
+        tracing::info!("Registration sync disabled (REGISTRATION_SYNC_SOURCES not set)");
+    } else {
+        let sync_state = state.clone();
+        let sync_interval = config.registration_sync_interval_secs;
+        tokio::spawn(async move {
+            registration::registration_sync_loop(sync_state, registration_sources, sync_interval)
+                .await;
+        });
+    }
+
+    // Spawn Google Sheets sync (a no-op until sheets are added)
+    let sheet_state = state.clone();
+    let sheet_interval = config.sheet_sync_interval_secs;
+    tokio::spawn(async move {
+        sheets::sheet_sync_loop(sheet_state, sheet_interval).await;
+    });
+
+    // Spawn tag rules, re-applied as opens and clicks come in
+    let tag_rules_db = state.db.clone();
+    tokio::spawn(async move {
+        tags::rules_loop(tag_rules_db, housekeeping_interval).await;
     });
 
-    let app = build_router(state);
+    // Spawn CRM lifecycle webhook delivery (if configured)
+    if let Some(hook) = crm_webhook::CrmWebhook::from_config(&config) {
+        let webhook_db = state.db.clone();
+        tokio::spawn(async move {
+            crm_webhook::delivery_loop(webhook_db, hook).await;
+        });
+    } else {
+        tracing::info!("CRM webhook disabled (CRM_WEBHOOK_URL or CRM_WEBHOOK_SECRET not set)");
+    }
+
+    // Spawn short-link click sync (if YOURLS is configured)
+    if config.short_link_sync_interval_secs == 0
+        || (config.yourls_api_url.is_none() && state.short_domains.is_empty())
+    {
+        tracing::info!("Short-link click sync disabled");
+    } else {
+        let click_db = state.db.clone();
+        let click_default = state.shorturl.clone();
+        let click_domains = state.short_domains.clone();
+        let click_interval = config.short_link_sync_interval_secs;
+        let click_days = config.short_link_sync_days;
+        tokio::spawn(async move {
+            shorturl::click_sync_loop(
+                click_db,
+                click_default,
+                click_domains,
+                click_interval,
+                click_days,
+            )
+            .await;
+        });
+    }
+
+    // Spawn nightly export to object storage (if configured)
+    if let (Some(endpoint), Some(bucket), Some(access_key_id), Some(secret_access_key)) = (
+        &config.s3_endpoint,
+        &config.s3_bucket,
+        &config.s3_access_key_id,
+        &config.s3_secret_access_key,
+    ) {
+        let store: Arc<dyn storage::ObjectStore> = Arc::new(storage::S3ObjectStore::new(
+            endpoint,
+            config.s3_region.clone(),
+            bucket.clone(),
+            access_key_id.clone(),
+            secret_access_key.clone(),
+        ));
+        let settings = backup::ExportSettings {
+            prefix: config.export_prefix.clone(),
+            retention_days: config.export_retention_days,
+            hour: config.export_hour,
+        };
+        let export_db = state.db.clone();
+        tokio::spawn(async move {
+            backup::export_loop(export_db, store, settings).await;
+        });
+    } else {
+        tracing::info!("Nightly export disabled (S3_ENDPOINT / S3_BUCKET / credentials not set)");
+    }
+
+    readiness::sd_notify("READY=1");
+
+    server.await.expect("Server task failed");
 
+    // Write out tracking events still buffered after the last requests finished
+    event_flusher.shutdown().await;
+}
+
+/// Bind `PORT` and serve `app` in the background: over HTTPS when a
+/// certificate is configured (plus HSTS and the optional HTTP redirect
+/// listener), plain HTTP otherwise.
+async fn start_server(config: &config::AppConfig, app: Router) -> tokio::task::JoinHandle<()> {
     let addr = format!("{}:{}", config.host, config.port);
-    tracing::info!("Starting server on {addr}");
 
-    let listener = tokio::net::TcpListener::bind(&addr)
+    let (Some(cert_path), Some(key_path)) = (&config.tls_cert_path, &config.tls_key_path) else {
+        tracing::info!("Starting server on {addr}");
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .expect("Failed to bind");
+        return tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+            .expect("Server error");
+        });
+    };
+
+    let rustls = tls::load(cert_path, key_path)
         .await
-        .expect("Failed to bind");
+        .expect("Failed to load TLS certificate");
+    let app = match tls::hsts_header(config.hsts_max_age_secs) {
+        Some(hsts) => app.layer(axum::middleware::from_fn_with_state(
+            hsts,
+            tls::hsts_middleware,
+        )),
+        None => app,
+    };
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await
-    .expect("Server error");
+    if let Some(redirect_port) = config.http_redirect_port {
+        let redirect_addr = format!("{}:{redirect_port}", config.host);
+        let listener = tokio::net::TcpListener::bind(&redirect_addr)
+            .await
+            .expect("Failed to bind HTTP redirect port");
+        tracing::info!("Redirecting HTTP on {redirect_addr} to HTTPS");
+        tokio::spawn(tls::serve_redirect(
+            listener,
+            config.port,
+            shutdown_signal(),
+        ));
+    }
+
+    tracing::info!("Starting HTTPS server on {addr}");
+    let listener = std::net::TcpListener::bind(&addr).expect("Failed to bind");
+    listener
+        .set_nonblocking(true)
+        .expect("Failed to configure listener");
+    tokio::spawn(async move {
+        tls::serve(listener, rustls, app, shutdown_signal())
+            .await
+            .expect("Server error");
+    })
 }
 
 /// Passthrough service that returns original URLs when YOURLS is not configured.
@@ -282,6 +976,14 @@ impl ShortUrlService for PassthroughShortUrlService {
         Ok(url.to_string())
     }
 
+    async fn shorten_custom(
+        &self,
+        url: &str,
+        _keyword: &str,
+    ) -> Result<String, shorturl::ShortUrlError> {
+        Ok(url.to_string())
+    }
+
     async fn get_clicks(&self, _short_url: &str) -> Result<u64, shorturl::ShortUrlError> {
         Ok(0)
     }
@@ -292,4 +994,5 @@ async fn shutdown_signal() {
         .await
         .expect("Failed to install signal handler");
     tracing::info!("Shutting down...");
+    readiness::sd_notify("STOPPING=1");
 }
@@ -0,0 +1,208 @@
+//! Per-route request counters, kept in memory since the last restart, so
+//! admins can see which pages slow down as the tables grow
+//! (`/admin/metrics`).
+//!
+//! Slow database queries are logged by sqlx itself, with the start of the
+//! statement as its tag (see `db::create_pool` and `SLOW_QUERY_MS`). Every
+//! request runs in a `route` span, so those warnings also name the page that
+//! ran the query.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, State};
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Serialize;
+use tracing::Instrument;
+
+use crate::AppState;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RouteStats {
+    requests: u64,
+    /// By status class: 2xx, 3xx, 4xx, 5xx
+    by_class: [u64; 4],
+    total: Duration,
+    max: Duration,
+}
+
+impl RouteStats {
+    fn record(&mut self, status: StatusCode, elapsed: Duration) {
+        self.requests += 1;
+        if let Some(count) = (status.as_u16() / 100)
+            .checked_sub(2)
+            .and_then(|class| self.by_class.get_mut(usize::from(class)))
+        {
+            *count += 1;
+        }
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+}
+
+/// One row of the metrics page.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RouteSummary {
+    pub method: String,
+    /// The route pattern, e.g. `/admin/newsletters/{id}`
+    pub route: String,
+    pub requests: u64,
+    pub success: u64,
+    pub redirects: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub mean_ms: u64,
+    pub max_ms: u64,
+    pub total_ms: u64,
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[derive(Clone, Default)]
+pub struct RouteMetrics {
+    routes: Arc<Mutex<HashMap<(String, String), RouteStats>>>,
+}
+
+impl RouteMetrics {
+    pub fn record(&self, method: &str, route: &str, status: StatusCode, elapsed: Duration) {
+        self.routes
+            .lock()
+            .unwrap()
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .record(status, elapsed);
+    }
+
+    /// All routes requested so far, those that took the most time in total
+    /// first.
+    pub fn summary(&self) -> Vec<RouteSummary> {
+        let mut rows: Vec<RouteSummary> = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((method, route), stats)| {
+                let [success, redirects, client_errors, server_errors] = stats.by_class;
+                RouteSummary {
+                    method: method.clone(),
+                    route: route.clone(),
+                    requests: stats.requests,
+                    success,
+                    redirects,
+                    client_errors,
+                    server_errors,
+                    mean_ms: millis(stats.total) / stats.requests.max(1),
+                    max_ms: millis(stats.max),
+                    total_ms: millis(stats.total),
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.total_ms
+                .cmp(&a.total_ms)
+                .then_with(|| a.route.cmp(&b.route))
+                .then_with(|| a.method.cmp(&b.method))
+        });
+        rows
+    }
+}
+
+/// Time each request by its route pattern, and run it in a `route` span so
+/// slow query warnings say where they came from.
+pub async fn metrics_middleware(
+    State(state): State<AppState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let Some(route) = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+    else {
+        return next.run(req).await;
+    };
+    let method = req.method().to_string();
+    let span = tracing::info_span!("route", route = %route, method = %method);
+
+    let started = Instant::now();
+    let response = next.run(req).instrument(span).await;
+    state
+        .metrics
+        .record(&method, &route, response.status(), started.elapsed());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let metrics = RouteMetrics::default();
+        let ms = Duration::from_millis;
+        metrics.record("GET", "/admin/subscribers", StatusCode::OK, ms(120));
+        metrics.record("GET", "/admin/subscribers", StatusCode::OK, ms(480));
+        metrics.record("GET", "/admin/subscribers", StatusCode::NOT_FOUND, ms(3));
+        metrics.record("POST", "/subscribe", StatusCode::SEE_OTHER, ms(40));
+        metrics.record("POST", "/subscribe", StatusCode::BAD_GATEWAY, ms(20));
+
+        let rows = metrics.summary();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            RouteSummary {
+                method: "GET".to_string(),
+                route: "/admin/subscribers".to_string(),
+                requests: 3,
+                success: 2,
+                redirects: 0,
+                client_errors: 1,
+                server_errors: 0,
+                mean_ms: 201,
+                max_ms: 480,
+                total_ms: 603,
+            }
+        );
+        assert_eq!(rows[1].route, "/subscribe");
+        assert_eq!((rows[1].redirects, rows[1].server_errors), (1, 1));
+    }
+
+    #[test]
+    fn test_informational_status_only_counted_as_request() {
+        let mut stats = RouteStats::default();
+        stats.record(StatusCode::SWITCHING_PROTOCOLS, Duration::ZERO);
+        assert_eq!(stats.requests, 1);
+        assert_eq!(stats.by_class, [0; 4]);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_records_route_pattern() {
+        let app = crate::test_utils::TestStateBuilder::without_db().build();
+        app.get("/health/live").await;
+        app.get("/no/such/page").await;
+
+        let rows = app.state.metrics.summary();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            (rows[0].route.as_str(), rows[0].success),
+            ("/health/live", 1)
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_metrics_page(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        app.get("/admin/subscribers").await;
+
+        let response = app.get("/admin/metrics").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.body.contains("/admin/subscribers"));
+    }
+}
@@ -1,42 +1,262 @@
+use futures_util::StreamExt;
 use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
 
+use crate::email::EmailService;
+use crate::html_rewrite::Rewriter;
+use crate::jobs::JobKind;
 use crate::security;
 use crate::shorturl::ShortUrlService;
+use crate::throttle::{AdaptiveThrottle, SendPacer};
 use crate::AppState;
 
-/// Convert Markdown to HTML using comrak, absolutize relative image srcs,
-/// and add inline styles on `<img>` tags so images display properly in email clients.
+/// Convert Markdown to HTML using comrak and run the default render hooks:
+/// absolutize relative image srcs and add inline styles on `<img>` tags so
+/// images display properly in email clients. Sponsor shortcodes expand to
+/// nothing and event dates are left unresolved.
 pub fn render_markdown(md: &str, base_url: &str) -> String {
-    use comrak::{markdown_to_html, Options};
+    RenderPipeline::default().render(
+        md,
+        &HookContext {
+            base_url,
+            slug: "",
+            sponsors: &[],
+            today: crate::timezone::today(),
+            event: crate::shortcodes::EventDates::default(),
+        },
+    )
+}
+
+fn markdown_to_html(md: &str) -> String {
+    use comrak::Options;
     let mut options = Options::default();
     options.extension.strikethrough = true;
     options.extension.table = true;
     options.extension.autolink = true;
     options.render.unsafe_ = true;
-    let html = markdown_to_html(md, &options);
-    let html = style_images_for_email(&html);
-    absolutize_image_srcs(&html, base_url)
+    comrak::markdown_to_html(md, &options)
+}
+
+/// A transform applied to a newsletter's HTML after Markdown rendering.
+/// Content hooks run once per edition; recipient hooks run for each
+/// subscriber when sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderHook {
+    Shortcodes,
+    StyleImages,
+    AbsolutizeImages,
+    UtmTags,
+    ClickTracking,
+}
+
+impl RenderHook {
+    pub const ALL: [Self; 5] = [
+        Self::Shortcodes,
+        Self::StyleImages,
+        Self::AbsolutizeImages,
+        Self::UtmTags,
+        Self::ClickTracking,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Shortcodes => "shortcodes",
+            Self::StyleImages => "style_images",
+            Self::AbsolutizeImages => "absolutize_images",
+            Self::UtmTags => "utm_tags",
+            Self::ClickTracking => "click_tracking",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|h| h.as_str() == s)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Shortcodes => "展開短代碼（{{countdown}}、{{event_dates}}、{{sponsors}}）",
+            Self::StyleImages => "圖片加上郵件用樣式",
+            Self::AbsolutizeImages => "圖片改為完整網址",
+            Self::UtmTags => "連結加上 UTM 參數",
+            Self::ClickTracking => "點擊追蹤",
+        }
+    }
+
+    /// Whether the hook needs the subscriber (and so runs at send time).
+    pub fn is_per_recipient(self) -> bool {
+        matches!(self, Self::ClickTracking)
+    }
+}
+
+/// What content hooks know about the newsletter being rendered.
+pub struct HookContext<'a> {
+    pub base_url: &'a str,
+    /// Newsletter slug, used as the UTM campaign
+    pub slug: &'a str,
+    /// Sponsors in the `%sponsors%` block, in block order
+    pub sponsors: &'a [crate::sponsors::Sponsor],
+    /// Send date that date shortcodes count from (Taiwan time)
+    pub today: chrono::NaiveDate,
+    pub event: crate::shortcodes::EventDates,
+}
+
+/// The render hooks of one newsletter, in the order they run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderPipeline {
+    hooks: Vec<RenderHook>,
+}
+
+impl Default for RenderPipeline {
+    fn default() -> Self {
+        Self {
+            hooks: vec![
+                RenderHook::Shortcodes,
+                RenderHook::StyleImages,
+                RenderHook::AbsolutizeImages,
+                RenderHook::ClickTracking,
+            ],
+        }
+    }
+}
+
+impl RenderPipeline {
+    /// Pipeline stored with a newsletter; `NULL` (or anything unreadable)
+    /// means the default hooks.
+    pub fn from_json(value: Option<&serde_json::Value>) -> Self {
+        value
+            .and_then(|v| serde_json::from_value::<Vec<String>>(v.clone()).ok())
+            .map(|names| Self {
+                hooks: names.iter().filter_map(|n| RenderHook::parse(n)).collect(),
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::Value::from(self.hooks.iter().map(|h| h.as_str()).collect::<Vec<_>>())
+    }
+
+    /// Markdown to HTML, then every content hook in order.
+    pub fn render(&self, md: &str, ctx: &HookContext<'_>) -> String {
+        self.apply_content(markdown_to_html(md), ctx)
+    }
+
+    pub fn apply_content(&self, html: String, ctx: &HookContext<'_>) -> String {
+        self.hooks
+            .iter()
+            .filter(|h| !h.is_per_recipient())
+            .fold(html, |html, hook| match hook {
+                RenderHook::Shortcodes => crate::shortcodes::expand(
+                    &html,
+                    &crate::shortcodes::Values {
+                        today: ctx.today,
+                        event: ctx.event,
+                        sponsor_block: &crate::sponsors::render_block(ctx.sponsors, ctx.base_url),
+                    },
+                ),
+                RenderHook::StyleImages => style_images_for_email(&html),
+                RenderHook::AbsolutizeImages => absolutize_image_srcs(&html, ctx.base_url),
+                RenderHook::UtmTags => add_utm_tags(&html, ctx.slug),
+                RenderHook::ClickTracking => html,
+            })
+    }
+
+    /// Every recipient hook in order, for one subscriber.
+    pub fn apply_recipient(
+        &self,
+        html: &str,
+        base_url: &str,
+        ucode: &str,
+        topic: &str,
+        secret_code: &str,
+    ) -> String {
+        self.hooks
+            .iter()
+            .filter(|h| h.is_per_recipient())
+            .fold(html.to_string(), |html, hook| match hook {
+                RenderHook::ClickTracking => {
+                    rewrite_links_for_tracking(&html, base_url, ucode, topic, secret_code)
+                }
+                RenderHook::Shortcodes
+                | RenderHook::StyleImages
+                | RenderHook::AbsolutizeImages
+                | RenderHook::UtmTags => html,
+            })
+    }
+}
+
+/// Parse the render hooks field: hook names separated by commas or new
+/// lines, run in the order given. Empty means the default pipeline.
+pub fn parse_render_hooks(input: &str) -> Result<Option<RenderPipeline>, String> {
+    let mut hooks = Vec::new();
+    for name in input
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        let hook = RenderHook::parse(name).ok_or_else(|| format!("未知的轉換步驟：{name}"))?;
+        if hooks.contains(&hook) {
+            return Err(format!("轉換步驟重複：{name}"));
+        }
+        hooks.push(hook);
+    }
+    Ok((!hooks.is_empty()).then_some(RenderPipeline { hooks }))
+}
+
+/// Inverse of `parse_render_hooks`, for the edit form.
+pub fn format_render_hooks(pipeline: &RenderPipeline) -> String {
+    pipeline
+        .hooks
+        .iter()
+        .map(|h| h.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `utm_source` and `utm_medium` added by the UTM hook.
+const UTM_SOURCE: &str = "newsletter";
+const UTM_MEDIUM: &str = "email";
+
+/// Add `utm_source`, `utm_medium` and `utm_campaign` (the newsletter slug)
+/// to http/https links, before any fragment. Links that already carry a
+/// `utm_source` are left as they are.
+pub fn add_utm_tags(html: &str, campaign: &str) -> String {
+    Rewriter::new()
+        .links(|url| {
+            if !is_http_url(url) || url.contains("utm_source=") {
+                return None;
+            }
+            let (url, fragment) = url.split_at(url.find('#').unwrap_or(url.len()));
+            let separator = if url.contains('?') { '&' } else { '?' };
+            Some(format!(
+                "{url}{separator}utm_source={UTM_SOURCE}&utm_medium={UTM_MEDIUM}&utm_campaign={}{fragment}",
+                urlencoding::encode(campaign)
+            ))
+        })
+        .rewrite(html)
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
 }
 
-/// Rewrite relative `src` attributes (e.g. `/uploads/...`) to absolute URLs
-/// so that images display correctly in email clients.
+/// Rewrite site-relative image URLs (e.g. `/uploads/...`) in `src` and
+/// `srcset` to absolute URLs so that images display correctly in email clients.
 pub fn absolutize_image_srcs(html: &str, base_url: &str) -> String {
-    let re = Regex::new(r#"src="(/[^"]+)""#).expect("valid regex");
-    re.replace_all(html, |caps: &regex::Captures| {
-        format!("src=\"{}{}\"", base_url, &caps[1])
-    })
-    .into_owned()
+    Rewriter::new()
+        .images(|url| {
+            (url.starts_with('/') && !url.starts_with("//")).then(|| format!("{base_url}{url}"))
+        })
+        .rewrite(html)
 }
 
 /// Add inline styles to `<img>` tags so images display properly in email clients
-/// without breaking layout.
+/// without breaking layout. Styles the image already has come after, so they win.
 fn style_images_for_email(html: &str) -> String {
-    let re = Regex::new(r"<img\b").expect("valid regex");
-    re.replace_all(
-        html,
-        r#"<img style="max-width:100%;height:auto;display:block;""#,
-    )
-    .into_owned()
+    Rewriter::new()
+        .image_style("max-width:100%;height:auto;display:block;")
+        .rewrite(html)
 }
 
 /// Sanitize HTML for public web display: strip `<script>`, event handlers,
@@ -55,35 +275,36 @@ pub fn replace_recipient_name(html: &str, name: &str) -> String {
     html.replace("%recipient_name%", name)
 }
 
-/// Find all `<a href="...">` links in HTML, shorten them via `ShortUrlService`,
+/// Find all `<a href>` links in HTML, shorten them via `ShortUrlService`,
 /// and return (rewritten HTML, list of (original, short) pairs).
-/// Skips mailto:, tel:, and anchor (#) links.
+/// Only http/https links are shortened, so mailto:, tel:, anchor (#) and
+/// template variable links are kept. URLs in `custom_slugs` (URL → slug)
+/// get their custom slug, or a generated one if it is unavailable.
 pub async fn shorten_links(
     html: &str,
     svc: &dyn ShortUrlService,
+    custom_slugs: &BTreeMap<String, String>,
 ) -> (String, Vec<(String, String)>) {
-    let re = Regex::new(r#"<a\s[^>]*href\s*=\s*"([^"]+)"#).expect("valid regex");
-
     let mut link_map: Vec<(String, String)> = Vec::new();
     let mut seen: std::collections::HashMap<String, String> = std::collections::HashMap::new();
 
     // Collect all unique URLs to shorten
-    for cap in re.captures_iter(html) {
-        let url = cap[1].to_string();
-        if url.starts_with("mailto:")
-            || url.starts_with("tel:")
-            || url.starts_with('#')
-            || url.starts_with("{{")
-        {
-            continue;
-        }
-        if !url.starts_with("http://") && !url.starts_with("https://") {
-            continue;
-        }
-        if seen.contains_key(&url) {
+    for url in crate::html_rewrite::link_urls(html) {
+        if !is_http_url(&url) || seen.contains_key(&url) {
             continue;
         }
-        match svc.shorten(&url).await {
+        let custom = custom_slugs.get(&url);
+        let result = match custom {
+            Some(slug) => match svc.shorten_custom(&url, slug).await {
+                Ok(short) => Ok(short),
+                Err(e) => {
+                    tracing::warn!("Custom slug {slug} for {url} failed: {e}");
+                    svc.shorten(&url).await
+                }
+            },
+            None => svc.shorten(&url).await,
+        };
+        match result {
             Ok(short) => {
                 seen.insert(url.clone(), short.clone());
                 link_map.push((url, short));
@@ -96,19 +317,20 @@ pub async fn shorten_links(
     }
 
     // Replace all occurrences in HTML
-    let mut result = html.to_string();
-    for (original, short) in &link_map {
-        result = result.replace(
-            &format!("href=\"{original}\""),
-            &format!("href=\"{short}\""),
-        );
-    }
+    let shortened: std::collections::HashMap<&str, &str> = link_map
+        .iter()
+        .map(|(original, short)| (original.as_str(), short.as_str()))
+        .collect();
+    let result = Rewriter::new()
+        .links(|url| shortened.get(url).map(|short| (*short).to_string()))
+        .rewrite(html);
 
     (result, link_map)
 }
 
 /// Personalize the email template for a specific subscriber.
 /// Fills in `{{ content }}`, `{{ title }}`, `{{ tracking_pixel }}`, `{{ unsubscribe_url }}`.
+#[allow(clippy::too_many_arguments)]
 pub fn personalize_email(
     template_html: &str,
     content_html: &str,
@@ -117,6 +339,7 @@ pub fn personalize_email(
     unsubscribe_url: &str,
     base_url: &str,
     web_url: &str,
+    language: ContentLanguage<'_>,
 ) -> Result<String, tera::Error> {
     let mut ctx = tera::Context::new();
     ctx.insert("content", content_html);
@@ -125,12 +348,336 @@ pub fn personalize_email(
     ctx.insert("unsubscribe_url", unsubscribe_url);
     ctx.insert("base_url", base_url);
     ctx.insert("web_url", web_url);
+    ctx.insert("lang", language.lang);
+    ctx.insert("dir", language.dir);
+
+    let html = tera::Tera::one_off(template_html, &ctx, false)?;
+    Ok(set_root_language(&html, language))
+}
+
+/// Stand-in for `{{ unsubscribe_url }}` when checking that a template renders it.
+const UNSUBSCRIBE_PROBE: &str = "https://unsubscribe.invalid/probe";
+
+/// Whether the template's output still contains the `{{ unsubscribe_url }}`
+/// substitution. Every newsletter must carry a working unsubscribe link.
+pub fn has_unsubscribe_link(template_html: &str) -> Result<bool, tera::Error> {
+    let html = personalize_email(
+        template_html,
+        "",
+        "",
+        "",
+        UNSUBSCRIBE_PROBE,
+        "",
+        "",
+        ContentLanguage::default(),
+    )?;
+    Ok(html.contains(UNSUBSCRIBE_PROBE))
+}
+
+/// Organization details every newsletter carries in its footer, from
+/// `ORG_POSTAL_ADDRESS` and `LEGAL_FOOTER`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComplianceFooter<'a> {
+    pub postal_address: Option<&'a str>,
+    pub legal_footer: Option<&'a str>,
+}
+
+impl<'a> ComplianceFooter<'a> {
+    pub fn from_config(config: &'a crate::config::AppConfig) -> Self {
+        Self {
+            postal_address: config.postal_address.as_deref(),
+            legal_footer: config.legal_footer.as_deref(),
+        }
+    }
+}
+
+pub const POSTAL_ADDRESS_PLACEHOLDER: &str = "%postal_address%";
+pub const LEGAL_FOOTER_PLACEHOLDER: &str = "%legal_footer%";
+
+/// Escape a footer value for HTML, keeping its line breaks.
+fn footer_html(text: &str) -> String {
+    tera::escape_html(text).replace('\n', "<br>")
+}
+
+/// `footer_html`, kept out of Tera's way since the template is rendered after.
+fn footer_raw(text: &str) -> String {
+    format!("{{% raw %}}{}{{% endraw %}}", footer_html(text))
+}
+
+/// Fill the template's `%postal_address%` and `%legal_footer%` placeholders.
+/// A template using neither gets a footer block with both before `</body>`
+/// (or at the end).
+pub fn apply_compliance_footer(template_html: &str, footer: ComplianceFooter<'_>) -> String {
+    let address = footer.postal_address.map(footer_raw).unwrap_or_default();
+    let legal = footer.legal_footer.map(footer_raw).unwrap_or_default();
+    if template_html.contains(POSTAL_ADDRESS_PLACEHOLDER)
+        || template_html.contains(LEGAL_FOOTER_PLACEHOLDER)
+    {
+        return template_html
+            .replace(POSTAL_ADDRESS_PLACEHOLDER, &address)
+            .replace(LEGAL_FOOTER_PLACEHOLDER, &legal);
+    }
+    if address.is_empty() && legal.is_empty() {
+        return template_html.to_string();
+    }
+
+    let lines: Vec<&str> = [address.as_str(), legal.as_str()]
+        .into_iter()
+        .filter(|l| !l.is_empty())
+        .collect();
+    let block = format!(
+        "<div class=\"compliance-footer\" style=\"padding:16px;text-align:center;font-size:12px;color:#718096;\">{}</div>",
+        lines
+            .iter()
+            .fold(String::new(), |mut out, l| {
+                let _ = write!(out, "<p style=\"margin:4px 0;\">{l}</p>");
+                out
+            })
+    );
+    match template_html.rfind("</body>") {
+        Some(pos) => format!("{}{block}{}", &template_html[..pos], &template_html[pos..]),
+        None => format!("{template_html}{block}"),
+    }
+}
+
+/// A legally required element (CAN-SPAM) missing from a newsletter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComplianceProblem {
+    MissingUnsubscribeLink,
+    NoPostalAddress,
+    /// Configured, but the template doesn't render it
+    PostalAddressHidden,
+}
+
+impl ComplianceProblem {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::MissingUnsubscribeLink => {
+                "模板缺少退訂連結（{{ unsubscribe_url }}），請在模板中加入退訂連結"
+            }
+            Self::NoPostalAddress => "尚未設定實體郵寄地址（ORG_POSTAL_ADDRESS）",
+            Self::PostalAddressHidden => "模板沒有顯示實體郵寄地址（%postal_address%）",
+        }
+    }
+}
+
+/// Check a template, with the compliance footer already applied, for the
+/// elements every newsletter must carry.
+pub fn compliance_problems(
+    template_html: &str,
+    footer: ComplianceFooter<'_>,
+) -> Result<Vec<ComplianceProblem>, tera::Error> {
+    let mut problems = Vec::new();
+    if !has_unsubscribe_link(template_html)? {
+        problems.push(ComplianceProblem::MissingUnsubscribeLink);
+    }
+    match footer.postal_address {
+        None => problems.push(ComplianceProblem::NoPostalAddress),
+        Some(address) => {
+            let html = personalize_email(
+                template_html,
+                "",
+                "",
+                "",
+                UNSUBSCRIBE_PROBE,
+                "",
+                "",
+                ContentLanguage::default(),
+            )?;
+            if !html.contains(&footer_html(address)) {
+                problems.push(ComplianceProblem::PostalAddressHidden);
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Language and text direction of a newsletter's content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLanguage<'a> {
+    /// BCP 47 tag, e.g. `zh-TW` or `en`
+    pub lang: &'a str,
+    /// `ltr` or `rtl`
+    pub dir: &'a str,
+}
+
+impl Default for ContentLanguage<'_> {
+    fn default() -> Self {
+        Self {
+            lang: "zh-TW",
+            dir: "ltr",
+        }
+    }
+}
+
+/// Normalize a language tag such as `en-us` to `en-US`; `None` if it isn't a
+/// plausible BCP 47 tag.
+pub fn parse_lang(tag: &str) -> Option<String> {
+    let re = Regex::new(r"^[A-Za-z]{2,3}(-[A-Za-z0-9]{2,8})*$").expect("valid regex");
+    let tag = tag.trim();
+    if !re.is_match(tag) {
+        return None;
+    }
+    let mut parts = tag.split('-');
+    let mut normalized = parts.next().unwrap_or_default().to_ascii_lowercase();
+    for part in parts {
+        normalized.push('-');
+        match part.len() {
+            2 => normalized.push_str(&part.to_ascii_uppercase()),
+            4 => {
+                normalized.push_str(&part[..1].to_ascii_uppercase());
+                normalized.push_str(&part[1..].to_ascii_lowercase());
+            }
+            _ => normalized.push_str(&part.to_ascii_lowercase()),
+        }
+    }
+    Some(normalized)
+}
+
+/// Set `lang` and `dir` on the `<html>` root, replacing any the template has.
+/// HTML without a root element is returned unchanged.
+fn set_root_language(html: &str, language: ContentLanguage<'_>) -> String {
+    let root_re = Regex::new(r"(?i)<html\b[^>]*>").expect("valid regex");
+    let attr_re =
+        Regex::new(r#"(?i)\s(lang|dir)\s*=\s*("[^"]*"|'[^']*'|[^\s>]+)"#).expect("valid regex");
+    let Some(root) = root_re.find(html) else {
+        return html.to_string();
+    };
+    let tag = attr_re.replace_all(root.as_str(), "");
+    let tag = format!(
+        "{} lang=\"{}\" dir=\"{}\">",
+        tag.trim_end_matches('>').trim_end(),
+        language.lang,
+        language.dir
+    );
+    format!("{}{tag}{}", &html[..root.start()], &html[root.end()..])
+}
+
+/// Colors used when the reader's mail client is in dark mode.
+const DARK_MODE_RULES: &str = "body, table, td, th, div, p, li { background-color: #1a202c !important; color: #e2e8f0 !important; } \
+     h1, h2, h3, h4, h5, h6, strong { color: #f7fafc !important; } \
+     a { color: #90cdf4 !important; } \
+     hr { border-color: #4a5568 !important; } \
+     blockquote, pre, code { background-color: #2d3748 !important; color: #e2e8f0 !important; }";
+
+/// Declare light and dark support and add dark color overrides to a template
+/// or rendered email. With `force` the overrides apply unconditionally, to
+/// preview how a dark-mode client shows the message.
+pub fn apply_dark_mode(html: &str, force: bool) -> String {
+    let rules = if force {
+        DARK_MODE_RULES.to_string()
+    } else {
+        format!("@media (prefers-color-scheme: dark) {{ {DARK_MODE_RULES} }}")
+    };
+    let head = format!(
+        "<meta name=\"color-scheme\" content=\"light dark\">\
+         <meta name=\"supported-color-schemes\" content=\"light dark\">\
+         <style>:root {{ color-scheme: light dark; supported-color-schemes: light dark; }} {rules}</style>"
+    );
+
+    let re = Regex::new(r"(?i)</head>").expect("valid regex");
+    match re.find(html) {
+        Some(m) => format!("{}{head}{}", &html[..m.start()], &html[m.start()..]),
+        None => format!("{head}{html}"),
+    }
+}
+
+/// Gmail clips HTML bodies larger than about 102KB, hiding everything after
+/// the cut, including the unsubscribe footer.
+pub const GMAIL_CLIP_LIMIT_BYTES: usize = 102 * 1024;
+
+/// How close a message is to Gmail's clipping limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipRisk {
+    Ok,
+    /// Within 10% of the limit
+    Near,
+    Over,
+}
+
+pub fn clip_risk(size_bytes: usize) -> ClipRisk {
+    if size_bytes > GMAIL_CLIP_LIMIT_BYTES {
+        ClipRisk::Over
+    } else if size_bytes * 10 >= GMAIL_CLIP_LIMIT_BYTES * 9 {
+        ClipRisk::Near
+    } else {
+        ClipRisk::Ok
+    }
+}
+
+/// Size in KB with one decimal, e.g. `95.3`.
+pub fn format_kb(size_bytes: usize) -> String {
+    format!("{}.{}", size_bytes / 1024, size_bytes % 1024 * 10 / 1024)
+}
 
-    tera::Tera::one_off(template_html, &ctx, false)
+/// Typical time for the relay to accept one message, on top of the configured
+/// delay between sends.
+pub const ASSUMED_SMTP_MS: u64 = 200;
+
+/// How long sending to `recipients` takes when `concurrency` messages are in
+/// flight at once and each is followed by `delay_ms`.
+pub fn estimate_send_duration(
+    recipients: i64,
+    delay_ms: u64,
+    concurrency: u64,
+) -> chrono::Duration {
+    let recipients = u64::try_from(recipients).unwrap_or(0);
+    let rounds = recipients.div_ceil(concurrency.max(1));
+    let millis = rounds.saturating_mul(delay_ms + ASSUMED_SMTP_MS);
+    chrono::Duration::milliseconds(i64::try_from(millis).unwrap_or(i64::MAX))
+}
+
+/// Rounded duration for humans, e.g. `2 小時 10 分`, `3 分` or `45 秒`.
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    if secs < 60 {
+        return format!("{secs} 秒");
+    }
+    // Round to the nearest minute
+    let minutes = (secs + 30) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{m} 分"),
+        (h, 0) => format!("{h} 小時"),
+        (h, m) => format!("{h} 小時 {m} 分"),
+    }
+}
+
+/// Render the email the way `send_newsletter` does for one subscriber, with
+/// dummy per-subscriber values of realistic length, to estimate its size.
+pub fn render_sample_email(
+    template_html: &str,
+    content_html: &str,
+    title: &str,
+    slug: &str,
+    base_url: &str,
+    language: ContentLanguage<'_>,
+    hooks: &RenderPipeline,
+) -> Result<String, tera::Error> {
+    let ucode = "00000000";
+    let secret_code = "0".repeat(64);
+    let openhash = security::compute_openhash(&secret_code, ucode, slug, "");
+    let tracked_html = hooks.apply_recipient(content_html, base_url, ucode, slug, &secret_code);
+    let unsubscribe_url = format!(
+        "{base_url}/manage/{}?from={}",
+        "0".repeat(64),
+        urlencoding::encode(slug)
+    );
+    personalize_email(
+        template_html,
+        &replace_recipient_name(&tracked_html, "王小明"),
+        title,
+        &build_tracking_pixel(base_url, ucode, slug, &openhash),
+        &unsubscribe_url,
+        base_url,
+        &format!("{base_url}/newsletters/{slug}"),
+        language,
+    )
 }
 
 /// Rewrite all http/https links in HTML to go through `/r/c` click tracking.
-/// Each link becomes `/r/c?ucode=...&topic=...&hash=...&url=<original>`.
+/// Each link becomes `/r/c?ucode=...&topic=...&hash=...&url=<original>`,
+/// with the original as the browser would request it (entities decoded).
 /// The hash is HMAC-SHA256 over (ucode, topic, url), so the URL is tamper-proof.
 /// This is per-subscriber (each subscriber gets their own hash per link).
 pub fn rewrite_links_for_tracking(
@@ -140,21 +687,22 @@ pub fn rewrite_links_for_tracking(
     topic: &str,
     secret_code: &str,
 ) -> String {
-    let re = Regex::new(r#"href="(https?://[^"]+)""#).expect("valid regex");
-    re.replace_all(html, |caps: &regex::Captures| {
-        let original_url = &caps[1];
-        let hash = security::compute_openhash(secret_code, ucode, topic, original_url);
-        let tracking_url = format!(
-            "{}/r/c?ucode={}&topic={}&hash={}&url={}",
-            base_url,
-            urlencoding::encode(ucode),
-            urlencoding::encode(topic),
-            urlencoding::encode(&hash),
-            urlencoding::encode(original_url),
-        );
-        format!("href=\"{tracking_url}\"")
-    })
-    .into_owned()
+    Rewriter::new()
+        .links(|original_url| {
+            if !is_http_url(original_url) {
+                return None;
+            }
+            let hash = security::compute_openhash(secret_code, ucode, topic, original_url);
+            Some(format!(
+                "{}/r/c?ucode={}&topic={}&hash={}&url={}",
+                base_url,
+                urlencoding::encode(ucode),
+                urlencoding::encode(topic),
+                urlencoding::encode(&hash),
+                urlencoding::encode(original_url),
+            ))
+        })
+        .rewrite(html)
 }
 
 /// Build a tracking pixel `<img>` tag for a specific subscriber.
@@ -169,26 +717,149 @@ pub fn build_tracking_pixel(base_url: &str, ucode: &str, topic: &str, openhash:
     format!("<img src=\"{pixel_url}\" width=\"1\" height=\"1\" alt=\"\" style=\"border:0;width:1px;height:1px;\" />")
 }
 
-/// Send a newsletter to all active+verified subscribers.
-/// This is meant to be called in a background task.
-#[allow(clippy::too_many_lines)]
-pub async fn send_newsletter(
+/// Build the RFC 2369 `List-Unsubscribe` and RFC 8058 `List-Unsubscribe-Post` headers.
+pub fn build_list_unsubscribe_headers(
+    one_click_url: &str,
+    unsubscribe_url: &str,
+) -> Vec<crate::email::EmailHeader> {
+    vec![
+        (
+            "List-Unsubscribe".to_string(),
+            format!("<{one_click_url}>, <{unsubscribe_url}>"),
+        ),
+        (
+            "List-Unsubscribe-Post".to_string(),
+            "List-Unsubscribe=One-Click".to_string(),
+        ),
+    ]
+}
+
+/// Build the list identity headers: RFC 2919 `List-ID`, `Precedence: bulk`,
+/// and Gmail's `Feedback-ID`. Each is omitted when not configured.
+pub fn build_list_identity_headers(
+    list_id: Option<&str>,
+    precedence_bulk: bool,
+    feedback_id: Option<&str>,
+) -> Vec<crate::email::EmailHeader> {
+    let mut headers = Vec::new();
+    if let Some(list_id) = list_id {
+        headers.push(("List-ID".to_string(), list_id.to_string()));
+    }
+    if precedence_bulk {
+        headers.push(("Precedence".to_string(), "bulk".to_string()));
+    }
+    if let Some(feedback_id) = feedback_id {
+        headers.push(("Feedback-ID".to_string(), feedback_id.to_string()));
+    }
+    headers
+}
+
+/// Gmail Postmaster `Feedback-ID` (`CampaignID:MailType:SenderId`); the
+/// newsletter id is used as campaign id so stats can be attributed per issue.
+pub fn build_feedback_id(newsletter_id: uuid::Uuid, sender_id: &str) -> String {
+    format!("{}:newsletter:{sender_id}", newsletter_id.simple())
+}
+
+/// Times a send deferred by relay rate limiting is retried (after backing
+/// off) before it is recorded as failed.
+const MAX_THROTTLE_RETRIES: u32 = 3;
+
+/// Recipient row loaded by `send_newsletter`: (id, email, name, ucode, `secret_code`, locale).
+type SubscriberRow = (uuid::Uuid, String, String, String, String, Option<String>);
+
+/// Split recipients into (to send, deferred) by the set of frequency-capped subscriber ids.
+fn partition_frequency_capped(
+    subscribers: Vec<SubscriberRow>,
+    capped: &HashSet<uuid::Uuid>,
+) -> (Vec<SubscriberRow>, Vec<SubscriberRow>) {
+    subscribers
+        .into_iter()
+        .partition(|(id, ..)| !capped.contains(id))
+}
+
+/// Recipients still to send to, in order, leaving out those a paused or
+/// interrupted run already sent to or failed on.
+fn pending_recipients(
+    mut subscribers: Vec<SubscriberRow>,
+    done: &HashSet<uuid::Uuid>,
+) -> Vec<SubscriberRow> {
+    subscribers.retain(|(id, ..)| !done.contains(id));
+    subscribers
+}
+
+/// Subscribers a newsletter was already sent to or failed on, with the
+/// number of each.
+async fn send_progress(
     state: &AppState,
     newsletter_id: uuid::Uuid,
-    shorturl_service: &dyn ShortUrlService,
-    rate_limit_ms: u64,
-) -> Result<(), String> {
-    // Load newsletter
-    let row = sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>)>(
-        "SELECT title, markdown_content, slug, template_id FROM newsletters WHERE id = $1",
+) -> Result<(HashSet<uuid::Uuid>, i32, i32), sqlx::Error> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String)>(
+        "SELECT subscriber_id, status FROM newsletter_sends \
+         WHERE newsletter_id = $1 AND status IN ('sent', 'failed')",
+    )
+    .bind(newsletter_id)
+    .fetch_all(&state.db)
+    .await?;
+    let sent = rows.iter().filter(|(_, status)| status == "sent").count();
+    let failed = rows.len() - sent;
+    Ok((
+        rows.into_iter().map(|(id, _)| id).collect(),
+        i32::try_from(sent).unwrap_or(i32::MAX),
+        i32::try_from(failed).unwrap_or(i32::MAX),
+    ))
+}
+
+/// Record progress after a batch, so a paused send shows where it stopped.
+async fn checkpoint(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    sent_count: i32,
+    failed_count: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE newsletters SET sent_count = $1, failed_count = $2, \
+         checkpoint_batches = checkpoint_batches + 1, checkpoint_at = NOW(), updated_at = NOW() \
+         WHERE id = $3",
     )
+    .bind(sent_count)
+    .bind(failed_count)
     .bind(newsletter_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+/// One language edition of a newsletter, rendered once for all its recipients.
+struct Edition {
+    id: uuid::Uuid,
+    /// Slug of the edition's own web archive page
+    slug: String,
+    title: String,
+    lang: String,
+    dir: String,
+    template_html: String,
+    /// Sanitized content with short links and proxied images
+    content_html: String,
+    /// Content hooks already ran; recipient hooks run per subscriber
+    hooks: RenderPipeline,
+    /// Links to blocked domains, found before the links were shortened
+    blocked_links: Vec<String>,
+}
+
+/// Render an edition's content (sanitized, links not shortened yet) and
+/// template, without writing anything.
+async fn render_edition(state: &AppState, edition_id: uuid::Uuid) -> Result<Edition, String> {
+    #[allow(clippy::type_complexity)]
+    let row = sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>, bool, String, String, Option<serde_json::Value>)>(
+        "SELECT title, markdown_content, slug, template_id, dark_mode, lang, dir, render_hooks FROM newsletters WHERE id = $1",
+    )
+    .bind(edition_id)
     .fetch_optional(&state.db)
     .await
     .map_err(|e| e.to_string())?
     .ok_or_else(|| "Newsletter not found".to_string())?;
 
-    let (title, markdown_content, slug, template_id) = row;
+    let (title, markdown_content, slug, template_id, dark_mode, lang, dir, render_hooks) = row;
 
     // Load template (use selected template, or fall back to coscup-default)
     let template_html = if let Some(tid) = template_id {
@@ -209,226 +880,781 @@ pub async fn send_newsletter(
         .await
         .map_err(|e| e.to_string())?,
     };
+    let sponsors = crate::sponsors::active_today(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(assemble_edition(
+        EditionSource {
+            id: edition_id,
+            title,
+            markdown_content,
+            slug,
+            dark_mode,
+            lang,
+            dir,
+            render_hooks,
+            template_html,
+        },
+        &state.config,
+        &sponsors,
+        crate::timezone::today(),
+    ))
+}
+
+/// An edition as loaded from the database, before rendering.
+struct EditionSource {
+    id: uuid::Uuid,
+    title: String,
+    markdown_content: String,
+    slug: String,
+    dark_mode: bool,
+    lang: String,
+    dir: String,
+    render_hooks: Option<serde_json::Value>,
+    template_html: String,
+}
+
+/// Render an edition's content through its content hooks and sanitize it,
+/// and add the compliance footer and dark mode to its template. Does no I/O,
+/// which is what the rendering snapshot tests rely on.
+fn assemble_edition(
+    source: EditionSource,
+    config: &crate::config::AppConfig,
+    sponsors: &[crate::sponsors::Sponsor],
+    today: chrono::NaiveDate,
+) -> Edition {
+    let template_html =
+        apply_compliance_footer(&source.template_html, ComplianceFooter::from_config(config));
+    let template_html = if source.dark_mode {
+        apply_dark_mode(&template_html, false)
+    } else {
+        template_html
+    };
 
-    // Render markdown → HTML (includes image src absolutization), then sanitize
-    let content_html = render_markdown(&markdown_content, &state.config.base_url);
+    // Render markdown → HTML through the newsletter's content hooks, then sanitize
+    let hooks = RenderPipeline::from_json(source.render_hooks.as_ref());
+    let content_html = hooks.render(
+        &source.markdown_content,
+        &HookContext {
+            base_url: &config.base_url,
+            slug: &source.slug,
+            sponsors,
+            today,
+            event: crate::shortcodes::EventDates::from_config(config),
+        },
+    );
     let content_html = sanitize_html(&content_html);
 
+    Edition {
+        id: source.id,
+        slug: source.slug,
+        title: source.title,
+        lang: source.lang,
+        dir: source.dir,
+        template_html,
+        content_html,
+        hooks,
+        blocked_links: Vec::new(),
+    }
+}
+
+/// Flagged links in an edition's content and template.
+fn edition_link_issues(
+    edition: &Edition,
+    rules: &crate::link_domains::Rules,
+) -> Vec<crate::link_domains::LinkIssue> {
+    let html = format!("{}\n{}", edition.content_html, edition.template_html);
+    crate::link_domains::check(&html, rules)
+}
+
+/// Flagged links in each edition of a newsletter, as (language, issues),
+/// for the send and schedule checks.
+pub async fn link_issues(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+) -> Result<Vec<(String, Vec<crate::link_domains::LinkIssue>)>, String> {
+    let rules = crate::link_domains::Rules::load(state)
+        .await
+        .map_err(|e| e.to_string())?;
+    let edition_ids = sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT id FROM newsletters WHERE id = $1 OR parent_id = $1 \
+         ORDER BY parent_id NULLS FIRST, lang",
+    )
+    .bind(newsletter_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut issues = Vec::with_capacity(edition_ids.len());
+    for edition_id in edition_ids {
+        let edition = render_edition(state, edition_id).await?;
+        issues.push((edition.lang.clone(), edition_link_issues(&edition, &rules)));
+    }
+    Ok(issues)
+}
+
+/// Route an edition's external images through the image proxy, if enabled.
+fn proxy_edition_images(config: &crate::config::AppConfig, edition: &mut Edition) {
+    if let Some(key) = &config.image_proxy_key {
+        edition.content_html =
+            crate::image_proxy::rewrite_image_srcs(&edition.content_html, &config.base_url, key);
+        edition.template_html =
+            crate::image_proxy::rewrite_image_srcs(&edition.template_html, &config.base_url, key);
+    }
+}
+
+/// Render an edition's content and template, shorten its links (on its short
+/// domain if one is chosen) and store the link mappings.
+async fn prepare_edition(
+    state: &AppState,
+    edition_id: uuid::Uuid,
+    shorturl_service: &dyn ShortUrlService,
+) -> Result<Edition, String> {
+    let mut edition = render_edition(state, edition_id).await?;
+
+    // Before shortening hides where the links go
+    let rules = crate::link_domains::Rules::load(state)
+        .await
+        .map_err(|e| e.to_string())?;
+    edition.blocked_links = edition_link_issues(&edition, &rules)
+        .into_iter()
+        .filter(|issue| issue.blocked)
+        .map(|issue| issue.url)
+        .collect();
+
     // Update rendered_html
     sqlx::query("UPDATE newsletters SET rendered_html = $1, updated_at = NOW() WHERE id = $2")
-        .bind(&content_html)
-        .bind(newsletter_id)
+        .bind(&edition.content_html)
+        .bind(edition_id)
         .execute(&state.db)
         .await
         .map_err(|e| e.to_string())?;
 
-    // Shorten links (once for all subscribers)
-    let (shortened_html, link_pairs) = shorten_links(&content_html, shorturl_service).await;
+    let (short_domain, custom_slugs) = sqlx::query_as::<_, (Option<String>, serde_json::Value)>(
+        "SELECT short_domain, custom_slugs FROM newsletters WHERE id = $1",
+    )
+    .bind(edition_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Shorten links (once for all subscribers), on the newsletter's short domain if one is chosen
+    let domain_service = short_domain.as_deref().and_then(|d| {
+        let found = state.short_domains.iter().find(|sd| sd.domain == d);
+        if found.is_none() {
+            tracing::warn!("Short domain {d} is no longer configured, using the default");
+        }
+        found.map(|sd| sd.service.clone())
+    });
+    let shorturl_service = domain_service.as_deref().unwrap_or(shorturl_service);
+    let custom_slugs: BTreeMap<String, String> =
+        serde_json::from_value(custom_slugs).unwrap_or_default();
+    let (shortened_html, link_pairs) =
+        shorten_links(&edition.content_html, shorturl_service, &custom_slugs).await;
+    edition.content_html = shortened_html;
+    proxy_edition_images(&state.config, &mut edition);
 
     // Store link mappings
     for (original, short) in &link_pairs {
         let _ = sqlx::query(
             "INSERT INTO newsletter_links (newsletter_id, original_url, short_url) VALUES ($1, $2, $3)",
         )
-        .bind(newsletter_id)
+        .bind(edition_id)
         .bind(original)
         .bind(short)
         .execute(&state.db)
         .await;
     }
 
-    // Mark as sending
-    sqlx::query(
-        "UPDATE newsletters SET status = 'sending', sending_started_at = NOW(), updated_at = NOW() WHERE id = $1",
-    )
-    .bind(newsletter_id)
-    .execute(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    // Fetch all active+verified subscribers (excluding bounced)
-    let subscribers = sqlx::query_as::<_, (uuid::Uuid, String, String, String, String)>(
-        "SELECT id, email, name, ucode, secret_code FROM subscribers \
-         WHERE status = true AND verified_email = true AND bounced_at IS NULL",
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| e.to_string())?;
-
-    let total = i32::try_from(subscribers.len()).unwrap_or(0);
-    sqlx::query("UPDATE newsletters SET total_count = $1, updated_at = NOW() WHERE id = $2")
-        .bind(total)
-        .bind(newsletter_id)
-        .execute(&state.db)
-        .await
-        .map_err(|e| e.to_string())?;
+    Ok(edition)
+}
 
-    // Create pending send records
-    for (sub_id, _, _, _, _) in &subscribers {
-        let _ = sqlx::query(
-            "INSERT INTO newsletter_sends (newsletter_id, subscriber_id, status) VALUES ($1, $2, 'pending') ON CONFLICT DO NOTHING",
-        )
-        .bind(newsletter_id)
-        .bind(sub_id)
-        .execute(&state.db)
-        .await;
-    }
+/// Mail service for the newsletter's sending identity, or the default one
+/// when none is chosen or it is no longer configured.
+fn sending_identity_service(state: &AppState, name: Option<&str>) -> Arc<dyn EmailService> {
+    name.and_then(|name| {
+        let found = state.identities.iter().find(|i| i.name == name);
+        if found.is_none() {
+            tracing::warn!("Sending identity {name} is no longer configured, using the default");
+        }
+        found.map(|i| i.service.clone())
+    })
+    .unwrap_or_else(|| state.email.clone())
+}
 
-    let mut sent_count = 0i32;
-    let mut failed_count = 0i32;
+/// What every recipient of one newsletter has in common.
+struct SendContext<'a> {
+    newsletter_id: uuid::Uuid,
+    /// Tracking topic, the newsletter's slug for all editions
+    topic: &'a str,
+    base_url: &'a str,
+    manage_link_ttl_days: i64,
+    message_id_domain: &'a str,
+    /// List identity and admin-defined extra headers
+    shared_headers: &'a [crate::email::EmailHeader],
+}
 
-    for (sub_id, email, name, ucode, secret_code) in &subscribers {
-        // Check if newsletter was paused
-        let current_status =
-            sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
-                .bind(newsletter_id)
-                .fetch_one(&state.db)
-                .await
-                .map_err(|e| e.to_string())?;
+/// List identity headers plus the newsletter's own extra headers.
+fn shared_send_headers(
+    config: &crate::config::AppConfig,
+    newsletter_id: uuid::Uuid,
+    extra_headers: serde_json::Value,
+) -> Vec<crate::email::EmailHeader> {
+    let mut headers = build_list_identity_headers(
+        config.list_id.as_deref(),
+        config.precedence_bulk,
+        config
+            .feedback_id_sender
+            .as_deref()
+            .map(|sender| build_feedback_id(newsletter_id, sender))
+            .as_deref(),
+    );
+    headers.extend(
+        serde_json::from_value::<Vec<crate::email::EmailHeader>>(extra_headers).unwrap_or_default(),
+    );
+    headers
+}
 
-        if current_status == "paused" {
-            tracing::info!("Newsletter {newsletter_id} was paused, stopping send");
-            break;
-        }
+/// Host part of the base URL, used as the Message-ID domain.
+fn message_id_domain(base_url: &str) -> String {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "localhost".to_string())
+}
 
-        // Skip subscribers already sent (important for resume after pause)
-        let already_sent = sqlx::query_scalar::<_, bool>(
-            "SELECT EXISTS(SELECT 1 FROM newsletter_sends WHERE newsletter_id = $1 AND subscriber_id = $2 AND status = 'sent')",
+/// Personalize an edition for one subscriber: tracked links and pixel, manage
+/// and unsubscribe links, and the headers to send it with.
+fn build_recipient_email(
+    ctx: &SendContext<'_>,
+    edition: &Edition,
+    name: &str,
+    ucode: &str,
+    secret_code: &str,
+) -> Result<(String, Vec<crate::email::EmailHeader>), tera::Error> {
+    let base_url = ctx.base_url;
+    let topic = ctx.topic;
+
+    // Per-subscriber open-tracking pixel hash (no URL)
+    let openhash = security::compute_openhash(secret_code, ucode, topic, "");
+    let tracking_pixel = build_tracking_pixel(base_url, ucode, topic, &openhash);
+
+    // Recipient hooks, e.g. per-subscriber click tracking (each link gets its own HMAC)
+    let tracked_html =
+        edition
+            .hooks
+            .apply_recipient(&edition.content_html, base_url, ucode, topic, secret_code);
+    let tracked_html = replace_recipient_name(&tracked_html, name);
+
+    // Expiring signed token rather than the legacy permanent admin_link
+    let admin_link = security::issue_manage_token(secret_code, ucode, ctx.manage_link_ttl_days);
+    let unsubscribe_url = format!(
+        "{base_url}/manage/{admin_link}?from={}",
+        urlencoding::encode(topic)
+    );
+
+    let web_url = format!("{base_url}/newsletters/{}", edition.slug);
+    let html = personalize_email(
+        &edition.template_html,
+        &tracked_html,
+        &edition.title,
+        &tracking_pixel,
+        &unsubscribe_url,
+        base_url,
+        &web_url,
+        ContentLanguage {
+            lang: &edition.lang,
+            dir: &edition.dir,
+        },
+    )?;
+
+    // List-Unsubscribe headers (RFC 2369 + RFC 8058)
+    let one_click_url = format!(
+        "{base_url}/unsubscribe/{admin_link}?from={}",
+        urlencoding::encode(topic)
+    );
+    let mut headers = build_list_unsubscribe_headers(&one_click_url, &unsubscribe_url);
+    headers.extend(ctx.shared_headers.iter().cloned());
+    // Per-recipient Message-ID so replies can be threaded back (see `inbound`)
+    headers.push((
+        "Message-ID".to_string(),
+        crate::inbound::send_message_id(ctx.newsletter_id, ucode, ctx.message_id_domain),
+    ));
+    Ok((html, headers))
+}
+
+/// Sample recipient shown in the message source preview.
+pub const SAMPLE_RECIPIENT_EMAIL: &str = "subscriber@example.com";
+
+/// Subject prefix of test sends.
+pub const TEST_SUBJECT_PREFIX: &str = "[TEST] ";
+
+/// What a sample subscriber would receive, and the service to send it with.
+struct SampleMessage {
+    service: Arc<dyn EmailService>,
+    title: String,
+    html: String,
+    headers: Vec<crate::email::EmailHeader>,
+    attachments: Vec<crate::email::Attachment>,
+}
+
+/// Personalize the given language edition (the newsletter itself if `lang`
+/// matches none) for a sample subscriber. Links are not shortened, since
+/// that only happens when sending; tracked links carry the sample ucode.
+async fn sample_message(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    lang: Option<&str>,
+) -> Result<SampleMessage, String> {
+    let (slug, extra_headers, sending_identity) =
+        sqlx::query_as::<_, (String, serde_json::Value, Option<String>)>(
+            "SELECT slug, extra_headers, sending_identity FROM newsletters WHERE id = $1",
         )
         .bind(newsletter_id)
-        .bind(sub_id)
-        .fetch_one(&state.db)
+        .fetch_optional(&state.db)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Newsletter not found".to_string())?;
 
-        if already_sent {
-            sent_count += 1;
+    let edition_id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT id FROM newsletters WHERE (id = $1 OR parent_id = $1) AND lang = $2",
+    )
+    .bind(newsletter_id)
+    .bind(lang.unwrap_or_default())
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| e.to_string())?
+    .unwrap_or(newsletter_id);
+    let mut edition = render_edition(state, edition_id).await?;
+    proxy_edition_images(&state.config, &mut edition);
+
+    let shared_headers = shared_send_headers(&state.config, newsletter_id, extra_headers);
+    let domain = message_id_domain(&state.config.base_url);
+    let ctx = SendContext {
+        newsletter_id,
+        topic: &slug,
+        base_url: &state.config.base_url,
+        manage_link_ttl_days: state.config.manage_link_ttl_days,
+        message_id_domain: &domain,
+        shared_headers: &shared_headers,
+    };
+    let (html, headers) =
+        build_recipient_email(&ctx, &edition, "王小明", "00000000", &"0".repeat(64))
+            .map_err(|e| e.to_string())?;
+    let attachments =
+        crate::attachments::load_files(&state.db, &state.config, newsletter_id).await?;
+
+    Ok(SampleMessage {
+        service: sending_identity_service(state, sending_identity.as_deref()),
+        title: edition.title,
+        html,
+        headers,
+        attachments,
+    })
+}
+
+/// The exact MIME message a sample subscriber would receive for the given
+/// language edition.
+pub async fn render_message_source(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    lang: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let message = sample_message(state, newsletter_id, lang).await?;
+    message
+        .service
+        .format_message(
+            SAMPLE_RECIPIENT_EMAIL,
+            &message.title,
+            &message.html,
+            &message.headers,
+            &message.attachments,
+        )
+        .map_err(|e| e.to_string())
+}
+
+/// Send the sample subscriber's message for the given language edition to
+/// each address, with a `[TEST]` subject. Nothing is recorded: no send
+/// counters, audience or link mappings.
+pub async fn send_test(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    lang: Option<&str>,
+    recipients: &[String],
+) -> Result<(), String> {
+    let message = sample_message(state, newsletter_id, lang).await?;
+    let subject = format!("{TEST_SUBJECT_PREFIX}{}", message.title);
+    for to in recipients {
+        message
+            .service
+            .send_email_with_attachments(
+                to,
+                &subject,
+                &message.html,
+                &message.headers,
+                &message.attachments,
+            )
+            .await
+            .map_err(|e| format!("{to}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Take a newsletter off the schedule, or pause it with `reason` if it
+/// already started.
+async fn hold_back(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    reason: &str,
+) -> Result<(), String> {
+    sqlx::query(
+        "UPDATE newsletters SET status = CASE WHEN sent_count > 0 THEN 'paused' ELSE 'draft' END, \
+         scheduled_at = CASE WHEN sent_count > 0 THEN scheduled_at END, \
+         pause_reason = CASE WHEN sent_count > 0 THEN $2 END, \
+         paused_by = NULL, paused_at = CASE WHEN sent_count > 0 THEN NOW() END, \
+         local_release_at = NULL, updated_at = NOW() \
+         WHERE id = $1 AND status IN ('scheduled', 'sending')",
+    )
+    .bind(newsletter_id)
+    .bind(reason)
+    .execute(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Refuse to send when an edition lacks a legally required element, such as
+/// its unsubscribe link, or links to a blocked domain (the send endpoints
+/// check too, but a template, the blocklist or the configuration can change
+/// after scheduling). The newsletter is taken off the schedule, or paused if
+/// it already started.
+async fn ensure_compliance(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    editions: &[Edition],
+) -> Result<(), String> {
+    let footer = ComplianceFooter::from_config(&state.config);
+    for edition in editions {
+        if !edition.blocked_links.is_empty() {
+            hold_back(
+                state,
+                newsletter_id,
+                &format!("{} 版本含有被封鎖網域的連結", edition.lang),
+            )
+            .await?;
+            return Err(format!(
+                "The {} edition links to blocked domains ({:?}), not sending",
+                edition.lang, edition.blocked_links
+            ));
+        }
+        let problems =
+            compliance_problems(&edition.template_html, footer).map_err(|e| e.to_string())?;
+        if problems.is_empty() {
             continue;
         }
+        hold_back(
+            state,
+            newsletter_id,
+            &format!("{} 版本未通過合規檢查", edition.lang),
+        )
+        .await?;
+        return Err(format!(
+            "The {} edition fails the compliance check ({problems:?}), not sending",
+            edition.lang
+        ));
+    }
+    Ok(())
+}
 
-        // Compute per-subscriber open-tracking pixel hash (no URL)
-        let openhash = security::compute_openhash(secret_code, ucode, &slug, "");
-        let tracking_pixel = build_tracking_pixel(&state.config.base_url, ucode, &slug, &openhash);
+/// Index of the edition for a subscriber's preferred language: an exact tag
+/// match, else the same primary language (`en-US` → `en`), else the first
+/// (primary) edition.
+fn edition_for_locale(edition_langs: &[&str], locale: Option<&str>) -> usize {
+    let Some(locale) = locale else {
+        return 0;
+    };
+    let primary = |tag: &str| {
+        tag.split('-')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    edition_langs
+        .iter()
+        .position(|lang| lang.eq_ignore_ascii_case(locale))
+        .or_else(|| {
+            edition_langs
+                .iter()
+                .position(|lang| primary(lang) == primary(locale))
+        })
+        .unwrap_or(0)
+}
 
-        // Rewrite links for per-subscriber click tracking (each link gets its own HMAC)
-        let tracked_html = rewrite_links_for_tracking(
-            &shortened_html,
-            &state.config.base_url,
-            ucode,
-            &slug,
-            secret_code,
-        );
-        let tracked_html = replace_recipient_name(&tracked_html, name);
-
-        let admin_link = security::compute_admin_link(secret_code, email);
-        let unsubscribe_url = format!(
-            "{}/manage/{}?from={}",
-            state.config.base_url,
-            admin_link,
-            urlencoding::encode(&slug)
-        );
+/// Send a newsletter to all active+verified subscribers.
+/// This is run by `send_worker` for a job from `send_queue`.
+#[allow(clippy::too_many_lines)]
+pub async fn send_newsletter(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    shorturl_service: &dyn ShortUrlService,
+    rate_limit_ms: u64,
+) -> Result<(), String> {
+    // Load newsletter. For a local-time send, the target is the Taipei wall-clock
+    // time it was scheduled at.
+    #[allow(clippy::type_complexity)]
+    let (slug, segment_id, tag_id, extra_headers, local_target, sending_identity, list_topic) =
+        sqlx::query_as::<
+            _,
+            (
+                String,
+                Option<uuid::Uuid>,
+                Option<uuid::Uuid>,
+                serde_json::Value,
+                Option<chrono::NaiveDateTime>,
+                Option<String>,
+                Option<String>,
+            ),
+        >(
+            "SELECT slug, segment_id, tag_id, extra_headers, \
+         CASE WHEN local_delivery THEN scheduled_at AT TIME ZONE 'Asia/Taipei' END, sending_identity, \
+         list_topic FROM newsletters WHERE id = $1",
+    )
+    .bind(newsletter_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| "Newsletter not found".to_string())?;
 
-        // Personalize template
-        let web_url = format!("{}/newsletters/{}", state.config.base_url, slug);
-        let final_html = match personalize_email(
-            &template_html,
-            &tracked_html,
-            &title,
-            &tracking_pixel,
-            &unsubscribe_url,
-            &state.config.base_url,
-            &web_url,
-        ) {
-            Ok(html) => html,
-            Err(e) => {
-                tracing::error!("Template error for {email}: {e}");
-                failed_count += 1;
-                let _ = sqlx::query(
-                    "UPDATE newsletter_sends SET status = 'failed', error_message = $1 WHERE newsletter_id = $2 AND subscriber_id = $3",
-                )
-                .bind(e.to_string())
+    // The newsletter itself first, then its other language editions
+    let edition_ids = sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT id FROM newsletters WHERE id = $1 OR parent_id = $1 \
+         ORDER BY parent_id NULLS FIRST, lang",
+    )
+    .bind(newsletter_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut editions = Vec::with_capacity(edition_ids.len());
+    for edition_id in edition_ids {
+        editions.push(prepare_edition(state, edition_id, shorturl_service).await?);
+    }
+    ensure_compliance(state, newsletter_id, &editions).await?;
+    let attachments =
+        crate::attachments::load_files(&state.db, &state.config, newsletter_id).await?;
+    let edition_langs: Vec<&str> = editions.iter().map(|e| e.lang.as_str()).collect();
+
+    // Mark as sending, unless paused again while the editions were prepared.
+    // One sent or cancelled meanwhile (e.g. by the time a job whose lease ran
+    // out is retried) is not sent again.
+    let marked = sqlx::query(
+        "UPDATE newsletters SET status = 'sending', sending_started_at = NOW(), \
+         pause_reason = NULL, paused_by = NULL, paused_at = NULL, updated_at = NOW() \
+         WHERE id = $1 AND status IN ('draft', 'scheduled', 'missed', 'sending')",
+    )
+    .bind(newsletter_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| e.to_string())?
+    .rows_affected();
+    if marked == 0 {
+        let status =
+            sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
                 .bind(newsletter_id)
-                .bind(sub_id)
-                .execute(&state.db)
-                .await;
-                continue;
-            }
-        };
+                .fetch_one(&state.db)
+                .await
+                .map_err(|e| e.to_string())?;
+        if status != "paused" {
+            return Err(format!("Newsletter is {status}, not sending it again"));
+        }
+    }
+
+    // Fetch all active+verified subscribers (excluding bounced and those who
+    // stopped the newsletter's list topic), narrowed to the target segment
+    // and tag if set. A missing segment is an error rather than silently
+    // falling back to everyone.
+    let send_target = crate::segment::load_target(&state.db, segment_id, tag_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            format!(
+                "Target segment {} not found",
+                segment_id.unwrap_or_default()
+            )
+        })?;
+    let mut qb = sqlx::QueryBuilder::<sqlx::Postgres>::new(
+        "SELECT s.id, s.email, s.name, s.ucode, s.secret_code, s.locale FROM subscribers s WHERE ",
+    );
+    qb.push(crate::segment::RECIPIENT_CONDITION);
+    send_target.push_conditions(&mut qb);
+    if let Some(topic) = &list_topic {
+        crate::list_topics::push_exclusion(&mut qb, topic);
+    }
+    if let Some(target) = local_target {
+        // Only those whose timezone has reached the target time so far
+        qb.push(" AND ");
+        push_local_release(&mut qb, target);
+        qb.push(" <= NOW()");
+    }
+    // A stable order, so batches after a resume follow on from the last checkpoint
+    qb.push(" ORDER BY s.id");
+    let subscribers = qb
+        .build_query_as::<SubscriberRow>()
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
 
-        // Build List-Unsubscribe headers (RFC 2369 + RFC 8058)
-        let one_click_url = format!(
-            "{}/unsubscribe/{}?from={}",
-            state.config.base_url,
-            admin_link,
-            urlencoding::encode(&slug)
+    // Frequency capping: defer subscribers who already received enough newsletters
+    // within the window. Subscribers already sent this newsletter (resume) are never capped.
+    let capped = if state.config.frequency_cap_max > 0 {
+        sqlx::query_scalar::<_, uuid::Uuid>(
+            "SELECT subscriber_id FROM newsletter_sends \
+             WHERE status = 'sent' AND newsletter_id <> $1 \
+             AND sent_at > NOW() - make_interval(days => $2) \
+             AND subscriber_id NOT IN ( \
+                 SELECT subscriber_id FROM newsletter_sends WHERE newsletter_id = $1 AND status = 'sent') \
+             GROUP BY subscriber_id HAVING COUNT(*) >= $3",
+        )
+        .bind(newsletter_id)
+        .bind(state.config.frequency_cap_window_days)
+        .bind(state.config.frequency_cap_max)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect()
+    } else {
+        HashSet::new()
+    };
+    let (subscribers, deferred) = partition_frequency_capped(subscribers, &capped);
+
+    // Record who this send is addressed to before anything goes out
+    let recipients = subscribers
+        .iter()
+        .map(|(id, email, ..)| (*id, email.as_str(), false))
+        .chain(
+            deferred
+                .iter()
+                .map(|(id, email, ..)| (*id, email.as_str(), true)),
         );
-        let list_unsubscribe_headers: Vec<crate::email::EmailHeader> = vec![
-            (
-                "List-Unsubscribe".to_string(),
-                format!("<{one_click_url}>, <{unsubscribe_url}>"),
-            ),
-            (
-                "List-Unsubscribe-Post".to_string(),
-                "List-Unsubscribe=One-Click".to_string(),
-            ),
-        ];
+    crate::audience::record(&state.db, newsletter_id, recipients)
+        .await
+        .map_err(|e| format!("Failed to record the audience: {e}"))?;
 
-        // Send email
-        match state
-            .email
-            .send_email_with_headers(email, &title, &final_html, &list_unsubscribe_headers)
-            .await
-        {
-            Ok(()) => {
-                sent_count += 1;
-                let _ = sqlx::query(
-                    "UPDATE newsletter_sends SET status = 'sent', sent_at = NOW() WHERE newsletter_id = $1 AND subscriber_id = $2",
-                )
-                .bind(newsletter_id)
-                .bind(sub_id)
-                .execute(&state.db)
-                .await;
-            }
-            Err(e) => {
-                tracing::error!("Failed to send to {email}: {e}");
-                failed_count += 1;
-                let _ = sqlx::query(
-                    "UPDATE newsletter_sends SET status = 'failed', error_message = $1 WHERE newsletter_id = $2 AND subscriber_id = $3",
-                )
-                .bind(e.to_string())
-                .bind(newsletter_id)
-                .bind(sub_id)
-                .execute(&state.db)
-                .await;
+    for (sub_id, ..) in &deferred {
+        let _ = sqlx::query(
+            "INSERT INTO newsletter_sends (newsletter_id, subscriber_id, status) VALUES ($1, $2, 'deferred') \
+             ON CONFLICT (newsletter_id, subscriber_id) DO UPDATE SET status = 'deferred' \
+             WHERE newsletter_sends.status = 'pending'",
+        )
+        .bind(newsletter_id)
+        .bind(sub_id)
+        .execute(&state.db)
+        .await;
+    }
+    let deferred_count = i32::try_from(deferred.len()).unwrap_or(0);
+    if deferred_count > 0 {
+        tracing::info!(
+            "Newsletter {newsletter_id}: {deferred_count} subscribers deferred by frequency cap"
+        );
+    }
 
-                // On hard bounce (5xx), mark subscriber so we never send again
-                if e.is_hard_bounce() {
-                    tracing::warn!("Hard bounce for {email}, marking as bounced");
-                    let _ = sqlx::query("UPDATE subscribers SET bounced_at = NOW() WHERE id = $1")
-                        .bind(sub_id)
-                        .execute(&state.db)
-                        .await;
-                }
-            }
-        }
+    let total = i32::try_from(subscribers.len()).unwrap_or(0);
+    sqlx::query(
+        "UPDATE newsletters SET total_count = $1, deferred_count = $2, updated_at = NOW() WHERE id = $3",
+    )
+    .bind(total)
+    .bind(deferred_count)
+    .bind(newsletter_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| e.to_string())?;
 
-        // Update progress
+    // Create pending send records
+    for (sub_id, ..) in &subscribers {
         let _ = sqlx::query(
-            "UPDATE newsletters SET sent_count = $1, failed_count = $2, updated_at = NOW() WHERE id = $3",
+            "INSERT INTO newsletter_sends (newsletter_id, subscriber_id, status) VALUES ($1, $2, 'pending') ON CONFLICT DO NOTHING",
         )
-        .bind(sent_count)
-        .bind(failed_count)
         .bind(newsletter_id)
+        .bind(sub_id)
         .execute(&state.db)
         .await;
+    }
+
+    // List identity and admin-defined extra headers are the same for every recipient
+    let shared_headers = shared_send_headers(&state.config, newsletter_id, extra_headers);
+    let mailer = sending_identity_service(state, sending_identity.as_deref());
+    let message_id_domain = message_id_domain(&state.config.base_url);
+    let send_ctx = SendContext {
+        newsletter_id,
+        topic: &slug,
+        base_url: &state.config.base_url,
+        manage_link_ttl_days: state.config.manage_link_ttl_days,
+        message_id_domain: &message_id_domain,
+        shared_headers: &shared_headers,
+    };
+
+    // Pick up where a paused or interrupted run stopped: whoever was already
+    // sent to, or failed on, is not sent to again
+    let (done, mut sent_count, mut failed_count) = send_progress(state, newsletter_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let recipients = pending_recipients(subscribers, &done);
+    let pacer = SendPacer::new(AdaptiveThrottle::new(
+        rate_limit_ms,
+        state.live.get().smtp_rate_limit_max_ms,
+    ));
+    let delivery = Delivery {
+        state,
+        ctx: &send_ctx,
+        editions: &editions,
+        edition_langs: &edition_langs,
+        mailer: mailer.as_ref(),
+        attachments: &attachments,
+        pacer: &pacer,
+    };
+
+    for batch in recipients.chunks(state.config.send_batch_size) {
+        // A pause or cancel takes effect between batches
+        let current_status =
+            sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+                .bind(newsletter_id)
+                .fetch_one(&state.db)
+                .await
+                .map_err(|e| e.to_string())?;
+        if current_status != "sending" {
+            tracing::info!("Newsletter {newsletter_id} is now {current_status}, stopping send");
+            break;
+        }
 
-        // Rate limit
-        if rate_limit_ms > 0 {
-            tokio::time::sleep(std::time::Duration::from_millis(rate_limit_ms)).await;
+        // By index: a closure over `&SubscriberRow` trips up the `Send`
+        // check of the spawned worker task
+        let mut outcomes = futures_util::stream::iter(0..batch.len())
+            .map(|i| delivery.deliver(&batch[i]))
+            .buffer_unordered(state.config.smtp_concurrency);
+        while let Some(sent) = outcomes.next().await {
+            if sent {
+                sent_count += 1;
+            } else {
+                failed_count += 1;
+            }
+
+            // Update progress
+            let _ = sqlx::query(
+                "UPDATE newsletters SET sent_count = $1, failed_count = $2, updated_at = NOW() WHERE id = $3",
+            )
+            .bind(sent_count)
+            .bind(failed_count)
+            .bind(newsletter_id)
+            .execute(&state.db)
+            .await;
         }
+
+        checkpoint(state, newsletter_id, sent_count, failed_count)
+            .await
+            .map_err(|e| e.to_string())?;
     }
 
-    // Check if we stopped because of a pause
+    // Check if we stopped because of a pause or cancel
     let current_status =
         sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
             .bind(newsletter_id)
@@ -436,8 +1662,15 @@ pub async fn send_newsletter(
             .await
             .map_err(|e| e.to_string())?;
 
-    if current_status == "paused" {
-        // Only update counts, keep paused status
+    let next_release = match local_target {
+        Some(target) => next_local_release(state, target, &send_target, list_topic.as_deref())
+            .await
+            .map_err(|e| e.to_string())?,
+        None => None,
+    };
+
+    if current_status != "sending" {
+        // Only update counts, keep the paused (or ended) status
         sqlx::query(
             "UPDATE newsletters SET sent_count = $1, failed_count = $2, updated_at = NOW() WHERE id = $3",
         )
@@ -449,7 +1682,24 @@ pub async fn send_newsletter(
         .map_err(|e| e.to_string())?;
 
         tracing::info!(
-            "Newsletter {newsletter_id} paused: {sent_count} sent, {failed_count} failed so far"
+            "Newsletter {newsletter_id} {current_status}: {sent_count} sent, {failed_count} failed so far"
+        );
+    } else if let Some(next_release) = next_release {
+        // Wait for the next timezone to reach the target time
+        sqlx::query(
+            "UPDATE newsletters SET status = 'scheduled', local_release_at = $1, sent_count = $2, \
+             failed_count = $3, updated_at = NOW() WHERE id = $4",
+        )
+        .bind(next_release)
+        .bind(sent_count)
+        .bind(failed_count)
+        .bind(newsletter_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tracing::info!(
+            "Newsletter {newsletter_id} local-time batch done: {sent_count} sent, {failed_count} failed, next batch at {next_release}"
         );
     } else {
         // Mark as completed
@@ -470,62 +1720,536 @@ pub async fn send_newsletter(
         .await
         .map_err(|e| e.to_string())?;
 
+        state.archive_cache.invalidate();
+
         tracing::info!(
             "Newsletter {newsletter_id} send complete: {sent_count} sent, {failed_count} failed"
         );
     }
 
-    Ok(())
+    Ok(())
+}
+
+/// Push a subscriber's release time for a local-time send: the target
+/// wall-clock time in their timezone, Taipei when they have none.
+fn push_local_release(
+    qb: &mut sqlx::QueryBuilder<'_, sqlx::Postgres>,
+    target: chrono::NaiveDateTime,
+) {
+    qb.push("(")
+        .push_bind(target)
+        .push("::TIMESTAMP AT TIME ZONE COALESCE(s.timezone, 'Asia/Taipei'))");
+}
+
+/// Earliest release time among recipients of a local-time send whose
+/// timezone has not reached the target yet; `None` once all are released.
+async fn next_local_release(
+    state: &AppState,
+    target: chrono::NaiveDateTime,
+    send_target: &crate::segment::SendTarget,
+    list_topic: Option<&str>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+    let mut qb = sqlx::QueryBuilder::<sqlx::Postgres>::new("SELECT MIN");
+    push_local_release(&mut qb, target);
+    qb.push(" FROM subscribers s WHERE ");
+    qb.push(crate::segment::RECIPIENT_CONDITION);
+    send_target.push_conditions(&mut qb);
+    if let Some(topic) = list_topic {
+        crate::list_topics::push_exclusion(&mut qb, topic);
+    }
+    qb.push(" AND ");
+    push_local_release(&mut qb, target);
+    qb.push(" > NOW()");
+    qb.build_query_scalar().fetch_one(&state.db).await
+}
+
+/// What the deliveries of one send share.
+struct Delivery<'a> {
+    state: &'a AppState,
+    ctx: &'a SendContext<'a>,
+    editions: &'a [Edition],
+    edition_langs: &'a [&'a str],
+    mailer: &'a dyn EmailService,
+    attachments: &'a [crate::email::Attachment],
+    pacer: &'a SendPacer,
+}
+
+impl Delivery<'_> {
+    /// Render and send one recipient's email in their language, backing off
+    /// and retrying while the relay rate limits us, and record the outcome.
+    /// Returns whether it was sent.
+    async fn deliver(&self, recipient: &SubscriberRow) -> bool {
+        let (sub_id, email, name, ucode, secret_code, locale) = recipient;
+        let newsletter_id = self.ctx.newsletter_id;
+        let edition = &self.editions[edition_for_locale(self.edition_langs, locale.as_deref())];
+
+        let (final_html, list_headers) = match build_recipient_email(
+            self.ctx,
+            edition,
+            name,
+            ucode,
+            secret_code,
+        ) {
+            Ok(built) => built,
+            Err(e) => {
+                tracing::error!("Template error for {email}: {e}");
+                let _ = sqlx::query(
+                        "UPDATE newsletter_sends SET status = 'failed', error_message = $1, failed_at = NOW() WHERE newsletter_id = $2 AND subscriber_id = $3",
+                    )
+                    .bind(e.to_string())
+                    .bind(newsletter_id)
+                    .bind(sub_id)
+                    .execute(&self.state.db)
+                    .await;
+                return false;
+            }
+        };
+
+        let mut throttle_retries = 0;
+        let result = loop {
+            self.pacer.wait_turn().await;
+            let result = self
+                .mailer
+                .send_email_with_attachments(
+                    email,
+                    &edition.title,
+                    &final_html,
+                    &list_headers,
+                    self.attachments,
+                )
+                .await;
+            match &result {
+                Err(e) if e.is_throttled() && throttle_retries < MAX_THROTTLE_RETRIES => {
+                    let delay = self.pacer.on_throttled();
+                    tracing::warn!(
+                        "SMTP relay is rate limiting ({e}), delay between sends now {} ms",
+                        delay.as_millis()
+                    );
+                    throttle_retries += 1;
+                }
+                _ => break result,
+            }
+        };
+        match result {
+            Ok(()) => {
+                self.pacer.on_success();
+                record_send_success(self.state, newsletter_id, *sub_id, edition.id).await;
+                true
+            }
+            Err(e) => {
+                if e.is_throttled() {
+                    self.pacer.on_throttled();
+                }
+                tracing::error!("Failed to send to {email}: {e}");
+                record_send_failure(self.state, newsletter_id, *sub_id, email, &e).await;
+                false
+            }
+        }
+    }
+}
+
+/// Mark a per-subscriber send as delivered and reset the subscriber's
+/// consecutive soft-bounce counter.
+async fn record_send_success(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    sub_id: uuid::Uuid,
+    edition_id: uuid::Uuid,
+) {
+    let _ = sqlx::query(
+        "UPDATE newsletter_sends SET status = 'sent', sent_at = NOW(), edition_id = $3 WHERE newsletter_id = $1 AND subscriber_id = $2",
+    )
+    .bind(newsletter_id)
+    .bind(sub_id)
+    .bind(edition_id)
+    .execute(&state.db)
+    .await;
+
+    let _ = sqlx::query(
+        "UPDATE subscribers SET soft_bounce_count = 0 WHERE id = $1 AND soft_bounce_count > 0",
+    )
+    .bind(sub_id)
+    .execute(&state.db)
+    .await;
+}
+
+/// Record a failed per-subscriber send (with its SMTP code) and apply bounce
+/// handling: hard bounces are marked immediately, soft bounces only after
+/// `soft_bounce_threshold` consecutive occurrences across newsletters.
+async fn record_send_failure(
+    state: &AppState,
+    newsletter_id: uuid::Uuid,
+    sub_id: uuid::Uuid,
+    email: &str,
+    error: &crate::email::EmailError,
+) {
+    let smtp_code = error.smtp_code().and_then(|c| i16::try_from(c).ok());
+    let _ = sqlx::query(
+        "UPDATE newsletter_sends SET status = 'failed', error_message = $1, smtp_code = $2, failed_at = NOW() WHERE newsletter_id = $3 AND subscriber_id = $4",
+    )
+    .bind(error.to_string())
+    .bind(smtp_code)
+    .bind(newsletter_id)
+    .bind(sub_id)
+    .execute(&state.db)
+    .await;
+
+    // On hard bounce (5xx), mark subscriber so we never send again
+    if error.is_hard_bounce() {
+        tracing::warn!("Hard bounce for {email}, marking as bounced");
+        mark_bounced(state, sub_id).await;
+    } else if error.is_soft_bounce() && !error.is_throttled() {
+        // Relay rate limiting says nothing about the recipient's mailbox
+        record_soft_bounce(state, sub_id, email).await;
+    }
+}
+
+/// Count a soft bounce, marking the subscriber bounced once there have been
+/// `soft_bounce_threshold` in a row.
+pub async fn record_soft_bounce(state: &AppState, sub_id: uuid::Uuid, email: &str) {
+    let count = sqlx::query_scalar::<_, i32>(
+        "UPDATE subscribers SET soft_bounce_count = soft_bounce_count + 1 WHERE id = $1 RETURNING soft_bounce_count",
+    )
+    .bind(sub_id)
+    .fetch_one(&state.db)
+    .await;
+
+    if let Ok(count) = count {
+        if count >= state.config.soft_bounce_threshold {
+            tracing::warn!("{count} consecutive soft bounces for {email}, marking as bounced");
+            mark_bounced(state, sub_id).await;
+        }
+    }
+}
+
+/// Stop sending to the subscriber and tell the CRM, the first time only.
+pub async fn mark_bounced(state: &AppState, sub_id: uuid::Uuid) {
+    let marked = sqlx::query(
+        "UPDATE subscribers SET bounced_at = NOW() WHERE id = $1 AND bounced_at IS NULL",
+    )
+    .bind(sub_id)
+    .execute(&state.db)
+    .await;
+    if matches!(marked, Ok(r) if r.rows_affected() > 0) {
+        if let Err(e) =
+            crate::crm_webhook::emit(state, sub_id, crate::crm_webhook::Lifecycle::Bounced).await
+        {
+            tracing::error!("Failed to queue CRM bounce event for {sub_id}: {e}");
+        }
+    }
+}
+
+/// When a scheduled newsletter is next due. A local-time send is first due
+/// when the earliest timezone (UTC+14) reaches the target time, then at each
+/// `local_release_at`.
+pub const SCHEDULED_DUE_AT_SQL: &str = "COALESCE(local_release_at, \
+     CASE WHEN local_delivery \
+     THEN (scheduled_at AT TIME ZONE 'Asia/Taipei') AT TIME ZONE 'Etc/GMT-14' \
+     ELSE scheduled_at END)";
+
+/// How late a due send may be and still be considered on time, since the
+/// scheduler only looks every `NEWSLETTER_SCHEDULER_INTERVAL_SECS`. Also the
+/// shortest catch-up window, so `SCHEDULE_CATCH_UP_HOURS=0` doesn't hold
+/// sends that are merely waiting for the next tick.
+const SCHEDULE_GRACE_SECS: i64 = 300;
+
+/// Difference between this server's clock and the database's worth a warning.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+/// Warn when this server's clock is off from the database's. Due times are
+/// compared with the database clock, so skew only shows in the times
+/// displayed and entered in the admin, but it usually means NTP is broken.
+async fn check_clock_skew(db: &sqlx::PgPool) {
+    match sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>("SELECT NOW()")
+        .fetch_one(db)
+        .await
+    {
+        Ok(db_now) => {
+            let skew = (chrono::Utc::now() - db_now).num_seconds();
+            if skew.abs() > MAX_CLOCK_SKEW_SECS {
+                tracing::warn!(
+                    "Server clock is {skew}s off from the database clock; schedules follow the database"
+                );
+            }
+        }
+        Err(e) => tracing::error!("Failed to read the database clock: {e}"),
+    }
+}
+
+/// Take scheduled sends that are overdue by more than the catch-up window off
+/// the schedule as `missed`, to be sent, rescheduled or cancelled by an
+/// admin. A local-time delivery that already went out to some timezones
+/// keeps going. Returns the ids and titles of those marked.
+pub async fn mark_missed_schedules(
+    db: &sqlx::PgPool,
+    catch_up_hours: i64,
+) -> Result<Vec<(uuid::Uuid, String)>, sqlx::Error> {
+    let window_secs = catch_up_hours.saturating_mul(3600).max(SCHEDULE_GRACE_SECS);
+    sqlx::query_as(&format!(
+        "UPDATE newsletters SET status = 'missed', missed_at = NOW(), updated_at = NOW() \
+         WHERE status = 'scheduled' AND local_release_at IS NULL \
+         AND {SCHEDULED_DUE_AT_SQL} < NOW() - $1 * INTERVAL '1 second' \
+         AND NOT EXISTS (SELECT 1 FROM send_jobs \
+             WHERE send_jobs.newsletter_id = newsletters.id AND state IN ('pending', 'running')) \
+         RETURNING id, title"
+    ))
+    .bind(window_secs)
+    .fetch_all(db)
+    .await
 }
 
-/// Background scheduler loop: checks for scheduled newsletters every `interval_secs`.
-pub async fn newsletter_scheduler(
-    state: AppState,
-    shorturl_service: std::sync::Arc<dyn ShortUrlService>,
-    interval_secs: u64,
-    rate_limit_ms: u64,
-) {
-    let interval = std::time::Duration::from_secs(interval_secs);
+/// Queue the sends of scheduled newsletters that are due; `send_worker` runs
+/// them. One already queued, or still preparing its editions, is left alone.
+/// Sends found too late (see `mark_missed_schedules`) are held instead.
+pub async fn newsletter_scheduler(state: AppState) {
+    state.readiness.mark_scheduler_started();
+    check_clock_skew(&state.db).await;
     loop {
+        let interval =
+            std::time::Duration::from_secs(state.live.get().newsletter_scheduler_interval_secs);
         tokio::time::sleep(interval).await;
 
-        let due = sqlx::query_as::<_, (uuid::Uuid,)>(
-            "SELECT id FROM newsletters WHERE status = 'scheduled' AND scheduled_at <= NOW()",
-        )
+        match mark_missed_schedules(&state.db, state.config.schedule_catch_up_hours).await {
+            Ok(missed) if missed.is_empty() => {}
+            Ok(missed) => {
+                for (newsletter_id, title) in &missed {
+                    tracing::warn!(
+                        "Newsletter {newsletter_id} ({title}) missed its schedule, waiting for an admin"
+                    );
+                }
+                let subject = format!("{} 封錯過排程", missed.len());
+                crate::jobs::record(&state.db, JobKind::Scheduler, Some(&subject), None).await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to mark missed schedules: {e}");
+                let error = e.to_string();
+                crate::jobs::record(&state.db, JobKind::Scheduler, None, Some(&error)).await;
+            }
+        }
+
+        let due = sqlx::query_as::<_, (uuid::Uuid, i64)>(&format!(
+            "SELECT id, EXTRACT(EPOCH FROM NOW() - {SCHEDULED_DUE_AT_SQL})::BIGINT FROM newsletters \
+             WHERE status = 'scheduled' AND {SCHEDULED_DUE_AT_SQL} <= NOW() \
+             AND NOT EXISTS (SELECT 1 FROM send_jobs \
+                 WHERE send_jobs.newsletter_id = newsletters.id AND state IN ('pending', 'running'))"
+        ))
         .fetch_all(&state.db)
         .await;
 
         match due {
+            Ok(rows) if rows.is_empty() => {}
             Ok(rows) => {
-                for (newsletter_id,) in rows {
-                    tracing::info!("Scheduler triggering newsletter {newsletter_id}");
-                    let state_clone = state.clone();
-                    let svc = shorturl_service.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = send_newsletter(
-                            &state_clone,
-                            newsletter_id,
-                            svc.as_ref(),
-                            rate_limit_ms,
-                        )
-                        .await
-                        {
-                            tracing::error!("Scheduled send failed for {newsletter_id}: {e}");
-                        }
-                    });
+                let subject = format!("{} 封到期", rows.len());
+                crate::jobs::record(&state.db, JobKind::Scheduler, Some(&subject), None).await;
+                for (newsletter_id, late_secs) in rows {
+                    if late_secs > SCHEDULE_GRACE_SECS {
+                        tracing::warn!(
+                            "Scheduler catching up on newsletter {newsletter_id}, {} minutes late",
+                            late_secs / 60
+                        );
+                    } else {
+                        tracing::info!("Scheduler queueing newsletter {newsletter_id}");
+                    }
+                    if let Err(e) = crate::send_queue::enqueue(&state.db, newsletter_id).await {
+                        tracing::error!("Failed to queue scheduled send of {newsletter_id}: {e}");
+                    }
                 }
             }
             Err(e) => {
                 tracing::error!("Scheduler query failed: {e}");
+                let error = e.to_string();
+                crate::jobs::record(&state.db, JobKind::Scheduler, None, Some(&error)).await;
+            }
+        }
+    }
+}
+
+/// Sends one process runs at once.
+const WORKER_SLOTS: usize = 4;
+
+/// How often `send_worker` looks for queued sends.
+const WORKER_POLL_SECS: u64 = 2;
+
+/// Run the sends in `send_queue`, up to `WORKER_SLOTS` at a time. Every
+/// replica runs one of these; each send is claimed by exactly one of them.
+pub async fn send_worker(state: AppState) {
+    let worker = crate::send_queue::worker_id();
+    let mut running = tokio::task::JoinSet::new();
+    loop {
+        match crate::send_queue::requeue_expired(&state.db).await {
+            Ok(0) => {}
+            Ok(n) => tracing::warn!("Requeued {n} sends whose worker stopped"),
+            Err(e) => tracing::error!("Failed to requeue expired sends: {e}"),
+        }
+        while running.len() < WORKER_SLOTS {
+            match crate::send_queue::claim(&state.db, &worker).await {
+                Ok(Some(job)) => {
+                    running.spawn(run_send_job(state.clone(), worker.clone(), job));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("Failed to claim a queued send: {e}");
+                    break;
+                }
+            }
+        }
+
+        // A send that ends frees its slot right away
+        let poll = tokio::time::sleep(std::time::Duration::from_secs(WORKER_POLL_SECS));
+        tokio::select! {
+            () = poll => {}
+            Some(_) = running.join_next(), if !running.is_empty() => {}
+        }
+    }
+}
+
+/// Run one claimed send, renewing its lease until it ends. If the lease is
+/// lost, another worker has taken the job over, so this one stops.
+async fn run_send_job(state: AppState, worker: String, job: crate::send_queue::Claimed) {
+    let newsletter_id = job.newsletter_id;
+    tracing::info!("Worker {worker} sending newsletter {newsletter_id}");
+
+    let rate_limit_ms = state.live.get().smtp_rate_limit_ms;
+    let send = send_newsletter(
+        &state,
+        newsletter_id,
+        state.shorturl.as_ref(),
+        rate_limit_ms,
+    );
+    let send = crate::jobs::track(
+        &state.db,
+        JobKind::Send,
+        Some(newsletter_id.to_string()),
+        send,
+    );
+    let keep_lease = async {
+        let every = std::time::Duration::from_secs(
+            u64::try_from(crate::send_queue::LEASE_SECS / 3).unwrap_or(20),
+        );
+        loop {
+            tokio::time::sleep(every).await;
+            match crate::send_queue::renew(&state.db, job.id, &worker).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => tracing::warn!("Failed to renew send job {}: {e}", job.id),
             }
         }
+    };
+
+    let result = tokio::select! {
+        result = send => result,
+        () = keep_lease => {
+            tracing::error!("Worker {worker} lost the send of {newsletter_id} to another worker");
+            return;
+        }
+    };
+    if let Err(e) = &result {
+        tracing::error!("Send failed for {newsletter_id}: {e}");
+    }
+    if let Err(e) = crate::send_queue::finish(&state.db, job.id, result.err().as_deref()).await {
+        tracing::error!("Failed to record the end of send job {}: {e}", job.id);
     }
 }
 
+#[cfg(test)]
+mod snapshot_tests;
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_apply_dark_mode() {
+        let template =
+            "<html><head><title>{{ title }}</title></head><body>{{ content }}</body></html>";
+        let html = apply_dark_mode(template, false);
+        assert!(html.contains(
+            r#"<meta name="color-scheme" content="light dark"><meta name="supported-color-schemes""#
+        ));
+        assert!(html.contains("@media (prefers-color-scheme: dark) { body,"));
+        assert!(html.find("<style>").unwrap() < html.find("</head>").unwrap());
+
+        // The template still renders with Tera
+        let rendered = personalize_email(
+            &html,
+            "<p>Hi</p>",
+            "T",
+            "",
+            "#",
+            "",
+            "#",
+            ContentLanguage::default(),
+        )
+        .unwrap();
+        assert!(rendered.contains("<title>T</title>"));
+        assert!(rendered.contains("<body><p>Hi</p></body>"));
+
+        let forced = apply_dark_mode("<p>x</p>", true);
+        assert!(!forced.contains("@media"));
+        assert!(forced.ends_with("</style><p>x</p>"));
+    }
+
+    #[test]
+    fn test_format_kb() {
+        assert_eq!(format_kb(0), "0.0");
+        assert_eq!(format_kb(1536), "1.5");
+        assert_eq!(format_kb(GMAIL_CLIP_LIMIT_BYTES), "102.0");
+    }
+
+    #[test]
+    fn test_estimate_send_duration() {
+        assert_eq!(
+            estimate_send_duration(8200, 750, 1),
+            chrono::Duration::milliseconds(8200 * 950)
+        );
+        assert_eq!(
+            estimate_send_duration(10, 0, 4),
+            chrono::Duration::milliseconds(3 * 200)
+        );
+        assert_eq!(estimate_send_duration(-1, 100, 1), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(chrono::Duration::seconds(45)), "45 秒");
+        assert_eq!(format_duration(chrono::Duration::seconds(150)), "3 分");
+        assert_eq!(
+            format_duration(chrono::Duration::seconds(7790)),
+            "2 小時 10 分"
+        );
+        assert_eq!(format_duration(chrono::Duration::hours(3)), "3 小時");
+    }
+
+    #[test]
+    fn test_clip_risk() {
+        assert_eq!(clip_risk(50 * 1024), ClipRisk::Ok);
+        assert_eq!(clip_risk(92 * 1024), ClipRisk::Near);
+        assert_eq!(clip_risk(GMAIL_CLIP_LIMIT_BYTES), ClipRisk::Near);
+        assert_eq!(clip_risk(GMAIL_CLIP_LIMIT_BYTES + 1), ClipRisk::Over);
+    }
+
+    #[test]
+    fn test_render_sample_email_includes_tracking() {
+        let template = "<h1>{{ title }}</h1>{{ content }}{{ tracking_pixel }}<a href=\"{{ unsubscribe_url }}\">unsubscribe</a>";
+        let content =
+            r#"<p>Hi %recipient_name%, <a href="https://coscup.org/2025/">COSCUP</a></p>"#;
+        let html = render_sample_email(
+            template,
+            content,
+            "Hello",
+            "2025-08",
+            "https://newsletter.coscup.org",
+            ContentLanguage::default(),
+            &RenderPipeline::default(),
+        )
+        .unwrap();
+        assert!(html.contains("王小明"));
+        assert!(html.contains("https://newsletter.coscup.org/r/c?ucode=00000000&amp;topic=2025-08"));
+        assert!(html.contains("/r/o?ucode=00000000"));
+        assert!(html.contains("/manage/0000"));
+        assert!(html.len() > template.len() + content.len());
+    }
+
     #[test]
     fn test_render_markdown_basic() {
         let html = render_markdown("# Hello\n\nWorld", "");
@@ -570,6 +2294,27 @@ mod tests {
         assert_eq!(result, html);
     }
 
+    #[test]
+    fn test_absolutize_image_srcs_quoting_and_srcset() {
+        let html = r#"<img src='/uploads/a.png' srcset="/uploads/a.png 1x, /uploads/a@2x.png 2x"><img src="//cdn.example.com/b.png"><a href="/about">about</a>"#;
+        let result = absolutize_image_srcs(html, "https://example.com");
+        assert!(result.contains(r#"src="https://example.com/uploads/a.png""#));
+        assert!(result.contains(
+            r#"srcset="https://example.com/uploads/a.png 1x, https://example.com/uploads/a@2x.png 2x""#
+        ));
+        assert!(result.contains(r#"<img src="//cdn.example.com/b.png">"#));
+        assert!(result.contains(r#"<a href="/about">"#));
+    }
+
+    #[test]
+    fn test_style_images_for_email_keeps_own_style() {
+        let html = r#"<img style="border:1px solid #000" src='a.png'>"#;
+        let result = style_images_for_email(html);
+        assert_eq!(result.matches("style=").count(), 1);
+        assert!(result
+            .contains(r#"style="max-width:100%;height:auto;display:block;border:1px solid #000""#));
+    }
+
     #[test]
     fn test_absolutize_image_srcs_multiple() {
         let html = r#"<img src="/uploads/a.png"><img src="/static/logo.svg">"#;
@@ -593,9 +2338,131 @@ mod tests {
         assert_eq!(result.matches("max-width:100%").count(), 2);
     }
 
+    #[test]
+    fn test_render_pipeline_runs_hooks_in_order() {
+        let pipeline = parse_render_hooks("utm_tags, absolutize_images\nclick_tracking")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            pipeline.hooks,
+            [
+                RenderHook::UtmTags,
+                RenderHook::AbsolutizeImages,
+                RenderHook::ClickTracking
+            ]
+        );
+        let ctx = HookContext {
+            base_url: "https://newsletter.coscup.org",
+            slug: "2025-08",
+            sponsors: &[],
+            today: chrono::NaiveDate::from_ymd_opt(2025, 7, 28).unwrap(),
+            event: crate::shortcodes::EventDates::default(),
+        };
+        let html = pipeline.render("![a](/uploads/a.png) [CfP](https://coscup.org/cfp)", &ctx);
+        assert!(html.contains(r#"src="https://newsletter.coscup.org/uploads/a.png""#));
+        assert!(!html.contains("max-width"));
+        assert!(html.contains(
+            "https://coscup.org/cfp?utm_source=newsletter&amp;utm_medium=email&amp;utm_campaign=2025-08"
+        ));
+        assert!(!html.contains("/r/c?"));
+
+        let tracked =
+            pipeline.apply_recipient(&html, "https://newsletter.coscup.org", "u", "2025-08", "s");
+        assert!(tracked.contains("/r/c?ucode=u&amp;topic=2025-08"));
+        assert!(
+            !RenderPipeline::from_json(Some(&serde_json::json!(["utm_tags"])))
+                .hooks
+                .contains(&RenderHook::ClickTracking)
+        );
+        assert_eq!(RenderPipeline::from_json(None), RenderPipeline::default());
+        assert_eq!(
+            RenderPipeline::from_json(Some(&pipeline.to_json())),
+            pipeline
+        );
+    }
+
+    #[test]
+    fn test_default_pipeline_expands_sponsor_shortcode() {
+        let sponsors = [crate::sponsors::Sponsor {
+            id: uuid::Uuid::nil(),
+            name: "Acme".to_string(),
+            logo_url: "/uploads/acme.png".to_string(),
+            url: "https://acme.example/".to_string(),
+            tier: crate::sponsors::Tier::Gold,
+            starts_on: None,
+            ends_on: None,
+        }];
+        let ctx = HookContext {
+            base_url: "https://newsletter.coscup.org",
+            slug: "2025-08",
+            sponsors: &sponsors,
+            today: chrono::NaiveDate::from_ymd_opt(2025, 7, 28).unwrap(),
+            event: crate::shortcodes::EventDates::default(),
+        };
+        let html = RenderPipeline::default().render("Hi\n\n%sponsors%\n", &ctx);
+        assert!(!html.contains("%sponsors%"));
+        assert!(html.contains(r#"src="https://newsletter.coscup.org/uploads/acme.png""#));
+        assert!(html.contains("max-width:100%;height:auto;display:block;display:inline-block;"));
+        let tracked = RenderPipeline::default().apply_recipient(
+            &html,
+            "https://newsletter.coscup.org",
+            "u",
+            "2025-08",
+            "s",
+        );
+        assert!(tracked.contains(&format!(
+            "url=https%3A%2F%2Fnewsletter.coscup.org%2Fsp%2F{}",
+            uuid::Uuid::nil()
+        )));
+
+        let without = parse_render_hooks("style_images").unwrap().unwrap();
+        assert!(without.render("%sponsors%", &ctx).contains("%sponsors%"));
+    }
+
+    #[test]
+    fn test_shortcodes_resolve_after_markdown() {
+        let ctx = HookContext {
+            base_url: "",
+            slug: "2025-08",
+            sponsors: &[],
+            today: chrono::NaiveDate::from_ymd_opt(2025, 7, 28).unwrap(),
+            event: crate::shortcodes::EventDates {
+                start: chrono::NaiveDate::from_ymd_opt(2025, 8, 9),
+                end: chrono::NaiveDate::from_ymd_opt(2025, 8, 10),
+            },
+        };
+        let html = RenderPipeline::default().render(
+            "還有 {{countdown}} 天，議程公布還有 {{countdown to=\"2025-07-30\"}} 天\n\n{{event_dates}}",
+            &ctx,
+        );
+        assert!(html.contains("還有 12 天，議程公布還有 2 天"));
+        assert!(html.contains("<p>2025 年 8 月 9 日（六）至 8 月 10 日（日）</p>"));
+    }
+
+    #[test]
+    fn test_parse_render_hooks() {
+        assert_eq!(parse_render_hooks("  \n "), Ok(None));
+        assert!(parse_render_hooks("utm_tags\nshout").is_err());
+        assert!(parse_render_hooks("utm_tags,utm_tags").is_err());
+        let pipeline = RenderPipeline::default();
+        assert_eq!(
+            parse_render_hooks(&format_render_hooks(&pipeline)),
+            Ok(Some(pipeline))
+        );
+    }
+
+    #[test]
+    fn test_add_utm_tags() {
+        let html = r#"<a href="https://coscup.org/2025/?a=1&amp;b=2#agenda">x</a><a href="https://coscup.org/?utm_source=fb">y</a><a href="mailto:a@b.c">z</a>"#;
+        let result = add_utm_tags(html, "2025 08");
+        assert!(result.contains(r#"href="https://coscup.org/2025/?a=1&amp;b=2&amp;utm_source=newsletter&amp;utm_medium=email&amp;utm_campaign=2025%2008#agenda""#));
+        assert!(result.contains(r#"href="https://coscup.org/?utm_source=fb""#));
+        assert!(result.contains(r#"href="mailto:a@b.c""#));
+    }
+
     #[test]
     fn test_sanitize_html_strips_script() {
-        let html = r#"<p>Hello</p><script>alert('xss')</script><p>World</p>"#;
+        let html = r"<p>Hello</p><script>alert('xss')</script><p>World</p>";
         let result = sanitize_html(html);
         assert!(!result.contains("<script>"));
         assert!(!result.contains("alert"));
@@ -621,7 +2488,8 @@ mod tests {
 
     #[test]
     fn test_sanitize_html_preserves_formatting() {
-        let html = r#"<h1>Title</h1><p><strong>Bold</strong> and <em>italic</em></p><ul><li>Item</li></ul>"#;
+        let html =
+            r"<h1>Title</h1><p><strong>Bold</strong> and <em>italic</em></p><ul><li>Item</li></ul>";
         let result = sanitize_html(html);
         assert!(result.contains("<h1>Title</h1>"));
         assert!(result.contains("<strong>Bold</strong>"));
@@ -657,7 +2525,7 @@ mod tests {
         let svc = MockShortUrlService::default();
         let html = r#"<a href="https://coscup.org">COSCUP</a> and <a href="https://example.com">Example</a>"#;
 
-        let (result, pairs) = shorten_links(html, &svc).await;
+        let (result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
         assert_eq!(pairs.len(), 2);
         assert!(!result.contains("href=\"https://coscup.org\""));
         assert!(!result.contains("href=\"https://example.com\""));
@@ -670,7 +2538,7 @@ mod tests {
         let svc = MockShortUrlService::default();
         let html = r#"<a href="mailto:test@example.com">Email</a>"#;
 
-        let (result, pairs) = shorten_links(html, &svc).await;
+        let (result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
         assert_eq!(pairs.len(), 0);
         assert!(result.contains("mailto:test@example.com"));
     }
@@ -681,7 +2549,7 @@ mod tests {
         let svc = MockShortUrlService::default();
         let html = r##"<a href="#section">Jump</a>"##;
 
-        let (result, pairs) = shorten_links(html, &svc).await;
+        let (result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
         assert_eq!(pairs.len(), 0);
         assert!(result.contains("#section"));
     }
@@ -692,7 +2560,7 @@ mod tests {
         let svc = MockShortUrlService::default();
         let html = r#"<a href="{{ unsubscribe_url }}">Unsub</a>"#;
 
-        let (result, pairs) = shorten_links(html, &svc).await;
+        let (result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
         assert_eq!(pairs.len(), 0);
         assert!(result.contains("{{ unsubscribe_url }}"));
     }
@@ -706,12 +2574,45 @@ mod tests {
         };
         let html = r#"<a href="https://coscup.org">COSCUP</a>"#;
 
-        let (result, pairs) = shorten_links(html, &svc).await;
+        let (result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
         // On failure, link_map is empty (original URL kept via seen map)
         assert_eq!(pairs.len(), 0);
         assert!(result.contains("https://coscup.org"));
     }
 
+    #[tokio::test]
+    async fn test_shorten_links_custom_slug() {
+        use crate::shorturl::tests::MockShortUrlService;
+        let svc = MockShortUrlService::default();
+        let html = r#"<a href="https://coscup.org/2025/cfp?a=1&amp;b=2">CfP</a> <a href="https://coscup.org">Home</a>"#;
+        let slugs = BTreeMap::from([(
+            "https://coscup.org/2025/cfp?a=1&b=2".to_string(),
+            "cfp2025".to_string(),
+        )]);
+
+        let (result, pairs) = shorten_links(html, &svc, &slugs).await;
+        assert_eq!(pairs.len(), 2);
+        assert!(result.contains(r#"href="https://s.coscup.org/cfp2025""#));
+        assert!(result.contains(r#"href="https://s.coscup.org/test_"#));
+    }
+
+    #[tokio::test]
+    async fn test_shorten_links_any_quoting() {
+        use crate::shorturl::tests::MockShortUrlService;
+        let svc = MockShortUrlService::default();
+        let html = r#"<a href='https://coscup.org/'>A</a> <a class="btn" href=https://coscup.org/ >B</a> <a href="tel:+886">C</a>"#;
+
+        let (result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].0, "https://coscup.org/");
+        assert_eq!(
+            result.matches(&format!("href=\"{}\"", pairs[0].1)).count(),
+            2
+        );
+        assert!(!result.contains("'https://coscup.org/'"));
+        assert!(result.contains(r#"href="tel:+886""#));
+    }
+
     #[tokio::test]
     async fn test_shorten_links_dedup() {
         use crate::shorturl::tests::MockShortUrlService;
@@ -719,7 +2620,7 @@ mod tests {
         let html =
             r#"<a href="https://coscup.org">Link1</a> <a href="https://coscup.org">Link2</a>"#;
 
-        let (_result, pairs) = shorten_links(html, &svc).await;
+        let (_result, pairs) = shorten_links(html, &svc, &BTreeMap::new()).await;
         // Same URL should only appear once
         assert_eq!(pairs.len(), 1);
 
@@ -739,6 +2640,7 @@ mod tests {
             "https://example.com/unsub",
             "https://example.com",
             "https://example.com/newsletters/test",
+            ContentLanguage::default(),
         )
         .unwrap();
 
@@ -749,6 +2651,111 @@ mod tests {
         assert!(result.contains("https://example.com/newsletters/test"));
     }
 
+    #[test]
+    fn test_personalize_email_sets_root_language() {
+        let template = r#"<!DOCTYPE html><HTML lang="zh-TW" class="x"><body dir="ltr">{{ content }}</body></html>"#;
+        let result = personalize_email(
+            template,
+            "<p>مرحبا</p>",
+            "T",
+            "",
+            "#",
+            "",
+            "#",
+            ContentLanguage {
+                lang: "ar",
+                dir: "rtl",
+            },
+        )
+        .unwrap();
+        assert!(result
+            .starts_with(r#"<!DOCTYPE html><HTML class="x" lang="ar" dir="rtl"><body dir="ltr">"#));
+
+        // Fragments without a root element are left alone
+        let result = personalize_email(
+            "<div>{{ content }}</div>",
+            "x",
+            "T",
+            "",
+            "#",
+            "",
+            "#",
+            ContentLanguage::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "<div>x</div>");
+    }
+
+    #[test]
+    fn test_has_unsubscribe_link() {
+        assert!(
+            has_unsubscribe_link(r#"{{ content }}<a href="{{ unsubscribe_url }}">退訂</a>"#)
+                .unwrap()
+        );
+        assert!(!has_unsubscribe_link("{{ content }}<p>COSCUP</p>").unwrap());
+        // Mentioned only in a comment, so it never reaches the output
+        assert!(!has_unsubscribe_link("{{ content }}{# {{ unsubscribe_url }} #}").unwrap());
+        assert!(has_unsubscribe_link("{{ broken").is_err());
+    }
+
+    #[test]
+    fn test_apply_compliance_footer() {
+        let footer = ComplianceFooter {
+            postal_address: Some("台北市 {{ x }} <1 號>\n10000"),
+            legal_footer: None,
+        };
+        // Appended before </body>, escaped and not treated as template syntax
+        let html = apply_compliance_footer("<body><p>COSCUP</p></body>", footer);
+        assert!(html.ends_with("</div></body>"));
+        let rendered = tera::Tera::one_off(&html, &tera::Context::new(), false).unwrap();
+        assert!(rendered.contains("台北市 {{ x }} &lt;1 號&gt;<br>10000"));
+
+        // Placeholders are filled in place
+        let html = apply_compliance_footer("<p>%postal_address%</p>%legal_footer%", footer);
+        assert!(html.starts_with("<p>{% raw %}台北市"));
+        assert!(!html.contains("%legal_footer%"));
+
+        assert_eq!(
+            apply_compliance_footer("<body></body>", ComplianceFooter::default()),
+            "<body></body>"
+        );
+    }
+
+    #[test]
+    fn test_compliance_problems() {
+        let template = r#"{{ content }}<a href="{{ unsubscribe_url }}">退訂</a>"#;
+        let footer = ComplianceFooter {
+            postal_address: Some("台北市"),
+            legal_footer: None,
+        };
+        let with_footer = apply_compliance_footer(template, footer);
+        assert!(compliance_problems(&with_footer, footer)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            compliance_problems(template, footer).unwrap(),
+            vec![ComplianceProblem::PostalAddressHidden]
+        );
+        assert_eq!(
+            compliance_problems("{{ content }}", ComplianceFooter::default()).unwrap(),
+            vec![
+                ComplianceProblem::MissingUnsubscribeLink,
+                ComplianceProblem::NoPostalAddress
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_lang() {
+        assert_eq!(parse_lang("en").as_deref(), Some("en"));
+        assert_eq!(parse_lang(" zh-tw ").as_deref(), Some("zh-TW"));
+        assert_eq!(parse_lang("zh-hant-tw").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(parse_lang("AR").as_deref(), Some("ar"));
+        assert_eq!(parse_lang(""), None);
+        assert_eq!(parse_lang("english"), None);
+        assert_eq!(parse_lang("en\" onload=\"x"), None);
+    }
+
     #[test]
     fn test_build_tracking_pixel() {
         let pixel = build_tracking_pixel(
@@ -796,6 +2803,142 @@ mod tests {
         assert!(result.contains(&urlencoding::encode(&hash2).to_string()));
     }
 
+    #[test]
+    fn test_push_local_release() {
+        let target = chrono::NaiveDate::from_ymd_opt(2025, 8, 9)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let mut qb = sqlx::QueryBuilder::<sqlx::Postgres>::new("SELECT ");
+        push_local_release(&mut qb, target);
+        assert_eq!(
+            qb.sql(),
+            "SELECT ($1::TIMESTAMP AT TIME ZONE COALESCE(s.timezone, 'Asia/Taipei'))"
+        );
+    }
+
+    #[test]
+    fn test_edition_for_locale() {
+        let langs = ["zh-TW", "en", "ja"];
+        assert_eq!(edition_for_locale(&langs, None), 0);
+        assert_eq!(edition_for_locale(&langs, Some("en")), 1);
+        assert_eq!(edition_for_locale(&langs, Some("en-US")), 1);
+        assert_eq!(edition_for_locale(&langs, Some("JA")), 2);
+        assert_eq!(edition_for_locale(&langs, Some("zh-HK")), 0);
+        assert_eq!(edition_for_locale(&langs, Some("fr")), 0);
+        assert_eq!(edition_for_locale(&["zh-TW"], Some("en")), 0);
+    }
+
+    #[test]
+    fn test_partition_frequency_capped() {
+        let a = uuid::Uuid::new_v4();
+        let b = uuid::Uuid::new_v4();
+        let row = |id| {
+            (
+                id,
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                None,
+            )
+        };
+        let capped: HashSet<uuid::Uuid> = [b].into_iter().collect();
+        let (send, deferred) = partition_frequency_capped(vec![row(a), row(b)], &capped);
+        assert_eq!(send.len(), 1);
+        assert_eq!(send[0].0, a);
+        assert_eq!(deferred.len(), 1);
+        assert_eq!(deferred[0].0, b);
+
+        let (send, deferred) = partition_frequency_capped(vec![row(a)], &HashSet::new());
+        assert_eq!(send.len(), 1);
+        assert!(deferred.is_empty());
+    }
+
+    #[test]
+    fn test_pending_recipients() {
+        let ids: Vec<uuid::Uuid> = (0..4).map(|_| uuid::Uuid::new_v4()).collect();
+        let row = |id| {
+            (
+                id,
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                None,
+            )
+        };
+        let done: HashSet<uuid::Uuid> = [ids[0], ids[2]].into_iter().collect();
+        let pending = pending_recipients(ids.iter().copied().map(row).collect(), &done);
+        let pending: Vec<uuid::Uuid> = pending.into_iter().map(|(id, ..)| id).collect();
+        assert_eq!(pending, vec![ids[1], ids[3]]);
+
+        assert!(pending_recipients(vec![row(ids[0])], &done).is_empty());
+    }
+
+    #[test]
+    fn test_build_list_unsubscribe_headers() {
+        let headers = build_list_unsubscribe_headers(
+            "https://x.com/unsubscribe/abc",
+            "https://x.com/manage/abc",
+        );
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].0, "List-Unsubscribe");
+        assert_eq!(
+            headers[0].1,
+            "<https://x.com/unsubscribe/abc>, <https://x.com/manage/abc>"
+        );
+        assert_eq!(headers[1].1, "List-Unsubscribe=One-Click");
+    }
+
+    #[test]
+    fn test_build_list_identity_headers_all() {
+        let headers = build_list_identity_headers(
+            Some("COSCUP Newsletter <newsletter.coscup.org>"),
+            true,
+            Some("abc:newsletter:coscup"),
+        );
+        assert_eq!(
+            headers,
+            vec![
+                (
+                    "List-ID".to_string(),
+                    "COSCUP Newsletter <newsletter.coscup.org>".to_string()
+                ),
+                ("Precedence".to_string(), "bulk".to_string()),
+                (
+                    "Feedback-ID".to_string(),
+                    "abc:newsletter:coscup".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_list_identity_headers_none() {
+        assert!(build_list_identity_headers(None, false, None).is_empty());
+    }
+
+    #[test]
+    fn test_build_feedback_id() {
+        let id = uuid::Uuid::nil();
+        assert_eq!(
+            build_feedback_id(id, "coscup"),
+            "00000000000000000000000000000000:newsletter:coscup"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_links_for_tracking_decodes_entities() {
+        let html = r#"<a href='https://coscup.org/?a=1&amp;b=2' class="x">CfP</a>"#;
+        let result = rewrite_links_for_tracking(html, "https://x.com", "u", "t", "secret");
+        let hash = security::compute_openhash("secret", "u", "t", "https://coscup.org/?a=1&b=2");
+        assert!(result.starts_with(r#"<a href="https://x.com/r/c?ucode=u&amp;topic=t&amp;hash="#));
+        assert!(result.contains(&urlencoding::encode(&hash).to_string()));
+        assert!(result.contains("url=https%3A%2F%2Fcoscup.org%2F%3Fa%3D1%26b%3D2"));
+        assert!(result.contains(r#"class="x">CfP</a>"#));
+    }
+
     #[test]
     fn test_rewrite_links_skips_non_http() {
         let html = r##"<a href="mailto:hi@coscup.org">Mail</a> <a href="#top">Top</a>"##;
@@ -805,4 +2948,201 @@ mod tests {
         assert!(result.contains("#top"));
         assert!(!result.contains("/r/c"));
     }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_concurrent_send_reaches_everyone_once(db: sqlx::PgPool) {
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| {
+                c.smtp_concurrency = 4;
+                c.send_batch_size = 5;
+                c.postal_address = Some("臺北市中正區 10 號".to_string());
+            })
+            .build();
+        app.migrate().await;
+        for i in 0..12 {
+            sqlx::query(
+                "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+                 VALUES ($1, $2, $3, true, true)",
+            )
+            .bind(format!("reader{i}@example.org"))
+            .bind(security::generate_secret_code())
+            .bind(format!("u{i}"))
+            .execute(&app.state.db)
+            .await
+            .unwrap();
+        }
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('Parallel', 'parallel', '# Hi') RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        send_newsletter(&app.state, newsletter_id, app.state.shorturl.as_ref(), 1)
+            .await
+            .unwrap();
+
+        let mut recipients: Vec<String> =
+            app.sent_emails().into_iter().map(|(to, ..)| to).collect();
+        recipients.sort();
+        recipients.dedup();
+        assert_eq!(app.sent_emails().len(), 12);
+        assert_eq!(recipients.len(), 12);
+        let (status, sent_count) = sqlx::query_as::<_, (String, i32)>(
+            "SELECT status, sent_count FROM newsletters WHERE id = $1",
+        )
+        .bind(newsletter_id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!((status.as_str(), sent_count), ("sent", 12));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_send_limited_to_tag(db: sqlx::PgPool) {
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.postal_address = Some("臺北市中正區 10 號".to_string()))
+            .build();
+        app.migrate().await;
+        let tag_id = crate::tags::ensure_tag(&app.state.db, "volunteer")
+            .await
+            .unwrap();
+        for i in 0..3 {
+            let id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+                 VALUES ($1, $2, $3, true, true) RETURNING id",
+            )
+            .bind(format!("reader{i}@example.org"))
+            .bind(security::generate_secret_code())
+            .bind(format!("u{i}"))
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+            if i > 0 {
+                crate::tags::tag_subscriber(&app.state.db, id, tag_id)
+                    .await
+                    .unwrap();
+            }
+        }
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content, tag_id) \
+             VALUES ('Volunteers', 'volunteers', '# Hi', $1) RETURNING id",
+        )
+        .bind(tag_id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        send_newsletter(&app.state, newsletter_id, app.state.shorturl.as_ref(), 1)
+            .await
+            .unwrap();
+
+        let mut recipients: Vec<String> =
+            app.sent_emails().into_iter().map(|(to, ..)| to).collect();
+        recipients.sort();
+        assert_eq!(recipients, ["reader1@example.org", "reader2@example.org"]);
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_missed_schedules_wait_for_an_admin(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let mut ids = Vec::new();
+        for (slug, hours_late, partly_sent) in [
+            ("late", 1, false),
+            ("missed", 10, false),
+            ("partial", 10, true),
+        ] {
+            let id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO newsletters (title, slug, markdown_content, status, scheduled_at, \
+                 local_delivery, local_release_at) \
+                 VALUES ($1, $1, '', 'scheduled', NOW() - $2 * INTERVAL '1 hour', $3, \
+                 CASE WHEN $3 THEN NOW() - $2 * INTERVAL '1 hour' END) RETURNING id",
+            )
+            .bind(slug)
+            .bind(f64::from(hours_late))
+            .bind(partly_sent)
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        // An hour late is caught up on; a local-time send under way keeps going
+        let missed = mark_missed_schedules(&app.state.db, 6).await.unwrap();
+        assert_eq!(missed, vec![(ids[1], "missed".to_string())]);
+        // With catch-up off, anything past the grace period waits
+        let missed = mark_missed_schedules(&app.state.db, 0).await.unwrap();
+        assert_eq!(missed, vec![(ids[0], "late".to_string())]);
+
+        let page = app.get(&format!("/admin/newsletters/{}", ids[1])).await;
+        assert!(page.body.contains("錯過排程"));
+        app.post_form(&format!("/admin/newsletters/{}/cancel", ids[1]), &[])
+            .await;
+        let (status, missed_at) =
+            sqlx::query_as::<_, (String, Option<chrono::DateTime<chrono::Utc>>)>(
+                "SELECT status, missed_at FROM newsletters WHERE id = $1",
+            )
+            .bind(ids[1])
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        assert_eq!((status.as_str(), missed_at), ("draft", None));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_test_send_leaves_counters_alone(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let newsletter_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('試寄', 'test-send', '%recipient_name% 您好，[議程](https://coscup.org/2025/)') \
+             RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        let response = app
+            .post_form(
+                &format!("/admin/newsletters/{newsletter_id}/test-send"),
+                &[("emails", "a@example.org, b@example.org")],
+            )
+            .await;
+        assert_eq!(
+            response.location(),
+            Some(format!("/admin/newsletters/{newsletter_id}/preview?test_sent=2").as_str())
+        );
+        let sent = app.sent_emails();
+        assert_eq!(sent.len(), 2);
+        let (to, subject, html) = &sent[0];
+        assert_eq!(to, "a@example.org");
+        assert_eq!(subject, "[TEST] 試寄");
+        assert!(html.contains("王小明 您好"));
+        assert!(html.contains("/r/c?ucode=00000000"));
+
+        // Without addresses it goes to the admin
+        app.post_form(
+            &format!("/admin/newsletters/{newsletter_id}/test-send"),
+            &[],
+        )
+        .await;
+        assert_eq!(app.sent_emails()[2].0, "admin@coscup.org");
+
+        let (status, sent_count, audience) = sqlx::query_as::<_, (String, i32, i64)>(
+            "SELECT status, sent_count, \
+             (SELECT COUNT(*) FROM newsletter_audience WHERE newsletter_id = id) \
+             FROM newsletters WHERE id = $1",
+        )
+        .bind(newsletter_id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!((status.as_str(), sent_count, audience), ("draft", 0, 0));
+    }
 }
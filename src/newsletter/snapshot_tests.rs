@@ -0,0 +1,284 @@
+//! Rendering snapshot tests: every Markdown fixture in `tests/render` goes
+//! through the same steps as a send (content hooks, sanitizing, link
+//! shortening, recipient hooks, personalization) for a fixed subscriber, and
+//! the result is compared with the stored `.snap` file next to it: the links
+//! shortened, the headers, and the HTML body.
+//!
+//! A fixture may start with a front matter block of `key: value` lines
+//! between `---` lines: `title`, `lang`, `dir`, `dark_mode` and
+//! `render_hooks` (as entered in the editor).
+//!
+//! After an intended rendering change, rewrite the snapshots with
+//! `UPDATE_SNAPSHOTS=1 cargo test snapshot` and review their diff.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use regex::Regex;
+
+use super::*;
+use crate::shorturl::ShortUrlError;
+use crate::sponsors::{Sponsor, Tier};
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/render");
+const BASE_URL: &str = "https://newsletter.coscup.org";
+const UCODE: &str = "a1b2c3d4";
+const SECRET_CODE: &str = "5ecre7c0de5ecre7c0de5ecre7c0de5ecre7c0de5ecre7c0de5ecre7c0de5ec0";
+
+/// Numbers links in the order they are shortened, so every link of a
+/// fixture gets its own short URL.
+#[derive(Default)]
+struct NumberingShortener {
+    urls: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl ShortUrlService for NumberingShortener {
+    async fn shorten(&self, url: &str) -> Result<String, ShortUrlError> {
+        let mut urls = self.urls.lock().unwrap();
+        urls.push(url.to_string());
+        Ok(format!("https://s.coscup.org/{}", urls.len()))
+    }
+
+    async fn shorten_custom(&self, _url: &str, keyword: &str) -> Result<String, ShortUrlError> {
+        Ok(format!("https://s.coscup.org/{keyword}"))
+    }
+
+    async fn get_clicks(&self, _short_url: &str) -> Result<u64, ShortUrlError> {
+        Ok(0)
+    }
+}
+
+/// Fixture options from the front matter.
+struct Fixture {
+    title: String,
+    lang: String,
+    dir: String,
+    dark_mode: bool,
+    render_hooks: Option<serde_json::Value>,
+    markdown: String,
+}
+
+fn parse_fixture(text: &str) -> Fixture {
+    let mut fixture = Fixture {
+        title: "電子報".to_string(),
+        lang: "zh-TW".to_string(),
+        dir: "ltr".to_string(),
+        dark_mode: false,
+        render_hooks: None,
+        markdown: text.to_string(),
+    };
+    let Some((front, markdown)) = text
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+    else {
+        return fixture;
+    };
+    for line in front.lines() {
+        let (key, value) = line.split_once(':').expect("front matter is `key: value`");
+        let value = value.trim().to_string();
+        match key.trim() {
+            "title" => fixture.title = value,
+            "lang" => fixture.lang = value,
+            "dir" => fixture.dir = value,
+            "dark_mode" => fixture.dark_mode = value == "true",
+            "render_hooks" => {
+                fixture.render_hooks = parse_render_hooks(&value)
+                    .expect("valid render hooks")
+                    .map(|p| p.to_json());
+            }
+            other => panic!("unknown front matter key {other}"),
+        }
+    }
+    fixture.markdown = markdown.to_string();
+    fixture
+}
+
+fn config() -> crate::config::AppConfig {
+    crate::config::AppConfig {
+        base_url: BASE_URL.to_string(),
+        postal_address: Some("臺北市中正區 10 號".to_string()),
+        legal_footer: Some("您收到這封信是因為訂閱了 COSCUP 電子報。".to_string()),
+        event_start_date: NaiveDate::from_ymd_opt(2025, 8, 9),
+        event_end_date: NaiveDate::from_ymd_opt(2025, 8, 10),
+        list_id: Some("COSCUP Newsletter <newsletter.coscup.org>".to_string()),
+        feedback_id_sender: Some("coscup".to_string()),
+        ..crate::config::tests::test_config()
+    }
+}
+
+fn sponsors() -> Vec<Sponsor> {
+    let sponsor = |n: u128, name: &str, tier| Sponsor {
+        id: uuid::Uuid::from_u128(n),
+        name: name.to_string(),
+        logo_url: format!("https://coscup.org/2025/sponsors/{n}.png"),
+        url: format!("https://sponsor{n}.example/"),
+        tier,
+        starts_on: None,
+        ends_on: None,
+    };
+    vec![
+        sponsor(1, "鑽石贊助 & Co.", Tier::Diamond),
+        sponsor(2, "Gold \"Quoted\"", Tier::Gold),
+    ]
+}
+
+/// What one subscriber would receive, in sections: the short links (short,
+/// then original), the message headers, then the HTML body.
+async fn render_fixture(text: &str, slug: &str) -> String {
+    let fixture = parse_fixture(text);
+    let config = config();
+    let newsletter_id = uuid::Uuid::from_u128(0x2025);
+    let template_html = std::fs::read_to_string(Path::new(FIXTURE_DIR).join("template.html"))
+        .expect("template fixture");
+
+    let mut edition = assemble_edition(
+        EditionSource {
+            id: newsletter_id,
+            title: fixture.title,
+            markdown_content: fixture.markdown,
+            slug: slug.to_string(),
+            dark_mode: fixture.dark_mode,
+            lang: fixture.lang,
+            dir: fixture.dir,
+            render_hooks: fixture.render_hooks,
+            template_html,
+        },
+        &config,
+        &sponsors(),
+        NaiveDate::from_ymd_opt(2025, 7, 28).unwrap(),
+    );
+    let mut custom_slugs = BTreeMap::new();
+    custom_slugs.insert(
+        "https://coscup.org/2025/zh-TW/session".to_string(),
+        "session2025".to_string(),
+    );
+    let (shortened, link_pairs) = shorten_links(
+        &edition.content_html,
+        &NumberingShortener::default(),
+        &custom_slugs,
+    )
+    .await;
+    edition.content_html = shortened;
+    proxy_edition_images(&config, &mut edition);
+
+    let shared_headers = shared_send_headers(
+        &config,
+        newsletter_id,
+        serde_json::json!([["X-COSCUP-Issue", slug]]),
+    );
+    let ctx = SendContext {
+        newsletter_id,
+        topic: slug,
+        base_url: BASE_URL,
+        manage_link_ttl_days: config.manage_link_ttl_days,
+        message_id_domain: &message_id_domain(BASE_URL),
+        shared_headers: &shared_headers,
+    };
+    let (html, headers) = build_recipient_email(&ctx, &edition, "王小明", UCODE, SECRET_CODE)
+        .expect("template renders");
+
+    let mut out = String::from("== short links\n");
+    for (original, short) in &link_pairs {
+        let _ = writeln!(out, "{short} {original}");
+    }
+    out.push_str("== headers\n");
+    for (name, value) in &headers {
+        let _ = writeln!(out, "{name}: {value}");
+    }
+    out.push_str("== html\n");
+    out.push_str(&html);
+    stabilize(&out)
+}
+
+/// Manage tokens carry their expiry time; replace them so snapshots don't
+/// change from day to day.
+fn stabilize(message: &str) -> String {
+    let re = Regex::new(&format!(r"{UCODE}\.\d+\.[0-9a-f]{{64}}")).expect("valid regex");
+    re.replace_all(message, "{manage_token}").into_owned()
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(FIXTURE_DIR)
+        .expect("fixture directory")
+        .map(|entry| entry.expect("fixture entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// First line where the two differ, for the failure message.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line_no in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => break,
+            (e, a) if e == a => {}
+            (e, a) => {
+                return format!(
+                    "line {line_no}\n  snapshot: {}\n  rendered: {}",
+                    e.unwrap_or("<end>"),
+                    a.unwrap_or("<end>")
+                );
+            }
+        }
+    }
+    "trailing whitespace".to_string()
+}
+
+#[tokio::test]
+async fn test_rendering_matches_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let paths = fixtures();
+    assert!(!paths.is_empty(), "no fixtures in {FIXTURE_DIR}");
+
+    let mut failures = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let text = std::fs::read_to_string(&path).expect("fixture");
+        let rendered = render_fixture(&text, &format!("2025-{name}")).await;
+        let snap_path = path.with_extension("snap");
+
+        if update {
+            std::fs::write(&snap_path, &rendered).expect("write snapshot");
+            continue;
+        }
+        match std::fs::read_to_string(&snap_path) {
+            Ok(expected) if expected == rendered => {}
+            Ok(expected) => failures.push(format!(
+                "{name}: differs at {}",
+                first_difference(&expected, &rendered)
+            )),
+            Err(_) => failures.push(format!("{name}: no snapshot")),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "rendering changed (rerun with UPDATE_SNAPSHOTS=1 if intended):\n{}",
+        failures.join("\n")
+    );
+}
+
+#[tokio::test]
+async fn test_rendering_is_deterministic() {
+    let text = std::fs::read_to_string(Path::new(FIXTURE_DIR).join("basic.md")).unwrap();
+    assert_eq!(
+        render_fixture(&text, "2025-basic").await,
+        render_fixture(&text, "2025-basic").await
+    );
+}
+
+#[test]
+fn test_parse_fixture() {
+    let fixture =
+        parse_fixture("---\ntitle: T\ndark_mode: true\nrender_hooks: utm_tags\n---\n# Hi\n");
+    assert_eq!(fixture.title, "T");
+    assert!(fixture.dark_mode);
+    assert_eq!(fixture.render_hooks, Some(serde_json::json!(["utm_tags"])));
+    assert_eq!(fixture.markdown, "# Hi\n");
+    assert_eq!(parse_fixture("# Hi\n").markdown, "# Hi\n");
+}
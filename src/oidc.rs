@@ -0,0 +1,482 @@
+//! Admin single sign-on through an OIDC provider such as Google
+//! Workspace, as an alternative to magic links: the authorization code flow
+//! with PKCE and a nonce. The provider only vouches for the email address;
+//! the `admins` table still decides who gets in.
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::security;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("OIDC request failed: {0}")]
+    RequestFailed(String),
+
+    #[error("Invalid ID token: {0}")]
+    InvalidToken(String),
+}
+
+#[async_trait]
+pub trait OidcProvider: Send + Sync {
+    /// Where to send the browser to sign in.
+    async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, OidcError>;
+
+    /// Redeem the code the provider sent back and return the verified,
+    /// lowercased email of the ID token issued for `nonce`.
+    async fn exchange(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<String, OidcError>;
+}
+
+/// PKCE `S256` challenge for a code verifier (RFC 7636).
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Self::One(aud) => aud == client_id,
+            Self::Many(auds) => auds.iter().any(|a| a == client_id),
+        }
+    }
+}
+
+/// The ID token claims sign-in depends on.
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    iss: String,
+    aud: Audience,
+    azp: Option<String>,
+    exp: i64,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+}
+
+/// Read the claims of an ID token. The signature isn't checked: the token
+/// comes straight from the token endpoint over TLS, which OIDC Core 3.1.3.7
+/// allows in place of validating it.
+fn decode_claims(id_token: &str) -> Result<IdTokenClaims, OidcError> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| OidcError::InvalidToken("not a JWT".to_string()))?;
+    let json = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| OidcError::InvalidToken(e.to_string()))?;
+    serde_json::from_slice(&json).map_err(|e| OidcError::InvalidToken(e.to_string()))
+}
+
+/// The email the claims vouch for, once they are checked to be for this
+/// client and sign-in attempt, unexpired, and from `issuer`. Google also
+/// issues tokens whose `iss` is the bare host, which is accepted too.
+pub fn verified_email(
+    claims: &IdTokenClaims,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<String, OidcError> {
+    let invalid = |reason: &str| Err(OidcError::InvalidToken(reason.to_string()));
+    if claims.iss != issuer && Some(claims.iss.as_str()) != issuer.strip_prefix("https://") {
+        return invalid("wrong issuer");
+    }
+    if !claims.aud.contains(client_id) {
+        return invalid("wrong audience");
+    }
+    if matches!(claims.aud, Audience::Many(_))
+        && claims.azp.as_deref().is_some_and(|azp| azp != client_id)
+    {
+        return invalid("wrong authorized party");
+    }
+    if claims.exp <= now {
+        return invalid("expired");
+    }
+    if !claims
+        .nonce
+        .as_deref()
+        .is_some_and(|n| security::constant_time_eq(n, nonce))
+    {
+        return invalid("nonce mismatch");
+    }
+    match (&claims.email, claims.email_verified) {
+        (Some(email), Some(true)) => Ok(email.trim().to_lowercase()),
+        (Some(_), _) => invalid("email not verified"),
+        (None, _) => invalid("no email"),
+    }
+}
+
+// --- HTTP implementation ---
+
+/// The endpoints from the provider's discovery document.
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+pub struct HttpOidcProvider {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    client: reqwest::Client,
+    /// Fetched on first use, so startup doesn't depend on the provider
+    discovery: tokio::sync::OnceCell<Discovery>,
+}
+
+impl HttpOidcProvider {
+    pub fn new(issuer: &str, client_id: String, client_secret: String, base_url: &str) -> Self {
+        Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            redirect_uri: format!("{base_url}/admin/auth/oidc/callback"),
+            client: reqwest::Client::builder()
+                .user_agent("COSCUP-Newsletter")
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            discovery: tokio::sync::OnceCell::new(),
+        }
+    }
+
+    async fn discovery(&self) -> Result<&Discovery, OidcError> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let discovery = self
+                    .client
+                    .get(format!("{}/.well-known/openid-configuration", self.issuer))
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map_err(|e| OidcError::RequestFailed(e.to_string()))?
+                    .json::<Discovery>()
+                    .await
+                    .map_err(|e| OidcError::RequestFailed(e.to_string()))?;
+                if discovery.issuer.trim_end_matches('/') != self.issuer {
+                    return Err(OidcError::RequestFailed(format!(
+                        "discovery document is for issuer {}",
+                        discovery.issuer
+                    )));
+                }
+                Ok(discovery)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl OidcProvider for HttpOidcProvider {
+    async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, OidcError> {
+        let discovery = self.discovery().await?;
+        let url = reqwest::Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_uri),
+                ("scope", "openid email"),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::RequestFailed(e.to_string()))?;
+        Ok(url.into())
+    }
+
+    async fn exchange(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<String, OidcError> {
+        let discovery = self.discovery().await?;
+        let token = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| OidcError::RequestFailed(e.to_string()))?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| OidcError::RequestFailed(e.to_string()))?;
+        let claims = decode_claims(&token.id_token)?;
+        verified_email(
+            &claims,
+            &self.issuer,
+            &self.client_id,
+            nonce,
+            chrono::Utc::now().timestamp(),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Signs in as `email` when the callback brings code `"good-code"` and
+    /// the verifier and nonce of the last authorization URL.
+    #[derive(Default)]
+    pub struct MockOidcProvider {
+        pub email: String,
+        /// (code challenge, nonce) of the last authorization URL
+        pending: Mutex<Option<(String, String)>>,
+    }
+
+    impl MockOidcProvider {
+        pub fn signing_in(email: &str) -> Self {
+            Self {
+                email: email.to_string(),
+                ..Self::default()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl OidcProvider for MockOidcProvider {
+        async fn authorization_url(
+            &self,
+            state: &str,
+            nonce: &str,
+            code_challenge: &str,
+        ) -> Result<String, OidcError> {
+            *self.pending.lock().unwrap() = Some((code_challenge.to_string(), nonce.to_string()));
+            Ok(format!("https://idp.example.org/authorize?state={state}"))
+        }
+
+        async fn exchange(
+            &self,
+            code: &str,
+            code_verifier: &str,
+            nonce: &str,
+        ) -> Result<String, OidcError> {
+            let pending = self.pending.lock().unwrap().clone();
+            if code != "good-code"
+                || pending != Some((code_challenge(code_verifier), nonce.to_string()))
+            {
+                return Err(OidcError::RequestFailed("invalid_grant".to_string()));
+            }
+            Ok(self.email.clone())
+        }
+    }
+
+    fn claims(json: serde_json::Value) -> IdTokenClaims {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // base64url(SHA-256("verifier")) without padding
+        assert_eq!(
+            code_challenge("verifier"),
+            "iMnq5o6zALKXGivsnlom_0F5_WYda32GHkxlV7mq7hQ"
+        );
+    }
+
+    #[test]
+    fn test_decode_claims() {
+        let payload = URL_SAFE_NO_PAD.encode(
+            r#"{"iss":"https://accounts.google.com","aud":"client","exp":2000000000,"nonce":"n","email":"Admin@COSCUP.org","email_verified":true}"#,
+        );
+        let claims = decode_claims(&format!("eyJhbGciOiJSUzI1NiJ9.{payload}.sig")).unwrap();
+        assert_eq!(
+            verified_email(
+                &claims,
+                "https://accounts.google.com",
+                "client",
+                "n",
+                1_900_000_000
+            )
+            .unwrap(),
+            "admin@coscup.org"
+        );
+        assert!(decode_claims("not-a-token").is_err());
+        assert!(decode_claims("a.%%%.c").is_err());
+    }
+
+    #[test]
+    fn test_verified_email() {
+        let issuer = "https://accounts.google.com";
+        let check = |json: serde_json::Value| {
+            let mut base = serde_json::json!({
+                "iss": issuer,
+                "aud": "client",
+                "exp": 1_000,
+                "nonce": "n0nce",
+                "email": "admin@coscup.org",
+                "email_verified": true,
+            });
+            base.as_object_mut()
+                .unwrap()
+                .extend(json.as_object().unwrap().clone());
+            verified_email(&claims(base), issuer, "client", "n0nce", 500)
+        };
+
+        assert_eq!(check(serde_json::json!({})).unwrap(), "admin@coscup.org");
+        assert!(check(serde_json::json!({ "iss": "accounts.google.com" })).is_ok());
+        assert!(check(serde_json::json!({ "aud": ["other", "client"], "azp": "client" })).is_ok());
+
+        for bad in [
+            serde_json::json!({ "iss": "https://evil.example.org" }),
+            serde_json::json!({ "aud": "other" }),
+            serde_json::json!({ "aud": ["other", "client"], "azp": "other" }),
+            serde_json::json!({ "exp": 500 }),
+            serde_json::json!({ "nonce": "replayed" }),
+            serde_json::json!({ "nonce": null }),
+            serde_json::json!({ "email_verified": false }),
+            serde_json::json!({ "email_verified": null }),
+            serde_json::json!({ "email": null }),
+        ] {
+            assert!(check(bad.clone()).is_err(), "{bad}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oidc_disabled() {
+        let app = crate::test_utils::TestStateBuilder::without_db().build();
+        assert_eq!(
+            app.get("/admin/auth/oidc/start").await.status,
+            axum::http::StatusCode::NOT_FOUND
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_oidc_login(db: sqlx::PgPool) {
+        use axum::body::Body;
+        use axum::http::{header, Request, StatusCode};
+
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .oidc(MockOidcProvider::signing_in("admin@coscup.org"))
+            .build();
+        app.migrate().await;
+        sqlx::query("INSERT INTO admins (email, added_by) VALUES ('admin@coscup.org', 'test')")
+            .execute(&app.state.db)
+            .await
+            .unwrap();
+        assert!(app
+            .get("/admin/login")
+            .await
+            .body
+            .contains("/admin/auth/oidc/start"));
+
+        // (state, cookie) of a new sign-in attempt
+        let start = || async {
+            let started = app.get("/admin/auth/oidc/start?remember=1").await;
+            assert_eq!(started.status, StatusCode::SEE_OTHER);
+            let state = started
+                .location()
+                .unwrap()
+                .split_once("state=")
+                .unwrap()
+                .1
+                .to_string();
+            let cookie = started.headers[header::SET_COOKIE]
+                .to_str()
+                .unwrap()
+                .split(';')
+                .next()
+                .unwrap()
+                .to_string();
+            (state, cookie)
+        };
+        let callback = |query: String, cookie: String| {
+            app.send(
+                Request::get(format!("/admin/auth/oidc/callback?{query}"))
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let (state, cookie) = start().await;
+        let refused = callback("code=good-code&state=wrong".to_string(), cookie).await;
+        assert!(refused.body.contains("登入逾時或連結無效"));
+        let (state_again, cookie) = start().await;
+        assert_ne!(state, state_again);
+        let cancelled = callback(format!("error=access_denied&state={state_again}"), cookie).await;
+        assert!(cancelled.body.contains("單一登入已取消"));
+
+        let (state, cookie) = start().await;
+        let signed_in = callback(format!("code=good-code&state={state}"), cookie).await;
+        assert_eq!(signed_in.status, StatusCode::SEE_OTHER);
+        assert_eq!(signed_in.location(), Some("/admin"));
+        assert!(signed_in
+            .headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .any(|c| c.to_str().unwrap().starts_with(crate::auth::SESSION_COOKIE)));
+        let (remember, method): (bool, String) = sqlx::query_as(
+            "SELECT s.remember, a.details->>'method' FROM admin_sessions s \
+             JOIN audit_log a ON a.admin_email = s.admin_email AND a.action = 'admin.login'",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert!(remember);
+        assert_eq!(method, "oidc");
+
+        // The provider vouching for the email isn't enough once it's disabled
+        sqlx::query("UPDATE admins SET disabled_at = NOW()")
+            .execute(&app.state.db)
+            .await
+            .unwrap();
+        let (state, cookie) = start().await;
+        let denied = callback(format!("code=good-code&state={state}"), cookie).await;
+        assert_eq!(denied.status, StatusCode::OK);
+        assert!(denied.body.contains("沒有管理權限"));
+        let logins: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM admin_login_log WHERE email = 'admin@coscup.org'",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(logins, 2);
+    }
+}
@@ -0,0 +1,271 @@
+//! Plain-text version of an HTML email, sent as the `text/plain` alternative
+//! of every message (see `email::SmtpEmailService`). Mail that is HTML only
+//! scores worse with spam filters, and some readers prefer text.
+//!
+//! Blocks become lines, list items get a `- `, images their alt text and
+//! links their URL in parentheses after the link text. `<head>`, styles,
+//! scripts and elements hidden with `display:none` are left out.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lol_html::html_content::Element;
+use lol_html::{doc_text, element, rewrite_str, RewriteStrSettings};
+
+/// Elements whose content never shows up as text.
+const SKIPPED: &str =
+    "head, style, script, template, [style*='display:none'], [style*='display: none']";
+
+/// Elements that start and end a line.
+const LINE_BLOCKS: &str = "div, ul, ol, li, tr, table, section, header, footer, article, center";
+
+/// Elements set off by a blank line.
+const PARAGRAPHS: &str = "p, h1, h2, h3, h4, h5, h6, blockquote, pre, hr";
+
+#[derive(Default)]
+struct Text {
+    out: String,
+    pending_space: bool,
+    /// How many skipped elements we are in
+    skip: usize,
+    /// How deep in `<pre>`, where whitespace is kept
+    pre: usize,
+}
+
+impl Text {
+    fn push_str(&mut self, s: &str) {
+        if self.skip > 0 {
+            return;
+        }
+        for c in s.chars() {
+            if self.pre == 0 && c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+            if std::mem::take(&mut self.pending_space) && !self.at_line_start() {
+                self.out.push(' ');
+            }
+            self.out.push(c);
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    /// End the current line, leaving `blank_lines` empty lines after it.
+    fn break_line(&mut self, blank_lines: usize) {
+        if self.skip > 0 || self.out.is_empty() {
+            return;
+        }
+        self.pending_space = false;
+        let trailing = self.out.len() - self.out.trim_end_matches('\n').len();
+        for _ in trailing..=blank_lines {
+            self.out.push('\n');
+        }
+    }
+
+    fn finish(&self) -> String {
+        let lines: Vec<&str> = self.out.lines().map(str::trim_end).collect();
+        lines.join("\n").trim().to_string()
+    }
+}
+
+/// Run `f` on the element's end tag, if it has one.
+fn on_end(el: &mut Element, text: &Rc<RefCell<Text>>, f: impl FnOnce(&mut Text) + 'static) {
+    let text = Rc::clone(text);
+    if let Some(handlers) = el.end_tag_handlers() {
+        handlers.push(Box::new(move |_| {
+            f(&mut text.borrow_mut());
+            Ok(())
+        }));
+    }
+}
+
+/// The text of an HTML document or fragment.
+pub fn from_html(html: &str) -> String {
+    let text = Rc::new(RefCell::new(Text::default()));
+    let result = {
+        let text = &text;
+        rewrite_str(
+            html,
+            RewriteStrSettings {
+                element_content_handlers: vec![
+                    element!(SKIPPED, move |el| {
+                        if el.can_have_content() {
+                            text.borrow_mut().skip += 1;
+                            on_end(el, text, |t| t.skip -= 1);
+                        }
+                        Ok(())
+                    }),
+                    element!(LINE_BLOCKS, move |el| {
+                        let mut t = text.borrow_mut();
+                        t.break_line(0);
+                        if el.tag_name() == "li" {
+                            t.push_str("- ");
+                        }
+                        drop(t);
+                        on_end(el, text, |t| t.break_line(0));
+                        Ok(())
+                    }),
+                    element!(PARAGRAPHS, move |el| {
+                        let mut t = text.borrow_mut();
+                        t.break_line(1);
+                        match el.tag_name().as_str() {
+                            "hr" => {
+                                t.push_str("----------");
+                                t.break_line(1);
+                            }
+                            "pre" => t.pre += 1,
+                            _ => {}
+                        }
+                        drop(t);
+                        let pre = el.tag_name() == "pre";
+                        on_end(el, text, move |t| {
+                            if pre {
+                                t.pre -= 1;
+                            }
+                            t.break_line(1);
+                        });
+                        Ok(())
+                    }),
+                    element!("br", move |_| {
+                        let mut t = text.borrow_mut();
+                        if t.skip == 0 {
+                            t.pending_space = false;
+                            t.out.push('\n');
+                        }
+                        Ok(())
+                    }),
+                    element!("td, th", move |_| {
+                        text.borrow_mut().pending_space = true;
+                        Ok(())
+                    }),
+                    element!("img[alt]", move |el| {
+                        let alt = el.get_attribute("alt").unwrap_or_default();
+                        let mut t = text.borrow_mut();
+                        t.pending_space = true;
+                        t.push_str(&decode_entities(&alt));
+                        t.pending_space = true;
+                        Ok(())
+                    }),
+                    element!("a[href]", move |el| {
+                        let href = crate::html_rewrite::decode_attr(
+                            &el.get_attribute("href").unwrap_or_default(),
+                        );
+                        if !(href.starts_with("https://")
+                            || href.starts_with("http://")
+                            || href.starts_with("mailto:"))
+                        {
+                            return Ok(());
+                        }
+                        let start = text.borrow().out.len();
+                        on_end(el, text, move |t| {
+                            let label = t.out.get(start..).unwrap_or_default().trim();
+                            let shown = href.strip_prefix("mailto:").unwrap_or(&href);
+                            if label != shown {
+                                t.push_str(&format!(" ({shown})"));
+                            }
+                        });
+                        Ok(())
+                    }),
+                ],
+                document_content_handlers: vec![doc_text!(move |chunk| {
+                    text.borrow_mut().push_str(&decode_entities(chunk.as_str()));
+                    Ok(())
+                })],
+                ..RewriteStrSettings::new()
+            },
+        )
+    };
+    if let Err(e) = result {
+        tracing::warn!("Could not convert the HTML to text: {e}");
+        return String::new();
+    }
+    let text = text.borrow().finish();
+    text
+}
+
+/// Decode character references: numeric ones and the named ones that
+/// Markdown rendering and the templates produce. Others are kept as written.
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let name = &rest[1..end];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                "copy" => Some('©'),
+                "hellip" => Some('…'),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                _ => name
+                    .strip_prefix("#x")
+                    .or_else(|| name.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| name.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        if let Some((c, end)) = decoded {
+            out.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            out.push('&');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_lists_and_links() {
+        let html = r#"<html><head><title>T</title><style>p { color: red; }</style></head>
+            <body><h2>COSCUP  2025</h2><p>感謝您&amp;歡迎<br>第二行</p>
+            <ul><li>議程 <a href="https://coscup.org/2025/">查看</a></li>
+            <li><a href="https://coscup.org/">https://coscup.org/</a></li></ul>
+            <div style="display:none">預覽文字</div>
+            <p><img src="logo.png" alt="COSCUP"><a href="mailto:a@coscup.org">a@coscup.org</a></p>
+            <img src="https://x/pixel.png" alt="" width="1"></body></html>"#;
+        assert_eq!(
+            from_html(html),
+            "COSCUP 2025\n\n感謝您&歡迎\n第二行\n\n- 議程 查看 (https://coscup.org/2025/)\n\
+             - https://coscup.org/\n\nCOSCUP a@coscup.org"
+        );
+    }
+
+    #[test]
+    fn test_pre_keeps_whitespace() {
+        assert_eq!(
+            from_html("<p>a</p><pre>x  =  1\n  y</pre><hr><p>b</p>"),
+            "a\n\nx  =  1\n  y\n\n----------\n\nb"
+        );
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            decode_entities("&lt;a&gt; &#x27;b&#39; &nbsp;"),
+            "<a> 'b' \u{a0}"
+        );
+        assert_eq!(decode_entities("AT&T &unknown; &"), "AT&T &unknown; &");
+    }
+}
@@ -0,0 +1,85 @@
+use qrcode::{Color, EcLevel, QrCode};
+
+/// Pixels per QR module, large enough to print sharply on a standee.
+const MODULE_PX: usize = 12;
+
+/// Blank border in modules, as the QR spec asks for.
+const QUIET_ZONE: usize = 4;
+
+/// Longest source tag accepted.
+const MAX_SOURCE_CHARS: usize = 50;
+
+/// Lowercased source tag if it only uses letters, digits, `-` and `_`, so it
+/// reads well in the source breakdown and needs no escaping in the URL.
+pub fn normalize_source(source: &str) -> Option<String> {
+    let source = source.trim().to_ascii_lowercase();
+    (!source.is_empty()
+        && source.chars().count() <= MAX_SOURCE_CHARS
+        && source
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    .then_some(source)
+}
+
+/// Subscribe page URL tagged with `utm_source` and `utm_medium=qr`.
+pub fn subscribe_url(base_url: &str, source: &str) -> String {
+    format!("{base_url}/?utm_source={source}&utm_medium=qr")
+}
+
+/// Encode `data` as a black-on-white grayscale PNG QR code.
+pub fn png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::M)
+        .map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let size = (modules + 2 * QUIET_ZONE) * MODULE_PX;
+
+    let mut pixels = vec![255u8; size * size];
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color != Color::Dark {
+            continue;
+        }
+        let left = (i % modules + QUIET_ZONE) * MODULE_PX;
+        let top = (i / modules + QUIET_ZONE) * MODULE_PX;
+        for y in top..top + MODULE_PX {
+            pixels[y * size + left..y * size + left + MODULE_PX].fill(0);
+        }
+    }
+
+    let width = u32::try_from(size).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, width);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(
+            normalize_source(" Booth2025 ").as_deref(),
+            Some("booth2025")
+        );
+        assert_eq!(
+            normalize_source("blog_post-1").as_deref(),
+            Some("blog_post-1")
+        );
+        assert_eq!(normalize_source(""), None);
+        assert_eq!(normalize_source("a&b=c"), None);
+        assert_eq!(normalize_source(&"x".repeat(MAX_SOURCE_CHARS + 1)), None);
+    }
+
+    #[test]
+    fn test_png() {
+        let image = png(&subscribe_url("https://newsletter.coscup.org", "booth2025")).unwrap();
+        assert!(image.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+}
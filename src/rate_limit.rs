@@ -0,0 +1,257 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// At most `limit` hits per sliding window of `window_secs`; a limit of 0
+/// disables the check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitRule {
+    pub limit: u32,
+    pub window_secs: i64,
+}
+
+impl RateLimitRule {
+    pub const fn new(limit: u32, window_secs: i64) -> Self {
+        Self { limit, window_secs }
+    }
+
+    /// Parse `count/window`, where the window is seconds or has an `s`, `m`,
+    /// `h` or `d` suffix, e.g. `5/24h` or `10/600`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (limit, window) = spec.trim().split_once('/')?;
+        let limit = limit.trim().parse().ok()?;
+        let window = window.trim();
+        let (number, unit) = match window.char_indices().last()? {
+            (i, c) if c.is_ascii_alphabetic() => (&window[..i], c.to_ascii_lowercase()),
+            _ => (window, 's'),
+        };
+        let multiplier = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return None,
+        };
+        let window_secs = number.trim().parse::<i64>().ok()?.checked_mul(multiplier)?;
+        (window_secs > 0).then_some(Self::new(limit, window_secs))
+    }
+}
+
+/// Hit counters per key and fixed window.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Count a hit for `key` in the window starting at `window_start` (Unix
+    /// seconds) and return (hits in that window including this one, hits in
+    /// the window before it).
+    async fn hit(
+        &self,
+        key: &str,
+        window_start: i64,
+        window_secs: i64,
+    ) -> Result<(i64, i64), sqlx::Error>;
+}
+
+// --- Postgres implementation ---
+
+pub struct PgRateLimitStore {
+    db: PgPool,
+}
+
+impl PgRateLimitStore {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+    async fn hit(
+        &self,
+        key: &str,
+        window_start: i64,
+        window_secs: i64,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        // Counters are only needed until the next window ends
+        sqlx::query_as::<_, (i64, i64)>(
+            "WITH cur AS ( \
+                 INSERT INTO rate_limit_hits (key, window_start, hits, expires_at) \
+                 VALUES ($1, $2, 1, to_timestamp($2 + 2 * $3)) \
+                 ON CONFLICT (key, window_start) DO UPDATE SET hits = rate_limit_hits.hits + 1 \
+                 RETURNING hits) \
+             SELECT (SELECT hits FROM cur), \
+                 COALESCE((SELECT hits FROM rate_limit_hits WHERE key = $1 AND window_start = $2 - $3), 0)",
+        )
+        .bind(key)
+        .bind(window_start)
+        .bind(window_secs)
+        .fetch_one(&self.db)
+        .await
+    }
+}
+
+/// Sliding-window counter: the previous fixed window's hits are weighted by
+/// the share of it still inside the sliding window ending now.
+pub(crate) fn within_limit(
+    rule: RateLimitRule,
+    current: i64,
+    previous: i64,
+    elapsed_secs: i64,
+) -> bool {
+    let overlap = rule.window_secs - elapsed_secs;
+    previous * overlap + current * rule.window_secs <= i64::from(rule.limit) * rule.window_secs
+}
+
+/// Rate limiter shared by all endpoints; each caller passes its own scope
+/// and rule.
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>) -> Self {
+        Self { store }
+    }
+
+    /// Record a hit for `subject` (an email, an IP, ...) under `scope` and
+    /// return whether it is within `rule`. Rejected hits count too, so a
+    /// client that keeps retrying stays limited.
+    pub async fn check(
+        &self,
+        scope: &str,
+        subject: &str,
+        rule: RateLimitRule,
+    ) -> Result<bool, sqlx::Error> {
+        self.check_at(scope, subject, rule, Utc::now()).await
+    }
+
+    async fn check_at(
+        &self,
+        scope: &str,
+        subject: &str,
+        rule: RateLimitRule,
+        now: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        if rule.limit == 0 {
+            return Ok(true);
+        }
+        let now = now.timestamp();
+        let elapsed = now.rem_euclid(rule.window_secs);
+        let (current, previous) = self
+            .store
+            .hit(
+                &format!("{scope}:{subject}"),
+                now - elapsed,
+                rule.window_secs,
+            )
+            .await?;
+        Ok(within_limit(rule, current, previous, elapsed))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    pub struct MockRateLimitStore {
+        hits: Mutex<HashMap<(String, i64), i64>>,
+    }
+
+    #[async_trait]
+    impl RateLimitStore for MockRateLimitStore {
+        async fn hit(
+            &self,
+            key: &str,
+            window_start: i64,
+            window_secs: i64,
+        ) -> Result<(i64, i64), sqlx::Error> {
+            let mut hits = self.hits.lock().unwrap();
+            let current = hits.entry((key.to_string(), window_start)).or_insert(0);
+            *current += 1;
+            let current = *current;
+            let previous = hits
+                .get(&(key.to_string(), window_start - window_secs))
+                .copied()
+                .unwrap_or(0);
+            Ok((current, previous))
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            RateLimitRule::parse("5/24h"),
+            Some(RateLimitRule::new(5, 86400))
+        );
+        assert_eq!(
+            RateLimitRule::parse(" 10 / 600 "),
+            Some(RateLimitRule::new(10, 600))
+        );
+        assert_eq!(
+            RateLimitRule::parse("3/15m"),
+            Some(RateLimitRule::new(3, 900))
+        );
+        assert_eq!(
+            RateLimitRule::parse("0/1d"),
+            Some(RateLimitRule::new(0, 86400))
+        );
+        assert_eq!(RateLimitRule::parse("5"), None);
+        assert_eq!(RateLimitRule::parse("5/0"), None);
+        assert_eq!(RateLimitRule::parse("5/2w"), None);
+        assert_eq!(RateLimitRule::parse("-1/1h"), None);
+    }
+
+    #[test]
+    fn test_within_limit_weights_previous_window() {
+        let rule = RateLimitRule::new(10, 100);
+        // Halfway through the window, 10 earlier hits still count as 5
+        assert!(within_limit(rule, 5, 10, 50));
+        assert!(!within_limit(rule, 6, 10, 50));
+        // At the start of the window the previous one counts in full
+        assert!(!within_limit(rule, 1, 10, 0));
+        // Near the end of the window little of the previous one remains
+        assert!(within_limit(rule, 9, 10, 95));
+    }
+
+    #[tokio::test]
+    async fn test_check_limits_per_scope_and_subject() {
+        let limiter = RateLimiter::new(Arc::new(MockRateLimitStore::default()));
+        let rule = RateLimitRule::new(2, 60);
+        let at = |secs: i64| Utc.timestamp_opt(1_700_000_040 + secs, 0).unwrap();
+
+        assert!(limiter.check_at("login", "a@x", rule, at(0)).await.unwrap());
+        assert!(limiter.check_at("login", "a@x", rule, at(5)).await.unwrap());
+        assert!(!limiter
+            .check_at("login", "a@x", rule, at(10))
+            .await
+            .unwrap());
+
+        // Other subjects and scopes have their own counters
+        assert!(limiter
+            .check_at("login", "b@x", rule, at(10))
+            .await
+            .unwrap());
+        assert!(limiter
+            .check_at("subscribe", "a@x", rule, at(10))
+            .await
+            .unwrap());
+
+        // Two windows later the earlier hits no longer count
+        assert!(limiter
+            .check_at("login", "a@x", rule, at(150))
+            .await
+            .unwrap());
+
+        // A zero limit disables the check
+        let off = RateLimitRule::new(0, 60);
+        for _ in 0..5 {
+            assert!(limiter.check_at("login", "c@x", off, at(0)).await.unwrap());
+        }
+    }
+}
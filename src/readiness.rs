@@ -0,0 +1,115 @@
+use std::ffi::OsStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::AppState;
+
+/// Startup progress. The server listens before migrations run so
+/// `/health/live` answers right away; everything else waits for this.
+#[derive(Clone, Default)]
+pub struct Readiness {
+    migrated: Arc<AtomicBool>,
+    scheduler_started: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn mark_migrated(&self) {
+        self.migrated.store(true, Ordering::Release);
+    }
+
+    pub fn mark_scheduler_started(&self) {
+        self.scheduler_started.store(true, Ordering::Release);
+    }
+
+    pub fn is_migrated(&self) -> bool {
+        self.migrated.load(Ordering::Acquire)
+    }
+
+    pub fn is_scheduler_started(&self) -> bool {
+        self.scheduler_started.load(Ordering::Acquire)
+    }
+}
+
+/// Answer 503 for everything but the health checks until migrations are done.
+pub async fn readiness_middleware(
+    State(state): State<AppState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    if !state.readiness.is_migrated() && !req.uri().path().starts_with("/health") {
+        return (StatusCode::SERVICE_UNAVAILABLE, "starting").into_response();
+    }
+    next.run(req).await
+}
+
+/// Tell systemd about a state change (`READY=1`, `STOPPING=1`) when started
+/// as a `Type=notify` service; a no-op otherwise.
+pub fn sd_notify(message: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(e) = notify_socket(&socket_path, message) {
+        tracing::warn!("sd_notify({message}) failed: {e}");
+    }
+}
+
+fn notify_socket(socket_path: &OsStr, message: &str) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = UnixDatagram::unbound()?;
+    // A leading '@' is a Linux abstract socket name
+    if let Some(name) = socket_path.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(message.as_bytes(), &addr)?;
+            return Ok(());
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = name;
+            return Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+        }
+    }
+    socket.send_to(message.as_bytes(), socket_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_flags() {
+        let readiness = Readiness::default();
+        let shared = readiness.clone();
+        assert!(!readiness.is_migrated());
+        shared.mark_migrated();
+        assert!(readiness.is_migrated());
+        assert!(!readiness.is_scheduler_started());
+        shared.mark_scheduler_started();
+        assert!(readiness.is_scheduler_started());
+    }
+
+    #[test]
+    fn test_notify_socket() {
+        let dir = std::env::temp_dir().join(format!("sd-notify-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let listener = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        notify_socket(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0u8; 16];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
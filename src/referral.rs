@@ -0,0 +1,88 @@
+use sqlx::PgPool;
+
+/// `utm_source`/`utm_medium` recorded for signups through a referral link
+/// that carry no UTM parameters of their own.
+pub const REFERRAL_SOURCE: &str = "referral";
+pub const REFERRAL_MEDIUM: &str = "subscriber";
+
+/// A subscriber's personal link to the subscribe page.
+pub fn referral_url(base_url: &str, ucode: &str) -> String {
+    format!("{base_url}/?referral={}", urlencoding::encode(ucode))
+}
+
+/// Whether `code` looks like a ucode (8 hex digits), so junk never reaches
+/// the database.
+fn is_ucode(code: &str) -> bool {
+    code.len() == 8 && code.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The active subscriber a referral code belongs to. Unsubscribed or
+/// unverified subscribers don't get credit.
+pub async fn find_referrer(db: &PgPool, code: &str) -> Result<Option<uuid::Uuid>, sqlx::Error> {
+    let code = code.trim().to_ascii_lowercase();
+    if !is_ucode(&code) {
+        return Ok(None);
+    }
+    sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT id FROM subscribers WHERE ucode = $1 AND status = true AND verified_email = true",
+    )
+    .bind(code)
+    .fetch_optional(db)
+    .await
+}
+
+/// Verified signups a subscriber referred.
+pub async fn count(db: &PgPool, subscriber_id: uuid::Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM subscribers WHERE referred_by = $1 AND verified_email = true",
+    )
+    .bind(subscriber_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Subscribers with the most verified referrals, for the stats page.
+pub async fn top_referrers(db: &PgPool, limit: i64) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, String, i64, i64)>(
+        "SELECT r.id, r.email, r.name, COUNT(*), COUNT(*) FILTER (WHERE s.verified_email = true) \
+         FROM subscribers s JOIN subscribers r ON r.id = s.referred_by \
+         GROUP BY r.id, r.email, r.name \
+         ORDER BY COUNT(*) FILTER (WHERE s.verified_email = true) DESC, COUNT(*) DESC LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, email, name, total, verified)| {
+            serde_json::json!({
+                "id": id.to_string(),
+                "email": email,
+                "name": name,
+                "total": total,
+                "verified": verified,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_referral_url() {
+        assert_eq!(
+            referral_url("https://newsletter.coscup.org", "0a1b2c3d"),
+            "https://newsletter.coscup.org/?referral=0a1b2c3d"
+        );
+    }
+
+    #[test]
+    fn test_is_ucode() {
+        assert!(is_ucode("0a1b2c3d"));
+        assert!(!is_ucode("0a1b2c3"));
+        assert!(!is_ucode("0a1b2c3g"));
+        assert!(!is_ucode("' OR 1=1"));
+    }
+}
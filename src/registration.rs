@@ -0,0 +1,342 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::import::{self, ImportFormat, ImportRow};
+use crate::jobs::JobKind;
+use crate::security;
+use crate::AppState;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error("Failed to fetch registration export: {0}")]
+    FetchFailed(String),
+
+    #[error("Failed to parse registration export: {0}")]
+    ParseFailed(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A source of registrant emails (attendees, volunteers, ...). Everyone it
+/// returns is added as a subscriber and tagged with `segment()`.
+#[async_trait]
+pub trait RegistrationSource: Send + Sync {
+    fn segment(&self) -> &str;
+    async fn fetch(&self) -> Result<Vec<ImportRow>, SyncError>;
+
+    /// Subscriber source label stored on rows created by the sync.
+    fn subscription_source(&self) -> String {
+        subscription_source(self.segment())
+    }
+
+    /// Whether role addresses (`info@`, ...) are rejected. Registrants gave
+    /// their addresses themselves, role addresses included.
+    fn block_role_accounts(&self) -> bool {
+        false
+    }
+}
+
+// --- HTTP export implementation ---
+
+/// Pulls a JSON or CSV export (columns `email`, `name`) from a URL.
+pub struct HttpRegistrationSource {
+    segment: String,
+    url: String,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl HttpRegistrationSource {
+    pub fn new(segment: String, url: String, token: Option<String>) -> Self {
+        Self {
+            segment,
+            url,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl RegistrationSource for HttpRegistrationSource {
+    fn segment(&self) -> &str {
+        &self.segment
+    }
+
+    async fn fetch(&self) -> Result<Vec<ImportRow>, SyncError> {
+        let mut req = self.client.get(&self.url);
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| SyncError::FetchFailed(e.to_string()))?;
+
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| SyncError::FetchFailed(e.to_string()))?;
+
+        import::parse_payload(detect_format(&content_type, &self.url), &body)
+            .map_err(SyncError::ParseFailed)
+    }
+}
+
+/// Use the response `Content-Type`, falling back to the URL extension
+/// (many static exports are served as `text/plain` or octet-stream).
+fn detect_format(content_type: &str, url: &str) -> ImportFormat {
+    ImportFormat::from_content_type(content_type).unwrap_or_else(|| {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        if path.to_lowercase().ends_with(".csv") {
+            ImportFormat::Csv
+        } else {
+            ImportFormat::Json
+        }
+    })
+}
+
+/// Parse `REGISTRATION_SYNC_SOURCES`: comma-separated `segment|url` pairs.
+/// Malformed entries are skipped with a warning.
+pub fn parse_sources(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('|')
+                .map(|(segment, url)| (segment.trim().to_lowercase(), url.trim().to_string()))
+                .filter(|(segment, url)| !segment.is_empty() && url.starts_with("http"));
+            if parsed.is_none() {
+                tracing::warn!("Ignoring malformed registration sync source: {entry}");
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// Build the configured sources.
+pub fn sources_from_config(config: &crate::config::AppConfig) -> Vec<Arc<dyn RegistrationSource>> {
+    parse_sources(&config.registration_sync_sources)
+        .into_iter()
+        .map(|(segment, url)| {
+            Arc::new(HttpRegistrationSource::new(
+                segment,
+                url,
+                config.registration_sync_token.clone(),
+            )) as Arc<dyn RegistrationSource>
+        })
+        .collect()
+}
+
+/// Outcome of syncing one source.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncStats {
+    pub fetched: usize,
+    pub created: usize,
+    pub tagged: usize,
+    pub invalid: usize,
+}
+
+/// Subscriber source label stored on rows created by the sync.
+pub fn subscription_source(segment: &str) -> String {
+    format!("registration:{segment}")
+}
+
+/// Sync one source: create missing subscribers and tag everyone with the segment.
+/// Existing subscribers are only tagged; their status (e.g. unsubscribed) is left alone.
+pub async fn sync_source(
+    db: &sqlx::PgPool,
+    source: &dyn RegistrationSource,
+) -> Result<SyncStats, SyncError> {
+    let rows = source.fetch().await?;
+    let fetched = rows.len();
+    let (valid, errors) = import::validate_rows(rows, source.block_role_accounts());
+
+    let tag_id = crate::tags::ensure_tag(db, source.segment()).await?;
+    let source_label = source.subscription_source();
+
+    let mut stats = SyncStats {
+        fetched,
+        invalid: errors.len(),
+        ..SyncStats::default()
+    };
+
+    for (_, row, canonical) in valid {
+        let created = sqlx::query_scalar::<_, uuid::Uuid>(
+            "INSERT INTO subscribers (email, canonical_email, name, secret_code, ucode, status, verified_email, subscription_source) \
+             SELECT $1, $2, $3, $4, $5, true, true, $6 \
+             WHERE NOT EXISTS (SELECT 1 FROM subscribers WHERE canonical_email = $2) \
+             ON CONFLICT (email) DO NOTHING RETURNING id",
+        )
+        .bind(&row.email)
+        .bind(&canonical)
+        .bind(&row.name)
+        .bind(security::generate_secret_code())
+        .bind(security::generate_ucode())
+        .bind(&source_label)
+        .fetch_optional(db)
+        .await?;
+
+        let subscriber_id = if let Some(id) = created {
+            stats.created += 1;
+            id
+        } else {
+            // The same address, else another spelling of the same Gmail mailbox
+            sqlx::query_scalar::<_, uuid::Uuid>(
+                "SELECT id FROM subscribers WHERE email = $1 OR canonical_email = $2 \
+                 ORDER BY email = $1 DESC LIMIT 1",
+            )
+            .bind(&row.email)
+            .bind(&canonical)
+            .fetch_one(db)
+            .await?
+        };
+
+        if crate::tags::tag_subscriber(db, subscriber_id, tag_id).await? {
+            stats.tagged += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Sync all sources once, logging (and auditing) the result of each.
+pub async fn sync_all(state: &AppState, sources: &[Arc<dyn RegistrationSource>]) {
+    for source in sources {
+        let subject = Some(source.segment().to_string());
+        let sync = sync_source(&state.db, source.as_ref());
+        match crate::jobs::track(&state.db, JobKind::RegistrationSync, subject, sync).await {
+            Ok(result) => {
+                tracing::info!(
+                    "Registration sync [{}]: {} fetched, {} created, {} newly tagged, {} invalid",
+                    source.segment(),
+                    result.fetched,
+                    result.created,
+                    result.tagged,
+                    result.invalid
+                );
+                crate::audit::log(
+                    &state.db,
+                    "system",
+                    "subscriber.registration_sync",
+                    Some(serde_json::json!({
+                        "segment": source.segment(),
+                        "fetched": result.fetched,
+                        "created": result.created,
+                        "tagged": result.tagged,
+                        "invalid": result.invalid,
+                    })),
+                    None,
+                )
+                .await;
+            }
+            Err(e) => {
+                tracing::error!("Registration sync [{}] failed: {e}", source.segment());
+            }
+        }
+    }
+}
+
+/// Background loop that syncs all configured sources every `interval_secs`.
+pub async fn registration_sync_loop(
+    state: AppState,
+    sources: Vec<Arc<dyn RegistrationSource>>,
+    interval_secs: u64,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(60)));
+    loop {
+        interval.tick().await;
+        sync_all(&state, &sources).await;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Mock source returning a fixed set of rows.
+    pub struct MockRegistrationSource {
+        pub segment: String,
+        pub rows: Vec<ImportRow>,
+    }
+
+    #[async_trait]
+    impl RegistrationSource for MockRegistrationSource {
+        fn segment(&self) -> &str {
+            &self.segment
+        }
+
+        async fn fetch(&self) -> Result<Vec<ImportRow>, SyncError> {
+            Ok(self.rows.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_registration_source() {
+        let source = MockRegistrationSource {
+            segment: "attendee".to_string(),
+            rows: import::parse_payload(ImportFormat::Csv, "email,name\na@example.com,A").unwrap(),
+        };
+        let rows = source.fetch().await.unwrap();
+        assert_eq!(source.segment(), "attendee");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].email, "a@example.com");
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        let sources = parse_sources(
+            " Attendee|https://reg.example.com/export.csv , volunteer|https://v.example.com/api,bad,x|ftp://nope,",
+        );
+        assert_eq!(
+            sources,
+            vec![
+                (
+                    "attendee".to_string(),
+                    "https://reg.example.com/export.csv".to_string()
+                ),
+                (
+                    "volunteer".to_string(),
+                    "https://v.example.com/api".to_string()
+                ),
+            ]
+        );
+        assert!(parse_sources("").is_empty());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            detect_format("text/csv", "https://x/api"),
+            ImportFormat::Csv
+        );
+        assert_eq!(
+            detect_format("application/json; charset=utf-8", "https://x/a.csv"),
+            ImportFormat::Json
+        );
+        assert_eq!(
+            detect_format("text/plain", "https://x/export.CSV?token=1"),
+            ImportFormat::Csv
+        );
+        assert_eq!(
+            detect_format("application/octet-stream", "https://x/api"),
+            ImportFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_subscription_source() {
+        assert_eq!(subscription_source("volunteer"), "registration:volunteer");
+    }
+}
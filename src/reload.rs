@@ -0,0 +1,177 @@
+use std::sync::{Arc, RwLock};
+
+use crate::config::AppConfig;
+use crate::rate_limit::RateLimitRule;
+use crate::AppState;
+
+/// The parts of `AppConfig` that can change without a restart. Sends already
+/// in progress keep the rate they started with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reloadable {
+    pub admin_emails: Vec<String>,
+    pub newsletter_scheduler_interval_secs: u64,
+    pub smtp_rate_limit_ms: u64,
+    pub smtp_rate_limit_max_ms: u64,
+    pub rate_limit_subscribe_email: RateLimitRule,
+    pub rate_limit_subscribe_ip: RateLimitRule,
+    pub rate_limit_login_email: RateLimitRule,
+    pub rate_limit_login_ip: RateLimitRule,
+    pub rate_limit_share_ip: RateLimitRule,
+}
+
+impl Reloadable {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            admin_emails: config.admin_emails.clone(),
+            newsletter_scheduler_interval_secs: config.newsletter_scheduler_interval_secs,
+            smtp_rate_limit_ms: config.smtp_rate_limit_ms,
+            smtp_rate_limit_max_ms: config.smtp_rate_limit_max_ms,
+            rate_limit_subscribe_email: config.rate_limit_subscribe_email,
+            rate_limit_subscribe_ip: config.rate_limit_subscribe_ip,
+            rate_limit_login_email: config.rate_limit_login_email,
+            rate_limit_login_ip: config.rate_limit_login_ip,
+            rate_limit_share_ip: config.rate_limit_share_ip,
+        }
+    }
+
+    /// Names of the settings that differ from `other`, for the log.
+    fn changes(&self, other: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut check = |name, differs: bool| {
+            if differs {
+                changed.push(name);
+            }
+        };
+        check("ADMIN_EMAILS", self.admin_emails != other.admin_emails);
+        check(
+            "NEWSLETTER_SCHEDULER_INTERVAL_SECS",
+            self.newsletter_scheduler_interval_secs != other.newsletter_scheduler_interval_secs,
+        );
+        check(
+            "SMTP_RATE_LIMIT_MS",
+            self.smtp_rate_limit_ms != other.smtp_rate_limit_ms,
+        );
+        check(
+            "SMTP_RATE_LIMIT_MAX_MS",
+            self.smtp_rate_limit_max_ms != other.smtp_rate_limit_max_ms,
+        );
+        check(
+            "RATE_LIMIT_SUBSCRIBE_EMAIL",
+            self.rate_limit_subscribe_email != other.rate_limit_subscribe_email,
+        );
+        check(
+            "RATE_LIMIT_SUBSCRIBE_IP",
+            self.rate_limit_subscribe_ip != other.rate_limit_subscribe_ip,
+        );
+        check(
+            "RATE_LIMIT_LOGIN_EMAIL",
+            self.rate_limit_login_email != other.rate_limit_login_email,
+        );
+        check(
+            "RATE_LIMIT_LOGIN_IP",
+            self.rate_limit_login_ip != other.rate_limit_login_ip,
+        );
+        check(
+            "RATE_LIMIT_SHARE_IP",
+            self.rate_limit_share_ip != other.rate_limit_share_ip,
+        );
+        changed
+    }
+}
+
+/// Current reloadable settings, shared by every clone of the state.
+#[derive(Clone)]
+pub struct LiveConfig {
+    current: Arc<RwLock<Reloadable>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Reloadable::from_config(config))),
+        }
+    }
+
+    pub fn get(&self) -> Reloadable {
+        self.current
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Swap in `next` and return the names of the settings that changed.
+    fn replace(&self, next: Reloadable) -> Vec<&'static str> {
+        let mut current = self
+            .current
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let changed = current.changes(&next);
+        *current = next;
+        changed
+    }
+}
+
+/// Re-read `.env` and the environment, apply the reloadable settings and seed
+/// any newly listed admins. Everything else still needs a restart.
+pub async fn reload(state: &AppState) -> Result<Vec<&'static str>, String> {
+    dotenvy::dotenv_override().ok();
+    let config = AppConfig::from_env().map_err(|e| format!("Invalid configuration: {e}"))?;
+    let next = Reloadable::from_config(&config);
+
+    crate::db::sync_seed_admins(&state.db, &next.admin_emails)
+        .await
+        .map_err(|e| format!("Failed to sync seed admins: {e}"))?;
+
+    let changed = state.live.replace(next);
+    if changed.is_empty() {
+        tracing::info!("Configuration reloaded, nothing changed");
+    } else {
+        tracing::info!("Configuration reloaded: {}", changed.join(", "));
+    }
+    Ok(changed)
+}
+
+/// Reload the configuration on every SIGHUP.
+pub async fn sighup_loop(state: AppState) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => signal,
+        Err(e) => {
+            tracing::warn!("Cannot listen for SIGHUP, configuration reload disabled: {e}");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        if let Err(e) = reload(&state).await {
+            tracing::error!("Configuration reload failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_reports_changes() {
+        let config = crate::config::tests::test_config();
+        let live = LiveConfig::new(&config);
+        let shared = live.clone();
+
+        let mut next = Reloadable::from_config(&config);
+        assert!(live.replace(next.clone()).is_empty());
+
+        next.smtp_rate_limit_ms = 500;
+        next.rate_limit_login_ip = RateLimitRule::new(3, 3600);
+        next.rate_limit_share_ip = RateLimitRule::new(10, 60);
+        assert_eq!(
+            live.replace(next),
+            vec![
+                "SMTP_RATE_LIMIT_MS",
+                "RATE_LIMIT_LOGIN_IP",
+                "RATE_LIMIT_SHARE_IP"
+            ]
+        );
+        assert_eq!(shared.get().smtp_rate_limit_ms, 500);
+        assert_eq!(shared.get().rate_limit_share_ip, RateLimitRule::new(10, 60));
+    }
+}
@@ -2,29 +2,46 @@ use std::net::SocketAddr;
 
 use axum::extract::{ConnectInfo, Multipart, Path, Query, State};
 use axum::http::{header, HeaderMap};
-use axum::response::{Html, IntoResponse, Redirect, Response};
-use axum_extra::extract::cookie::SameSite;
+use axum::response::{Html, IntoResponse, Json, Redirect, Response};
 use axum_extra::extract::CookieJar;
 use chrono::Utc;
 use serde::Deserialize;
 
 use crate::auth::{AdminUser, SESSION_COOKIE};
-use crate::csv_handler::{self, ExportCsvRecord};
+use crate::crm_webhook::{self, Lifecycle};
+use crate::csv_handler;
 use crate::error::AppError;
 use crate::security;
 use crate::AppState;
 
 // --- Login ---
 
+/// Cookie holding the state, nonce and PKCE verifier of a single sign-on
+/// attempt until the provider redirects back.
+const OIDC_COOKIE: &str = "oidc_login";
+
+/// How long a single sign-on attempt may take at the provider.
+const OIDC_ATTEMPT_MINUTES: i64 = 10;
+
+fn login_context(state: &AppState) -> tera::Context {
+    let mut ctx = tera::Context::new();
+    ctx.insert("remember_days", &state.config.admin_session_remember_days);
+    ctx.insert("oidc_enabled", &state.oidc.is_some());
+    ctx
+}
+
 pub async fn login_page(State(state): State<AppState>) -> Result<Html<String>, AppError> {
-    let ctx = tera::Context::new();
-    let html = state.tera.render("admin/login.html", &ctx)?;
+    let html = state
+        .tera
+        .render("admin/login.html", &login_context(&state))?;
     Ok(Html(html))
 }
 
 #[derive(Deserialize)]
 pub struct LoginForm {
     pub email: String,
+    /// "Remember this device" checkbox
+    pub remember: Option<String>,
 }
 
 pub async fn login_submit(
@@ -37,39 +54,37 @@ pub async fn login_submit(
     let client_ip = super::extract_client_ip(&headers, &connect_info);
     let ip_str = client_ip.to_string();
 
-    // Rate limiting: same limits as subscribe (email: 5/24h, IP: 10/24h)
-    let email_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM admin_login_log WHERE email = $1 AND created_at > NOW() - INTERVAL '24 hours'",
-    )
-    .bind(&email)
-    .fetch_one(&state.db)
-    .await?;
-
-    if email_count >= 5 {
+    if !state
+        .rate_limiter
+        .check(
+            "login_email",
+            &email,
+            state.live.get().rate_limit_login_email,
+        )
+        .await?
+    {
         return Err(AppError::RateLimitExceeded);
     }
-
-    let ip_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM admin_login_log WHERE ip_address = $1::inet AND created_at > NOW() - INTERVAL '24 hours'",
-    )
-    .bind(&ip_str)
-    .fetch_one(&state.db)
-    .await?;
-
-    if ip_count >= 10 {
+    if !state
+        .rate_limiter
+        .check("login_ip", &ip_str, state.live.get().rate_limit_login_ip)
+        .await?
+    {
         return Err(AppError::RateLimitExceeded);
     }
 
     // Always show success to prevent email enumeration
-    let mut ctx = tera::Context::new();
+    let mut ctx = login_context(&state);
     ctx.insert("message", "如果此 Email 有管理權限，您將收到一封登入連結。");
 
-    let is_admin: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM admins WHERE email = $1)")
-        .bind(&email)
-        .fetch_one(&state.db)
-        .await?;
+    let is_admin: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM admins WHERE email = $1 AND disabled_at IS NULL)",
+    )
+    .bind(&email)
+    .fetch_one(&state.db)
+    .await?;
 
-    // Log unconditionally (before checking is_admin) so rate limit applies to all attempts
+    // Log unconditionally (before checking is_admin) for auditing
     sqlx::query("INSERT INTO admin_login_log (email, ip_address) VALUES ($1, $2::inet)")
         .bind(&email)
         .bind(&ip_str)
@@ -80,12 +95,15 @@ pub async fn login_submit(
         let token = security::generate_token();
         let expires_at = Utc::now() + chrono::Duration::minutes(15);
 
+        let remember = form.remember.is_some() && state.config.admin_session_remember_days > 0;
+
         sqlx::query(
-            "INSERT INTO verification_tokens (admin_email, token, token_type, expires_at) VALUES ($1, $2, 'magic_link', $3)",
+            "INSERT INTO verification_tokens (admin_email, token, token_type, expires_at, remember) VALUES ($1, $2, 'magic_link', $3, $4)",
         )
         .bind(&email)
         .bind(&token)
         .bind(expires_at)
+        .bind(remember)
         .execute(&state.db)
         .await?;
 
@@ -118,8 +136,8 @@ pub async fn auth_magic_link(
 ) -> Result<(CookieJar, Redirect), AppError> {
     let now = Utc::now();
 
-    let row = sqlx::query_as::<_, (uuid::Uuid, String)>(
-        "SELECT id, admin_email FROM verification_tokens \
+    let row = sqlx::query_as::<_, (uuid::Uuid, String, bool)>(
+        "SELECT id, admin_email, remember FROM verification_tokens \
          WHERE token = $1 AND token_type = 'magic_link' \
          AND expires_at > $2 AND used_at IS NULL",
     )
@@ -128,7 +146,7 @@ pub async fn auth_magic_link(
     .fetch_optional(&state.db)
     .await?;
 
-    let Some((token_id, admin_email)) = row else {
+    let Some((token_id, admin_email, remember)) = row else {
         return Err(AppError::NotFound);
     };
 
@@ -139,63 +157,407 @@ pub async fn auth_magic_link(
         .execute(&state.db)
         .await?;
 
-    // Create session
+    start_session(
+        &state,
+        jar,
+        addr,
+        &headers,
+        &admin_email,
+        remember,
+        "magic_link",
+    )
+    .await
+}
+
+/// Sign `admin_email` in once a magic link or single sign-on vouched for it:
+/// create the session, note the device and set the session cookie.
+async fn start_session(
+    state: &AppState,
+    jar: CookieJar,
+    addr: SocketAddr,
+    headers: &HeaderMap,
+    admin_email: &str,
+    remember: bool,
+    method: &str,
+) -> Result<(CookieJar, Redirect), AppError> {
+    let now = Utc::now();
+
+    // Admins disabled since the link was sent or the provider answered can't sign in
+    let last_login = sqlx::query_scalar::<_, uuid::Uuid>(
+        "UPDATE admins SET last_login_at = $1 WHERE email = $2 AND disabled_at IS NULL RETURNING id",
+    )
+    .bind(now)
+    .bind(admin_email)
+    .fetch_optional(&state.db)
+    .await?;
+    if last_login.is_none() {
+        return Err(AppError::NotFound);
+    }
+
+    // Create session, recording the device it was created from
     let session_token = security::generate_token();
-    let session_expires = now + chrono::Duration::hours(24);
+    let revoke_token = security::generate_token();
+    let session_ttl = crate::auth::session_ttl(&state.config, remember);
+    let client_ip = super::extract_client_ip(headers, &ConnectInfo(addr));
+    let ip_str = client_ip.to_string();
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let fingerprint = crate::devices::fingerprint(&ip_str, &user_agent);
 
     sqlx::query(
-        "INSERT INTO admin_sessions (admin_email, session_token, expires_at) VALUES ($1, $2, $3)",
+        "INSERT INTO admin_sessions \
+         (admin_email, session_token, expires_at, remember, ip_address, user_agent, fingerprint, revoke_token) \
+         VALUES ($1, $2, $3, $4, $5::inet, $6, $7, $8)",
     )
-    .bind(&admin_email)
+    .bind(admin_email)
     .bind(&session_token)
-    .bind(session_expires)
+    .bind(now + session_ttl)
+    .bind(remember)
+    .bind(&ip_str)
+    .bind(&user_agent)
+    .bind(&fingerprint)
+    .bind(&revoke_token)
     .execute(&state.db)
     .await?;
 
-    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    match crate::devices::register(&state.db, admin_email, &fingerprint).await {
+        Ok(true) => {
+            send_new_signin_email(
+                state,
+                admin_email,
+                &ip_str,
+                &user_agent,
+                headers,
+                &revoke_token,
+            )
+            .await;
+        }
+        Ok(false) => {}
+        Err(e) => tracing::error!("Failed to record admin device: {e}"),
+    }
+
     crate::audit::log(
         &state.db,
-        &admin_email,
+        admin_email,
         "admin.login",
-        None,
+        Some(serde_json::json!({ "remember": remember, "method": method })),
         Some(client_ip),
     )
     .await;
 
     let is_https = state.config.base_url.starts_with("https://");
-    let cookie = axum_extra::extract::cookie::Cookie::build((SESSION_COOKIE, session_token))
-        .path("/admin")
-        .http_only(true)
-        .secure(is_https)
-        .same_site(SameSite::Lax)
-        .max_age(time::Duration::hours(24))
-        .build();
+    let cookie =
+        crate::auth::session_cookie(session_token, is_https, remember.then_some(session_ttl));
 
     Ok((jar.add(cookie), Redirect::to("/admin")))
 }
 
+#[derive(Deserialize)]
+pub struct OidcStartQuery {
+    /// "Remember this device" checkbox
+    pub remember: Option<String>,
+}
+
+/// Send the browser to the single sign-on provider, remembering the attempt
+/// in a short-lived cookie.
+pub async fn oidc_start(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<OidcStartQuery>,
+) -> Result<(CookieJar, Redirect), AppError> {
+    let provider = state.oidc.as_ref().ok_or(AppError::NotFound)?;
+    let login_state = security::generate_token();
+    let nonce = security::generate_token();
+    let verifier = security::generate_token();
+    let remember = query.remember.is_some() && state.config.admin_session_remember_days > 0;
+
+    let url = provider
+        .authorization_url(
+            &login_state,
+            &nonce,
+            &crate::oidc::code_challenge(&verifier),
+        )
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let cookie = axum_extra::extract::cookie::Cookie::build((
+        OIDC_COOKIE,
+        format!("{login_state}.{nonce}.{verifier}.{}", u8::from(remember)),
+    ))
+    .path("/admin/auth/oidc")
+    .http_only(true)
+    .secure(state.config.base_url.starts_with("https://"))
+    // Lax, so the cookie comes along on the provider's redirect back
+    .same_site(axum_extra::extract::cookie::SameSite::Lax)
+    .max_age(time::Duration::minutes(OIDC_ATTEMPT_MINUTES))
+    .build();
+    Ok((jar.add(cookie), Redirect::to(&url)))
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Set instead of `code` when the sign-in was cancelled or refused
+    pub error: Option<String>,
+}
+
+/// Where the provider sends the browser back: redeem the code, and sign in
+/// if the verified email belongs to an enabled admin.
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Result<Response, AppError> {
+    let provider = state.oidc.clone().ok_or(AppError::NotFound)?;
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    let ip_str = client_ip.to_string();
+    if !state
+        .rate_limiter
+        .check("login_ip", &ip_str, state.live.get().rate_limit_login_ip)
+        .await?
+    {
+        return Err(AppError::RateLimitExceeded);
+    }
+
+    let attempt = jar.get(OIDC_COOKIE).map(|c| c.value().to_string());
+    let jar = jar
+        .remove(axum_extra::extract::cookie::Cookie::build(OIDC_COOKIE).path("/admin/auth/oidc"));
+    let failed = |jar: CookieJar, message: &str| -> Result<Response, AppError> {
+        let mut ctx = login_context(&state);
+        ctx.insert("error", message);
+        let html = state.tera.render("admin/login.html", &ctx)?;
+        Ok((jar, Html(html)).into_response())
+    };
+
+    if let Some(error) = &query.error {
+        tracing::info!("OIDC sign-in from {ip_str} returned {error}");
+        return failed(jar, "單一登入已取消或未完成，請再試一次。");
+    }
+    let attempt = attempt.as_deref().map(|v| v.split('.').collect::<Vec<_>>());
+    let (Some([login_state, nonce, verifier, remember]), Some(code), Some(returned_state)) = (
+        attempt.as_deref(),
+        query.code.as_deref(),
+        query.state.as_deref(),
+    ) else {
+        return failed(jar, "登入逾時或連結無效，請重新登入。");
+    };
+    if !security::constant_time_eq(login_state, returned_state) {
+        return failed(jar, "登入逾時或連結無效，請重新登入。");
+    }
+
+    let email = match provider.exchange(code, verifier, nonce).await {
+        Ok(email) => email,
+        Err(e) => {
+            tracing::warn!("OIDC sign-in from {ip_str} failed: {e}");
+            return failed(jar, "無法完成單一登入，請再試一次或改用 Email 登入連結。");
+        }
+    };
+
+    sqlx::query("INSERT INTO admin_login_log (email, ip_address) VALUES ($1, $2::inet)")
+        .bind(&email)
+        .bind(&ip_str)
+        .execute(&state.db)
+        .await?;
+    let is_admin: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM admins WHERE email = $1 AND disabled_at IS NULL)",
+    )
+    .bind(&email)
+    .fetch_one(&state.db)
+    .await?;
+    if !is_admin {
+        return failed(jar, &format!("{email} 沒有管理權限。"));
+    }
+
+    let remember = *remember == "1" && state.config.admin_session_remember_days > 0;
+    Ok(
+        start_session(&state, jar, addr, &headers, &email, remember, "oidc")
+            .await?
+            .into_response(),
+    )
+}
+
+/// Tell an admin about a sign-in from a device they haven't used before,
+/// unless they turned these notifications off.
+async fn send_new_signin_email(
+    state: &AppState,
+    admin_email: &str,
+    ip: &str,
+    user_agent: &str,
+    headers: &HeaderMap,
+    revoke_token: &str,
+) {
+    let tz = match crate::admin_profile::load(&state.db, admin_email).await {
+        Ok(profile) if !profile.notify_new_signin => return,
+        Ok(profile) => profile
+            .timezone
+            .as_deref()
+            .and_then(crate::timezone::parse)
+            .unwrap_or(crate::timezone::DEFAULT),
+        Err(e) => {
+            tracing::warn!("Failed to load profile of {admin_email}: {e}");
+            crate::timezone::DEFAULT
+        }
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("ip", ip);
+    ctx.insert(
+        "location",
+        &crate::devices::rough_location(headers).unwrap_or_else(|| "未知".to_string()),
+    );
+    ctx.insert("user_agent", user_agent);
+    ctx.insert("signed_in_at", &Utc::now().to_rfc3339());
+    ctx.insert("admin_tz", tz.name());
+    ctx.insert(
+        "revoke_url",
+        &format!("{}/admin/revoke/{revoke_token}", state.config.base_url),
+    );
+    ctx.insert(
+        "logo_url",
+        &format!("{}/static/coscup-logo.png", state.config.base_url),
+    );
+    let html = match state.tera.render("emails/new_signin.html", &ctx) {
+        Ok(html) => html,
+        Err(e) => {
+            tracing::error!("Failed to render new sign-in email: {e}");
+            return;
+        }
+    };
+    if let Err(e) = state
+        .email
+        .send_email(
+            admin_email,
+            "COSCUP Newsletter Admin - 新裝置登入通知",
+            &html,
+        )
+        .await
+    {
+        tracing::error!("Failed to send new sign-in email: {e}");
+    }
+}
+
+/// Session shown on the revoke page: (admin email, IP, User-Agent, created at).
+type RevocableSession = (
+    String,
+    Option<String>,
+    Option<String>,
+    chrono::DateTime<Utc>,
+);
+
+async fn find_revocable_session(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<RevocableSession>, AppError> {
+    Ok(sqlx::query_as::<_, RevocableSession>(
+        "SELECT admin_email, host(ip_address), user_agent, created_at FROM admin_sessions \
+         WHERE revoke_token = $1 AND expires_at > NOW()",
+    )
+    .bind(token)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Confirmation page for the revoke link in the new sign-in email. Revoking
+/// needs a POST so mail scanners following the link don't end the session.
+pub async fn revoke_session_page(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let mut ctx = tera::Context::new();
+    if let Some((admin_email, ip, user_agent, created_at)) =
+        find_revocable_session(&state, &token).await?
+    {
+        let tz = crate::timezone::for_admin(&state.db, &admin_email).await;
+        ctx.insert("admin_tz", tz.name());
+        ctx.insert(
+            "session",
+            &serde_json::json!({
+                "admin_email": admin_email,
+                "ip": ip.unwrap_or_default(),
+                "user_agent": user_agent.unwrap_or_default(),
+                "created_at": created_at.to_rfc3339(),
+            }),
+        );
+        ctx.insert("token", &token);
+    }
+    let html = state.tera.render("admin/revoke_session.html", &ctx)?;
+    Ok(Html(html))
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let revoked = sqlx::query_scalar::<_, String>(
+        "DELETE FROM admin_sessions WHERE revoke_token = $1 RETURNING admin_email",
+    )
+    .bind(&token)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let mut ctx = tera::Context::new();
+    if let Some(admin_email) = revoked {
+        let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+        crate::audit::log(
+            &state.db,
+            &admin_email,
+            "admin.session_revoke",
+            None,
+            Some(client_ip),
+        )
+        .await;
+        ctx.insert("revoked", &true);
+    }
+    let html = state.tera.render("admin/revoke_session.html", &ctx)?;
+    Ok(Html(html))
+}
+
 // --- Dashboard ---
 
+/// Channels listed in the dashboard's signup source breakdown.
+const SOURCE_BREAKDOWN_LIMIT: i64 = 20;
+
 pub async fn dashboard(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
 ) -> Result<Html<String>, AppError> {
-    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscribers")
-        .fetch_one(&state.db)
-        .await?;
-    let active: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscribers WHERE status = true")
-        .fetch_one(&state.db)
-        .await?;
-    let verified: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM subscribers WHERE verified_email = true")
-            .fetch_one(&state.db)
-            .await?;
+    let counts = crate::stats_cache::subscriber_counts(&state.db).await?;
+    let cache = crate::stats_cache::staleness(
+        counts.refreshed_at,
+        Utc::now(),
+        state.config.stats_refresh_interval_secs,
+    );
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
-    ctx.insert("total", &total);
-    ctx.insert("active", &active);
-    ctx.insert("verified", &verified);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("total", &counts.total);
+    ctx.insert("active", &counts.active);
+    ctx.insert("verified", &counts.verified);
+    ctx.insert("cache", &cache);
+    ctx.insert(
+        "sources",
+        &crate::attribution::breakdown(&state.db, SOURCE_BREAKDOWN_LIMIT).await?,
+    );
+    ctx.insert(
+        "growth",
+        &crate::snapshots::growth_chart(
+            &crate::snapshots::history(&state.db, crate::snapshots::CHART_DAYS).await?,
+        ),
+    );
     let html = state.tera.render("admin/dashboard.html", &ctx)?;
     Ok(Html(html))
 }
@@ -206,6 +568,51 @@ pub async fn dashboard(
 pub struct PaginationQuery {
     pub page: Option<i64>,
     pub search: Option<String>,
+    pub segment: Option<String>,
+}
+
+/// Searches shorter than this can't use the trigram indexes, so the JSON
+/// search endpoint answers them with no results instead of a table scan.
+const MIN_SEARCH_CHARS: usize = 3;
+
+/// Most matches returned by the JSON search endpoint.
+const SEARCH_RESULT_LIMIT: i64 = 10;
+
+/// `ILIKE` pattern matching `term` anywhere, with `%`, `_` and `\` in the term
+/// taken literally. `None` for a blank term.
+fn like_pattern(term: &str) -> Option<String> {
+    let term = term.trim();
+    if term.is_empty() {
+        return None;
+    }
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    Some(pattern)
+}
+
+/// Append the search and segment conditions shared by the list and count queries.
+fn push_subscriber_filters(
+    qb: &mut sqlx::QueryBuilder<'_, sqlx::Postgres>,
+    search_pattern: Option<&String>,
+    segment: Option<&crate::segment::SegmentFilter>,
+) {
+    if let Some(pattern) = search_pattern {
+        qb.push(" AND (s.email ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR s.name ILIKE ")
+            .push_bind(pattern.clone())
+            .push(")");
+    }
+    if let Some(filter) = segment {
+        filter.push_conditions(qb);
+    }
 }
 
 pub async fn subscribers_list(
@@ -217,70 +624,47 @@ pub async fn subscribers_list(
     let per_page: i64 = 50;
     let offset = (page - 1) * per_page;
 
-    let search_pattern = query
-        .search
-        .as_deref()
-        .filter(|s| !s.is_empty())
-        .map(|s| format!("%{s}%"));
-
-    let (rows, total): (Vec<_>, i64) = if let Some(ref pattern) = search_pattern {
-        let rows = sqlx::query_as::<
-            _,
-            (
-                uuid::Uuid,
-                String,
-                String,
-                bool,
-                bool,
-                String,
-                Option<chrono::DateTime<chrono::Utc>>,
-            ),
-        >(
-            "SELECT id, email, name, status, verified_email, ucode, bounced_at FROM subscribers \
-             WHERE email ILIKE $1 OR name ILIKE $1 \
-             ORDER BY created_at DESC LIMIT $2 OFFSET $3",
-        )
-        .bind(pattern)
-        .bind(per_page)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await?;
+    let search_pattern = query.search.as_deref().and_then(like_pattern);
 
-        let total: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM subscribers WHERE email ILIKE $1 OR name ILIKE $1",
-        )
-        .bind(pattern)
-        .fetch_one(&state.db)
-        .await?;
+    let segment_id = query
+        .segment
+        .as_deref()
+        .and_then(|s| s.parse::<uuid::Uuid>().ok());
+    let segment_filter = match segment_id {
+        Some(id) => crate::segment::load(&state.db, id)
+            .await?
+            .map(|(_, filter)| filter),
+        None => None,
+    };
 
-        (rows, total)
-    } else {
-        let rows = sqlx::query_as::<
-            _,
-            (
-                uuid::Uuid,
-                String,
-                String,
-                bool,
-                bool,
-                String,
-                Option<chrono::DateTime<chrono::Utc>>,
-            ),
-        >(
-            "SELECT id, email, name, status, verified_email, ucode, bounced_at FROM subscribers \
-             ORDER BY created_at DESC LIMIT $1 OFFSET $2",
-        )
-        .bind(per_page)
-        .bind(offset)
+    let mut qb = sqlx::QueryBuilder::<sqlx::Postgres>::new(
+        "SELECT s.id, s.email, s.name, s.status, s.verified_email, s.ucode, s.bounced_at \
+         FROM subscribers s WHERE true",
+    );
+    push_subscriber_filters(&mut qb, search_pattern.as_ref(), segment_filter.as_ref());
+    qb.push(" ORDER BY s.created_at DESC LIMIT ")
+        .push_bind(per_page)
+        .push(" OFFSET ")
+        .push_bind(offset);
+    let rows = qb
+        .build_query_as::<(
+            uuid::Uuid,
+            String,
+            String,
+            bool,
+            bool,
+            String,
+            Option<chrono::DateTime<chrono::Utc>>,
+        )>()
         .fetch_all(&state.db)
         .await?;
 
-        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscribers")
-            .fetch_one(&state.db)
-            .await?;
+    let mut qb =
+        sqlx::QueryBuilder::<sqlx::Postgres>::new("SELECT COUNT(*) FROM subscribers s WHERE true");
+    push_subscriber_filters(&mut qb, search_pattern.as_ref(), segment_filter.as_ref());
+    let total: i64 = qb.build_query_scalar().fetch_one(&state.db).await?;
 
-        (rows, total)
-    };
+    let segments = crate::segment::options(&state.db).await?;
 
     let total_pages = (total + per_page - 1) / per_page;
 
@@ -295,21 +679,375 @@ pub async fn subscribers_list(
                     "status": status,
                     "verified_email": verified_email,
                     "ucode": ucode,
-                    "bounced_at": bounced_at.map(|t| t.format("%Y-%m-%d %H:%M").to_string()),
+                    "bounced_at": bounced_at.map(|t| t.to_rfc3339()),
                 })
             },
         )
-        .collect();
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("subscribers", &subscribers);
+    ctx.insert("page", &page);
+    ctx.insert("total_pages", &total_pages);
+    ctx.insert("total", &total);
+    ctx.insert("search", &query.search.unwrap_or_default());
+    ctx.insert("segments", &segments);
+    ctx.insert(
+        "segment",
+        &segment_id.map(|id| id.to_string()).unwrap_or_default(),
+    );
+    ctx.insert(
+        "registration_sync_enabled",
+        &!crate::registration::parse_sources(&state.config.registration_sync_sources).is_empty(),
+    );
+    ctx.insert("block_role_accounts", &state.config.block_role_accounts);
+    let html = state.tera.render("admin/subscribers.html", &ctx)?;
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// Quick search for the subscribers page, called as the admin types: the
+/// best trigram matches on email or name, without counting or paging. The
+/// query is echoed back so the page can drop responses for stale input.
+pub async fn subscribers_search(
+    State(state): State<AppState>,
+    AdminUser(_admin_email): AdminUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let term = query.q.trim();
+    let pattern = like_pattern(term).filter(|_| term.chars().count() >= MIN_SEARCH_CHARS);
+    let Some(pattern) = pattern else {
+        return Ok(Json(serde_json::json!({ "q": query.q, "results": [] })));
+    };
+
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, String, bool)>(
+        "SELECT s.id, s.email, s.name, s.status FROM subscribers s \
+         WHERE s.email ILIKE $1 OR s.name ILIKE $1 \
+         ORDER BY GREATEST(similarity(s.email, $2), similarity(s.name, $2)) DESC, s.created_at DESC \
+         LIMIT $3",
+    )
+    .bind(&pattern)
+    .bind(term)
+    .bind(SEARCH_RESULT_LIMIT)
+    .fetch_all(&state.db)
+    .await?;
+
+    let results: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|(id, email, name, status)| {
+            serde_json::json!({
+                "id": id.to_string(),
+                "email": mask_email(&email),
+                "name": mask_name(&name),
+                "status": status,
+            })
+        })
+        .collect();
+    Ok(Json(
+        serde_json::json!({ "q": query.q, "results": results }),
+    ))
+}
+
+// --- Subscriber detail ---
+
+/// Longest note admins can save on a subscriber.
+const MAX_NOTE_CHARS: usize = 5000;
+
+/// A subscriber's notes, newest first: the first entry is the current note.
+async fn subscriber_notes(
+    state: &AppState,
+    id: uuid::Uuid,
+) -> Result<Vec<serde_json::Value>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String, chrono::DateTime<Utc>)>(
+        "SELECT body, author_email, created_at FROM subscriber_notes \
+         WHERE subscriber_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(body, author, at)| {
+            serde_json::json!({ "body": body, "author": author, "at": at.to_rfc3339() })
+        })
+        .collect())
+}
+
+pub async fn subscriber_detail(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Html<String>, AppError> {
+    #[allow(clippy::type_complexity)]
+    let row = sqlx::query_as::<
+        _,
+        (
+            String,
+            String,
+            bool,
+            bool,
+            String,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<chrono::DateTime<Utc>>,
+            chrono::DateTime<Utc>,
+        ),
+    >(
+        "SELECT email, name, status, verified_email, ucode, subscription_source, locale, timezone, \
+         bounced_at, created_at FROM subscribers WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    let (
+        email,
+        name,
+        status,
+        verified_email,
+        ucode,
+        source,
+        locale,
+        timezone,
+        bounced_at,
+        created_at,
+    ) = row;
+
+    let subscriber = serde_json::json!({
+        "id": id.to_string(),
+        "email": mask_email(&email),
+        "role_account": crate::email_validation::is_role_account(&email),
+        "name": mask_name(&name),
+        "status": status,
+        "verified_email": verified_email,
+        "ucode": ucode,
+        "source": source,
+        "locale": locale,
+        "timezone": timezone,
+        "bounced_at": bounced_at.map(|t| t.to_rfc3339()),
+        "created_at": created_at.to_rfc3339(),
+    });
+
+    let tags = sqlx::query_scalar::<_, String>(
+        "SELECT t.name FROM subscriber_tags st JOIN tags t ON t.id = st.tag_id \
+         WHERE st.subscriber_id = $1 ORDER BY t.name",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("subscriber", &subscriber);
+    ctx.insert("tags", &tags);
+    ctx.insert("sends", &send_rows(&state, id).await?);
+    ctx.insert("notes", &subscriber_notes(&state, id).await?);
+    ctx.insert("consents", &consent_rows(&state, id).await?);
+    ctx.insert(
+        "subscriber_events",
+        &crate::subscriber_events::for_subscriber(&state.db, id).await?,
+    );
+    ctx.insert("max_note_chars", &MAX_NOTE_CHARS);
+    let html = state.tera.render("admin/subscriber_detail.html", &ctx)?;
+    Ok(Html(html))
+}
+
+/// The subscriber's latest sends for the detail page, with any bounce or
+/// complaint the provider reported for them.
+async fn send_rows(state: &AppState, id: uuid::Uuid) -> Result<Vec<serde_json::Value>, AppError> {
+    Ok(sqlx::query_as::<
+        _,
+        (
+            uuid::Uuid,
+            String,
+            String,
+            Option<chrono::DateTime<Utc>>,
+            Option<String>,
+            bool,
+        ),
+    >(
+        "SELECT n.id, n.title, ns.status, COALESCE(ns.sent_at, ns.failed_at), \
+         ns.bounce_type, ns.complained_at IS NOT NULL \
+         FROM newsletter_sends ns JOIN newsletters n ON n.id = ns.newsletter_id \
+         WHERE ns.subscriber_id = $1 ORDER BY n.created_at DESC LIMIT 20",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|(nid, title, send_status, at, bounce_type, complained)| {
+        serde_json::json!({
+            "newsletter_id": nid.to_string(),
+            "title": title,
+            "status": send_status,
+            "at": at.map(|t| t.to_rfc3339()),
+            "bounce_type": bounce_type,
+            "complained": complained,
+        })
+    })
+    .collect())
+}
+
+/// Consent records for the detail page.
+async fn consent_rows(
+    state: &AppState,
+    id: uuid::Uuid,
+) -> Result<Vec<serde_json::Value>, AppError> {
+    Ok(crate::consent::for_subscriber(&state.db, id)
+        .await?
+        .into_iter()
+        .map(|c| {
+            serde_json::json!({
+                "action": c.action,
+                "version": c.version,
+                "source": c.source,
+                "ip_address": c.ip_address,
+                "at": c.created_at.to_rfc3339(),
+            })
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+pub struct NoteForm {
+    pub body: String,
+}
+
+/// Save a new version of the subscriber's note; earlier versions stay as history.
+pub async fn save_note(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    axum::Form(form): axum::Form<NoteForm>,
+) -> Result<Redirect, AppError> {
+    let body = form.body.trim();
+    if body.chars().count() > MAX_NOTE_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Notes are limited to {MAX_NOTE_CHARS} characters"
+        )));
+    }
+
+    let current = sqlx::query_scalar::<_, String>(
+        "SELECT body FROM subscriber_notes WHERE subscriber_id = $1 ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+    // Saving without changes (or clearing a note that doesn't exist) adds nothing
+    if current.as_deref().unwrap_or_default() == body {
+        return Ok(Redirect::to(&format!("/admin/subscribers/{id}")));
+    }
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM subscribers WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    if !exists {
+        return Err(AppError::NotFound);
+    }
+
+    sqlx::query(
+        "INSERT INTO subscriber_notes (subscriber_id, body, author_email) VALUES ($1, $2, $3)",
+    )
+    .bind(id)
+    .bind(body)
+    .bind(&admin_email)
+    .execute(&state.db)
+    .await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "subscriber.note",
+        Some(serde_json::json!({ "subscriber_id": id.to_string() })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/subscribers/{id}")))
+}
+
+#[derive(Deserialize)]
+pub struct SubscriberTagForm {
+    pub name: String,
+    /// `remove` takes the tag off instead of adding it
+    #[serde(default)]
+    pub action: String,
+}
+
+/// Add a tag to the subscriber, or remove one.
+pub async fn update_tags(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    axum::Form(form): axum::Form<SubscriberTagForm>,
+) -> Result<Redirect, AppError> {
+    let name = crate::tags::clean_name(&form.name).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Tag name must be 1 to {} characters",
+            crate::tags::MAX_NAME_CHARS
+        ))
+    })?;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM subscribers WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    if !exists {
+        return Err(AppError::NotFound);
+    }
+
+    let remove = form.action == "remove";
+    let changed = if remove {
+        let tag_id = sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM tags WHERE name = $1")
+            .bind(&name)
+            .fetch_optional(&state.db)
+            .await?;
+        match tag_id {
+            Some(tag_id) => crate::tags::untag_subscriber(&state.db, id, tag_id).await?,
+            None => false,
+        }
+    } else {
+        let tag_id = crate::tags::ensure_tag(&state.db, &name).await?;
+        crate::tags::tag_subscriber(&state.db, id, tag_id).await?
+    };
+
+    if changed {
+        let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+        crate::audit::log(
+            &state.db,
+            &admin_email,
+            if remove { "tag.remove" } else { "tag.add" },
+            Some(serde_json::json!({ "subscriber_id": id.to_string(), "tag": name })),
+            Some(client_ip),
+        )
+        .await;
+    }
 
-    let mut ctx = tera::Context::new();
-    ctx.insert("admin_email", &admin_email);
-    ctx.insert("subscribers", &subscribers);
-    ctx.insert("page", &page);
-    ctx.insert("total_pages", &total_pages);
-    ctx.insert("total", &total);
-    ctx.insert("search", &query.search.unwrap_or_default());
-    let html = state.tera.render("admin/subscribers.html", &ctx)?;
-    Ok(Html(html))
+    Ok(Redirect::to(&format!("/admin/subscribers/{id}")))
 }
 
 // --- Toggle status ---
@@ -323,11 +1061,21 @@ pub async fn toggle_status(
 ) -> Result<Redirect, AppError> {
     let now = Utc::now();
 
-    sqlx::query("UPDATE subscribers SET status = NOT status, updated_at = $1 WHERE id = $2")
-        .bind(now)
-        .bind(id)
-        .execute(&state.db)
-        .await?;
+    let status = sqlx::query_scalar::<_, bool>(
+        "UPDATE subscribers SET status = NOT status, updated_at = $1 WHERE id = $2 RETURNING status",
+    )
+    .bind(now)
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+    if let Some(status) = status {
+        let event = if status {
+            Lifecycle::Resubscribed
+        } else {
+            Lifecycle::Unsubscribed
+        };
+        crm_webhook::emit(&state, id, event).await?;
+    }
 
     let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
     crate::audit::log(
@@ -410,16 +1158,21 @@ pub async fn import_csv(
     mut multipart: Multipart,
 ) -> Result<Redirect, AppError> {
     let mut csv_data = String::new();
+    let mut allow_role_accounts = false;
     while let Some(field) = multipart
         .next_field()
         .await
         .map_err(|e| AppError::BadRequest(e.to_string()))?
     {
-        if field.name() == Some("file") {
-            csv_data = field
-                .text()
-                .await
-                .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        match field.name() {
+            Some("file") => {
+                csv_data = field
+                    .text()
+                    .await
+                    .map_err(|e| AppError::BadRequest(e.to_string()))?;
+            }
+            Some("allow_role_accounts") => allow_role_accounts = true,
+            _ => {}
         }
     }
 
@@ -430,15 +1183,31 @@ pub async fn import_csv(
     let records = csv_handler::parse_import_csv(&csv_data)
         .map_err(|e| AppError::BadRequest(e.to_string()))?;
 
+    let block_role_accounts = state.config.block_role_accounts && !allow_role_accounts;
+    let mut role_accounts_skipped = 0usize;
     for record in &records {
+        let normalized =
+            match crate::email_validation::normalize_for_signup(&record.email, block_role_accounts)
+            {
+                Ok(normalized) => normalized,
+                Err(e) => {
+                    if e == crate::email_validation::EmailError::RoleAccount {
+                        role_accounts_skipped += 1;
+                    }
+                    tracing::warn!("Skipping import record {:?}: {e}", record.email);
+                    continue;
+                }
+            };
         let secret_code = security::generate_secret_code();
 
         let result = sqlx::query(
-            "INSERT INTO subscribers (email, name, secret_code, ucode, legacy_admin_link, status, verified_email, subscription_source) \
-             VALUES ($1, $2, $3, $4, $5, $6, $7, 'import') \
+            "INSERT INTO subscribers (email, canonical_email, name, secret_code, ucode, legacy_admin_link, status, verified_email, subscription_source) \
+             SELECT $1, $2, $3, $4, $5, $6, $7, $8, 'import' \
+             WHERE NOT EXISTS (SELECT 1 FROM subscribers WHERE canonical_email = $2) \
              ON CONFLICT (email) DO NOTHING",
         )
-        .bind(&record.email)
+        .bind(&normalized.email)
+        .bind(&normalized.canonical)
         .bind(&record.name)
         .bind(&secret_code)
         .bind(&record.ucode)
@@ -458,7 +1227,11 @@ pub async fn import_csv(
         &state.db,
         &admin_email,
         "subscriber.import",
-        Some(serde_json::json!({ "count": records.len() })),
+        Some(serde_json::json!({
+            "count": records.len(),
+            "allow_role_accounts": allow_role_accounts,
+            "role_accounts_skipped": role_accounts_skipped,
+        })),
         Some(client_ip),
     )
     .await;
@@ -466,36 +1239,325 @@ pub async fn import_csv(
     Ok(Redirect::to("/admin/subscribers"))
 }
 
-// --- CSV Export ---
+// --- Bulk unsubscribe ---
 
-pub async fn export_csv(
+fn render_bulk_unsubscribe(
+    state: &AppState,
+    admin_email: &str,
+    results: Option<&[crate::bulk_unsubscribe::RowResult]>,
+) -> Result<Html<String>, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", admin_email);
+    ctx.insert("max_rows", &crate::bulk_unsubscribe::MAX_ROWS);
+    if let Some(results) = results {
+        let counts: Vec<serde_json::Value> = crate::bulk_unsubscribe::counts(results)
+            .into_iter()
+            .map(|(outcome, count)| serde_json::json!({ "label": outcome.label(), "count": count }))
+            .collect();
+        ctx.insert("results", results);
+        ctx.insert("counts", &counts);
+    }
+    let html = state.tera.render("admin/bulk_unsubscribe.html", &ctx)?;
+    Ok(Html(html))
+}
+
+pub async fn bulk_unsubscribe_page(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    render_bulk_unsubscribe(&state, &admin_email, None)
+}
+
+/// Unsubscribe or suppress the pasted and uploaded addresses and show a
+/// result per line.
+pub async fn bulk_unsubscribe(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Html<String>, AppError> {
+    let mut text = String::new();
+    let mut mode = None;
+    let mut reason = String::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?
+    {
+        let name = field.name().map(str::to_string);
+        let value = field
+            .text()
+            .await
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        match name.as_deref() {
+            Some("emails" | "file") => {
+                text.push_str(&value);
+                text.push('\n');
+            }
+            Some("mode") => mode = crate::bulk_unsubscribe::Mode::parse(&value),
+            Some("reason") => reason = value.trim().to_string(),
+            _ => {}
+        }
+    }
+    let mode = mode.ok_or_else(|| AppError::BadRequest("Unknown mode".to_string()))?;
+
+    let lines = crate::bulk_unsubscribe::parse_lines(&text);
+    if lines.is_empty() {
+        return Err(AppError::BadRequest(
+            "請貼上或上傳要處理的 Email".to_string(),
+        ));
+    }
+    if lines.len() > crate::bulk_unsubscribe::MAX_ROWS {
+        return Err(AppError::BadRequest(format!(
+            "一次最多處理 {} 筆",
+            crate::bulk_unsubscribe::MAX_ROWS
+        )));
+    }
+    let results = crate::bulk_unsubscribe::apply(&state, &lines, mode).await?;
+
+    let counts: serde_json::Map<String, serde_json::Value> =
+        crate::bulk_unsubscribe::counts(&results)
+            .into_iter()
+            .map(|(outcome, count)| (outcome.as_str().to_string(), count.into()))
+            .collect();
+    let changed: Vec<&str> = results
+        .iter()
+        .filter(|r| r.outcome.changed())
+        .map(|r| r.input.as_str())
+        .collect();
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "subscriber.bulk_unsubscribe",
+        Some(serde_json::json!({
+            "mode": mode.as_str(),
+            "reason": reason,
+            "rows": results.len(),
+            "counts": counts,
+            "changed": changed,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    render_bulk_unsubscribe(&state, &admin_email, Some(&results))
+}
+
+// --- Registration sync ---
+
+pub async fn sync_registration(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Redirect, AppError> {
+    let sources = crate::registration::sources_from_config(&state.config);
+    if sources.is_empty() {
+        return Err(AppError::BadRequest(
+            "No registration sync sources configured".to_string(),
+        ));
+    }
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "subscriber.registration_sync_trigger",
+        Some(serde_json::json!({
+            "segments": sources.iter().map(|s| s.segment().to_string()).collect::<Vec<_>>(),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    tokio::spawn(async move {
+        crate::registration::sync_all(&state, &sources).await;
+    });
+
+    Ok(Redirect::to("/admin/subscribers"))
+}
+
+// --- Google Sheets sync ---
+
+pub async fn sheets_page(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("sheets", &crate::sheets::list(&state.db).await?);
+    ctx.insert("block_role_accounts", &state.config.block_role_accounts);
+    ctx.insert(
+        "interval_mins",
+        &(state.config.sheet_sync_interval_secs.max(60) / 60),
+    );
+    let html = state.tera.render("admin/sheets.html", &ctx)?;
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct SheetForm {
+    pub url: String,
+    pub tag: String,
+    #[serde(default)]
+    pub allow_role_accounts: Option<String>,
+}
+
+pub async fn add_sheet(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    axum::Form(form): axum::Form<SheetForm>,
+) -> Result<Redirect, AppError> {
+    let url = form.url.trim();
+    let csv_url = crate::sheets::csv_export_url(url).map_err(AppError::BadRequest)?;
+    let tag = crate::tags::clean_name(&form.tag).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "標籤名稱不可空白或超過 {} 字",
+            crate::tags::MAX_NAME_CHARS
+        ))
+    })?;
+    let allow_role_accounts = form.allow_role_accounts.is_some();
+    let id = crate::sheets::add(
+        &state.db,
+        url,
+        &csv_url,
+        &tag,
+        allow_role_accounts,
+        &admin_email,
+    )
+    .await?
+    .ok_or_else(|| AppError::BadRequest("這個試算表已經在同步清單中".to_string()))?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "subscriber.sheet_add",
+        Some(serde_json::json!({
+            "sheet_id": id.to_string(),
+            "url": url,
+            "tag": tag,
+            "allow_role_accounts": allow_role_accounts,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/subscribers/sheets"))
+}
+
+/// Sync one sheet now rather than waiting for the next round; the outcome
+/// shows on the page.
+pub async fn sync_sheet(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let sheet = crate::sheets::find(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let result = crate::sheets::sync_sheet(&state, &sheet).await;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "subscriber.sheet_sync",
+        Some(serde_json::json!({
+            "sheet_id": id.to_string(),
+            "tag": sheet.tag,
+            "created": result.as_ref().ok().map(|r| r.created),
+            "error": result.as_ref().err().map(ToString::to_string),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/subscribers/sheets"))
+}
+
+pub async fn remove_sheet(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let url = crate::sheets::remove(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "subscriber.sheet_remove",
+        Some(serde_json::json!({ "sheet_id": id.to_string(), "url": url })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/subscribers/sheets"))
+}
+
+// --- Subscribe QR code ---
+
+#[derive(Deserialize)]
+pub struct SubscribeQrQuery {
+    pub source: Option<String>,
+}
+
+/// PNG QR code linking to the subscribe page with `utm_source` set, for
+/// printed material; signups through it show up in the source breakdown.
+pub async fn subscribe_qr(
     AdminUser(_admin_email): AdminUser,
     State(state): State<AppState>,
+    Query(query): Query<SubscribeQrQuery>,
 ) -> Result<Response, AppError> {
-    let rows = sqlx::query_as::<_, (String, String, String, bool, String)>(
-        "SELECT email, name, ucode, status, secret_code FROM subscribers ORDER BY created_at DESC",
+    let source = query
+        .source
+        .as_deref()
+        .and_then(crate::qr::normalize_source)
+        .ok_or_else(|| {
+            AppError::BadRequest(
+                "請提供來源標籤 source（英數字、- 或 _，最多 50 字），例如 booth2025".to_string(),
+            )
+        })?;
+    let url = crate::qr::subscribe_url(&state.config.base_url, &source);
+    let png = crate::qr::png(&url).map_err(AppError::Internal)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"subscribe-{source}.png\""),
+            ),
+        ],
+        png,
     )
-    .fetch_all(&state.db)
-    .await?;
+        .into_response())
+}
 
-    let records: Vec<ExportCsvRecord> = rows
-        .into_iter()
-        .map(|(email, name, ucode, status, secret_code)| {
-            let admin_link = security::compute_admin_link(&secret_code, &email);
-            let openhash = security::compute_openhash(&secret_code, &ucode, "", "");
-            ExportCsvRecord {
-                email,
-                name,
-                ucode,
-                status,
-                admin_link,
-                openhash,
-            }
-        })
-        .collect();
+// --- CSV Export ---
 
-    let csv_data =
-        csv_handler::write_export_csv(&records).map_err(|e| AppError::Internal(e.to_string()))?;
+pub async fn export_csv(
+    AdminUser(_admin_email): AdminUser,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let csv_data = crate::backup::subscriber_export_csv(&state.db).await?;
 
     Ok((
         [
@@ -512,73 +1574,124 @@ pub async fn export_csv(
 
 // --- Stats ---
 
+/// Subscribers listed in the stats page's referral leaderboard.
+const TOP_REFERRERS_LIMIT: i64 = 20;
+
 pub async fn stats_page(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
 ) -> Result<Html<String>, AppError> {
-    // Per-newsletter aggregated stats
-    let newsletter_stats = sqlx::query_as::<_, (uuid::Uuid, String, String, i32, i32)>(
-        "SELECT id, title, slug, sent_count, total_count FROM newsletters \
-         WHERE status IN ('sent', 'sending') ORDER BY created_at DESC",
-    )
-    .fetch_all(&state.db)
-    .await?;
-
-    let mut stats_rows: Vec<serde_json::Value> = Vec::new();
-    for (id, title, slug, sent_count, _total_count) in &newsletter_stats {
-        let unique_opens: i64 = sqlx::query_scalar(
-            "SELECT COUNT(DISTINCT ucode) FROM email_events WHERE topic = $1 AND event_type = 'open'",
-        )
-        .bind(slug)
-        .fetch_one(&state.db)
-        .await?;
-
-        #[allow(clippy::cast_precision_loss)]
-        let open_rate = if *sent_count > 0 {
-            format!(
-                "{:.1}%",
-                (unique_opens as f64 / f64::from(*sent_count)) * 100.0
-            )
-        } else {
-            "—".to_string()
-        };
-
-        stats_rows.push(serde_json::json!({
-            "id": id.to_string(),
-            "title": title,
-            "sent_count": sent_count,
-            "unique_opens": unique_opens,
-            "open_rate": open_rate,
-        }));
-    }
-
-    // Legacy topic-based stats (for events not linked to a newsletter)
-    let topic_stats = sqlx::query_as::<_, (String, String, i64)>(
-        "SELECT topic, event_type, COUNT(*) as count FROM email_events \
-         GROUP BY topic, event_type ORDER BY topic, event_type",
-    )
-    .fetch_all(&state.db)
-    .await?;
+    let stats_rows: Vec<serde_json::Value> = crate::stats::newsletter_summaries(&state.db)
+        .await?
+        .into_iter()
+        .map(|n| {
+            serde_json::json!({
+                "id": n.id.to_string(),
+                "title": n.title,
+                "sent_count": n.sent_count,
+                "unique_opens": n.unique_opens,
+                "open_rate": crate::stats::format_rate(n.open_rate),
+            })
+        })
+        .collect();
 
-    let legacy_stats: Vec<serde_json::Value> = topic_stats
+    let cta_rows: Vec<serde_json::Value> = crate::stats::cta_comparison(&state.db)
+        .await?
         .into_iter()
-        .map(|(topic, event_type, count)| {
+        .map(|n| {
             serde_json::json!({
-                "topic": topic,
-                "event_type": event_type,
-                "count": count,
+                "id": n.id.to_string(),
+                "title": n.title,
+                "sent_count": n.sent_count,
+                "open_rate": crate::stats::format_rate(n.open_rate),
+                "cta_links": n.cta.links.len(),
+                "cta_unique_clicks": n.cta.unique_clicks,
+                "cta_click_through_rate": crate::stats::format_rate(n.cta.click_through_rate),
+                "goals": n.goals,
+                "goals_reached": n.goals_reached,
             })
         })
         .collect();
 
+    // Legacy topic-based stats (for events not linked to a newsletter)
+    let legacy_stats = crate::stats::topic_events(&state.db).await?;
+
+    let counts = crate::stats_cache::subscriber_counts(&state.db).await?;
+    let cache = crate::stats_cache::staleness(
+        counts.refreshed_at,
+        Utc::now(),
+        state.config.stats_refresh_interval_secs,
+    );
+
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
     ctx.insert("newsletter_stats", &stats_rows);
+    ctx.insert("cta_comparison", &cta_rows);
     ctx.insert("stats", &legacy_stats);
+    ctx.insert("cache", &cache);
+    ctx.insert(
+        "top_referrers",
+        &crate::referral::top_referrers(&state.db, TOP_REFERRERS_LIMIT).await?,
+    );
     let html = state.tera.render("admin/stats.html", &ctx)?;
     Ok(Html(html))
 }
 
+/// Opens by weekday and hour across all newsletters, for the heatmap on the
+/// stats page.
+pub async fn open_heatmap(
+    State(state): State<AppState>,
+    AdminUser(_admin_email): AdminUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let grid = crate::stats_cache::open_heatmap(&state.db).await?;
+    let max = grid.iter().flatten().copied().max().unwrap_or(0);
+    Ok(Json(serde_json::json!({
+        "weekdays": ["一", "二", "三", "四", "五", "六", "日"],
+        "opens": grid,
+        "max": max,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct RefreshStatsForm {
+    #[serde(default)]
+    pub back: String,
+}
+
+/// Refresh the cached stats now instead of waiting for the next scheduled run.
+pub async fn refresh_stats(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    axum::Form(form): axum::Form<RefreshStatsForm>,
+) -> Result<Redirect, AppError> {
+    crate::stats_cache::refresh(&state.db).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "stats.refresh",
+        None,
+        Some(client_ip),
+    )
+    .await;
+
+    let back = if form.back == "/admin" {
+        "/admin"
+    } else {
+        "/admin/stats"
+    };
+    Ok(Redirect::to(back))
+}
+
 // --- Logout ---
 
 pub async fn logout(
@@ -630,7 +1743,7 @@ fn mask_str(s: &str) -> String {
     format!("{first}****{last}")
 }
 
-fn mask_email(email: &str) -> String {
+pub(crate) fn mask_email(email: &str) -> String {
     match email.split_once('@') {
         Some((local, domain)) => format!("{}@{domain}", mask_str(local)),
         None => mask_str(email),
@@ -640,3 +1753,86 @@ fn mask_email(email: &str) -> String {
 fn mask_name(name: &str) -> String {
     mask_str(name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!(like_pattern("  "), None);
+        assert_eq!(like_pattern(" alice ").as_deref(), Some("%alice%"));
+        assert_eq!(
+            like_pattern("50%_off\\").as_deref(),
+            Some("%50\\%\\_off\\\\%")
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_save_note(db: sqlx::PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.org', 's', 'u1', true, true) RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let uri = format!("/admin/subscribers/{id}/notes");
+        let counts = || async {
+            sqlx::query_as::<_, (i64, i64)>(
+                "SELECT (SELECT COUNT(*) FROM subscriber_notes), \
+                 (SELECT COUNT(*) FROM audit_log WHERE action = 'subscriber.note')",
+            )
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap()
+        };
+
+        let response = app
+            .post_form(&uri, &[("body", " 講者，請勿寄送贊助信 ")])
+            .await;
+        assert_eq!(
+            response.location(),
+            Some(format!("/admin/subscribers/{id}").as_str())
+        );
+        assert_eq!(counts().await, (1, 1));
+        let (body, author): (String, String) =
+            sqlx::query_as("SELECT body, author_email FROM subscriber_notes")
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(body, "講者，請勿寄送贊助信");
+        assert_eq!(author, "admin@coscup.org");
+        let (admin_email, details): (String, serde_json::Value) = sqlx::query_as(
+            "SELECT admin_email, details FROM audit_log WHERE action = 'subscriber.note'",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(admin_email, "admin@coscup.org");
+        assert_eq!(details["subscriber_id"], id.to_string());
+
+        // Saving the same note again adds neither a version nor an audit entry
+        let response = app
+            .post_form(&uri, &[("body", "講者，請勿寄送贊助信")])
+            .await;
+        assert!(response.location().is_some());
+        assert_eq!(counts().await, (1, 1));
+
+        let too_long = "長".repeat(MAX_NOTE_CHARS + 1);
+        let response = app.post_form(&uri, &[("body", &too_long)]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        let at_limit = "長".repeat(MAX_NOTE_CHARS);
+        app.post_form(&uri, &[("body", &at_limit)]).await;
+        assert_eq!(counts().await, (2, 2));
+
+        let detail = app.get(&format!("/admin/subscribers/{id}")).await;
+        assert_eq!(detail.status, StatusCode::OK);
+        assert!(detail.body.contains("講者，請勿寄送贊助信"));
+    }
+}
@@ -4,47 +4,102 @@ use axum::extract::{ConnectInfo, Path, Query, State};
 use axum::http::HeaderMap;
 use axum::response::{Html, Redirect};
 use axum::Form;
-use chrono::{FixedOffset, Utc};
+use chrono::{DateTime, Months, Utc};
 use serde::Deserialize;
 
 use crate::auth::AdminUser;
 use crate::error::AppError;
+use crate::jobs::JobKind;
 use crate::AppState;
 
-fn taiwan_offset() -> FixedOffset {
-    FixedOffset::east_opt(8 * 3600).expect("valid offset")
+// --- Admins list ---
+
+/// Whether an admin whose last sign-in (or creation, if they never signed in)
+/// was at `last_activity` counts as dormant. `months = 0` disables the check.
+fn is_dormant(last_activity: DateTime<Utc>, now: DateTime<Utc>, months: u32) -> bool {
+    months > 0
+        && now
+            .checked_sub_months(Months::new(months))
+            .is_some_and(|cutoff| last_activity < cutoff)
 }
 
-// --- Admins list ---
+#[derive(Deserialize)]
+pub struct AdminsQuery {
+    /// Outcome of the last SMTP test, `ok` or `failed`
+    pub smtp_test: Option<String>,
+    pub smtp_error: Option<String>,
+}
 
 pub async fn admins_list(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
+    Query(query): Query<AdminsQuery>,
 ) -> Result<Html<String>, AppError> {
-    let rows = sqlx::query_as::<_, (uuid::Uuid, String, Option<String>, chrono::DateTime<Utc>)>(
-        "SELECT id, email, added_by, created_at FROM admins ORDER BY created_at ASC",
+    #[allow(clippy::type_complexity)]
+    let rows = sqlx::query_as::<
+        _,
+        (
+            uuid::Uuid,
+            String,
+            Option<String>,
+            DateTime<Utc>,
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+        ),
+    >(
+        "SELECT id, email, added_by, created_at, last_login_at, disabled_at FROM admins ORDER BY created_at ASC",
     )
     .fetch_all(&state.db)
     .await?;
 
+    let now = Utc::now();
+    let dormant_months = state.config.dormant_admin_months;
     let admins: Vec<serde_json::Value> = rows
         .into_iter()
-        .map(|(id, email, added_by, created_at)| {
-            serde_json::json!({
-                "id": id.to_string(),
-                "email": email,
-                "added_by": added_by.unwrap_or_default(),
-                "created_at": created_at.with_timezone(&taiwan_offset()).format("%Y-%m-%d %H:%M").to_string(),
-            })
-        })
+        .map(
+            |(id, email, added_by, created_at, last_login_at, disabled_at)| {
+                let dormant = disabled_at.is_none()
+                    && is_dormant(last_login_at.unwrap_or(created_at), now, dormant_months);
+                serde_json::json!({
+                    "id": id.to_string(),
+                    "email": email,
+                    "added_by": added_by.unwrap_or_default(),
+                    "created_at": created_at.to_rfc3339(),
+                    "last_login_at": last_login_at.map(|t| t.to_rfc3339()),
+                    "disabled": disabled_at.is_some(),
+                    "dormant": dormant,
+                })
+            },
+        )
         .collect();
 
     let admin_count = admins.len();
+    let active_count = admins.iter().filter(|a| a["disabled"] == false).count();
+    let dormant_count = admins.iter().filter(|a| a["dormant"] == true).count();
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
     ctx.insert("admins", &admins);
     ctx.insert("admin_count", &admin_count);
+    ctx.insert("active_count", &active_count);
+    ctx.insert("dormant_count", &dormant_count);
+    ctx.insert("dormant_months", &dormant_months);
+    ctx.insert("smtp_test", &query.smtp_test.unwrap_or_default());
+    ctx.insert("smtp_error", &query.smtp_error.unwrap_or_default());
+    ctx.insert(
+        "sending_identities",
+        &state
+            .identities
+            .iter()
+            .map(|i| serde_json::json!({ "name": i.name, "from_email": i.from_email }))
+            .collect::<Vec<_>>(),
+    );
     let html = state.tera.render("admin/admins.html", &ctx)?;
     Ok(Html(html))
 }
@@ -63,10 +118,9 @@ pub async fn add_admin(
     headers: HeaderMap,
     Form(form): Form<AddAdminForm>,
 ) -> Result<Redirect, AppError> {
-    let email = form.email.trim().to_lowercase();
-    if email.is_empty() {
-        return Err(AppError::BadRequest("Email is required".to_string()));
-    }
+    let email = crate::email_validation::normalize(&form.email)
+        .map_err(|e| AppError::BadRequest(e.to_string()))?
+        .email;
 
     sqlx::query(
         "INSERT INTO admins (email, added_by) VALUES ($1, $2) ON CONFLICT (email) DO NOTHING",
@@ -143,6 +197,280 @@ pub async fn remove_admin(
     Ok(Redirect::to("/admin/admins"))
 }
 
+// --- Disable / enable admin ---
+
+/// Disable a (typically dormant) admin: they can no longer sign in and their
+/// sessions end, but the account and its history are kept.
+pub async fn disable_admin(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let target_email = sqlx::query_scalar::<_, String>("SELECT email FROM admins WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if target_email == admin_email {
+        return Err(AppError::BadRequest("無法停用自己的管理員帳號".to_string()));
+    }
+
+    let active_count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM admins WHERE disabled_at IS NULL")
+            .fetch_one(&state.db)
+            .await?;
+    if active_count <= 1 {
+        return Err(AppError::BadRequest("無法停用最後一位管理員".to_string()));
+    }
+
+    sqlx::query(
+        "UPDATE admins SET disabled_at = NOW(), disabled_by = $1 WHERE id = $2 AND disabled_at IS NULL",
+    )
+    .bind(&admin_email)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    let _ = sqlx::query("DELETE FROM admin_sessions WHERE admin_email = $1")
+        .bind(&target_email)
+        .execute(&state.db)
+        .await;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "admin.disable",
+        Some(serde_json::json!({ "disabled_email": target_email })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/admins"))
+}
+
+pub async fn enable_admin(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let target_email = sqlx::query_scalar::<_, String>(
+        "UPDATE admins SET disabled_at = NULL, disabled_by = NULL WHERE id = $1 RETURNING email",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "admin.enable",
+        Some(serde_json::json!({ "enabled_email": target_email })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/admins"))
+}
+
+// --- Configuration reload ---
+
+/// Same as sending the process SIGHUP: reload rate limits, the scheduler
+/// interval, SMTP rate limits and seed admins from the environment.
+pub async fn reload_config(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Redirect, AppError> {
+    let changed = crate::reload::reload(&state)
+        .await
+        .map_err(AppError::Internal)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "config.reload",
+        Some(serde_json::json!({ "changed": changed })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/admins"))
+}
+
+// --- SMTP test ---
+
+#[derive(Deserialize)]
+pub struct TestSmtpForm {
+    /// Sending identity to test; empty for the default relay
+    #[serde(default)]
+    pub identity: String,
+}
+
+/// Send a test message to the requesting admin through the default relay or
+/// a sending identity, so broken credentials are caught before a real send.
+pub async fn test_smtp(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<TestSmtpForm>,
+) -> Result<Redirect, AppError> {
+    let identity = form.identity.trim();
+    let service = if identity.is_empty() {
+        state.email.clone()
+    } else {
+        state
+            .identities
+            .iter()
+            .find(|i| i.name == identity)
+            .map(|i| i.service.clone())
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown sending identity: {identity}")))?
+    };
+
+    let tz = crate::timezone::for_admin(&state.db, &admin_email).await;
+    let sent_at = crate::timezone::format(Utc::now(), tz, crate::timezone::SECONDS);
+    let tz_name = tz.name();
+    let relay = tera::escape_html(if identity.is_empty() {
+        "預設"
+    } else {
+        identity
+    });
+    let body = format!(
+        "<p>這是 COSCUP Newsletter 的 SMTP 測試信（寄件設定：{relay}），於 {sent_at}（{tz_name}）由 {admin_email} 發出。</p>\
+         <p>收到這封信代表 SMTP 設定正常。</p>"
+    );
+    // Check the relay answers and accepts our credentials first, so a
+    // broken login reads as such rather than as a failed send.
+    let result = match service.check_connection().await {
+        Ok(()) => {
+            service
+                .send_email(&admin_email, "COSCUP Newsletter Admin - SMTP 測試", &body)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "config.smtp_test",
+        Some(serde_json::json!({
+            "identity": identity,
+            "ok": result.is_ok(),
+            "error": result.as_ref().err().map(ToString::to_string),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&match result {
+        Ok(()) => "/admin/admins?smtp_test=ok".to_string(),
+        Err(e) => {
+            tracing::warn!("SMTP test by {admin_email} failed: {e}");
+            format!(
+                "/admin/admins?smtp_test=failed&smtp_error={}",
+                urlencoding::encode(&e.to_string())
+            )
+        }
+    }))
+}
+
+// --- Profile ---
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    pub saved: Option<String>,
+}
+
+pub async fn profile_page(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, AppError> {
+    let profile = crate::admin_profile::load(&state.db, &admin_email).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("profile", &profile);
+    ctx.insert(
+        "max_display_name_chars",
+        &crate::admin_profile::MAX_DISPLAY_NAME_CHARS,
+    );
+    ctx.insert("saved", &query.saved.is_some());
+    let html = state.tera.render("admin/profile.html", &ctx)?;
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct ProfileForm {
+    #[serde(default)]
+    pub display_name: String,
+    pub timezone: Option<String>,
+    /// Checkboxes: present when checked
+    pub notify_new_signin: Option<String>,
+    pub notify_comments: Option<String>,
+}
+
+pub async fn update_profile(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<ProfileForm>,
+) -> Result<Redirect, AppError> {
+    let display_name = crate::admin_profile::normalize_display_name(&form.display_name)
+        .map_err(AppError::BadRequest)?;
+    let timezone = match form
+        .timezone
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        Some(tz) => Some(
+            crate::timezone::parse(tz)
+                .ok_or_else(|| AppError::BadRequest(format!("未知的時區：{tz}")))?
+                .name()
+                .to_string(),
+        ),
+        None => None,
+    };
+    let profile = crate::admin_profile::AdminProfile {
+        display_name,
+        timezone,
+        notify_new_signin: form.notify_new_signin.is_some(),
+        notify_comments: form.notify_comments.is_some(),
+    };
+    crate::admin_profile::save(&state.db, &admin_email, &profile).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "admin.profile_update",
+        Some(serde_json::json!({
+            "display_name": profile.display_name,
+            "timezone": profile.timezone,
+            "notify_new_signin": profile.notify_new_signin,
+            "notify_comments": profile.notify_comments,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/profile?saved=1"))
+}
+
 // --- Audit log page ---
 
 #[derive(Deserialize)]
@@ -171,17 +499,19 @@ pub async fn audit_log_page(
         let rows = sqlx::query_as::<
             _,
             (
+                String,
                 String,
                 String,
                 Option<serde_json::Value>,
                 Option<String>,
                 chrono::DateTime<Utc>,
             ),
-        >(
-            "SELECT admin_email, action, details, ip_address, created_at \
-             FROM audit_log WHERE action = $1 \
-             ORDER BY created_at DESC LIMIT $2 OFFSET $3",
-        )
+        >(&format!(
+            "SELECT admin_email, {}, action, details, ip_address, created_at \
+                 FROM audit_log WHERE action = $1 \
+                 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+            crate::admin_profile::display_name_sql("admin_email")
+        ))
         .bind(action)
         .bind(per_page)
         .bind(offset)
@@ -198,16 +528,18 @@ pub async fn audit_log_page(
         let rows = sqlx::query_as::<
             _,
             (
+                String,
                 String,
                 String,
                 Option<serde_json::Value>,
                 Option<String>,
                 chrono::DateTime<Utc>,
             ),
-        >(
-            "SELECT admin_email, action, details, ip_address, created_at \
-             FROM audit_log ORDER BY created_at DESC LIMIT $1 OFFSET $2",
-        )
+        >(&format!(
+            "SELECT admin_email, {}, action, details, ip_address, created_at \
+                 FROM audit_log ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+            crate::admin_profile::display_name_sql("admin_email")
+        ))
         .bind(per_page)
         .bind(offset)
         .fetch_all(&state.db)
@@ -225,13 +557,14 @@ pub async fn audit_log_page(
     let logs: Vec<serde_json::Value> = rows
         .into_iter()
         .map(
-            |(log_admin_email, action, details, ip_address, created_at)| {
+            |(log_admin_email, log_admin_name, action, details, ip_address, created_at)| {
                 serde_json::json!({
                     "admin_email": log_admin_email,
+                    "admin_name": log_admin_name,
                     "action": action,
                     "details": details.map(|d| d.to_string()).unwrap_or_default(),
                     "ip_address": ip_address.unwrap_or_default(),
-                    "created_at": created_at.with_timezone(&taiwan_offset()).format("%Y-%m-%d %H:%M:%S").to_string(),
+                    "created_at": created_at.to_rfc3339(),
                 })
             },
         )
@@ -239,6 +572,12 @@ pub async fn audit_log_page(
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
     ctx.insert("logs", &logs);
     ctx.insert("page", &page);
     ctx.insert("total_pages", &total_pages);
@@ -247,3 +586,245 @@ pub async fn audit_log_page(
     let html = state.tera.render("admin/audit_log.html", &ctx)?;
     Ok(Html(html))
 }
+
+// --- Background jobs page ---
+
+#[derive(Deserialize)]
+pub struct JobsQuery {
+    pub page: Option<i64>,
+    pub kind: Option<String>,
+}
+
+/// (kind, subject, state, error, started at, finished at)
+type JobRow = (
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+);
+
+fn job_json(row: JobRow) -> serde_json::Value {
+    let (kind, subject, job_state, error, started_at, finished_at) = row;
+    serde_json::json!({
+        "kind": kind,
+        "label": JobKind::parse(&kind).map_or_else(|| kind.clone(), |k| k.label().to_string()),
+        "subject": subject.unwrap_or_default(),
+        "state": job_state,
+        "error": error.unwrap_or_default(),
+        "started_at": started_at.to_rfc3339(),
+        "finished_at": finished_at.map(|t| t.to_rfc3339()),
+        "duration_secs": finished_at.map(|f| (f - started_at).num_seconds()),
+    })
+}
+
+/// Background task runs: the latest run of each kind, then the full history.
+pub async fn jobs_page(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Query(query): Query<JobsQuery>,
+) -> Result<Html<String>, AppError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page: i64 = 50;
+    let offset = (page - 1) * per_page;
+    let kind_filter = query.kind.as_deref().and_then(JobKind::parse);
+
+    let latest: Vec<serde_json::Value> = sqlx::query_as::<_, JobRow>(
+        "SELECT DISTINCT ON (kind) kind, subject, state, error, started_at, finished_at \
+         FROM background_jobs ORDER BY kind, started_at DESC",
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(job_json)
+    .collect();
+
+    let rows = sqlx::query_as::<_, JobRow>(
+        "SELECT kind, subject, state, error, started_at, finished_at FROM background_jobs \
+         WHERE ($1::TEXT IS NULL OR kind = $1) \
+         ORDER BY started_at DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(kind_filter.map(JobKind::as_str))
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM background_jobs WHERE ($1::TEXT IS NULL OR kind = $1)",
+    )
+    .bind(kind_filter.map(JobKind::as_str))
+    .fetch_one(&state.db)
+    .await?;
+    let total_pages = (total + per_page - 1) / per_page;
+
+    let kinds: Vec<serde_json::Value> = JobKind::ALL
+        .into_iter()
+        .map(|k| serde_json::json!({ "value": k.as_str(), "label": k.label() }))
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("latest", &latest);
+    ctx.insert("jobs", &rows.into_iter().map(job_json).collect::<Vec<_>>());
+    ctx.insert("kinds", &kinds);
+    ctx.insert("kind_filter", &kind_filter.map_or("", JobKind::as_str));
+    ctx.insert("page", &page);
+    ctx.insert("total_pages", &total_pages);
+    ctx.insert("total", &total);
+    let html = state.tera.render("admin/jobs.html", &ctx)?;
+    Ok(Html(html))
+}
+
+// --- Route metrics page ---
+
+pub async fn metrics_page(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("routes", &state.metrics.summary());
+    ctx.insert("slow_query_ms", &state.config.slow_query_ms);
+    let html = state.tera.render("admin/metrics.html", &ctx)?;
+    Ok(Html(html))
+}
+
+// --- Link domains ---
+
+pub async fn link_domains_page(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("domains", &crate::link_domains::list(&state.db).await?);
+    ctx.insert("config_blocked", &state.config.blocked_link_domains);
+    ctx.insert("shorteners", crate::link_domains::KNOWN_SHORTENERS);
+    let html = state.tera.render("admin/link_domains.html", &ctx)?;
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct LinkDomainForm {
+    pub domain: String,
+    pub action: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+pub async fn save_link_domain(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<LinkDomainForm>,
+) -> Result<Redirect, AppError> {
+    let domain = crate::link_domains::normalize(&form.domain)
+        .ok_or_else(|| AppError::BadRequest(format!("無效的網域：{}", form.domain)))?;
+    let action = crate::link_domains::Action::parse(&form.action)
+        .ok_or_else(|| AppError::BadRequest("未知的處理方式".to_string()))?;
+    let note = form.note.trim();
+    crate::link_domains::save(&state.db, &domain, action, note, &admin_email).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "link_domain.save",
+        Some(serde_json::json!({ "domain": domain, "action": action.as_str(), "note": note })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/link-domains"))
+}
+
+pub async fn remove_link_domain(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(domain): Path<String>,
+) -> Result<Redirect, AppError> {
+    if !crate::link_domains::remove(&state.db, &domain).await? {
+        return Err(AppError::NotFound);
+    }
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "link_domain.remove",
+        Some(serde_json::json!({ "domain": domain })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/link-domains"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_is_dormant() {
+        let now = Utc.with_ymd_and_hms(2025, 8, 9, 0, 0, 0).unwrap();
+        let recent = Utc.with_ymd_and_hms(2025, 5, 1, 0, 0, 0).unwrap();
+        let old = Utc.with_ymd_and_hms(2025, 1, 31, 0, 0, 0).unwrap();
+        assert!(!is_dormant(recent, now, 6));
+        assert!(is_dormant(old, now, 6));
+        assert!(!is_dormant(old, now, 0));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_smtp_test_result_is_shown(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@example.com").await;
+
+        let response = app
+            .post_form("/admin/settings/test-smtp", &[("identity", "")])
+            .await;
+        assert_eq!(response.location(), Some("/admin/admins?smtp_test=ok"));
+        assert_eq!(app.sent_emails().len(), 1);
+        let page = app.get("/admin/admins?smtp_test=ok").await;
+        assert!(page.body.contains("測試信已送出"));
+
+        *app.email.connection_error.lock().unwrap() = Some("535 auth <failed>".to_string());
+        let response = app
+            .post_form("/admin/settings/test-smtp", &[("identity", "")])
+            .await;
+        let location = response.location().unwrap().to_string();
+        assert!(location.starts_with("/admin/admins?smtp_test=failed&smtp_error="));
+        // No test mail goes out when the connection check fails
+        assert_eq!(app.sent_emails().len(), 1);
+        let page = app.get(&location).await;
+        assert!(page
+            .body
+            .contains("測試信寄送失敗：Failed to send email: 535 auth &lt;failed&gt;"));
+
+        let outcomes: Vec<bool> = sqlx::query_scalar(
+            "SELECT (details->>'ok')::boolean FROM audit_log \
+             WHERE action = 'config.smtp_test' ORDER BY created_at",
+        )
+        .fetch_all(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(outcomes, vec![true, false]);
+    }
+}
@@ -0,0 +1,521 @@
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use serde::Deserialize;
+
+use crate::auth::ApiTokenId;
+use crate::bounce_webhook;
+use crate::error::AppError;
+use crate::import::{self, ImportFormat};
+use crate::inbound;
+use crate::jobs::JobKind;
+use crate::AppState;
+
+// --- Subscriber import ---
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Import role addresses even with `BLOCK_ROLE_ACCOUNTS` set
+    #[serde(default)]
+    pub allow_role_accounts: bool,
+}
+
+/// Accept a JSON or CSV payload and import it in a background job.
+/// Responds `202 Accepted` with the job id; poll the status endpoint for progress.
+pub async fn import_subscribers(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let format = ImportFormat::from_content_type(content_type).ok_or_else(|| {
+        AppError::BadRequest("Content-Type must be application/json or text/csv".to_string())
+    })?;
+
+    let rows = import::parse_payload(format, &body).map_err(AppError::BadRequest)?;
+    if rows.is_empty() {
+        return Err(AppError::BadRequest("No rows provided".to_string()));
+    }
+
+    let job_id = import::create_job(&state.db, format, rows.len()).await?;
+
+    crate::audit::log(
+        &state.db,
+        "api",
+        "subscriber.api_import",
+        Some(serde_json::json!({
+            "job_id": job_id.to_string(),
+            "format": format.as_str(),
+            "rows": rows.len(),
+            "allow_role_accounts": query.allow_role_accounts,
+        })),
+        None,
+    )
+    .await;
+
+    let job_state = state.clone();
+    tokio::spawn(async move {
+        let db = job_state.db.clone();
+        let _ = crate::jobs::track(&db, JobKind::Import, Some(job_id.to_string()), async {
+            import::run_job(job_state, job_id, rows, query.allow_role_accounts).await;
+            Ok::<_, std::convert::Infallible>(())
+        })
+        .await;
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "job_id": job_id.to_string(),
+            "status": "queued",
+            "status_url": format!("/api/v1/subscribers/import/{job_id}"),
+        })),
+    ))
+}
+
+/// Progress and per-row errors of an import job.
+pub async fn import_job_status(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    type JobRow = (
+        String,
+        String,
+        i32,
+        i32,
+        i32,
+        i32,
+        i32,
+        serde_json::Value,
+        Option<String>,
+        chrono::DateTime<chrono::Utc>,
+        Option<chrono::DateTime<chrono::Utc>>,
+        Option<chrono::DateTime<chrono::Utc>>,
+    );
+
+    let row = sqlx::query_as::<_, JobRow>(
+        "SELECT status, format, total_rows, processed_rows, imported_count, skipped_count, \
+         error_count, errors, error_message, created_at, started_at, finished_at \
+         FROM import_jobs WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let (
+        status,
+        format,
+        total_rows,
+        processed_rows,
+        imported_count,
+        skipped_count,
+        error_count,
+        errors,
+        error_message,
+        created_at,
+        started_at,
+        finished_at,
+    ) = row;
+
+    Ok(Json(serde_json::json!({
+        "job_id": id.to_string(),
+        "status": status,
+        "format": format,
+        "total_rows": total_rows,
+        "processed_rows": processed_rows,
+        "imported_count": imported_count,
+        "skipped_count": skipped_count,
+        "error_count": error_count,
+        "errors": errors,
+        "error_message": error_message,
+        "created_at": created_at.to_rfc3339(),
+        "started_at": started_at.map(|t| t.to_rfc3339()),
+        "finished_at": finished_at.map(|t| t.to_rfc3339()),
+    })))
+}
+
+// --- Subscriber tags ---
+
+/// Most addresses one tagging request may list.
+const MAX_TAG_EMAILS: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct TagRequest {
+    pub tag: String,
+    pub emails: Vec<String>,
+    /// Take the tag off instead of adding it
+    #[serde(default)]
+    pub remove: bool,
+}
+
+/// Add a tag to (or with `remove`, take it from) subscribers by address,
+/// creating the tag if needed. Addresses without a subscriber are listed in
+/// `not_found`.
+pub async fn tag_subscribers(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let req: TagRequest = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {e}")))?;
+    let tag = crate::tags::clean_name(&req.tag).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "tag must be 1 to {} characters",
+            crate::tags::MAX_NAME_CHARS
+        ))
+    })?;
+    if req.emails.len() > MAX_TAG_EMAILS {
+        return Err(AppError::BadRequest(format!(
+            "At most {MAX_TAG_EMAILS} emails per request"
+        )));
+    }
+
+    let tag_id = crate::tags::ensure_tag(&state.db, &tag).await?;
+    let (changed, not_found) =
+        crate::tags::set_by_email(&state.db, tag_id, &req.emails, req.remove).await?;
+
+    crate::audit::log(
+        &state.db,
+        "api",
+        if req.remove { "tag.remove" } else { "tag.add" },
+        Some(serde_json::json!({
+            "tag": tag,
+            "emails": req.emails.len(),
+            "changed": changed,
+            "not_found": not_found.len(),
+        })),
+        None,
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({
+        "tag": tag,
+        "changed": changed,
+        "not_found": not_found,
+    })))
+}
+
+// --- Inbound replies ---
+
+/// Inbound mail webhook: store a subscriber's reply to a newsletter so it
+/// shows up in the admin inbox. Retries of the same message are accepted
+/// and ignored.
+pub async fn inbound_reply(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .config
+        .inbound_webhook()
+        .verify(&state.db, &headers, &body)
+        .await?;
+    let email: inbound::InboundEmail = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {e}")))?;
+
+    if inbound::sender_address(&email.from).is_none() {
+        return Err(AppError::BadRequest(
+            "from must be an email address".to_string(),
+        ));
+    }
+
+    let id = inbound::store_reply(&state.db, &email).await?;
+    Ok(Json(serde_json::json!({
+        "id": id.map(|id| id.to_string()),
+        "duplicate": id.is_none(),
+    })))
+}
+
+// --- Bounces and complaints ---
+
+/// Bounce and complaint callbacks from the mail provider (SES through SNS,
+/// Mailgun or `SendGrid`), each checked with the provider's own signature
+/// rather than an API token. `matched` counts the events for a subscriber.
+pub async fn email_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let (provider, events) = bounce_webhook::receive(&state, &headers, &body).await?;
+    let mut matched = 0;
+    for event in &events {
+        if bounce_webhook::apply(&state, provider, event).await? {
+            matched += 1;
+        }
+    }
+    Ok(Json(serde_json::json!({
+        "events": events.len(),
+        "matched": matched,
+    })))
+}
+
+// --- Sending ---
+
+#[derive(Deserialize, Default)]
+pub struct SendRequest {
+    /// Send even though the message exceeds Gmail's clipping limit
+    #[serde(default)]
+    pub override_size: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleRequest {
+    pub scheduled_at: chrono::DateTime<chrono::Utc>,
+    /// Deliver at `scheduled_at`'s Taipei wall-clock time in each
+    /// subscriber's timezone
+    #[serde(default)]
+    pub local_delivery: bool,
+    #[serde(default)]
+    pub override_size: bool,
+}
+
+/// Send a newsletter now (or request approval, as from the admin page).
+/// With an `Idempotency-Key` header a retried call returns the first
+/// response instead of failing on the changed status.
+pub async fn send_newsletter(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+    Extension(token): Extension<ApiTokenId>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let req: SendRequest = if body.is_empty() {
+        SendRequest::default()
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {e}")))?
+    };
+    let request = format!("POST /api/v1/newsletters/{id}/send");
+    crate::idempotency::run(&state.db, &headers, &token, &request, &body, || async {
+        let status =
+            super::newsletter::start_send(&state, "api", None, id, req.override_size).await?;
+        Ok((
+            StatusCode::ACCEPTED,
+            serde_json::json!({ "newsletter_id": id.to_string(), "status": status }),
+        ))
+    })
+    .await
+}
+
+/// Schedule a newsletter, with the same `Idempotency-Key` handling as
+/// `send_newsletter`.
+pub async fn schedule_newsletter(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+    Extension(token): Extension<ApiTokenId>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let req: ScheduleRequest = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {e}")))?;
+    let request = format!("POST /api/v1/newsletters/{id}/schedule");
+    crate::idempotency::run(&state.db, &headers, &token, &request, &body, || async {
+        let status = super::newsletter::start_schedule(
+            &state,
+            "api",
+            None,
+            id,
+            req.scheduled_at,
+            req.local_delivery,
+            req.override_size,
+        )
+        .await?;
+        Ok((
+            StatusCode::ACCEPTED,
+            serde_json::json!({
+                "newsletter_id": id.to_string(),
+                "status": status,
+                "scheduled_at": req.scheduled_at.to_rfc3339(),
+            }),
+        ))
+    })
+    .await
+}
+
+// --- Stats ---
+
+/// The numbers on a newsletter's stats page.
+pub async fn newsletter_stats(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    crate::stats::newsletter(&state.db, id)
+        .await?
+        .map(Json)
+        .ok_or(AppError::NotFound)
+}
+
+/// Call-to-action click-through of every sent newsletter that has calls to
+/// action, newest first, for tracking them across issues.
+pub async fn compare_newsletters(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(crate::stats::cta_comparison(&state.db).await?))
+}
+
+/// A series' issues and their totals, as on the series page.
+pub async fn series_stats(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    if crate::series::find(&state.db, id).await?.is_none() {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(crate::stats::series(&state.db, id).await?))
+}
+
+/// The numbers on the stats page, from the cached views. The referral
+/// leaderboard is left out since it names subscribers.
+pub async fn stats_overview(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let counts = crate::stats_cache::subscriber_counts(&state.db).await?;
+    Ok(Json(serde_json::json!({
+        "subscribers": {
+            "total": counts.total,
+            "active": counts.active,
+            "verified": counts.verified,
+        },
+        "newsletters": crate::stats::newsletter_summaries(&state.db).await?,
+        "topics": crate::stats::topic_events(&state.db).await?,
+        "open_heatmap": crate::stats_cache::open_heatmap(&state.db).await?,
+        "refreshed_at": counts.refreshed_at.to_rfc3339(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_tag_subscribers(db: sqlx::PgPool) {
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.api_tokens = vec!["tok".to_string()])
+            .build();
+        app.migrate().await;
+        sqlx::query(
+            "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.org', 's', 'u1', true, true)",
+        )
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+
+        let request = |token: &str| {
+            Request::post("/api/v1/subscribers/tags")
+                .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    r#"{"tag": "attendee", "emails": ["a@example.org", "b@example.org"]}"#,
+                ))
+                .unwrap()
+        };
+        assert_eq!(
+            app.send(request("nope")).await.status,
+            StatusCode::UNAUTHORIZED
+        );
+        let response = app.send(request("tok")).await;
+        assert_eq!(response.status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "tag": "attendee",
+                "changed": 1,
+                "not_found": ["b@example.org"],
+            })
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_send_is_idempotent(db: sqlx::PgPool) {
+        let mut app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| {
+                c.api_tokens = vec!["tok".to_string()];
+                c.postal_address = Some("臺北市中正區 10 號".to_string());
+            })
+            .build();
+        app.migrate().await;
+        app.login_as("admin@coscup.org").await;
+        let mut ids = Vec::new();
+        for slug in ["api-send", "admin-send"] {
+            let id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO newsletters (title, slug, markdown_content) \
+                 VALUES ('T', $1, '內容') RETURNING id",
+            )
+            .bind(slug)
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        let jobs = |id: uuid::Uuid| {
+            let db = app.state.db.clone();
+            async move {
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT COUNT(*) FROM send_jobs WHERE newsletter_id = $1",
+                )
+                .bind(id)
+                .fetch_one(&db)
+                .await
+                .unwrap()
+            }
+        };
+
+        let request = |action: &str, key: Option<&str>, body: &str| {
+            let mut req = Request::post(format!("/api/v1/newsletters/{}/{action}", ids[0]))
+                .header(header::AUTHORIZATION, "Bearer tok")
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(key) = key {
+                req = req.header(crate::idempotency::HEADER, key);
+            }
+            req.body(Body::from(body.to_string())).unwrap()
+        };
+        let first = app.send(request("send", Some("k1"), "")).await;
+        assert_eq!(first.status, StatusCode::ACCEPTED);
+        assert!(first.body.contains(r#""status":"sending""#));
+        let retry = app.send(request("send", Some("k1"), "")).await;
+        assert_eq!(retry.status, StatusCode::ACCEPTED);
+        assert_eq!(retry.body, first.body);
+        assert!(retry
+            .headers
+            .contains_key(crate::idempotency::REPLAYED_HEADER));
+        assert_eq!(jobs(ids[0]).await, 1);
+
+        let reused = app
+            .send(request(
+                "schedule",
+                Some("k1"),
+                r#"{"scheduled_at": "2030-01-01T00:00:00Z"}"#,
+            ))
+            .await;
+        assert_eq!(reused.status, StatusCode::UNPROCESSABLE_ENTITY);
+        // Without a key the repeat is refused by the status check
+        let again = app.send(request("send", None, "")).await;
+        assert_eq!(again.status, StatusCode::BAD_REQUEST);
+        // A failed call leaves its key free for a corrected retry
+        let failed = app.send(request("send", Some("k2"), "")).await;
+        assert_eq!(failed.status, StatusCode::BAD_REQUEST);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM idempotency_keys")
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // A double click on the admin page queues one send
+        let uri = format!("/admin/newsletters/{}/send", ids[1]);
+        assert_eq!(app.post_form(&uri, &[]).await.status, StatusCode::SEE_OTHER);
+        assert_eq!(
+            app.post_form(&uri, &[]).await.status,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(jobs(ids[1]).await, 1);
+    }
+}
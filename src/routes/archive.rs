@@ -1,70 +1,180 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::response::Html;
+use serde::Deserialize;
 
+use crate::archive_cache::LIST_KEY;
 use crate::error::AppError;
 use crate::newsletter;
 use crate::AppState;
 
-/// Public page: list all sent newsletters.
-pub async fn list(State(state): State<AppState>) -> Result<Html<String>, AppError> {
-    let rows = sqlx::query_as::<_, (String, String, chrono::DateTime<chrono::Utc>)>(
-        "SELECT slug, title, sending_completed_at \
-         FROM newsletters \
-         WHERE status = 'sent' AND sending_completed_at IS NOT NULL \
-         ORDER BY sending_completed_at DESC",
+#[derive(Deserialize)]
+pub struct ListQuery {
+    /// Only this series
+    pub series: Option<String>,
+}
+
+/// Public page: list all sent newsletters, or those of one series.
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Html<String>, AppError> {
+    // Unknown or malformed series ids just show everything
+    let series_id = query
+        .series
+        .as_deref()
+        .and_then(|s| s.parse::<uuid::Uuid>().ok());
+    let requested =
+        series_id.map_or_else(|| LIST_KEY.to_string(), crate::archive_cache::series_key);
+    if let Some(html) = state.archive_cache.get(&requested) {
+        return Ok(Html(html));
+    }
+
+    // Series with something published, for the filter links
+    let series = sqlx::query_as::<_, (uuid::Uuid, String, String)>(
+        "SELECT s.id, s.name, s.description FROM newsletter_series s \
+         WHERE EXISTS (SELECT 1 FROM newsletters n WHERE n.series_id = s.id \
+         AND n.status = 'sent' AND n.sending_completed_at IS NOT NULL) \
+         ORDER BY s.name",
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let current = series_id.and_then(|id| series.iter().find(|(sid, _, _)| *sid == id));
+    let series_id = current.map(|(id, _, _)| *id);
+    // Stored under the series actually shown, so unknown ids can't fill the cache
+    let key = series_id.map_or_else(|| LIST_KEY.to_string(), crate::archive_cache::series_key);
+
+    let rows = sqlx::query_as::<
+        _,
+        (
+            String,
+            String,
+            chrono::DateTime<chrono::Utc>,
+            Option<String>,
+        ),
+    >(
+        "SELECT n.slug, n.title, n.sending_completed_at, s.name \
+         FROM newsletters n LEFT JOIN newsletter_series s ON s.id = n.series_id \
+         WHERE n.status = 'sent' AND n.sending_completed_at IS NOT NULL \
+         AND ($1::UUID IS NULL OR n.series_id = $1) \
+         ORDER BY n.sending_completed_at DESC",
     )
+    .bind(series_id)
     .fetch_all(&state.db)
     .await?;
 
     let newsletters: Vec<serde_json::Value> = rows
         .into_iter()
-        .map(|(slug, title, sent_at)| {
+        .map(|(slug, title, sent_at, series)| {
             serde_json::json!({
                 "slug": slug,
                 "title": title,
                 "sent_at": sent_at.format("%Y-%m-%d").to_string(),
+                "series": series,
             })
         })
         .collect();
+    let series_links: Vec<serde_json::Value> = series
+        .iter()
+        .map(|(id, name, _)| serde_json::json!({ "id": id.to_string(), "name": name }))
+        .collect();
 
     let mut ctx = tera::Context::new();
     ctx.insert("newsletters", &newsletters);
+    ctx.insert("series", &series_links);
+    if let Some((id, name, description)) = current {
+        ctx.insert(
+            "current_series",
+            &serde_json::json!({ "id": id.to_string(), "name": name, "description": description }),
+        );
+    }
     let html = state.tera.render("newsletters.html", &ctx)?;
+    state.archive_cache.insert(&key, &html);
     Ok(Html(html))
 }
 
-/// Public page: view a single sent newsletter.
-pub async fn view(
-    State(state): State<AppState>,
-    Path(slug): Path<String>,
-) -> Result<Html<String>, AppError> {
-    let row = sqlx::query_as::<_, (String, String, Option<uuid::Uuid>)>(
-        "SELECT title, markdown_content, template_id \
-         FROM newsletters \
-         WHERE slug = $1 AND status = 'sent'",
-    )
-    .bind(&slug)
-    .fetch_optional(&state.db)
-    .await?;
+/// A newsletter or language edition as shown on the web.
+pub(super) struct WebVersion {
+    /// The newsletter itself, also for an edition
+    primary_id: uuid::Uuid,
+    pub(super) title: String,
+    markdown_content: String,
+    template_id: Option<uuid::Uuid>,
+    pub(super) lang: String,
+    pub(super) dir: String,
+    /// Sponsors and dates are those of this day; today when not sent yet
+    sent_at: Option<chrono::DateTime<chrono::Utc>>,
+    slug: String,
+}
 
-    let Some(row) = row else {
-        let mut ctx = tera::Context::new();
-        ctx.insert("title", "找不到此電子報");
-        ctx.insert("message", "此電子報不存在或尚未寄送。");
-        let html = state.tera.render("error.html", &ctx)?;
-        return Ok(Html(html));
-    };
+pub(super) type WebVersionRow = (
+    uuid::Uuid,
+    String,
+    String,
+    Option<uuid::Uuid>,
+    String,
+    String,
+    Option<chrono::DateTime<chrono::Utc>>,
+    String,
+);
+
+pub(super) const WEB_VERSION_SQL: &str =
+    "SELECT COALESCE(n.parent_id, n.id), n.title, n.markdown_content, \
+     n.template_id, n.lang, n.dir, COALESCE(p.sending_completed_at, n.sending_completed_at), n.slug \
+     FROM newsletters n LEFT JOIN newsletters p ON p.id = n.parent_id";
+
+impl From<WebVersionRow> for WebVersion {
+    fn from(
+        (primary_id, title, markdown_content, template_id, lang, dir, sent_at, slug): WebVersionRow,
+    ) -> Self {
+        Self {
+            primary_id,
+            title,
+            markdown_content,
+            template_id,
+            lang,
+            dir,
+            sent_at,
+            slug,
+        }
+    }
+}
 
-    let (title, markdown_content, template_id) = row;
+/// The email as a reader without tracking sees it, with `web_url` as its
+/// "view in browser" link.
+pub(super) async fn render_web_version(
+    state: &AppState,
+    newsletter: &WebVersion,
+    web_url: &str,
+) -> Result<String, AppError> {
+    let language = newsletter::ContentLanguage {
+        lang: &newsletter.lang,
+        dir: &newsletter.dir,
+    };
 
     // Render markdown to HTML (includes image src absolutization), then sanitize
-    // (strips <script>, event handlers, and other dangerous elements)
-    let content_html = newsletter::render_markdown(&markdown_content, &state.config.base_url);
+    // (strips <script>, event handlers, and other dangerous elements).
+    // The sponsor block shows the sponsors of the day it was sent.
+    let sent_on = newsletter
+        .sent_at
+        .unwrap_or_else(chrono::Utc::now)
+        .with_timezone(&crate::timezone::DEFAULT)
+        .date_naive();
+    let sponsors = crate::sponsors::active_on(&state.db, sent_on).await?;
+    let content_html = newsletter::RenderPipeline::default().render(
+        &newsletter.markdown_content,
+        &newsletter::HookContext {
+            base_url: &state.config.base_url,
+            slug: &newsletter.slug,
+            sponsors: &sponsors,
+            today: sent_on,
+            event: crate::shortcodes::EventDates::from_config(&state.config),
+        },
+    );
     let content_html = newsletter::replace_recipient_name(&content_html, "訂閱者");
     let content_html = newsletter::sanitize_html(&content_html);
 
     // Load template
-    let template_html = if let Some(tid) = template_id {
+    let template_html = if let Some(tid) = newsletter.template_id {
         sqlx::query_scalar::<_, String>("SELECT html_body FROM newsletter_templates WHERE id = $1")
             .bind(tid)
             .fetch_optional(&state.db)
@@ -85,23 +195,96 @@ pub async fn view(
             .ok_or_else(|| AppError::Internal("No default template found".to_string()))?
         }
     };
+    let template_html = newsletter::apply_compliance_footer(
+        &template_html,
+        newsletter::ComplianceFooter::from_config(&state.config),
+    );
+
+    // External images go through the image proxy, as in the sent email
+    let (content_html, template_html) = match &state.config.image_proxy_key {
+        Some(key) => (
+            crate::image_proxy::rewrite_image_srcs(&content_html, &state.config.base_url, key),
+            crate::image_proxy::rewrite_image_srcs(&template_html, &state.config.base_url, key),
+        ),
+        None => (content_html, template_html),
+    };
 
     // Personalize with empty tracking/unsubscribe (public view)
-    let web_url = format!("{}/newsletters/{}", state.config.base_url, slug);
-    let rendered = newsletter::personalize_email(
+    newsletter::personalize_email(
         &template_html,
         &content_html,
-        &title,
+        &newsletter.title,
         "",
         "#",
         &state.config.base_url,
-        &web_url,
+        web_url,
+        language,
+    )
+    .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Public page: view a single sent newsletter.
+pub async fn view(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Html<String>, AppError> {
+    if let Some(html) = state.archive_cache.get(&slug) {
+        return Ok(Html(html));
+    }
+
+    // Language editions are only published once their newsletter has been sent
+    let row = sqlx::query_as::<_, WebVersionRow>(&format!(
+        "{WEB_VERSION_SQL} WHERE n.slug = $1 AND COALESCE(p.status, n.status) = 'sent'"
+    ))
+    .bind(&slug)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let Some(row) = row else {
+        let mut ctx = tera::Context::new();
+        ctx.insert("title", "找不到此電子報");
+        ctx.insert("message", "此電子報不存在或尚未寄送。");
+        let html = state.tera.render("error.html", &ctx)?;
+        return Ok(Html(html));
+    };
+    let web_version = WebVersion::from(row);
+
+    // Sibling editions for the language switcher
+    let editions: Vec<serde_json::Value> = sqlx::query_as::<_, (String, String)>(
+        "SELECT lang, slug FROM newsletters WHERE id = $1 OR parent_id = $1 \
+         ORDER BY parent_id NULLS FIRST, lang",
     )
-    .map_err(|e| AppError::Internal(e.to_string()))?;
+    .bind(web_version.primary_id)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|(lang, slug)| serde_json::json!({ "lang": lang, "slug": slug }))
+    .collect();
+
+    let web_url = format!("{}/newsletters/{}", state.config.base_url, slug);
+    let rendered = render_web_version(&state, &web_version, &web_url).await?;
 
     let mut ctx = tera::Context::new();
-    ctx.insert("subject", &title);
+    ctx.insert("subject", &web_version.title);
+    ctx.insert("lang", &web_version.lang);
+    ctx.insert("dir", &web_version.dir);
     ctx.insert("rendered_html", &rendered);
+    ctx.insert("slug", &slug);
+    ctx.insert("editions", &editions);
+    let attachments: Vec<serde_json::Value> =
+        crate::attachments::list(&state.db, web_version.primary_id)
+            .await?
+            .into_iter()
+            .map(|a| {
+                serde_json::json!({
+                    "url": crate::attachments::link(&state.config.base_url, a.id),
+                    "filename": a.filename,
+                    "size_kb": (a.size_bytes + 1023) / 1024,
+                })
+            })
+            .collect();
+    ctx.insert("attachments", &attachments);
     let html = state.tera.render("newsletter_view.html", &ctx)?;
+    state.archive_cache.insert(&slug, &html);
     Ok(Html(html))
 }
@@ -0,0 +1,204 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Multipart, Path, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Redirect, Response};
+
+use crate::attachments;
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::AppState;
+
+// --- Public download ---
+
+/// Download link on the web version of a sent newsletter; each request is
+/// counted.
+pub async fn download(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Response, AppError> {
+    let attachment = attachments::load(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+        .bind(attachment.newsletter_id)
+        .fetch_one(&state.db)
+        .await?;
+    if status != "sent" {
+        return Err(AppError::NotFound);
+    }
+
+    let data = tokio::fs::read(attachments::path(&state.config, &attachment.stored_name))
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read attachment {id}: {e}")))?;
+
+    sqlx::query(
+        "UPDATE newsletter_attachments SET download_count = download_count + 1 WHERE id = $1",
+    )
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachments::CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "inline; filename*=UTF-8''{}",
+                    urlencoding::encode(&attachment.filename)
+                ),
+            ),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+// --- Admin ---
+
+/// Attachments can only change while the newsletter is a draft, and belong
+/// to the newsletter rather than one of its language editions.
+async fn ensure_draft(state: &AppState, newsletter_id: uuid::Uuid) -> Result<(), AppError> {
+    let (status, parent_id) = sqlx::query_as::<_, (String, Option<uuid::Uuid>)>(
+        "SELECT status, parent_id FROM newsletters WHERE id = $1",
+    )
+    .bind(newsletter_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    if parent_id.is_some() {
+        return Err(AppError::BadRequest(
+            "Attachments are added to the newsletter, not a language edition".to_string(),
+        ));
+    }
+    if status != "draft" {
+        return Err(AppError::BadRequest(
+            "Attachments can only be changed while the newsletter is a draft".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn upload(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(newsletter_id): Path<uuid::Uuid>,
+    mut multipart: Multipart,
+) -> Result<Redirect, AppError> {
+    ensure_draft(&state, newsletter_id).await?;
+
+    let mut file = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let filename = field.file_name().unwrap_or("").to_string();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        file = Some((filename, data));
+    }
+    let (filename, data) =
+        file.ok_or_else(|| AppError::BadRequest("No file found in upload".to_string()))?;
+
+    if !attachments::is_pdf(&data) {
+        return Err(AppError::BadRequest(
+            "Only PDF files can be attached".to_string(),
+        ));
+    }
+    let attached_bytes: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(size_bytes), 0)::BIGINT FROM newsletter_attachments WHERE newsletter_id = $1",
+    )
+    .bind(newsletter_id)
+    .fetch_one(&state.db)
+    .await?;
+    attachments::check_size(
+        attached_bytes,
+        data.len(),
+        state.config.max_attachment_bytes,
+    )
+    .map_err(AppError::BadRequest)?;
+
+    let filename = attachments::sanitize_filename(&filename);
+    let stored_name = format!("{}.pdf", uuid::Uuid::new_v4());
+    tokio::fs::write(attachments::path(&state.config, &stored_name), &data)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to write attachment: {e}")))?;
+
+    let size_bytes = i32::try_from(data.len()).unwrap_or(i32::MAX);
+    let id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletter_attachments (newsletter_id, filename, stored_name, size_bytes) \
+         VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(newsletter_id)
+    .bind(&filename)
+    .bind(&stored_name)
+    .bind(size_bytes)
+    .fetch_one(&state.db)
+    .await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.attachment_add",
+        Some(serde_json::json!({
+            "newsletter_id": newsletter_id.to_string(),
+            "attachment_id": id.to_string(),
+            "filename": filename,
+            "size_bytes": size_bytes,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{newsletter_id}")))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((newsletter_id, id)): Path<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<Redirect, AppError> {
+    ensure_draft(&state, newsletter_id).await?;
+
+    let (filename, stored_name) = sqlx::query_as::<_, (String, String)>(
+        "DELETE FROM newsletter_attachments WHERE id = $1 AND newsletter_id = $2 \
+         RETURNING filename, stored_name",
+    )
+    .bind(id)
+    .bind(newsletter_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    if let Err(e) = tokio::fs::remove_file(attachments::path(&state.config, &stored_name)).await {
+        tracing::warn!("Failed to remove attachment file {stored_name}: {e}");
+    }
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.attachment_delete",
+        Some(serde_json::json!({
+            "newsletter_id": newsletter_id.to_string(),
+            "attachment_id": id.to_string(),
+            "filename": filename,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{newsletter_id}")))
+}
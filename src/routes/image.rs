@@ -0,0 +1,88 @@
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::image_proxy;
+use crate::security;
+use crate::AppState;
+
+#[derive(Deserialize)]
+pub struct ImageQuery {
+    pub url: String,
+    pub sig: String,
+}
+
+/// Serve an external newsletter image through this server, so the image host
+/// never sees subscriber IPs. Only URLs signed at send time are accepted.
+pub async fn proxy_image(
+    State(state): State<AppState>,
+    Query(query): Query<ImageQuery>,
+) -> Result<Response, AppError> {
+    let key = state
+        .config
+        .image_proxy_key
+        .as_deref()
+        .ok_or(AppError::NotFound)?;
+    if !security::verify_url_signature(key, &query.url, &query.sig) {
+        return Err(AppError::BadRequest("Invalid signature".to_string()));
+    }
+    if !query.url.starts_with("https://") && !query.url.starts_with("http://") {
+        return Err(AppError::BadRequest("Invalid image URL".to_string()));
+    }
+
+    let (content_type, body) = image_proxy::fetch_cached(
+        std::path::Path::new(&state.config.image_proxy_cache_dir),
+        state.images.as_ref(),
+        &query.url,
+    )
+    .await
+    .map_err(|e| {
+        tracing::warn!("Image proxy failed for {}: {e}", query.url);
+        AppError::NotFound
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CACHE_CONTROL,
+                "public, max-age=31536000, immutable".to_string(),
+            ),
+            // Served from our own origin, so an SVG opened directly must not
+            // run scripts or be sniffed as HTML
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, StatusCode};
+
+    #[tokio::test]
+    async fn test_proxied_images_are_sandboxed() {
+        let app = crate::test_utils::TestStateBuilder::without_db()
+            .config(|c| {
+                c.image_proxy_key = Some("key".to_string());
+                c.image_proxy_cache_dir = std::env::temp_dir()
+                    .join(format!("image-route-test-{}", uuid::Uuid::new_v4()))
+                    .to_string_lossy()
+                    .into_owned();
+            })
+            .build();
+        let url = "https://cdn.example.com/a.svg";
+        let response = app
+            .get(&crate::image_proxy::proxy_url("", "key", url))
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(response.headers[header::CONTENT_SECURITY_POLICY], "sandbox");
+
+        let _ = std::fs::remove_dir_all(&app.state.config.image_proxy_cache_dir);
+    }
+}
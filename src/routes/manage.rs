@@ -1,11 +1,16 @@
-use axum::extract::{Path, Query, State};
-use axum::response::Html;
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{Html, IntoResponse, Response};
 use axum::Form;
 use chrono::Utc;
 use serde::Deserialize;
 
+use crate::crm_webhook::{self, Lifecycle};
 use crate::error::AppError;
 use crate::security;
+use crate::subscriber_events::{self, Event};
 use crate::AppState;
 
 #[derive(Deserialize, Default)]
@@ -19,37 +24,115 @@ struct SubscriberRow {
     email: String,
     name: String,
     status: bool,
+    /// Preferred newsletter language; `None` gets the default edition
+    locale: Option<String>,
+    /// IANA timezone for local-time sends; `None` is Taipei
+    timezone: Option<String>,
+}
+
+/// Result of resolving a manage link.
+enum ManageLink {
+    Valid(SubscriberRow),
+    /// Genuine but past its expiry (or a legacy link after the grace period);
+    /// still good enough to unsubscribe
+    Expired(SubscriberRow),
+    Invalid,
 }
 
+/// Resolve a signed manage token, or a legacy `admin_link` (SHA256 of the
+/// secret code and email, or an imported one) while those are accepted.
 async fn find_subscriber_by_admin_link(
     state: &AppState,
     admin_link: &str,
+) -> Result<ManageLink, AppError> {
+    if let Some((ucode, expires_at)) = security::parse_manage_token(admin_link) {
+        #[allow(clippy::type_complexity)]
+        let row = sqlx::query_as::<_, (uuid::Uuid, String, String, bool, Option<String>, Option<String>, String)>(
+            "SELECT id, email, name, status, locale, timezone, secret_code FROM subscribers WHERE ucode = $1",
+        )
+        .bind(ucode)
+        .fetch_optional(&state.db)
+        .await?;
+
+        return Ok(match row {
+            Some((id, email, name, status, locale, timezone, secret_code))
+                if security::verify_manage_token(&secret_code, admin_link) =>
+            {
+                let subscriber = SubscriberRow {
+                    id,
+                    email,
+                    name,
+                    status,
+                    locale,
+                    timezone,
+                };
+                if expires_at > Utc::now().timestamp() {
+                    ManageLink::Valid(subscriber)
+                } else {
+                    ManageLink::Expired(subscriber)
+                }
+            }
+            _ => ManageLink::Invalid,
+        });
+    }
+
+    let Some(subscriber) = find_subscriber_by_legacy_link(state, admin_link).await? else {
+        return Ok(ManageLink::Invalid);
+    };
+    let grace_over = state
+        .config
+        .legacy_manage_links_until
+        .is_some_and(|until| Utc::now().date_naive() > until);
+    Ok(if grace_over {
+        ManageLink::Expired(subscriber)
+    } else {
+        ManageLink::Valid(subscriber)
+    })
+}
+
+async fn find_subscriber_by_legacy_link(
+    state: &AppState,
+    admin_link: &str,
 ) -> Result<Option<SubscriberRow>, AppError> {
     // First try legacy_admin_link
-    let row = sqlx::query_as::<_, (uuid::Uuid, String, String, bool)>(
-        "SELECT id, email, name, status FROM subscribers WHERE legacy_admin_link = $1",
+    #[allow(clippy::type_complexity)]
+    let row = sqlx::query_as::<_, (uuid::Uuid, String, String, bool, Option<String>, Option<String>)>(
+        "SELECT id, email, name, status, locale, timezone FROM subscribers WHERE legacy_admin_link = $1",
     )
     .bind(admin_link)
     .fetch_optional(&state.db)
     .await?;
 
-    if let Some((id, email, name, status)) = row {
+    if let Some((id, email, name, status, locale, timezone)) = row {
         return Ok(Some(SubscriberRow {
             id,
             email,
             name,
             status,
+            locale,
+            timezone,
         }));
     }
 
     // Try computing admin_link for all subscribers
-    let rows = sqlx::query_as::<_, (uuid::Uuid, String, String, bool, String)>(
-        "SELECT id, email, name, status, secret_code FROM subscribers",
-    )
-    .fetch_all(&state.db)
-    .await?;
+    #[allow(clippy::type_complexity)]
+    let rows =
+        sqlx::query_as::<
+            _,
+            (
+                uuid::Uuid,
+                String,
+                String,
+                bool,
+                Option<String>,
+                Option<String>,
+                String,
+            ),
+        >("SELECT id, email, name, status, locale, timezone, secret_code FROM subscribers")
+        .fetch_all(&state.db)
+        .await?;
 
-    for (id, email, name, status, secret_code) in rows {
+    for (id, email, name, status, locale, timezone, secret_code) in rows {
         let computed = security::compute_admin_link(&secret_code, &email);
         if security::verify_admin_link(admin_link, &computed) {
             return Ok(Some(SubscriberRow {
@@ -57,6 +140,8 @@ async fn find_subscriber_by_admin_link(
                 email,
                 name,
                 status,
+                locale,
+                timezone,
             }));
         }
     }
@@ -80,31 +165,153 @@ fn render_link_error(
     Ok(Html(html))
 }
 
+/// The subscriber's referral link and how many verified signups it brought.
+async fn referral_context(
+    state: &AppState,
+    subscriber_id: uuid::Uuid,
+    ctx: &mut tera::Context,
+) -> Result<(), AppError> {
+    let ucode = sqlx::query_scalar::<_, String>("SELECT ucode FROM subscribers WHERE id = $1")
+        .bind(subscriber_id)
+        .fetch_one(&state.db)
+        .await?;
+    ctx.insert(
+        "referral_url",
+        &crate::referral::referral_url(&state.config.base_url, &ucode),
+    );
+    ctx.insert(
+        "referral_count",
+        &crate::referral::count(&state.db, subscriber_id).await?,
+    );
+    Ok(())
+}
+
+/// Most newsletters listed on the manage page.
+const HISTORY_LIMIT: i64 = 50;
+
+/// Newsletters this subscriber was sent, newest first, linking to the web
+/// archive version of the edition they received.
+async fn delivery_history(
+    state: &AppState,
+    subscriber_id: uuid::Uuid,
+) -> Result<Vec<serde_json::Value>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String, chrono::DateTime<Utc>)>(
+        "SELECT e.slug, e.title, ns.sent_at \
+         FROM newsletter_sends ns \
+         JOIN newsletters n ON n.id = ns.newsletter_id \
+         JOIN newsletters e ON e.id = COALESCE(ns.edition_id, ns.newsletter_id) \
+         WHERE ns.subscriber_id = $1 AND ns.status = 'sent' AND ns.sent_at IS NOT NULL \
+         AND n.status = 'sent' \
+         ORDER BY ns.sent_at DESC LIMIT $2",
+    )
+    .bind(subscriber_id)
+    .bind(HISTORY_LIMIT)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(slug, title, sent_at)| {
+            serde_json::json!({
+                "slug": slug,
+                "title": title,
+                "sent_at": sent_at.format("%Y-%m-%d").to_string(),
+            })
+        })
+        .collect())
+}
+
+/// List topic preferences, and the topic of the newsletter the subscriber
+/// came from (offered as "stop this topic only" when they still get it).
+async fn topic_context(
+    state: &AppState,
+    subscriber_id: uuid::Uuid,
+    from: Option<&str>,
+    ctx: &mut tera::Context,
+) -> Result<(), AppError> {
+    let opted_out = crate::list_topics::opted_out(&state.db, subscriber_id).await?;
+    let from_topic = match from.filter(|f| !f.is_empty()) {
+        Some(slug) => crate::list_topics::for_newsletter_slug(&state.db, slug)
+            .await?
+            .filter(|topic| !opted_out.contains(topic)),
+        None => None,
+    };
+    let all = crate::list_topics::all(&state.db).await?;
+    ctx.insert("topics", &crate::list_topics::preferences(&all, &opted_out));
+    ctx.insert("from_topic", &from_topic);
+    Ok(())
+}
+
+fn locale_options() -> Vec<serde_json::Value> {
+    crate::locale::OPTIONS
+        .iter()
+        .map(|(tag, label)| serde_json::json!({ "tag": tag, "label": label }))
+        .collect()
+}
+
 const INVALID_LINK_TITLE: &str = "管理連結已失效";
 const INVALID_LINK_MSG: &str = "此連結無效或找不到對應的訂閱記錄。";
 const INVALID_LINK_HINT: &str =
     "如需管理訂閱，請使用信箱中最新的管理連結，或前往首頁重新訂閱以取得新的連結。";
+const EXPIRED_LINK_TITLE: &str = "管理連結已過期";
+const EXPIRED_LINK_MSG: &str = "為了保護您的訂閱，管理連結有使用期限，此連結已過期。";
+const EXPIRED_LINK_HINT: &str =
+    "請使用最近一封電子報中的管理連結，或前往首頁輸入您的 Email 以取得新的連結。";
+
+/// The subscriber for pages that need a current link, or the error page to show.
+fn require_valid(
+    state: &AppState,
+    link: ManageLink,
+) -> Result<Result<SubscriberRow, Html<String>>, AppError> {
+    match link {
+        ManageLink::Valid(subscriber) => Ok(Ok(subscriber)),
+        ManageLink::Expired(_) => Ok(Err(render_link_error(
+            state,
+            EXPIRED_LINK_TITLE,
+            EXPIRED_LINK_MSG,
+            Some(EXPIRED_LINK_HINT),
+        )?)),
+        ManageLink::Invalid => Ok(Err(render_link_error(
+            state,
+            INVALID_LINK_TITLE,
+            INVALID_LINK_MSG,
+            Some(INVALID_LINK_HINT),
+        )?)),
+    }
+}
 
 pub async fn manage_page(
     State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path(admin_link): Path<String>,
     Query(query): Query<FromQuery>,
 ) -> Result<Html<String>, AppError> {
-    let Some(subscriber) = find_subscriber_by_admin_link(&state, &admin_link).await? else {
-        return render_link_error(
-            &state,
-            INVALID_LINK_TITLE,
-            INVALID_LINK_MSG,
-            Some(INVALID_LINK_HINT),
-        );
+    let link = find_subscriber_by_admin_link(&state, &admin_link).await?;
+    let subscriber = match require_valid(&state, link)? {
+        Ok(subscriber) => subscriber,
+        Err(page) => return Ok(page),
     };
+    subscriber_events::record_manage_visit(
+        &state.db,
+        subscriber.id,
+        super::extract_client_ip(&headers, &connect_info),
+        super::user_agent(&headers),
+    )
+    .await;
 
     let mut ctx = tera::Context::new();
     ctx.insert("name", &subscriber.name);
     ctx.insert("email", &subscriber.email);
+    ctx.insert("locale", &subscriber.locale);
+    ctx.insert("timezone", &subscriber.timezone);
     ctx.insert("status", &subscriber.status);
     ctx.insert("admin_link", &admin_link);
+    topic_context(&state, subscriber.id, query.from.as_deref(), &mut ctx).await?;
     ctx.insert("from_newsletter", &query.from.unwrap_or_default());
+    ctx.insert("history", &delivery_history(&state, subscriber.id).await?);
+    referral_context(&state, subscriber.id, &mut ctx).await?;
+    ctx.insert("locales", &locale_options());
     let html = state.tera.render("manage.html", &ctx)?;
     Ok(Html(html))
 }
@@ -112,12 +319,29 @@ pub async fn manage_page(
 #[derive(Deserialize)]
 pub struct UpdateNameForm {
     pub name: String,
+    /// Empty for the default language
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// IANA timezone name; empty for Taipei
+    #[serde(default)]
+    pub timezone: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct UnsubscribeForm {
     #[serde(default)]
     pub from: Option<String>,
+    /// `topic` to stop only the list topic of the `from` newsletter;
+    /// anything else unsubscribes from everything
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TopicForm {
+    pub topic: String,
+    /// `stop` or `resume`
+    pub action: String,
 }
 
 pub async fn update_name(
@@ -125,32 +349,59 @@ pub async fn update_name(
     Path(admin_link): Path<String>,
     Form(form): Form<UpdateNameForm>,
 ) -> Result<Html<String>, AppError> {
-    let Some(subscriber) = find_subscriber_by_admin_link(&state, &admin_link).await? else {
-        return render_link_error(
-            &state,
-            INVALID_LINK_TITLE,
-            INVALID_LINK_MSG,
-            Some(INVALID_LINK_HINT),
-        );
+    let link = find_subscriber_by_admin_link(&state, &admin_link).await?;
+    let subscriber = match require_valid(&state, link)? {
+        Ok(subscriber) => subscriber,
+        Err(page) => return Ok(page),
     };
 
     let name = form.name.trim().to_string();
+    let locale = form
+        .locale
+        .as_deref()
+        .and_then(crate::locale::find)
+        .map(str::to_string);
+    // Unknown timezone names are dropped rather than breaking local-time sends
+    let timezone = match form
+        .timezone
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        Some(tz) => {
+            sqlx::query_scalar::<_, String>("SELECT name FROM pg_timezone_names WHERE name = $1")
+                .bind(tz)
+                .fetch_optional(&state.db)
+                .await?
+        }
+        None => None,
+    };
     let now = Utc::now();
 
-    sqlx::query("UPDATE subscribers SET name = $1, updated_at = $2 WHERE id = $3")
-        .bind(&name)
-        .bind(now)
-        .bind(subscriber.id)
-        .execute(&state.db)
-        .await?;
+    sqlx::query(
+        "UPDATE subscribers SET name = $1, locale = $2, timezone = $3, updated_at = $4 WHERE id = $5",
+    )
+    .bind(&name)
+    .bind(&locale)
+    .bind(&timezone)
+    .bind(now)
+    .bind(subscriber.id)
+    .execute(&state.db)
+    .await?;
 
     let mut ctx = tera::Context::new();
     ctx.insert("name", &name);
     ctx.insert("email", &subscriber.email);
+    ctx.insert("locale", &locale);
+    ctx.insert("timezone", &timezone);
     ctx.insert("status", &subscriber.status);
     ctx.insert("admin_link", &admin_link);
     ctx.insert("from_newsletter", "");
-    ctx.insert("message", "名稱已更新！");
+    topic_context(&state, subscriber.id, None, &mut ctx).await?;
+    ctx.insert("message", "資料已更新！");
+    ctx.insert("history", &delivery_history(&state, subscriber.id).await?);
+    referral_context(&state, subscriber.id, &mut ctx).await?;
+    ctx.insert("locales", &locale_options());
     let html = state.tera.render("manage.html", &ctx)?;
     Ok(Html(html))
 }
@@ -171,41 +422,100 @@ async fn lookup_newsletter_id(
     }
 }
 
-/// Record an unsubscribe event linking the subscriber to the newsletter that triggered it.
+/// Record an unsubscribe event linking the subscriber to the newsletter that
+/// triggered it; `topic` is set when only that list topic was stopped.
+/// Unsubscribing from everything is also reported to the CRM.
 async fn record_unsubscribe_event(
     state: &AppState,
     subscriber_id: uuid::Uuid,
     newsletter_id: Option<uuid::Uuid>,
+    topic: Option<&str>,
 ) -> Result<(), AppError> {
-    sqlx::query("INSERT INTO unsubscribe_events (subscriber_id, newsletter_id) VALUES ($1, $2)")
-        .bind(subscriber_id)
-        .bind(newsletter_id)
-        .execute(&state.db)
-        .await?;
+    sqlx::query(
+        "INSERT INTO unsubscribe_events (subscriber_id, newsletter_id, topic) VALUES ($1, $2, $3)",
+    )
+    .bind(subscriber_id)
+    .bind(newsletter_id)
+    .bind(topic)
+    .execute(&state.db)
+    .await?;
+    if topic.is_none() {
+        crm_webhook::emit(state, subscriber_id, Lifecycle::Unsubscribed).await?;
+    }
     Ok(())
 }
 
+/// Stop the list topic of the newsletter `from` refers to, recording the
+/// event the first time. `None` when that newsletter has no topic.
+async fn stop_from_topic(
+    state: &AppState,
+    subscriber_id: uuid::Uuid,
+    from: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    let Some(slug) = from else {
+        return Ok(None);
+    };
+    let Some(topic) = crate::list_topics::for_newsletter_slug(&state.db, slug).await? else {
+        return Ok(None);
+    };
+    if crate::list_topics::opt_out(&state.db, subscriber_id, &topic).await? {
+        let newsletter_id = lookup_newsletter_id(state, Some(slug)).await?;
+        record_unsubscribe_event(state, subscriber_id, newsletter_id, Some(&topic)).await?;
+    }
+    Ok(Some(topic))
+}
+
+/// What a subscriber event notes about an unsubscribe: the newsletter it
+/// came from and, when only its list topic was stopped, that topic.
+fn unsubscribe_details(from: Option<&str>, topic: Option<&str>) -> serde_json::Value {
+    serde_json::json!({
+        "from": from.filter(|slug| !slug.is_empty()),
+        "topic": topic,
+    })
+}
+
 /// RFC 8058 one-click unsubscribe endpoint.
-/// Email clients POST `List-Unsubscribe=One-Click` to this URL.
+/// Email clients POST `List-Unsubscribe=One-Click` to this URL. For a
+/// newsletter with a list topic only that topic is stopped; the manage page
+/// offers unsubscribing from everything.
 pub async fn one_click_unsubscribe(
     State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path(admin_link): Path<String>,
     Query(query): Query<FromQuery>,
 ) -> Result<axum::http::StatusCode, AppError> {
-    let Some(subscriber) = find_subscriber_by_admin_link(&state, &admin_link).await? else {
+    let (ManageLink::Valid(subscriber) | ManageLink::Expired(subscriber)) =
+        find_subscriber_by_admin_link(&state, &admin_link).await?
+    else {
         return Err(AppError::NotFound);
     };
 
-    let now = Utc::now();
-    sqlx::query("UPDATE subscribers SET status = false, updated_at = $1 WHERE id = $2")
-        .bind(now)
-        .bind(subscriber.id)
-        .execute(&state.db)
-        .await?;
+    let stopped_topic = stop_from_topic(&state, subscriber.id, query.from.as_deref()).await?;
+    if stopped_topic.is_none() {
+        let now = Utc::now();
+        sqlx::query("UPDATE subscribers SET status = false, updated_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(subscriber.id)
+            .execute(&state.db)
+            .await?;
 
-    let newsletter_id = lookup_newsletter_id(&state, query.from.as_deref()).await?;
-    record_unsubscribe_event(&state, subscriber.id, newsletter_id).await?;
+        let newsletter_id = lookup_newsletter_id(&state, query.from.as_deref()).await?;
+        record_unsubscribe_event(&state, subscriber.id, newsletter_id, None).await?;
+    }
 
+    subscriber_events::record(
+        &state.db,
+        subscriber.id,
+        Event::UnsubscribeOneClick,
+        Some(unsubscribe_details(
+            query.from.as_deref(),
+            stopped_topic.as_deref(),
+        )),
+        Some(super::extract_client_ip(&headers, &connect_info)),
+        super::user_agent(&headers),
+    )
+    .await;
     Ok(axum::http::StatusCode::OK)
 }
 
@@ -213,41 +523,165 @@ pub async fn resubscribe(
     State(state): State<AppState>,
     Path(admin_link): Path<String>,
 ) -> Result<Html<String>, AppError> {
-    let Some(subscriber) = find_subscriber_by_admin_link(&state, &admin_link).await? else {
-        return render_link_error(
-            &state,
-            INVALID_LINK_TITLE,
-            INVALID_LINK_MSG,
-            Some(INVALID_LINK_HINT),
-        );
+    let link = find_subscriber_by_admin_link(&state, &admin_link).await?;
+    let subscriber = match require_valid(&state, link)? {
+        Ok(subscriber) => subscriber,
+        Err(page) => return Ok(page),
     };
 
     let now = Utc::now();
-    sqlx::query(
-        "UPDATE subscribers SET status = true, bounced_at = NULL, updated_at = $1 WHERE id = $2",
+    let was_stopped = sqlx::query_scalar::<_, bool>(
+        "UPDATE subscribers s SET status = true, bounced_at = NULL, soft_bounce_count = 0, updated_at = $1 \
+         FROM subscribers old WHERE s.id = $2 AND old.id = s.id \
+         RETURNING NOT old.status OR old.bounced_at IS NOT NULL",
     )
     .bind(now)
     .bind(subscriber.id)
-    .execute(&state.db)
+    .fetch_one(&state.db)
     .await?;
+    if was_stopped {
+        crm_webhook::emit(&state, subscriber.id, Lifecycle::Resubscribed).await?;
+    }
 
     let mut ctx = tera::Context::new();
     ctx.insert("name", &subscriber.name);
     ctx.insert("email", &subscriber.email);
+    ctx.insert("locale", &subscriber.locale);
+    ctx.insert("timezone", &subscriber.timezone);
     ctx.insert("status", &true);
     ctx.insert("admin_link", &admin_link);
     ctx.insert("from_newsletter", "");
+    topic_context(&state, subscriber.id, None, &mut ctx).await?;
     ctx.insert("message", "您已成功重新訂閱！");
+    ctx.insert("history", &delivery_history(&state, subscriber.id).await?);
+    referral_context(&state, subscriber.id, &mut ctx).await?;
+    ctx.insert("locales", &locale_options());
+    let html = state.tera.render("manage.html", &ctx)?;
+    Ok(Html(html))
+}
+
+/// Replace the subscriber's secret code, invalidating every manage link sent
+/// so far, and show the page under a fresh link.
+pub async fn rotate_link(
+    State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(admin_link): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let link = find_subscriber_by_admin_link(&state, &admin_link).await?;
+    let subscriber = match require_valid(&state, link)? {
+        Ok(subscriber) => subscriber,
+        Err(page) => return Ok(page),
+    };
+
+    // Clearing legacy_admin_link retires the imported link as well
+    let (secret_code, ucode) = sqlx::query_as::<_, (String, String)>(
+        "UPDATE subscribers SET previous_secret_code = secret_code, secret_code = $1, \
+         secret_rotated_at = NOW(), legacy_admin_link = NULL, updated_at = NOW() \
+         WHERE id = $2 RETURNING secret_code, ucode",
+    )
+    .bind(security::generate_secret_code())
+    .bind(subscriber.id)
+    .fetch_one(&state.db)
+    .await?;
+    let new_link =
+        security::issue_manage_token(&secret_code, &ucode, state.config.manage_link_ttl_days);
+    subscriber_events::record(
+        &state.db,
+        subscriber.id,
+        Event::ManageLinkRotated,
+        None,
+        Some(super::extract_client_ip(&headers, &connect_info)),
+        super::user_agent(&headers),
+    )
+    .await;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("name", &subscriber.name);
+    ctx.insert("email", &subscriber.email);
+    ctx.insert("locale", &subscriber.locale);
+    ctx.insert("timezone", &subscriber.timezone);
+    ctx.insert("status", &subscriber.status);
+    ctx.insert("admin_link", &new_link);
+    ctx.insert("from_newsletter", "");
+    topic_context(&state, subscriber.id, None, &mut ctx).await?;
+    ctx.insert(
+        "message",
+        "已重設管理連結，先前信件中的管理連結已失效。請將此頁加入書籤，或使用之後收到的信件中的連結。",
+    );
+    ctx.insert("history", &delivery_history(&state, subscriber.id).await?);
+    referral_context(&state, subscriber.id, &mut ctx).await?;
+    ctx.insert("locales", &locale_options());
     let html = state.tera.render("manage.html", &ctx)?;
     Ok(Html(html))
 }
 
+/// Download everything stored about the subscriber as JSON, including the
+/// consent records behind the subscription.
+pub async fn export_data(
+    State(state): State<AppState>,
+    Path(admin_link): Path<String>,
+) -> Result<Response, AppError> {
+    let link = find_subscriber_by_admin_link(&state, &admin_link).await?;
+    let subscriber = match require_valid(&state, link)? {
+        Ok(subscriber) => subscriber,
+        Err(page) => return Ok(page.into_response()),
+    };
+
+    let (verified_email, source, created_at) =
+        sqlx::query_as::<_, (bool, Option<String>, chrono::DateTime<Utc>)>(
+            "SELECT verified_email, subscription_source, created_at FROM subscribers WHERE id = $1",
+        )
+        .bind(subscriber.id)
+        .fetch_one(&state.db)
+        .await?;
+    let tags = sqlx::query_scalar::<_, String>(
+        "SELECT t.name FROM subscriber_tags st JOIN tags t ON t.id = st.tag_id \
+         WHERE st.subscriber_id = $1 ORDER BY t.name",
+    )
+    .bind(subscriber.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let data = serde_json::json!({
+        "email": subscriber.email,
+        "name": subscriber.name,
+        "subscribed": subscriber.status,
+        "verified_email": verified_email,
+        "locale": subscriber.locale,
+        "timezone": subscriber.timezone,
+        "source": source,
+        "created_at": created_at,
+        "tags": tags,
+        "consents": crate::consent::for_subscriber(&state.db, subscriber.id).await?,
+        "newsletters_received": delivery_history(&state, subscriber.id).await?,
+    });
+    let body =
+        serde_json::to_string_pretty(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"coscup-newsletter-data.json\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
 pub async fn unsubscribe(
     State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path(admin_link): Path<String>,
     Form(form): Form<UnsubscribeForm>,
 ) -> Result<Html<String>, AppError> {
-    let Some(subscriber) = find_subscriber_by_admin_link(&state, &admin_link).await? else {
+    let (ManageLink::Valid(subscriber) | ManageLink::Expired(subscriber)) =
+        find_subscriber_by_admin_link(&state, &admin_link).await?
+    else {
         return render_link_error(
             &state,
             INVALID_LINK_TITLE,
@@ -256,23 +690,165 @@ pub async fn unsubscribe(
         );
     };
 
-    let now = Utc::now();
-    sqlx::query("UPDATE subscribers SET status = false, updated_at = $1 WHERE id = $2")
-        .bind(now)
-        .bind(subscriber.id)
-        .execute(&state.db)
-        .await?;
+    let topic_only = form.scope.as_deref() == Some("topic");
+    let stopped_topic = if topic_only {
+        stop_from_topic(&state, subscriber.id, form.from.as_deref()).await?
+    } else {
+        None
+    };
+    subscriber_events::record(
+        &state.db,
+        subscriber.id,
+        Event::UnsubscribePage,
+        Some(unsubscribe_details(
+            form.from.as_deref(),
+            stopped_topic.as_deref(),
+        )),
+        Some(super::extract_client_ip(&headers, &connect_info)),
+        super::user_agent(&headers),
+    )
+    .await;
+    let (status, message) = if let Some(topic) = stopped_topic {
+        (
+            subscriber.status,
+            format!("已停止寄送「{topic}」主題的電子報，其他電子報仍會照常寄送。"),
+        )
+    } else {
+        let now = Utc::now();
+        sqlx::query("UPDATE subscribers SET status = false, updated_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(subscriber.id)
+            .execute(&state.db)
+            .await?;
 
-    let newsletter_id = lookup_newsletter_id(&state, form.from.as_deref()).await?;
-    record_unsubscribe_event(&state, subscriber.id, newsletter_id).await?;
+        let newsletter_id = lookup_newsletter_id(&state, form.from.as_deref()).await?;
+        record_unsubscribe_event(&state, subscriber.id, newsletter_id, None).await?;
+        (false, "您已成功取消訂閱。".to_string())
+    };
 
     let mut ctx = tera::Context::new();
     ctx.insert("name", &subscriber.name);
     ctx.insert("email", &subscriber.email);
-    ctx.insert("status", &false);
+    ctx.insert("locale", &subscriber.locale);
+    ctx.insert("timezone", &subscriber.timezone);
+    ctx.insert("status", &status);
     ctx.insert("admin_link", &admin_link);
     ctx.insert("from_newsletter", "");
-    ctx.insert("message", "您已成功取消訂閱。");
+    topic_context(&state, subscriber.id, None, &mut ctx).await?;
+    ctx.insert("message", &message);
+    ctx.insert("history", &delivery_history(&state, subscriber.id).await?);
+    referral_context(&state, subscriber.id, &mut ctx).await?;
+    ctx.insert("locales", &locale_options());
     let html = state.tera.render("manage.html", &ctx)?;
     Ok(Html(html))
 }
+
+/// Stop or resume one list topic from the manage page.
+pub async fn update_topic(
+    State(state): State<AppState>,
+    Path(admin_link): Path<String>,
+    Form(form): Form<TopicForm>,
+) -> Result<Html<String>, AppError> {
+    let link = find_subscriber_by_admin_link(&state, &admin_link).await?;
+    let subscriber = match require_valid(&state, link)? {
+        Ok(subscriber) => subscriber,
+        Err(page) => return Ok(page),
+    };
+
+    let topics = crate::list_topics::all(&state.db).await?;
+    let Some(topic) = topics.iter().find(|t| **t == form.topic) else {
+        return Err(AppError::BadRequest("Unknown topic".to_string()));
+    };
+    let message = match form.action.as_str() {
+        "stop" => {
+            if crate::list_topics::opt_out(&state.db, subscriber.id, topic).await? {
+                record_unsubscribe_event(&state, subscriber.id, None, Some(topic)).await?;
+            }
+            format!("已停止寄送「{topic}」主題的電子報。")
+        }
+        "resume" => {
+            crate::list_topics::opt_in(&state.db, subscriber.id, topic).await?;
+            format!("已恢復寄送「{topic}」主題的電子報。")
+        }
+        _ => return Err(AppError::BadRequest("Invalid action".to_string())),
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("name", &subscriber.name);
+    ctx.insert("email", &subscriber.email);
+    ctx.insert("locale", &subscriber.locale);
+    ctx.insert("timezone", &subscriber.timezone);
+    ctx.insert("status", &subscriber.status);
+    ctx.insert("admin_link", &admin_link);
+    ctx.insert("from_newsletter", "");
+    topic_context(&state, subscriber.id, None, &mut ctx).await?;
+    ctx.insert("message", &message);
+    ctx.insert("history", &delivery_history(&state, subscriber.id).await?);
+    referral_context(&state, subscriber.id, &mut ctx).await?;
+    ctx.insert("locales", &locale_options());
+    let html = state.tera.render("manage.html", &ctx)?;
+    Ok(Html(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_delivery_history(db: sqlx::PgPool) {
+        let app = crate::test_utils::TestApp::with_db(db).await;
+        let db = &app.state.db;
+        let mut subscribers = Vec::new();
+        for (email, ucode) in [("a@example.org", "ua"), ("b@example.org", "ub")] {
+            let id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO subscribers (email, name, secret_code, ucode, status, verified_email) \
+                 VALUES ($1, 'S', 'secret', $2, true, true) RETURNING id",
+            )
+            .bind(email)
+            .bind(ucode)
+            .fetch_one(db)
+            .await
+            .unwrap();
+            subscribers.push(id);
+        }
+        for (title, slug, subscriber_id) in [
+            ("給 A 的電子報", "for-a", subscribers[0]),
+            ("給 B 的電子報", "for-b", subscribers[1]),
+        ] {
+            sqlx::query(
+                "WITH n AS (INSERT INTO newsletters (title, slug, markdown_content, status) \
+                 VALUES ($1, $2, '內容', 'sent') RETURNING id) \
+                 INSERT INTO newsletter_sends (newsletter_id, subscriber_id, status, sent_at) \
+                 SELECT id, $3, 'sent', NOW() FROM n",
+            )
+            .bind(title)
+            .bind(slug)
+            .bind(subscriber_id)
+            .execute(db)
+            .await
+            .unwrap();
+        }
+
+        let token = security::issue_manage_token("secret", "ua", 1);
+        let page = app.get(&format!("/manage/{token}")).await;
+        assert!(page.body.contains("給 A 的電子報"));
+        assert!(page.body.contains("/newsletters/for-a"));
+        assert!(!page.body.contains("給 B 的電子報"));
+
+        sqlx::query(
+            "WITH n AS (INSERT INTO newsletters (title, slug, markdown_content, status) \
+             SELECT 'Issue ' || i, 'issue-' || i, '內容', 'sent' FROM generate_series(1, $1) i \
+             RETURNING id) \
+             INSERT INTO newsletter_sends (newsletter_id, subscriber_id, status, sent_at) \
+             SELECT id, $2, 'sent', NOW() FROM n",
+        )
+        .bind(i32::try_from(HISTORY_LIMIT).unwrap())
+        .bind(subscribers[0])
+        .execute(db)
+        .await
+        .unwrap();
+        let history = delivery_history(&app.state, subscribers[0]).await.unwrap();
+        assert_eq!(history.len(), usize::try_from(HISTORY_LIMIT).unwrap());
+    }
+}
@@ -5,9 +5,17 @@ use axum::http::HeaderMap;
 
 pub mod admin;
 pub mod admin_mgmt;
+pub mod api;
 pub mod archive;
+pub mod attachment;
+pub mod image;
 pub mod manage;
 pub mod newsletter;
+pub mod reply;
+pub mod segment;
+pub mod series;
+pub mod share;
+pub mod sponsor;
 pub mod subscribe;
 pub mod template;
 pub mod tracking;
@@ -29,3 +37,10 @@ pub(crate) fn extract_client_ip(
     }
     connect_info.0.ip()
 }
+
+/// The request's `User-Agent`, if it sent a readable one.
+pub(crate) fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+}
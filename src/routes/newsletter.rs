@@ -1,10 +1,10 @@
 use std::net::SocketAddr;
 
-use axum::extract::{ConnectInfo, Path, State};
-use axum::http::HeaderMap;
-use axum::response::{Html, IntoResponse, Json, Redirect};
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{Html, IntoResponse, Json, Redirect, Response};
 use axum::Form;
-use chrono::{FixedOffset, NaiveDateTime, Utc};
+use chrono::{NaiveDateTime, Utc};
 use serde::Deserialize;
 
 use crate::auth::AdminUser;
@@ -12,10 +12,6 @@ use crate::error::AppError;
 use crate::newsletter;
 use crate::AppState;
 
-fn taiwan_offset() -> FixedOffset {
-    FixedOffset::east_opt(8 * 3600).expect("valid offset")
-}
-
 fn generate_slug(title: &str) -> String {
     let timestamp = Utc::now().timestamp();
     let sanitized: String = title
@@ -39,10 +35,116 @@ fn generate_slug(title: &str) -> String {
 
 // --- List ---
 
+const NEWSLETTER_STATUSES: [&str; 8] = [
+    "draft",
+    "pending_approval",
+    "scheduled",
+    "missed",
+    "sending",
+    "paused",
+    "sent",
+    "failed",
+];
+
+#[derive(Deserialize, Default)]
+pub struct ListQuery {
+    pub page: Option<i64>,
+    pub search: Option<String>,
+    pub status: Option<String>,
+    pub creator: Option<String>,
+    /// Series id
+    pub series: Option<String>,
+    /// Created-at range (inclusive, `YYYY-MM-DD`, Taiwan time)
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub sort: Option<String>,
+    pub dir: Option<String>,
+}
+
+/// Map the `sort`/`dir` query params to a whitelisted ORDER BY clause.
+/// Returns the normalized (sort key, direction) alongside the SQL.
+fn list_order_by(sort: Option<&str>, dir: Option<&str>) -> (&'static str, &'static str, String) {
+    let (key, column) = match sort {
+        Some("title") => ("title", "title"),
+        Some("status") => ("status", "status"),
+        Some("sent") => ("sent", "sent_count"),
+        Some("sent_at") => ("sent_at", "sending_completed_at"),
+        Some("creator") => ("creator", "created_by"),
+        _ => ("created_at", "created_at"),
+    };
+    let dir = if dir == Some("asc") { "asc" } else { "desc" };
+    let nulls = if dir == "asc" {
+        "NULLS FIRST"
+    } else {
+        "NULLS LAST"
+    };
+    (
+        key,
+        dir,
+        format!("{column} {} {nulls}, created_at DESC", dir.to_uppercase()),
+    )
+}
+
+/// Query string carrying the active filters (no page/sort), for building links.
+fn list_filter_query_string(query: &ListQuery) -> String {
+    [
+        ("search", &query.search),
+        ("status", &query.status),
+        ("creator", &query.creator),
+        ("series", &query.series),
+        ("from", &query.from),
+        ("to", &query.to),
+    ]
+    .into_iter()
+    .filter_map(|(k, v)| {
+        v.as_deref()
+            .filter(|v| !v.is_empty())
+            .map(|v| format!("{k}={}", urlencoding::encode(v)))
+    })
+    .collect::<Vec<_>>()
+    .join("&")
+}
+
+fn parse_date_param(value: Option<&str>) -> Option<chrono::NaiveDate> {
+    value.and_then(|v| chrono::NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d").ok())
+}
+
+#[allow(clippy::too_many_lines)]
 pub async fn list(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
+    Query(query): Query<ListQuery>,
 ) -> Result<Html<String>, AppError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page: i64 = 30;
+    let offset = (page - 1) * per_page;
+
+    let search_pattern = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("%{s}%"));
+    let status = query
+        .status
+        .as_deref()
+        .filter(|s| NEWSLETTER_STATUSES.contains(s));
+    let creator = query.creator.as_deref().filter(|s| !s.is_empty());
+    let series_id = parse_optional_uuid(query.series.as_deref());
+    let from = parse_date_param(query.from.as_deref());
+    let to = parse_date_param(query.to.as_deref());
+    let (sort, dir, order_by) = list_order_by(query.sort.as_deref(), query.dir.as_deref());
+    let tz = crate::timezone::for_admin(&state.db, &admin_email).await;
+
+    // Language editions are listed on their newsletter's edit page
+    let filter_sql = "WHERE parent_id IS NULL \
+         AND ($1::TEXT IS NULL OR status = $1) \
+         AND ($2::TEXT IS NULL OR title ILIKE $2) \
+         AND ($3::TEXT IS NULL OR created_by = $3) \
+         AND ($4::DATE IS NULL OR created_at >= ($4::DATE::TIMESTAMP AT TIME ZONE $6)) \
+         AND ($5::DATE IS NULL OR created_at < (($5::DATE + 1)::TIMESTAMP AT TIME ZONE $6)) \
+         AND ($7::UUID IS NULL OR series_id = $7)";
+
     let rows = sqlx::query_as::<
         _,
         (
@@ -53,19 +155,70 @@ pub async fn list(
             i32,
             i32,
             i32,
+            Option<String>,
             chrono::DateTime<Utc>,
+            Option<chrono::DateTime<Utc>>,
+            Option<String>,
         ),
-    >(
-        "SELECT id, title, slug, status, sent_count, failed_count, total_count, created_at \
-         FROM newsletters ORDER BY created_at DESC",
-    )
+    >(&format!(
+        "SELECT id, title, slug, status, sent_count, failed_count, total_count, {}, \
+         created_at, sending_completed_at, \
+         (SELECT name FROM newsletter_series WHERE id = series_id) \
+         FROM newsletters {filter_sql} \
+         ORDER BY {order_by} LIMIT $8 OFFSET $9",
+        crate::admin_profile::display_name_sql("created_by"),
+    ))
+    .bind(status)
+    .bind(&search_pattern)
+    .bind(creator)
+    .bind(from)
+    .bind(to)
+    .bind(tz.name())
+    .bind(series_id)
+    .bind(per_page)
+    .bind(offset)
     .fetch_all(&state.db)
     .await?;
 
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM newsletters {filter_sql}"))
+        .bind(status)
+        .bind(&search_pattern)
+        .bind(creator)
+        .bind(from)
+        .bind(to)
+        .bind(tz.name())
+        .bind(series_id)
+        .fetch_one(&state.db)
+        .await?;
+    let total_pages = ((total + per_page - 1) / per_page).max(1);
+
+    let creators = sqlx::query_as::<_, (String, String)>(&format!(
+        "SELECT created_by, {} FROM newsletters WHERE created_by IS NOT NULL \
+         GROUP BY created_by ORDER BY created_by",
+        crate::admin_profile::display_name_sql("created_by"),
+    ))
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|(email, name)| serde_json::json!({ "email": email, "name": name }))
+    .collect::<Vec<_>>();
+
     let newsletters: Vec<serde_json::Value> = rows
         .into_iter()
         .map(
-            |(id, title, slug, status, sent_count, failed_count, total_count, created_at)| {
+            |(
+                id,
+                title,
+                slug,
+                status,
+                sent_count,
+                failed_count,
+                total_count,
+                created_by,
+                created_at,
+                sent_at,
+                series,
+            )| {
                 serde_json::json!({
                     "id": id.to_string(),
                     "title": title,
@@ -74,7 +227,10 @@ pub async fn list(
                     "sent_count": sent_count,
                     "failed_count": failed_count,
                     "total_count": total_count,
-                    "created_at": created_at.with_timezone(&taiwan_offset()).format("%Y-%m-%d %H:%M").to_string(),
+                    "created_by": created_by.unwrap_or_default(),
+                    "created_at": created_at.to_rfc3339(),
+                    "sent_at": sent_at.map(|t| t.to_rfc3339()),
+                    "series": series,
                 })
             },
         )
@@ -82,16 +238,42 @@ pub async fn list(
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
+    ctx.insert("admin_tz", tz.name());
     ctx.insert("newsletters", &newsletters);
+    ctx.insert("statuses", &NEWSLETTER_STATUSES);
+    ctx.insert("creators", &creators);
+    ctx.insert("search", query.search.as_deref().unwrap_or(""));
+    ctx.insert("status", status.unwrap_or(""));
+    ctx.insert("creator", creator.unwrap_or(""));
+    ctx.insert("series_options", &crate::series::options(&state.db).await?);
+    ctx.insert(
+        "series_id",
+        &series_id.map(|s| s.to_string()).unwrap_or_default(),
+    );
+    ctx.insert("from", &from.map(|d| d.to_string()).unwrap_or_default());
+    ctx.insert("to", &to.map(|d| d.to_string()).unwrap_or_default());
+    ctx.insert("sort", sort);
+    ctx.insert("dir", dir);
+    ctx.insert("filter_qs", &list_filter_query_string(&query));
+    ctx.insert("page", &page);
+    ctx.insert("total_pages", &total_pages);
+    ctx.insert("total", &total);
     let html = state.tera.render("admin/newsletters.html", &ctx)?;
     Ok(Html(html))
 }
 
 // --- New ---
 
+#[derive(Deserialize, Default)]
+pub struct NewQuery {
+    /// Series to create the newsletter in, e.g. from the series page
+    pub series: Option<String>,
+}
+
 pub async fn new_form(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
+    Query(query): Query<NewQuery>,
 ) -> Result<Html<String>, AppError> {
     let templates = sqlx::query_as::<_, (uuid::Uuid, String, String)>(
         "SELECT id, slug, name FROM newsletter_templates ORDER BY name",
@@ -109,7 +291,32 @@ pub async fn new_form(
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
     ctx.insert("templates", &template_list);
+    ctx.insert("segments", &crate::segment::options(&state.db).await?);
+    ctx.insert("tags", &crate::tags::list(&state.db).await?);
     ctx.insert("newsletter", &serde_json::json!(null));
+    let series_id = parse_optional_uuid(query.series.as_deref());
+    let template_id = match series_id {
+        Some(id) => crate::series::default_template(&state.db, id).await?,
+        None => None,
+    };
+    ctx.insert("series_options", &crate::series::options(&state.db).await?);
+    ctx.insert(
+        "series_id",
+        &series_id.map(|s| s.to_string()).unwrap_or_default(),
+    );
+    ctx.insert(
+        "template_id",
+        &template_id.map(|t| t.to_string()).unwrap_or_default(),
+    );
+    ctx.insert("tag_id", "");
+    ctx.insert("sending_identity", "");
+    ctx.insert("list_topic", "");
+    ctx.insert(
+        "list_topic_names",
+        &crate::list_topics::names(&state.db).await?,
+    );
+    short_domain_context(&state, &mut ctx);
+    render_hooks_context(&newsletter::RenderPipeline::default(), &mut ctx);
     let html = state.tera.render("admin/newsletter_edit.html", &ctx)?;
     Ok(Html(html))
 }
@@ -119,6 +326,156 @@ pub struct NewsletterForm {
     pub title: String,
     pub markdown_content: String,
     pub template_id: Option<String>,
+    /// Series, only read when creating; empty means none
+    pub series_id: Option<String>,
+    /// Send target; empty means all subscribers
+    pub segment_id: Option<String>,
+    /// Only subscribers with this tag (within the segment, if any); empty means no tag filter
+    pub tag_id: Option<String>,
+    /// Short-link domain; empty means the default YOURLS instance
+    pub short_domain: Option<String>,
+    /// Sending identity name; empty means the default SMTP settings
+    pub sending_identity: Option<String>,
+    /// One `slug URL` pair per line
+    pub custom_slugs: Option<String>,
+    /// One `Name: value` header per line
+    pub extra_headers: Option<String>,
+    /// Render hook names in order; empty means the default pipeline
+    pub render_hooks: Option<String>,
+    /// List topic subscribers can stop on its own; empty means none
+    pub list_topic: Option<String>,
+    /// Checkbox: add dark-mode color overrides
+    pub dark_mode: Option<String>,
+    /// Content language tag; empty means `zh-TW`
+    pub lang: Option<String>,
+    /// `ltr` or `rtl`
+    pub dir: Option<String>,
+}
+
+/// Validate the language and direction fields: (lang, dir).
+fn parse_language_fields(form: &NewsletterForm) -> Result<(String, String), AppError> {
+    let default = newsletter::ContentLanguage::default();
+    let lang = match form
+        .lang
+        .as_deref()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+    {
+        Some(tag) => newsletter::parse_lang(tag)
+            .ok_or_else(|| AppError::BadRequest(format!("無效的語言代碼：{tag}")))?,
+        None => default.lang.to_string(),
+    };
+    let dir = match form.dir.as_deref().unwrap_or(default.dir) {
+        d @ ("ltr" | "rtl") => d.to_string(),
+        d => return Err(AppError::BadRequest(format!("無效的文字方向：{d}"))),
+    };
+    Ok((lang, dir))
+}
+
+/// Validate the extra headers field, returning them as JSON for storage.
+fn parse_extra_headers_field(form: &NewsletterForm) -> Result<serde_json::Value, AppError> {
+    let headers = crate::email::parse_extra_headers(form.extra_headers.as_deref().unwrap_or(""))
+        .map_err(AppError::BadRequest)?;
+    serde_json::to_value(headers).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Validate the render hooks field, returning them as JSON for storage
+/// (`None` for the default pipeline, so it follows later changes to it).
+fn parse_render_hooks_field(form: &NewsletterForm) -> Result<Option<serde_json::Value>, AppError> {
+    let pipeline = newsletter::parse_render_hooks(form.render_hooks.as_deref().unwrap_or(""))
+        .map_err(AppError::BadRequest)?;
+    Ok(pipeline
+        .filter(|p| *p != newsletter::RenderPipeline::default())
+        .map(|p| p.to_json()))
+}
+
+fn parse_list_topic_field(form: &NewsletterForm) -> Result<Option<String>, AppError> {
+    crate::list_topics::parse_name(form.list_topic.as_deref().unwrap_or(""))
+        .map_err(AppError::BadRequest)
+}
+
+/// Validate the short-link fields of the form: (domain, URL → slug map as JSON).
+fn parse_short_link_fields(
+    state: &AppState,
+    form: &NewsletterForm,
+) -> Result<(Option<String>, serde_json::Value), AppError> {
+    let short_domain = form
+        .short_domain
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(str::to_lowercase);
+    if let Some(domain) = &short_domain {
+        if !state.short_domains.iter().any(|sd| &sd.domain == domain) {
+            return Err(AppError::BadRequest(format!(
+                "Unknown short domain: {domain}"
+            )));
+        }
+    }
+    let custom_slugs =
+        crate::shorturl::parse_custom_slugs(form.custom_slugs.as_deref().unwrap_or(""))
+            .map_err(AppError::BadRequest)?;
+    let custom_slugs =
+        serde_json::to_value(custom_slugs).map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok((short_domain, custom_slugs))
+}
+
+/// Validate the sending identity field.
+fn parse_sending_identity_field(
+    state: &AppState,
+    form: &NewsletterForm,
+) -> Result<Option<String>, AppError> {
+    let identity = form
+        .sending_identity
+        .as_deref()
+        .map(str::trim)
+        .filter(|i| !i.is_empty());
+    match identity {
+        Some(name) if !state.identities.iter().any(|i| i.name == name) => Err(
+            AppError::BadRequest(format!("Unknown sending identity: {name}")),
+        ),
+        _ => Ok(identity.map(str::to_string)),
+    }
+}
+
+/// The newsletter's render hooks and the hooks to choose from, for the edit form.
+fn render_hooks_context(pipeline: &newsletter::RenderPipeline, ctx: &mut tera::Context) {
+    let options: Vec<serde_json::Value> = newsletter::RenderHook::ALL
+        .into_iter()
+        .map(|h| serde_json::json!({ "name": h.as_str(), "label": h.label() }))
+        .collect();
+    ctx.insert("render_hook_options", &options);
+    ctx.insert("render_hooks", &newsletter::format_render_hooks(pipeline));
+}
+
+/// Short-link domain and sending identity choices for the edit form.
+fn short_domain_context(state: &AppState, ctx: &mut tera::Context) {
+    let identities: Vec<serde_json::Value> = state
+        .identities
+        .iter()
+        .map(|i| serde_json::json!({ "name": i.name, "from_email": i.from_email }))
+        .collect();
+    ctx.insert("sending_identities", &identities);
+    ctx.insert("default_from_email", &state.config.smtp_from_email);
+    let domains: Vec<&str> = state
+        .short_domains
+        .iter()
+        .map(|sd| sd.domain.as_str())
+        .collect();
+    ctx.insert("short_domains", &domains);
+    ctx.insert(
+        "default_short_domain",
+        &state
+            .config
+            .yourls_api_url
+            .as_deref()
+            .and_then(crate::shorturl::domain_of)
+            .unwrap_or_default(),
+    );
+}
+
+fn parse_optional_uuid(value: Option<&str>) -> Option<uuid::Uuid> {
+    value.filter(|s| !s.is_empty()).and_then(|s| s.parse().ok())
 }
 
 pub async fn create(
@@ -134,23 +491,49 @@ pub async fn create(
     }
 
     let slug = generate_slug(&title);
-    let template_id: Option<uuid::Uuid> = form
-        .template_id
-        .as_deref()
-        .filter(|s| !s.is_empty())
-        .and_then(|s| s.parse().ok());
+    let series_id = parse_optional_uuid(form.series_id.as_deref());
+    let template_id = match (parse_optional_uuid(form.template_id.as_deref()), series_id) {
+        (None, Some(series_id)) => crate::series::default_template(&state.db, series_id).await?,
+        (template_id, _) => template_id,
+    };
+    let segment_id = parse_optional_uuid(form.segment_id.as_deref());
+    let tag_id = parse_optional_uuid(form.tag_id.as_deref());
+    let (short_domain, custom_slugs) = parse_short_link_fields(&state, &form)?;
+    let sending_identity = parse_sending_identity_field(&state, &form)?;
+    let extra_headers = parse_extra_headers_field(&form)?;
+    let render_hooks = parse_render_hooks_field(&form)?;
+    let list_topic = parse_list_topic_field(&form)?;
+    let (lang, dir) = parse_language_fields(&form)?;
 
     let id = sqlx::query_scalar::<_, uuid::Uuid>(
-        "INSERT INTO newsletters (title, slug, markdown_content, template_id, created_by) \
-         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        "INSERT INTO newsletters (title, slug, markdown_content, template_id, segment_id, tag_id, short_domain, custom_slugs, extra_headers, dark_mode, lang, dir, created_by, sending_identity, render_hooks, list_topic, series_id) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17) RETURNING id",
     )
     .bind(&title)
     .bind(&slug)
     .bind(&form.markdown_content)
     .bind(template_id)
+    .bind(segment_id)
+    .bind(tag_id)
+    .bind(&short_domain)
+    .bind(&custom_slugs)
+    .bind(&extra_headers)
+    .bind(form.dark_mode.is_some())
+    .bind(&lang)
+    .bind(&dir)
     .bind(&admin_email)
+    .bind(&sending_identity)
+    .bind(&render_hooks)
+    .bind(&list_topic)
+    .bind(series_id)
     .fetch_one(&state.db)
-    .await?;
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+            AppError::BadRequest("找不到所選的模板或系列".to_string())
+        }
+        e => e.into(),
+    })?;
 
     let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
     crate::audit::log(
@@ -167,21 +550,39 @@ pub async fn create(
 
 // --- Edit ---
 
+#[allow(clippy::too_many_lines)]
 pub async fn edit_form(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
     Path(id): Path<uuid::Uuid>,
 ) -> Result<Html<String>, AppError> {
-    let row = sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>, String, i32, i32, i32)>(
-        "SELECT title, slug, markdown_content, template_id, status, sent_count, failed_count, total_count FROM newsletters WHERE id = $1",
+    #[allow(clippy::type_complexity)]
+    let row = sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>, Option<uuid::Uuid>, Option<String>, serde_json::Value, serde_json::Value, bool, String, String, String, i32, i32, i32, i32)>(
+        "SELECT title, slug, markdown_content, template_id, segment_id, short_domain, custom_slugs, extra_headers, dark_mode, lang, dir, status, sent_count, failed_count, total_count, deferred_count FROM newsletters WHERE id = $1",
     )
     .bind(id)
     .fetch_optional(&state.db)
     .await?
     .ok_or(AppError::NotFound)?;
 
-    let (title, slug, markdown_content, template_id, status, sent_count, failed_count, total_count) =
-        row;
+    let (
+        title,
+        slug,
+        markdown_content,
+        template_id,
+        segment_id,
+        short_domain,
+        custom_slugs,
+        extra_headers,
+        dark_mode,
+        lang,
+        dir,
+        status,
+        sent_count,
+        failed_count,
+        total_count,
+        deferred_count,
+    ) = row;
 
     let templates = sqlx::query_as::<_, (uuid::Uuid, String, String)>(
         "SELECT id, slug, name FROM newsletter_templates ORDER BY name",
@@ -202,16 +603,118 @@ pub async fn edit_form(
         "slug": slug,
         "markdown_content": markdown_content,
         "template_id": template_id.map(|t| t.to_string()).unwrap_or_default(),
+        "segment_id": segment_id.map(|s| s.to_string()).unwrap_or_default(),
+        "short_domain": short_domain.unwrap_or_default(),
+        "custom_slugs": crate::shorturl::format_custom_slugs(
+            &serde_json::from_value(custom_slugs).unwrap_or_default(),
+        ),
+        "extra_headers": crate::email::format_extra_headers(
+            &serde_json::from_value::<Vec<crate::email::EmailHeader>>(extra_headers).unwrap_or_default(),
+        ),
+        "dark_mode": dark_mode,
+        "lang": lang,
+        "dir": dir,
         "status": status,
         "sent_count": sent_count,
         "failed_count": failed_count,
         "total_count": total_count,
+        "deferred_count": deferred_count,
     });
 
+    #[allow(clippy::type_complexity)]
+    let (tag_id, sending_identity, render_hooks, list_topic, series_id) = sqlx::query_as::<
+        _,
+        (
+            Option<uuid::Uuid>,
+            Option<String>,
+            Option<serde_json::Value>,
+            Option<String>,
+            Option<uuid::Uuid>,
+        ),
+    >(
+        "SELECT tag_id, sending_identity, render_hooks, list_topic, series_id FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+
+    let tz = crate::timezone::for_admin(&state.db, &admin_email).await;
     let mut ctx = tera::Context::new();
+    ctx.insert("admin_tz", tz.name());
+    ctx.insert("admin_tz_label", &crate::timezone::label(tz));
+    ctx.insert("tag_id", &tag_id.map(|t| t.to_string()).unwrap_or_default());
+    ctx.insert("sending_identity", &sending_identity.unwrap_or_default());
+    ctx.insert("list_topic", &list_topic.unwrap_or_default());
+    ctx.insert(
+        "list_topic_names",
+        &crate::list_topics::names(&state.db).await?,
+    );
+    ctx.insert("series_options", &crate::series::options(&state.db).await?);
+    ctx.insert(
+        "series_id",
+        &series_id.map(|s| s.to_string()).unwrap_or_default(),
+    );
+    render_hooks_context(
+        &newsletter::RenderPipeline::from_json(render_hooks.as_ref()),
+        &mut ctx,
+    );
+    editions_context(&state, id, &mut ctx).await?;
+    let attachments: Vec<serde_json::Value> = crate::attachments::list(&state.db, id)
+        .await?
+        .into_iter()
+        .map(|a| {
+            serde_json::json!({
+                "id": a.id.to_string(),
+                "filename": a.filename,
+                "size_kb": (a.size_bytes + 1023) / 1024,
+                "download_count": a.download_count,
+            })
+        })
+        .collect();
+    ctx.insert("attachments", &attachments);
+    share_links_context(&state, id, &mut ctx).await?;
+    ctx.insert(
+        "max_attachment_kb",
+        &(state.config.max_attachment_bytes / 1024),
+    );
+    if status == "draft" {
+        match email_size(&state, id).await {
+            Ok(size) => ctx.insert("email_size", &email_size_context(size)),
+            Err(e) => tracing::warn!("Failed to estimate size of newsletter {id}: {e}"),
+        }
+    }
+    if status == "draft" || status == "paused" {
+        let remaining =
+            (status == "paused").then(|| i64::from(total_count - sent_count - failed_count));
+        match send_plan(&state, id, remaining).await {
+            Ok(plan) => ctx.insert("send_plan", &plan),
+            Err(e) => tracing::warn!("Failed to estimate send duration of newsletter {id}: {e}"),
+        }
+    }
+    if status == "pending_approval" {
+        ctx.insert("approval", &approval_info(&state, id).await?);
+    }
+    if status == "paused" {
+        ctx.insert("pause", &pause_info(&state, id).await?);
+    }
+    comments_context(&state, id, &mut ctx).await?;
+    revisions_context(&state, id, &mut ctx).await?;
+    ctx.insert(
+        "content_only",
+        &scheduled_edit_target(&state, id, &status).await?.is_some(),
+    );
+    if status == "scheduled" {
+        ctx.insert("local_delivery", &local_delivery_info(&state, id).await?);
+    }
+    if status == "missed" {
+        ctx.insert("missed", &missed_info(&state, id).await?);
+    }
     ctx.insert("admin_email", &admin_email);
     ctx.insert("templates", &template_list);
+    ctx.insert("segments", &crate::segment::options(&state.db).await?);
+    ctx.insert("tags", &crate::tags::list(&state.db).await?);
     ctx.insert("newsletter", &nl);
+    short_domain_context(&state, &mut ctx);
     let html = state.tera.render("admin/newsletter_edit.html", &ctx)?;
     Ok(Html(html))
 }
@@ -224,31 +727,63 @@ pub async fn update(
     Path(id): Path<uuid::Uuid>,
     Form(form): Form<NewsletterForm>,
 ) -> Result<Redirect, AppError> {
-    // Only allow editing drafts
     let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
+    if let Some(scheduled_id) = scheduled_edit_target(&state, id, &status).await? {
+        let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+        update_scheduled(&state, &admin_email, client_ip, id, scheduled_id, &form).await?;
+        return Ok(Redirect::to(&format!("/admin/newsletters/{id}")));
+    }
+
+    // Everything else can only change while a draft
     if status != "draft" {
         return Err(AppError::BadRequest(
             "Only draft newsletters can be edited".to_string(),
         ));
     }
+    if edition_parent(&state, id)
+        .await?
+        .is_some_and(|parent| parent["status"] != "draft")
+    {
+        return Err(AppError::BadRequest(
+            "Editions can only be edited while their newsletter is a draft".to_string(),
+        ));
+    }
 
-    let template_id: Option<uuid::Uuid> = form
-        .template_id
-        .as_deref()
-        .filter(|s| !s.is_empty())
-        .and_then(|s| s.parse().ok());
+    let template_id = parse_optional_uuid(form.template_id.as_deref());
+    let segment_id = parse_optional_uuid(form.segment_id.as_deref());
+    let tag_id = parse_optional_uuid(form.tag_id.as_deref());
+    let (short_domain, custom_slugs) = parse_short_link_fields(&state, &form)?;
+    let sending_identity = parse_sending_identity_field(&state, &form)?;
+    let extra_headers = parse_extra_headers_field(&form)?;
+    let render_hooks = parse_render_hooks_field(&form)?;
+    let list_topic = parse_list_topic_field(&form)?;
+    let (lang, dir) = parse_language_fields(&form)?;
 
     sqlx::query(
-        "UPDATE newsletters SET title = $1, markdown_content = $2, template_id = $3, updated_at = NOW() WHERE id = $4",
+        "UPDATE newsletters SET title = $1, markdown_content = $2, template_id = $3, segment_id = $4, \
+         tag_id = $5, short_domain = $6, custom_slugs = $7, extra_headers = $8, dark_mode = $9, lang = $10, \
+         dir = $11, sending_identity = $12, render_hooks = $13, list_topic = $14, updated_at = NOW() \
+         WHERE id = $15",
     )
     .bind(form.title.trim())
     .bind(&form.markdown_content)
     .bind(template_id)
+    .bind(segment_id)
+    .bind(tag_id)
+    .bind(&short_domain)
+    .bind(&custom_slugs)
+    .bind(&extra_headers)
+    .bind(form.dark_mode.is_some())
+    .bind(&lang)
+    .bind(&dir)
+    .bind(&sending_identity)
+    .bind(&render_hooks)
+    .bind(&list_topic)
     .bind(id)
     .execute(&state.db)
     .await?;
@@ -266,110 +801,51 @@ pub async fn update(
     Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
 }
 
-// --- Preview ---
-
-pub async fn preview(
-    State(state): State<AppState>,
-    AdminUser(admin_email): AdminUser,
-    Path(id): Path<uuid::Uuid>,
-) -> Result<Html<String>, AppError> {
-    let row = sqlx::query_as::<_, (String, String, Option<uuid::Uuid>)>(
-        "SELECT title, markdown_content, template_id FROM newsletters WHERE id = $1",
-    )
-    .bind(id)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(AppError::NotFound)?;
-
-    let (title, markdown_content, template_id) = row;
-
-    // Load template (use selected template, or fall back to coscup-default)
-    let template_html = if let Some(tid) = template_id {
-        sqlx::query_scalar::<_, String>("SELECT html_body FROM newsletter_templates WHERE id = $1")
-            .bind(tid)
-            .fetch_optional(&state.db)
-            .await?
-    } else {
-        None
-    };
-    let template_html = match template_html {
-        Some(html) => html,
-        None => {
-            sqlx::query_scalar::<_, String>(
-                "SELECT html_body FROM newsletter_templates WHERE slug = 'coscup-default'",
-            )
-            .fetch_one(&state.db)
-            .await?
-        }
-    };
-
-    let content_html = newsletter::render_markdown(&markdown_content, &state.config.base_url);
-    let content_html = newsletter::replace_recipient_name(&content_html, "王小明");
-
-    // Use dummy values for preview
-    let tracking_pixel = "<!-- tracking pixel placeholder -->";
-    let unsubscribe_url = "#";
-    let web_url = "#";
-
-    let rendered = newsletter::personalize_email(
-        &template_html,
-        &content_html,
-        &title,
-        tracking_pixel,
-        unsubscribe_url,
-        &state.config.base_url,
-        web_url,
-    )
-    .map_err(|e| AppError::Internal(e.to_string()))?;
-
-    let mut ctx = tera::Context::new();
-    ctx.insert("admin_email", &admin_email);
-    ctx.insert("newsletter_id", &id.to_string());
-    ctx.insert("title", &title);
-    ctx.insert("rendered_html", &rendered);
-    let html = state.tera.render("admin/newsletter_preview.html", &ctx)?;
-    Ok(Html(html))
+#[derive(Deserialize)]
+pub struct SeriesForm {
+    /// Empty takes the newsletter out of its series
+    pub series_id: Option<String>,
 }
 
-// --- Send ---
-
-pub async fn send_now(
+/// Move a newsletter to another series. Unlike the rest of the form this
+/// works in any status, so sent newsletters can be grouped afterwards.
+pub async fn set_series(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Path(id): Path<uuid::Uuid>,
+    Form(form): Form<SeriesForm>,
 ) -> Result<Redirect, AppError> {
-    let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::NotFound)?;
-
-    if status != "draft" && status != "scheduled" && status != "paused" {
-        return Err(AppError::BadRequest(
-            "Newsletter must be in draft, scheduled, or paused status to send".to_string(),
-        ));
-    }
-
-    let rate_limit_ms = state.config.smtp_rate_limit_ms;
-    let state_clone = state.clone();
-    let svc = state.shorturl.clone();
-
-    tokio::spawn(async move {
-        if let Err(e) =
-            newsletter::send_newsletter(&state_clone, id, svc.as_ref(), rate_limit_ms).await
-        {
-            tracing::error!("Newsletter send failed: {e}");
+    let series_id = parse_optional_uuid(form.series_id.as_deref());
+    let result = sqlx::query(
+        "UPDATE newsletters SET series_id = $1, updated_at = NOW() \
+         WHERE id = $2 AND parent_id IS NULL",
+    )
+    .bind(series_id)
+    .bind(id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+            AppError::BadRequest("找不到所選的系列".to_string())
         }
-    });
+        e => e.into(),
+    })?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    state.archive_cache.invalidate();
 
     let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
     crate::audit::log(
         &state.db,
         &admin_email,
-        "newsletter.send",
-        Some(serde_json::json!({ "newsletter_id": id.to_string() })),
+        "newsletter.series",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "series_id": series_id.map(|s| s.to_string()),
+        })),
         Some(client_ip),
     )
     .await;
@@ -377,95 +853,1276 @@ pub async fn send_now(
     Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
 }
 
-// --- Schedule ---
-
-#[derive(Deserialize)]
-pub struct ScheduleForm {
-    pub scheduled_at: String,
+/// Edits to a scheduled newsletter stop this long before it is due, so the
+/// scheduler never picks up content that is still being changed.
+const SCHEDULED_EDIT_LOCK_SECS: i32 = 60;
+
+/// The scheduled newsletter whose title and content `id` may still edit: the
+/// newsletter itself, or the parent of one of its language editions.
+async fn scheduled_edit_target(
+    state: &AppState,
+    id: uuid::Uuid,
+    status: &str,
+) -> Result<Option<uuid::Uuid>, AppError> {
+    if status == "scheduled" {
+        return Ok(Some(id));
+    }
+    if status != "draft" {
+        return Ok(None);
+    }
+    let parent = sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT p.id FROM newsletters n JOIN newsletters p ON p.id = n.parent_id \
+         WHERE n.id = $1 AND p.status = 'scheduled'",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+    Ok(parent)
 }
 
-pub async fn schedule(
-    State(state): State<AppState>,
-    AdminUser(admin_email): AdminUser,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    headers: HeaderMap,
-    Path(id): Path<uuid::Uuid>,
-    Form(form): Form<ScheduleForm>,
-) -> Result<Redirect, AppError> {
-    let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::NotFound)?;
-
-    if status != "draft" {
-        return Err(AppError::BadRequest(
-            "Only draft newsletters can be scheduled".to_string(),
-        ));
+/// Saves a new title and content for a newsletter that is waiting to be sent,
+/// keeping the previous version as a revision. Everything else stays as it
+/// was scheduled.
+async fn update_scheduled(
+    state: &AppState,
+    admin_email: &str,
+    client_ip: std::net::IpAddr,
+    id: uuid::Uuid,
+    scheduled_id: uuid::Uuid,
+    form: &NewsletterForm,
+) -> Result<(), AppError> {
+    let mut tx = state.db.begin().await?;
+
+    // Partly sent local-time deliveries and approved sends keep what was
+    // sent or approved; the row lock holds off a concurrent cancel
+    let editable = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT {due} > NOW() + $2 * INTERVAL '1 second' FROM newsletters \
+         WHERE id = $1 AND status = 'scheduled' AND sent_count = 0 \
+         AND local_release_at IS NULL AND approved_by IS NULL FOR UPDATE",
+        due = crate::newsletter::SCHEDULED_DUE_AT_SQL,
+    ))
+    .bind(scheduled_id)
+    .bind(SCHEDULED_EDIT_LOCK_SECS)
+    .fetch_optional(&mut *tx)
+    .await?;
+    match editable {
+        Some(true) => {}
+        Some(false) => {
+            return Err(AppError::BadRequest(
+                "電子報即將開始發送，已無法修改內容".to_string(),
+            ));
+        }
+        None => {
+            return Err(AppError::BadRequest(
+                "已核准或已開始發送的排程無法修改內容，請先取消排程".to_string(),
+            ));
+        }
     }
 
-    let naive = NaiveDateTime::parse_from_str(&form.scheduled_at, "%Y-%m-%dT%H:%M")
-        .map_err(|e| AppError::BadRequest(format!("Invalid datetime: {e}")))?;
-    let scheduled_at = naive
-        .and_local_timezone(taiwan_offset())
-        .single()
-        .ok_or_else(|| AppError::BadRequest("Invalid timezone conversion".to_string()))?
-        .with_timezone(&Utc);
+    let revision_id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletter_revisions (newsletter_id, title, markdown_content, edited_by) \
+         SELECT id, title, markdown_content, $2 FROM newsletters WHERE id = $1 RETURNING id",
+    )
+    .bind(id)
+    .bind(admin_email)
+    .fetch_one(&mut *tx)
+    .await?;
 
     sqlx::query(
-        "UPDATE newsletters SET status = 'scheduled', scheduled_at = $1, updated_at = NOW() WHERE id = $2",
+        "UPDATE newsletters SET title = $1, markdown_content = $2, updated_at = NOW() WHERE id = $3",
     )
-    .bind(scheduled_at)
+    .bind(form.title.trim())
+    .bind(&form.markdown_content)
     .bind(id)
-    .execute(&state.db)
+    .execute(&mut *tx)
     .await?;
 
-    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    tx.commit().await?;
+
     crate::audit::log(
         &state.db,
-        &admin_email,
-        "newsletter.schedule",
-        Some(serde_json::json!({ "newsletter_id": id.to_string(), "scheduled_at": form.scheduled_at })),
+        admin_email,
+        "newsletter.update_scheduled",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "revision_id": revision_id.to_string(),
+        })),
         Some(client_ip),
     )
     .await;
-
-    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+    Ok(())
 }
 
-// --- Cancel ---
+/// Earlier versions of a newsletter's title and content, newest first.
+async fn revisions_context(
+    state: &AppState,
+    id: uuid::Uuid,
+    ctx: &mut tera::Context,
+) -> Result<(), AppError> {
+    let revisions: Vec<serde_json::Value> =
+        sqlx::query_as::<_, (String, String, chrono::DateTime<Utc>)>(&format!(
+            "SELECT title, {edited_by}, created_at FROM newsletter_revisions \
+             WHERE newsletter_id = $1 ORDER BY created_at DESC",
+            edited_by = crate::admin_profile::display_name_sql("edited_by"),
+        ))
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?
+        .into_iter()
+        .map(|(title, edited_by, created_at)| {
+            serde_json::json!({
+                "title": title,
+                "edited_by": edited_by,
+                "created_at": created_at.to_rfc3339(),
+            })
+        })
+        .collect();
+    ctx.insert("revisions", &revisions);
+    Ok(())
+}
 
-pub async fn cancel(
-    State(state): State<AppState>,
-    AdminUser(admin_email): AdminUser,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    headers: HeaderMap,
-    Path(id): Path<uuid::Uuid>,
-) -> Result<Redirect, AppError> {
-    let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+/// The parent of an edition, or the editions of a primary newsletter.
+async fn editions_context(
+    state: &AppState,
+    id: uuid::Uuid,
+    ctx: &mut tera::Context,
+) -> Result<(), AppError> {
+    if let Some(parent) = edition_parent(state, id).await? {
+        ctx.insert("parent", &parent);
+    } else {
+        let editions: Vec<serde_json::Value> = sqlx::query_as::<_, (uuid::Uuid, String, String)>(
+            "SELECT id, lang, title FROM newsletters WHERE parent_id = $1 ORDER BY lang",
+        )
         .bind(id)
-        .fetch_optional(&state.db)
+        .fetch_all(&state.db)
         .await?
-        .ok_or(AppError::NotFound)?;
+        .into_iter()
+        .map(|(eid, lang, title)| {
+            serde_json::json!({ "id": eid.to_string(), "lang": lang, "title": title })
+        })
+        .collect();
+        ctx.insert("editions", &editions);
+    }
+    Ok(())
+}
+
+/// Share links of the newsletter with their full URLs.
+async fn share_links_context(
+    state: &AppState,
+    id: uuid::Uuid,
+    ctx: &mut tera::Context,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let links: Vec<serde_json::Value> = crate::share_links::list(&state.db, id)
+        .await?
+        .into_iter()
+        .map(|l| {
+            serde_json::json!({
+                "id": l.id.to_string(),
+                "label": l.label,
+                "url": format!("{}/share/{}", state.config.base_url, l.token),
+                "expires_at": l.expires_at.to_rfc3339(),
+                "created_by": l.created_by.unwrap_or_default(),
+                "view_count": l.view_count,
+                "last_viewed_at": l.last_viewed_at.map(|t| t.to_rfc3339()),
+                "revoked": l.revoked_at.is_some(),
+                "expired": l.expires_at <= now,
+            })
+        })
+        .collect();
+    ctx.insert("share_links", &links);
+    ctx.insert(
+        "share_link_default_days",
+        &crate::share_links::DEFAULT_TTL_DAYS,
+    );
+    ctx.insert("share_link_max_days", &crate::share_links::MAX_TTL_DAYS);
+    Ok(())
+}
+
+/// Local-time delivery of a scheduled newsletter, or null for a regular send.
+async fn local_delivery_info(
+    state: &AppState,
+    id: uuid::Uuid,
+) -> Result<serde_json::Value, AppError> {
+    let row = sqlx::query_as::<_, (bool, Option<chrono::DateTime<Utc>>)>(
+        "SELECT local_delivery, local_release_at FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    Ok(match row {
+        (true, next_release) => serde_json::json!({
+            "next_release": next_release.map(|t| t.to_rfc3339()),
+        }),
+        (false, _) => serde_json::Value::Null,
+    })
+}
+
+/// When a missed newsletter was due and when the scheduler gave up on it.
+async fn missed_info(state: &AppState, id: uuid::Uuid) -> Result<serde_json::Value, AppError> {
+    let (scheduled_at, missed_at) =
+        sqlx::query_as::<_, (Option<chrono::DateTime<Utc>>, Option<chrono::DateTime<Utc>>)>(
+            "SELECT scheduled_at, missed_at FROM newsletters WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    Ok(serde_json::json!({
+        "scheduled_at": scheduled_at.map(|t| t.to_rfc3339()),
+        "missed_at": missed_at.map(|t| t.to_rfc3339()),
+        "catch_up_hours": state.config.schedule_catch_up_hours,
+    }))
+}
+
+// --- Language editions ---
+
+/// The newsletter an edition belongs to (`id`, `title`, `status`), or `None`
+/// for a newsletter that is not an edition.
+async fn edition_parent(
+    state: &AppState,
+    id: uuid::Uuid,
+) -> Result<Option<serde_json::Value>, AppError> {
+    let parent = sqlx::query_as::<_, (uuid::Uuid, String, String)>(
+        "SELECT p.id, p.title, p.status FROM newsletters n \
+         JOIN newsletters p ON p.id = n.parent_id WHERE n.id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+    Ok(parent.map(|(pid, title, status)| {
+        serde_json::json!({ "id": pid.to_string(), "title": title, "status": status })
+    }))
+}
+
+/// Editions are sent together with their newsletter, never on their own.
+async fn reject_edition(state: &AppState, id: uuid::Uuid) -> Result<(), AppError> {
+    if edition_parent(state, id).await?.is_some() {
+        return Err(AppError::BadRequest(
+            "Language editions are sent with their newsletter".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct EditionForm {
+    pub lang: String,
+}
+
+/// Add a language edition to a draft newsletter, starting from a copy of it.
+pub async fn create_edition(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<EditionForm>,
+) -> Result<Redirect, AppError> {
+    let lang = crate::newsletter::parse_lang(&form.lang)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid language tag: {}", form.lang)))?;
+
+    let (slug, primary_lang, status, parent_id) =
+        sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>)>(
+            "SELECT slug, lang, status, parent_id FROM newsletters WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if parent_id.is_some() {
+        return Err(AppError::BadRequest(
+            "Editions cannot have editions of their own".to_string(),
+        ));
+    }
+    if status != "draft" {
+        return Err(AppError::BadRequest(
+            "Editions can only be added to draft newsletters".to_string(),
+        ));
+    }
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM newsletters WHERE parent_id = $1 AND lower(lang) = lower($2))",
+    )
+    .bind(id)
+    .bind(&lang)
+    .fetch_one(&state.db)
+    .await?;
+    if exists || primary_lang.eq_ignore_ascii_case(&lang) {
+        return Err(AppError::BadRequest(format!(
+            "This newsletter already has a {lang} edition"
+        )));
+    }
+
+    let edition_id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletters (parent_id, title, slug, markdown_content, template_id, short_domain, \
+         custom_slugs, dark_mode, lang, dir, created_by, render_hooks) \
+         SELECT id, title, $2, markdown_content, template_id, short_domain, custom_slugs, dark_mode, $3, dir, $4, render_hooks \
+         FROM newsletters WHERE id = $1 RETURNING id",
+    )
+    .bind(id)
+    .bind(format!("{slug}-{}", lang.to_ascii_lowercase()))
+    .bind(&lang)
+    .bind(&admin_email)
+    .fetch_one(&state.db)
+    .await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.edition_create",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "edition_id": edition_id.to_string(),
+            "lang": lang,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{edition_id}")))
+}
+
+// --- Preview ---
+
+/// Selected template's HTML, or the `coscup-default` template.
+async fn load_template_html(
+    state: &AppState,
+    template_id: Option<uuid::Uuid>,
+) -> Result<String, AppError> {
+    let template_html = if let Some(tid) = template_id {
+        sqlx::query_scalar::<_, String>("SELECT html_body FROM newsletter_templates WHERE id = $1")
+            .bind(tid)
+            .fetch_optional(&state.db)
+            .await?
+    } else {
+        None
+    };
+    let template_html = match template_html {
+        Some(html) => html,
+        None => {
+            sqlx::query_scalar::<_, String>(
+                "SELECT html_body FROM newsletter_templates WHERE slug = 'coscup-default'",
+            )
+            .fetch_one(&state.db)
+            .await?
+        }
+    };
+    Ok(newsletter::apply_compliance_footer(
+        &template_html,
+        newsletter::ComplianceFooter::from_config(&state.config),
+    ))
+}
+
+/// Estimated size in bytes of the email one subscriber would receive.
+async fn email_size(state: &AppState, id: uuid::Uuid) -> Result<usize, AppError> {
+    #[allow(clippy::type_complexity)]
+    let (title, markdown_content, slug, template_id, dark_mode, lang, dir, render_hooks) =
+        sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>, bool, String, String, Option<serde_json::Value>)>(
+            "SELECT title, markdown_content, slug, template_id, dark_mode, lang, dir, render_hooks FROM newsletters WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let mut template_html = load_template_html(state, template_id).await?;
+    if dark_mode {
+        template_html = newsletter::apply_dark_mode(&template_html, false);
+    }
+    let hooks = newsletter::RenderPipeline::from_json(render_hooks.as_ref());
+    let sponsors = crate::sponsors::active_today(&state.db).await?;
+    let content_html = hooks.render(
+        &markdown_content,
+        &newsletter::HookContext {
+            base_url: &state.config.base_url,
+            slug: &slug,
+            sponsors: &sponsors,
+            today: crate::timezone::today(),
+            event: crate::shortcodes::EventDates::from_config(&state.config),
+        },
+    );
+    let (content_html, template_html) = match &state.config.image_proxy_key {
+        Some(key) => (
+            crate::image_proxy::rewrite_image_srcs(&content_html, &state.config.base_url, key),
+            crate::image_proxy::rewrite_image_srcs(&template_html, &state.config.base_url, key),
+        ),
+        None => (content_html, template_html),
+    };
+    let html = newsletter::render_sample_email(
+        &template_html,
+        &content_html,
+        &title,
+        &slug,
+        &state.config.base_url,
+        newsletter::ContentLanguage {
+            lang: &lang,
+            dir: &dir,
+        },
+        &hooks,
+    )
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(html.len())
+}
+
+fn email_size_context(size: usize) -> serde_json::Value {
+    serde_json::json!({
+        "kb": newsletter::format_kb(size),
+        "limit_kb": newsletter::GMAIL_CLIP_LIMIT_BYTES / 1024,
+        "risk": newsletter::clip_risk(size),
+    })
+}
+
+/// Audience size and how long sending to it takes at the current rate limit,
+/// with the completion time if the send started now. A paused send only
+/// counts the recipients it hasn't reached yet.
+async fn send_plan(
+    state: &AppState,
+    id: uuid::Uuid,
+    remaining: Option<i64>,
+) -> Result<serde_json::Value, AppError> {
+    let recipients = match remaining {
+        Some(n) => n.max(0),
+        None => count_recipients(state, id).await?,
+    };
+    let delay_ms = state.live.get().smtp_rate_limit_ms;
+    // Messages go out one at a time
+    let duration = newsletter::estimate_send_duration(recipients, delay_ms, 1);
+    Ok(serde_json::json!({
+        "recipients": recipients,
+        "delay_ms": delay_ms,
+        "smtp_ms": newsletter::ASSUMED_SMTP_MS,
+        "duration": newsletter::format_duration(duration),
+        "finish_at": (Utc::now() + duration).to_rfc3339(),
+    }))
+}
+
+/// Refuse to send or schedule a message Gmail would clip, unless the admin
+/// explicitly overrode the check.
+async fn check_email_size(
+    state: &AppState,
+    id: uuid::Uuid,
+    override_size: bool,
+) -> Result<(), AppError> {
+    if override_size {
+        return Ok(());
+    }
+    let size = email_size(state, id).await?;
+    if newsletter::clip_risk(size) == newsletter::ClipRisk::Over {
+        return Err(AppError::BadRequest(format!(
+            "郵件大小約 {} KB，超過 Gmail {} KB 的截斷上限，退訂連結可能被隱藏。請精簡內容，或在編輯頁確認後強制發送。",
+            newsletter::format_kb(size),
+            newsletter::GMAIL_CLIP_LIMIT_BYTES / 1024,
+        )));
+    }
+    Ok(())
+}
+
+/// Refuse to send or schedule when the newsletter (or an edition) lacks a
+/// legally required element: the `{{ unsubscribe_url }}` link or the postal
+/// address.
+async fn check_compliance(state: &AppState, id: uuid::Uuid) -> Result<(), AppError> {
+    let editions = sqlx::query_as::<_, (String, Option<uuid::Uuid>)>(
+        "SELECT lang, template_id FROM newsletters WHERE id = $1 OR parent_id = $1 \
+         ORDER BY parent_id NULLS FIRST, lang",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+    for (lang, template_id) in editions {
+        let template_html = load_template_html(state, template_id).await?;
+        let problems = newsletter::compliance_problems(
+            &template_html,
+            newsletter::ComplianceFooter::from_config(&state.config),
+        )
+        .map_err(|e| AppError::BadRequest(format!("模板無法轉譯：{e}")))?;
+        if !problems.is_empty() {
+            let messages: Vec<&str> = problems.iter().map(|p| p.message()).collect();
+            return Err(AppError::BadRequest(format!(
+                "{lang} 版本未通過法規檢查，無法發送：{}。",
+                messages.join("；")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Refuse to send or schedule when an edition links to a blocked domain.
+async fn check_links(state: &AppState, id: uuid::Uuid) -> Result<(), AppError> {
+    let editions = newsletter::link_issues(state, id)
+        .await
+        .map_err(AppError::Internal)?;
+    for (lang, issues) in editions {
+        let blocked: Vec<&str> = issues
+            .iter()
+            .filter(|i| i.blocked)
+            .map(|i| i.url.as_str())
+            .collect();
+        if !blocked.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "{lang} 版本含有被封鎖網域的連結，無法發送：{}。",
+                blocked.join("、")
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    /// Show the email as a dark-mode client would
+    pub dark: Option<String>,
+    /// Outcome of the last test send: how many it went to, or why it failed
+    pub test_sent: Option<usize>,
+    pub test_error: Option<String>,
+    /// Frame width: `320`, `600` or `desktop` (the default)
+    pub frame: Option<String>,
+    /// Render as Outlook for Windows would
+    pub outlook: Option<String>,
+    /// Drop `<style>` blocks, as some webmail does
+    pub no_style: Option<String>,
+}
+
+pub async fn preview(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+    Query(query): Query<PreviewQuery>,
+) -> Result<Html<String>, AppError> {
+    #[allow(clippy::type_complexity)]
+    let row = sqlx::query_as::<_, (String, String, String, Option<uuid::Uuid>, bool, String, String, Option<serde_json::Value>)>(
+        "SELECT title, markdown_content, slug, template_id, dark_mode, lang, dir, render_hooks FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let (title, markdown_content, slug, template_id, dark_mode, lang, dir, render_hooks) = row;
+    let dark_preview = query.dark.is_some();
+    let langs = sqlx::query_scalar::<_, String>(
+        "SELECT lang FROM newsletters WHERE id = $1 OR parent_id = $1 \
+         ORDER BY parent_id NULLS FIRST, lang",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut template_html = load_template_html(&state, template_id).await?;
+    if dark_mode {
+        template_html = newsletter::apply_dark_mode(&template_html, dark_preview);
+    }
+
+    let sponsors = crate::sponsors::active_today(&state.db).await?;
+    let content_html = newsletter::RenderPipeline::from_json(render_hooks.as_ref()).render(
+        &markdown_content,
+        &newsletter::HookContext {
+            base_url: &state.config.base_url,
+            slug: &slug,
+            sponsors: &sponsors,
+            today: crate::timezone::today(),
+            event: crate::shortcodes::EventDates::from_config(&state.config),
+        },
+    );
+    let a11y_issues = crate::a11y::lint(&content_html);
+    let content_html = newsletter::replace_recipient_name(&content_html, "王小明");
+
+    // Use dummy values for preview
+    let tracking_pixel = "<!-- tracking pixel placeholder -->";
+    let unsubscribe_url = "#";
+    let web_url = "#";
+
+    let rendered = newsletter::personalize_email(
+        &template_html,
+        &content_html,
+        &title,
+        tracking_pixel,
+        unsubscribe_url,
+        &state.config.base_url,
+        web_url,
+        newsletter::ContentLanguage {
+            lang: &lang,
+            dir: &dir,
+        },
+    )
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let frame = crate::client_preview::Frame::parse(query.frame.as_deref());
+    let quirks = crate::client_preview::Quirks {
+        outlook: query.outlook.is_some(),
+        strip_style: query.no_style.is_some(),
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("newsletter_id", &id.to_string());
+    ctx.insert("title", &title);
+    ctx.insert(
+        "rendered_html",
+        &crate::client_preview::simulate(&rendered, quirks),
+    );
+    ctx.insert("frame", &frame);
+    ctx.insert("frame_width", &frame.width());
+    ctx.insert("quirks", &quirks);
+    ctx.insert("dark_mode", &dark_mode);
+    ctx.insert("dark_preview", &dark_preview);
+    ctx.insert("a11y_issues", &a11y_issues);
+    ctx.insert("langs", &langs);
+    ctx.insert("test_sent", &query.test_sent);
+    ctx.insert("test_error", &query.test_error);
+    ctx.insert(
+        "link_issues",
+        &crate::link_domains::check(&rendered, &crate::link_domains::Rules::load(&state).await?),
+    );
+    ctx.insert(
+        "broken_images",
+        &crate::image_proxy::find_broken_images(state.images.clone(), &rendered).await,
+    );
+    ctx.insert(
+        "email_size",
+        &email_size_context(email_size(&state, id).await?),
+    );
+    let html = state.tera.render("admin/newsletter_preview.html", &ctx)?;
+    Ok(Html(html))
+}
+
+fn default_lang() -> String {
+    "zh-TW".to_string()
+}
+
+fn default_dir() -> String {
+    "ltr".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct RenderPreviewRequest {
+    pub markdown: String,
+    #[serde(default)]
+    pub title: String,
+    /// The default template when absent
+    pub template_id: Option<uuid::Uuid>,
+    #[serde(default)]
+    pub dark_mode: bool,
+    #[serde(default = "default_lang")]
+    pub lang: String,
+    #[serde(default = "default_dir")]
+    pub dir: String,
+}
+
+/// Render unsaved editor content for the live preview: the sanitized content
+/// fragment, and the full email in the chosen template with placeholder links.
+pub async fn render_preview(
+    State(state): State<AppState>,
+    AdminUser(_admin_email): AdminUser,
+    Json(req): Json<RenderPreviewRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mut template_html = load_template_html(&state, req.template_id).await?;
+    if req.dark_mode {
+        template_html = newsletter::apply_dark_mode(&template_html, false);
+    }
+
+    let sponsors = crate::sponsors::active_today(&state.db).await?;
+    let content_html = newsletter::sanitize_html(&newsletter::RenderPipeline::default().render(
+        &req.markdown,
+        &newsletter::HookContext {
+            base_url: &state.config.base_url,
+            slug: "",
+            sponsors: &sponsors,
+            today: crate::timezone::today(),
+            event: crate::shortcodes::EventDates::from_config(&state.config),
+        },
+    ));
+    let html = newsletter::personalize_email(
+        &template_html,
+        &newsletter::replace_recipient_name(&content_html, "王小明"),
+        &req.title,
+        "",
+        "#",
+        &state.config.base_url,
+        "#",
+        newsletter::ContentLanguage {
+            lang: &req.lang,
+            dir: &req.dir,
+        },
+    )
+    .map_err(|e| AppError::BadRequest(format!("模板無法轉譯：{e}")))?;
+
+    Ok(Json(serde_json::json!({
+        "content_html": content_html,
+        "html": html,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct SourceQuery {
+    /// Language edition to show; the newsletter itself when absent
+    pub lang: Option<String>,
+    /// Download as an `.eml` file instead of showing the page
+    pub download: Option<String>,
+}
+
+/// The raw MIME message a sample subscriber would receive, shown as escaped
+/// text so nothing in it is rendered or loaded.
+pub async fn preview_source(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+    Query(query): Query<SourceQuery>,
+) -> Result<Response, AppError> {
+    let (title, langs) = sqlx::query_as::<_, (String, Vec<String>)>(
+        "SELECT n.title, ARRAY(SELECT lang FROM newsletters \
+         WHERE id = n.id OR parent_id = n.id ORDER BY parent_id NULLS FIRST, lang) \
+         FROM newsletters n WHERE n.id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let lang = query.lang.filter(|lang| langs.contains(lang));
+    let source = newsletter::render_message_source(&state, id, lang.as_deref())
+        .await
+        .map_err(AppError::Internal)?;
+
+    if query.download.is_some() {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "message/rfc822".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"newsletter-{id}.eml\""),
+                ),
+            ],
+            source,
+        )
+            .into_response());
+    }
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("newsletter_id", &id.to_string());
+    ctx.insert("title", &title);
+    ctx.insert("langs", &langs);
+    ctx.insert("lang", &lang);
+    ctx.insert("recipient", newsletter::SAMPLE_RECIPIENT_EMAIL);
+    ctx.insert("source", &String::from_utf8_lossy(&source));
+    let html = state.tera.render("admin/newsletter_source.html", &ctx)?;
+    Ok(Html(html).into_response())
+}
+
+// --- Test send ---
+
+/// Most addresses one test send goes to.
+const MAX_TEST_RECIPIENTS: usize = 10;
+
+#[derive(Deserialize)]
+pub struct TestSendForm {
+    /// Addresses separated by commas, spaces or new lines
+    #[serde(default)]
+    pub emails: String,
+    pub lang: Option<String>,
+}
+
+/// Normalized, deduplicated test send addresses; `fallback` (the admin's own)
+/// when none are given.
+fn parse_test_recipients(input: &str, fallback: &str) -> Result<Vec<String>, String> {
+    let mut recipients: Vec<String> = Vec::new();
+    for raw in input
+        .split([',', ';', ' ', '\n', '\r'])
+        .filter(|s| !s.is_empty())
+    {
+        let email = crate::email_validation::normalize(raw)
+            .map_err(|e| format!("{raw}：{e}"))?
+            .email;
+        if !recipients.contains(&email) {
+            recipients.push(email);
+        }
+    }
+    if recipients.is_empty() {
+        recipients.push(fallback.to_string());
+    }
+    if recipients.len() > MAX_TEST_RECIPIENTS {
+        return Err(format!("測試信最多寄給 {MAX_TEST_RECIPIENTS} 個地址"));
+    }
+    Ok(recipients)
+}
+
+/// Send the newsletter as a sample subscriber would get it to the given
+/// addresses, with a `[TEST]` subject, in any status. Send counters, the
+/// audience and stats are left alone.
+pub async fn test_send(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<TestSendForm>,
+) -> Result<Redirect, AppError> {
+    let langs = sqlx::query_scalar::<_, String>(
+        "SELECT lang FROM newsletters WHERE id = $1 OR parent_id = $1",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+    if langs.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    let recipients =
+        parse_test_recipients(&form.emails, &admin_email).map_err(AppError::BadRequest)?;
+    let lang = form.lang.filter(|lang| langs.contains(lang));
+
+    let result = newsletter::send_test(&state, id, lang.as_deref(), &recipients).await;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.test_send",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "recipients": recipients,
+            "lang": lang,
+            "ok": result.is_ok(),
+            "error": result.as_ref().err(),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&match result {
+        Ok(()) => format!(
+            "/admin/newsletters/{id}/preview?test_sent={}",
+            recipients.len()
+        ),
+        Err(e) => {
+            tracing::warn!("Test send of {id} by {admin_email} failed: {e}");
+            format!(
+                "/admin/newsletters/{id}/preview?test_error={}",
+                urlencoding::encode(&e)
+            )
+        }
+    }))
+}
+
+// --- Send ---
+
+/// Whether a send to `recipients` people needs a second admin's approval.
+/// A threshold of 0 disables approvals.
+fn needs_approval(recipients: i64, threshold: i64) -> bool {
+    threshold > 0 && recipients > threshold
+}
+
+/// Recipients a send would currently target, before frequency capping.
+async fn count_recipients(state: &AppState, id: uuid::Uuid) -> Result<i64, AppError> {
+    #[allow(clippy::type_complexity)]
+    let (segment_id, tag_id, list_topic) =
+        sqlx::query_as::<_, (Option<uuid::Uuid>, Option<uuid::Uuid>, Option<String>)>(
+            "SELECT segment_id, tag_id, list_topic FROM newsletters WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    let target = crate::segment::load_target(&state.db, segment_id, tag_id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("收件對象的分眾已不存在".to_string()))?;
+    let mut qb =
+        sqlx::QueryBuilder::<sqlx::Postgres>::new("SELECT COUNT(*) FROM subscribers s WHERE ");
+    qb.push(crate::segment::RECIPIENT_CONDITION);
+    target.push_conditions(&mut qb);
+    // Those who stopped the newsletter's topic aren't sent it
+    if let Some(topic) = &list_topic {
+        crate::list_topics::push_exclusion(&mut qb, topic);
+    }
+    Ok(qb.build_query_scalar::<i64>().fetch_one(&state.db).await?)
+}
+
+/// Statuses a send can start from.
+const SENDABLE: [&str; 3] = ["draft", "scheduled", "missed"];
+
+/// Statuses a newsletter can be scheduled from; a missed schedule may be
+/// moved to a new time.
+const SCHEDULABLE: [&str; 2] = ["draft", "missed"];
+
+/// Error when a conditional status change finds the newsletter already
+/// moved on, e.g. the second of two clicks on 送出.
+fn status_changed() -> AppError {
+    AppError::BadRequest("電子報狀態已變更（可能已開始寄送），請重新整理頁面".to_string())
+}
+
+/// If the send needs approval, move the newsletter from one of the `from`
+/// statuses to `pending_approval` (keeping the requested schedule, `None` =
+/// send now) and return true.
+async fn request_approval_if_needed(
+    state: &AppState,
+    actor: &str,
+    client_ip: Option<std::net::IpAddr>,
+    id: uuid::Uuid,
+    scheduled_at: Option<chrono::DateTime<Utc>>,
+    from: &[&str],
+) -> Result<bool, AppError> {
+    let threshold = state.config.send_approval_threshold;
+    if threshold <= 0 {
+        return Ok(false);
+    }
+    let recipients = count_recipients(state, id).await?;
+    if !needs_approval(recipients, threshold) {
+        return Ok(false);
+    }
+
+    let updated = sqlx::query(
+        "UPDATE newsletters SET status = 'pending_approval', scheduled_at = $1, \
+         approval_requested_by = $2, approval_requested_at = NOW(), approval_recipients = $3, \
+         approved_by = NULL, approved_at = NULL, updated_at = NOW() \
+         WHERE id = $4 AND status = ANY($5)",
+    )
+    .bind(scheduled_at)
+    .bind(actor)
+    .bind(recipients)
+    .bind(id)
+    .bind(from)
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(status_changed());
+    }
+
+    crate::audit::log(
+        &state.db,
+        actor,
+        "newsletter.approval_request",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "recipients": recipients,
+            "scheduled_at": scheduled_at.map(|t| t.to_rfc3339()),
+        })),
+        client_ip,
+    )
+    .await;
+    Ok(true)
+}
+
+/// Pending approval details for the edit page.
+async fn approval_info(state: &AppState, id: uuid::Uuid) -> Result<serde_json::Value, AppError> {
+    #[allow(clippy::type_complexity)]
+    let (requested_by, requested_at, recipients, scheduled_at) = sqlx::query_as::<
+        _,
+        (
+            Option<String>,
+            Option<chrono::DateTime<Utc>>,
+            Option<i64>,
+            Option<chrono::DateTime<Utc>>,
+        ),
+    >(
+        "SELECT approval_requested_by, approval_requested_at, approval_recipients, scheduled_at \
+         FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    Ok(serde_json::json!({
+        "requested_by": requested_by.unwrap_or_default(),
+        "requested_at": requested_at.map(|t| t.to_rfc3339()),
+        "recipients": recipients.unwrap_or_default(),
+        "scheduled_at": scheduled_at.map(|t| t.to_rfc3339()),
+    }))
+}
+
+/// Queue the send for `newsletter::send_worker`.
+async fn queue_send(state: &AppState, id: uuid::Uuid) -> Result<(), AppError> {
+    crate::send_queue::enqueue(&state.db, id).await?;
+    Ok(())
+}
+
+/// Send the newsletter now, or ask for approval first if it goes to many
+/// subscribers. Returns the new status, `sending` or `pending_approval`.
+///
+/// The status moves with a conditional UPDATE, so of two requests racing
+/// (a double click, a retried API call) only one queues a send.
+pub(crate) async fn start_send(
+    state: &AppState,
+    actor: &str,
+    client_ip: Option<std::net::IpAddr>,
+    id: uuid::Uuid,
+    override_size: bool,
+) -> Result<&'static str, AppError> {
+    let (status, approved_by) = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT status, approved_by FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    if status == "paused" {
+        return Err(AppError::BadRequest(
+            "Paused newsletters are resumed with /resume".to_string(),
+        ));
+    }
+    if !SENDABLE.contains(&status.as_str()) {
+        return Err(AppError::BadRequest(
+            "Newsletter must be in draft, scheduled or missed status to send".to_string(),
+        ));
+    }
+    reject_edition(state, id).await?;
+    // A send still finishing (e.g. its batch after a pause) has to end first
+    if crate::send_queue::is_active(&state.db, id).await? {
+        return Err(AppError::BadRequest("這份電子報已在寄送佇列中".to_string()));
+    }
+    check_compliance(state, id).await?;
+    check_links(state, id).await?;
+
+    if status == "draft" {
+        check_email_size(state, id, override_size).await?;
+    }
+
+    // Sending now goes out to every timezone at once
+    sqlx::query(
+        "UPDATE newsletters SET local_delivery = FALSE, local_release_at = NULL WHERE id = $1",
+    )
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    // Sending an approved one needs no new approval
+    if approved_by.is_none()
+        && request_approval_if_needed(state, actor, client_ip, id, None, &SENDABLE).await?
+    {
+        return Ok("pending_approval");
+    }
+
+    let claimed = sqlx::query(
+        "UPDATE newsletters SET status = 'sending', updated_at = NOW() \
+         WHERE id = $1 AND status = ANY($2)",
+    )
+    .bind(id)
+    .bind(&SENDABLE[..])
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Err(status_changed());
+    }
+    queue_send(state, id).await?;
+
+    crate::audit::log(
+        &state.db,
+        actor,
+        "newsletter.send",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "override_size": override_size,
+        })),
+        client_ip,
+    )
+    .await;
+    Ok("sending")
+}
+
+/// Schedule the newsletter, or ask for approval first. Returns the new
+/// status, `scheduled` or `pending_approval`; like `start_send`, only one
+/// of two racing requests gets through.
+pub(crate) async fn start_schedule(
+    state: &AppState,
+    actor: &str,
+    client_ip: Option<std::net::IpAddr>,
+    id: uuid::Uuid,
+    scheduled_at: chrono::DateTime<Utc>,
+    local_delivery: bool,
+    override_size: bool,
+) -> Result<&'static str, AppError> {
+    let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !SCHEDULABLE.contains(&status.as_str()) {
+        return Err(AppError::BadRequest(
+            "Only draft or missed newsletters can be scheduled".to_string(),
+        ));
+    }
+    reject_edition(state, id).await?;
+    check_compliance(state, id).await?;
+    check_links(state, id).await?;
+    check_email_size(state, id, override_size).await?;
+
+    // Stored up front so it also applies once an approval comes through
+    sqlx::query(
+        "UPDATE newsletters SET local_delivery = $1, local_release_at = NULL WHERE id = $2",
+    )
+    .bind(local_delivery)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    if request_approval_if_needed(
+        state,
+        actor,
+        client_ip,
+        id,
+        Some(scheduled_at),
+        &SCHEDULABLE,
+    )
+    .await?
+    {
+        return Ok("pending_approval");
+    }
+
+    let updated = sqlx::query(
+        "UPDATE newsletters SET status = 'scheduled', scheduled_at = $1, missed_at = NULL, \
+         updated_at = NOW() WHERE id = $2 AND status = ANY($3)",
+    )
+    .bind(scheduled_at)
+    .bind(id)
+    .bind(&SCHEDULABLE[..])
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(status_changed());
+    }
+
+    crate::audit::log(
+        &state.db,
+        actor,
+        "newsletter.schedule",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "scheduled_at": scheduled_at.to_rfc3339(),
+            "local_delivery": local_delivery,
+        })),
+        client_ip,
+    )
+    .await;
+    Ok("scheduled")
+}
+
+#[derive(Deserialize)]
+pub struct SendForm {
+    /// Set to send even though the message exceeds Gmail's clipping limit
+    pub override_size: Option<String>,
+}
+
+pub async fn send_now(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<SendForm>,
+) -> Result<Redirect, AppError> {
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    start_send(
+        &state,
+        &admin_email,
+        Some(client_ip),
+        id,
+        form.override_size.is_some(),
+    )
+    .await?;
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+}
+
+// --- Schedule ---
+
+#[derive(Deserialize)]
+pub struct ScheduleForm {
+    pub scheduled_at: String,
+    /// Set to schedule even though the message exceeds Gmail's clipping limit
+    pub override_size: Option<String>,
+    /// Set to deliver at `scheduled_at`'s wall-clock time in each subscriber's timezone
+    pub local_delivery: Option<String>,
+}
+
+pub async fn schedule(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<ScheduleForm>,
+) -> Result<Redirect, AppError> {
+    let naive = NaiveDateTime::parse_from_str(&form.scheduled_at, "%Y-%m-%dT%H:%M")
+        .map_err(|e| AppError::BadRequest(format!("Invalid datetime: {e}")))?;
+    let tz = crate::timezone::for_admin(&state.db, &admin_email).await;
+    let scheduled_at = crate::timezone::from_local(naive, tz)
+        .ok_or_else(|| AppError::BadRequest("Invalid timezone conversion".to_string()))?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    start_schedule(
+        &state,
+        &admin_email,
+        Some(client_ip),
+        id,
+        scheduled_at,
+        form.local_delivery.is_some(),
+        form.override_size.is_some(),
+    )
+    .await?;
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+}
+
+// --- Cancel ---
+
+#[derive(Deserialize)]
+pub struct CancelForm {
+    /// Reason given when sending a pending approval back to draft; kept
+    /// as a review comment on the newsletter.
+    pub comment: Option<String>,
+}
+
+pub async fn cancel(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    // Optional, so that posting without a form body still cancels
+    form: Result<Form<CancelForm>, axum::extract::rejection::FormRejection>,
+) -> Result<Redirect, AppError> {
+    let comment = form.ok().and_then(|Form(form)| form.comment);
+    let (status, partially_sent) = sqlx::query_as::<_, (String, bool)>(
+        "SELECT status, local_release_at IS NOT NULL FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
 
     match status.as_str() {
-        "scheduled" => {
+        // A local-time send between batches has gone out to some timezones
+        // already, so it ends like a paused send instead
+        "scheduled" if partially_sent => {
             sqlx::query(
-                "UPDATE newsletters SET status = 'draft', scheduled_at = NULL, updated_at = NOW() WHERE id = $1",
+                "UPDATE newsletters SET status = 'sent', local_release_at = NULL, \
+                 sending_completed_at = NOW(), updated_at = NOW() WHERE id = $1",
             )
             .bind(id)
             .execute(&state.db)
             .await?;
         }
-        "sending" => {
+        // Back to draft; edits after this need a fresh approval
+        "scheduled" | "missed" | "pending_approval" => {
+            let comment = comment
+                .as_deref()
+                .map(str::trim)
+                .filter(|c| !c.is_empty() && status == "pending_approval");
+            if let Some(comment) = comment {
+                validate_comment(comment)?;
+                insert_comment(&state, id, &admin_email, comment).await?;
+            }
             sqlx::query(
-                "UPDATE newsletters SET status = 'paused', updated_at = NOW() WHERE id = $1",
+                "UPDATE newsletters SET status = 'draft', scheduled_at = NULL, local_delivery = FALSE, \
+                 missed_at = NULL, approved_by = NULL, approved_at = NULL, updated_at = NOW() \
+                 WHERE id = $1",
             )
             .bind(id)
             .execute(&state.db)
             .await?;
         }
-        "paused" => {
+        // Ends the send for good; the send loop stops after its current batch
+        "sending" | "paused" => {
             sqlx::query(
                 "UPDATE newsletters SET status = 'sent', sending_completed_at = NOW(), updated_at = NOW() WHERE id = $1",
             )
@@ -473,24 +2130,493 @@ pub async fn cancel(
             .execute(&state.db)
             .await?;
         }
-        _ => {
-            return Err(AppError::BadRequest(
-                "Newsletter is not in a cancellable state".to_string(),
-            ));
+        _ => {
+            return Err(AppError::BadRequest(
+                "Newsletter is not in a cancellable state".to_string(),
+            ));
+        }
+    }
+    state.archive_cache.invalidate();
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        if status == "pending_approval" {
+            "newsletter.approval_reject"
+        } else {
+            "newsletter.cancel"
+        },
+        Some(serde_json::json!({ "newsletter_id": id.to_string() })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+}
+
+// --- Pause / resume ---
+
+/// Longest reason accepted when pausing a send.
+const MAX_PAUSE_REASON_CHARS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct PauseForm {
+    pub reason: Option<String>,
+}
+
+/// Pause a send. The send loop stops at the end of its current batch, and
+/// a local-time send waiting for its next timezone is held back.
+pub async fn pause(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<PauseForm>,
+) -> Result<Redirect, AppError> {
+    let reason = form
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.chars().count() > MAX_PAUSE_REASON_CHARS) {
+        return Err(AppError::BadRequest(format!(
+            "暫停原因不可超過 {MAX_PAUSE_REASON_CHARS} 字"
+        )));
+    }
+
+    let paused = sqlx::query(
+        "UPDATE newsletters SET status = 'paused', local_release_at = NULL, \
+         pause_reason = $2, paused_by = $3, paused_at = NOW(), updated_at = NOW() \
+         WHERE id = $1 AND (status = 'sending' OR (status = 'scheduled' AND local_release_at IS NOT NULL))",
+    )
+    .bind(id)
+    .bind(reason)
+    .bind(&admin_email)
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+    if paused == 0 {
+        return Err(
+            match sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&state.db)
+                .await?
+            {
+                None => AppError::NotFound,
+                Some(_) => {
+                    AppError::BadRequest("Only sending newsletters can be paused".to_string())
+                }
+            },
+        );
+    }
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.pause",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "reason": reason,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+}
+
+/// Resume a paused send from its last checkpoint: recipients already sent
+/// to or failed on are skipped. If the paused run is still finishing its
+/// batch, the queued send starts once it ends.
+pub async fn resume(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let status = sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if status != "paused" {
+        return Err(AppError::BadRequest(
+            "Only paused newsletters can be resumed".to_string(),
+        ));
+    }
+    check_compliance(&state, id).await?;
+    check_links(&state, id).await?;
+
+    let resumed = sqlx::query(
+        "UPDATE newsletters SET status = 'sending', pause_reason = NULL, paused_by = NULL, \
+         paused_at = NULL, updated_at = NOW() WHERE id = $1 AND status = 'paused'",
+    )
+    .bind(id)
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+    if resumed == 0 {
+        return Err(AppError::BadRequest(
+            "Only paused newsletters can be resumed".to_string(),
+        ));
+    }
+    queue_send(&state, id).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.resume",
+        Some(serde_json::json!({ "newsletter_id": id.to_string() })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+}
+
+/// Why, when and by whom a send was paused, and how far it got.
+async fn pause_info(state: &AppState, id: uuid::Uuid) -> Result<serde_json::Value, AppError> {
+    #[allow(clippy::type_complexity)]
+    let (reason, paused_by, paused_at, batches, checkpoint_at) = sqlx::query_as::<
+        _,
+        (
+            Option<String>,
+            Option<String>,
+            Option<chrono::DateTime<Utc>>,
+            i32,
+            Option<chrono::DateTime<Utc>>,
+        ),
+    >(
+        "SELECT pause_reason, paused_by, paused_at, checkpoint_batches, checkpoint_at \
+         FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    Ok(serde_json::json!({
+        "reason": reason,
+        "paused_by": paused_by,
+        "paused_at": paused_at.map(|t| t.to_rfc3339()),
+        "batches": batches,
+        "checkpoint_at": checkpoint_at.map(|t| t.to_rfc3339()),
+    }))
+}
+
+// --- Approve ---
+
+/// Second admin confirms a send waiting for approval. It then goes out
+/// immediately, or at the requested time if that is still in the future.
+pub async fn approve(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let (status, requested_by) = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT status, approval_requested_by FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    if status != "pending_approval" {
+        return Err(AppError::BadRequest(
+            "Newsletter is not waiting for approval".to_string(),
+        ));
+    }
+    if requested_by.as_deref() == Some(admin_email.as_str()) {
+        return Err(AppError::BadRequest("需由另一位管理員核准發送".to_string()));
+    }
+    check_compliance(&state, id).await?;
+    check_links(&state, id).await?;
+    if open_comment_count(&state, id).await? > 0 {
+        return Err(AppError::BadRequest(
+            "尚有未解決的留言，請先處理後再核准發送".to_string(),
+        ));
+    }
+
+    // Conditional update so two approvals can't both start a send
+    let new_status = sqlx::query_scalar::<_, String>(
+        "UPDATE newsletters SET approved_by = $1, approved_at = NOW(), \
+         status = CASE WHEN scheduled_at > NOW() THEN 'scheduled' ELSE 'sending' END, \
+         updated_at = NOW() \
+         WHERE id = $2 AND status = 'pending_approval' AND approval_requested_by IS DISTINCT FROM $1 \
+         RETURNING status",
+    )
+    .bind(&admin_email)
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Newsletter is not waiting for approval".to_string()))?;
+
+    if new_status == "sending" {
+        queue_send(&state, id).await?;
+    }
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.approve",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "requested_by": requested_by,
+            "status": new_status,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+}
+
+// --- Comments ---
+
+/// Longest review comment accepted.
+const MAX_COMMENT_CHARS: usize = 5000;
+
+fn validate_comment(body: &str) -> Result<(), AppError> {
+    if body.is_empty() {
+        return Err(AppError::BadRequest("留言內容不可為空".to_string()));
+    }
+    if body.chars().count() > MAX_COMMENT_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "留言不可超過 {MAX_COMMENT_CHARS} 字"
+        )));
+    }
+    Ok(())
+}
+
+async fn insert_comment(
+    state: &AppState,
+    id: uuid::Uuid,
+    author: &str,
+    body: &str,
+) -> Result<uuid::Uuid, AppError> {
+    Ok(sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletter_comments (newsletter_id, author_email, body) \
+         VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(id)
+    .bind(author)
+    .bind(body)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// Unresolved comments, which hold back approving the send.
+async fn open_comment_count(state: &AppState, id: uuid::Uuid) -> Result<i64, AppError> {
+    Ok(sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM newsletter_comments WHERE newsletter_id = $1 AND resolved_at IS NULL",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// Comment thread for the edit page, oldest first.
+async fn newsletter_comments(
+    state: &AppState,
+    id: uuid::Uuid,
+) -> Result<Vec<serde_json::Value>, AppError> {
+    #[allow(clippy::type_complexity)]
+    let rows = sqlx::query_as::<
+        _,
+        (
+            uuid::Uuid,
+            String,
+            String,
+            Option<String>,
+            Option<chrono::DateTime<Utc>>,
+            chrono::DateTime<Utc>,
+        ),
+    >(&format!(
+        "SELECT id, {}, body, {}, resolved_at, created_at \
+             FROM newsletter_comments WHERE newsletter_id = $1 ORDER BY created_at",
+        crate::admin_profile::display_name_sql("author_email"),
+        crate::admin_profile::display_name_sql("resolved_by"),
+    ))
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(cid, author, body, resolved_by, resolved_at, created_at)| {
+                serde_json::json!({
+                    "id": cid.to_string(),
+                    "author": author,
+                    "body": body,
+                    "resolved": resolved_at.is_some(),
+                    "resolved_by": resolved_by.unwrap_or_default(),
+                    "resolved_at": resolved_at.map(|t| t.to_rfc3339()),
+                    "created_at": created_at.to_rfc3339(),
+                })
+            },
+        )
+        .collect())
+}
+
+/// Comment thread and open comment count for the edit page.
+async fn comments_context(
+    state: &AppState,
+    id: uuid::Uuid,
+    ctx: &mut tera::Context,
+) -> Result<(), AppError> {
+    let comments = newsletter_comments(state, id).await?;
+    let open = comments
+        .iter()
+        .filter(|c| !c["resolved"].as_bool().unwrap_or(false))
+        .count();
+    ctx.insert("open_comments", &open);
+    ctx.insert("comments", &comments);
+    ctx.insert("max_comment_chars", &MAX_COMMENT_CHARS);
+    Ok(())
+}
+
+/// Email the newsletter's creator about a comment from another admin, unless
+/// they turned these notifications off.
+async fn notify_comment(
+    state: &AppState,
+    id: uuid::Uuid,
+    title: &str,
+    creator: &str,
+    author: &str,
+    body: &str,
+) {
+    match crate::admin_profile::load(&state.db, creator).await {
+        Ok(profile) if profile.notify_comments => {}
+        Ok(_) => return,
+        Err(e) => {
+            tracing::warn!("Failed to load profile of {creator}: {e}");
+            return;
+        }
+    }
+    let author_name = crate::admin_profile::load(&state.db, author)
+        .await
+        .ok()
+        .and_then(|p| p.display_name)
+        .unwrap_or_else(|| author.to_string());
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", title);
+    ctx.insert("author", &author_name);
+    ctx.insert("body", body);
+    ctx.insert(
+        "newsletter_url",
+        &format!("{}/admin/newsletters/{id}#comments", state.config.base_url),
+    );
+    ctx.insert(
+        "logo_url",
+        &format!("{}/static/coscup-logo.png", state.config.base_url),
+    );
+    let html = match state.tera.render("emails/new_comment.html", &ctx) {
+        Ok(html) => html,
+        Err(e) => {
+            tracing::error!("Failed to render comment notification: {e}");
+            return;
         }
+    };
+    if let Err(e) = state
+        .email
+        .send_email(
+            creator,
+            &format!("COSCUP Newsletter Admin - 「{title}」有新留言"),
+            &html,
+        )
+        .await
+    {
+        tracing::error!("Failed to send comment notification to {creator}: {e}");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommentForm {
+    pub body: String,
+}
+
+pub async fn add_comment(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<CommentForm>,
+) -> Result<Redirect, AppError> {
+    let (title, created_by) = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT title, created_by FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    let body = form.body.trim();
+    validate_comment(body)?;
+    let comment_id = insert_comment(&state, id, &admin_email, body).await?;
+    if let Some(creator) = created_by.filter(|c| *c != admin_email) {
+        notify_comment(&state, id, &title, &creator, &admin_email, body).await;
     }
 
     let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
     crate::audit::log(
         &state.db,
         &admin_email,
-        "newsletter.cancel",
-        Some(serde_json::json!({ "newsletter_id": id.to_string() })),
+        "newsletter.comment",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "comment_id": comment_id.to_string(),
+        })),
         Some(client_ip),
     )
     .await;
 
-    Ok(Redirect::to(&format!("/admin/newsletters/{id}")))
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}#comments")))
+}
+
+/// Mark a comment resolved, or reopen it if it already is.
+pub async fn resolve_comment(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((id, comment_id)): Path<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<Redirect, AppError> {
+    let resolved = sqlx::query_scalar::<_, bool>(
+        "UPDATE newsletter_comments SET \
+         resolved_by = CASE WHEN resolved_at IS NULL THEN $1 END, \
+         resolved_at = CASE WHEN resolved_at IS NULL THEN NOW() END \
+         WHERE id = $2 AND newsletter_id = $3 \
+         RETURNING resolved_at IS NOT NULL",
+    )
+    .bind(&admin_email)
+    .bind(comment_id)
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.comment_resolve",
+        Some(serde_json::json!({
+            "newsletter_id": id.to_string(),
+            "comment_id": comment_id.to_string(),
+            "resolved": resolved,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}#comments")))
 }
 
 // --- Status (JSON for polling) ---
@@ -500,21 +2626,22 @@ pub async fn status_json(
     State(state): State<AppState>,
     Path(id): Path<uuid::Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let row = sqlx::query_as::<_, (String, i32, i32, i32)>(
-        "SELECT status, sent_count, failed_count, total_count FROM newsletters WHERE id = $1",
+    let row = sqlx::query_as::<_, (String, i32, i32, i32, i32)>(
+        "SELECT status, sent_count, failed_count, total_count, deferred_count FROM newsletters WHERE id = $1",
     )
     .bind(id)
     .fetch_optional(&state.db)
     .await?
     .ok_or(AppError::NotFound)?;
 
-    let (status, sent_count, failed_count, total_count) = row;
+    let (status, sent_count, failed_count, total_count, deferred_count) = row;
 
     Ok(Json(serde_json::json!({
         "status": status,
         "sent_count": sent_count,
         "failed_count": failed_count,
         "total_count": total_count,
+        "deferred_count": deferred_count,
     })))
 }
 
@@ -525,116 +2652,303 @@ pub async fn stats(
     AdminUser(admin_email): AdminUser,
     Path(id): Path<uuid::Uuid>,
 ) -> Result<Html<String>, AppError> {
-    let row = sqlx::query_as::<_, (String, String, i32, i32, i32, Option<String>)>(
-        "SELECT title, status, sent_count, failed_count, total_count, rendered_html FROM newsletters WHERE id = $1",
-    )
-    .bind(id)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(AppError::NotFound)?;
+    let report = crate::stats::newsletter(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
 
-    let (title, status, sent_count, failed_count, total_count, rendered_html) = row;
-
-    // Extract link text from rendered HTML: URL → anchor text
-    let link_text_map: std::collections::HashMap<String, String> = {
-        let mut map = std::collections::HashMap::new();
-        if let Some(ref html) = rendered_html {
-            let re = regex::Regex::new(r#"<a\s[^>]*href="(https?://[^"]+)"[^>]*>(.*?)</a>"#)
-                .expect("valid regex");
-            let strip_tags = regex::Regex::new(r"<[^>]+>").expect("valid regex");
-            for caps in re.captures_iter(html) {
-                let url = caps[1].to_string();
-                // Strip HTML tags from link text (e.g. <img> inside <a>)
-                let text = strip_tags.replace_all(&caps[2], "").trim().to_string();
-                if !text.is_empty() {
-                    map.entry(url).or_insert(text);
-                }
-            }
-        }
-        map
-    };
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("newsletter_id", &id.to_string());
+    ctx.insert("title", &report.title);
+    ctx.insert("status", &report.status);
+    ctx.insert("sent_count", &report.sent_count);
+    ctx.insert("failed_count", &report.failed_count);
+    ctx.insert("total_count", &report.total_count);
+    ctx.insert("deferred_count", &report.deferred_count);
+    ctx.insert("unique_opens", &report.unique_opens);
+    ctx.insert("open_rate", &crate::stats::format_rate(report.open_rate));
+    ctx.insert("total_clicks", &report.total_clicks);
+    ctx.insert("unique_clicks", &report.unique_clicks);
+    ctx.insert("scanner_clicks", &report.scanner_clicks);
+    ctx.insert("unsubscribe_count", &report.unsubscribe_count);
+    ctx.insert("topic_unsubscribe_count", &report.topic_unsubscribe_count);
+    ctx.insert("links", &report.links);
+    ctx.insert(
+        "short_clicks_synced_at",
+        &report.short_clicks_synced_at.map(|t| t.to_rfc3339()),
+    );
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("editions", &report.editions);
+    ctx.insert("cta", &report.cta);
+    ctx.insert(
+        "cta_click_through_rate",
+        &crate::stats::format_rate(report.cta.click_through_rate),
+    );
+    let cta_links: Vec<crate::cta::CtaLink> = report
+        .cta
+        .links
+        .iter()
+        .map(|l| crate::cta::CtaLink {
+            url: l.url.clone(),
+            goal: l.goal,
+        })
+        .collect();
+    ctx.insert("cta_links", &crate::cta::format(&cta_links));
+    let html = state.tera.render("admin/newsletter_stats.html", &ctx)?;
+    Ok(Html(html))
+}
 
-    // Get unique opens from email_events
-    let slug = sqlx::query_scalar::<_, String>("SELECT slug FROM newsletters WHERE id = $1")
-        .bind(id)
-        .fetch_one(&state.db)
-        .await?;
+#[derive(Deserialize)]
+pub struct CtaForm {
+    pub cta_links: String,
+}
+
+/// Mark the primary call-to-action links. Allowed at any status, since
+/// goals are often set or adjusted after sending.
+pub async fn update_cta(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<CtaForm>,
+) -> Result<Redirect, AppError> {
+    let links = crate::cta::parse(&form.cta_links).map_err(AppError::BadRequest)?;
+    let cta_links = crate::cta::to_json(&links);
+
+    let result =
+        sqlx::query("UPDATE newsletters SET cta_links = $1, updated_at = NOW() WHERE id = $2")
+            .bind(&cta_links)
+            .bind(id)
+            .execute(&state.db)
+            .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
 
-    let unique_opens: i64 = sqlx::query_scalar(
-        "SELECT COUNT(DISTINCT ucode) FROM email_events WHERE topic = $1 AND event_type = 'open'",
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.cta",
+        Some(serde_json::json!({ "newsletter_id": id.to_string(), "cta_links": cta_links })),
+        Some(client_ip),
     )
-    .bind(&slug)
-    .fetch_one(&state.db)
-    .await?;
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/newsletters/{id}/stats")))
+}
+
+// --- Failed sends ---
+
+#[derive(Deserialize)]
+pub struct FailuresQuery {
+    pub page: Option<i64>,
+    pub search: Option<String>,
+    pub class: Option<String>,
+}
+
+pub async fn failures(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+    Query(query): Query<FailuresQuery>,
+) -> Result<Html<String>, AppError> {
+    let title = sqlx::query_scalar::<_, String>("SELECT title FROM newsletters WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
 
-    // Get per-URL click counts from email_events
-    let url_clicks = sqlx::query_as::<_, (String, i64)>(
-        "SELECT clicked_url, COUNT(*) as clicks FROM email_events \
-         WHERE topic = $1 AND event_type = 'click' AND clicked_url IS NOT NULL \
-         GROUP BY clicked_url ORDER BY clicks DESC",
+    let search_pattern = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("%{s}%"));
+
+    let rows = sqlx::query_as::<_, (String, Option<String>, Option<i16>, chrono::DateTime<Utc>)>(
+        "SELECT s.email, ns.error_message, ns.smtp_code, COALESCE(ns.failed_at, ns.created_at) \
+         FROM newsletter_sends ns JOIN subscribers s ON s.id = ns.subscriber_id \
+         WHERE ns.newsletter_id = $1 AND ns.status = 'failed' \
+         AND ($2::TEXT IS NULL OR s.email ILIKE $2 OR ns.error_message ILIKE $2) \
+         ORDER BY 4 DESC",
     )
-    .bind(&slug)
+    .bind(id)
+    .bind(search_pattern.as_deref())
     .fetch_all(&state.db)
     .await?;
 
-    let link_list: Vec<serde_json::Value> = url_clicks
+    let selected_class = query
+        .class
+        .as_deref()
+        .and_then(crate::email::SendErrorClass::from_key);
+
+    // Group counts are computed over the search results, before the class filter,
+    // so the summary always shows every class present.
+    let mut class_counts: std::collections::HashMap<crate::email::SendErrorClass, usize> =
+        std::collections::HashMap::new();
+    let classified: Vec<_> = rows
+        .into_iter()
+        .map(|(email, error_message, smtp_code, failed_at)| {
+            let error_message = error_message.unwrap_or_default();
+            let class = crate::email::classify_send_error(&error_message);
+            *class_counts.entry(class).or_insert(0) += 1;
+            (email, error_message, smtp_code, failed_at, class)
+        })
+        .collect();
+
+    let groups: Vec<serde_json::Value> = crate::email::SendErrorClass::ALL
+        .into_iter()
+        .filter_map(|class| {
+            class_counts.get(&class).map(|count| {
+                serde_json::json!({
+                    "key": class.key(),
+                    "label": class.label(),
+                    "count": count,
+                })
+            })
+        })
+        .collect();
+
+    let filtered: Vec<_> = classified
+        .into_iter()
+        .filter(|(_, _, _, _, class)| selected_class.is_none_or(|c| c == *class))
+        .collect();
+
+    let per_page: usize = 50;
+    let total = filtered.len();
+    let total_pages = total.div_ceil(per_page).max(1);
+    let page = usize::try_from(query.page.unwrap_or(1).max(1))
+        .unwrap_or(1)
+        .min(total_pages);
+
+    let failures: Vec<serde_json::Value> = filtered
         .into_iter()
-        .map(|(url, clicks)| {
-            let text = link_text_map.get(&url).cloned().unwrap_or_default();
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .map(|(email, error_message, smtp_code, failed_at, class)| {
             serde_json::json!({
-                "url": url,
-                "text": text,
-                "clicks": clicks,
+                "email": super::admin::mask_email(&email),
+                "error_message": error_message,
+                "smtp_code": smtp_code.map(|c| c.to_string()).unwrap_or_default(),
+                "failed_at": failed_at.to_rfc3339(),
+                "class": class.key(),
+                "class_label": class.label(),
             })
         })
         .collect();
 
-    let total_clicks: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM email_events WHERE topic = $1 AND event_type = 'click'",
-    )
-    .bind(&slug)
-    .fetch_one(&state.db)
-    .await?;
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("newsletter_id", &id.to_string());
+    ctx.insert("title", &title);
+    ctx.insert("failures", &failures);
+    ctx.insert("groups", &groups);
+    ctx.insert("total", &total);
+    ctx.insert("page", &page);
+    ctx.insert("total_pages", &total_pages);
+    ctx.insert("search", &query.search.unwrap_or_default());
+    ctx.insert(
+        "class_filter",
+        &selected_class
+            .map(crate::email::SendErrorClass::key)
+            .unwrap_or_default(),
+    );
+    let html = state.tera.render("admin/newsletter_failures.html", &ctx)?;
+    Ok(Html(html))
+}
 
-    let unique_clicks: i64 = sqlx::query_scalar(
-        "SELECT COUNT(DISTINCT ucode) FROM email_events WHERE topic = $1 AND event_type = 'click'",
-    )
-    .bind(&slug)
-    .fetch_one(&state.db)
-    .await?;
+// --- Audience snapshot ---
 
-    let open_rate = if sent_count > 0 {
-        #[allow(clippy::cast_precision_loss)]
-        let rate = (unique_opens as f64 / f64::from(sent_count)) * 100.0;
-        format!("{rate:.1}%")
-    } else {
-        "—".to_string()
-    };
+#[derive(Deserialize)]
+pub struct AudienceQuery {
+    pub email: Option<String>,
+}
 
-    let unsubscribe_count: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM unsubscribe_events WHERE newsletter_id = $1")
-            .bind(id)
-            .fetch_one(&state.db)
-            .await?;
+/// Who the newsletter was addressed to as its send started, with a lookup
+/// of one address.
+pub async fn audience(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+    Query(query): Query<AudienceQuery>,
+) -> Result<Html<String>, AppError> {
+    let title = sqlx::query_scalar::<_, String>("SELECT title FROM newsletters WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let (total, recorded_at) = crate::audience::summary(&state.db, id).await?;
+
+    let email = query
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let lookup = match email {
+        Some(email) => Some(serde_json::json!({
+            "email": email,
+            "member": crate::audience::find(&state.db, id, email).await?,
+        })),
+        None => None,
+    };
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
     ctx.insert("newsletter_id", &id.to_string());
     ctx.insert("title", &title);
-    ctx.insert("status", &status);
-    ctx.insert("sent_count", &sent_count);
-    ctx.insert("failed_count", &failed_count);
-    ctx.insert("total_count", &total_count);
-    ctx.insert("unique_opens", &unique_opens);
-    ctx.insert("open_rate", &open_rate);
-    ctx.insert("total_clicks", &total_clicks);
-    ctx.insert("unique_clicks", &unique_clicks);
-    ctx.insert("unsubscribe_count", &unsubscribe_count);
-    ctx.insert("links", &link_list);
-    let html = state.tera.render("admin/newsletter_stats.html", &ctx)?;
+    ctx.insert("total", &total);
+    ctx.insert("recorded_at", &recorded_at.map(|t| t.to_rfc3339()));
+    ctx.insert("lookup", &lookup);
+    let html = state.tera.render("admin/newsletter_audience.html", &ctx)?;
     Ok(Html(html))
 }
 
+pub async fn audience_csv(
+    State(state): State<AppState>,
+    AdminUser(_admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Response, AppError> {
+    let slug = sqlx::query_scalar::<_, String>("SELECT slug FROM newsletters WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let csv_data = crate::audience::export_csv(&state.db, id)
+        .await
+        .map_err(AppError::Internal)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"audience-{slug}.csv\""),
+            ),
+        ],
+        csv_data,
+    )
+        .into_response())
+}
+
 // --- Delete ---
 
 pub async fn delete(
@@ -673,3 +2987,443 @@ pub async fn delete(
 
     Ok(Redirect::to("/admin/newsletters"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_approval() {
+        assert!(!needs_approval(50_000, 0));
+        assert!(!needs_approval(1000, 1000));
+        assert!(needs_approval(1001, 1000));
+    }
+
+    #[test]
+    fn test_list_order_by_default() {
+        let (sort, dir, sql) = list_order_by(None, None);
+        assert_eq!((sort, dir), ("created_at", "desc"));
+        assert_eq!(sql, "created_at DESC NULLS LAST, created_at DESC");
+    }
+
+    #[test]
+    fn test_list_order_by_whitelist() {
+        let (sort, dir, sql) = list_order_by(Some("sent_at"), Some("asc"));
+        assert_eq!((sort, dir), ("sent_at", "asc"));
+        assert!(sql.starts_with("sending_completed_at ASC NULLS FIRST"));
+
+        // Unknown columns and directions fall back to the defaults
+        let (sort, dir, _) = list_order_by(Some("id; DROP TABLE newsletters"), Some("sideways"));
+        assert_eq!((sort, dir), ("created_at", "desc"));
+    }
+
+    #[test]
+    fn test_list_filter_query_string() {
+        let query = ListQuery {
+            page: Some(3),
+            search: Some("年會 2025".to_string()),
+            status: Some("sent".to_string()),
+            creator: Some(String::new()),
+            sort: Some("title".to_string()),
+            ..ListQuery::default()
+        };
+        assert_eq!(
+            list_filter_query_string(&query),
+            "search=%E5%B9%B4%E6%9C%83%202025&status=sent"
+        );
+        assert_eq!(list_filter_query_string(&ListQuery::default()), "");
+    }
+
+    #[test]
+    fn test_parse_date_param() {
+        assert_eq!(
+            parse_date_param(Some("2025-08-09")),
+            chrono::NaiveDate::from_ymd_opt(2025, 8, 9)
+        );
+        assert_eq!(parse_date_param(Some("")), None);
+        assert_eq!(parse_date_param(Some("09/08/2025")), None);
+        assert_eq!(parse_date_param(None), None);
+    }
+
+    #[test]
+    fn test_parse_test_recipients() {
+        assert_eq!(
+            parse_test_recipients(
+                " A@coscup.org,\r\nb@coscup.org a@coscup.org ",
+                "me@coscup.org"
+            )
+            .unwrap(),
+            ["a@coscup.org", "b@coscup.org"]
+        );
+        assert_eq!(
+            parse_test_recipients("", "me@coscup.org").unwrap(),
+            ["me@coscup.org"]
+        );
+        assert!(parse_test_recipients("not-an-address", "me@coscup.org").is_err());
+        let many: Vec<String> = (0..11).map(|i| format!("r{i}@coscup.org")).collect();
+        assert!(parse_test_recipients(&many.join(","), "me@coscup.org").is_err());
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_open_comments_hold_back_approval(db: sqlx::PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.postal_address = Some("臺北市南港區研究院路二段 128 號".to_string()))
+            .build();
+        app.migrate().await;
+        app.login_as("admin@coscup.org").await;
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content, status, scheduled_at, \
+             approval_requested_by) \
+             VALUES ('大會快訊', 'news', '內容', 'pending_approval', NOW() + INTERVAL '1 day', \
+             'editor@coscup.org') RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let status = || async {
+            sqlx::query_scalar::<_, String>("SELECT status FROM newsletters WHERE id = $1")
+                .bind(id)
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap()
+        };
+
+        let response = app
+            .post_form(
+                &format!("/admin/newsletters/{id}/comments"),
+                &[("body", " 請補上報名連結 ")],
+            )
+            .await;
+        assert_eq!(
+            response.location(),
+            Some(format!("/admin/newsletters/{id}#comments").as_str())
+        );
+        let comment_id: uuid::Uuid = sqlx::query_scalar(
+            "SELECT id FROM newsletter_comments WHERE newsletter_id = $1 AND body = '請補上報名連結'",
+        )
+        .bind(id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let page = app.get(&format!("/admin/newsletters/{id}")).await;
+        assert!(page.body.contains("請補上報名連結"));
+
+        let approve = format!("/admin/newsletters/{id}/approve");
+        let response = app.post_form(&approve, &[]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert!(response.body.contains("尚有未解決的留言"));
+        assert_eq!(status().await, "pending_approval");
+
+        let response = app
+            .post_form(
+                &format!("/admin/newsletters/{id}/comments/{comment_id}/resolve"),
+                &[],
+            )
+            .await;
+        assert!(response.location().is_some());
+        let resolved_by: Option<String> =
+            sqlx::query_scalar("SELECT resolved_by FROM newsletter_comments WHERE id = $1")
+                .bind(comment_id)
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(resolved_by.as_deref(), Some("admin@coscup.org"));
+
+        let response = app.post_form(&approve, &[]).await;
+        assert!(response.location().is_some());
+        assert_eq!(status().await, "scheduled");
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_cancel_with_and_without_a_body(db: sqlx::PgPool) {
+        use axum::body::Body;
+        use axum::http::Request;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let mut ids = Vec::new();
+        for (slug, status) in [("scheduled", "scheduled"), ("pending", "pending_approval")] {
+            let id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO newsletters (title, slug, markdown_content, status, scheduled_at) \
+                 VALUES ($1, $1, '內容', $2, NOW() + INTERVAL '1 day') RETURNING id",
+            )
+            .bind(slug)
+            .bind(status)
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        // A bare POST, as scripts send it, still cancels
+        let response = app
+            .send(
+                Request::post(format!("/admin/newsletters/{}/cancel", ids[0]))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        assert!(response.location().is_some(), "{}", response.status);
+
+        let response = app
+            .post_form(
+                &format!("/admin/newsletters/{}/cancel", ids[1]),
+                &[("comment", "標題有錯字")],
+            )
+            .await;
+        assert!(response.location().is_some());
+        let statuses: Vec<String> =
+            sqlx::query_scalar("SELECT status FROM newsletters WHERE id = ANY($1) ORDER BY slug")
+                .bind(&ids)
+                .fetch_all(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(statuses, ["draft", "draft"]);
+        let comments: Vec<(uuid::Uuid, String)> =
+            sqlx::query_as("SELECT newsletter_id, body FROM newsletter_comments")
+                .fetch_all(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(comments, [(ids[1], "標題有錯字".to_string())]);
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_preview_source(db: sqlx::PgPool) {
+        use axum::http::StatusCode;
+
+        // A real SMTP service formats the message; building it connects nowhere
+        let service = crate::email::SmtpEmailService::new(
+            "localhost",
+            2525,
+            None,
+            None,
+            false,
+            "news@coscup.org".to_string(),
+        )
+        .unwrap();
+        let mut app = crate::test_utils::TestStateBuilder::new(db)
+            .identities(vec![crate::email::SendingIdentity {
+                name: "bulk".to_string(),
+                from_email: "news@coscup.org".to_string(),
+                service: std::sync::Arc::new(service),
+            }])
+            .build();
+        app.migrate().await;
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content, sending_identity) \
+             VALUES ('八月電子報', 'aug', '內容', 'bulk') RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let uri = format!("/admin/newsletters/{id}/preview/source");
+
+        // Without an admin session the source is not shown
+        let response = app.get(&uri).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert!(!response.body.contains("List-Unsubscribe"));
+
+        app.login_as("admin@coscup.org").await;
+        let response = app.get(&uri).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.body.contains("From: news@coscup.org"));
+        assert!(response.body.contains("List-Unsubscribe: &lt;"));
+        assert!(response
+            .body
+            .contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+
+        let response = app.get(&format!("{uri}?download=1")).await;
+        assert_eq!(response.headers[header::CONTENT_TYPE], "message/rfc822");
+        assert!(response.body.contains("\r\nList-Unsubscribe: <"));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_render_preview(db: sqlx::PgPool) {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let render = |body: serde_json::Value| {
+            Request::post("/admin/render-preview")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .send(render(serde_json::json!({
+                "markdown": "# 嗨 %recipient_name%\n\n<script>alert(1)</script>\n\n**議程**公開",
+                "title": "八月電子報",
+                "lang": "en",
+            })))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        let content = json["content_html"].as_str().unwrap();
+        assert!(content.contains("<strong>議程</strong>"));
+        assert!(!content.contains("<script>"));
+        let html = json["html"].as_str().unwrap();
+        assert!(html.contains("<strong>議程</strong>"));
+        assert!(html.contains("嗨 王小明"));
+        assert!(html.contains("八月電子報"));
+        assert!(html.contains("lang=\"en\""));
+
+        let broken: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletter_templates (name, slug, description, html_body) \
+             VALUES ('壞掉', 'broken', '', '{{ content | no_such_filter }}') RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let response = app
+            .send(render(serde_json::json!({
+                "markdown": "內容",
+                "template_id": broken,
+            })))
+            .await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert!(response.body.contains("模板無法轉譯"));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_scheduled_edits(db: sqlx::PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let db = app.state.db.clone();
+        let scheduled = |slug: &'static str, due: &'static str| {
+            let db = db.clone();
+            async move {
+                sqlx::query_scalar::<_, uuid::Uuid>(&format!(
+                    "INSERT INTO newsletters (title, slug, markdown_content, status, scheduled_at) \
+                     VALUES ('舊標題', '{slug}', '舊內容', 'scheduled', NOW() + INTERVAL '{due}') \
+                     RETURNING id"
+                ))
+                .fetch_one(&db)
+                .await
+                .unwrap()
+            }
+        };
+        let edit = |id: uuid::Uuid| {
+            let app = &app;
+            async move {
+                app.post_form(
+                    &format!("/admin/newsletters/{id}"),
+                    &[("title", " 新標題 "), ("markdown_content", "新內容")],
+                )
+                .await
+            }
+        };
+        let title_and_content = |id: uuid::Uuid| {
+            let db = db.clone();
+            async move {
+                sqlx::query_as::<_, (String, String)>(
+                    "SELECT title, markdown_content FROM newsletters WHERE id = $1",
+                )
+                .bind(id)
+                .fetch_one(&db)
+                .await
+                .unwrap()
+            }
+        };
+        let revisions = |id: uuid::Uuid| {
+            let db = db.clone();
+            async move {
+                sqlx::query_as::<_, (String, String, String)>(
+                    "SELECT title, markdown_content, edited_by FROM newsletter_revisions \
+                     WHERE newsletter_id = $1",
+                )
+                .bind(id)
+                .fetch_all(&db)
+                .await
+                .unwrap()
+            }
+        };
+        let old = ("舊標題".to_string(), "舊內容".to_string());
+
+        // Well ahead of the send: saved, with the old version kept
+        let id = scheduled("ahead", "1 day").await;
+        let response = edit(id).await;
+        assert_eq!(
+            response.location(),
+            Some(format!("/admin/newsletters/{id}").as_str())
+        );
+        assert_eq!(
+            title_and_content(id).await,
+            ("新標題".to_string(), "新內容".to_string())
+        );
+        assert_eq!(
+            revisions(id).await,
+            [(old.0.clone(), old.1.clone(), "admin@coscup.org".to_string())]
+        );
+
+        // Inside the lock window before the send
+        let due = scheduled("due", "30 seconds").await;
+        let response = edit(due).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert!(response.body.contains("即將開始發送"));
+
+        // Approved, or partly sent by local time
+        let approved = scheduled("approved", "1 day").await;
+        let partly_sent = scheduled("partly-sent", "1 day").await;
+        sqlx::query("UPDATE newsletters SET approved_by = 'boss@coscup.org' WHERE id = $1")
+            .bind(approved)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE newsletters SET local_delivery = TRUE, local_release_at = NOW() + INTERVAL '1 hour' \
+             WHERE id = $1",
+        )
+        .bind(partly_sent)
+        .execute(&db)
+        .await
+        .unwrap();
+        for id in [due, approved, partly_sent] {
+            let response = edit(id).await;
+            assert_eq!(response.status, StatusCode::BAD_REQUEST);
+            assert_eq!(title_and_content(id).await, old);
+            assert!(revisions(id).await.is_empty());
+        }
+
+        // An edition saves its own content, but the parent's schedule decides
+        let parent = scheduled("parent", "1 day").await;
+        let edition: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletters (parent_id, title, slug, markdown_content, lang) \
+             VALUES ($1, '舊標題', 'parent-en', '舊內容', 'en') RETURNING id",
+        )
+        .bind(parent)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert!(edit(edition).await.location().is_some());
+        assert_eq!(
+            title_and_content(edition).await,
+            ("新標題".to_string(), "新內容".to_string())
+        );
+        assert_eq!(title_and_content(parent).await, old);
+        assert_eq!(revisions(edition).await.len(), 1);
+        assert!(revisions(parent).await.is_empty());
+
+        sqlx::query(
+            "UPDATE newsletters SET scheduled_at = NOW() + INTERVAL '30 seconds' WHERE id = $1",
+        )
+        .bind(parent)
+        .execute(&db)
+        .await
+        .unwrap();
+        let response = edit(edition).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert!(response.body.contains("即將開始發送"));
+        assert_eq!(revisions(edition).await.len(), 1);
+    }
+}
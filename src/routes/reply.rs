@@ -0,0 +1,164 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::HeaderMap;
+use axum::response::{Html, Redirect};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::AppState;
+
+// --- Reply inbox ---
+
+#[derive(Deserialize)]
+pub struct InboxQuery {
+    pub page: Option<i64>,
+    /// `all` to include replies already handled
+    pub show: Option<String>,
+    pub newsletter_id: Option<String>,
+}
+
+pub async fn inbox(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Query(query): Query<InboxQuery>,
+) -> Result<Html<String>, AppError> {
+    #[allow(clippy::type_complexity)]
+    type ReplyRow = (
+        uuid::Uuid,
+        String,
+        String,
+        String,
+        Option<uuid::Uuid>,
+        Option<String>,
+        Option<uuid::Uuid>,
+        Option<String>,
+        Option<chrono::DateTime<Utc>>,
+        chrono::DateTime<Utc>,
+    );
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page: i64 = 50;
+    let offset = (page - 1) * per_page;
+    let show_all = query.show.as_deref() == Some("all");
+    let newsletter_filter = query
+        .newsletter_id
+        .as_deref()
+        .and_then(|id| uuid::Uuid::parse_str(id).ok());
+
+    let rows = sqlx::query_as::<_, ReplyRow>(
+        "SELECT r.id, r.from_email, r.subject, r.body_text, r.newsletter_id, n.title, \
+                r.subscriber_id, r.handled_by, r.handled_at, r.received_at \
+         FROM newsletter_replies r LEFT JOIN newsletters n ON n.id = r.newsletter_id \
+         WHERE ($1 OR r.handled_at IS NULL) AND ($2::UUID IS NULL OR r.newsletter_id = $2) \
+         ORDER BY r.received_at DESC LIMIT $3 OFFSET $4",
+    )
+    .bind(show_all)
+    .bind(newsletter_filter)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM newsletter_replies \
+         WHERE ($1 OR handled_at IS NULL) AND ($2::UUID IS NULL OR newsletter_id = $2)",
+    )
+    .bind(show_all)
+    .bind(newsletter_filter)
+    .fetch_one(&state.db)
+    .await?;
+
+    let total_pages = (total + per_page - 1) / per_page;
+    let replies: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(
+            |(
+                id,
+                from_email,
+                subject,
+                body_text,
+                newsletter_id,
+                newsletter_title,
+                subscriber_id,
+                handled_by,
+                handled_at,
+                received_at,
+            )| {
+                serde_json::json!({
+                    "id": id.to_string(),
+                    "from_email": from_email,
+                    "subject": subject,
+                    "body": body_text,
+                    "newsletter_id": newsletter_id.map(|id| id.to_string()).unwrap_or_default(),
+                    "newsletter_title": newsletter_title.unwrap_or_default(),
+                    "subscriber_id": subscriber_id.map(|id| id.to_string()).unwrap_or_default(),
+                    "handled_by": handled_by.unwrap_or_default(),
+                    "handled_at": handled_at.map(|t| t.to_rfc3339()),
+                    "received_at": received_at.to_rfc3339(),
+                })
+            },
+        )
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("replies", &replies);
+    ctx.insert("page", &page);
+    ctx.insert("total_pages", &total_pages);
+    ctx.insert("total", &total);
+    ctx.insert("show_all", &show_all);
+    ctx.insert(
+        "newsletter_filter",
+        &newsletter_filter
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+    );
+    let html = state.tera.render("admin/replies.html", &ctx)?;
+    Ok(Html(html))
+}
+
+/// Mark a reply handled, or back to open if it already was.
+pub async fn toggle_handled(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let handled = sqlx::query_scalar::<_, bool>(
+        "UPDATE newsletter_replies SET \
+         handled_by = CASE WHEN handled_at IS NULL THEN $1 END, \
+         handled_at = CASE WHEN handled_at IS NULL THEN NOW() END \
+         WHERE id = $2 \
+         RETURNING handled_at IS NOT NULL",
+    )
+    .bind(&admin_email)
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "reply.handled",
+        Some(serde_json::json!({
+            "reply_id": id.to_string(),
+            "handled": handled,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/replies"))
+}
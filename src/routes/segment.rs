@@ -0,0 +1,605 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::HeaderMap;
+use axum::response::{Html, Redirect};
+use axum::Form;
+use serde::Deserialize;
+
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::segment::{self, Engagement, SegmentFilter, StatusFilter};
+use crate::tags::{self, TagRule};
+use crate::AppState;
+
+// --- List ---
+
+pub async fn list(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    render_list(&state, &admin_email, None).await
+}
+
+/// The segments page, with the tags and tag rules below. `notice` reports
+/// the outcome of a bulk tagging.
+async fn render_list(
+    state: &AppState,
+    admin_email: &str,
+    notice: Option<serde_json::Value>,
+) -> Result<Html<String>, AppError> {
+    let rows =
+        sqlx::query_as::<_, (uuid::Uuid, String, serde_json::Value, Option<String>)>(&format!(
+            "SELECT id, name, definition, {} FROM segments ORDER BY name",
+            crate::admin_profile::display_name_sql("created_by")
+        ))
+        .fetch_all(&state.db)
+        .await?;
+
+    let mut segments = Vec::with_capacity(rows.len());
+    for (id, name, definition, created_by) in rows {
+        let filter: SegmentFilter = serde_json::from_value(definition).unwrap_or_default();
+        let matching = segment::count_matching(&state.db, &filter, false).await?;
+        let recipients = segment::count_matching(&state.db, &filter, true).await?;
+        segments.push(serde_json::json!({
+            "id": id.to_string(),
+            "name": name,
+            "conditions": filter.describe(),
+            "matching": matching,
+            "recipients": recipients,
+            "created_by": created_by.unwrap_or_default(),
+        }));
+    }
+
+    let rule_kinds: Vec<serde_json::Value> = TagRule::KINDS
+        .into_iter()
+        .map(|(key, label)| serde_json::json!({ "key": key, "label": label }))
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, admin_email)
+            .await
+            .name(),
+    );
+    ctx.insert("segments", &segments);
+    ctx.insert("tags", &tags::list(&state.db).await?);
+    ctx.insert("rules", &tags::list_rules(&state.db).await?);
+    ctx.insert("rule_kinds", &rule_kinds);
+    ctx.insert("max_rule_newsletters", &tags::MAX_RULE_NEWSLETTERS);
+    ctx.insert("notice", &notice);
+    let html = state.tera.render("admin/segments.html", &ctx)?;
+    Ok(Html(html))
+}
+
+// --- Form ---
+
+#[derive(Deserialize, Default)]
+pub struct SegmentForm {
+    pub name: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub verified: String,
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub engagement: String,
+    #[serde(default)]
+    pub signup_from: String,
+    #[serde(default)]
+    pub signup_to: String,
+}
+
+/// Convert the HTML form into a filter; empty or unknown values mean "any".
+fn filter_from_form(form: &SegmentForm) -> SegmentFilter {
+    let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    let date = |s: &str| chrono::NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok();
+    SegmentFilter {
+        status: match form.status.as_str() {
+            "active" => Some(StatusFilter::Active),
+            "inactive" => Some(StatusFilter::Inactive),
+            _ => None,
+        },
+        verified: match form.verified.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        },
+        tag: non_empty(&form.tag),
+        source: non_empty(&form.source),
+        engagement: Engagement::from_key(&form.engagement),
+        signup_from: date(&form.signup_from),
+        signup_to: date(&form.signup_to),
+    }
+}
+
+/// Render the create/edit form. `segment` is null when creating.
+async fn render_form(
+    state: &AppState,
+    admin_email: &str,
+    segment: serde_json::Value,
+) -> Result<Html<String>, AppError> {
+    let tags = sqlx::query_scalar::<_, String>("SELECT name FROM tags ORDER BY name")
+        .fetch_all(&state.db)
+        .await?;
+    let sources = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT subscription_source FROM subscribers \
+         WHERE subscription_source IS NOT NULL ORDER BY subscription_source",
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let engagements: Vec<serde_json::Value> = Engagement::ALL
+        .into_iter()
+        .map(|e| serde_json::json!({ "key": e.key(), "label": e.label() }))
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", admin_email);
+    ctx.insert("segment", &segment);
+    ctx.insert("tags", &tags);
+    ctx.insert("sources", &sources);
+    ctx.insert("engagements", &engagements);
+    let html = state.tera.render("admin/segment_edit.html", &ctx)?;
+    Ok(Html(html))
+}
+
+pub async fn new_form(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    render_form(&state, &admin_email, serde_json::json!(null)).await
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<SegmentForm>,
+) -> Result<Redirect, AppError> {
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("Name is required".to_string()));
+    }
+    let filter = filter_from_form(&form);
+    let definition =
+        serde_json::to_value(&filter).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO segments (name, definition, created_by) VALUES ($1, $2, $3) \
+         ON CONFLICT (name) DO NOTHING RETURNING id",
+    )
+    .bind(&name)
+    .bind(&definition)
+    .bind(&admin_email)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("A segment with this name already exists".to_string()))?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "segment.create",
+        Some(serde_json::json!({ "segment_id": id.to_string(), "name": name, "definition": definition })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/segments"))
+}
+
+// --- Edit ---
+
+pub async fn edit_form(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Html<String>, AppError> {
+    let (name, filter) = segment::load(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let seg = serde_json::json!({
+        "id": id.to_string(),
+        "name": name,
+        "status": match filter.status {
+            Some(StatusFilter::Active) => "active",
+            Some(StatusFilter::Inactive) => "inactive",
+            None => "",
+        },
+        "verified": filter.verified.map(|v| v.to_string()).unwrap_or_default(),
+        "tag": filter.tag.clone().unwrap_or_default(),
+        "source": filter.source.clone().unwrap_or_default(),
+        "engagement": filter.engagement.map(Engagement::key).unwrap_or_default(),
+        "signup_from": filter.signup_from.map(|d| d.to_string()).unwrap_or_default(),
+        "signup_to": filter.signup_to.map(|d| d.to_string()).unwrap_or_default(),
+    });
+    render_form(&state, &admin_email, seg).await
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<SegmentForm>,
+) -> Result<Redirect, AppError> {
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("Name is required".to_string()));
+    }
+    let filter = filter_from_form(&form);
+    let definition =
+        serde_json::to_value(&filter).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let result = sqlx::query(
+        "UPDATE segments SET name = $1, definition = $2, updated_at = NOW() WHERE id = $3",
+    )
+    .bind(&name)
+    .bind(&definition)
+    .bind(id)
+    .execute(&state.db)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => return Err(AppError::NotFound),
+        Ok(_) => {}
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Err(AppError::BadRequest(
+                "A segment with this name already exists".to_string(),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "segment.update",
+        Some(serde_json::json!({ "segment_id": id.to_string(), "name": name, "definition": definition })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/segments"))
+}
+
+// --- Delete ---
+
+pub async fn delete(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    // Deleting a segment targeted by an unsent newsletter would silently widen
+    // its audience to all subscribers, so refuse instead.
+    let in_use: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM newsletters WHERE segment_id = $1 AND status NOT IN ('sent', 'failed')",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    if in_use > 0 {
+        return Err(AppError::BadRequest(
+            "Segment is the send target of an unsent newsletter".to_string(),
+        ));
+    }
+
+    let name = sqlx::query_scalar::<_, String>("DELETE FROM segments WHERE id = $1 RETURNING name")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "segment.delete",
+        Some(serde_json::json!({ "segment_id": id.to_string(), "name": name })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/segments"))
+}
+
+// --- Tags ---
+
+#[derive(Deserialize)]
+pub struct TagForm {
+    pub name: String,
+    /// Addresses separated by whitespace, commas or semicolons
+    #[serde(default)]
+    pub emails: String,
+    /// `remove` takes the tag off instead
+    #[serde(default)]
+    pub action: String,
+}
+
+/// Add a tag to (or remove it from) a pasted list of subscribers, creating
+/// the tag if needed.
+pub async fn tag_emails(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<TagForm>,
+) -> Result<Html<String>, AppError> {
+    let name = tags::clean_name(&form.name).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Tag name must be 1 to {} characters",
+            tags::MAX_NAME_CHARS
+        ))
+    })?;
+    let remove = form.action == "remove";
+    let emails = tags::split_emails(&form.emails);
+    let tag_id = tags::ensure_tag(&state.db, &name).await?;
+    let (changed, not_found) = tags::set_by_email(&state.db, tag_id, &emails, remove).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        if remove { "tag.remove" } else { "tag.add" },
+        Some(serde_json::json!({
+            "tag": name,
+            "emails": emails.len(),
+            "changed": changed,
+            "not_found": not_found.len(),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    let notice = serde_json::json!({
+        "tag": name,
+        "remove": remove,
+        "changed": changed,
+        "not_found": not_found,
+    });
+    render_list(&state, &admin_email, Some(notice)).await
+}
+
+pub async fn delete_tag(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    // Same as segments: an unsent newsletter limited to the tag would go to everyone
+    let in_use: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM newsletters WHERE tag_id = $1 AND status NOT IN ('sent', 'failed')",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    if in_use > 0 {
+        return Err(AppError::BadRequest(
+            "Tag is the send target of an unsent newsletter".to_string(),
+        ));
+    }
+
+    let name = sqlx::query_scalar::<_, String>("DELETE FROM tags WHERE id = $1 RETURNING name")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "tag.delete",
+        Some(serde_json::json!({ "tag_id": id.to_string(), "name": name })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/segments"))
+}
+
+// --- Tag rules ---
+
+#[derive(Deserialize)]
+pub struct RuleForm {
+    pub tag: String,
+    pub kind: String,
+    pub newsletters: u32,
+}
+
+/// Save a tag rule and apply it right away.
+pub async fn create_rule(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<RuleForm>,
+) -> Result<Redirect, AppError> {
+    let tag = tags::clean_name(&form.tag).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Tag name must be 1 to {} characters",
+            tags::MAX_NAME_CHARS
+        ))
+    })?;
+    let rule = TagRule::from_form(&form.kind, form.newsletters).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Rules look back over 1 to {} newsletters",
+            tags::MAX_RULE_NEWSLETTERS
+        ))
+    })?;
+    let id = tags::create_rule(&state.db, &tag, rule, &admin_email).await?;
+    tags::apply_rule(&state.db, id).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "tag_rule.create",
+        Some(serde_json::json!({ "rule_id": id.to_string(), "tag": tag, "rule": rule })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/segments"))
+}
+
+pub async fn apply_rule(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    tags::apply_rule(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Redirect::to("/admin/segments"))
+}
+
+/// Delete a rule along with the tags it added.
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let rule = sqlx::query_scalar::<_, serde_json::Value>(
+        "DELETE FROM tag_rules WHERE id = $1 RETURNING rule",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "tag_rule.delete",
+        Some(serde_json::json!({ "rule_id": id.to_string(), "rule": rule })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/segments"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_from_empty_form() {
+        let form = SegmentForm {
+            name: "All".to_string(),
+            ..SegmentForm::default()
+        };
+        assert_eq!(filter_from_form(&form), SegmentFilter::default());
+    }
+
+    #[test]
+    fn test_filter_from_form() {
+        let form = SegmentForm {
+            name: "Engaged volunteers".to_string(),
+            status: "active".to_string(),
+            verified: "true".to_string(),
+            tag: " volunteer ".to_string(),
+            source: String::new(),
+            engagement: "engaged_90d".to_string(),
+            signup_from: "2025-01-01".to_string(),
+            signup_to: "not-a-date".to_string(),
+        };
+        assert_eq!(
+            filter_from_form(&form),
+            SegmentFilter {
+                status: Some(StatusFilter::Active),
+                verified: Some(true),
+                tag: Some("volunteer".to_string()),
+                source: None,
+                engagement: Some(Engagement::Engaged90d),
+                signup_from: chrono::NaiveDate::from_ymd_opt(2025, 1, 1),
+                signup_to: None,
+            }
+        );
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_tagging_from_admin_pages(db: sqlx::PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.org', 's', 'u1', true, true) RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        let response = app
+            .post_form(
+                "/admin/segments/tags",
+                &[
+                    ("name", " speaker "),
+                    ("emails", "A@example.org\nnobody@example.org"),
+                    ("action", "add"),
+                ],
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.body.contains("加到 1 位訂閱者"));
+        assert!(response.body.contains("nobody@example.org"));
+
+        let detail = format!("/admin/subscribers/{id}");
+        let tags_url = format!("{detail}/tags");
+        app.post_form(&tags_url, &[("name", "volunteer")]).await;
+        assert!(app.get(&detail).await.body.contains("volunteer"));
+        app.post_form(&tags_url, &[("name", "speaker"), ("action", "remove")])
+            .await;
+        let tags: Vec<String> = sqlx::query_scalar(
+            "SELECT t.name FROM subscriber_tags st JOIN tags t ON t.id = st.tag_id",
+        )
+        .fetch_all(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(tags, ["volunteer"]);
+
+        let response = app
+            .post_form(
+                "/admin/segments/rules",
+                &[
+                    ("tag", "loyal"),
+                    ("kind", "opened_all"),
+                    ("newsletters", "3"),
+                ],
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::SEE_OTHER);
+        let page = app.get("/admin/segments").await;
+        assert!(page.body.contains("最近 3 期電子報每一期都有開信"));
+        let bad = app
+            .post_form(
+                "/admin/segments/rules",
+                &[
+                    ("tag", "loyal"),
+                    ("kind", "opened_all"),
+                    ("newsletters", "0"),
+                ],
+            )
+            .await;
+        assert_eq!(bad.status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,262 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::HeaderMap;
+use axum::response::{Html, Redirect};
+use axum::Form;
+use serde::Deserialize;
+
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::series;
+use crate::AppState;
+
+// --- List ---
+
+pub async fn list(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    let all = series::list(&state.db).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("series", &all);
+    let html = state.tera.render("admin/series.html", &ctx)?;
+    Ok(Html(html))
+}
+
+// --- Form ---
+
+#[derive(Deserialize)]
+pub struct SeriesForm {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Template new issues start from; empty means none
+    pub default_template_id: Option<String>,
+}
+
+async fn template_options(state: &AppState) -> Result<Vec<serde_json::Value>, AppError> {
+    let templates = sqlx::query_as::<_, (uuid::Uuid, String)>(
+        "SELECT id, name FROM newsletter_templates ORDER BY name",
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(templates
+        .into_iter()
+        .map(|(id, name)| serde_json::json!({ "id": id.to_string(), "name": name }))
+        .collect())
+}
+
+/// Render the create/edit form. `series` is null when creating; `series_stats` are
+/// only shown when editing.
+async fn render_form(
+    state: &AppState,
+    admin_email: &str,
+    series: &serde_json::Value,
+    series_stats: Option<&crate::stats::SeriesStats>,
+) -> Result<Html<String>, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", admin_email);
+    ctx.insert("series", series);
+    ctx.insert("templates", &template_options(state).await?);
+    if let Some(stats) = series_stats {
+        let issues: Vec<serde_json::Value> = stats
+            .issues
+            .iter()
+            .map(|i| {
+                serde_json::json!({
+                    "id": i.id.to_string(),
+                    "title": i.title,
+                    "status": i.status,
+                    "sent_at": i.sent_at.map(|t| t.to_rfc3339()),
+                    "sent_count": i.sent_count,
+                    "unique_opens": i.unique_opens,
+                    "open_rate": crate::stats::format_rate(i.open_rate),
+                    "unique_clicks": i.unique_clicks,
+                    "click_through_rate": crate::stats::format_rate(i.click_through_rate),
+                    "unsubscribe_count": i.unsubscribe_count,
+                })
+            })
+            .collect();
+        ctx.insert(
+            "stats",
+            &serde_json::json!({
+                "sent_count": stats.sent_count,
+                "unique_opens": stats.unique_opens,
+                "open_rate": crate::stats::format_rate(stats.open_rate),
+                "unique_clicks": stats.unique_clicks,
+                "click_through_rate": crate::stats::format_rate(stats.click_through_rate),
+                "unsubscribe_count": stats.unsubscribe_count,
+                "recipients": stats.recipients,
+                "readers": stats.readers,
+                "issues": issues,
+            }),
+        );
+        let tz = crate::timezone::for_admin(&state.db, admin_email).await;
+        ctx.insert("admin_tz", tz.name());
+    }
+    let html = state.tera.render("admin/series_edit.html", &ctx)?;
+    Ok(Html(html))
+}
+
+/// Map a failed insert or update to the error shown to the admin.
+fn save_error(e: sqlx::Error) -> AppError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            AppError::BadRequest("已有同名的系列".to_string())
+        }
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+            AppError::BadRequest("找不到所選的模板".to_string())
+        }
+        e => e.into(),
+    }
+}
+
+fn parse_template_id(form: &SeriesForm) -> Option<uuid::Uuid> {
+    form.default_template_id
+        .as_deref()
+        .filter(|s| !s.is_empty())
+        .and_then(|s| s.parse().ok())
+}
+
+pub async fn new_form(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    render_form(&state, &admin_email, &serde_json::json!(null), None).await
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<SeriesForm>,
+) -> Result<Redirect, AppError> {
+    let (name, description) =
+        series::validate(&form.name, &form.description).map_err(AppError::BadRequest)?;
+    let default_template_id = parse_template_id(&form);
+
+    let id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletter_series (name, description, default_template_id, created_by) \
+         VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(&name)
+    .bind(&description)
+    .bind(default_template_id)
+    .bind(&admin_email)
+    .fetch_one(&state.db)
+    .await
+    .map_err(save_error)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "series.create",
+        Some(serde_json::json!({
+            "series_id": id.to_string(),
+            "name": name,
+            "default_template_id": default_template_id.map(|t| t.to_string()),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/series/{id}")))
+}
+
+// --- Edit ---
+
+pub async fn edit_form(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Html<String>, AppError> {
+    let found = series::find(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let series_stats = crate::stats::series(&state.db, id).await?;
+    let series = serde_json::to_value(&found).map_err(|e| AppError::Internal(e.to_string()))?;
+    render_form(&state, &admin_email, &series, Some(&series_stats)).await
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<SeriesForm>,
+) -> Result<Redirect, AppError> {
+    let (name, description) =
+        series::validate(&form.name, &form.description).map_err(AppError::BadRequest)?;
+    let default_template_id = parse_template_id(&form);
+
+    let result = sqlx::query(
+        "UPDATE newsletter_series SET name = $1, description = $2, default_template_id = $3, \
+         updated_at = NOW() WHERE id = $4",
+    )
+    .bind(&name)
+    .bind(&description)
+    .bind(default_template_id)
+    .bind(id)
+    .execute(&state.db)
+    .await
+    .map_err(save_error)?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    // The archive shows series names
+    state.archive_cache.invalidate();
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "series.update",
+        Some(serde_json::json!({
+            "series_id": id.to_string(),
+            "name": name,
+            "default_template_id": default_template_id.map(|t| t.to_string()),
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/series/{id}")))
+}
+
+// --- Delete ---
+
+pub async fn delete(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    // Its newsletters stay, just no longer in a series
+    let name = sqlx::query_scalar::<_, String>(
+        "DELETE FROM newsletter_series WHERE id = $1 RETURNING name",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    state.archive_cache.invalidate();
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "series.delete",
+        Some(serde_json::json!({ "series_id": id.to_string(), "name": name })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/series"))
+}
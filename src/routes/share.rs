@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::Form;
+use serde::Deserialize;
+
+use super::archive;
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::share_links;
+use crate::AppState;
+
+// --- Public view ---
+
+/// Public page: a newsletter opened through a share link, whatever its
+/// status. Rate limited per IP, marked `noindex` and never cached, so neither
+/// the archive nor search engines pick up an unsent issue.
+pub async fn view(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<Response, AppError> {
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr)).to_string();
+    if !state
+        .rate_limiter
+        .check("share_ip", &client_ip, state.live.get().rate_limit_share_ip)
+        .await?
+    {
+        return Err(AppError::RateLimitExceeded);
+    }
+
+    let opened = share_links::open(&state.db, &token).await?;
+    let row = match opened {
+        Some((newsletter_id, expires_at)) => sqlx::query_as::<_, archive::WebVersionRow>(&format!(
+            "{} WHERE n.id = $1",
+            archive::WEB_VERSION_SQL
+        ))
+        .bind(newsletter_id)
+        .fetch_optional(&state.db)
+        .await?
+        .map(|row| (archive::WebVersion::from(row), expires_at)),
+        None => None,
+    };
+    let Some((web_version, expires_at)) = row else {
+        let mut ctx = tera::Context::new();
+        ctx.insert("title", "分享連結已失效");
+        ctx.insert(
+            "message",
+            "此分享連結無效、已過期或已被撤銷，請向提供連結的人索取新的連結。",
+        );
+        let html = state.tera.render("error.html", &ctx)?;
+        return Ok((StatusCode::NOT_FOUND, Html(html)).into_response());
+    };
+
+    let web_url = format!("{}/share/{}", state.config.base_url, token);
+    let rendered = archive::render_web_version(&state, &web_version, &web_url).await?;
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("subject", &web_version.title);
+    ctx.insert("lang", &web_version.lang);
+    ctx.insert("dir", &web_version.dir);
+    ctx.insert("rendered_html", &rendered);
+    ctx.insert("expires_at", &expires_at.to_rfc3339());
+    let html = state.tera.render("newsletter_share.html", &ctx)?;
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+        ],
+        Html(html),
+    )
+        .into_response())
+}
+
+// --- Admin ---
+
+#[derive(Deserialize)]
+pub struct ShareLinkForm {
+    /// Who the link is for
+    #[serde(default)]
+    pub label: String,
+    pub ttl_days: Option<i64>,
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(newsletter_id): Path<uuid::Uuid>,
+    Form(form): Form<ShareLinkForm>,
+) -> Result<Redirect, AppError> {
+    let (label, ttl_days) =
+        share_links::validate(&form.label, form.ttl_days).map_err(AppError::BadRequest)?;
+    let exists = sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM newsletters WHERE id = $1")
+        .bind(newsletter_id)
+        .fetch_optional(&state.db)
+        .await?;
+    if exists.is_none() {
+        return Err(AppError::NotFound);
+    }
+
+    let (id, _) =
+        share_links::create(&state.db, newsletter_id, &label, ttl_days, &admin_email).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "newsletter.share_link_create",
+        Some(serde_json::json!({
+            "newsletter_id": newsletter_id.to_string(),
+            "share_link_id": id.to_string(),
+            "label": label,
+            "ttl_days": ttl_days,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!(
+        "/admin/newsletters/{newsletter_id}#share-links"
+    )))
+}
+
+pub async fn revoke(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((newsletter_id, id)): Path<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<Redirect, AppError> {
+    if share_links::revoke(&state.db, newsletter_id, id).await? {
+        let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+        crate::audit::log(
+            &state.db,
+            &admin_email,
+            "newsletter.share_link_revoke",
+            Some(serde_json::json!({
+                "newsletter_id": newsletter_id.to_string(),
+                "share_link_id": id.to_string(),
+            })),
+            Some(client_ip),
+        )
+        .await;
+    }
+
+    Ok(Redirect::to(&format!(
+        "/admin/newsletters/{newsletter_id}#share-links"
+    )))
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::HeaderMap;
+use axum::response::{Html, Redirect};
+use axum::Form;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::sponsors::{self, Sponsor, Tier};
+use crate::AppState;
+
+// --- Public redirect ---
+
+/// Where sponsor logos in newsletters link to. The click itself is counted
+/// by click tracking on the way here.
+pub async fn visit(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    let sponsor = sponsors::load(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Redirect::temporary(&sponsor.url))
+}
+
+// --- List ---
+
+fn sponsor_json(sponsor: &Sponsor, today: NaiveDate) -> serde_json::Value {
+    let status = if sponsor.is_active_on(today) {
+        "active"
+    } else if sponsor.starts_on.is_some_and(|d| d > today) {
+        "upcoming"
+    } else {
+        "ended"
+    };
+    serde_json::json!({
+        "id": sponsor.id.to_string(),
+        "name": sponsor.name,
+        "logo_url": sponsor.logo_url,
+        "url": sponsor.url,
+        "tier": sponsor.tier.as_str(),
+        "tier_label": sponsor.tier.label(),
+        "starts_on": sponsor.starts_on.map(|d| d.to_string()).unwrap_or_default(),
+        "ends_on": sponsor.ends_on.map(|d| d.to_string()).unwrap_or_default(),
+        "status": status,
+    })
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    let all = sponsors::all(&state.db).await?;
+    let report = sponsors::click_report(&state.db, &state.config.base_url).await?;
+    let mut totals: HashMap<uuid::Uuid, (i64, i64)> = HashMap::new();
+    for row in &report {
+        let total = totals.entry(row.sponsor_id).or_default();
+        total.0 += row.clicks;
+        total.1 += row.unique_clicks;
+    }
+
+    let today = crate::timezone::today();
+    let rows: Vec<serde_json::Value> = all
+        .iter()
+        .map(|s| {
+            let mut row = sponsor_json(s, today);
+            let (clicks, unique_clicks) = totals.get(&s.id).copied().unwrap_or_default();
+            row["clicks"] = clicks.into();
+            row["unique_clicks"] = unique_clicks.into();
+            row
+        })
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("sponsors", &rows);
+    ctx.insert("shortcode", sponsors::SHORTCODE);
+    let html = state.tera.render("admin/sponsors.html", &ctx)?;
+    Ok(Html(html))
+}
+
+// --- Report ---
+
+pub async fn report(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    let all = sponsors::all(&state.db).await?;
+    let report = sponsors::click_report(&state.db, &state.config.base_url).await?;
+
+    let today = crate::timezone::today();
+    let groups: Vec<serde_json::Value> = all
+        .iter()
+        .map(|s| {
+            let newsletters: Vec<&sponsors::SponsorClicks> =
+                report.iter().filter(|r| r.sponsor_id == s.id).collect();
+            let mut group = sponsor_json(s, today);
+            group["clicks"] = newsletters.iter().map(|r| r.clicks).sum::<i64>().into();
+            group["unique_clicks"] = newsletters
+                .iter()
+                .map(|r| r.unique_clicks)
+                .sum::<i64>()
+                .into();
+            group["newsletters"] = serde_json::json!(newsletters);
+            group
+        })
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", &admin_email);
+    ctx.insert("groups", &groups);
+    let html = state.tera.render("admin/sponsor_report.html", &ctx)?;
+    Ok(Html(html))
+}
+
+// --- Form ---
+
+#[derive(Deserialize, Default)]
+pub struct SponsorForm {
+    pub name: String,
+    pub logo_url: String,
+    pub url: String,
+    pub tier: String,
+    #[serde(default)]
+    pub starts_on: String,
+    #[serde(default)]
+    pub ends_on: String,
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// Validate the form into a sponsor with the given id.
+fn sponsor_from_form(id: uuid::Uuid, form: &SponsorForm) -> Result<Sponsor, String> {
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Name is required".to_string());
+    }
+    let url = form.url.trim().to_string();
+    if !is_http_url(&url) {
+        return Err("Sponsor URL must start with http:// or https://".to_string());
+    }
+    // Uploaded logos are site-relative; the render hooks make them absolute
+    let logo_url = form.logo_url.trim().to_string();
+    if !is_http_url(&logo_url) && !logo_url.starts_with('/') {
+        return Err("Logo must be an http(s) URL or an uploaded image path".to_string());
+    }
+    let tier = Tier::parse(&form.tier).ok_or_else(|| "Unknown tier".to_string())?;
+    let date = |s: &str| -> Result<Option<NaiveDate>, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(None);
+        }
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| format!("Invalid date: {s}"))
+    };
+    let starts_on = date(&form.starts_on)?;
+    let ends_on = date(&form.ends_on)?;
+    if let (Some(start), Some(end)) = (starts_on, ends_on) {
+        if end < start {
+            return Err("End date is before the start date".to_string());
+        }
+    }
+    Ok(Sponsor {
+        id,
+        name,
+        logo_url,
+        url,
+        tier,
+        starts_on,
+        ends_on,
+    })
+}
+
+fn audit_details(sponsor: &Sponsor) -> serde_json::Value {
+    serde_json::json!({
+        "sponsor_id": sponsor.id.to_string(),
+        "name": sponsor.name,
+        "url": sponsor.url,
+        "tier": sponsor.tier.as_str(),
+        "starts_on": sponsor.starts_on,
+        "ends_on": sponsor.ends_on,
+    })
+}
+
+/// Render the create/edit form. `sponsor` is null when creating.
+fn render_form(
+    state: &AppState,
+    admin_email: &str,
+    sponsor: &serde_json::Value,
+) -> Result<Html<String>, AppError> {
+    let tiers: Vec<serde_json::Value> = Tier::ALL
+        .into_iter()
+        .map(|t| serde_json::json!({ "key": t.as_str(), "label": t.label() }))
+        .collect();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("admin_email", admin_email);
+    ctx.insert("sponsor", sponsor);
+    ctx.insert("tiers", &tiers);
+    let html = state.tera.render("admin/sponsor_edit.html", &ctx)?;
+    Ok(Html(html))
+}
+
+pub async fn new_form(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+) -> Result<Html<String>, AppError> {
+    render_form(&state, &admin_email, &serde_json::json!(null))
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<SponsorForm>,
+) -> Result<Redirect, AppError> {
+    let mut sponsor = sponsor_from_form(uuid::Uuid::nil(), &form).map_err(AppError::BadRequest)?;
+
+    sponsor.id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO sponsors (name, logo_url, url, tier, starts_on, ends_on) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(&sponsor.name)
+    .bind(&sponsor.logo_url)
+    .bind(&sponsor.url)
+    .bind(sponsor.tier.as_str())
+    .bind(sponsor.starts_on)
+    .bind(sponsor.ends_on)
+    .fetch_one(&state.db)
+    .await?;
+    state.archive_cache.invalidate();
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "sponsor.create",
+        Some(audit_details(&sponsor)),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/sponsors"))
+}
+
+// --- Edit ---
+
+pub async fn edit_form(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Html<String>, AppError> {
+    let sponsor = sponsors::load(&state.db, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    render_form(
+        &state,
+        &admin_email,
+        &sponsor_json(&sponsor, crate::timezone::today()),
+    )
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<SponsorForm>,
+) -> Result<Redirect, AppError> {
+    let sponsor = sponsor_from_form(id, &form).map_err(AppError::BadRequest)?;
+
+    let result = sqlx::query(
+        "UPDATE sponsors SET name = $1, logo_url = $2, url = $3, tier = $4, \
+         starts_on = $5, ends_on = $6, updated_at = NOW() WHERE id = $7",
+    )
+    .bind(&sponsor.name)
+    .bind(&sponsor.logo_url)
+    .bind(&sponsor.url)
+    .bind(sponsor.tier.as_str())
+    .bind(sponsor.starts_on)
+    .bind(sponsor.ends_on)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    // Archive pages show the sponsor block as it was when they were cached
+    state.archive_cache.invalidate();
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "sponsor.update",
+        Some(audit_details(&sponsor)),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/sponsors"))
+}
+
+// --- Delete ---
+
+pub async fn delete(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Redirect, AppError> {
+    // Logos in sent newsletters link to /sp/{id}, which stops redirecting
+    let name = sqlx::query_scalar::<_, String>("DELETE FROM sponsors WHERE id = $1 RETURNING name")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    state.archive_cache.invalidate();
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "sponsor.delete",
+        Some(serde_json::json!({ "sponsor_id": id.to_string(), "name": name })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to("/admin/sponsors"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form() -> SponsorForm {
+        SponsorForm {
+            name: " Acme ".to_string(),
+            logo_url: "/uploads/acme.png".to_string(),
+            url: "https://acme.example/".to_string(),
+            tier: "gold".to_string(),
+            starts_on: "2025-07-01".to_string(),
+            ends_on: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_sponsor_from_form() {
+        let sponsor = sponsor_from_form(uuid::Uuid::nil(), &form()).unwrap();
+        assert_eq!(sponsor.name, "Acme");
+        assert_eq!(sponsor.tier, Tier::Gold);
+        assert_eq!(sponsor.starts_on, NaiveDate::from_ymd_opt(2025, 7, 1));
+        assert_eq!(sponsor.ends_on, None);
+    }
+
+    #[test]
+    fn test_sponsor_from_form_rejects() {
+        let cases = [
+            SponsorForm {
+                name: "  ".to_string(),
+                ..form()
+            },
+            SponsorForm {
+                url: "javascript:alert(1)".to_string(),
+                ..form()
+            },
+            SponsorForm {
+                logo_url: "acme.png".to_string(),
+                ..form()
+            },
+            SponsorForm {
+                tier: "titanium".to_string(),
+                ..form()
+            },
+            SponsorForm {
+                ends_on: "2025-06-30".to_string(),
+                ..form()
+            },
+            SponsorForm {
+                starts_on: "07/01/2025".to_string(),
+                ..form()
+            },
+        ];
+        for case in &cases {
+            assert!(sponsor_from_form(uuid::Uuid::nil(), case).is_err());
+        }
+    }
+}
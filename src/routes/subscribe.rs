@@ -1,14 +1,17 @@
 use std::net::SocketAddr;
 
-use axum::extract::{ConnectInfo, State};
+use axum::extract::{ConnectInfo, Query, State};
 use axum::http::HeaderMap;
 use axum::response::Html;
 use axum::{extract::Path, Form};
 use chrono::Utc;
 use serde::Deserialize;
 
+use crate::attribution::Attribution;
+use crate::crm_webhook::{self, Lifecycle};
 use crate::error::AppError;
 use crate::security;
+use crate::subscriber_events;
 use crate::AppState;
 
 #[derive(Deserialize)]
@@ -17,11 +20,35 @@ pub struct SubscribeForm {
     pub name: String,
     #[serde(rename = "cf-turnstile-response")]
     pub captcha_response: String,
+    /// Carried over from the subscribe page's query string
+    #[serde(flatten)]
+    pub attribution: Attribution,
+    /// ucode of the subscriber whose referral link was followed
+    pub referral: Option<String>,
 }
 
-pub async fn subscribe_page(State(state): State<AppState>) -> Result<Html<String>, AppError> {
+#[derive(Deserialize)]
+pub struct ReferralQuery {
+    pub referral: Option<String>,
+}
+
+pub async fn subscribe_page(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(attribution): Query<Attribution>,
+    Query(referral): Query<ReferralQuery>,
+) -> Result<Html<String>, AppError> {
+    let referer = headers
+        .get(axum::http::header::REFERER)
+        .and_then(|v| v.to_str().ok());
+    let attribution = attribution
+        .normalized()
+        .with_referer_header(referer, &state.config.base_url);
+
     let mut ctx = tera::Context::new();
     ctx.insert("turnstile_sitekey", &state.config.turnstile_sitekey);
+    ctx.insert("attribution", &attribution);
+    ctx.insert("referral", &referral.referral.unwrap_or_default());
     let html = state.tera.render("subscribe.html", &ctx)?;
     Ok(Html(html))
 }
@@ -33,12 +60,14 @@ pub async fn subscribe_api(
     headers: HeaderMap,
     Form(form): Form<SubscribeForm>,
 ) -> Result<Html<String>, AppError> {
-    let email = form.email.trim().to_lowercase();
+    let normalized = crate::email_validation::normalize_for_signup(
+        &form.email,
+        state.config.block_role_accounts,
+    )
+    .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let email = normalized.email;
     let name = form.name.trim().to_string();
-
-    if email.is_empty() {
-        return Err(AppError::BadRequest("Email is required".to_string()));
-    }
+    let mut attribution = form.attribution.normalized();
 
     // Verify captcha
     let captcha_ok = state
@@ -56,46 +85,54 @@ pub async fn subscribe_api(
     let client_ip = super::extract_client_ip(&headers, &connect_info);
     let ip_str = client_ip.to_string();
 
-    let email_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM subscribe_email_log WHERE email = $1 AND created_at > NOW() - INTERVAL '24 hours'",
-    )
-    .bind(&email)
-    .fetch_one(&state.db)
-    .await?;
-
-    if email_count >= 5 {
+    if !state
+        .rate_limiter
+        .check(
+            "subscribe_email",
+            &email,
+            state.live.get().rate_limit_subscribe_email,
+        )
+        .await?
+    {
         return Err(AppError::RateLimitExceeded);
     }
-
-    let ip_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM subscribe_email_log WHERE ip_address = $1::inet AND created_at > NOW() - INTERVAL '24 hours'",
-    )
-    .bind(&ip_str)
-    .fetch_one(&state.db)
-    .await?;
-
-    if ip_count >= 10 {
+    if !state
+        .rate_limiter
+        .check(
+            "subscribe_ip",
+            &ip_str,
+            state.live.get().rate_limit_subscribe_ip,
+        )
+        .await?
+    {
         return Err(AppError::RateLimitExceeded);
     }
 
-    // Check if already exists
-    let existing =
-        sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM subscribers WHERE email = $1")
-            .bind(&email)
-            .fetch_optional(&state.db)
-            .await?;
+    // Check if already exists, also under another spelling of the same Gmail mailbox
+    let existing = sqlx::query_scalar::<_, uuid::Uuid>(
+        "SELECT id FROM subscribers WHERE email = $1 OR canonical_email = $2 \
+         ORDER BY email = $1 DESC LIMIT 1",
+    )
+    .bind(&email)
+    .bind(&normalized.canonical)
+    .fetch_optional(&state.db)
+    .await?;
 
-    if existing.is_some() {
-        // Send management URL to the existing subscriber
-        let row = sqlx::query_as::<_, (String, String)>(
-            "SELECT secret_code, email FROM subscribers WHERE email = $1",
+    if let Some(existing_id) = existing {
+        // Send management URL to the existing subscriber, at the address they signed up with
+        let row = sqlx::query_as::<_, (String, String, String)>(
+            "SELECT secret_code, ucode, email FROM subscribers WHERE id = $1",
         )
-        .bind(&email)
+        .bind(existing_id)
         .fetch_optional(&state.db)
         .await?;
 
-        if let Some((secret_code, subscriber_email)) = row {
-            let admin_link = security::compute_admin_link(&secret_code, &subscriber_email);
+        if let Some((secret_code, ucode, subscriber_email)) = row {
+            let admin_link = security::issue_manage_token(
+                &secret_code,
+                &ucode,
+                state.config.manage_link_ttl_days,
+            );
             let manage_url = format!("{}/manage/{}", state.config.base_url, admin_link);
 
             let logo_url = format!("{}/static/coscup-logo.png", state.config.base_url);
@@ -132,18 +169,45 @@ pub async fn subscribe_api(
         return Ok(Html(html));
     }
 
+    // Credit the referrer, if the signup came through a referral link
+    let referred_by = match form.referral.as_deref() {
+        Some(code) => crate::referral::find_referrer(&state.db, code).await?,
+        None => None,
+    };
+    if referred_by.is_some() && attribution.utm_source.is_none() {
+        attribution.utm_source = Some(crate::referral::REFERRAL_SOURCE.to_string());
+        attribution.utm_medium = Some(crate::referral::REFERRAL_MEDIUM.to_string());
+    }
+
+    // Start them on the language their browser asks for; they can change it on the manage page
+    let locale = headers
+        .get(axum::http::header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(crate::locale::from_accept_language);
+
     // Create subscriber
     let secret_code = security::generate_secret_code();
     let ucode = security::generate_ucode();
 
     sqlx::query(
-        "INSERT INTO subscribers (email, name, secret_code, ucode, subscription_source) VALUES ($1, $2, $3, $4, $5)",
+        "INSERT INTO subscribers (email, name, secret_code, ucode, subscription_source, \
+         utm_source, utm_medium, utm_campaign, utm_term, utm_content, referrer, referred_by, canonical_email, locale) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
     )
     .bind(&email)
     .bind(&name)
     .bind(&secret_code)
     .bind(&ucode)
     .bind("web")
+    .bind(&attribution.utm_source)
+    .bind(&attribution.utm_medium)
+    .bind(&attribution.utm_campaign)
+    .bind(&attribution.utm_term)
+    .bind(&attribution.utm_content)
+    .bind(&attribution.referrer)
+    .bind(referred_by)
+    .bind(&normalized.canonical)
+    .bind(locale)
     .execute(&state.db)
     .await?;
 
@@ -153,6 +217,16 @@ pub async fn subscribe_api(
             .fetch_one(&state.db)
             .await?;
 
+    crate::consent::record(
+        &state.db,
+        subscriber_id,
+        "subscribe",
+        "web",
+        Some(client_ip),
+        super::user_agent(&headers),
+    )
+    .await?;
+
     // Create verification token
     let token = security::generate_token();
     let expires_at = Utc::now() + chrono::Duration::hours(24);
@@ -214,6 +288,8 @@ fn render_link_error(
 
 pub async fn verify_email(
     State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path(token): Path<String>,
 ) -> Result<Html<String>, AppError> {
     let now = Utc::now();
@@ -244,8 +320,24 @@ pub async fn verify_email(
         .bind(token_id)
         .execute(&state.db)
         .await?;
+    let client_ip = super::extract_client_ip(&headers, &connect_info);
+    subscriber_events::record(
+        &state.db,
+        subscriber_id,
+        subscriber_events::Event::VerificationUsed,
+        None,
+        Some(client_ip),
+        super::user_agent(&headers),
+    )
+    .await;
 
     // Activate subscriber
+    let (was_verified, was_active) = sqlx::query_as::<_, (bool, bool)>(
+        "SELECT verified_email, status FROM subscribers WHERE id = $1",
+    )
+    .bind(subscriber_id)
+    .fetch_one(&state.db)
+    .await?;
     sqlx::query(
         "UPDATE subscribers SET verified_email = true, status = true, updated_at = $1 WHERE id = $2",
     )
@@ -253,16 +345,32 @@ pub async fn verify_email(
     .bind(subscriber_id)
     .execute(&state.db)
     .await?;
+    if !was_verified {
+        crm_webhook::emit(&state, subscriber_id, Lifecycle::Verified).await?;
+    } else if !was_active {
+        crm_webhook::emit(&state, subscriber_id, Lifecycle::Resubscribed).await?;
+    }
 
-    // Get admin_link for the user
-    let (secret_code, email) = sqlx::query_as::<_, (String, String)>(
-        "SELECT secret_code, email FROM subscribers WHERE id = $1",
+    // Get a manage link for the user
+    let (secret_code, ucode, source) = sqlx::query_as::<_, (String, String, Option<String>)>(
+        "SELECT secret_code, ucode, subscription_source FROM subscribers WHERE id = $1",
     )
     .bind(subscriber_id)
     .fetch_one(&state.db)
     .await?;
 
-    let admin_link = security::compute_admin_link(&secret_code, &email);
+    crate::consent::record(
+        &state.db,
+        subscriber_id,
+        "verify",
+        source.as_deref().unwrap_or("web"),
+        Some(client_ip),
+        super::user_agent(&headers),
+    )
+    .await?;
+
+    let admin_link =
+        security::issue_manage_token(&secret_code, &ucode, state.config.manage_link_ttl_days);
     let manage_url = format!("{}/manage/{}", state.config.base_url, admin_link);
 
     let mut ctx = tera::Context::new();
@@ -330,4 +438,181 @@ mod tests {
         let ip = super::super::extract_client_ip(&headers, &connect_info);
         assert_eq!(ip, "::1".parse::<IpAddr>().unwrap());
     }
+
+    #[tokio::test]
+    async fn test_subscribe_rejects_failed_captcha() {
+        let app = crate::test_utils::TestStateBuilder::without_db()
+            .captcha_passes(false)
+            .build();
+        let response = app
+            .post_form(
+                "/api/subscribe",
+                &[
+                    ("email", "someone@example.org"),
+                    ("name", "Someone"),
+                    ("cf-turnstile-response", "token"),
+                ],
+            )
+            .await;
+        assert_eq!(response.status, axum::http::StatusCode::BAD_REQUEST);
+        assert!(app.sent_emails().is_empty());
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_subscribe_sends_verification_email(db: sqlx::PgPool) {
+        let app = crate::test_utils::TestApp::with_db(db).await;
+        let form = [
+            ("email", "Someone@Example.org"),
+            ("name", "Someone"),
+            ("cf-turnstile-response", "token"),
+        ];
+        let response = app.post_form("/api/subscribe", &form).await;
+        assert_eq!(response.status, axum::http::StatusCode::OK);
+
+        let (verified, source): (bool, Option<String>) = sqlx::query_as(
+            "SELECT verified_email, subscription_source FROM subscribers WHERE email = $1",
+        )
+        .bind("someone@example.org")
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert!(!verified);
+        assert_eq!(source.as_deref(), Some("web"));
+        let sent = app.sent_emails();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "someone@example.org");
+        let token: String = sqlx::query_scalar("SELECT token FROM verification_tokens")
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        assert!(sent[0].2.contains(&token));
+
+        // Signing up again mails the management link instead of a new subscriber
+        app.post_form("/api/subscribe", &form).await;
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscribers")
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        let sent = app.sent_emails();
+        assert_eq!(sent.len(), 2);
+        assert!(!sent[1].2.contains(&token));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_subscribe_detects_locale(db: sqlx::PgPool) {
+        use axum::body::Body;
+        use axum::http::{header, Request};
+
+        let app = crate::test_utils::TestApp::with_db(db).await;
+        let signup = |email: &str, accept_language: &str| {
+            Request::post("/api/subscribe")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .header(header::ACCEPT_LANGUAGE, accept_language)
+                .body(Body::from(format!(
+                    "email={}&name=Someone&cf-turnstile-response=token",
+                    urlencoding::encode(email)
+                )))
+                .unwrap()
+        };
+        app.send(signup("ja@example.org", "ja-JP,ja;q=0.9,en;q=0.8"))
+            .await;
+        app.send(signup("de@example.org", "de-DE,de;q=0.9")).await;
+        app.post_form(
+            "/api/subscribe",
+            &[
+                ("email", "none@example.org"),
+                ("name", "Someone"),
+                ("cf-turnstile-response", "token"),
+            ],
+        )
+        .await;
+
+        let locales: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT email, locale FROM subscribers ORDER BY email")
+                .fetch_all(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(
+            locales,
+            [
+                ("de@example.org".to_string(), None),
+                ("ja@example.org".to_string(), Some("ja".to_string())),
+                ("none@example.org".to_string(), None),
+            ]
+        );
+
+        // Signing up again from another browser leaves the language alone
+        app.send(signup("ja@example.org", "en")).await;
+        let locale: Option<String> =
+            sqlx::query_scalar("SELECT locale FROM subscribers WHERE email = 'ja@example.org'")
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(locale.as_deref(), Some("ja"));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_consent_recorded_at_signup_and_verify(db: sqlx::PgPool) {
+        use axum::body::Body;
+        use axum::http::{header, Request, StatusCode};
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.send(
+            Request::post("/api/subscribe")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .header(header::USER_AGENT, "SignupBrowser/1.0")
+                .body(Body::from(
+                    "email=someone%40example.org&name=Someone&cf-turnstile-response=token",
+                ))
+                .unwrap(),
+        )
+        .await;
+        let token: String = sqlx::query_scalar("SELECT token FROM verification_tokens")
+            .fetch_one(&app.state.db)
+            .await
+            .unwrap();
+        let response = app.get(&format!("/verify/{token}")).await;
+        assert_eq!(response.status, StatusCode::OK);
+
+        let id: uuid::Uuid =
+            sqlx::query_scalar("SELECT id FROM subscribers WHERE email = 'someone@example.org'")
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        let consents = crate::consent::for_subscriber(&app.state.db, id)
+            .await
+            .unwrap();
+        let steps: Vec<(&str, &str, &str, Option<&str>)> = consents
+            .iter()
+            .map(|c| {
+                (
+                    c.action.as_str(),
+                    c.version.as_str(),
+                    c.source.as_str(),
+                    c.ip_address.as_deref(),
+                )
+            })
+            .collect();
+        let version = crate::consent::CONSENT_VERSION;
+        assert_eq!(
+            steps,
+            [
+                ("subscribe", version, "web", Some("127.0.0.1")),
+                ("verify", version, "web", Some("127.0.0.1")),
+            ]
+        );
+        assert_eq!(consents[0].user_agent.as_deref(), Some("SignupBrowser/1.0"));
+
+        app.login_as("admin@coscup.org").await;
+        let detail = app.get(&format!("/admin/subscribers/{id}")).await;
+        assert_eq!(detail.status, StatusCode::OK);
+        assert!(detail.body.contains("送出訂閱"));
+        assert!(detail.body.contains("驗證 Email"));
+        assert!(detail.body.contains(&format!("<td>{version}</td>")));
+        assert!(!detail.body.contains("無同意紀錄"));
+    }
 }
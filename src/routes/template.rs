@@ -1,6 +1,6 @@
 use std::net::SocketAddr;
 
-use axum::extract::{ConnectInfo, Path, State};
+use axum::extract::{ConnectInfo, Path, Query, State};
 use axum::http::HeaderMap;
 use axum::response::{Html, Redirect};
 use axum::Form;
@@ -9,6 +9,7 @@ use serde::Deserialize;
 use crate::auth::AdminUser;
 use crate::error::AppError;
 use crate::newsletter;
+use crate::template_profiles::{self, Profile};
 use crate::AppState;
 
 // --- List ---
@@ -41,13 +42,19 @@ pub async fn list(
                 "slug": slug,
                 "name": name,
                 "description": description,
-                "created_at": created_at.format("%Y-%m-%d %H:%M").to_string(),
+                "created_at": created_at.to_rfc3339(),
             })
         })
         .collect();
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
+    ctx.insert(
+        "admin_tz",
+        crate::timezone::for_admin(&state.db, &admin_email)
+            .await
+            .name(),
+    );
     ctx.insert("templates", &templates);
     let html = state.tera.render("admin/templates.html", &ctx)?;
     Ok(Html(html))
@@ -185,6 +192,9 @@ pub async fn update(
     .execute(&state.db)
     .await?;
 
+    // Sent newsletters using this template render differently now
+    state.archive_cache.invalidate();
+
     let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
     crate::audit::log(
         &state.db,
@@ -240,10 +250,17 @@ pub async fn delete(
 
 // --- Preview ---
 
+#[derive(Deserialize, Default)]
+pub struct PreviewQuery {
+    /// Preview data profile; unset for the built-in sample
+    pub profile: Option<uuid::Uuid>,
+}
+
 pub async fn preview(
     State(state): State<AppState>,
     AdminUser(admin_email): AdminUser,
     Path(id): Path<uuid::Uuid>,
+    Query(query): Query<PreviewQuery>,
 ) -> Result<Html<String>, AppError> {
     let row = sqlx::query_as::<_, (String, String)>(
         "SELECT name, html_body FROM newsletter_templates WHERE id = $1",
@@ -255,54 +272,143 @@ pub async fn preview(
 
     let (name, html_body) = row;
 
-    // Use a realistic Markdown sample so the preview goes through the same
-    // render_markdown pipeline as actual newsletters.
-    let sample_markdown = "\
-## COSCUP 2025 活動公告
-
-感謝您訂閱 COSCUP 電子報！以下是本期精彩內容：
-
-### 活動亮點
-
-- 超過 **100 場**議程，涵蓋 Open Source 各領域
-- 活動日期：**8 月 9 日 ~ 10 日**
-- 地點：[台灣科技大學](https://coscup.org)
-
-### 特別活動
-
-本年度特別新增「親子工作坊」，歡迎帶孩子一起參與開源文化！
-
----
+    let profile = match query.profile {
+        Some(profile_id) => template_profiles::find(&state.db, id, profile_id)
+            .await?
+            .ok_or(AppError::NotFound)?,
+        None => Profile::sample(),
+    };
 
-[立即報名](https://coscup.org) | [查看議程](https://coscup.org)\
-";
-    let content_html = newsletter::render_markdown(sample_markdown, &state.config.base_url);
+    let content_html =
+        newsletter::render_markdown(&profile.markdown_content, &state.config.base_url);
     let tracking_pixel = "<!-- tracking pixel placeholder -->";
     let unsubscribe_url = "#unsubscribe";
 
+    let html_body = newsletter::apply_compliance_footer(
+        &html_body,
+        newsletter::ComplianceFooter::from_config(&state.config),
+    );
     let rendered = newsletter::personalize_email(
         &html_body,
         &content_html,
-        "COSCUP 2025 電子報 - 第一期",
+        &profile.title,
         tracking_pixel,
         unsubscribe_url,
         &state.config.base_url,
         "#web-version",
+        newsletter::ContentLanguage {
+            lang: &profile.lang,
+            dir: profile.dir(),
+        },
     )
     .map_err(|e| AppError::Internal(e.to_string()))?;
 
     // Replace recipient name placeholder as the actual send pipeline does.
-    let rendered = newsletter::replace_recipient_name(&rendered, "COSCUP 訂閱者");
+    let rendered = newsletter::replace_recipient_name(&rendered, &profile.recipient_name);
 
     let mut ctx = tera::Context::new();
     ctx.insert("admin_email", &admin_email);
     ctx.insert("template_id", &id.to_string());
     ctx.insert("name", &name);
     ctx.insert("rendered_html", &rendered);
+    ctx.insert("profiles", &template_profiles::list(&state.db, id).await?);
+    ctx.insert("profile", &profile);
     let html = state.tera.render("admin/template_preview.html", &ctx)?;
     Ok(Html(html))
 }
 
+// --- Preview data profiles ---
+
+#[derive(Deserialize)]
+pub struct ProfileForm {
+    pub name: String,
+    pub title: String,
+    pub markdown_content: String,
+    pub recipient_name: String,
+    pub lang: String,
+}
+
+/// Save a preview data profile; one with the same name is replaced.
+pub async fn save_profile(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Form(form): Form<ProfileForm>,
+) -> Result<Redirect, AppError> {
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM newsletter_templates WHERE id = $1)",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
+    if !exists {
+        return Err(AppError::NotFound);
+    }
+
+    let profile = template_profiles::validate(
+        &form.name,
+        &form.title,
+        &form.markdown_content,
+        &form.recipient_name,
+        &form.lang,
+    )
+    .map_err(AppError::BadRequest)?;
+    let profile_id = template_profiles::save(&state.db, id, &profile, &admin_email).await?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "template.profile_save",
+        Some(serde_json::json!({
+            "template_id": id.to_string(),
+            "profile_id": profile_id.to_string(),
+            "name": profile.name,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!(
+        "/admin/templates/{id}/preview?profile={profile_id}"
+    )))
+}
+
+pub async fn delete_profile(
+    State(state): State<AppState>,
+    AdminUser(admin_email): AdminUser,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((id, profile_id)): Path<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<Redirect, AppError> {
+    let name = sqlx::query_scalar::<_, String>(
+        "DELETE FROM template_preview_profiles WHERE template_id = $1 AND id = $2 RETURNING name",
+    )
+    .bind(id)
+    .bind(profile_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
+    crate::audit::log(
+        &state.db,
+        &admin_email,
+        "template.profile_delete",
+        Some(serde_json::json!({
+            "template_id": id.to_string(),
+            "profile_id": profile_id.to_string(),
+            "name": name,
+        })),
+        Some(client_ip),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/admin/templates/{id}/preview")))
+}
+
 // --- Duplicate ---
 
 pub async fn duplicate(
@@ -336,6 +442,7 @@ pub async fn duplicate(
     .bind(&admin_email)
     .fetch_one(&state.db)
     .await?;
+    template_profiles::copy(&state.db, id, new_id).await?;
 
     let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr));
     crate::audit::log(
@@ -425,4 +532,79 @@ mod tests {
         // produce a valid slug
         assert!(slug1.starts_with("test-copy-"));
     }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_preview_profiles(db: sqlx::PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO newsletter_templates (name, slug, description, html_body) \
+             VALUES ('T', 'profile-test', '', \
+             '<h1>{{ title }}</h1><p>Hi %recipient_name%</p>{{ content }}<a href=\"{{ unsubscribe_url }}\">x</a>') \
+             RETURNING id",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        let preview = format!("/admin/templates/{id}/preview");
+        assert!(app
+            .get(&preview)
+            .await
+            .body
+            .contains("COSCUP 2025 電子報 - 第一期"));
+
+        let saved = app
+            .post_form(
+                &format!("/admin/templates/{id}/profiles"),
+                &[
+                    ("name", "English"),
+                    ("title", "An unusually long English title"),
+                    ("markdown_content", "No images here"),
+                    ("recipient_name", "Ada"),
+                    ("lang", "en"),
+                ],
+            )
+            .await;
+        assert_eq!(saved.status, StatusCode::SEE_OTHER);
+        let profiles = template_profiles::list(&app.state.db, id).await.unwrap();
+        assert_eq!(profiles.len(), 1);
+        let profile_id = profiles[0].id.unwrap();
+
+        let page = app.get(&format!("{preview}?profile={profile_id}")).await;
+        assert!(page.body.contains("An unusually long English title"));
+        assert!(page.body.contains("Hi Ada"));
+        assert!(page.body.contains("No images here"));
+        // Another template's profile isn't found through this one
+        let other = app
+            .get(&format!(
+                "/admin/templates/{}/preview?profile={profile_id}",
+                uuid::Uuid::new_v4()
+            ))
+            .await;
+        assert_eq!(other.status, StatusCode::NOT_FOUND);
+
+        app.post_form(&format!("/admin/templates/{id}/duplicate"), &[])
+            .await;
+        let copied: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM template_preview_profiles WHERE template_id <> $1",
+        )
+        .bind(id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(copied, 1);
+
+        app.post_form(
+            &format!("/admin/templates/{id}/profiles/{profile_id}/delete"),
+            &[],
+        )
+        .await;
+        assert!(template_profiles::list(&app.state.db, id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
 }
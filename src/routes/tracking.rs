@@ -1,10 +1,16 @@
-use axum::extract::{Query, State};
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Query, State};
 use axum::http::header;
-use axum::http::HeaderMap;
-use axum::response::{IntoResponse, Redirect, Response};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use chrono::Utc;
 use serde::Deserialize;
 
+use crate::click_guard::{self, Verdict};
 use crate::error::AppError;
+use crate::event_buffer::TrackingEvent;
+use crate::scanner;
 use crate::security;
 use crate::AppState;
 
@@ -17,43 +23,63 @@ const TRANSPARENT_PNG: &[u8] = &[
     0x60, 0x82,
 ];
 
+/// Missing fields fail verification instead of the request, so a truncated
+/// click link still gets the fallback page.
 #[derive(Deserialize)]
 pub struct TrackingQuery {
+    #[serde(default)]
     pub ucode: String,
+    #[serde(default)]
     pub topic: String,
+    #[serde(default)]
     pub hash: String,
     pub url: Option<String>,
 }
 
+/// Links in emails sent before the subscriber's secret code was rotated are
+/// signed with the previous one.
+fn verify_with_any_secret(
+    (secret_code, previous_secret_code): (String, Option<String>),
+    verify: impl Fn(&str) -> bool,
+) -> bool {
+    verify(&secret_code) || previous_secret_code.as_deref().is_some_and(verify)
+}
+
 pub async fn track_open(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(query): Query<TrackingQuery>,
 ) -> Result<Response, AppError> {
     // Verify openhash
-    let subscriber =
-        sqlx::query_as::<_, (String,)>("SELECT secret_code FROM subscribers WHERE ucode = $1")
-            .bind(&query.ucode)
-            .fetch_optional(&state.db)
-            .await?;
-
-    if let Some((secret_code,)) = subscriber {
-        if security::verify_openhash(&secret_code, &query.ucode, &query.topic, "", &query.hash) {
+    let subscriber = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT secret_code, previous_secret_code FROM subscribers WHERE ucode = $1",
+    )
+    .bind(&query.ucode)
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(secrets) = subscriber {
+        if verify_with_any_secret(secrets, |secret| {
+            security::verify_openhash(secret, &query.ucode, &query.topic, "", &query.hash)
+        }) {
             let user_agent = headers
                 .get(header::USER_AGENT)
                 .and_then(|v| v.to_str().ok())
                 .unwrap_or("")
                 .to_string();
 
-            // Record event (best-effort, don't fail on error)
-            let _ = sqlx::query(
-                "INSERT INTO email_events (ucode, event_type, topic, user_agent) VALUES ($1, 'open', $2, $3)",
-            )
-            .bind(&query.ucode)
-            .bind(&query.topic)
-            .bind(&user_agent)
-            .execute(&state.db)
-            .await;
+            // Queue event for the batched writer (best-effort)
+            let newsletter_id = state.topics.resolve(&state.db, &query.topic).await;
+            state.events.record(TrackingEvent {
+                ucode: query.ucode.clone(),
+                event_type: "open",
+                topic: query.topic.clone(),
+                newsletter_id,
+                user_agent,
+                clicked_url: None,
+                scanner_reason: None,
+                created_at: Utc::now(),
+            });
         }
     }
 
@@ -65,53 +91,255 @@ pub async fn track_open(
         .into_response())
 }
 
+fn bad_link_page(state: &AppState) -> Result<Response, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", "連結無效");
+    ctx.insert(
+        "message",
+        "這個連結缺少或帶有無效的目的網址，可能在複製時被截斷了。請回到原本的郵件重新點選。",
+    );
+    let html = state.tera.render("error.html", &ctx)?;
+    Ok((StatusCode::BAD_REQUEST, Html(html)).into_response())
+}
+
+/// Page shown instead of redirecting: a warning for a blocked destination,
+/// or a confirmation for a link whose hash doesn't verify.
+fn interstitial(
+    state: &AppState,
+    status: StatusCode,
+    url: &str,
+    host: &str,
+    blocked: bool,
+) -> Result<Response, AppError> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("url", url);
+    ctx.insert("host", host);
+    ctx.insert("blocked", &blocked);
+    let html = state.tera.render("link_interstitial.html", &ctx)?;
+    Ok((status, Html(html)).into_response())
+}
+
 pub async fn track_click(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    method: Method,
     headers: HeaderMap,
     Query(query): Query<TrackingQuery>,
 ) -> Result<Response, AppError> {
-    let redirect_url = query
+    // Only http(s) destinations, to prevent open redirects to other schemes
+    let Some((redirect_url, host)) = query
         .url
         .as_deref()
-        .ok_or_else(|| AppError::BadRequest("Missing url parameter".to_string()))?;
+        .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+        .and_then(|url| Some((url, crate::shorturl::domain_of(url)?)))
+    else {
+        return bad_link_page(&state);
+    };
 
-    // Validate redirect URL to prevent open redirect attacks
-    if !redirect_url.starts_with("https://") && !redirect_url.starts_with("http://") {
-        return Err(AppError::BadRequest("Invalid redirect URL".to_string()));
+    // Never sent on, whatever the hash says
+    let rules = crate::link_domains::Rules::load(&state)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to load link domains, using BLOCKED_LINK_DOMAINS only: {e}");
+            crate::link_domains::Rules::from_config(&state)
+        });
+    if rules.is_blocked(&host) {
+        tracing::warn!("Refused click through to blocked domain {host}");
+        return interstitial(&state, StatusCode::FORBIDDEN, redirect_url, &host, true);
     }
 
     // Verify openhash
-    let subscriber =
-        sqlx::query_as::<_, (String,)>("SELECT secret_code FROM subscribers WHERE ucode = $1")
-            .bind(&query.ucode)
-            .fetch_optional(&state.db)
-            .await?;
-
-    if let Some((secret_code,)) = subscriber {
-        if security::verify_openhash(
-            &secret_code,
-            &query.ucode,
-            &query.topic,
-            redirect_url,
-            &query.hash,
-        ) {
-            let user_agent = headers
-                .get(header::USER_AGENT)
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("")
-                .to_string();
+    let subscriber = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT secret_code, previous_secret_code FROM subscribers WHERE ucode = $1",
+    )
+    .bind(&query.ucode)
+    .fetch_optional(&state.db)
+    .await?;
 
-            let _ = sqlx::query(
-                "INSERT INTO email_events (ucode, event_type, topic, user_agent, clicked_url) VALUES ($1, 'click', $2, $3, $4)",
+    let verified = subscriber.is_some_and(|secrets| {
+        verify_with_any_secret(secrets, |secret| {
+            security::verify_openhash(
+                secret,
+                &query.ucode,
+                &query.topic,
+                redirect_url,
+                &query.hash,
             )
-            .bind(&query.ucode)
-            .bind(&query.topic)
-            .bind(&user_agent)
-            .bind(redirect_url)
-            .execute(&state.db)
-            .await;
-        }
+        })
+    });
+
+    // Blocked sources are not recorded, and hash guessers are slowed down;
+    // where the reader is sent still depends on the hash alone
+    let client_ip = super::extract_client_ip(&headers, &ConnectInfo(addr)).to_string();
+    let verdict = state.click_guard.verdict(&query.ucode, &client_ip);
+    if verdict == Verdict::Tarpit {
+        tokio::time::sleep(click_guard::TARPIT_DELAY).await;
     }
+    let incident = match verdict {
+        Verdict::Allow if verified => state.click_guard.record_click(&query.ucode),
+        Verdict::Allow => state.click_guard.record_bad_hash(&client_ip),
+        Verdict::Ignore | Verdict::Tarpit => None,
+    };
+    // The click that trips the limit is not recorded either
+    if let Some(incident) = &incident {
+        click_guard::log_incident(&state.db, incident, state.config.click_block_secs).await;
+    }
+
+    if verified && verdict == Verdict::Allow && incident.is_none() {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+
+        // Link scanners probe with HEAD or identify themselves in the UA;
+        // timing and HEAD-then-GET are checked when the event is written.
+        let scanner_reason = if method == Method::HEAD {
+            Some(scanner::REASON_HEAD)
+        } else if scanner::is_scanner_user_agent(&user_agent) {
+            Some(scanner::REASON_USER_AGENT)
+        } else {
+            None
+        };
 
+        let newsletter_id = state.topics.resolve(&state.db, &query.topic).await;
+        state.events.record(TrackingEvent {
+            ucode: query.ucode.clone(),
+            event_type: "click",
+            topic: query.topic.clone(),
+            newsletter_id,
+            user_agent,
+            clicked_url: Some(redirect_url.to_string()),
+            scanner_reason,
+            created_at: Utc::now(),
+        });
+    }
+
+    // A forwarded or mangled link still works, after the reader confirms
+    if !verified {
+        return interstitial(&state, StatusCode::OK, redirect_url, &host, false);
+    }
     Ok(Redirect::temporary(redirect_url).into_response())
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use sqlx::PgPool;
+
+    use crate::test_utils::TestApp;
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_click_redirects_and_records_verified_clicks(db: PgPool) {
+        let mut app = TestApp::with_db(db).await;
+        let secret = "s".repeat(64);
+        sqlx::query(
+            "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.org', $1, 'u1', true, true)",
+        )
+        .bind(&secret)
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+
+        let url = "https://coscup.org/2025/";
+        let hash = crate::security::compute_openhash(&secret, "u1", "2025-08", url);
+        let click = |hash: &str| {
+            format!(
+                "/r/c?ucode=u1&topic=2025-08&hash={hash}&url={}",
+                urlencoding::encode(url)
+            )
+        };
+        let response = app.get(&click(&hash)).await;
+        assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.location(), Some(url));
+        // A forged hash asks before sending the reader on
+        let response = app.get(&click("forged")).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.body.contains("即將前往外部網站"));
+        assert!(response.body.contains("繼續前往 coscup.org"));
+
+        app.flush_events().await;
+        let clicked: Vec<String> =
+            sqlx::query_scalar("SELECT clicked_url FROM email_events WHERE event_type = 'click'")
+                .fetch_all(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(clicked, [url]);
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_click_fallback_pages(db: PgPool) {
+        let app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.blocked_link_domains = vec!["evil.example".to_string()])
+            .build();
+        app.migrate().await;
+
+        let missing = app.get("/r/c?ucode=u1&topic=t&hash=h").await;
+        assert_eq!(missing.status, StatusCode::BAD_REQUEST);
+        assert!(missing.body.contains("連結無效"));
+        let scheme = app.get("/r/c?url=javascript%3Aalert(1)").await;
+        assert_eq!(scheme.status, StatusCode::BAD_REQUEST);
+        // Truncated link without a hash
+        let truncated = app.get("/r/c?url=https%3A%2F%2Fcoscup.org%2F").await;
+        assert_eq!(truncated.status, StatusCode::OK);
+        assert!(truncated.body.contains("繼續前往 coscup.org"));
+
+        let blocked = app
+            .get("/r/c?ucode=u1&topic=t&hash=h&url=https%3A%2F%2Flogin.evil.example%2F")
+            .await;
+        assert_eq!(blocked.status, StatusCode::FORBIDDEN);
+        assert!(blocked.body.contains("此連結已被封鎖"));
+        assert!(!blocked.body.contains("繼續前往"));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_flooded_ucode_still_confirms_unsigned_links(db: PgPool) {
+        let mut app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.click_limit_ucode = crate::rate_limit::RateLimitRule::new(2, 3600))
+            .build();
+        app.migrate().await;
+        let secret = "s".repeat(64);
+        sqlx::query(
+            "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+             VALUES ('a@example.org', $1, 'u1', true, true)",
+        )
+        .bind(&secret)
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+
+        let url = "https://coscup.org/2025/";
+        let hash = crate::security::compute_openhash(&secret, "u1", "2025-08", url);
+        let signed = format!(
+            "/r/c?ucode=u1&topic=2025-08&hash={hash}&url={}",
+            urlencoding::encode(url)
+        );
+        for _ in 0..3 {
+            let response = app.get(&signed).await;
+            assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+        }
+
+        // The ucode is now blocked, which must not skip the confirmation
+        let unsigned = app
+            .get("/r/c?ucode=u1&topic=2025-08&hash=x&url=https%3A%2F%2Fphish.example%2F")
+            .await;
+        assert_eq!(unsigned.status, StatusCode::OK);
+        assert!(unsigned.body.contains("繼續前往 phish.example"));
+        // Signed links still go straight through, unrecorded
+        let response = app.get(&signed).await;
+        assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.location(), Some(url));
+
+        app.flush_events().await;
+        let clicks: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM email_events WHERE event_type = 'click'")
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(clicks, 2);
+    }
+}
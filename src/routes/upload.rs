@@ -104,6 +104,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::case_sensitive_file_extension_comparisons)]
     fn test_filename_generation() {
         let ext = "png";
         let filename = format!("{}.{}", uuid::Uuid::new_v4(), ext);
@@ -0,0 +1,107 @@
+use sqlx::PgPool;
+
+/// `email_events.scanner_reason` values.
+pub const REASON_USER_AGENT: &str = "user_agent";
+pub const REASON_HEAD: &str = "head";
+pub const REASON_HEAD_THEN_GET: &str = "head_then_get";
+pub const REASON_TIMING: &str = "timing";
+
+/// Lowercase User-Agent fragments of known link scanners and HTTP libraries.
+const SCANNER_UA_PATTERNS: &[&str] = &[
+    "proofpoint",
+    "urldefense",
+    "mimecast",
+    "barracuda",
+    "safelinks",
+    "microsoft office existence discovery",
+    "ms-office",
+    "bingpreview",
+    "trendmicro",
+    "symantec",
+    "forcepoint",
+    "headlesschrome",
+    "python-requests",
+    "go-http-client",
+    "curl/",
+    "wget/",
+    "zgrab",
+];
+
+/// A GET shortly after a HEAD for the same link is treated as part of the scan.
+const HEAD_THEN_GET_WINDOW_SECS: i64 = 30;
+
+/// Whether a click's User-Agent looks like a link scanner. Browsers always
+/// send a User-Agent, so an empty one counts as a scanner too.
+pub fn is_scanner_user_agent(user_agent: &str) -> bool {
+    let ua = user_agent.trim().to_lowercase();
+    ua.is_empty() || SCANNER_UA_PATTERNS.iter().any(|p| ua.contains(p))
+}
+
+/// Flag clicks among the given (just inserted) events that were made within
+/// `window_secs` of the newsletter being sent to that subscriber, or that
+/// follow a HEAD request for the same link. `window_secs = 0` disables the
+/// timing check.
+pub async fn flag_batch(
+    db: &PgPool,
+    event_ids: &[uuid::Uuid],
+    window_secs: i64,
+) -> Result<(), sqlx::Error> {
+    if event_ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "UPDATE email_events e SET is_scanner = true, scanner_reason = $2 \
+         WHERE e.id = ANY($1) AND e.event_type = 'click' AND NOT e.is_scanner \
+         AND EXISTS (SELECT 1 FROM email_events h \
+             WHERE h.ucode = e.ucode AND h.newsletter_id = e.newsletter_id AND h.scanner_reason = $3 \
+             AND h.clicked_url IS NOT DISTINCT FROM e.clicked_url \
+             AND h.created_at <= e.created_at \
+             AND h.created_at > e.created_at - ($4::BIGINT * INTERVAL '1 second'))",
+    )
+    .bind(event_ids)
+    .bind(REASON_HEAD_THEN_GET)
+    .bind(REASON_HEAD)
+    .bind(HEAD_THEN_GET_WINDOW_SECS)
+    .execute(db)
+    .await?;
+
+    if window_secs > 0 {
+        sqlx::query(
+            "UPDATE email_events e SET is_scanner = true, scanner_reason = $2 \
+             FROM newsletter_sends ns \
+             JOIN subscribers s ON s.id = ns.subscriber_id \
+             WHERE e.id = ANY($1) AND e.event_type = 'click' AND NOT e.is_scanner \
+             AND ns.newsletter_id = e.newsletter_id AND s.ucode = e.ucode AND ns.sent_at IS NOT NULL \
+             AND e.created_at < ns.sent_at + ($3::BIGINT * INTERVAL '1 second')",
+        )
+        .bind(event_ids)
+        .bind(REASON_TIMING)
+        .bind(window_secs)
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_scanner_user_agent() {
+        assert!(is_scanner_user_agent(""));
+        assert!(is_scanner_user_agent(
+            "Mozilla/4.0 (compatible; ms-office; MSOffice 16)"
+        ));
+        assert!(is_scanner_user_agent("python-requests/2.31.0"));
+        assert!(is_scanner_user_agent("Proofpoint URL Defense"));
+        assert!(!is_scanner_user_agent(
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_5) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15"
+        ));
+        assert!(!is_scanner_user_agent(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0"
+        ));
+    }
+}
@@ -34,6 +34,62 @@ pub fn compute_admin_link(secret_code: &str, email: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
+/// Compute a manage token `{ucode}.{expires_at}.{sig}`, where sig =
+/// `HMAC-SHA256(secret_code, "manage:ucode:expires_at")` and `expires_at` is a
+/// Unix timestamp. Rotating the secret code invalidates all its tokens.
+pub fn compute_manage_token(secret_code: &str, ucode: &str, expires_at: i64) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret_code.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("manage:{ucode}:{expires_at}").as_bytes());
+    format!(
+        "{ucode}.{expires_at}.{}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// Manage token valid for `ttl_days` from now.
+pub fn issue_manage_token(secret_code: &str, ucode: &str, ttl_days: i64) -> String {
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(ttl_days);
+    compute_manage_token(secret_code, ucode, expires_at.timestamp())
+}
+
+/// Split a manage token into (ucode, `expires_at`). `None` for anything else,
+/// such as a legacy `admin_link`.
+pub fn parse_manage_token(token: &str) -> Option<(&str, i64)> {
+    let mut parts = token.split('.');
+    let (ucode, expires_at, sig) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || ucode.is_empty() || sig.len() != 64 {
+        return None;
+    }
+    Some((ucode, expires_at.parse().ok()?))
+}
+
+/// Verify a manage token's signature (not its expiry) in constant time.
+pub fn verify_manage_token(secret_code: &str, token: &str) -> bool {
+    let Some((ucode, expires_at)) = parse_manage_token(token) else {
+        return false;
+    };
+    constant_time_eq(token, &compute_manage_token(secret_code, ucode, expires_at))
+}
+
+/// Compute a share link token `{link_id}.{expires_at}.{sig}`, where sig =
+/// `HMAC-SHA256(secret, "share:link_id:newsletter_id:expires_at")`. Each link
+/// has its own secret, so revoking one leaves the others working.
+pub fn compute_share_token(
+    secret: &str,
+    link_id: &str,
+    newsletter_id: &str,
+    expires_at: i64,
+) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("share:{link_id}:{newsletter_id}:{expires_at}").as_bytes());
+    format!(
+        "{link_id}.{expires_at}.{}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
 /// Compute openhash = HMAC-SHA256(secret_code, "ucode:topic:url").
 /// For open-tracking (no URL), pass `url = ""`.
 pub fn compute_openhash(secret_code: &str, ucode: &str, topic: &str, url: &str) -> String {
@@ -44,10 +100,27 @@ pub fn compute_openhash(secret_code: &str, ucode: &str, topic: &str, url: &str)
     hex::encode(mac.finalize().into_bytes())
 }
 
+/// Sign a URL with a server-side key: HMAC-SHA256(key, url).
+pub fn compute_url_signature(key: &str, url: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(url.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Verify a URL signature using constant-time comparison.
+pub fn verify_url_signature(key: &str, url: &str, provided: &str) -> bool {
+    constant_time_eq(provided, &compute_url_signature(key, url))
+}
+
 /// Constant-time comparison for `admin_link` verification.
 pub fn verify_admin_link(provided: &str, expected: &str) -> bool {
-    let a = provided.as_bytes();
-    let b = expected.as_bytes();
+    constant_time_eq(provided, expected)
+}
+
+/// Constant-time string comparison (length is not hidden).
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
     if a.len() != b.len() {
         return false;
     }
@@ -122,6 +195,25 @@ mod tests {
         assert!(!verify_admin_link("short", "muchlongerstring"));
     }
 
+    #[test]
+    fn test_manage_token_roundtrip() {
+        let token = compute_manage_token("secret", "abcd1234", 1_800_000_000);
+        assert!(token.starts_with("abcd1234.1800000000."));
+        assert_eq!(
+            parse_manage_token(&token),
+            Some(("abcd1234", 1_800_000_000))
+        );
+        assert!(verify_manage_token("secret", &token));
+
+        // Rotated secret, tampered expiry, and legacy links are rejected
+        assert!(!verify_manage_token("rotated", &token));
+        let tampered = token.replacen("1800000000", "1900000000", 1);
+        assert!(!verify_manage_token("secret", &tampered));
+        let legacy = compute_admin_link("secret", "user@test.com");
+        assert_eq!(parse_manage_token(&legacy), None);
+        assert!(!verify_manage_token("secret", &legacy));
+    }
+
     #[test]
     fn test_compute_openhash_deterministic() {
         let h1 = compute_openhash("secret", "abc123", "newsletter-01", "");
@@ -202,4 +294,18 @@ mod tests {
         let token = generate_token();
         assert_eq!(token.len(), 64);
     }
+
+    #[test]
+    fn test_url_signature() {
+        let url = "https://example.com/banner.png";
+        let sig = compute_url_signature("key", url);
+        assert_eq!(sig.len(), 64);
+        assert!(verify_url_signature("key", url, &sig));
+        assert!(!verify_url_signature("other-key", url, &sig));
+        assert!(!verify_url_signature(
+            "key",
+            "https://example.com/other.png",
+            &sig
+        ));
+    }
 }
@@ -0,0 +1,355 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+/// Subscription status filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusFilter {
+    Active,
+    Inactive,
+}
+
+/// Engagement filter, based on open/click events in `email_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engagement {
+    /// Opened or clicked within the last 30 days
+    #[serde(rename = "engaged_30d")]
+    Engaged30d,
+    /// Opened or clicked within the last 90 days
+    #[serde(rename = "engaged_90d")]
+    Engaged90d,
+    /// No open or click within the last 90 days
+    #[serde(rename = "dormant_90d")]
+    Dormant90d,
+    /// Never opened any newsletter
+    NeverOpened,
+}
+
+impl Engagement {
+    pub const ALL: [Self; 4] = [
+        Self::Engaged30d,
+        Self::Engaged90d,
+        Self::Dormant90d,
+        Self::NeverOpened,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Engaged30d => "engaged_30d",
+            Self::Engaged90d => "engaged_90d",
+            Self::Dormant90d => "dormant_90d",
+            Self::NeverOpened => "never_opened",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Engaged30d => "30 天內有開信/點擊",
+            Self::Engaged90d => "90 天內有開信/點擊",
+            Self::Dormant90d => "90 天內無互動",
+            Self::NeverOpened => "從未開信",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.key() == key)
+    }
+}
+
+/// A saved subscriber filter ("smart segment"), stored as JSON in `segments.definition`.
+/// Every field is optional; unset fields do not restrict the result.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusFilter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engagement: Option<Engagement>,
+    /// Signup date range (inclusive, Taiwan time)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signup_from: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signup_to: Option<NaiveDate>,
+}
+
+impl SegmentFilter {
+    /// Append ` AND ...` conditions for this filter. The query must select
+    /// from `subscribers s`.
+    pub fn push_conditions(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        match self.status {
+            Some(StatusFilter::Active) => {
+                qb.push(" AND s.status = true");
+            }
+            Some(StatusFilter::Inactive) => {
+                qb.push(" AND s.status = false");
+            }
+            None => {}
+        }
+        if let Some(verified) = self.verified {
+            qb.push(" AND s.verified_email = ").push_bind(verified);
+        }
+        if let Some(tag) = &self.tag {
+            qb.push(
+                " AND EXISTS (SELECT 1 FROM subscriber_tags st JOIN tags t ON t.id = st.tag_id \
+                 WHERE st.subscriber_id = s.id AND t.name = ",
+            )
+            .push_bind(tag.clone())
+            .push(")");
+        }
+        if let Some(source) = &self.source {
+            qb.push(" AND s.subscription_source = ")
+                .push_bind(source.clone());
+        }
+        if let Some(engagement) = self.engagement {
+            let events =
+                "SELECT 1 FROM email_events e WHERE e.ucode = s.ucode AND NOT e.is_scanner";
+            match engagement {
+                Engagement::Engaged30d => qb.push(format!(
+                    " AND EXISTS ({events} AND e.created_at > NOW() - INTERVAL '30 days')"
+                )),
+                Engagement::Engaged90d => qb.push(format!(
+                    " AND EXISTS ({events} AND e.created_at > NOW() - INTERVAL '90 days')"
+                )),
+                Engagement::Dormant90d => qb.push(format!(
+                    " AND NOT EXISTS ({events} AND e.created_at > NOW() - INTERVAL '90 days')"
+                )),
+                Engagement::NeverOpened => qb.push(format!(
+                    " AND NOT EXISTS ({events} AND e.event_type = 'open')"
+                )),
+            };
+        }
+        if let Some(from) = self.signup_from {
+            qb.push(" AND s.created_at >= (")
+                .push_bind(from)
+                .push("::DATE::TIMESTAMP AT TIME ZONE 'Asia/Taipei')");
+        }
+        if let Some(to) = self.signup_to {
+            qb.push(" AND s.created_at < ((")
+                .push_bind(to)
+                .push("::DATE + 1)::TIMESTAMP AT TIME ZONE 'Asia/Taipei')");
+        }
+    }
+
+    /// Human-readable summary of the active conditions.
+    pub fn describe(&self) -> Vec<String> {
+        let mut parts = Vec::new();
+        match self.status {
+            Some(StatusFilter::Active) => parts.push("訂閱中".to_string()),
+            Some(StatusFilter::Inactive) => parts.push("已取消訂閱".to_string()),
+            None => {}
+        }
+        match self.verified {
+            Some(true) => parts.push("已驗證".to_string()),
+            Some(false) => parts.push("未驗證".to_string()),
+            None => {}
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("標籤：{tag}"));
+        }
+        if let Some(source) = &self.source {
+            parts.push(format!("來源：{source}"));
+        }
+        if let Some(engagement) = self.engagement {
+            parts.push(engagement.label().to_string());
+        }
+        match (self.signup_from, self.signup_to) {
+            (Some(from), Some(to)) => parts.push(format!("訂閱日期 {from} ~ {to}")),
+            (Some(from), None) => parts.push(format!("訂閱日期 {from} 起")),
+            (None, Some(to)) => parts.push(format!("訂閱日期至 {to}")),
+            (None, None) => {}
+        }
+        if parts.is_empty() {
+            parts.push("所有訂閱者".to_string());
+        }
+        parts
+    }
+}
+
+/// Who a newsletter is sent to: its target segment, narrowed to subscribers
+/// with its target tag. Neither set means every subscriber.
+#[derive(Debug, Clone, Default)]
+pub struct SendTarget {
+    pub segment: Option<SegmentFilter>,
+    pub tag_id: Option<uuid::Uuid>,
+}
+
+impl SendTarget {
+    /// Append ` AND ...` conditions, as `SegmentFilter::push_conditions`.
+    pub fn push_conditions(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(filter) = &self.segment {
+            filter.push_conditions(qb);
+        }
+        if let Some(tag_id) = self.tag_id {
+            qb.push(
+                " AND EXISTS (SELECT 1 FROM subscriber_tags st \
+                 WHERE st.subscriber_id = s.id AND st.tag_id = ",
+            )
+            .push_bind(tag_id)
+            .push(")");
+        }
+    }
+}
+
+/// Load a newsletter's send target. `None` if its target segment no longer
+/// exists, which senders treat as an error rather than sending to everyone.
+pub async fn load_target(
+    db: &PgPool,
+    segment_id: Option<uuid::Uuid>,
+    tag_id: Option<uuid::Uuid>,
+) -> Result<Option<SendTarget>, sqlx::Error> {
+    let segment = match segment_id {
+        Some(id) => match load(db, id).await? {
+            Some((_, filter)) => Some(filter),
+            None => return Ok(None),
+        },
+        None => None,
+    };
+    Ok(Some(SendTarget { segment, tag_id }))
+}
+
+/// Load a saved segment's name and filter.
+pub async fn load(
+    db: &PgPool,
+    id: uuid::Uuid,
+) -> Result<Option<(String, SegmentFilter)>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (String, serde_json::Value)>(
+        "SELECT name, definition FROM segments WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|(name, definition)| {
+        let filter = serde_json::from_value(definition).unwrap_or_else(|e| {
+            tracing::warn!("Invalid definition for segment {id}: {e}");
+            SegmentFilter::default()
+        });
+        (name, filter)
+    }))
+}
+
+/// All saved segments as `{id, name}` objects, for select boxes.
+pub async fn options(db: &PgPool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows =
+        sqlx::query_as::<_, (uuid::Uuid, String)>("SELECT id, name FROM segments ORDER BY name")
+            .fetch_all(db)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, name)| serde_json::json!({ "id": id.to_string(), "name": name }))
+        .collect())
+}
+
+/// Count subscribers matching a filter. With `recipients_only`, only
+/// addresses a newsletter would actually be sent to are counted.
+pub async fn count_matching(
+    db: &PgPool,
+    filter: &SegmentFilter,
+    recipients_only: bool,
+) -> Result<i64, sqlx::Error> {
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM subscribers s WHERE ");
+    qb.push(if recipients_only {
+        RECIPIENT_CONDITION
+    } else {
+        "true"
+    });
+    filter.push_conditions(&mut qb);
+    qb.build_query_scalar::<i64>().fetch_one(db).await
+}
+
+/// Base condition for newsletter recipients (subscribed, verified, not bounced).
+pub const RECIPIENT_CONDITION: &str =
+    "s.status = true AND s.verified_email = true AND s.bounced_at IS NULL";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql_for(filter: &SegmentFilter) -> String {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT s.id FROM subscribers s WHERE true");
+        filter.push_conditions(&mut qb);
+        qb.sql().to_string()
+    }
+
+    #[test]
+    fn test_empty_filter_adds_no_conditions() {
+        let filter = SegmentFilter::default();
+        assert_eq!(
+            sql_for(&filter),
+            "SELECT s.id FROM subscribers s WHERE true"
+        );
+        assert_eq!(filter.describe(), vec!["所有訂閱者"]);
+    }
+
+    #[test]
+    fn test_filter_conditions_use_bind_params() {
+        let filter = SegmentFilter {
+            status: Some(StatusFilter::Active),
+            verified: Some(true),
+            tag: Some("volunteer'; --".to_string()),
+            source: Some("registration:attendee".to_string()),
+            engagement: Some(Engagement::Dormant90d),
+            signup_from: NaiveDate::from_ymd_opt(2025, 1, 1),
+            signup_to: NaiveDate::from_ymd_opt(2025, 8, 31),
+        };
+        let sql = sql_for(&filter);
+        assert!(sql.contains("s.status = true"));
+        assert!(sql.contains("s.verified_email = $1"));
+        assert!(sql.contains("t.name = $2"));
+        assert!(sql.contains("s.subscription_source = $3"));
+        assert!(sql.contains("NOT EXISTS (SELECT 1 FROM email_events"));
+        assert!(sql.contains("s.created_at >= ($4"));
+        assert!(sql.contains("s.created_at < (($5"));
+        assert!(!sql.contains("volunteer"));
+    }
+
+    #[test]
+    fn test_definition_json_roundtrip() {
+        let filter = SegmentFilter {
+            tag: Some("attendee".to_string()),
+            engagement: Some(Engagement::Engaged30d),
+            signup_from: NaiveDate::from_ymd_opt(2025, 1, 1),
+            ..SegmentFilter::default()
+        };
+        let json = serde_json::to_value(&filter).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "tag": "attendee",
+                "engagement": "engaged_30d",
+                "signup_from": "2025-01-01",
+            })
+        );
+        let back: SegmentFilter = serde_json::from_value(json).unwrap();
+        assert_eq!(back, filter);
+    }
+
+    #[test]
+    fn test_describe() {
+        let filter = SegmentFilter {
+            status: Some(StatusFilter::Inactive),
+            tag: Some("attendee".to_string()),
+            signup_to: NaiveDate::from_ymd_opt(2025, 8, 31),
+            ..SegmentFilter::default()
+        };
+        assert_eq!(
+            filter.describe(),
+            vec!["已取消訂閱", "標籤：attendee", "訂閱日期至 2025-08-31"]
+        );
+    }
+
+    #[test]
+    fn test_engagement_key_roundtrip() {
+        for e in Engagement::ALL {
+            assert_eq!(Engagement::from_key(e.key()), Some(e));
+        }
+        assert_eq!(Engagement::from_key("nope"), None);
+    }
+}
@@ -0,0 +1,194 @@
+//! The `send_jobs` queue: starting a send inserts a job, and the
+//! `newsletter::send_worker` of any replica claims it with
+//! `FOR UPDATE SKIP LOCKED`.
+//!
+//! A claimed job holds a lease its worker renews while the send runs. When a
+//! process dies mid-send its lease runs out and the job goes back in the
+//! queue; the next run skips whoever was already sent to (see
+//! `newsletter::send_newsletter`).
+
+use sqlx::PgPool;
+
+/// How long a claimed job stays with its worker without a renewal.
+pub const LEASE_SECS: i64 = 60;
+
+/// A job taken after this many leases ran out is failed instead of retried,
+/// e.g. when the send keeps crashing the process.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Identifies this process in `send_jobs.worker`.
+pub fn worker_id() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string());
+    format!("{host}-{}", std::process::id())
+}
+
+/// Queue a send of the newsletter. A send already waiting is not queued
+/// twice; one still running (e.g. finishing its batch after a pause) is
+/// followed by the new one once it ends.
+pub async fn enqueue(db: &PgPool, newsletter_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO send_jobs (newsletter_id) VALUES ($1) \
+         ON CONFLICT (newsletter_id) WHERE state = 'pending' DO NOTHING",
+    )
+    .bind(newsletter_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// A claimed job.
+#[derive(Debug, Clone, Copy)]
+pub struct Claimed {
+    pub id: uuid::Uuid,
+    pub newsletter_id: uuid::Uuid,
+}
+
+/// Take the oldest waiting job whose newsletter isn't being sent already.
+/// Jobs another worker is claiming at the same moment are skipped, not
+/// waited for.
+pub async fn claim(db: &PgPool, worker: &str) -> Result<Option<Claimed>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (uuid::Uuid, uuid::Uuid)>(
+        "UPDATE send_jobs SET state = 'running', worker = $1, attempts = attempts + 1, \
+         locked_until = NOW() + ($2::BIGINT * INTERVAL '1 second'), started_at = NOW() \
+         WHERE id = ( \
+             SELECT id FROM send_jobs j WHERE state = 'pending' \
+             AND NOT EXISTS (SELECT 1 FROM send_jobs r \
+                 WHERE r.newsletter_id = j.newsletter_id AND r.state = 'running') \
+             ORDER BY created_at LIMIT 1 FOR UPDATE SKIP LOCKED) \
+         RETURNING id, newsletter_id",
+    )
+    .bind(worker)
+    .bind(LEASE_SECS)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|(id, newsletter_id)| Claimed { id, newsletter_id }))
+}
+
+/// Extend the lease of a running job; false if it is no longer this
+/// worker's.
+pub async fn renew(db: &PgPool, id: uuid::Uuid, worker: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE send_jobs SET locked_until = NOW() + ($3::BIGINT * INTERVAL '1 second') \
+         WHERE id = $1 AND worker = $2 AND state = 'running'",
+    )
+    .bind(id)
+    .bind(worker)
+    .bind(LEASE_SECS)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record how a job ended.
+pub async fn finish(db: &PgPool, id: uuid::Uuid, error: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE send_jobs SET state = $2, error = $3, locked_until = NULL, finished_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(if error.is_some() { "failed" } else { "done" })
+    .bind(error)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Put running jobs whose lease ran out back in the queue, or fail them
+/// after `MAX_ATTEMPTS` or when another send of the newsletter is already
+/// waiting. Returns the number of jobs requeued.
+pub async fn requeue_expired(db: &PgPool) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        "UPDATE send_jobs j SET state = 'failed', locked_until = NULL, finished_at = NOW(), \
+         error = CASE WHEN attempts >= $1 THEN 'Gave up after ' || attempts || ' attempts' \
+             ELSE 'Lease expired, superseded by a newer send' END \
+         WHERE state = 'running' AND locked_until < NOW() \
+         AND (attempts >= $1 OR EXISTS (SELECT 1 FROM send_jobs p \
+             WHERE p.newsletter_id = j.newsletter_id AND p.state = 'pending'))",
+    )
+    .bind(MAX_ATTEMPTS)
+    .execute(db)
+    .await?;
+
+    let result = sqlx::query(
+        "UPDATE send_jobs SET state = 'pending', worker = NULL, locked_until = NULL \
+         WHERE state = 'running' AND locked_until < NOW()",
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Whether a send of the newsletter is waiting or running.
+pub async fn is_active(db: &PgPool, newsletter_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM send_jobs \
+         WHERE newsletter_id = $1 AND state IN ('pending', 'running'))",
+    )
+    .bind(newsletter_id)
+    .fetch_one(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn newsletter(db: &PgPool) -> uuid::Uuid {
+        crate::db::run_migrations(db).await.unwrap();
+        sqlx::query_scalar(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('T', 'queue-test', '') RETURNING id",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_claim_one_send_per_newsletter(db: PgPool) {
+        let id = newsletter(&db).await;
+        enqueue(&db, id).await.unwrap();
+        enqueue(&db, id).await.unwrap();
+
+        let job = claim(&db, "a").await.unwrap().unwrap();
+        assert_eq!(job.newsletter_id, id);
+        assert!(claim(&db, "b").await.unwrap().is_none());
+
+        // Resumed while the first run is still finishing: waits for it
+        enqueue(&db, id).await.unwrap();
+        assert!(claim(&db, "b").await.unwrap().is_none());
+        finish(&db, job.id, None).await.unwrap();
+        let next = claim(&db, "b").await.unwrap().unwrap();
+        assert_ne!(next.id, job.id);
+        assert!(renew(&db, next.id, "b").await.unwrap());
+        assert!(!renew(&db, next.id, "a").await.unwrap());
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_requeue_expired(db: PgPool) {
+        let id = newsletter(&db).await;
+        enqueue(&db, id).await.unwrap();
+        let job = claim(&db, "a").await.unwrap().unwrap();
+
+        // Still leased
+        assert_eq!(requeue_expired(&db).await.unwrap(), 0);
+
+        let expire = "UPDATE send_jobs SET locked_until = NOW() - INTERVAL '1 second' \
+                      WHERE state = 'running'";
+        sqlx::query(expire).execute(&db).await.unwrap();
+        assert_eq!(requeue_expired(&db).await.unwrap(), 1);
+        let again = claim(&db, "b").await.unwrap().unwrap();
+        assert_eq!(again.id, job.id);
+
+        sqlx::query("UPDATE send_jobs SET attempts = $1")
+            .bind(MAX_ATTEMPTS)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query(expire).execute(&db).await.unwrap();
+        assert_eq!(requeue_expired(&db).await.unwrap(), 0);
+        assert!(!is_active(&db, id).await.unwrap());
+    }
+}
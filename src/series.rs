@@ -0,0 +1,239 @@
+//! Newsletter series: named groups of issues such as "2025 CFP" or 每月摘要.
+//! A series groups its newsletters in the admin list and the public archive,
+//! and new issues created for it start from its default template. Language
+//! editions belong to the series of their newsletter.
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Longest series name accepted.
+const MAX_NAME_CHARS: usize = 50;
+
+/// Longest description accepted.
+const MAX_DESCRIPTION_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub description: String,
+    pub default_template_id: Option<uuid::Uuid>,
+    pub default_template_name: Option<String>,
+    /// Newsletters in the series, not counting language editions
+    pub newsletter_count: i64,
+    /// Of those, the ones sent
+    pub sent_count: i64,
+}
+
+/// Check and normalize the name and description from the form.
+pub fn validate(name: &str, description: &str) -> Result<(String, String), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("請輸入系列名稱".to_string());
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!("系列名稱不可超過 {MAX_NAME_CHARS} 字"));
+    }
+    let description = description.trim();
+    if description.chars().count() > MAX_DESCRIPTION_CHARS {
+        return Err(format!("系列說明不可超過 {MAX_DESCRIPTION_CHARS} 字"));
+    }
+    Ok((name.to_string(), description.to_string()))
+}
+
+type SeriesRow = (
+    uuid::Uuid,
+    String,
+    String,
+    Option<uuid::Uuid>,
+    Option<String>,
+    i64,
+    i64,
+);
+
+fn from_row(
+    (
+        id,
+        name,
+        description,
+        default_template_id,
+        default_template_name,
+        newsletter_count,
+        sent_count,
+    ): SeriesRow,
+) -> Series {
+    Series {
+        id,
+        name,
+        description,
+        default_template_id,
+        default_template_name,
+        newsletter_count,
+        sent_count,
+    }
+}
+
+const SELECT_SQL: &str = "SELECT s.id, s.name, s.description, s.default_template_id, t.name, \
+     COUNT(n.id), COUNT(n.id) FILTER (WHERE n.status = 'sent') \
+     FROM newsletter_series s \
+     LEFT JOIN newsletter_templates t ON t.id = s.default_template_id \
+     LEFT JOIN newsletters n ON n.series_id = s.id AND n.parent_id IS NULL";
+
+/// Every series, by name.
+pub async fn list(db: &PgPool) -> Result<Vec<Series>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, SeriesRow>(&format!(
+        "{SELECT_SQL} GROUP BY s.id, t.name ORDER BY s.name"
+    ))
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+pub async fn find(db: &PgPool, id: uuid::Uuid) -> Result<Option<Series>, sqlx::Error> {
+    let row = sqlx::query_as::<_, SeriesRow>(&format!(
+        "{SELECT_SQL} WHERE s.id = $1 GROUP BY s.id, t.name"
+    ))
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(from_row))
+}
+
+/// The template new issues of a series start from, if it has one.
+pub async fn default_template(
+    db: &PgPool,
+    id: uuid::Uuid,
+) -> Result<Option<uuid::Uuid>, sqlx::Error> {
+    let template_id = sqlx::query_scalar::<_, Option<uuid::Uuid>>(
+        "SELECT default_template_id FROM newsletter_series WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+    Ok(template_id.flatten())
+}
+
+/// Every series with its default template, for select boxes.
+pub async fn options(db: &PgPool) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, Option<uuid::Uuid>)>(
+        "SELECT id, name, default_template_id FROM newsletter_series ORDER BY name",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, default_template_id)| {
+            serde_json::json!({
+                "id": id.to_string(),
+                "name": name,
+                "default_template_id": default_template_id.map(|t| t.to_string()).unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_series() {
+        assert_eq!(
+            validate(" 2025 CFP ", "  徵稿相關公告 ").unwrap(),
+            ("2025 CFP".to_string(), "徵稿相關公告".to_string())
+        );
+        assert!(validate("每月摘要", "").is_ok());
+        assert!(validate(" ", "").is_err());
+        assert!(validate(&"名".repeat(51), "").is_err());
+        assert!(validate("x", &"字".repeat(501)).is_err());
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_series_pages(db: PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let template_id: uuid::Uuid =
+            sqlx::query_scalar("SELECT id FROM newsletter_templates WHERE slug = 'coscup-default'")
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        let template = template_id.to_string();
+
+        let created = app
+            .post_form(
+                "/admin/series/new",
+                &[("name", "2025 CFP"), ("default_template_id", &template)],
+            )
+            .await;
+        assert_eq!(created.status, StatusCode::SEE_OTHER);
+        let again = app
+            .post_form("/admin/series/new", &[("name", " 2025 CFP ")])
+            .await;
+        assert_eq!(again.status, StatusCode::BAD_REQUEST);
+        let series = list(&app.state.db).await.unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].default_template_id, Some(template_id));
+        let series_id = series[0].id.to_string();
+
+        // A new issue without a template takes the series' default
+        app.post_form(
+            "/admin/newsletters/new",
+            &[
+                ("title", "徵稿開跑"),
+                ("markdown_content", "內容"),
+                ("series_id", &series_id),
+            ],
+        )
+        .await;
+        let (id, issue_template): (uuid::Uuid, Option<uuid::Uuid>) = sqlx::query_as(
+            "UPDATE newsletters SET status = 'sent', sent_count = 2, sending_started_at = NOW(), \
+             sending_completed_at = NOW() WHERE series_id = $1 RETURNING id, template_id",
+        )
+        .bind(series[0].id)
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+        assert_eq!(issue_template, Some(template_id));
+        sqlx::query(
+            "INSERT INTO newsletters (title, slug, markdown_content, status, sending_completed_at) \
+             VALUES ('其他', 'other', '內容', 'sent', NOW())",
+        )
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+
+        let admin_list = app
+            .get(&format!("/admin/newsletters?series={series_id}"))
+            .await;
+        assert!(admin_list.body.contains("徵稿開跑"));
+        assert!(!admin_list.body.contains("其他"));
+        let archive = app.get(&format!("/newsletters?series={series_id}")).await;
+        assert!(archive.body.contains("徵稿開跑"));
+        assert!(!archive.body.contains("其他"));
+        assert!(app.get("/newsletters").await.body.contains("其他"));
+
+        let stats = crate::stats::series(&app.state.db, series[0].id)
+            .await
+            .unwrap();
+        assert_eq!(stats.issues.len(), 1);
+        assert_eq!(stats.sent_count, 2);
+        assert_eq!(stats.open_rate, Some(0.0));
+        let page = app.get(&format!("/admin/series/{series_id}")).await;
+        assert_eq!(page.status, StatusCode::OK);
+        assert!(page.body.contains("徵稿開跑"));
+
+        // Deleting the series keeps its newsletters
+        app.post_form(&format!("/admin/series/{series_id}/delete"), &[])
+            .await;
+        let left: Option<uuid::Uuid> =
+            sqlx::query_scalar("SELECT series_id FROM newsletters WHERE id = $1")
+                .bind(id)
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        assert_eq!(left, None);
+    }
+}
@@ -0,0 +1,313 @@
+//! Share links: signed, expiring links to a newsletter that is not sent yet,
+//! e.g. for a sponsor to check their issue before the blast. Admins create and
+//! revoke them on the newsletter page; `/share/{token}` shows the web version
+//! without the public archive's `status = 'sent'` check and without caching it.
+
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::security;
+
+/// Days a new link stays valid unless the admin picks otherwise.
+pub const DEFAULT_TTL_DAYS: i64 = 7;
+
+/// Longest validity an admin can pick.
+pub const MAX_TTL_DAYS: i64 = 30;
+
+/// Longest label accepted.
+const MAX_LABEL_CHARS: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLink {
+    pub id: uuid::Uuid,
+    /// Who the link is for, e.g. the sponsor's name
+    pub label: String,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub view_count: i32,
+    pub last_viewed_at: Option<DateTime<Utc>>,
+}
+
+/// Check and normalize the label and validity from the form.
+pub fn validate(label: &str, ttl_days: Option<i64>) -> Result<(String, i64), String> {
+    let label = label.trim();
+    if label.chars().count() > MAX_LABEL_CHARS {
+        return Err(format!("說明不可超過 {MAX_LABEL_CHARS} 字"));
+    }
+    let ttl_days = ttl_days.unwrap_or(DEFAULT_TTL_DAYS);
+    if !(1..=MAX_TTL_DAYS).contains(&ttl_days) {
+        return Err(format!("有效天數須介於 1 到 {MAX_TTL_DAYS} 天"));
+    }
+    Ok((label.to_string(), ttl_days))
+}
+
+fn token(secret: &str, id: uuid::Uuid, newsletter_id: uuid::Uuid, expires_at: i64) -> String {
+    security::compute_share_token(
+        secret,
+        &id.simple().to_string(),
+        &newsletter_id.simple().to_string(),
+        expires_at,
+    )
+}
+
+/// Split a token into (link id, `expires_at`).
+fn parse_token(token: &str) -> Option<(uuid::Uuid, i64)> {
+    let mut parts = token.split('.');
+    let (id, expires_at, sig) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || sig.len() != 64 {
+        return None;
+    }
+    Some((id.parse().ok()?, expires_at.parse().ok()?))
+}
+
+/// Create a link to `newsletter_id` valid for `ttl_days` and return its token.
+pub async fn create(
+    db: &PgPool,
+    newsletter_id: uuid::Uuid,
+    label: &str,
+    ttl_days: i64,
+    created_by: &str,
+) -> Result<(uuid::Uuid, String), sqlx::Error> {
+    let secret = security::generate_secret_code();
+    // Whole seconds, so the signed timestamp matches the stored one
+    let expires_at = (Utc::now() + chrono::Duration::days(ttl_days)).trunc_subsecs(0);
+    let id = sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO newsletter_share_links (newsletter_id, label, secret, expires_at, created_by) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    )
+    .bind(newsletter_id)
+    .bind(label)
+    .bind(&secret)
+    .bind(expires_at)
+    .bind(created_by)
+    .fetch_one(db)
+    .await?;
+    Ok((
+        id,
+        token(&secret, id, newsletter_id, expires_at.timestamp()),
+    ))
+}
+
+type LinkRow = (
+    uuid::Uuid,
+    String,
+    String,
+    DateTime<Utc>,
+    Option<String>,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+    i32,
+    Option<DateTime<Utc>>,
+);
+
+/// Links of a newsletter, newest first, revoked and expired ones included.
+pub async fn list(db: &PgPool, newsletter_id: uuid::Uuid) -> Result<Vec<ShareLink>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, LinkRow>(
+        "SELECT id, label, secret, expires_at, created_by, created_at, revoked_at, view_count, \
+         last_viewed_at FROM newsletter_share_links WHERE newsletter_id = $1 \
+         ORDER BY created_at DESC",
+    )
+    .bind(newsletter_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(
+                id,
+                label,
+                secret,
+                expires_at,
+                created_by,
+                created_at,
+                revoked_at,
+                view_count,
+                last_viewed_at,
+            )| ShareLink {
+                token: token(&secret, id, newsletter_id, expires_at.timestamp()),
+                id,
+                label,
+                expires_at,
+                created_by,
+                created_at,
+                revoked_at,
+                view_count,
+                last_viewed_at,
+            },
+        )
+        .collect())
+}
+
+/// Revoke a link of `newsletter_id`. Returns whether it was live until now.
+pub async fn revoke(
+    db: &PgPool,
+    newsletter_id: uuid::Uuid,
+    id: uuid::Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE newsletter_share_links SET revoked_at = NOW() \
+         WHERE id = $1 AND newsletter_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(newsletter_id)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The newsletter a token opens and when the link expires, counting the
+/// view. `None` when the token is malformed, forged, expired or revoked.
+pub async fn open(
+    db: &PgPool,
+    token_str: &str,
+) -> Result<Option<(uuid::Uuid, DateTime<Utc>)>, sqlx::Error> {
+    let Some((id, expires_at)) = parse_token(token_str) else {
+        return Ok(None);
+    };
+    if expires_at <= Utc::now().timestamp() {
+        return Ok(None);
+    }
+    let row = sqlx::query_as::<_, (uuid::Uuid, String, DateTime<Utc>)>(
+        "SELECT newsletter_id, secret, expires_at FROM newsletter_share_links \
+         WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+    let Some((newsletter_id, secret, stored_expiry)) = row else {
+        return Ok(None);
+    };
+    let expected = token(&secret, id, newsletter_id, expires_at);
+    if !security::constant_time_eq(token_str, &expected) || stored_expiry.timestamp() != expires_at
+    {
+        return Ok(None);
+    }
+    sqlx::query(
+        "UPDATE newsletter_share_links SET view_count = view_count + 1, last_viewed_at = NOW() \
+         WHERE id = $1",
+    )
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(Some((newsletter_id, stored_expiry)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_share_link() {
+        assert_eq!(
+            validate(" 贊助商 A ", None).unwrap(),
+            ("贊助商 A".to_string(), DEFAULT_TTL_DAYS)
+        );
+        assert_eq!(validate("", Some(30)).unwrap().1, 30);
+        assert!(validate("", Some(0)).is_err());
+        assert!(validate("", Some(31)).is_err());
+        assert!(validate(&"字".repeat(101), None).is_err());
+    }
+
+    #[test]
+    fn test_share_token() {
+        let id = uuid::Uuid::new_v4();
+        let newsletter_id = uuid::Uuid::new_v4();
+        let t = token("secret", id, newsletter_id, 1_900_000_000);
+        assert_eq!(parse_token(&t), Some((id, 1_900_000_000)));
+        // Bound to the secret, the newsletter and the expiry
+        assert_ne!(t, token("other", id, newsletter_id, 1_900_000_000));
+        assert_ne!(t, token("secret", id, uuid::Uuid::new_v4(), 1_900_000_000));
+        assert_ne!(t, token("secret", id, newsletter_id, 1_900_000_001));
+        assert_eq!(parse_token("abc.123"), None);
+        assert_eq!(parse_token(&format!("{t}.x")), None);
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_share_links(db: PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestStateBuilder::new(db)
+            .config(|c| c.rate_limit_share_ip = crate::rate_limit::RateLimitRule::new(3, 3600))
+            .build();
+        app.migrate().await;
+        app.login_as("admin@coscup.org").await;
+        let (newsletter_id, slug): (uuid::Uuid, String) = sqlx::query_as(
+            "INSERT INTO newsletters (title, slug, markdown_content) \
+             VALUES ('贊助商搶先看', 'sponsor-preview', '感謝贊助') RETURNING id, slug",
+        )
+        .fetch_one(&app.state.db)
+        .await
+        .unwrap();
+
+        let created = app
+            .post_form(
+                &format!("/admin/newsletters/{newsletter_id}/share-links"),
+                &[("label", "贊助商 A"), ("ttl_days", "3")],
+            )
+            .await;
+        assert_eq!(created.status, StatusCode::SEE_OTHER);
+        let links = list(&app.state.db, newsletter_id).await.unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].label, "贊助商 A");
+        let page = app
+            .get(&format!("/admin/newsletters/{newsletter_id}"))
+            .await;
+        assert!(page.body.contains(&links[0].token));
+
+        // The draft opens through the link only, and is not cached for the archive
+        let shared = app.get(&format!("/share/{}", links[0].token)).await;
+        assert_eq!(shared.status, StatusCode::OK);
+        assert!(shared.body.contains("感謝贊助"));
+        assert!(shared.body.contains("noindex"));
+        assert_eq!(shared.headers["cache-control"], "no-store");
+        let archive = app.get(&format!("/newsletters/{slug}")).await;
+        assert!(!archive.body.contains("感謝贊助"));
+        let mut forged = links[0].token.clone();
+        let last = forged.pop().unwrap();
+        forged.push(if last == '0' { '1' } else { '0' });
+        assert_eq!(
+            app.get(&format!("/share/{forged}")).await.status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            list(&app.state.db, newsletter_id).await.unwrap()[0].view_count,
+            1
+        );
+
+        app.post_form(
+            &format!(
+                "/admin/newsletters/{newsletter_id}/share-links/{}/revoke",
+                links[0].id
+            ),
+            &[],
+        )
+        .await;
+        assert_eq!(
+            app.get(&format!("/share/{}", links[0].token)).await.status,
+            StatusCode::NOT_FOUND
+        );
+
+        // Fourth view from the same IP within the hour
+        assert_eq!(
+            app.get(&format!("/share/{}", links[0].token)).await.status,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // Once sent, the archive has it
+        sqlx::query(
+            "UPDATE newsletters SET status = 'sent', sending_started_at = NOW(), \
+             sending_completed_at = NOW() WHERE id = $1",
+        )
+        .bind(newsletter_id)
+        .execute(&app.state.db)
+        .await
+        .unwrap();
+        let archive = app.get(&format!("/newsletters/{slug}")).await;
+        assert!(archive.body.contains("感謝贊助"));
+    }
+}
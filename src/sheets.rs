@@ -0,0 +1,407 @@
+//! Subscriber sync from Google Sheets, e.g. the responses sheet of a signup
+//! form volunteers share during an event. Sheets are added on
+//! `/admin/subscribers/sheets` and synced every `SHEET_SYNC_INTERVAL_SECS`
+//! like a registration export (see `registration`): new addresses become
+//! subscribers, everyone gets the sheet's tag, and rows go through the same
+//! validation as a file import, role addresses included.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::import::{self, ImportFormat, ImportRow};
+use crate::jobs::JobKind;
+use crate::registration::{RegistrationSource, SyncError, SyncStats};
+use crate::AppState;
+
+/// The CSV export URL for a Google Sheets URL, as copied from the address
+/// bar (the sheet must be shared with anyone who has the link) or from
+/// "Publish to the web". The tab in `gid` is kept. Other hosts are refused,
+/// so the server only ever fetches from Google.
+pub fn csv_export_url(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|_| "無效的網址".to_string())?;
+    if parsed.scheme() != "https" || parsed.host_str() != Some("docs.google.com") {
+        return Err(
+            "只支援 Google 試算表網址（https://docs.google.com/spreadsheets/...）".to_string(),
+        );
+    }
+    let gid = parsed
+        .query_pairs()
+        .find(|(key, _)| key == "gid")
+        .map(|(_, value)| value.into_owned())
+        .or_else(|| {
+            parsed
+                .fragment()
+                .and_then(|f| f.split('&').find_map(|p| p.strip_prefix("gid=")))
+                .map(str::to_string)
+        })
+        .filter(|gid| !gid.is_empty() && gid.chars().all(|c| c.is_ascii_digit()));
+    let is_id = |id: &str| {
+        !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+
+    let segments: Vec<&str> = parsed
+        .path_segments()
+        .map_or_else(Vec::new, Iterator::collect);
+    match segments.as_slice() {
+        ["spreadsheets", "d", "e", id, ..] if is_id(id) => {
+            let tab = gid.map_or_else(String::new, |gid| format!("&single=true&gid={gid}"));
+            Ok(format!(
+                "https://docs.google.com/spreadsheets/d/e/{id}/pub?output=csv{tab}"
+            ))
+        }
+        ["spreadsheets", "d", id, ..] if is_id(id) => {
+            let tab = gid.map_or_else(String::new, |gid| format!("&gid={gid}"));
+            Ok(format!(
+                "https://docs.google.com/spreadsheets/d/{id}/export?format=csv{tab}"
+            ))
+        }
+        _ => Err("找不到試算表 ID，請貼上試算表的網址".to_string()),
+    }
+}
+
+/// A sheet on `/admin/subscribers/sheets` and how its last sync went.
+#[derive(Debug, Serialize)]
+pub struct Sheet {
+    pub id: uuid::Uuid,
+    pub url: String,
+    pub csv_url: String,
+    pub tag: String,
+    pub allow_role_accounts: bool,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_fetched: Option<i32>,
+    pub last_created: Option<i32>,
+    pub last_tagged: Option<i32>,
+    pub last_invalid: Option<i32>,
+    pub last_error: Option<String>,
+}
+
+type SheetRow = (
+    uuid::Uuid,
+    String,
+    String,
+    String,
+    bool,
+    Option<String>,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+    Option<i32>,
+    Option<i32>,
+    Option<i32>,
+    Option<i32>,
+    Option<String>,
+);
+
+const SHEET_COLUMNS: &str = "id, url, csv_url, tag, allow_role_accounts, created_by, created_at, \
+     last_synced_at, last_fetched, last_created, last_tagged, last_invalid, last_error";
+
+fn from_row(row: SheetRow) -> Sheet {
+    let (
+        id,
+        url,
+        csv_url,
+        tag,
+        allow_role_accounts,
+        created_by,
+        created_at,
+        last_synced_at,
+        last_fetched,
+        last_created,
+        last_tagged,
+        last_invalid,
+        last_error,
+    ) = row;
+    Sheet {
+        id,
+        url,
+        csv_url,
+        tag,
+        allow_role_accounts,
+        created_by,
+        created_at,
+        last_synced_at,
+        last_fetched,
+        last_created,
+        last_tagged,
+        last_invalid,
+        last_error,
+    }
+}
+
+pub async fn list(db: &PgPool) -> Result<Vec<Sheet>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, SheetRow>(&format!(
+        "SELECT {SHEET_COLUMNS} FROM sheet_imports ORDER BY created_at"
+    ))
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+pub async fn find(db: &PgPool, id: uuid::Uuid) -> Result<Option<Sheet>, sqlx::Error> {
+    let row = sqlx::query_as::<_, SheetRow>(&format!(
+        "SELECT {SHEET_COLUMNS} FROM sheet_imports WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(from_row))
+}
+
+/// Add a sheet; `None` if its export URL is already there.
+pub async fn add(
+    db: &PgPool,
+    url: &str,
+    csv_url: &str,
+    tag: &str,
+    allow_role_accounts: bool,
+    created_by: &str,
+) -> Result<Option<uuid::Uuid>, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO sheet_imports (url, csv_url, tag, allow_role_accounts, created_by) \
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (csv_url) DO NOTHING RETURNING id",
+    )
+    .bind(url)
+    .bind(csv_url)
+    .bind(tag)
+    .bind(allow_role_accounts)
+    .bind(created_by)
+    .fetch_optional(db)
+    .await
+}
+
+/// Stop syncing a sheet. Subscribers it added and their tags stay.
+pub async fn remove(db: &PgPool, id: uuid::Uuid) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("DELETE FROM sheet_imports WHERE id = $1 RETURNING url")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+/// A sheet's CSV export as a registration source.
+pub struct SheetSource {
+    tag: String,
+    csv_url: String,
+    block_role_accounts: bool,
+    client: reqwest::Client,
+}
+
+impl SheetSource {
+    pub fn new(sheet: &Sheet, block_role_accounts: bool) -> Self {
+        Self {
+            tag: sheet.tag.clone(),
+            csv_url: sheet.csv_url.clone(),
+            block_role_accounts: block_role_accounts && !sheet.allow_role_accounts,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl RegistrationSource for SheetSource {
+    fn segment(&self) -> &str {
+        &self.tag
+    }
+
+    async fn fetch(&self) -> Result<Vec<ImportRow>, SyncError> {
+        let body = self
+            .client
+            .get(&self.csv_url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| SyncError::FetchFailed(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| SyncError::FetchFailed(e.to_string()))?;
+        // A sheet that isn't shared comes back as Google's sign-in page
+        if body.trim_start().starts_with('<') {
+            return Err(SyncError::FetchFailed(
+                "Got a web page instead of CSV; is the sheet shared with anyone who has the link?"
+                    .to_string(),
+            ));
+        }
+        import::parse_payload(ImportFormat::Csv, &body).map_err(SyncError::ParseFailed)
+    }
+
+    fn subscription_source(&self) -> String {
+        format!("sheet:{}", self.tag)
+    }
+
+    fn block_role_accounts(&self) -> bool {
+        self.block_role_accounts
+    }
+}
+
+/// Store the outcome of a sync on the sheet. Counts are kept from the last
+/// successful sync when this one failed.
+async fn record_result(db: &PgPool, id: uuid::Uuid, result: &Result<SyncStats, SyncError>) {
+    let (stats, error) = match result {
+        Ok(stats) => (Some(stats), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let count = |f: fn(&SyncStats) -> usize| stats.map(|s| i32::try_from(f(s)).unwrap_or(i32::MAX));
+    let updated = sqlx::query(
+        "UPDATE sheet_imports SET last_synced_at = NOW(), last_error = $2, \
+         last_fetched = COALESCE($3, last_fetched), last_created = COALESCE($4, last_created), \
+         last_tagged = COALESCE($5, last_tagged), last_invalid = COALESCE($6, last_invalid) \
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(error)
+    .bind(count(|s| s.fetched))
+    .bind(count(|s| s.created))
+    .bind(count(|s| s.tagged))
+    .bind(count(|s| s.invalid))
+    .execute(db)
+    .await;
+    if let Err(e) = updated {
+        tracing::warn!("Failed to record sheet sync result: {e}");
+    }
+}
+
+/// Sync one sheet as a recorded job and store the outcome on it.
+pub async fn sync_sheet(state: &AppState, sheet: &Sheet) -> Result<SyncStats, SyncError> {
+    let source = SheetSource::new(sheet, state.config.block_role_accounts);
+    let sync = crate::registration::sync_source(&state.db, &source);
+    let result =
+        crate::jobs::track(&state.db, JobKind::SheetSync, Some(sheet.tag.clone()), sync).await;
+    record_result(&state.db, sheet.id, &result).await;
+    result
+}
+
+/// Sync every sheet once, logging (and auditing) the result of each.
+pub async fn sync_all(state: &AppState) {
+    let sheets = match list(&state.db).await {
+        Ok(sheets) => sheets,
+        Err(e) => {
+            tracing::error!("Failed to load sheets to sync: {e}");
+            return;
+        }
+    };
+    for sheet in sheets {
+        match sync_sheet(state, &sheet).await {
+            Ok(result) => {
+                tracing::info!(
+                    "Sheet sync [{}]: {} fetched, {} created, {} newly tagged, {} invalid",
+                    sheet.tag,
+                    result.fetched,
+                    result.created,
+                    result.tagged,
+                    result.invalid
+                );
+                crate::audit::log(
+                    &state.db,
+                    "system",
+                    "subscriber.sheet_sync",
+                    Some(serde_json::json!({
+                        "sheet_id": sheet.id.to_string(),
+                        "tag": sheet.tag,
+                        "fetched": result.fetched,
+                        "created": result.created,
+                        "tagged": result.tagged,
+                        "invalid": result.invalid,
+                    })),
+                    None,
+                )
+                .await;
+            }
+            Err(e) => tracing::error!("Sheet sync [{}] failed: {e}", sheet.tag),
+        }
+    }
+}
+
+/// Background loop that syncs all sheets every `interval_secs`.
+pub async fn sheet_sync_loop(state: AppState, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(60)));
+    loop {
+        interval.tick().await;
+        sync_all(&state).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_export_url() {
+        assert_eq!(
+            csv_export_url("https://docs.google.com/spreadsheets/d/1AbC-_x/edit#gid=123").unwrap(),
+            "https://docs.google.com/spreadsheets/d/1AbC-_x/export?format=csv&gid=123"
+        );
+        assert_eq!(
+            csv_export_url("https://docs.google.com/spreadsheets/d/1AbC/edit?usp=sharing").unwrap(),
+            "https://docs.google.com/spreadsheets/d/1AbC/export?format=csv"
+        );
+        assert_eq!(
+            csv_export_url(
+                "https://docs.google.com/spreadsheets/d/e/2PACX-1v/pubhtml?gid=0&single=true"
+            )
+            .unwrap(),
+            "https://docs.google.com/spreadsheets/d/e/2PACX-1v/pub?output=csv&single=true&gid=0"
+        );
+        assert!(csv_export_url("https://evil.example/spreadsheets/d/1AbC/edit").is_err());
+        assert!(csv_export_url("http://docs.google.com/spreadsheets/d/1AbC/edit").is_err());
+        assert!(csv_export_url("https://docs.google.com/document/d/1AbC/edit").is_err());
+        assert!(csv_export_url("not a url").is_err());
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_sheets_page(db: PgPool) {
+        use axum::http::StatusCode;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.login_as("admin@coscup.org").await;
+        let url = "https://docs.google.com/spreadsheets/d/1AbC/edit#gid=7";
+
+        let added = app
+            .post_form(
+                "/admin/subscribers/sheets",
+                &[("url", url), ("tag", "volunteer")],
+            )
+            .await;
+        assert_eq!(added.status, StatusCode::SEE_OTHER);
+        let again = app
+            .post_form(
+                "/admin/subscribers/sheets",
+                &[("url", url), ("tag", "other")],
+            )
+            .await;
+        assert_eq!(again.status, StatusCode::BAD_REQUEST);
+        let other_host = app
+            .post_form(
+                "/admin/subscribers/sheets",
+                &[("url", "https://example.com/a.csv"), ("tag", "x")],
+            )
+            .await;
+        assert_eq!(other_host.status, StatusCode::BAD_REQUEST);
+
+        let sheets = list(&app.state.db).await.unwrap();
+        assert_eq!(sheets.len(), 1);
+        assert_eq!(sheets[0].tag, "volunteer");
+        let page = app.get("/admin/subscribers/sheets").await;
+        assert!(page.body.contains("volunteer"));
+        assert!(page.body.contains("尚未同步"));
+
+        // A failed sync is recorded on the sheet
+        let failed = Err(SyncError::FetchFailed("timeout".to_string()));
+        record_result(&app.state.db, sheets[0].id, &failed).await;
+        let sheet = find(&app.state.db, sheets[0].id).await.unwrap().unwrap();
+        assert!(sheet.last_error.unwrap().contains("timeout"));
+        assert_eq!(sheet.last_fetched, None);
+
+        app.post_form(
+            &format!("/admin/subscribers/sheets/{}/delete", sheet.id),
+            &[],
+        )
+        .await;
+        assert!(list(&app.state.db).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+//! Shortcodes in newsletter content, expanded by the `shortcodes` render
+//! hook when an edition is rendered for sending, so recurring phrases stay
+//! correct without editors recalculating them each issue:
+//!
+//! - `{{countdown}}`, `{{countdown to="2025-08-09"}}`: days left until the
+//!   date (the event's first day by default), `0` once it is reached
+//! - `{{event_dates}}`: the event dates from `EVENT_START_DATE` and
+//!   `EVENT_END_DATE`, e.g. `2025 年 8 月 9 日（六）至 8 月 10 日（日）`
+//! - `{{sponsors}}` (or `%sponsors%`): the sponsor block
+//!
+//! Shortcodes that can't be resolved (unknown name, bad date, event dates
+//! not configured) are left as written, so they stand out in the preview.
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use regex::{Captures, Regex};
+
+use crate::config::AppConfig;
+
+/// The event, as configured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventDates {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl EventDates {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            start: config.event_start_date,
+            end: config.event_end_date,
+        }
+    }
+}
+
+/// What shortcodes resolve against.
+pub struct Values<'a> {
+    /// The day the newsletter is sent (Taiwan time)
+    pub today: NaiveDate,
+    pub event: EventDates,
+    pub sponsor_block: &'a str,
+}
+
+/// Shortcodes whose output is a block; on a line of their own they replace
+/// the paragraph around them.
+const BLOCK_SHORTCODES: [&str; 1] = ["sponsors"];
+
+pub fn expand(html: &str, values: &Values<'_>) -> String {
+    let html = crate::sponsors::expand_shortcode(html, values.sponsor_block);
+    if !html.contains("{{") {
+        return html;
+    }
+    let re = Regex::new(r"(<p>)?\{\{\s*([a-z_]+)([^{}]*)\}\}(</p>)?").expect("valid regex");
+    re.replace_all(&html, |caps: &Captures<'_>| {
+        let name = &caps[2];
+        let Some(value) = resolve(name, &parse_args(&caps[3]), values) else {
+            return caps[0].to_string();
+        };
+        let (open, close) = (
+            caps.get(1).map_or("", |m| m.as_str()),
+            caps.get(4).map_or("", |m| m.as_str()),
+        );
+        if BLOCK_SHORTCODES.contains(&name) && !open.is_empty() && !close.is_empty() {
+            value
+        } else {
+            format!("{open}{value}{close}")
+        }
+    })
+    .into_owned()
+}
+
+/// `key="value"` pairs. Markdown rendering escapes the quotes, so entities
+/// are decoded first.
+fn parse_args(args: &str) -> Vec<(String, String)> {
+    let args = crate::html_rewrite::decode_attr(args);
+    let re = Regex::new(r#"([a-z_]+)\s*=\s*"([^"]*)""#).expect("valid regex");
+    re.captures_iter(&args)
+        .map(|c| (c[1].to_string(), c[2].trim().to_string()))
+        .collect()
+}
+
+fn resolve(name: &str, args: &[(String, String)], values: &Values<'_>) -> Option<String> {
+    let arg = |key: &str| args.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    match name {
+        "countdown" => {
+            let to = match arg("to") {
+                Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?,
+                None => values.event.start?,
+            };
+            Some(days_until(values.today, to).to_string())
+        }
+        "event_dates" => {
+            let start = values.event.start?;
+            Some(format_date_range(start, values.event.end.unwrap_or(start)))
+        }
+        "sponsors" => Some(values.sponsor_block.to_string()),
+        _ => None,
+    }
+}
+
+/// Whole days from `today` until `date`, never negative.
+fn days_until(today: NaiveDate, date: NaiveDate) -> i64 {
+    (date - today).num_days().max(0)
+}
+
+fn weekday_label(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "一",
+        Weekday::Tue => "二",
+        Weekday::Wed => "三",
+        Weekday::Thu => "四",
+        Weekday::Fri => "五",
+        Weekday::Sat => "六",
+        Weekday::Sun => "日",
+    }
+}
+
+/// `2025 年 8 月 9 日（六）`, without the year when `with_year` is false.
+fn format_date(date: NaiveDate, with_year: bool) -> String {
+    let day = format!(
+        "{} 月 {} 日（{}）",
+        date.month(),
+        date.day(),
+        weekday_label(date.weekday())
+    );
+    if with_year {
+        format!("{} 年 {day}", date.year())
+    } else {
+        day
+    }
+}
+
+/// One day, or `first至 last` with the year repeated only when it changes.
+fn format_date_range(start: NaiveDate, end: NaiveDate) -> String {
+    if end <= start {
+        return format_date(start, true);
+    }
+    format!(
+        "{}至 {}",
+        format_date(start, true),
+        format_date(end, end.year() != start.year())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn values(sponsor_block: &str) -> Values<'_> {
+        Values {
+            today: date(2025, 7, 28),
+            event: EventDates {
+                start: Some(date(2025, 8, 9)),
+                end: Some(date(2025, 8, 10)),
+            },
+            sponsor_block,
+        }
+    }
+
+    #[test]
+    fn test_countdown() {
+        let v = values("");
+        assert_eq!(expand("<p>{{countdown}}</p>", &v), "<p>12</p>");
+        assert_eq!(
+            expand(
+                "<p>還有 {{ countdown to=&quot;2025-07-30&quot; }} 天</p>",
+                &v
+            ),
+            "<p>還有 2 天</p>"
+        );
+        assert_eq!(
+            expand(r#"{{countdown to="2025-07-01"}}"#, &v),
+            "0",
+            "past dates count down to zero"
+        );
+        assert_eq!(
+            expand(r#"{{countdown to="next week"}}"#, &v),
+            r#"{{countdown to="next week"}}"#
+        );
+    }
+
+    #[test]
+    fn test_event_dates() {
+        let v = values("");
+        assert_eq!(
+            expand("<p>COSCUP 將於 {{event_dates}} 舉行</p>", &v),
+            "<p>COSCUP 將於 2025 年 8 月 9 日（六）至 8 月 10 日（日） 舉行</p>"
+        );
+        assert_eq!(
+            format_date_range(date(2025, 8, 9), date(2025, 8, 9)),
+            "2025 年 8 月 9 日（六）"
+        );
+        assert_eq!(
+            format_date_range(date(2025, 12, 31), date(2026, 1, 1)),
+            "2025 年 12 月 31 日（三）至 2026 年 1 月 1 日（四）"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_shortcodes_are_kept() {
+        let v = Values {
+            today: date(2025, 7, 28),
+            event: EventDates::default(),
+            sponsor_block: "",
+        };
+        for html in [
+            "{{countdown}}",
+            "{{event_dates}}",
+            "{{ recipient }}",
+            "{{x}",
+        ] {
+            assert_eq!(expand(html, &v), html);
+        }
+    }
+
+    #[test]
+    fn test_block_shortcode_replaces_its_paragraph() {
+        let v = values("<div>S</div>");
+        assert_eq!(
+            expand("<p>a</p>\n<p>{{ sponsors }}</p>\n<p>%sponsors%</p>", &v),
+            "<p>a</p>\n<div>S</div>\n<div>S</div>"
+        );
+        assert_eq!(
+            expand("<p>感謝 {{sponsors}}</p>", &v),
+            "<p>感謝 <div>S</div></p>"
+        );
+        assert_eq!(
+            expand("<p>{{countdown}}</p>", &v),
+            "<p>12</p>",
+            "inline shortcodes keep their paragraph"
+        );
+    }
+}
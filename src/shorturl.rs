@@ -1,8 +1,11 @@
 use async_trait::async_trait;
 use serde::Deserialize;
+use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Mutex;
 
+use crate::jobs::JobKind;
+
 #[derive(Debug, thiserror::Error)]
 pub enum ShortUrlError {
     #[error("Failed to shorten URL: {0}")]
@@ -15,9 +18,107 @@ pub enum ShortUrlError {
 #[async_trait]
 pub trait ShortUrlService: Send + Sync {
     async fn shorten(&self, url: &str) -> Result<String, ShortUrlError>;
+    /// Shorten with a custom slug (e.g. `s.coscup.org/cfp2025`).
+    async fn shorten_custom(&self, url: &str, keyword: &str) -> Result<String, ShortUrlError>;
     async fn get_clicks(&self, short_url: &str) -> Result<u64, ShortUrlError>;
 }
 
+/// An additional short-link domain (its own YOURLS instance) that a
+/// newsletter can choose instead of the default one.
+#[derive(Clone)]
+pub struct ShortDomain {
+    pub domain: String,
+    pub service: std::sync::Arc<dyn ShortUrlService>,
+}
+
+/// Parse `SHORT_DOMAINS`: comma-separated `domain|api_url|signature` entries.
+/// Malformed entries are skipped with a warning.
+pub fn parse_short_domains(spec: &str) -> Vec<(String, String, String)> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parts: Vec<&str> = entry.split('|').map(str::trim).collect();
+            match parts.as_slice() {
+                [domain, api_url, signature]
+                    if !domain.is_empty() && !api_url.is_empty() && !signature.is_empty() =>
+                {
+                    Some((
+                        domain.to_lowercase(),
+                        (*api_url).to_string(),
+                        (*signature).to_string(),
+                    ))
+                }
+                _ => {
+                    tracing::warn!("Ignoring malformed SHORT_DOMAINS entry: {entry}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Host name of a YOURLS API URL, used to label the default short domain.
+pub fn domain_of(api_url: &str) -> Option<String> {
+    reqwest::Url::parse(api_url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+}
+
+/// Whether a custom slug is acceptable: 1-50 ASCII letters, digits, `-` or `_`.
+pub fn is_valid_slug(slug: &str) -> bool {
+    (1..=50).contains(&slug.len())
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parse the custom slug field of the newsletter form: one `slug URL` pair per
+/// line. Returns a URL → slug map, or a message describing the first bad line.
+pub fn parse_custom_slugs(
+    text: &str,
+) -> Result<std::collections::BTreeMap<String, String>, String> {
+    let mut by_url = std::collections::BTreeMap::new();
+    let mut slugs = std::collections::HashSet::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let line_no = idx + 1;
+        let Some((slug, url)) = line.split_once(char::is_whitespace) else {
+            return Err(format!("第 {line_no} 行格式應為「slug 網址」"));
+        };
+        let url = url.trim();
+        if !is_valid_slug(slug) {
+            return Err(format!(
+                "第 {line_no} 行：slug 只能使用英數字、- 與 _（最多 50 字）"
+            ));
+        }
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!(
+                "第 {line_no} 行：網址需以 http:// 或 https:// 開頭"
+            ));
+        }
+        if !slugs.insert(slug.to_lowercase()) {
+            return Err(format!("第 {line_no} 行：slug「{slug}」重複"));
+        }
+        if by_url.insert(url.to_string(), slug.to_string()).is_some() {
+            return Err(format!("第 {line_no} 行：網址重複設定"));
+        }
+    }
+    Ok(by_url)
+}
+
+/// Inverse of `parse_custom_slugs`, for pre-filling the form.
+pub fn format_custom_slugs(by_url: &std::collections::BTreeMap<String, String>) -> String {
+    by_url
+        .iter()
+        .map(|(url, slug)| format!("{slug} {url}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 // --- YOURLS implementation ---
 
 pub struct YourlsService {
@@ -112,6 +213,35 @@ impl ShortUrlService for YourlsService {
         Ok(short_url)
     }
 
+    async fn shorten_custom(&self, url: &str, keyword: &str) -> Result<String, ShortUrlError> {
+        let resp = self
+            .client
+            .post(&self.api_url)
+            .form(&[
+                ("action", "shorturl"),
+                ("url", url),
+                ("keyword", keyword),
+                ("format", "json"),
+                ("signature", &self.signature),
+            ])
+            .send()
+            .await
+            .map_err(|e| ShortUrlError::ShortenFailed(e.to_string()))?;
+
+        let body = resp
+            .json::<YourlsShortenResponse>()
+            .await
+            .map_err(|e| ShortUrlError::ShortenFailed(e.to_string()))?;
+
+        // YOURLS reports a taken keyword as a failure without a short URL
+        body.shorturl.ok_or_else(|| {
+            ShortUrlError::ShortenFailed(
+                body.message
+                    .unwrap_or_else(|| format!("Keyword {keyword} not available")),
+            )
+        })
+    }
+
     async fn get_clicks(&self, short_url: &str) -> Result<u64, ShortUrlError> {
         let resp = self
             .client
@@ -141,6 +271,91 @@ impl ShortUrlService for YourlsService {
     }
 }
 
+// --- Click sync ---
+
+/// The service that issued `short_url`: the extra short domain it is on, or
+/// the default YOURLS instance.
+fn service_for<'a>(
+    short_url: &str,
+    default: &'a dyn ShortUrlService,
+    domains: &'a [ShortDomain],
+) -> &'a dyn ShortUrlService {
+    domain_of(short_url)
+        .and_then(|host| domains.iter().find(|d| d.domain == host))
+        .map_or(default, |d| d.service.as_ref())
+}
+
+/// Copy YOURLS click counts into `newsletter_links` for newsletters sent in
+/// the last `window_days` days (or still sending). Returns how many links
+/// were updated; fails only when every lookup failed.
+pub async fn sync_clicks(
+    db: &PgPool,
+    default: &dyn ShortUrlService,
+    domains: &[ShortDomain],
+    window_days: i32,
+) -> Result<usize, String> {
+    let links = sqlx::query_as::<_, (uuid::Uuid, String)>(
+        "SELECT l.id, l.short_url FROM newsletter_links l \
+         JOIN newsletters e ON e.id = l.newsletter_id \
+         JOIN newsletters n ON n.id = COALESCE(e.parent_id, e.id) \
+         WHERE l.short_url <> l.original_url \
+         AND n.status IN ('sending', 'paused', 'sent') \
+         AND COALESCE(n.sending_completed_at, NOW()) > NOW() - make_interval(days => $1)",
+    )
+    .bind(window_days)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut updated = 0;
+    let mut last_error = None;
+    for (id, short_url) in &links {
+        let clicks = match service_for(short_url, default, domains)
+            .get_clicks(short_url)
+            .await
+        {
+            Ok(clicks) => i64::try_from(clicks).unwrap_or(i64::MAX),
+            Err(e) => {
+                tracing::warn!("Failed to fetch clicks of {short_url}: {e}");
+                last_error = Some(e.to_string());
+                continue;
+            }
+        };
+        sqlx::query(
+            "UPDATE newsletter_links SET short_clicks = $1, short_clicks_synced_at = NOW() WHERE id = $2",
+        )
+        .bind(clicks)
+        .bind(id)
+        .execute(db)
+        .await
+        .map_err(|e| e.to_string())?;
+        updated += 1;
+    }
+
+    match last_error {
+        Some(e) if updated == 0 => Err(format!("{} 個短網址全部查詢失敗：{e}", links.len())),
+        _ => Ok(updated),
+    }
+}
+
+/// Background loop syncing short-link click counts every `interval_secs`.
+pub async fn click_sync_loop(
+    db: PgPool,
+    default: std::sync::Arc<dyn ShortUrlService>,
+    domains: Vec<ShortDomain>,
+    interval_secs: u64,
+    window_days: i32,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(60)));
+    loop {
+        interval.tick().await;
+        let sync = sync_clicks(&db, default.as_ref(), &domains, window_days);
+        if let Err(e) = crate::jobs::track(&db, JobKind::ShortLinkSync, None, sync).await {
+            tracing::error!("Short-link click sync failed: {e}");
+        }
+    }
+}
+
 // --- Mock implementation for testing ---
 
 #[cfg(test)]
@@ -167,6 +382,16 @@ pub mod tests {
             Ok(format!("https://s.coscup.org/test_{}", url.len()))
         }
 
+        async fn shorten_custom(&self, url: &str, keyword: &str) -> Result<String, ShortUrlError> {
+            if self.should_fail {
+                return Err(ShortUrlError::ShortenFailed("mock failure".to_string()));
+            }
+            if let Ok(mut calls) = self.shorten_calls.lock() {
+                calls.push(url.to_string());
+            }
+            Ok(format!("https://s.coscup.org/{keyword}"))
+        }
+
         async fn get_clicks(&self, _short_url: &str) -> Result<u64, ShortUrlError> {
             if self.should_fail {
                 return Err(ShortUrlError::StatsFailed("mock failure".to_string()));
@@ -202,4 +427,47 @@ pub mod tests {
         let result = svc.shorten("https://example.com").await;
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_short_domains() {
+        let domains = parse_short_domains(
+            "S.COSCUP.ORG|https://s.coscup.org/yourls-api.php|abc, bad-entry, go.coscup.org|https://go.coscup.org/yourls-api.php|def",
+        );
+        assert_eq!(
+            domains,
+            vec![
+                (
+                    "s.coscup.org".to_string(),
+                    "https://s.coscup.org/yourls-api.php".to_string(),
+                    "abc".to_string()
+                ),
+                (
+                    "go.coscup.org".to_string(),
+                    "https://go.coscup.org/yourls-api.php".to_string(),
+                    "def".to_string()
+                ),
+            ]
+        );
+        assert!(parse_short_domains("").is_empty());
+        assert_eq!(
+            domain_of("https://S.coscup.org/yourls-api.php").as_deref(),
+            Some("s.coscup.org")
+        );
+    }
+
+    #[test]
+    fn test_parse_custom_slugs() {
+        let map = parse_custom_slugs(
+            "cfp2025 https://coscup.org/2025/cfp\n\n  volunteer_2025   https://coscup.org/2025/volunteer \n",
+        )
+        .unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["https://coscup.org/2025/cfp"], "cfp2025");
+        assert_eq!(parse_custom_slugs(&format_custom_slugs(&map)).unwrap(), map);
+
+        assert!(parse_custom_slugs("cfp2025").is_err());
+        assert!(parse_custom_slugs("cfp/2025 https://coscup.org").is_err());
+        assert!(parse_custom_slugs("cfp ftp://coscup.org").is_err());
+        assert!(parse_custom_slugs("cfp https://a.org\nCFP https://b.org").is_err());
+    }
 }
@@ -0,0 +1,168 @@
+//! Nightly subscriber count snapshots and the dashboard's growth chart.
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::jobs::JobKind;
+
+/// Days of history shown on the dashboard.
+pub const CHART_DAYS: i32 = 365;
+
+/// Size of the chart's SVG viewBox.
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 160.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub date: NaiveDate,
+    pub total: i64,
+    pub active: i64,
+    pub verified: i64,
+}
+
+/// SVG polyline points for each series, scaled to the largest total.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct GrowthChart {
+    pub width: f64,
+    pub height: f64,
+    pub total: String,
+    pub active: String,
+    pub verified: String,
+    pub max: i64,
+    pub first_date: String,
+    pub last_date: String,
+    pub days: usize,
+}
+
+/// Store today's counts as the snapshot for `date`; running again the same
+/// day overwrites it.
+pub async fn record(db: &PgPool, date: NaiveDate) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO subscriber_snapshots (snapshot_date, total, active, verified) \
+         SELECT $1, COUNT(*), COUNT(*) FILTER (WHERE status = true), \
+         COUNT(*) FILTER (WHERE verified_email = true) FROM subscribers \
+         ON CONFLICT (snapshot_date) DO UPDATE SET total = EXCLUDED.total, \
+         active = EXCLUDED.active, verified = EXCLUDED.verified, created_at = NOW()",
+    )
+    .bind(date)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Snapshots of the last `days` days, oldest first.
+pub async fn history(db: &PgPool, days: i32) -> Result<Vec<Snapshot>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (NaiveDate, i64, i64, i64)>(
+        "SELECT snapshot_date, total, active, verified FROM subscriber_snapshots \
+         WHERE snapshot_date > CURRENT_DATE - $1 ORDER BY snapshot_date",
+    )
+    .bind(days)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(date, total, active, verified)| Snapshot {
+            date,
+            total,
+            active,
+            verified,
+        })
+        .collect())
+}
+
+/// Chart of `snapshots` (oldest first), or `None` with fewer than two days
+/// to draw a line between.
+pub fn growth_chart(snapshots: &[Snapshot]) -> Option<GrowthChart> {
+    let (first, last) = (snapshots.first()?, snapshots.last()?);
+    if snapshots.len() < 2 {
+        return None;
+    }
+    let max = snapshots.iter().map(|s| s.total).max().unwrap_or(0).max(1);
+    #[allow(clippy::cast_precision_loss)]
+    let points = |value: fn(&Snapshot) -> i64| {
+        let step = CHART_WIDTH / (snapshots.len() - 1) as f64;
+        snapshots
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let x = i as f64 * step;
+                let y = CHART_HEIGHT - value(s) as f64 / max as f64 * CHART_HEIGHT;
+                format!("{x:.1},{y:.1}")
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    Some(GrowthChart {
+        width: CHART_WIDTH,
+        height: CHART_HEIGHT,
+        total: points(|s| s.total),
+        active: points(|s| s.active),
+        verified: points(|s| s.verified),
+        max,
+        first_date: first.date.to_string(),
+        last_date: last.date.to_string(),
+        days: snapshots.len(),
+    })
+}
+
+/// Background loop recording, just after midnight Taiwan time, the counts
+/// at the end of the day that just ended.
+pub async fn snapshot_loop(db: PgPool) {
+    loop {
+        let next = crate::backup::next_run_at(Utc::now(), 0);
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let date = next.with_timezone(&crate::timezone::DEFAULT).date_naive() - Duration::days(1);
+        let snapshot = record(&db, date);
+        if let Err(e) = crate::jobs::track(
+            &db,
+            JobKind::SubscriberSnapshot,
+            Some(date.to_string()),
+            snapshot,
+        )
+        .await
+        {
+            tracing::error!("Subscriber snapshot for {date} failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(day: u32, total: i64, active: i64, verified: i64) -> Snapshot {
+        Snapshot {
+            date: NaiveDate::from_ymd_opt(2025, 8, day).unwrap(),
+            total,
+            active,
+            verified,
+        }
+    }
+
+    #[test]
+    fn test_growth_chart() {
+        let chart = growth_chart(&[
+            snapshot(1, 100, 80, 50),
+            snapshot(2, 150, 120, 75),
+            snapshot(3, 200, 160, 100),
+        ])
+        .unwrap();
+        assert_eq!(chart.max, 200);
+        assert_eq!(chart.total, "0.0,80.0 300.0,40.0 600.0,0.0");
+        assert_eq!(chart.verified, "0.0,120.0 300.0,100.0 600.0,80.0");
+        assert_eq!(chart.first_date, "2025-08-01");
+        assert_eq!(chart.last_date, "2025-08-03");
+        assert_eq!(chart.days, 3);
+    }
+
+    #[test]
+    fn test_growth_chart_needs_two_days() {
+        assert_eq!(growth_chart(&[]), None);
+        assert_eq!(growth_chart(&[snapshot(1, 10, 10, 10)]), None);
+        let empty = growth_chart(&[snapshot(1, 0, 0, 0), snapshot(2, 0, 0, 0)]).unwrap();
+        assert_eq!(empty.total, "0.0,160.0 600.0,160.0");
+    }
+}
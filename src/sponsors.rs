@@ -0,0 +1,362 @@
+//! Sponsors and partners, rendered into newsletters by the `%sponsors%`
+//! shortcode while their active period lasts.
+//!
+//! Logos link to `/sp/{id}`, which redirects to the sponsor. Clicks on that
+//! link go through the usual click tracking, so each sponsor's clicks can be
+//! told apart from the same URL linked elsewhere in the newsletter, and
+//! survive the sponsor's URL being edited.
+
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Shortcode replaced with the sponsor block.
+pub const SHORTCODE: &str = "%sponsors%";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    Diamond,
+    Platinum,
+    Gold,
+    Silver,
+    Bronze,
+    Partner,
+}
+
+impl Tier {
+    /// Highest first, the order of the sponsor block.
+    pub const ALL: [Self; 6] = [
+        Self::Diamond,
+        Self::Platinum,
+        Self::Gold,
+        Self::Silver,
+        Self::Bronze,
+        Self::Partner,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Diamond => "diamond",
+            Self::Platinum => "platinum",
+            Self::Gold => "gold",
+            Self::Silver => "silver",
+            Self::Bronze => "bronze",
+            Self::Partner => "partner",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Diamond => "鑽石級贊助",
+            Self::Platinum => "白金級贊助",
+            Self::Gold => "黃金級贊助",
+            Self::Silver => "白銀級贊助",
+            Self::Bronze => "青銅級贊助",
+            Self::Partner => "合作夥伴",
+        }
+    }
+
+    /// Logo height in the sponsor block, in pixels.
+    fn logo_height(self) -> u32 {
+        match self {
+            Self::Diamond => 80,
+            Self::Platinum => 64,
+            Self::Gold => 56,
+            Self::Silver => 48,
+            Self::Bronze | Self::Partner => 40,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sponsor {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub logo_url: String,
+    pub url: String,
+    pub tier: Tier,
+    /// First and last day shown, inclusive; `None` is open-ended
+    pub starts_on: Option<NaiveDate>,
+    pub ends_on: Option<NaiveDate>,
+}
+
+impl Sponsor {
+    pub fn is_active_on(&self, date: NaiveDate) -> bool {
+        self.starts_on.is_none_or(|d| d <= date) && self.ends_on.is_none_or(|d| d >= date)
+    }
+}
+
+/// Clicks on one sponsor's logo in one newsletter.
+#[derive(Debug, Serialize)]
+pub struct SponsorClicks {
+    pub sponsor_id: uuid::Uuid,
+    pub newsletter_id: uuid::Uuid,
+    pub title: String,
+    pub slug: String,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+type SponsorRow = (
+    uuid::Uuid,
+    String,
+    String,
+    String,
+    String,
+    Option<NaiveDate>,
+    Option<NaiveDate>,
+);
+
+const COLUMNS: &str = "id, name, logo_url, url, tier, starts_on, ends_on";
+
+fn from_row((id, name, logo_url, url, tier, starts_on, ends_on): SponsorRow) -> Sponsor {
+    Sponsor {
+        id,
+        name,
+        logo_url,
+        url,
+        tier: Tier::parse(&tier).unwrap_or(Tier::Partner),
+        starts_on,
+        ends_on,
+    }
+}
+
+/// Block order: by tier, then name.
+fn sort(sponsors: &mut [Sponsor]) {
+    sponsors.sort_by(|a, b| a.tier.cmp(&b.tier).then_with(|| a.name.cmp(&b.name)));
+}
+
+pub async fn all(db: &PgPool) -> Result<Vec<Sponsor>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, SponsorRow>(&format!("SELECT {COLUMNS} FROM sponsors"))
+        .fetch_all(db)
+        .await?;
+    let mut sponsors: Vec<Sponsor> = rows.into_iter().map(from_row).collect();
+    sort(&mut sponsors);
+    Ok(sponsors)
+}
+
+pub async fn load(db: &PgPool, id: uuid::Uuid) -> Result<Option<Sponsor>, sqlx::Error> {
+    let row =
+        sqlx::query_as::<_, SponsorRow>(&format!("SELECT {COLUMNS} FROM sponsors WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(db)
+            .await?;
+    Ok(row.map(from_row))
+}
+
+/// Sponsors shown on `date`, in block order.
+pub async fn active_on(db: &PgPool, date: NaiveDate) -> Result<Vec<Sponsor>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, SponsorRow>(&format!(
+        "SELECT {COLUMNS} FROM sponsors \
+         WHERE (starts_on IS NULL OR starts_on <= $1) AND (ends_on IS NULL OR ends_on >= $1)"
+    ))
+    .bind(date)
+    .fetch_all(db)
+    .await?;
+    let mut sponsors: Vec<Sponsor> = rows.into_iter().map(from_row).collect();
+    sort(&mut sponsors);
+    Ok(sponsors)
+}
+
+/// Sponsors shown today, Taiwan time.
+pub async fn active_today(db: &PgPool) -> Result<Vec<Sponsor>, sqlx::Error> {
+    active_on(db, crate::timezone::today()).await
+}
+
+/// The tracked link to a sponsor.
+pub fn link(base_url: &str, id: uuid::Uuid) -> String {
+    format!("{base_url}/sp/{id}")
+}
+
+/// The sponsor block: logos grouped under their tier, highest first.
+/// Empty without sponsors.
+pub fn render_block(sponsors: &[Sponsor], base_url: &str) -> String {
+    if sponsors.is_empty() {
+        return String::new();
+    }
+    let mut html =
+        String::from(r#"<div class="sponsors" style="text-align:center;margin:24px 0;">"#);
+    for tier in Tier::ALL {
+        let logos: Vec<String> = sponsors
+            .iter()
+            .filter(|s| s.tier == tier)
+            .map(|s| {
+                format!(
+                    r#"<a href="{}"><img src="{}" alt="{}" style="display:inline-block;max-height:{}px;margin:8px 12px;vertical-align:middle;"></a>"#,
+                    link(base_url, s.id),
+                    escape_attr(&s.logo_url),
+                    escape_attr(&s.name),
+                    tier.logo_height(),
+                )
+            })
+            .collect();
+        if logos.is_empty() {
+            continue;
+        }
+        let _ = write!(
+            html,
+            r#"<p style="margin:16px 0 4px;font-size:13px;color:#666;">{}</p><p style="margin:0;">{}</p>"#,
+            tier.label(),
+            logos.concat()
+        );
+    }
+    html.push_str("</div>");
+    html
+}
+
+/// Escape an attribute value; unlike `tera::escape_html`, `/` is kept so
+/// the later render hooks still see the URL as written.
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Replace the shortcode with the sponsor block. A shortcode on a line of
+/// its own (its own paragraph) is replaced paragraph and all.
+pub fn expand_shortcode(html: &str, block: &str) -> String {
+    if !html.contains(SHORTCODE) {
+        return html.to_string();
+    }
+    html.replace(&format!("<p>{SHORTCODE}</p>"), block)
+        .replace(SHORTCODE, block)
+}
+
+/// Tracked, non-scanner clicks on each sponsor's logo, per newsletter.
+/// Shortened logo links are followed back through `newsletter_links`.
+pub async fn click_report(db: &PgPool, base_url: &str) -> Result<Vec<SponsorClicks>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, uuid::Uuid, String, String, i64, i64)>(
+        "WITH sponsor_links AS ( \
+             SELECT id AS sponsor_id, $1 || '/sp/' || id::text AS url FROM sponsors \
+             UNION \
+             SELECT s.id, l.short_url FROM sponsors s \
+             JOIN newsletter_links l ON l.original_url = $1 || '/sp/' || s.id::text \
+         ) \
+         SELECT sl.sponsor_id, n.id, n.title, n.slug, COUNT(*), COUNT(DISTINCT e.ucode) \
+         FROM email_events e \
+         JOIN sponsor_links sl ON sl.url = e.clicked_url \
+         JOIN newsletters n ON n.id = e.newsletter_id \
+         WHERE e.event_type = 'click' AND NOT e.is_scanner \
+         GROUP BY sl.sponsor_id, n.id, n.title, n.slug \
+         ORDER BY MAX(n.sent_at) DESC NULLS LAST, n.title",
+    )
+    .bind(base_url)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(sponsor_id, newsletter_id, title, slug, clicks, unique_clicks)| SponsorClicks {
+                sponsor_id,
+                newsletter_id,
+                title,
+                slug,
+                clicks,
+                unique_clicks,
+            },
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sponsor(name: &str, tier: Tier) -> Sponsor {
+        Sponsor {
+            id: uuid::Uuid::nil(),
+            name: name.to_string(),
+            logo_url: format!("https://coscup.org/logos/{name}.png"),
+            url: format!("https://{name}.example/"),
+            tier,
+            starts_on: None,
+            ends_on: None,
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 8, day).unwrap()
+    }
+
+    #[test]
+    fn test_tier_roundtrip() {
+        for tier in Tier::ALL {
+            assert_eq!(Tier::parse(tier.as_str()), Some(tier));
+        }
+        assert_eq!(Tier::parse("titanium"), None);
+    }
+
+    #[test]
+    fn test_is_active_on_is_inclusive() {
+        let mut s = sponsor("a", Tier::Gold);
+        assert!(s.is_active_on(date(1)));
+        s.starts_on = Some(date(2));
+        s.ends_on = Some(date(9));
+        assert!(!s.is_active_on(date(1)));
+        assert!(s.is_active_on(date(2)));
+        assert!(s.is_active_on(date(9)));
+        assert!(!s.is_active_on(date(10)));
+    }
+
+    #[test]
+    fn test_render_block_groups_by_tier() {
+        let mut sponsors = vec![
+            sponsor("zeta", Tier::Partner),
+            sponsor("beta", Tier::Gold),
+            sponsor("alpha", Tier::Gold),
+            sponsor("gamma", Tier::Diamond),
+        ];
+        sort(&mut sponsors);
+        let html = render_block(&sponsors, "https://newsletter.coscup.org");
+        let order: Vec<usize> = [
+            "鑽石級",
+            "gamma",
+            "黃金級",
+            "alpha",
+            "beta",
+            "合作夥伴",
+            "zeta",
+        ]
+        .iter()
+        .map(|s| html.find(s).unwrap())
+        .collect();
+        assert!(order.windows(2).all(|w| w[0] < w[1]));
+        assert!(!html.contains("白金級"));
+        assert!(html.contains(&format!(
+            r#"href="https://newsletter.coscup.org/sp/{}""#,
+            uuid::Uuid::nil()
+        )));
+        assert!(!html.contains("zeta.example"));
+        assert_eq!(render_block(&[], "https://newsletter.coscup.org"), "");
+    }
+
+    #[test]
+    fn test_render_block_escapes() {
+        let mut s = sponsor("a", Tier::Gold);
+        s.name = r#"A "&" B"#.to_string();
+        let html = render_block(&[s], "");
+        assert!(html.contains(r#"alt="A &quot;&amp;&quot; B""#));
+    }
+
+    #[test]
+    fn test_expand_shortcode() {
+        assert_eq!(
+            expand_shortcode("<p>hi</p>\n<p>%sponsors%</p>\n", "<div>S</div>"),
+            "<p>hi</p>\n<div>S</div>\n"
+        );
+        assert_eq!(
+            expand_shortcode("<p>感謝 %sponsors%</p>", "<b>S</b>"),
+            "<p>感謝 <b>S</b></p>"
+        );
+        assert_eq!(expand_shortcode("<p>%sponsors%</p>", ""), "");
+        assert_eq!(expand_shortcode("<p>100%</p>", "S"), "<p>100%</p>");
+    }
+}
@@ -0,0 +1,790 @@
+//! Newsletter and overview aggregates, shared by the admin stats pages and
+//! the JSON stats API.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct LinkClicks {
+    pub url: String,
+    /// Anchor text of the link in the newsletter, empty when not found
+    pub text: String,
+    /// Clicks through `/r/c` tracking
+    pub clicks: i64,
+    /// Clicks YOURLS counted on the short link, including ones that didn't
+    /// go through tracking (forwarded mails, the web archive, shared links);
+    /// `None` until synced or for links that weren't shortened
+    pub short_clicks: Option<i64>,
+    /// Marked as a primary call to action
+    pub is_cta: bool,
+}
+
+/// Tracked (non-scanner) clicks on one primary call-to-action link.
+#[derive(Debug, Serialize)]
+pub struct CtaStats {
+    pub url: String,
+    pub text: String,
+    pub goal: Option<i64>,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+    /// Unique clicks per sent email, in percent
+    pub click_through_rate: Option<f64>,
+    /// Unique clicks as a percentage of the goal
+    pub goal_progress: Option<f64>,
+}
+
+/// A newsletter's calls to action together: each link, and the subscribers
+/// who clicked any of them.
+#[derive(Debug, Default, Serialize)]
+pub struct CtaSummary {
+    pub links: Vec<CtaStats>,
+    pub unique_clicks: i64,
+    pub click_through_rate: Option<f64>,
+}
+
+/// One sent newsletter with calls to action, for comparing issues.
+#[derive(Debug, Serialize)]
+pub struct CtaComparison {
+    pub id: uuid::Uuid,
+    pub title: String,
+    pub sent_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub sent_count: i32,
+    pub open_rate: Option<f64>,
+    pub cta: CtaSummary,
+    pub goals: usize,
+    pub goals_reached: usize,
+}
+
+/// What YOURLS reported for one short link.
+struct ShortLink {
+    original_url: String,
+    clicks: Option<i64>,
+}
+
+/// Recipients, unique opens and unique (non-scanner) clicks of one language
+/// edition.
+#[derive(Debug, Serialize)]
+pub struct EditionStats {
+    pub lang: String,
+    pub sent: i64,
+    pub opens: i64,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewsletterStats {
+    pub id: uuid::Uuid,
+    pub title: String,
+    pub status: String,
+    pub sent_count: i32,
+    pub failed_count: i32,
+    pub total_count: i32,
+    pub deferred_count: i32,
+    pub unique_opens: i64,
+    /// Unique opens per sent email, in percent; `None` before anything is sent
+    pub open_rate: Option<f64>,
+    /// Clicks flagged as link scanners are left out of the click totals
+    pub total_clicks: i64,
+    pub unique_clicks: i64,
+    pub scanner_clicks: i64,
+    /// Calls to action marked by editors; empty links when none
+    pub cta: CtaSummary,
+    /// Unsubscribed from everything
+    pub unsubscribe_count: i64,
+    /// Stopped only the newsletter's list topic
+    pub topic_unsubscribe_count: i64,
+    pub links: Vec<LinkClicks>,
+    /// Last time short-link clicks were synced from YOURLS
+    pub short_clicks_synced_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Empty for a newsletter without language editions
+    pub editions: Vec<EditionStats>,
+}
+
+/// One row of the stats page's newsletter table.
+#[derive(Debug, Serialize)]
+pub struct NewsletterSummary {
+    pub id: uuid::Uuid,
+    pub title: String,
+    pub sent_count: i32,
+    pub unique_opens: i64,
+    pub open_rate: Option<f64>,
+}
+
+/// Events recorded by topic, for events not linked to a newsletter.
+#[derive(Debug, Serialize)]
+pub struct TopicEvents {
+    pub topic: String,
+    pub event_type: String,
+    pub count: i64,
+}
+
+/// One issue of a series, for comparing it with the others.
+#[derive(Debug, Serialize)]
+pub struct SeriesIssue {
+    pub id: uuid::Uuid,
+    pub title: String,
+    pub status: String,
+    /// When sending started
+    pub sent_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub sent_count: i32,
+    pub unique_opens: i64,
+    pub open_rate: Option<f64>,
+    pub unique_clicks: i64,
+    pub click_through_rate: Option<f64>,
+    pub unsubscribe_count: i64,
+}
+
+/// A series' sent issues added up. The rates are over every email the series
+/// sent, so a large issue weighs more than a small one.
+#[derive(Debug, Serialize)]
+pub struct SeriesStats {
+    pub series_id: uuid::Uuid,
+    pub issues: Vec<SeriesIssue>,
+    pub sent_count: i64,
+    pub unique_opens: i64,
+    pub open_rate: Option<f64>,
+    pub unique_clicks: i64,
+    pub click_through_rate: Option<f64>,
+    pub unsubscribe_count: i64,
+    /// Subscribers who were sent at least one issue
+    pub recipients: i64,
+    /// Subscribers who opened at least one issue
+    pub readers: i64,
+}
+
+/// Unique opens per sent email, in percent.
+pub fn open_rate(unique_opens: i64, sent_count: i32) -> Option<f64> {
+    #[allow(clippy::cast_precision_loss)]
+    (sent_count > 0).then(|| (unique_opens as f64 / f64::from(sent_count)) * 100.0)
+}
+
+/// Unique clicks per sent email, in percent; the same ratio as `open_rate`.
+pub fn click_through_rate(unique_clicks: i64, sent_count: i32) -> Option<f64> {
+    open_rate(unique_clicks, sent_count)
+}
+
+/// How far unique clicks got towards a goal, in percent.
+pub fn goal_progress(unique_clicks: i64, goal: Option<i64>) -> Option<f64> {
+    #[allow(clippy::cast_precision_loss)]
+    goal.filter(|g| *g > 0)
+        .map(|g| unique_clicks as f64 / g as f64 * 100.0)
+}
+
+/// Rate as shown on the admin pages, e.g. `42.5%`.
+pub fn format_rate(rate: Option<f64>) -> String {
+    rate.map_or_else(|| "—".to_string(), |r| format!("{r:.1}%"))
+}
+
+/// Extract link text from rendered HTML: URL → anchor text. The first text
+/// seen for a URL wins.
+fn link_texts<'a>(htmls: impl Iterator<Item = &'a String>) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let re = regex::Regex::new(r#"<a\s[^>]*href="(https?://[^"]+)"[^>]*>(.*?)</a>"#)
+        .expect("valid regex");
+    let strip_tags = regex::Regex::new(r"<[^>]+>").expect("valid regex");
+    for html in htmls {
+        for caps in re.captures_iter(html) {
+            // Links are stored and tracked with entities decoded
+            let url = crate::html_rewrite::decode_attr(&caps[1]);
+            // Strip HTML tags from link text (e.g. <img> inside <a>)
+            let text = strip_tags.replace_all(&caps[2], "").trim().to_string();
+            if !text.is_empty() {
+                map.entry(url).or_insert(text);
+            }
+        }
+    }
+    map
+}
+
+/// Tracked clicks per URL, with the short-link clicks of shortened URLs.
+/// Short links YOURLS counted clicks on that never came through tracking are
+/// listed after the tracked ones. Text and calls to action are looked up by
+/// the original URL, since the stored HTML is from before shortening.
+fn merge_link_clicks(
+    tracked: Vec<(String, i64)>,
+    short_links: &HashMap<String, ShortLink>,
+    texts: &HashMap<String, String>,
+    ctas: &[crate::cta::CtaLink],
+) -> Vec<LinkClicks> {
+    let original_of = |url: &str| {
+        short_links
+            .get(url)
+            .map_or(url, |l| l.original_url.as_str())
+            .to_string()
+    };
+    let text_of = |url: &str| texts.get(&original_of(url)).cloned().unwrap_or_default();
+    let is_cta = |url: &str| {
+        let original = original_of(url);
+        ctas.iter().any(|c| crate::cta::matches(&c.url, &original))
+    };
+    let mut links: Vec<LinkClicks> = tracked
+        .into_iter()
+        .map(|(url, clicks)| LinkClicks {
+            text: text_of(&url),
+            short_clicks: short_links.get(&url).and_then(|l| l.clicks),
+            is_cta: is_cta(&url),
+            url,
+            clicks,
+        })
+        .collect();
+
+    let mut untracked: Vec<LinkClicks> = short_links
+        .iter()
+        .filter(|(url, link)| {
+            link.clicks.is_some_and(|c| c > 0) && !links.iter().any(|l| &l.url == *url)
+        })
+        .map(|(url, link)| LinkClicks {
+            url: url.clone(),
+            text: text_of(url),
+            clicks: 0,
+            short_clicks: link.clicks,
+            is_cta: is_cta(url),
+        })
+        .collect();
+    untracked.sort_by(|a, b| b.short_clicks.cmp(&a.short_clicks).then(a.url.cmp(&b.url)));
+    links.extend(untracked);
+    links
+}
+
+/// Tracked URLs of a newsletter mapped back to the URL in its content:
+/// short links to their original, everything else to itself.
+async fn clicked_originals(
+    db: &PgPool,
+    id: uuid::Uuid,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String)>(
+        "SELECT DISTINCT ev.clicked_url, COALESCE(l.original_url, ev.clicked_url) \
+         FROM email_events ev \
+         LEFT JOIN LATERAL ( \
+             SELECT l.original_url FROM newsletter_links l JOIN newsletters e ON e.id = l.newsletter_id \
+             WHERE (e.id = $1 OR e.parent_id = $1) AND l.short_url = ev.clicked_url LIMIT 1 \
+         ) l ON true \
+         WHERE ev.newsletter_id = $1 AND ev.event_type = 'click' AND ev.clicked_url IS NOT NULL",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await
+}
+
+/// Clicks on each call to action of a newsletter. `texts` maps content URLs
+/// to their anchor text.
+pub async fn cta_summary(
+    db: &PgPool,
+    id: uuid::Uuid,
+    sent_count: i32,
+    ctas: &[crate::cta::CtaLink],
+    texts: &HashMap<String, String>,
+) -> Result<CtaSummary, sqlx::Error> {
+    if ctas.is_empty() {
+        return Ok(CtaSummary::default());
+    }
+    let originals = clicked_originals(db, id).await?;
+    let count = |urls: Vec<String>| {
+        sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*), COUNT(DISTINCT ucode) FROM email_events \
+             WHERE newsletter_id = $1 AND event_type = 'click' AND NOT is_scanner \
+             AND clicked_url = ANY($2)",
+        )
+        .bind(id)
+        .bind(urls)
+        .fetch_one(db)
+    };
+
+    let mut links = Vec::with_capacity(ctas.len());
+    let mut all_urls = Vec::new();
+    for cta in ctas {
+        let urls: Vec<String> = originals
+            .iter()
+            .filter(|(_, original)| crate::cta::matches(&cta.url, original))
+            .map(|(clicked, _)| clicked.clone())
+            .collect();
+        all_urls.extend(urls.iter().cloned());
+        let (clicks, unique_clicks) = count(urls).await?;
+        links.push(CtaStats {
+            url: cta.url.clone(),
+            text: texts
+                .iter()
+                .find(|(url, _)| crate::cta::matches(&cta.url, url))
+                .map(|(_, text)| text.clone())
+                .unwrap_or_default(),
+            goal: cta.goal,
+            clicks,
+            unique_clicks,
+            click_through_rate: click_through_rate(unique_clicks, sent_count),
+            goal_progress: goal_progress(unique_clicks, cta.goal),
+        });
+    }
+    let (_, unique_clicks) = count(all_urls).await?;
+    Ok(CtaSummary {
+        links,
+        unique_clicks,
+        click_through_rate: click_through_rate(unique_clicks, sent_count),
+    })
+}
+
+/// Sent and sending newsletters with calls to action, newest first, for
+/// comparing their click-through across issues.
+pub async fn cta_comparison(db: &PgPool) -> Result<Vec<CtaComparison>, sqlx::Error> {
+    #[allow(clippy::type_complexity)]
+    let rows = sqlx::query_as::<
+        _,
+        (
+            uuid::Uuid,
+            String,
+            Option<chrono::DateTime<chrono::Utc>>,
+            i32,
+            serde_json::Value,
+        ),
+    >(
+        "SELECT id, title, sent_at, sent_count, cta_links FROM newsletters \
+         WHERE status IN ('sent', 'sending') AND parent_id IS NULL AND cta_links IS NOT NULL \
+         ORDER BY COALESCE(sent_at, created_at) DESC",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut comparison = Vec::with_capacity(rows.len());
+    for (id, title, sent_at, sent_count, cta_links) in rows {
+        let ctas = crate::cta::from_json(Some(&cta_links));
+        let cta = cta_summary(db, id, sent_count, &ctas, &HashMap::new()).await?;
+        let unique_opens: i64 = sqlx::query_scalar(
+            "SELECT COUNT(DISTINCT ucode) FROM email_events WHERE newsletter_id = $1 AND event_type = 'open'",
+        )
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+        let goals = cta.links.iter().filter(|l| l.goal.is_some()).count();
+        let goals_reached = cta
+            .links
+            .iter()
+            .filter(|l| l.goal.is_some_and(|g| l.unique_clicks >= g))
+            .count();
+        comparison.push(CtaComparison {
+            id,
+            title,
+            sent_at,
+            sent_count,
+            open_rate: open_rate(unique_opens, sent_count),
+            cta,
+            goals,
+            goals_reached,
+        });
+    }
+    Ok(comparison)
+}
+
+/// Every edition is tracked under the primary newsletter.
+async fn edition_stats(db: &PgPool, id: uuid::Uuid) -> Result<Vec<EditionStats>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, i64, i64, i64)>(
+        "SELECT e.lang, COUNT(DISTINCT ns.subscriber_id), \
+         COUNT(DISTINCT ev.ucode) FILTER (WHERE ev.event_type = 'open'), \
+         COUNT(DISTINCT ev.ucode) FILTER (WHERE ev.event_type = 'click' AND NOT ev.is_scanner) \
+         FROM newsletter_sends ns \
+         JOIN newsletters e ON e.id = COALESCE(ns.edition_id, ns.newsletter_id) \
+         JOIN subscribers s ON s.id = ns.subscriber_id \
+         LEFT JOIN email_events ev ON ev.ucode = s.ucode AND ev.newsletter_id = ns.newsletter_id \
+         WHERE ns.newsletter_id = $1 AND ns.status = 'sent' \
+         GROUP BY e.lang ORDER BY COUNT(DISTINCT ns.subscriber_id) DESC",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(lang, sent, opens, clicks)| EditionStats {
+            lang,
+            sent,
+            opens,
+            clicks,
+        })
+        .collect())
+}
+
+/// Delivery, open, click and unsubscribe numbers of one newsletter, or `None`
+/// when it does not exist.
+#[allow(clippy::too_many_lines)]
+pub async fn newsletter(
+    db: &PgPool,
+    id: uuid::Uuid,
+) -> Result<Option<NewsletterStats>, sqlx::Error> {
+    #[allow(clippy::type_complexity)]
+    let Some(row) = sqlx::query_as::<
+        _,
+        (
+            String,
+            String,
+            i32,
+            i32,
+            i32,
+            i32,
+            Option<String>,
+            Option<serde_json::Value>,
+        ),
+    >(
+        "SELECT title, status, sent_count, failed_count, total_count, deferred_count, rendered_html, cta_links \
+         FROM newsletters WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let (
+        title,
+        status,
+        sent_count,
+        failed_count,
+        total_count,
+        deferred_count,
+        rendered_html,
+        cta_links,
+    ) = row;
+    let ctas = crate::cta::from_json(cta_links.as_ref());
+
+    // Language editions link to their own pages, so their anchor text counts too
+    let edition_htmls = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT rendered_html FROM newsletters WHERE parent_id = $1 ORDER BY lang",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let link_text_map = link_texts(
+        std::iter::once(&rendered_html)
+            .chain(&edition_htmls)
+            .flatten(),
+    );
+
+    let unique_opens: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT ucode) FROM email_events WHERE newsletter_id = $1 AND event_type = 'open'",
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await?;
+
+    let tracked = sqlx::query_as::<_, (String, i64)>(
+        "SELECT clicked_url, COUNT(*) as clicks FROM email_events \
+         WHERE newsletter_id = $1 AND event_type = 'click' AND clicked_url IS NOT NULL AND NOT is_scanner \
+         GROUP BY clicked_url ORDER BY clicks DESC",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    // A link is stored again each time an edition is prepared, e.g. on resume
+    let short_rows = sqlx::query_as::<
+        _,
+        (
+            String,
+            String,
+            Option<i64>,
+            Option<chrono::DateTime<chrono::Utc>>,
+        ),
+    >(
+        "SELECT l.short_url, MIN(l.original_url), MAX(l.short_clicks), MAX(l.short_clicks_synced_at) \
+         FROM newsletter_links l JOIN newsletters e ON e.id = l.newsletter_id \
+         WHERE (e.id = $1 OR e.parent_id = $1) AND l.short_url <> l.original_url \
+         GROUP BY l.short_url",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+    let short_clicks_synced_at = short_rows.iter().filter_map(|row| row.3).max();
+    let short_links: HashMap<String, ShortLink> = short_rows
+        .into_iter()
+        .map(|(short_url, original_url, clicks, _)| {
+            (
+                short_url,
+                ShortLink {
+                    original_url,
+                    clicks,
+                },
+            )
+        })
+        .collect();
+    let links = merge_link_clicks(tracked, &short_links, &link_text_map, &ctas);
+    let cta = cta_summary(db, id, sent_count, &ctas, &link_text_map).await?;
+
+    let (total_clicks, unique_clicks, scanner_clicks) = sqlx::query_as::<_, (i64, i64, i64)>(
+        "SELECT COUNT(*) FILTER (WHERE NOT is_scanner), \
+         COUNT(DISTINCT ucode) FILTER (WHERE NOT is_scanner), \
+         COUNT(*) FILTER (WHERE is_scanner) \
+         FROM email_events WHERE newsletter_id = $1 AND event_type = 'click'",
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await?;
+
+    let (unsubscribe_count, topic_unsubscribe_count) = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT COUNT(*) FILTER (WHERE topic IS NULL), COUNT(*) FILTER (WHERE topic IS NOT NULL) \
+         FROM unsubscribe_events WHERE newsletter_id = $1",
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await?;
+
+    let editions = if edition_htmls.is_empty() {
+        Vec::new()
+    } else {
+        edition_stats(db, id).await?
+    };
+
+    Ok(Some(NewsletterStats {
+        id,
+        title,
+        status,
+        sent_count,
+        failed_count,
+        total_count,
+        deferred_count,
+        unique_opens,
+        open_rate: open_rate(unique_opens, sent_count),
+        total_clicks,
+        unique_clicks,
+        scanner_clicks,
+        cta,
+        unsubscribe_count,
+        topic_unsubscribe_count,
+        links,
+        short_clicks_synced_at,
+        editions,
+    }))
+}
+
+/// Numbers of a series' issues that went out (sent, sending or paused),
+/// newest first, with their totals.
+pub async fn series(db: &PgPool, series_id: uuid::Uuid) -> Result<SeriesStats, sqlx::Error> {
+    #[allow(clippy::type_complexity)]
+    let rows = sqlx::query_as::<
+        _,
+        (
+            uuid::Uuid,
+            String,
+            String,
+            Option<chrono::DateTime<chrono::Utc>>,
+            i32,
+            i64,
+            i64,
+            i64,
+        ),
+    >(
+        "SELECT n.id, n.title, n.status, n.sending_started_at, n.sent_count, \
+         (SELECT COUNT(DISTINCT ucode) FROM email_events \
+          WHERE newsletter_id = n.id AND event_type = 'open'), \
+         (SELECT COUNT(DISTINCT ucode) FROM email_events \
+          WHERE newsletter_id = n.id AND event_type = 'click' AND NOT is_scanner), \
+         (SELECT COUNT(*) FROM unsubscribe_events WHERE newsletter_id = n.id) \
+         FROM newsletters n \
+         WHERE n.series_id = $1 AND n.parent_id IS NULL AND n.status IN ('sent', 'sending', 'paused') \
+         ORDER BY COALESCE(n.sending_started_at, n.created_at) DESC",
+    )
+    .bind(series_id)
+    .fetch_all(db)
+    .await?;
+
+    // Editions are tracked under their primary newsletter
+    let (recipients, readers) = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT \
+         (SELECT COUNT(DISTINCT ns.subscriber_id) FROM newsletter_sends ns \
+          JOIN newsletters n ON n.id = ns.newsletter_id \
+          WHERE n.series_id = $1 AND ns.status = 'sent'), \
+         (SELECT COUNT(DISTINCT ev.ucode) FROM email_events ev \
+          JOIN newsletters n ON n.id = ev.newsletter_id \
+          WHERE n.series_id = $1 AND ev.event_type = 'open')",
+    )
+    .bind(series_id)
+    .fetch_one(db)
+    .await?;
+
+    let issues: Vec<SeriesIssue> = rows
+        .into_iter()
+        .map(
+            |(
+                id,
+                title,
+                status,
+                sent_at,
+                sent_count,
+                unique_opens,
+                unique_clicks,
+                unsubscribes,
+            )| {
+                SeriesIssue {
+                    id,
+                    title,
+                    status,
+                    sent_at,
+                    sent_count,
+                    unique_opens,
+                    open_rate: open_rate(unique_opens, sent_count),
+                    unique_clicks,
+                    click_through_rate: click_through_rate(unique_clicks, sent_count),
+                    unsubscribe_count: unsubscribes,
+                }
+            },
+        )
+        .collect();
+    let sent_count: i64 = issues.iter().map(|i| i64::from(i.sent_count)).sum();
+    let unique_opens = issues.iter().map(|i| i.unique_opens).sum();
+    let unique_clicks = issues.iter().map(|i| i.unique_clicks).sum();
+    let unsubscribe_count = issues.iter().map(|i| i.unsubscribe_count).sum();
+    #[allow(clippy::cast_precision_loss)]
+    let rate = |part: i64| (sent_count > 0).then(|| part as f64 / sent_count as f64 * 100.0);
+    Ok(SeriesStats {
+        series_id,
+        sent_count,
+        unique_opens,
+        open_rate: rate(unique_opens),
+        unique_clicks,
+        click_through_rate: rate(unique_clicks),
+        unsubscribe_count,
+        recipients,
+        readers,
+        issues,
+    })
+}
+
+/// Sent and sending newsletters with their unique opens, from the cached
+/// event counts; newest first.
+pub async fn newsletter_summaries(db: &PgPool) -> Result<Vec<NewsletterSummary>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, i32, i64)>(
+        "SELECT n.id, n.title, n.sent_count, COALESCE(e.unique_count, 0) \
+         FROM newsletters n \
+         LEFT JOIN stats_newsletter_events e ON e.newsletter_id = n.id AND e.event_type = 'open' \
+         WHERE n.status IN ('sent', 'sending') ORDER BY n.created_at DESC",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, title, sent_count, unique_opens)| NewsletterSummary {
+            id,
+            title,
+            sent_count,
+            unique_opens,
+            open_rate: open_rate(unique_opens, sent_count),
+        })
+        .collect())
+}
+
+/// Cached event counts by topic and event type.
+pub async fn topic_events(db: &PgPool) -> Result<Vec<TopicEvents>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String, i64)>(
+        "SELECT topic, event_type, event_count FROM stats_topic_events \
+         ORDER BY topic, event_type",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(topic, event_type, count)| TopicEvents {
+            topic,
+            event_type,
+            count,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_rate() {
+        assert_eq!(open_rate(0, 0), None);
+        assert_eq!(open_rate(5, 0), None);
+        assert_eq!(open_rate(1, 4), Some(25.0));
+        assert_eq!(format_rate(open_rate(1, 3)), "33.3%");
+        assert_eq!(format_rate(None), "—");
+    }
+
+    #[test]
+    fn test_goal_progress() {
+        assert_eq!(goal_progress(30, None), None);
+        assert_eq!(goal_progress(30, Some(0)), None);
+        assert_eq!(goal_progress(30, Some(120)), Some(25.0));
+        assert_eq!(goal_progress(150, Some(100)), Some(150.0));
+    }
+
+    #[test]
+    fn test_link_texts_first_text_wins() {
+        let htmls = [
+            r#"<p><a href="https://coscup.org/">COSCUP <b>2025</b></a> <a href="https://coscup.org/"><img src="x"></a></p>"#.to_string(),
+            r#"<a href="https://coscup.org/">Other</a> <a href="mailto:a@b.c">mail</a>"#.to_string(),
+        ];
+        let map = link_texts(htmls.iter());
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["https://coscup.org/"], "COSCUP 2025");
+    }
+
+    #[test]
+    fn test_merge_link_clicks() {
+        let short_links = HashMap::from([
+            (
+                "https://s.coscup.org/a".to_string(),
+                ShortLink {
+                    original_url: "https://coscup.org/a".to_string(),
+                    clicks: Some(40),
+                },
+            ),
+            (
+                "https://s.coscup.org/b".to_string(),
+                ShortLink {
+                    original_url: "https://coscup.org/b".to_string(),
+                    clicks: Some(7),
+                },
+            ),
+            (
+                "https://s.coscup.org/c".to_string(),
+                ShortLink {
+                    original_url: "https://coscup.org/c".to_string(),
+                    clicks: Some(0),
+                },
+            ),
+        ]);
+        let texts = HashMap::from([
+            ("https://coscup.org/a".to_string(), "議程".to_string()),
+            ("https://coscup.org/b".to_string(), "交通".to_string()),
+        ]);
+        let tracked = vec![
+            ("https://s.coscup.org/a".to_string(), 30),
+            ("https://not-shortened.example/".to_string(), 2),
+        ];
+
+        let ctas = [crate::cta::CtaLink {
+            url: "https://coscup.org/b".to_string(),
+            goal: None,
+        }];
+        let links = merge_link_clicks(tracked, &short_links, &texts, &ctas);
+        assert_eq!(
+            links,
+            vec![
+                LinkClicks {
+                    url: "https://s.coscup.org/a".to_string(),
+                    text: "議程".to_string(),
+                    clicks: 30,
+                    short_clicks: Some(40),
+                    is_cta: false,
+                },
+                LinkClicks {
+                    url: "https://not-shortened.example/".to_string(),
+                    text: String::new(),
+                    clicks: 2,
+                    short_clicks: None,
+                    is_cta: false,
+                },
+                LinkClicks {
+                    url: "https://s.coscup.org/b".to_string(),
+                    text: "交通".to_string(),
+                    clicks: 0,
+                    short_clicks: Some(7),
+                    is_cta: true,
+                },
+            ]
+        );
+    }
+}
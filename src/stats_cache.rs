@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::jobs::JobKind;
+
+/// Materialized views backing the dashboard and stats pages (see migrations
+/// 020, 034 and 037).
+const VIEWS: [&str; 4] = [
+    "stats_subscriber_counts",
+    "stats_topic_events",
+    "stats_newsletter_events",
+    "stats_open_heatmap",
+];
+
+/// Refresh all cached stats views. `CONCURRENTLY` keeps them readable while
+/// the refresh runs.
+pub async fn refresh(db: &PgPool) -> Result<(), sqlx::Error> {
+    for view in VIEWS {
+        sqlx::query(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {view}"))
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Background loop refreshing the cached stats every `interval_secs`.
+pub async fn refresh_loop(db: PgPool, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        if let Err(e) = crate::jobs::track(&db, JobKind::StatsRefresh, None, refresh(&db)).await {
+            tracing::error!("Stats cache refresh failed: {e}");
+        }
+    }
+}
+
+pub struct SubscriberCounts {
+    pub total: i64,
+    pub active: i64,
+    pub verified: i64,
+    pub refreshed_at: DateTime<Utc>,
+}
+
+/// Cached subscriber totals.
+pub async fn subscriber_counts(db: &PgPool) -> Result<SubscriberCounts, sqlx::Error> {
+    let (total, active, verified, refreshed_at) =
+        sqlx::query_as::<_, (i64, i64, i64, DateTime<Utc>)>(
+            "SELECT total, active, verified, refreshed_at FROM stats_subscriber_counts",
+        )
+        .fetch_one(db)
+        .await?;
+    Ok(SubscriberCounts {
+        total,
+        active,
+        verified,
+        refreshed_at,
+    })
+}
+
+/// Cached opens by weekday and hour (Taipei time): `grid[0]` is Monday,
+/// `grid[d][h]` the opens in hour `h` of that day.
+pub async fn open_heatmap(db: &PgPool) -> Result<Vec<[i64; 24]>, sqlx::Error> {
+    let cells =
+        sqlx::query_as::<_, (i32, i32, i64)>("SELECT weekday, hour, opens FROM stats_open_heatmap")
+            .fetch_all(db)
+            .await?;
+    Ok(heatmap_grid(&cells))
+}
+
+/// Lay out (ISO weekday, hour, count) cells on a 7×24 grid; hours with no
+/// opens are 0.
+fn heatmap_grid(cells: &[(i32, i32, i64)]) -> Vec<[i64; 24]> {
+    let mut grid = vec![[0; 24]; 7];
+    for &(weekday, hour, count) in cells {
+        let (Ok(day), Ok(hour)) = (usize::try_from(weekday - 1), usize::try_from(hour)) else {
+            continue;
+        };
+        if let Some(cell) = grid.get_mut(day).and_then(|row| row.get_mut(hour)) {
+            *cell = count;
+        }
+    }
+    grid
+}
+
+/// How fresh the cached numbers are, for display next to them.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Staleness {
+    /// Refresh time, RFC 3339; pages format it with `local_time`
+    pub refreshed_at: String,
+    /// Relative age, e.g. "3 分鐘前"
+    pub age: String,
+    /// True when the cache missed more than one scheduled refresh
+    pub stale: bool,
+}
+
+pub fn staleness(refreshed_at: DateTime<Utc>, now: DateTime<Utc>, interval_secs: u64) -> Staleness {
+    let age_secs = (now - refreshed_at).num_seconds().max(0);
+    let age = if age_secs < 60 {
+        "剛剛".to_string()
+    } else if age_secs < 3600 {
+        format!("{} 分鐘前", age_secs / 60)
+    } else if age_secs < 86400 {
+        format!("{} 小時前", age_secs / 3600)
+    } else {
+        format!("{} 天前", age_secs / 86400)
+    };
+    let threshold = i64::try_from(interval_secs.saturating_mul(2)).unwrap_or(i64::MAX);
+    Staleness {
+        refreshed_at: refreshed_at.to_rfc3339(),
+        age,
+        stale: age_secs > threshold,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_heatmap_grid() {
+        let grid = heatmap_grid(&[(1, 9, 12), (7, 23, 3), (8, 0, 5), (3, 24, 5)]);
+        assert_eq!(grid.len(), 7);
+        assert_eq!(grid[0][9], 12);
+        assert_eq!(grid[6][23], 3);
+        assert_eq!(grid.iter().flatten().sum::<i64>(), 15);
+    }
+
+    #[test]
+    fn test_staleness() {
+        let refreshed = Utc.with_ymd_and_hms(2025, 8, 9, 1, 0, 0).unwrap();
+
+        let s = staleness(refreshed, refreshed + chrono::Duration::seconds(30), 300);
+        assert_eq!(
+            s,
+            Staleness {
+                refreshed_at: "2025-08-09T01:00:00+00:00".to_string(),
+                age: "剛剛".to_string(),
+                stale: false,
+            }
+        );
+
+        let s = staleness(refreshed, refreshed + chrono::Duration::minutes(7), 300);
+        assert_eq!(s.age, "7 分鐘前");
+        assert!(!s.stale);
+
+        let s = staleness(refreshed, refreshed + chrono::Duration::minutes(11), 300);
+        assert!(s.stale);
+
+        let s = staleness(refreshed, refreshed + chrono::Duration::hours(30), 300);
+        assert_eq!(s.age, "1 天前");
+    }
+}
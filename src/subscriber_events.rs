@@ -0,0 +1,235 @@
+//! What subscribers do with their links that matters when someone asks "who
+//! unsubscribed me?" or a manage link leaks: using the verification link,
+//! opening the manage page from an IP not seen for them before, unsubscribing
+//! (by one-click from the mail client or on the page) and resetting the
+//! manage link. Shown on the admin subscriber page; admin actions go to
+//! `audit` instead.
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::PgPool;
+
+/// How many events the subscriber page shows.
+const PAGE_LIMIT: i64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Opened the verification link from the confirmation email
+    VerificationUsed,
+    /// Opened the manage page from an IP with no earlier event
+    ManageNewIp,
+    /// `List-Unsubscribe=One-Click` POST from the mail client
+    UnsubscribeOneClick,
+    /// Unsubscribe button on the manage page
+    UnsubscribePage,
+    /// Reset the manage link, invalidating the old ones
+    ManageLinkRotated,
+}
+
+impl Event {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::VerificationUsed => "verification.used",
+            Self::ManageNewIp => "manage.new_ip",
+            Self::UnsubscribeOneClick => "unsubscribe.one_click",
+            Self::UnsubscribePage => "unsubscribe.page",
+            Self::ManageLinkRotated => "manage.link_rotated",
+        }
+    }
+}
+
+/// Label for an event name on the admin page.
+fn label(event: &str) -> &str {
+    match event {
+        "verification.used" => "使用驗證連結",
+        "manage.new_ip" => "從新的 IP 開啟管理頁",
+        "unsubscribe.one_click" => "一鍵取消訂閱（郵件程式）",
+        "unsubscribe.page" => "於管理頁取消訂閱",
+        "manage.link_rotated" => "重設管理連結",
+        other => other,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberEvent {
+    pub event: String,
+    pub label: String,
+    pub details: Option<JsonValue>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Record an event. Failures are logged rather than failing the subscriber's
+/// request, as with the admin audit log.
+pub async fn record(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    event: Event,
+    details: Option<JsonValue>,
+    ip: Option<IpAddr>,
+    user_agent: Option<&str>,
+) {
+    let result = sqlx::query(
+        "INSERT INTO subscriber_events (subscriber_id, event, details, ip_address, user_agent) \
+         VALUES ($1, $2, $3, $4::inet, $5)",
+    )
+    .bind(subscriber_id)
+    .bind(event.as_str())
+    .bind(&details)
+    .bind(ip.map(|ip| ip.to_string()))
+    .bind(user_agent)
+    .execute(db)
+    .await;
+    if let Err(e) = result {
+        tracing::error!(
+            "Failed to record {} for subscriber {subscriber_id}: {e}",
+            event.as_str()
+        );
+    }
+}
+
+/// Note a manage page visit from `ip` if no earlier event for the subscriber
+/// came from it. Returns whether it was new.
+pub async fn record_manage_visit(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    ip: IpAddr,
+    user_agent: Option<&str>,
+) -> bool {
+    let result = sqlx::query(
+        "INSERT INTO subscriber_events (subscriber_id, event, ip_address, user_agent) \
+         SELECT $1, $2, $3::inet, $4 WHERE NOT EXISTS ( \
+             SELECT 1 FROM subscriber_events WHERE subscriber_id = $1 AND ip_address = $3::inet)",
+    )
+    .bind(subscriber_id)
+    .bind(Event::ManageNewIp.as_str())
+    .bind(ip.to_string())
+    .bind(user_agent)
+    .execute(db)
+    .await;
+    match result {
+        Ok(done) => done.rows_affected() > 0,
+        Err(e) => {
+            tracing::error!("Failed to record manage visit for subscriber {subscriber_id}: {e}");
+            false
+        }
+    }
+}
+
+/// The subscriber's latest events, newest first.
+pub async fn for_subscriber(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+) -> Result<Vec<SubscriberEvent>, sqlx::Error> {
+    #[allow(clippy::type_complexity)]
+    let rows = sqlx::query_as::<
+        _,
+        (
+            String,
+            Option<JsonValue>,
+            Option<String>,
+            Option<String>,
+            DateTime<Utc>,
+        ),
+    >(
+        "SELECT event, details, host(ip_address), user_agent, created_at \
+         FROM subscriber_events WHERE subscriber_id = $1 ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(subscriber_id)
+    .bind(PAGE_LIMIT)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(event, details, ip_address, user_agent, created_at)| SubscriberEvent {
+                label: label(&event).to_string(),
+                event,
+                details,
+                ip_address,
+                user_agent,
+                created_at,
+            },
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_subscriber_events(db: PgPool) {
+        use axum::body::Body;
+        use axum::http::Request;
+
+        let mut app = crate::test_utils::TestApp::with_db(db).await;
+        app.post_form(
+            "/api/subscribe",
+            &[
+                ("email", "someone@example.org"),
+                ("name", "Someone"),
+                ("cf-turnstile-response", "token"),
+            ],
+        )
+        .await;
+        let (token, subscriber_id): (String, uuid::Uuid) =
+            sqlx::query_as("SELECT token, subscriber_id FROM verification_tokens")
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        app.get(&format!("/verify/{token}")).await;
+        let (secret_code, ucode): (String, String) =
+            sqlx::query_as("SELECT secret_code, ucode FROM subscribers WHERE id = $1")
+                .bind(subscriber_id)
+                .fetch_one(&app.state.db)
+                .await
+                .unwrap();
+        let link = crate::security::issue_manage_token(&secret_code, &ucode, 30);
+
+        // The verifying IP is already known; another one is noted once
+        app.get(&format!("/manage/{link}")).await;
+        let from_elsewhere = || {
+            Request::get(format!("/manage/{link}"))
+                .header("x-forwarded-for", "203.0.113.9")
+                .body(Body::empty())
+                .unwrap()
+        };
+        app.send(from_elsewhere()).await;
+        app.send(from_elsewhere()).await;
+
+        app.send(
+            Request::post(format!("/unsubscribe/{link}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        app.post_form(&format!("/manage/{link}/unsubscribe"), &[])
+            .await;
+
+        let events = for_subscriber(&app.state.db, subscriber_id).await.unwrap();
+        let names: Vec<&str> = events.iter().rev().map(|e| e.event.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "verification.used",
+                "manage.new_ip",
+                "unsubscribe.one_click",
+                "unsubscribe.page"
+            ]
+        );
+        assert_eq!(events[2].ip_address.as_deref(), Some("203.0.113.9"));
+
+        app.login_as("admin@coscup.org").await;
+        let page = app
+            .get(&format!("/admin/subscribers/{subscriber_id}"))
+            .await;
+        assert!(page.body.contains("一鍵取消訂閱（郵件程式）"));
+        assert!(page.body.contains("從新的 IP 開啟管理頁"));
+    }
+}
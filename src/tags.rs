@@ -0,0 +1,540 @@
+//! Subscriber tags. Tags are added by hand (subscriber page, `/admin/segments`,
+//! `/api/v1/subscribers/tags`), by the `tags` column of an import, by the
+//! registration sync, or kept up to date by a `TagRule`; segments filter on
+//! them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::jobs::JobKind;
+
+/// Longest tag name (`tags.name`).
+pub const MAX_NAME_CHARS: usize = 100;
+
+/// Most recent newsletters a rule can look back over.
+pub const MAX_RULE_NEWSLETTERS: u32 = 20;
+
+/// The trimmed name, or `None` when it is empty or too long.
+pub fn clean_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    (!name.is_empty() && name.chars().count() <= MAX_NAME_CHARS).then(|| name.to_string())
+}
+
+/// Clean tag names, dropping duplicates and invalid ones.
+pub fn clean_list<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for name in names.into_iter().filter_map(clean_name) {
+        if !tags.contains(&name) {
+            tags.push(name);
+        }
+    }
+    tags
+}
+
+/// Tags as written in one import cell, e.g. `volunteer; speaker`. `|` is
+/// accepted too.
+pub fn parse_list(list: &str) -> Vec<String> {
+    clean_list(list.split([';', '|']))
+}
+
+/// Get the id of a tag by name, creating it if needed.
+pub async fn ensure_tag(db: &PgPool, name: &str) -> Result<uuid::Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, uuid::Uuid>(
+        "INSERT INTO tags (name) VALUES ($1) \
+         ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id",
+    )
+    .bind(name)
+    .fetch_one(db)
+    .await
+}
+
+/// Attach a tag to a subscriber. Returns true if the tag was newly added.
+pub async fn tag_subscriber(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    tag_id: uuid::Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO subscriber_tags (subscriber_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(subscriber_id)
+    .bind(tag_id)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Remove a tag from a subscriber. Returns true if they had it.
+pub async fn untag_subscriber(
+    db: &PgPool,
+    subscriber_id: uuid::Uuid,
+    tag_id: uuid::Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("DELETE FROM subscriber_tags WHERE subscriber_id = $1 AND tag_id = $2")
+            .bind(subscriber_id)
+            .bind(tag_id)
+            .execute(db)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The subscriber with this address, else another spelling of the same Gmail
+/// mailbox.
+pub async fn find_subscriber(db: &PgPool, email: &str) -> Result<Option<uuid::Uuid>, sqlx::Error> {
+    let Ok(normalized) = crate::email_validation::normalize(email) else {
+        return Ok(None);
+    };
+    sqlx::query_scalar(
+        "SELECT id FROM subscribers WHERE email = $1 OR canonical_email = $2 \
+         ORDER BY email = $1 DESC LIMIT 1",
+    )
+    .bind(&normalized.email)
+    .bind(&normalized.canonical)
+    .fetch_optional(db)
+    .await
+}
+
+/// Add the tag to (or with `remove`, take it from) the subscribers with these
+/// addresses. Returns how many subscribers changed and the addresses no
+/// subscriber has.
+pub async fn set_by_email(
+    db: &PgPool,
+    tag_id: uuid::Uuid,
+    emails: &[String],
+    remove: bool,
+) -> Result<(usize, Vec<String>), sqlx::Error> {
+    let mut changed = 0;
+    let mut not_found = Vec::new();
+    for email in emails {
+        let Some(subscriber_id) = find_subscriber(db, email).await? else {
+            not_found.push(email.clone());
+            continue;
+        };
+        let did = if remove {
+            untag_subscriber(db, subscriber_id, tag_id).await?
+        } else {
+            tag_subscriber(db, subscriber_id, tag_id).await?
+        };
+        if did {
+            changed += 1;
+        }
+    }
+    Ok((changed, not_found))
+}
+
+/// Split a pasted list of addresses on whitespace, commas and semicolons.
+pub fn split_emails(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagSummary {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub subscribers: i64,
+    pub rules: i64,
+}
+
+/// All tags with how many subscribers have each.
+pub async fn list(db: &PgPool) -> Result<Vec<TagSummary>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, i64, i64)>(
+        "SELECT t.id, t.name, \
+         (SELECT COUNT(*) FROM subscriber_tags st WHERE st.tag_id = t.id), \
+         (SELECT COUNT(*) FROM tag_rules r WHERE r.tag_id = t.id) \
+         FROM tags t ORDER BY t.name",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, subscribers, rules)| TagSummary {
+            id,
+            name,
+            subscribers,
+            rules,
+        })
+        .collect())
+}
+
+/// Keeps a tag on exactly the subscribers matching an engagement condition
+/// over the most recently sent newsletters. Stored as JSON in `tag_rules.rule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TagRule {
+    /// Opened every one of the last `newsletters`
+    OpenedAll { newsletters: u32 },
+    /// Clicked a link in any of the last `newsletters`
+    ClickedAny { newsletters: u32 },
+    /// Was sent some of the last `newsletters` but opened none of them
+    OpenedNone { newsletters: u32 },
+}
+
+/// The newsletters a rule looks at; `$2` is how many.
+const RECENT_NEWSLETTERS: &str = "SELECT id FROM newsletters WHERE status = 'sent' \
+     ORDER BY sending_completed_at DESC NULLS LAST LIMIT $2";
+
+impl TagRule {
+    /// Rule kinds for the form: (key, label).
+    pub const KINDS: [(&'static str, &'static str); 3] = [
+        ("opened_all", "每一期都有開信"),
+        ("clicked_any", "任一期有點擊連結"),
+        ("opened_none", "有收到但都沒開信"),
+    ];
+
+    /// Build a rule from the form; `None` for an unknown kind or a count out
+    /// of range.
+    pub fn from_form(kind: &str, newsletters: u32) -> Option<Self> {
+        if !(1..=MAX_RULE_NEWSLETTERS).contains(&newsletters) {
+            return None;
+        }
+        match kind {
+            "opened_all" => Some(Self::OpenedAll { newsletters }),
+            "clicked_any" => Some(Self::ClickedAny { newsletters }),
+            "opened_none" => Some(Self::OpenedNone { newsletters }),
+            _ => None,
+        }
+    }
+
+    fn newsletters(self) -> u32 {
+        match self {
+            Self::OpenedAll { newsletters }
+            | Self::ClickedAny { newsletters }
+            | Self::OpenedNone { newsletters } => newsletters,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Self::OpenedAll { newsletters } => format!("最近 {newsletters} 期電子報每一期都有開信"),
+            Self::ClickedAny { newsletters } => {
+                format!("最近 {newsletters} 期電子報中任一期有點擊連結")
+            }
+            Self::OpenedNone { newsletters } => {
+                format!("有收到最近 {newsletters} 期電子報，但都沒有開信")
+            }
+        }
+    }
+
+    /// SQL condition on `subscribers s`; `$2` is the number of newsletters.
+    fn condition(self) -> String {
+        let events = "SELECT 1 FROM email_events e WHERE e.ucode = s.ucode AND NOT e.is_scanner";
+        match self {
+            Self::OpenedAll { .. } => format!(
+                "(SELECT COUNT(DISTINCT e.newsletter_id) FROM email_events e \
+                 WHERE e.ucode = s.ucode AND NOT e.is_scanner AND e.event_type = 'open' \
+                 AND e.newsletter_id IN ({RECENT_NEWSLETTERS})) = $2"
+            ),
+            Self::ClickedAny { .. } => format!(
+                "EXISTS ({events} AND e.event_type = 'click' \
+                 AND e.newsletter_id IN ({RECENT_NEWSLETTERS}))"
+            ),
+            Self::OpenedNone { .. } => format!(
+                "EXISTS (SELECT 1 FROM newsletter_sends ns WHERE ns.subscriber_id = s.id \
+                 AND ns.status = 'sent' AND ns.newsletter_id IN ({RECENT_NEWSLETTERS})) \
+                 AND NOT EXISTS ({events} AND e.event_type IN ('open', 'click') \
+                 AND e.newsletter_id IN ({RECENT_NEWSLETTERS}))"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleSummary {
+    pub id: uuid::Uuid,
+    pub tag: String,
+    pub description: String,
+    pub created_by: Option<String>,
+    pub last_applied_at: Option<DateTime<Utc>>,
+    /// Subscribers the rule had tagged when last applied
+    pub last_tagged: Option<i32>,
+}
+
+pub async fn list_rules(db: &PgPool) -> Result<Vec<RuleSummary>, sqlx::Error> {
+    type Row = (
+        uuid::Uuid,
+        String,
+        serde_json::Value,
+        Option<String>,
+        Option<DateTime<Utc>>,
+        Option<i32>,
+    );
+    let rows = sqlx::query_as::<_, Row>(&format!(
+        "SELECT r.id, t.name, r.rule, {}, r.last_applied_at, r.last_matched \
+         FROM tag_rules r JOIN tags t ON t.id = r.tag_id ORDER BY t.name, r.created_at",
+        crate::admin_profile::display_name_sql("r.created_by")
+    ))
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, tag, rule, created_by, last_applied_at, last_tagged)| RuleSummary {
+                id,
+                tag,
+                description: serde_json::from_value::<TagRule>(rule)
+                    .map_or_else(|_| "（無法解析的規則）".to_string(), TagRule::describe),
+                created_by,
+                last_applied_at,
+                last_tagged,
+            },
+        )
+        .collect())
+}
+
+/// Save a rule for the tag (created if needed) and return its id.
+pub async fn create_rule(
+    db: &PgPool,
+    tag_name: &str,
+    rule: TagRule,
+    created_by: &str,
+) -> Result<uuid::Uuid, sqlx::Error> {
+    let tag_id = ensure_tag(db, tag_name).await?;
+    sqlx::query_scalar(
+        "INSERT INTO tag_rules (tag_id, rule, created_by) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(tag_id)
+    .bind(serde_json::to_value(rule).unwrap_or_default())
+    .bind(created_by)
+    .fetch_one(db)
+    .await
+}
+
+/// Tag everyone the rule matches and untag those it tagged before who no
+/// longer match. Returns (added, removed), or `None` if there is no such rule.
+pub async fn apply_rule(
+    db: &PgPool,
+    rule_id: uuid::Uuid,
+) -> Result<Option<(u64, u64)>, sqlx::Error> {
+    let Some((tag_id, rule)) = sqlx::query_as::<_, (uuid::Uuid, serde_json::Value)>(
+        "SELECT tag_id, rule FROM tag_rules WHERE id = $1",
+    )
+    .bind(rule_id)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let rule: TagRule = match serde_json::from_value(rule) {
+        Ok(rule) => rule,
+        Err(e) => {
+            tracing::warn!("Invalid tag rule {rule_id}: {e}");
+            return Ok(Some((0, 0)));
+        }
+    };
+    let newsletters = i64::from(rule.newsletters());
+    let condition = rule.condition();
+
+    let mut tx = db.begin().await?;
+    let removed = sqlx::query(&format!(
+        "DELETE FROM subscriber_tags st USING subscribers s \
+         WHERE st.subscriber_id = s.id AND st.rule_id = $1 AND NOT ({condition})"
+    ))
+    .bind(rule_id)
+    .bind(newsletters)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let added = sqlx::query(&format!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag_id, rule_id) \
+         SELECT s.id, $1, $3 FROM subscribers s WHERE {condition} ON CONFLICT DO NOTHING"
+    ))
+    .bind(tag_id)
+    .bind(newsletters)
+    .bind(rule_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    sqlx::query(
+        "UPDATE tag_rules SET last_applied_at = NOW(), \
+         last_matched = (SELECT COUNT(*) FROM subscriber_tags WHERE rule_id = $1) WHERE id = $1",
+    )
+    .bind(rule_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some((added, removed)))
+}
+
+/// Apply every rule; returns the number of tags added or removed.
+pub async fn apply_all_rules(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let ids = sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM tag_rules ORDER BY created_at")
+        .fetch_all(db)
+        .await?;
+    let mut changed = 0;
+    for id in ids {
+        if let Some((added, removed)) = apply_rule(db, id).await? {
+            changed += added + removed;
+        }
+    }
+    Ok(changed)
+}
+
+/// Background loop re-applying the tag rules every `interval_secs`, so tags
+/// follow the opens and clicks of each new newsletter.
+pub async fn rules_loop(db: PgPool, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        match crate::jobs::track(&db, JobKind::TagRules, None, apply_all_rules(&db)).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Tag rules added or removed {n} tags"),
+            Err(e) => tracing::error!("Applying tag rules failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list(" volunteer; speaker |volunteer;; "),
+            vec!["volunteer", "speaker"]
+        );
+        assert!(parse_list("").is_empty());
+        assert!(parse_list(&"x".repeat(MAX_NAME_CHARS + 1)).is_empty());
+    }
+
+    #[test]
+    fn test_split_emails() {
+        assert_eq!(
+            split_emails("a@example.org, b@example.org\nc@example.org;"),
+            vec!["a@example.org", "b@example.org", "c@example.org"]
+        );
+    }
+
+    #[test]
+    fn test_rule_from_form_and_json() {
+        assert_eq!(
+            TagRule::from_form("opened_all", 3),
+            Some(TagRule::OpenedAll { newsletters: 3 })
+        );
+        assert_eq!(TagRule::from_form("opened_all", 0), None);
+        assert_eq!(
+            TagRule::from_form("clicked_any", MAX_RULE_NEWSLETTERS + 1),
+            None
+        );
+        assert_eq!(TagRule::from_form("nope", 3), None);
+        for (kind, _) in TagRule::KINDS {
+            assert!(TagRule::from_form(kind, 1).is_some());
+        }
+
+        let rule = TagRule::OpenedNone { newsletters: 5 };
+        let json = serde_json::to_value(rule).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "kind": "opened_none", "newsletters": 5 })
+        );
+        assert_eq!(serde_json::from_value::<TagRule>(json).unwrap(), rule);
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_rule_follows_opens(db: PgPool) {
+        crate::db::run_migrations(&db).await.unwrap();
+        let subscriber = |email: &'static str, ucode: &'static str| {
+            sqlx::query_scalar::<_, uuid::Uuid>(
+                "INSERT INTO subscribers (email, secret_code, ucode, status, verified_email) \
+                 VALUES ($1, 's', $2, true, true) RETURNING id",
+            )
+            .bind(email)
+            .bind(ucode)
+            .fetch_one(&db)
+        };
+        let fan = subscriber("fan@example.org", "fan").await.unwrap();
+        let once = subscriber("once@example.org", "once").await.unwrap();
+        let mut newsletters = Vec::new();
+        for (i, slug) in ["n1", "n2"].into_iter().enumerate() {
+            let id: uuid::Uuid = sqlx::query_scalar(
+                "INSERT INTO newsletters (title, slug, markdown_content, status, sending_completed_at) \
+                 VALUES ('T', $1, '', 'sent', NOW() - ($2::BIGINT * INTERVAL '1 day')) RETURNING id",
+            )
+            .bind(slug)
+            .bind(i64::try_from(2 - i).unwrap())
+            .fetch_one(&db)
+            .await
+            .unwrap();
+            newsletters.push(id);
+        }
+        let open = |ucode: &'static str, newsletter_id: uuid::Uuid| {
+            sqlx::query(
+                "INSERT INTO email_events (ucode, event_type, topic, newsletter_id) \
+                 VALUES ($1, 'open', 't', $2)",
+            )
+            .bind(ucode)
+            .bind(newsletter_id)
+            .execute(&db)
+        };
+        open("fan", newsletters[0]).await.unwrap();
+        open("fan", newsletters[1]).await.unwrap();
+        open("once", newsletters[0]).await.unwrap();
+
+        let rule_id = create_rule(
+            &db,
+            "loyal",
+            TagRule::OpenedAll { newsletters: 2 },
+            "admin@coscup.org",
+        )
+        .await
+        .unwrap();
+        let tag_id = ensure_tag(&db, "loyal").await.unwrap();
+        assert_eq!(apply_rule(&db, rule_id).await.unwrap(), Some((1, 0)));
+
+        let tagged = || {
+            sqlx::query_scalar::<_, uuid::Uuid>(
+                "SELECT subscriber_id FROM subscriber_tags WHERE tag_id = $1 ORDER BY subscriber_id",
+            )
+            .bind(tag_id)
+            .fetch_all(&db)
+        };
+        assert_eq!(tagged().await.unwrap(), vec![fan]);
+
+        // A third newsletter nobody opened yet: the fan drops out
+        sqlx::query(
+            "INSERT INTO newsletters (title, slug, markdown_content, status, sending_completed_at) \
+             VALUES ('T', 'n3', '', 'sent', NOW())",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        // Tagged by hand: the rule never removes it
+        tag_subscriber(&db, once, tag_id).await.unwrap();
+        assert_eq!(apply_all_rules(&db).await.unwrap(), 1);
+        assert_eq!(tagged().await.unwrap(), vec![once]);
+        assert_eq!(list_rules(&db).await.unwrap()[0].last_tagged, Some(0));
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_set_by_email(db: PgPool) {
+        crate::db::run_migrations(&db).await.unwrap();
+        sqlx::query(
+            "INSERT INTO subscribers (email, canonical_email, secret_code, ucode, status, verified_email) \
+             VALUES ('jane.doe@gmail.com', 'janedoe@gmail.com', 's', 'u1', true, true)",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let tag_id = ensure_tag(&db, "speaker").await.unwrap();
+        let emails = split_emails("JaneDoe@gmail.com missing@example.org");
+
+        let (changed, not_found) = set_by_email(&db, tag_id, &emails, false).await.unwrap();
+        assert_eq!(
+            (changed, not_found),
+            (1, vec!["missing@example.org".to_string()])
+        );
+        assert_eq!(
+            set_by_email(&db, tag_id, &emails, false).await.unwrap().0,
+            0
+        );
+        assert_eq!(list(&db).await.unwrap()[0].subscribers, 1);
+        assert_eq!(set_by_email(&db, tag_id, &emails, true).await.unwrap().0, 1);
+    }
+}
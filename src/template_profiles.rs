@@ -0,0 +1,208 @@
+//! Preview data profiles: named sample content a template can be previewed
+//! with on `/admin/templates/{id}/preview`, e.g. a very long title, a
+//! newsletter without images, or English content, instead of only the
+//! built-in sample.
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Longest profile name accepted.
+const MAX_NAME_CHARS: usize = 100;
+
+/// Sample content and recipient for one preview.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Profile {
+    pub id: Option<uuid::Uuid>,
+    pub name: String,
+    pub title: String,
+    pub markdown_content: String,
+    pub recipient_name: String,
+    pub lang: String,
+}
+
+impl Profile {
+    /// The built-in sample every template is previewed with by default.
+    pub fn sample() -> Self {
+        Self {
+            id: None,
+            name: "預設範例".to_string(),
+            title: "COSCUP 2025 電子報 - 第一期".to_string(),
+            markdown_content: SAMPLE_MARKDOWN.to_string(),
+            recipient_name: "COSCUP 訂閱者".to_string(),
+            lang: "zh-TW".to_string(),
+        }
+    }
+
+    /// Text direction for the profile's language.
+    pub fn dir(&self) -> &'static str {
+        const RTL: [&str; 5] = ["ar", "fa", "he", "ur", "yi"];
+        let primary = self.lang.split('-').next().unwrap_or_default();
+        if RTL.contains(&primary) {
+            "rtl"
+        } else {
+            "ltr"
+        }
+    }
+}
+
+/// A realistic Markdown sample, so the preview goes through the same
+/// `render_markdown` pipeline as actual newsletters.
+const SAMPLE_MARKDOWN: &str = "\
+## COSCUP 2025 活動公告
+
+感謝您訂閱 COSCUP 電子報！以下是本期精彩內容：
+
+### 活動亮點
+
+- 超過 **100 場**議程，涵蓋 Open Source 各領域
+- 活動日期：**8 月 9 日 ~ 10 日**
+- 地點：[台灣科技大學](https://coscup.org)
+
+### 特別活動
+
+本年度特別新增「親子工作坊」，歡迎帶孩子一起參與開源文化！
+
+---
+
+[立即報名](https://coscup.org) | [查看議程](https://coscup.org)\
+";
+
+/// Check and normalize a profile from the form. Markdown and recipient name
+/// may be empty, to preview those cases too.
+pub fn validate(
+    name: &str,
+    title: &str,
+    markdown_content: &str,
+    recipient_name: &str,
+    lang: &str,
+) -> Result<Profile, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("請輸入設定檔名稱".to_string());
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!("設定檔名稱不可超過 {MAX_NAME_CHARS} 字"));
+    }
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("請輸入範例標題".to_string());
+    }
+    let lang = match lang.trim() {
+        "" => "zh-TW".to_string(),
+        tag => {
+            crate::newsletter::parse_lang(tag).ok_or_else(|| format!("無效的語言代碼：{tag}"))?
+        }
+    };
+    Ok(Profile {
+        id: None,
+        name: name.to_string(),
+        title: title.to_string(),
+        markdown_content: markdown_content.to_string(),
+        recipient_name: recipient_name.trim().to_string(),
+        lang,
+    })
+}
+
+type ProfileRow = (uuid::Uuid, String, String, String, String, String);
+
+fn from_row((id, name, title, markdown_content, recipient_name, lang): ProfileRow) -> Profile {
+    Profile {
+        id: Some(id),
+        name,
+        title,
+        markdown_content,
+        recipient_name,
+        lang,
+    }
+}
+
+/// A template's profiles, by name.
+pub async fn list(db: &PgPool, template_id: uuid::Uuid) -> Result<Vec<Profile>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ProfileRow>(
+        "SELECT id, name, title, markdown_content, recipient_name, lang \
+         FROM template_preview_profiles WHERE template_id = $1 ORDER BY name",
+    )
+    .bind(template_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+/// One of a template's profiles.
+pub async fn find(
+    db: &PgPool,
+    template_id: uuid::Uuid,
+    id: uuid::Uuid,
+) -> Result<Option<Profile>, sqlx::Error> {
+    let row = sqlx::query_as::<_, ProfileRow>(
+        "SELECT id, name, title, markdown_content, recipient_name, lang \
+         FROM template_preview_profiles WHERE template_id = $1 AND id = $2",
+    )
+    .bind(template_id)
+    .bind(id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(from_row))
+}
+
+/// Save a profile, replacing the template's profile of the same name.
+pub async fn save(
+    db: &PgPool,
+    template_id: uuid::Uuid,
+    profile: &Profile,
+    created_by: &str,
+) -> Result<uuid::Uuid, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO template_preview_profiles \
+         (template_id, name, title, markdown_content, recipient_name, lang, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) \
+         ON CONFLICT (template_id, name) DO UPDATE SET title = EXCLUDED.title, \
+         markdown_content = EXCLUDED.markdown_content, recipient_name = EXCLUDED.recipient_name, \
+         lang = EXCLUDED.lang RETURNING id",
+    )
+    .bind(template_id)
+    .bind(&profile.name)
+    .bind(&profile.title)
+    .bind(&profile.markdown_content)
+    .bind(&profile.recipient_name)
+    .bind(&profile.lang)
+    .bind(created_by)
+    .fetch_one(db)
+    .await
+}
+
+/// Give a duplicated template the profiles of the original.
+pub async fn copy(db: &PgPool, from: uuid::Uuid, to: uuid::Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO template_preview_profiles \
+         (template_id, name, title, markdown_content, recipient_name, lang, created_by) \
+         SELECT $2, name, title, markdown_content, recipient_name, lang, created_by \
+         FROM template_preview_profiles WHERE template_id = $1",
+    )
+    .bind(from)
+    .bind(to)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_profile() {
+        let profile = validate(" 長標題 ", "標題", "", " ", "EN-us").unwrap();
+        assert_eq!(profile.name, "長標題");
+        assert_eq!(profile.recipient_name, "");
+        assert_eq!(profile.lang, "en-US");
+        assert_eq!(profile.dir(), "ltr");
+
+        assert_eq!(validate("x", "t", "", "", "").unwrap().lang, "zh-TW");
+        assert_eq!(validate("x", "t", "", "", "ar-EG").unwrap().dir(), "rtl");
+        assert!(validate("", "t", "", "", "").is_err());
+        assert!(validate("x", " ", "", "", "").is_err());
+        assert!(validate("x", "t", "", "", "not a tag").is_err());
+        assert!(validate(&"名".repeat(101), "t", "", "", "").is_err());
+    }
+}
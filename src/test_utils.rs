@@ -0,0 +1,316 @@
+//! Support for end-to-end route tests: an `AppState` wired to the mock email,
+//! captcha, short URL, image, OIDC and rate limit services, and the full router
+//! around it, so a request goes through the middleware, extractors, handler
+//! and templates just as in production.
+//!
+//! Tests that touch the database take the pool `#[sqlx::test(migrations =
+//! false)]` creates for them, a fresh database per test on the server at
+//! `DATABASE_URL` (e.g. the one from `docker-compose.dev.yml`), and pass it to
+//! `TestApp::with_db`, which runs the app's own migrations on it. They are
+//! `#[ignore]`d so that `cargo test` runs without a database; run them with
+//! `cargo test -- --include-ignored`.
+//!
+//! ```ignore
+//! #[ignore = "needs DATABASE_URL"]
+//! #[sqlx::test(migrations = false)]
+//! async fn test_dashboard(db: PgPool) {
+//!     let mut app = TestApp::with_db(db).await;
+//!     app.login_as("admin@coscup.org").await;
+//!     assert_eq!(app.get("/admin").await.status, StatusCode::OK);
+//! }
+//! ```
+
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+use crate::captcha::tests::MockCaptchaVerifier;
+use crate::config::AppConfig;
+use crate::email::tests::MockEmailService;
+use crate::shorturl::tests::MockShortUrlService;
+use crate::AppState;
+
+/// Address requests appear to come from.
+pub const CLIENT_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 40000);
+
+/// Templates are parsed once for all tests, which takes a few seconds in a
+/// debug build.
+fn templates() -> &'static tera::Tera {
+    static TERA: OnceLock<tera::Tera> = OnceLock::new();
+    TERA.get_or_init(|| {
+        let mut tera = tera::Tera::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/templates/**/*.html"
+        ))
+        .expect("templates load");
+        crate::timezone::register(&mut tera);
+        tera
+    })
+}
+
+/// Builds a `TestApp`; by default captchas pass and the config is
+/// `config::tests::test_config()`.
+pub struct TestStateBuilder {
+    db: PgPool,
+    config: AppConfig,
+    captcha_passes: bool,
+    oidc: Option<Arc<dyn crate::oidc::OidcProvider>>,
+    identities: Vec<crate::email::SendingIdentity>,
+}
+
+impl TestStateBuilder {
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            config: crate::config::tests::test_config(),
+            captcha_passes: true,
+            oidc: None,
+            identities: Vec::new(),
+        }
+    }
+
+    /// A pool that never connects, for routes that don't reach the database.
+    pub fn without_db() -> Self {
+        Self::new(PgPool::connect_lazy("postgres://localhost:1/none").expect("valid URL"))
+    }
+
+    pub fn config(mut self, f: impl FnOnce(&mut AppConfig)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    pub fn captcha_passes(mut self, passes: bool) -> Self {
+        self.captcha_passes = passes;
+        self
+    }
+
+    /// Enable admin single sign-on with `provider`, e.g. a
+    /// `oidc::tests::MockOidcProvider`.
+    pub fn oidc(mut self, provider: impl crate::oidc::OidcProvider + 'static) -> Self {
+        self.oidc = Some(Arc::new(provider));
+        self
+    }
+
+    /// Sending identities newsletters can pick; mail through them is not
+    /// recorded in `TestApp::email`.
+    pub fn identities(mut self, identities: Vec<crate::email::SendingIdentity>) -> Self {
+        self.identities = identities;
+        self
+    }
+
+    /// Must run inside a Tokio runtime, for the tracking event flusher.
+    pub fn build(self) -> TestApp {
+        let config = self.config;
+
+        let email = MockEmailService::default();
+        let (events, flusher) =
+            crate::event_buffer::start(self.db.clone(), config.scanner_click_window_secs);
+
+        let state = AppState {
+            db: self.db,
+            config: config.clone(),
+            tera: templates().clone(),
+            email: Arc::new(email.clone()),
+            captcha: Arc::new(MockCaptchaVerifier {
+                should_pass: self.captcha_passes,
+            }),
+            shorturl: Arc::new(MockShortUrlService::default()),
+            short_domains: Vec::new(),
+            identities: self.identities,
+            events,
+            topics: crate::topics::TopicIds::default(),
+            images: Arc::new(crate::image_proxy::tests::MockImageFetcher::default()),
+            oidc: self.oidc,
+            rate_limiter: crate::rate_limit::RateLimiter::new(Arc::new(
+                crate::rate_limit::tests::MockRateLimitStore::default(),
+            )),
+            click_guard: crate::click_guard::ClickGuard::new(
+                config.click_limit_ucode,
+                config.click_limit_bad_hash_ip,
+                config.click_block_secs,
+            ),
+            readiness: crate::readiness::Readiness::default(),
+            live: crate::reload::LiveConfig::new(&config),
+            archive_cache: crate::archive_cache::ArchiveCache::default(),
+            metrics: crate::metrics::RouteMetrics::default(),
+        };
+        state.readiness.mark_migrated();
+
+        TestApp {
+            router: crate::build_router(state.clone()),
+            state,
+            email,
+            flusher: Some(flusher),
+            cookie: None,
+        }
+    }
+}
+
+/// The app under test, with handles on its mocks.
+pub struct TestApp {
+    pub state: AppState,
+    /// Built once, since building it is slow in a debug build
+    router: Router,
+    /// Every email the app sent
+    pub email: MockEmailService,
+    flusher: Option<crate::event_buffer::EventFlusher>,
+    /// Admin session sent with every request, see `login_as`
+    cookie: Option<String>,
+}
+
+/// A response with its body read.
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn location(&self) -> Option<&str> {
+        self.headers
+            .get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+    }
+}
+
+impl TestApp {
+    /// Default app on a migrated database.
+    pub async fn with_db(db: PgPool) -> Self {
+        let app = TestStateBuilder::new(db).build();
+        app.migrate().await;
+        app
+    }
+
+    pub async fn migrate(&self) {
+        crate::db::run_migrations(&self.state.db)
+            .await
+            .expect("migrations run");
+    }
+
+    /// Send a request through the router, from `CLIENT_ADDR` and with the
+    /// admin session if logged in.
+    pub async fn send(&self, mut req: Request<Body>) -> TestResponse {
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(CLIENT_ADDR)));
+        if let Some(cookie) = &self.cookie {
+            req.headers_mut()
+                .insert(header::COOKIE, cookie.parse().expect("valid cookie"));
+        }
+        let response = self.router.clone().oneshot(req).await.expect("infallible");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("body reads")
+            .to_bytes();
+        TestResponse {
+            status,
+            headers,
+            body: String::from_utf8_lossy(&bytes).into_owned(),
+        }
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.send(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+    }
+
+    /// POST an urlencoded form, e.g. `&[("email", "a@example.org")]`.
+    pub async fn post_form(&self, uri: &str, fields: &[(&str, &str)]) -> TestResponse {
+        let body = fields
+            .iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        self.send(
+            Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+    }
+
+    /// Start an admin session for `email` (added as an admin if needed) and
+    /// send it with every later request.
+    pub async fn login_as(&mut self, email: &str) {
+        sqlx::query(
+            "INSERT INTO admins (email, added_by) VALUES ($1, 'test') ON CONFLICT (email) DO NOTHING",
+        )
+        .bind(email)
+        .execute(&self.state.db)
+        .await
+        .expect("admin added");
+        let token = crate::security::generate_secret_code();
+        sqlx::query(
+            "INSERT INTO admin_sessions (admin_email, session_token, expires_at) \
+             VALUES ($1, $2, NOW() + INTERVAL '1 hour')",
+        )
+        .bind(email)
+        .bind(&token)
+        .execute(&self.state.db)
+        .await
+        .expect("session created");
+        self.cookie = Some(format!("{}={token}", crate::auth::SESSION_COOKIE));
+    }
+
+    /// Write the buffered tracking events, so tests can query them.
+    pub async fn flush_events(&mut self) {
+        if let Some(flusher) = self.flusher.take() {
+            flusher.shutdown().await;
+        }
+    }
+
+    /// Emails sent so far, as (to, subject, HTML).
+    pub fn sent_emails(&self) -> Vec<(String, String, String)> {
+        self.email.sent_emails.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_without_db() {
+        let app = TestStateBuilder::without_db().build();
+        let response = app.get("/health/live").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_api_requires_token() {
+        let app = TestStateBuilder::without_db()
+            .config(|c| c.api_tokens = vec!["t0ken".to_string()])
+            .build();
+        let response = app
+            .send(
+                Request::get("/api/v1/stats/overview")
+                    .header(header::AUTHORIZATION, "Bearer wrong")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[ignore = "needs DATABASE_URL"]
+    #[sqlx::test(migrations = false)]
+    async fn test_admin_session(db: PgPool) {
+        let mut app = TestApp::with_db(db).await;
+        assert_eq!(app.get("/admin").await.status, StatusCode::UNAUTHORIZED);
+        app.login_as("admin@coscup.org").await;
+        assert_eq!(app.get("/admin").await.status, StatusCode::OK);
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Consecutive successful sends before the delay is eased back down.
+const RAMP_UP_AFTER: u32 = 20;
+
+/// Smallest delay once the relay has pushed back, for a configured rate of 0.
+const MIN_BACKOFF_MS: u64 = 1000;
+
+/// Inter-send delay that backs off while the SMTP relay is rate limiting us
+/// and ramps back to the configured rate once sends go through again.
+#[derive(Debug, Clone)]
+pub struct AdaptiveThrottle {
+    base_ms: u64,
+    max_ms: u64,
+    delay_ms: u64,
+    successes: u32,
+}
+
+impl AdaptiveThrottle {
+    /// Start at `base_ms` (`SMTP_RATE_LIMIT_MS`), never backing off past `max_ms`.
+    pub fn new(base_ms: u64, max_ms: u64) -> Self {
+        Self {
+            base_ms,
+            max_ms: max_ms.max(base_ms),
+            delay_ms: base_ms,
+            successes: 0,
+        }
+    }
+
+    /// Delay to wait before the next send.
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+
+    /// The relay deferred a send for rate reasons: double the delay.
+    pub fn on_throttled(&mut self) {
+        self.successes = 0;
+        self.delay_ms = self
+            .delay_ms
+            .saturating_mul(2)
+            .max(self.base_ms.max(MIN_BACKOFF_MS))
+            .min(self.max_ms.max(MIN_BACKOFF_MS));
+    }
+
+    /// A send went through: after a run of them, halve the delay again.
+    pub fn on_success(&mut self) {
+        if self.delay_ms <= self.base_ms {
+            return;
+        }
+        self.successes += 1;
+        if self.successes >= RAMP_UP_AFTER {
+            self.successes = 0;
+            self.delay_ms = (self.delay_ms / 2).max(self.base_ms);
+        }
+    }
+}
+
+/// An `AdaptiveThrottle` shared by the concurrent deliveries of a send
+/// (`SMTP_CONCURRENCY`): sends start one delay apart across all of them, so
+/// the rate stays the same however many are in flight.
+#[derive(Debug)]
+pub struct SendPacer {
+    state: Mutex<PacerState>,
+}
+
+#[derive(Debug)]
+struct PacerState {
+    throttle: AdaptiveThrottle,
+    next_start: Instant,
+}
+
+impl SendPacer {
+    pub fn new(throttle: AdaptiveThrottle) -> Self {
+        Self {
+            state: Mutex::new(PacerState {
+                throttle,
+                next_start: Instant::now(),
+            }),
+        }
+    }
+
+    /// Reserve the next start time and wait for it.
+    pub async fn wait_turn(&self) {
+        let start = {
+            let mut state = self.state.lock().unwrap();
+            let start = state.next_start.max(Instant::now());
+            state.next_start = start + state.throttle.delay();
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// The relay deferred a send: back off, holding every delivery back for
+    /// the new delay. Returns the delay.
+    pub fn on_throttled(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        state.throttle.on_throttled();
+        let delay = state.throttle.delay();
+        state.next_start = state.next_start.max(Instant::now() + delay);
+        delay
+    }
+
+    pub fn on_success(&self) {
+        self.state.lock().unwrap().throttle.on_success();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(throttle: &AdaptiveThrottle) -> u128 {
+        throttle.delay().as_millis()
+    }
+
+    #[test]
+    fn test_backs_off_up_to_max() {
+        let mut throttle = AdaptiveThrottle::new(100, 5000);
+        assert_eq!(ms(&throttle), 100);
+        throttle.on_throttled();
+        assert_eq!(ms(&throttle), 1000);
+        throttle.on_throttled();
+        assert_eq!(ms(&throttle), 2000);
+        throttle.on_throttled();
+        throttle.on_throttled();
+        assert_eq!(ms(&throttle), 5000);
+    }
+
+    #[test]
+    fn test_ramps_back_to_base() {
+        let mut throttle = AdaptiveThrottle::new(100, 5000);
+        throttle.on_throttled();
+        throttle.on_throttled();
+        for _ in 0..RAMP_UP_AFTER - 1 {
+            throttle.on_success();
+        }
+        assert_eq!(ms(&throttle), 2000);
+        throttle.on_success();
+        assert_eq!(ms(&throttle), 1000);
+
+        // A deferral in between restarts the run
+        for _ in 0..RAMP_UP_AFTER - 1 {
+            throttle.on_success();
+        }
+        throttle.on_throttled();
+        throttle.on_success();
+        assert_eq!(ms(&throttle), 2000);
+
+        for _ in 0..RAMP_UP_AFTER * 10 {
+            throttle.on_success();
+        }
+        assert_eq!(ms(&throttle), 100);
+    }
+
+    #[test]
+    fn test_zero_base_rate() {
+        let mut throttle = AdaptiveThrottle::new(0, 0);
+        assert_eq!(ms(&throttle), 0);
+        throttle.on_throttled();
+        assert_eq!(ms(&throttle), MIN_BACKOFF_MS.into());
+        for _ in 0..RAMP_UP_AFTER * 10 {
+            throttle.on_success();
+        }
+        assert_eq!(ms(&throttle), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pacer_spaces_concurrent_starts() {
+        let pacer = SendPacer::new(AdaptiveThrottle::new(30, 5000));
+        let begin = Instant::now();
+        tokio::join!(pacer.wait_turn(), pacer.wait_turn(), pacer.wait_turn());
+        assert!(begin.elapsed() >= Duration::from_millis(60));
+
+        // A deferral holds every later start back by the new delay
+        assert_eq!(pacer.on_throttled(), Duration::from_secs(1));
+        let state = pacer.state.lock().unwrap();
+        assert!(state.next_start >= Instant::now() + Duration::from_millis(900));
+    }
+}
@@ -0,0 +1,155 @@
+//! Admin-facing timestamps. Each admin sees times in the timezone picked on
+//! `/admin/profile`; handlers pass RFC 3339 timestamps to templates, which
+//! format them with the `local_time` filter.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+
+/// Timezone for admins who have not picked one, and for organization-wide
+/// output such as backups and cached stats.
+pub const DEFAULT: Tz = chrono_tz::Asia::Taipei;
+
+/// Format used when a template does not pass one.
+pub const MINUTES: &str = "%Y-%m-%d %H:%M";
+pub const SECONDS: &str = "%Y-%m-%d %H:%M:%S";
+
+/// IANA timezone by name, `None` when unknown.
+pub fn parse(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// The timezone `email` picked on their profile, or [`DEFAULT`].
+pub async fn for_admin(db: &PgPool, email: &str) -> Tz {
+    let name = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT timezone FROM admin_profiles WHERE email = $1",
+    )
+    .bind(email)
+    .fetch_optional(db)
+    .await;
+    match name {
+        Ok(name) => name.flatten().as_deref().and_then(parse).unwrap_or(DEFAULT),
+        Err(e) => {
+            tracing::warn!("Failed to load timezone of {email}: {e}");
+            DEFAULT
+        }
+    }
+}
+
+/// Short label for page hints, e.g. `Asia/Taipei, UTC+08:00`.
+pub fn label(tz: Tz) -> String {
+    format!(
+        "{}, UTC{}",
+        tz.name(),
+        Utc::now().with_timezone(&tz).format("%:z")
+    )
+}
+
+pub fn format(t: DateTime<Utc>, tz: Tz, fmt: &str) -> String {
+    t.with_timezone(&tz).format(fmt).to_string()
+}
+
+/// Today in [`DEFAULT`], for organization-wide dates such as sponsor periods.
+pub fn today() -> NaiveDate {
+    Utc::now().with_timezone(&DEFAULT).date_naive()
+}
+
+/// A wall-clock time an admin entered, in their timezone. Times skipped by a
+/// DST change are rejected; repeated ones resolve to the earlier instant.
+pub fn from_local(naive: NaiveDateTime, tz: Tz) -> Option<DateTime<Utc>> {
+    naive
+        .and_local_timezone(tz)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// `{{ created_at | local_time(tz=admin_tz, format="%Y-%m-%d") }}`. Takes an
+/// RFC 3339 timestamp; `tz` defaults to [`DEFAULT`] and `format` to
+/// [`MINUTES`]. Null renders as an empty string.
+pub fn local_time_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let Some(raw) = value.as_str() else {
+        if value.is_null() {
+            return Ok(tera::Value::String(String::new()));
+        }
+        return Err(tera::Error::msg("local_time expects an RFC 3339 timestamp"));
+    };
+    let t = DateTime::parse_from_rfc3339(raw)
+        .map_err(|e| tera::Error::msg(format!("local_time: invalid timestamp {raw:?}: {e}")))?
+        .with_timezone(&Utc);
+    let tz = args
+        .get("tz")
+        .and_then(tera::Value::as_str)
+        .and_then(parse)
+        .unwrap_or(DEFAULT);
+    let fmt = args
+        .get("format")
+        .and_then(tera::Value::as_str)
+        .unwrap_or(MINUTES);
+    Ok(tera::Value::String(format(t, tz, fmt)))
+}
+
+pub fn register(tera: &mut tera::Tera) {
+    tera.register_filter("local_time", local_time_filter);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, ctx: &tera::Context) -> String {
+        let mut tera = tera::Tera::default();
+        register(&mut tera);
+        tera.add_raw_template("t", template).unwrap();
+        tera.render("t", ctx).unwrap()
+    }
+
+    #[test]
+    fn parses_iana_names_only() {
+        assert_eq!(parse("Europe/Berlin"), Some(chrono_tz::Europe::Berlin));
+        assert_eq!(parse(" Asia/Tokyo "), Some(chrono_tz::Asia::Tokyo));
+        assert_eq!(parse("Mars/Olympus"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn filter_formats_in_the_given_timezone() {
+        let mut ctx = tera::Context::new();
+        ctx.insert("t", "2025-08-09T01:30:00Z");
+        assert_eq!(render("{{ t | local_time }}", &ctx), "2025-08-09 09:30");
+        assert_eq!(
+            render("{{ t | local_time(tz=\"America/New_York\") }}", &ctx),
+            "2025-08-08 21:30"
+        );
+        assert_eq!(
+            render(
+                "{{ t | local_time(tz=\"Nowhere\", format=\"%H:%M:%S\") }}",
+                &ctx
+            ),
+            "09:30:00"
+        );
+    }
+
+    #[test]
+    fn filter_renders_null_as_empty() {
+        let mut ctx = tera::Context::new();
+        ctx.insert("t", &Option::<String>::None);
+        assert_eq!(render("[{{ t | local_time }}]", &ctx), "[]");
+    }
+
+    #[test]
+    fn local_input_converts_to_utc() {
+        let naive = NaiveDateTime::parse_from_str("2025-03-09T12:00", "%Y-%m-%dT%H:%M").unwrap();
+        assert_eq!(
+            from_local(naive, DEFAULT).unwrap().to_rfc3339(),
+            "2025-03-09T04:00:00+00:00"
+        );
+        // Clocks in New York jump from 02:00 to 03:00 that night
+        let skipped = NaiveDateTime::parse_from_str("2025-03-09T02:30", "%Y-%m-%dT%H:%M").unwrap();
+        assert_eq!(from_local(skipped, chrono_tz::America::New_York), None);
+    }
+}
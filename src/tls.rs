@@ -0,0 +1,149 @@
+use std::net::SocketAddr;
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, Request, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+
+/// `Strict-Transport-Security` value, `None` when `max_age_secs` is 0.
+pub fn hsts_header(max_age_secs: u64) -> Option<HeaderValue> {
+    (max_age_secs > 0)
+        .then(|| HeaderValue::try_from(format!("max-age={max_age_secs}; includeSubDomains")).ok())
+        .flatten()
+}
+
+/// Add HSTS to every response served over HTTPS.
+pub async fn hsts_middleware(
+    State(hsts): State<HeaderValue>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    response
+        .headers_mut()
+        .insert(header::STRICT_TRANSPORT_SECURITY, hsts);
+    response
+}
+
+/// HTTPS URL for a plain HTTP request to `host`, on `https_port`.
+fn https_url(host: &str, uri: &Uri, https_port: u16) -> Option<String> {
+    // Drop the port, keeping IPv6 literals like `[::1]` intact
+    let host = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    };
+    if host.is_empty() || !host.bytes().all(|b| b.is_ascii_graphic()) {
+        return None;
+    }
+    let port = if https_port == 443 {
+        String::new()
+    } else {
+        format!(":{https_port}")
+    };
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    Some(format!("https://{host}{port}{path}"))
+}
+
+/// Plain HTTP listener that permanently redirects everything to HTTPS.
+pub async fn serve_redirect(
+    listener: tokio::net::TcpListener,
+    https_port: u16,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) {
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        match https_url(host, &uri, https_port) {
+            Some(url) => Redirect::permanent(&url).into_response(),
+            None => axum::http::StatusCode::BAD_REQUEST.into_response(),
+        }
+    });
+    if let Err(e) = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        tracing::error!("HTTP redirect server error: {e}");
+    }
+}
+
+/// Load the certificate chain and key, reloading them on SIGHUP so renewed
+/// certificates (certbot, lego, ...) are picked up without a restart.
+pub async fn load(cert_path: &str, key_path: &str) -> std::io::Result<RustlsConfig> {
+    let config = RustlsConfig::from_pem_file(cert_path, key_path).await?;
+
+    let (reloaded, cert_path, key_path) =
+        (config.clone(), cert_path.to_string(), key_path.to_string());
+    tokio::spawn(async move {
+        let Ok(mut hangup) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        else {
+            return;
+        };
+        while hangup.recv().await.is_some() {
+            match reloaded.reload_from_pem_file(&cert_path, &key_path).await {
+                Ok(()) => tracing::info!("TLS certificate reloaded from {cert_path}"),
+                Err(e) => tracing::error!("Failed to reload TLS certificate: {e}"),
+            }
+        }
+    });
+
+    Ok(config)
+}
+
+/// Serve `app` over HTTPS until `shutdown` resolves, then drain connections.
+pub async fn serve(
+    listener: std::net::TcpListener,
+    config: RustlsConfig,
+    app: Router,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+    axum_server::from_tcp_rustls(listener, config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_https_url() {
+        let uri: Uri = "/newsletters/2025-08?from=x".parse().unwrap();
+        assert_eq!(
+            https_url("newsletter.coscup.org", &uri, 443).as_deref(),
+            Some("https://newsletter.coscup.org/newsletters/2025-08?from=x")
+        );
+        assert_eq!(
+            https_url("newsletter.coscup.org:80", &uri, 8443).as_deref(),
+            Some("https://newsletter.coscup.org:8443/newsletters/2025-08?from=x")
+        );
+        assert_eq!(
+            https_url("[::1]:80", &"/".parse().unwrap(), 443).as_deref(),
+            Some("https://[::1]/")
+        );
+        assert_eq!(
+            https_url("[::1]", &"/".parse().unwrap(), 443).as_deref(),
+            Some("https://[::1]/")
+        );
+        assert_eq!(https_url("", &uri, 443), None);
+    }
+
+    #[test]
+    fn test_hsts_header() {
+        assert_eq!(
+            hsts_header(31_536_000).unwrap(),
+            "max-age=31536000; includeSubDomains"
+        );
+        assert_eq!(hsts_header(0), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use sqlx::PgPool;
+
+/// Resolves the `topic` in tracking links (the newsletter slug) to the
+/// newsletter id stored on `email_events`. Slugs can't change once a
+/// newsletter has been sent, so hits are cached for the life of the process.
+#[derive(Clone, Default)]
+pub struct TopicIds {
+    cache: Arc<Mutex<HashMap<String, uuid::Uuid>>>,
+}
+
+impl TopicIds {
+    fn cached(&self, topic: &str) -> Option<uuid::Uuid> {
+        self.cache.lock().ok()?.get(topic).copied()
+    }
+
+    /// Newsletter id for `topic`, or `None` for topics that don't match a
+    /// newsletter (test sends of since-renamed drafts, very old links).
+    /// Lookup errors are logged; the event is still worth keeping without it.
+    pub async fn resolve(&self, db: &PgPool, topic: &str) -> Option<uuid::Uuid> {
+        if let Some(id) = self.cached(topic) {
+            return Some(id);
+        }
+        let id = sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM newsletters WHERE slug = $1")
+            .bind(topic)
+            .fetch_optional(db)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to look up newsletter for topic {topic}: {e}");
+                None
+            })?;
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(topic.to_string(), id);
+        }
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_uses_cache() {
+        // Never connects: a cached topic must not hit the database
+        let db = PgPool::connect_lazy("postgres://localhost:1/none").unwrap();
+        let topics = TopicIds::default();
+        let id = uuid::Uuid::new_v4();
+        topics
+            .cache
+            .lock()
+            .unwrap()
+            .insert("2025-08".to_string(), id);
+
+        assert_eq!(topics.resolve(&db, "2025-08").await, Some(id));
+        assert_eq!(topics.clone().cached("2025-08"), Some(id));
+        assert_eq!(topics.cached("2025-09"), None);
+    }
+}
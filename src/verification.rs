@@ -0,0 +1,154 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+use crate::jobs::{self, JobKind};
+use crate::security;
+use crate::AppState;
+
+/// Sign-ups still unverified this long after subscribing get one reminder.
+const REMINDER_AFTER_DAYS: i64 = 3;
+
+/// Reminders sent per run; the rest go out on the next one.
+const REMINDER_BATCH: i64 = 200;
+
+/// Hours a reminder link stays valid.
+const REMINDER_TOKEN_HOURS: i64 = 24;
+
+/// Only sign-ups from the subscribe form are reminded or pruned; imported
+/// and synced subscribers are trusted as given.
+const SOURCE: &str = "web";
+
+/// Whether unverified sign-ups of this age are old enough for the reminder
+/// and still young enough not to be pruned (`prune_days = 0` never prunes).
+fn reminder_due(age_days: i64, prune_days: i64) -> bool {
+    age_days >= REMINDER_AFTER_DAYS && (prune_days <= 0 || age_days < prune_days)
+}
+
+/// Send the one verification reminder to sign-ups unverified for
+/// `REMINDER_AFTER_DAYS`. Returns the number of reminders sent.
+pub async fn send_reminders(state: &AppState, prune_days: i64) -> Result<usize, AppError> {
+    let rows = sqlx::query_as::<_, (uuid::Uuid, String, String, i64)>(
+        "SELECT id, email, name, EXTRACT(DAY FROM NOW() - created_at)::BIGINT FROM subscribers \
+         WHERE verified_email = false AND subscription_source = $1 \
+         AND verification_reminder_sent_at IS NULL \
+         AND created_at < NOW() - ($2::BIGINT * INTERVAL '1 day') \
+         ORDER BY created_at LIMIT $3",
+    )
+    .bind(SOURCE)
+    .bind(REMINDER_AFTER_DAYS)
+    .bind(REMINDER_BATCH)
+    .fetch_all(&state.db)
+    .await?;
+
+    let delay = std::time::Duration::from_millis(state.live.get().smtp_rate_limit_ms);
+    let mut sent = 0;
+    for (id, email, name, age_days) in rows {
+        if !reminder_due(age_days, prune_days) {
+            continue;
+        }
+        // Claim the reminder first so an overlapping run can't send it twice
+        let claimed = sqlx::query(
+            "UPDATE subscribers SET verification_reminder_sent_at = NOW() \
+             WHERE id = $1 AND verification_reminder_sent_at IS NULL",
+        )
+        .bind(id)
+        .execute(&state.db)
+        .await?
+        .rows_affected()
+            > 0;
+        if !claimed {
+            continue;
+        }
+
+        let token = security::generate_token();
+        sqlx::query(
+            "INSERT INTO verification_tokens (subscriber_id, token, token_type, expires_at) VALUES ($1, $2, 'email_verify', $3)",
+        )
+        .bind(id)
+        .bind(&token)
+        .bind(Utc::now() + chrono::Duration::hours(REMINDER_TOKEN_HOURS))
+        .execute(&state.db)
+        .await?;
+
+        let mut ctx = tera::Context::new();
+        ctx.insert(
+            "verify_url",
+            &format!("{}/verify/{}", state.config.base_url, token),
+        );
+        ctx.insert("name", &name);
+        ctx.insert(
+            "logo_url",
+            &format!("{}/static/coscup-logo.png", state.config.base_url),
+        );
+        ctx.insert("prune_days", &prune_days);
+        let html = state
+            .tera
+            .render("emails/verification_reminder.html", &ctx)?;
+
+        match state
+            .email
+            .send_email(&email, "COSCUP Newsletter - 提醒您完成 Email 驗證", &html)
+            .await
+        {
+            Ok(()) => sent += 1,
+            Err(e) => tracing::error!("Failed to send verification reminder: {e}"),
+        }
+        tokio::time::sleep(delay).await;
+    }
+    Ok(sent)
+}
+
+/// Delete sign-ups still unverified after `prune_days` (0 disables).
+/// Addresses that unsubscribed are kept for the unsubscribe record.
+pub async fn prune(db: &PgPool, prune_days: i64) -> Result<u64, sqlx::Error> {
+    if prune_days <= 0 {
+        return Ok(0);
+    }
+    let result = sqlx::query(
+        "DELETE FROM subscribers s \
+         WHERE s.verified_email = false AND s.subscription_source = $1 \
+         AND s.created_at < NOW() - ($2::BIGINT * INTERVAL '1 day') \
+         AND NOT EXISTS (SELECT 1 FROM unsubscribe_events u WHERE u.subscriber_id = s.id)",
+    )
+    .bind(SOURCE)
+    .bind(prune_days)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Background loop sending reminders and pruning every `interval_secs`.
+pub async fn verification_loop(state: AppState, prune_days: i64, interval_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+    loop {
+        interval.tick().await;
+        let reminders = send_reminders(&state, prune_days);
+        match jobs::track(&state.db, JobKind::VerificationReminders, None, reminders).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Sent {n} verification reminders"),
+            Err(e) => tracing::error!("Verification reminders failed: {e}"),
+        }
+        let pruned = prune(&state.db, prune_days);
+        match jobs::track(&state.db, JobKind::UnverifiedPrune, None, pruned).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Pruned {n} unverified subscribers"),
+            Err(e) => tracing::error!("Pruning unverified subscribers failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminder_due() {
+        assert!(!reminder_due(2, 30));
+        assert!(reminder_due(3, 30));
+        assert!(reminder_due(29, 30));
+        assert!(!reminder_due(30, 30));
+        // Pruning disabled: remind whenever old enough
+        assert!(reminder_due(400, 0));
+    }
+}
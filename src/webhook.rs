@@ -0,0 +1,271 @@
+//! Signed inbound webhooks (mail provider events, integrations).
+//!
+//! The sender signs `{timestamp}.{body}` with HMAC-SHA256 and sends:
+//!
+//! - `X-Webhook-Timestamp`: Unix seconds when it was sent
+//! - `X-Webhook-Signature`: `sha256=<hex>`
+//! - `X-Webhook-Id` (optional): unique per delivery, kept for replay checks;
+//!   the signature is used when it is missing
+//!
+//! Requests older or newer than the tolerance are rejected, and a delivery
+//! seen before within the tolerance is rejected as a replay.
+//!
+//! Mail provider bounce callbacks, which come signed the provider's own way,
+//! are checked in `bounce_webhook` but share the replay store (`record_nonce`).
+
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const ID_HEADER: &str = "x-webhook-id";
+
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+/// Longest delivery id stored for replay checks.
+const MAX_ID_LEN: usize = 200;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Missing {0} header")]
+    MissingHeader(&'static str),
+    #[error("Invalid webhook timestamp")]
+    BadTimestamp,
+    #[error("Webhook timestamp outside the allowed window")]
+    Expired,
+    #[error("Invalid webhook signature")]
+    BadSignature,
+    #[error("Webhook delivery already received")]
+    Replayed,
+    #[error("{0} webhooks are not configured")]
+    NotConfigured(&'static str),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<WebhookError> for AppError {
+    fn from(e: WebhookError) -> Self {
+        match e {
+            WebhookError::Database(e) => Self::Database(e),
+            WebhookError::MissingHeader(_) | WebhookError::BadTimestamp => {
+                Self::BadRequest(e.to_string())
+            }
+            WebhookError::Expired
+            | WebhookError::BadSignature
+            | WebhookError::Replayed
+            | WebhookError::NotConfigured(_) => {
+                tracing::warn!("Rejected webhook: {e}");
+                Self::Unauthorized
+            }
+        }
+    }
+}
+
+/// Checks webhooks from one source against its secrets. More than one
+/// secret is accepted so a secret can be rotated without downtime.
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    /// Name the replay nonces are stored under, e.g. `inbound`
+    pub source: &'static str,
+    pub secrets: Vec<String>,
+    pub tolerance_secs: i64,
+}
+
+/// `sha256=<hex>` signature of a payload sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!(
+        "{SIGNATURE_PREFIX}{}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &'static str) -> Result<&'a str, WebhookError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .ok_or(WebhookError::MissingHeader(name))
+}
+
+impl Verifier {
+    /// Whether any secrets are configured; without them the source is not
+    /// signed and only the API token applies.
+    pub fn is_enabled(&self) -> bool {
+        !self.secrets.is_empty()
+    }
+
+    /// Check timestamp and signature, returning the delivery id to record
+    /// for replay protection.
+    pub fn check_signature(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: i64,
+    ) -> Result<String, WebhookError> {
+        let timestamp: i64 = header(headers, TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| WebhookError::BadTimestamp)?;
+        if (now - timestamp).abs() > self.tolerance_secs {
+            return Err(WebhookError::Expired);
+        }
+
+        let provided = header(headers, SIGNATURE_HEADER)?;
+        let valid = self.secrets.iter().any(|secret| {
+            crate::security::constant_time_eq(provided, &sign(secret, timestamp, body))
+        });
+        if !valid {
+            return Err(WebhookError::BadSignature);
+        }
+
+        let id = headers
+            .get(ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .unwrap_or(provided);
+        Ok(id.chars().take(MAX_ID_LEN).collect())
+    }
+
+    /// Verify a request and record its delivery id; a delivery id seen
+    /// before is rejected. Does nothing when no secrets are configured.
+    pub async fn verify(
+        &self,
+        db: &PgPool,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<(), WebhookError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let id = self.check_signature(headers, body, chrono::Utc::now().timestamp())?;
+
+        // Kept a little past the tolerance, so a delivery can't be replayed
+        // while its timestamp is still accepted
+        record_nonce(db, self.source, &id, self.tolerance_secs.saturating_mul(2)).await
+    }
+}
+
+/// Remember a delivery id of `source` for `keep_secs`; one seen before is
+/// rejected as a replay.
+pub async fn record_nonce(
+    db: &PgPool,
+    source: &str,
+    nonce: &str,
+    keep_secs: i64,
+) -> Result<(), WebhookError> {
+    let nonce: String = nonce.chars().take(MAX_ID_LEN).collect();
+    let inserted = sqlx::query(
+        "INSERT INTO webhook_nonces (source, nonce, expires_at) \
+         VALUES ($1, $2, NOW() + ($3::BIGINT * INTERVAL '1 second')) \
+         ON CONFLICT (source, nonce) DO NOTHING",
+    )
+    .bind(source)
+    .bind(&nonce)
+    .bind(keep_secs)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Err(WebhookError::Replayed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verifier() -> Verifier {
+        Verifier {
+            source: "test",
+            secrets: vec!["old-secret".to_string(), "new-secret".to_string()],
+            tolerance_secs: 300,
+        }
+    }
+
+    fn signed_headers(secret: &str, timestamp: i64, body: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMESTAMP_HEADER, timestamp.to_string().parse().unwrap());
+        headers.insert(
+            SIGNATURE_HEADER,
+            sign(secret, timestamp, body).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_sign_is_stable() {
+        let sig = sign("secret", 1_754_700_000, b"{}");
+        assert!(sig.starts_with("sha256="));
+        assert_eq!(sig.len(), "sha256=".len() + 64);
+        assert_eq!(sig, sign("secret", 1_754_700_000, b"{}"));
+        assert_ne!(sig, sign("secret", 1_754_700_001, b"{}"));
+    }
+
+    #[test]
+    fn test_accepts_any_configured_secret() {
+        let now = 1_754_700_000;
+        let body = br#"{"from":"a@example.com"}"#;
+        for secret in ["old-secret", "new-secret"] {
+            let headers = signed_headers(secret, now, body);
+            assert_eq!(
+                verifier().check_signature(&headers, body, now).unwrap(),
+                sign(secret, now, body)
+            );
+        }
+        let headers = signed_headers("other", now, body);
+        assert!(matches!(
+            verifier().check_signature(&headers, body, now),
+            Err(WebhookError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_tampered_body_and_stale_timestamp() {
+        let now = 1_754_700_000;
+        let headers = signed_headers("new-secret", now, b"original");
+        assert!(matches!(
+            verifier().check_signature(&headers, b"tampered", now),
+            Err(WebhookError::BadSignature)
+        ));
+        assert!(matches!(
+            verifier().check_signature(&headers, b"original", now + 301),
+            Err(WebhookError::Expired)
+        ));
+        assert!(verifier()
+            .check_signature(&headers, b"original", now - 300)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_delivery_id_and_missing_headers() {
+        let now = 1_754_700_000;
+        let mut headers = signed_headers("new-secret", now, b"x");
+        headers.insert(ID_HEADER, "evt_123".parse().unwrap());
+        assert_eq!(
+            verifier().check_signature(&headers, b"x", now).unwrap(),
+            "evt_123"
+        );
+
+        headers.remove(SIGNATURE_HEADER);
+        assert!(matches!(
+            verifier().check_signature(&headers, b"x", now),
+            Err(WebhookError::MissingHeader(SIGNATURE_HEADER))
+        ));
+        headers.insert(TIMESTAMP_HEADER, "yesterday".parse().unwrap());
+        assert!(matches!(
+            verifier().check_signature(&headers, b"x", now),
+            Err(WebhookError::BadTimestamp)
+        ));
+    }
+}